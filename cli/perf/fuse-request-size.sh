@@ -0,0 +1,72 @@
+#!/bin/bash
+#
+# Measures sequential write and read throughput of a FUSE mount for
+# different --max-write / --max-read request sizes.
+#
+# Each size gets a fresh database and mount. A file is written through the
+# mount with large blocks and flushed, then read back with O_DIRECT so the
+# page cache does not serve it. Every byte crosses the FUSE boundary in
+# requests of at most the configured size, so larger requests mean fewer
+# round-trips per megabyte.
+#
+# Usage: ./fuse-request-size.sh [size_mb] [request_sizes...]
+#   size_mb:       Size of the file written and read (default: 256)
+#   request_sizes: Request sizes in bytes (default: 4096 16384 65536 131072)
+
+set -e
+
+SIZE_MB="${1:-256}"
+shift || true
+if [ $# -gt 0 ]; then
+    REQUEST_SIZES=("$@")
+else
+    REQUEST_SIZES=(4096 16384 65536 131072)
+fi
+
+WORK_DIR=$(mktemp -d /tmp/agentfs-perf-XXXXXX)
+MOUNTPOINT="$WORK_DIR/mnt"
+MOUNT_PID=""
+
+cleanup() {
+    if [ -n "$MOUNT_PID" ]; then
+        fusermount -u "$MOUNTPOINT" 2>/dev/null || true
+        wait "$MOUNT_PID" 2>/dev/null || true
+    fi
+    rm -rf "$WORK_DIR"
+}
+trap cleanup EXIT
+
+cargo build --release --quiet
+AGENTFS="$(cargo metadata --format-version 1 --no-deps | jq -r .target_directory)/release/agentfs"
+
+# Print the throughput dd reports on its last line, e.g. "1.2 GB/s"
+throughput() {
+    tail -n 1 | awk -F', ' '{ print $NF }'
+}
+
+printf "%-12s %-14s %-14s\n" "REQUEST" "WRITE" "READ"
+for size in "${REQUEST_SIZES[@]}"; do
+    db="$WORK_DIR/bench-$size.db"
+    mkdir -p "$MOUNTPOINT"
+    "$AGENTFS" mount "$db" "$MOUNTPOINT" --create --backend fuse --foreground \
+        --max-read "$size" --max-write "$size" > /dev/null 2>&1 &
+    MOUNT_PID=$!
+
+    for _ in $(seq 1 20); do
+        mountpoint -q "$MOUNTPOINT" && break
+        sleep 0.5
+    done
+    if ! mountpoint -q "$MOUNTPOINT"; then
+        echo "Mount with request size $size did not become ready" >&2
+        exit 1
+    fi
+
+    write=$(dd if=/dev/zero of="$MOUNTPOINT/data" bs=1M count="$SIZE_MB" conv=fsync 2>&1 | throughput)
+    read=$(dd if="$MOUNTPOINT/data" of=/dev/null bs=1M iflag=direct 2>&1 | throughput)
+
+    printf "%-12s %-14s %-14s\n" "$size" "$write" "$read"
+
+    fusermount -u "$MOUNTPOINT"
+    wait "$MOUNT_PID" 2>/dev/null || true
+    MOUNT_PID=""
+done
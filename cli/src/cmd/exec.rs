@@ -10,10 +10,11 @@ use std::path::PathBuf;
 use std::process::Command;
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
 use turso::value::Value;
 
 use crate::cmd::init::open_agentfs;
-use crate::mount::{mount_fs, MountBackend, MountOpts};
+use crate::mount::{default_fsname, mount_fs, MountBackend, MountOpts, DEFAULT_SUBTYPE};
 
 /// Handle the exec command.
 ///
@@ -34,6 +35,8 @@ pub async fn handle_exec_command(
         });
     }
 
+    let db_path = PathBuf::from(opts.db_path()?);
+
     // Open AgentFS
     let agentfs = open_agentfs(opts).await?;
 
@@ -76,24 +79,33 @@ pub async fn handle_exec_command(
     let mountpoint = std::env::temp_dir().join(format!("agentfs-exec-{}", exec_id));
     std::fs::create_dir_all(&mountpoint).context("Failed to create mount directory")?;
 
-    let fsname = format!(
-        "agentfs:{}",
-        std::fs::canonicalize(&id_or_path)
-            .map(|p| p.to_string_lossy().to_string())
-            .unwrap_or_else(|_| id_or_path.clone())
-    );
+    let fsname = default_fsname(&db_path);
 
     let mount_opts = MountOpts {
         mountpoint: mountpoint.clone(),
         backend,
         fsname,
+        subtype: Some(DEFAULT_SUBTYPE.to_string()),
         uid: None,
         gid: None,
         allow_other: false,
         allow_root: false,
         auto_unmount: false,
         lazy_unmount: true,
+        extra_options: Vec::new(),
+        op_timeout: None,
+        max_read: None,
+        max_write: None,
+        attr_timeout: None,
+        entry_timeout: None,
+        allowed_clients: None,
+        checkpoint_interval: None,
+        database: Some(db_path),
+        read_only: false,
+        root_subdir: None,
+        runtime: None,
         timeout: std::time::Duration::from_secs(10),
+        cancel: CancellationToken::new(),
     };
 
     // Mount the filesystem
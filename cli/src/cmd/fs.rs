@@ -1,4 +1,5 @@
 use std::collections::VecDeque;
+use std::path::Path;
 
 use agentfs_sdk::{AgentFSOptions, EncryptionConfig};
 use anyhow::{Context, Result as AnyhowResult};
@@ -162,6 +163,40 @@ pub async fn write_filesystem(
     Ok(())
 }
 
+pub async fn optimize_filesystem(
+    id_or_path: String,
+    output: &Path,
+    checkpoint: bool,
+    encryption: Option<&(String, String)>,
+) -> AnyhowResult<()> {
+    let mut options = AgentFSOptions::resolve(&id_or_path)?;
+    if let Some((key, cipher)) = encryption {
+        options = options.with_encryption(EncryptionConfig {
+            hex_key: key.clone(),
+            cipher: cipher.clone(),
+        });
+    }
+    let agentfs = open_agentfs(options).await?;
+
+    if checkpoint {
+        agentfs
+            .fs
+            .checkpoint()
+            .await
+            .context("Failed to checkpoint the database")?;
+    }
+    let output_str = output
+        .to_str()
+        .with_context(|| format!("Output path is not valid UTF-8: {}", output.display()))?;
+    agentfs
+        .fs
+        .optimize(output_str)
+        .await
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+    eprintln!("Wrote optimized filesystem to {}", output.display());
+    Ok(())
+}
+
 /// Represents a change type in the overlay filesystem
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum ChangeType {
@@ -274,7 +309,7 @@ mod tests {
     use agentfs_sdk::{AgentFS, AgentFSOptions, EncryptionConfig};
     use tempfile::NamedTempFile;
 
-    use crate::cmd::fs::{cat_filesystem, ls_filesystem, write_filesystem};
+    use crate::cmd::fs::{cat_filesystem, ls_filesystem, optimize_filesystem, write_filesystem};
 
     const TEST_KEY: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
     const TEST_CIPHER: &str = "aes256gcm";
@@ -461,6 +496,26 @@ f d/e/3.md
         assert_eq!(buf, b"new content");
     }
 
+    #[tokio::test]
+    pub async fn optimize_writes_readable_copy() {
+        let (agentfs, path, _file) = agentfs().await;
+        write_file(&agentfs.fs, "a.txt", b"a", 0, 0).await.unwrap();
+        write_file(&agentfs.fs, "b.txt", b"b", 0, 0).await.unwrap();
+        drop(agentfs);
+
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("optimized.db");
+        optimize_filesystem(path, &output, true, None)
+            .await
+            .unwrap();
+
+        let mut buf = Vec::new();
+        ls_filesystem(&mut buf, output.to_str().unwrap().to_string(), "/", None)
+            .await
+            .unwrap();
+        assert_eq!(buf, b"f a.txt\nf b.txt\n");
+    }
+
     async fn write_file(
         fs: &agentfs_sdk::filesystem::AgentFS,
         path: &str,
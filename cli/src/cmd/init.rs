@@ -32,6 +32,7 @@ pub fn build_sync_options(sync_cmd_options: &SyncCommandOptions) -> SyncOptions
         remote_url: sync_cmd_options.sync_remote_url.clone(),
         auth_token: std::env::var("TURSO_DB_AUTH_TOKEN").ok(),
         partial_sync: None,
+        ..Default::default()
     };
 
     if sync_cmd_options.sync_remote_url.is_some() {
@@ -206,11 +207,12 @@ async fn run_init_cmd(
     base: Option<PathBuf>,
     agent: AgentFS,
 ) -> AnyhowResult<()> {
-    use crate::mount::{mount_fs, MountOpts};
+    use crate::mount::{mount_fs, MountOpts, DEFAULT_SUBTYPE};
     use agentfs_sdk::{FileSystem, HostFS};
     use std::process::Command;
     use std::sync::Arc;
     use tokio::sync::Mutex;
+    use tokio_util::sync::CancellationToken;
 
     let fs: Arc<Mutex<dyn FileSystem + Send>> = if let Some(ref base_path) = base {
         let canonical = base_path
@@ -231,13 +233,27 @@ async fn run_init_cmd(
         mountpoint: mountpoint.clone(),
         backend,
         fsname: format!("agentfs:{}", id),
+        subtype: Some(DEFAULT_SUBTYPE.to_string()),
         uid: None,
         gid: None,
         allow_other: false,
         allow_root: false,
         auto_unmount: false,
         lazy_unmount: true,
+        extra_options: Vec::new(),
+        op_timeout: None,
+        max_read: None,
+        max_write: None,
+        attr_timeout: None,
+        entry_timeout: None,
+        allowed_clients: None,
+        checkpoint_interval: None,
+        database: None,
+        read_only: false,
+        root_subdir: None,
+        runtime: None,
         timeout: std::time::Duration::from_secs(10),
+        cancel: CancellationToken::new(),
     };
 
     let mount_handle = mount_fs(fs, mount_opts).await?;
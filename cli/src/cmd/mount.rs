@@ -1,14 +1,21 @@
-use agentfs_sdk::{error::Error as SdkError, AgentFSOptions, FileSystem, HostFS, OverlayFS};
+use agentfs_sdk::{
+    error::Error as SdkError, AgentFS, AgentFSOptions, FileSystem, HostFS, OverlayFS,
+};
 use anyhow::{Context, Result};
+use serde::Serialize;
 use std::{
     path::{Path, PathBuf},
     process::Command,
     sync::Arc,
 };
 use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
 use turso::value::Value;
 
-use crate::mount::{mount_fs, MountOpts};
+use crate::cmd::init::open_agentfs;
+use crate::mount::{
+    default_fsname, mount_fs, with_root_subdir, MountLock, MountOpts, DEFAULT_SUBTYPE,
+};
 use crate::nfs::AgentNFS;
 use crate::nfsserve::tcp::NFSTcp;
 
@@ -20,16 +27,27 @@ use std::{
     os::unix::fs::MetadataExt,
 };
 
-#[cfg(target_os = "linux")]
-use crate::cmd::init::open_agentfs;
 #[cfg(target_os = "linux")]
 use crate::fuse::FuseMountOptions;
+#[cfg(target_os = "linux")]
+use crate::mount::SubdirFs;
 
 pub use crate::opts::MountBackend;
 
 /// Default NFS port to try (use a high port to avoid needing root)
 const DEFAULT_NFS_PORT: u32 = 11111;
 
+/// Connections to open before serving a mount
+const POOL_WARMUP_CONNECTIONS: usize = 4;
+
+/// How long a FUSE mount may take to appear after the session starts
+#[cfg(target_os = "linux")]
+const MOUNT_READY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// How long a mountpoint may take to answer `stat` before it is considered stale
+#[cfg(target_os = "linux")]
+const STALE_CHECK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
 /// Arguments for the mount command.
 #[derive(Debug, Clone)]
 pub struct MountArgs {
@@ -51,14 +69,144 @@ pub struct MountArgs {
     pub gid: Option<u32>,
     /// The mount backend to use (fuse or nfs).
     pub backend: MountBackend,
+    /// Open the database read-only and mount without write access.
+    pub read_only: bool,
+    /// Create a new, empty filesystem if the database does not exist.
+    pub create: bool,
+    /// Base database of an overlay mount, opened read-only. Writes go to the
+    /// `id_or_path` database, which holds the overlay's delta.
+    pub base: Option<String>,
+    /// Raw mount options appended to the backend's option string.
+    pub extra_options: Vec<String>,
+    /// Fail filesystem operations that take longer than this with EIO.
+    pub op_timeout: Option<std::time::Duration>,
+    /// Largest read the kernel sends in a single request (FUSE only).
+    pub max_read: Option<u32>,
+    /// Largest write the kernel sends in a single request (FUSE only).
+    pub max_write: Option<u32>,
+    /// How long NFS clients may cache file attributes (NFS only).
+    pub attr_timeout: Option<std::time::Duration>,
+    /// How long NFS clients may cache directory entries (NFS only).
+    pub entry_timeout: Option<std::time::Duration>,
+    /// Directory of the filesystem to mount as the mount root.
+    pub subdir: Option<PathBuf>,
+    /// Filesystem name shown by `mount` and `df`, instead of one derived
+    /// from the database path.
+    pub fsname: Option<String>,
+    /// Print the mount details as JSON on stdout once mounted.
+    pub json: bool,
+}
+
+/// Mount details printed on stdout by `mount --json`.
+#[derive(Debug, Serialize)]
+struct MountInfo {
+    mountpoint: PathBuf,
+    backend: String,
+    /// Process serving the mount when it runs in the background.
+    pid: Option<u32>,
+    /// Port of the NFS server backing the mount.
+    nfs_port: Option<u32>,
+    options: MountInfoOptions,
+}
+
+/// Options the mount was made with, after defaults were applied.
+#[derive(Debug, Serialize)]
+struct MountInfoOptions {
+    fsname: String,
+    subtype: Option<String>,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    allow_other: bool,
+    allow_root: bool,
+    auto_unmount: bool,
+    read_only: bool,
+    foreground: bool,
+    base: Option<String>,
+    extra_options: Vec<String>,
+    op_timeout_secs: Option<f64>,
+    max_read: Option<u32>,
+    max_write: Option<u32>,
+    attr_timeout_secs: Option<f64>,
+    entry_timeout_secs: Option<f64>,
+    subdir: Option<PathBuf>,
+}
+
+impl MountInfo {
+    fn new(args: &MountArgs, mountpoint: &Path, backend: MountBackend, fsname: &str) -> Self {
+        Self {
+            mountpoint: mountpoint.to_path_buf(),
+            backend: backend.to_string(),
+            pid: None,
+            nfs_port: None,
+            options: MountInfoOptions {
+                fsname: fsname.to_string(),
+                subtype: Some(DEFAULT_SUBTYPE.to_string()),
+                uid: args.uid,
+                gid: args.gid,
+                allow_other: args.allow_other,
+                allow_root: args.allow_root,
+                auto_unmount: args.auto_unmount,
+                read_only: args.read_only,
+                foreground: args.foreground,
+                base: args.base.clone(),
+                extra_options: args.extra_options.clone(),
+                op_timeout_secs: args.op_timeout.map(|timeout| timeout.as_secs_f64()),
+                max_read: args.max_read,
+                max_write: args.max_write,
+                attr_timeout_secs: args.attr_timeout.map(|timeout| timeout.as_secs_f64()),
+                entry_timeout_secs: args.entry_timeout.map(|timeout| timeout.as_secs_f64()),
+                subdir: args.subdir.clone(),
+            },
+        }
+    }
+
+    fn print(&self) -> Result<()> {
+        println!("{}", serde_json::to_string(self)?);
+        Ok(())
+    }
+}
+
+/// Check `--max-read` and `--max-write` against the kernel's limits.
+fn validate_request_sizes(args: &MountArgs) -> Result<()> {
+    if let Some(max_read) = args.max_read {
+        crate::mount::validate_fuse_io_size("max_read", max_read)?;
+        if args
+            .extra_options
+            .iter()
+            .any(|option| option.starts_with("max_read="))
+        {
+            anyhow::bail!("--max-read conflicts with the max_read mount option");
+        }
+    }
+    if let Some(max_write) = args.max_write {
+        crate::mount::validate_fuse_io_size("max_write", max_write)?;
+    }
+    Ok(())
+}
+
+/// Check that `--attr-timeout` and `--entry-timeout` don't conflict with
+/// NFS caching options passed with `-o`.
+fn validate_cache_timeouts(args: &MountArgs) -> Result<()> {
+    if args.attr_timeout.is_some() || args.entry_timeout.is_some() {
+        crate::mount::validate_nfs_cache_options(&args.extra_options)?;
+    }
+    Ok(())
 }
 
 /// Mount the agent filesystem (Linux).
 #[cfg(target_os = "linux")]
 pub fn mount(args: MountArgs) -> Result<()> {
-    match args.backend {
+    if let Some(fsname) = &args.fsname {
+        crate::mount::validate_fsname(fsname)?;
+    }
+    for option in &args.extra_options {
+        crate::mount::validate_extra_option(option)?;
+    }
+    validate_request_sizes(&args)?;
+    validate_cache_timeouts(&args)?;
+    match args.backend.resolve() {
         MountBackend::Fuse => mount_fuse(args),
-        MountBackend::Nfs => {
+        MountBackend::Nfs | MountBackend::Auto => {
             let rt = crate::get_runtime();
             rt.block_on(mount_nfs_backend(args))
         }
@@ -68,14 +216,22 @@ pub fn mount(args: MountArgs) -> Result<()> {
 /// Mount the agent filesystem (macOS).
 #[cfg(target_os = "macos")]
 pub fn mount(args: MountArgs) -> Result<()> {
-    match args.backend {
+    if let Some(fsname) = &args.fsname {
+        crate::mount::validate_fsname(fsname)?;
+    }
+    for option in &args.extra_options {
+        crate::mount::validate_extra_option(option)?;
+    }
+    validate_request_sizes(&args)?;
+    validate_cache_timeouts(&args)?;
+    match args.backend.resolve() {
         MountBackend::Fuse => {
             anyhow::bail!(
                 "FUSE mounting is not supported on macOS.\n\
                  Use --backend nfs (default) or `agentfs nfs` instead."
             );
         }
-        MountBackend::Nfs => {
+        MountBackend::Nfs | MountBackend::Auto => {
             let rt = crate::get_runtime();
             rt.block_on(mount_nfs_backend(args))
         }
@@ -85,13 +241,13 @@ pub fn mount(args: MountArgs) -> Result<()> {
 /// Mount the agent filesystem using FUSE (Linux only).
 #[cfg(target_os = "linux")]
 fn mount_fuse(args: MountArgs) -> Result<()> {
-    let opts = AgentFSOptions::resolve(&args.id_or_path)?;
+    let opts = resolve_mount_options(&args.id_or_path, args.create)?;
+    let db_path = opts.db_path()?;
 
     // Check schema version before daemonizing. This allows us to show the error
     // message to the user directly, rather than having it appear in daemon logs.
     {
         let rt = crate::get_runtime();
-        let db_path = opts.db_path()?;
         let result: Result<(), SdkError> = rt.block_on(async {
             let db = turso::Builder::new_local(&db_path).build().await?;
             let conn = db.connect()?;
@@ -103,12 +259,10 @@ fn mount_fuse(args: MountArgs) -> Result<()> {
         }
     }
 
-    let fsname = format!(
-        "agentfs:{}",
-        std::fs::canonicalize(&args.id_or_path)
-            .map(|p| p.to_string_lossy().to_string())
-            .unwrap_or_else(|_| args.id_or_path.clone())
-    );
+    let fsname = args
+        .fsname
+        .clone()
+        .unwrap_or_else(|| default_fsname(Path::new(&db_path)));
 
     if !args.mountpoint.exists() {
         anyhow::bail!("Mountpoint does not exist: {}", args.mountpoint.display());
@@ -128,23 +282,51 @@ fn mount_fuse(args: MountArgs) -> Result<()> {
         allow_root: args.allow_root,
         allow_other: args.allow_other,
         fsname,
+        subtype: Some(DEFAULT_SUBTYPE.to_string()),
         uid: args.uid,
         gid: args.gid,
+        read_only: args.read_only,
+        extra_options: args.extra_options.clone(),
+        op_timeout: args.op_timeout,
+        max_read: args.max_read,
+        max_write: args.max_write,
     };
 
+    let info = args
+        .json
+        .then(|| MountInfo::new(&args, &mountpoint, MountBackend::Fuse, &fuse_opts.fsname));
+
     let id_or_path = args.id_or_path.clone();
+    let read_only = args.read_only;
+    let base = args.base.clone();
+    let subdir = args.subdir.clone();
     let mount = move || {
+        // Held by the process serving the mount until it exits
+        let lock = if read_only {
+            None
+        } else {
+            Some(MountLock::acquire(Path::new(&db_path))?)
+        };
         let rt = crate::get_runtime();
-        let agentfs = match rt.block_on(open_agentfs(opts)) {
+        let agentfs = match rt.block_on(open_for_mount(opts, read_only)) {
             Ok(fs) => fs,
             Err(SdkError::SchemaVersionMismatch { found, expected }) => {
                 exit_schema_version_mismatch(&found, &expected, &id_or_path);
             }
             Err(e) => return Err(e.into()),
         };
+        if let Some(lock) = &lock {
+            rt.block_on(sweep_orphans(&agentfs, lock))?;
+        }
 
         // Check for overlay configuration
         let fs: Arc<dyn FileSystem> = rt.block_on(async {
+            if let Some(base) = &base {
+                eprintln!("Using overlay filesystem with base database: {}", base);
+                let overlay = open_overlay(base, agentfs).await?;
+                return Ok(Arc::new(overlay) as Arc<dyn FileSystem>);
+            }
+
             // Query base_path in a separate scope so connection is released
             let base_path: Option<String> = {
                 let conn = agentfs.get_connection().await?;
@@ -181,25 +363,128 @@ fn mount_fuse(args: MountArgs) -> Result<()> {
             }
         })?;
 
+        let fs = match &subdir {
+            Some(subdir) => {
+                let subdir_fs = rt.block_on(SubdirFs::new(fs, subdir))?;
+                Arc::new(subdir_fs) as Arc<dyn FileSystem>
+            }
+            None => fs,
+        };
+
+        // Fail before mounting if the database is unusable, so the daemon's
+        // readiness check only ever observes a mount backed by a healthy store.
+        {
+            use anyhow::Context as _;
+            rt.block_on(fs.health())
+                .context("Filesystem health check failed")?;
+        }
+
         crate::fuse::mount(fs, fuse_opts, rt)
     };
 
     if args.foreground {
+        // The session blocks until unmounted, so report once the mount appears
+        if let Some(info) = info {
+            let mountpoint = mountpoint.clone();
+            std::thread::spawn(move || {
+                let start = std::time::Instant::now();
+                while start.elapsed() < MOUNT_READY_TIMEOUT {
+                    if is_mounted(&mountpoint) {
+                        if let Err(e) = info.print() {
+                            eprintln!("Warning: Failed to print mount details: {}", e);
+                        }
+                        return;
+                    }
+                    std::thread::sleep(std::time::Duration::from_millis(50));
+                }
+            });
+        }
         mount()
     } else {
-        crate::daemon::daemonize(
+        let pid = crate::daemon::daemonize(
             mount,
             move || is_mounted(&mountpoint),
-            std::time::Duration::from_secs(10),
-        )
+            &crate::daemon::DaemonOpts::new(MOUNT_READY_TIMEOUT),
+        )?;
+        if let Some(mut info) = info {
+            info.pid = Some(pid);
+            info.print()?;
+        }
+        Ok(())
+    }
+}
+
+/// Resolve the database to mount.
+///
+/// A missing database is an error unless `create` is set, so a mistyped ID or
+/// path never silently mounts an empty filesystem. With `create`, an agent ID
+/// creates `.agentfs/<ID>.db` and anything else is taken as a file path; the
+/// schema is written when the database is first opened.
+fn resolve_mount_options(id_or_path: &str, create: bool) -> Result<AgentFSOptions> {
+    match AgentFSOptions::resolve(id_or_path) {
+        Ok(opts) => Ok(opts),
+        Err(SdkError::AgentNotFound { .. } | SdkError::InvalidAgentId(_)) if create => {
+            if AgentFSOptions::validate_agent_id(id_or_path) {
+                Ok(AgentFSOptions::with_id(id_or_path))
+            } else {
+                Ok(AgentFSOptions::with_path(id_or_path))
+            }
+        }
+        Err(SdkError::AgentNotFound { .. } | SdkError::InvalidAgentId(_)) => anyhow::bail!(
+            "Database `{}` does not exist. Pass --create to create a new, empty filesystem.",
+            id_or_path
+        ),
+        Err(e) => Err(e.into()),
     }
 }
 
+/// Open the database to mount.
+///
+/// Read-only mounts open it with `AgentFS::open_readonly`, so any number of
+/// them can share a database as long as no writer is active.
+async fn open_for_mount(opts: AgentFSOptions, read_only: bool) -> Result<AgentFS, SdkError> {
+    let agentfs = if read_only {
+        AgentFS::open_readonly(opts).await?
+    } else {
+        open_agentfs(opts).await?
+    };
+    // Open connections up front so the first requests from the kernel are fast
+    agentfs.get_pool().warmup(POOL_WARMUP_CONNECTIONS).await?;
+    Ok(agentfs)
+}
+
+/// Delete the inodes a crashed mount left unlinked but never released.
+///
+/// Only safe while holding the database's mount lock, as orphans still open
+/// through another read-write instance would be deleted too.
+async fn sweep_orphans(agentfs: &AgentFS, _lock: &MountLock) -> Result<()> {
+    agentfs
+        .fs
+        .sweep_orphans()
+        .await
+        .context("Failed to delete orphaned inodes")?;
+    Ok(())
+}
+
+/// Layer the opened `delta` over the `base` database of a `--base` mount.
+///
+/// The base is opened read-only, so the mount never changes it. Unlike
+/// `agentfs init --base`, no base path is recorded in the delta, as it would
+/// be taken for a host directory; the base is given again on every mount.
+async fn open_overlay(base: &str, delta: AgentFS) -> Result<OverlayFS> {
+    let opts = resolve_mount_options(base, false)?;
+    let base = AgentFS::open_readonly(opts)
+        .await
+        .with_context(|| format!("Failed to open base database {}", base))?;
+    let overlay = OverlayFS::new(Arc::new(base.fs), delta.fs);
+    overlay.init_tables().await?;
+    Ok(overlay)
+}
+
 /// Mount the agent filesystem using NFS over localhost.
 async fn mount_nfs_backend(args: MountArgs) -> Result<()> {
-    use crate::cmd::init::open_agentfs;
-
-    let opts = AgentFSOptions::resolve(&args.id_or_path)?;
+    let opts = resolve_mount_options(&args.id_or_path, args.create)?;
+    let db_path = PathBuf::from(opts.db_path()?);
 
     if !args.mountpoint.exists() {
         anyhow::bail!("Mountpoint does not exist: {}", args.mountpoint.display());
@@ -207,21 +492,30 @@ async fn mount_nfs_backend(args: MountArgs) -> Result<()> {
 
     let mountpoint = std::fs::canonicalize(args.mountpoint.clone())?;
 
-    let fsname = format!(
-        "agentfs:{}",
-        std::fs::canonicalize(&args.id_or_path)
-            .map(|p| p.to_string_lossy().to_string())
-            .unwrap_or_else(|_| args.id_or_path.clone())
-    );
+    let fsname = args
+        .fsname
+        .clone()
+        .unwrap_or_else(|| default_fsname(&db_path));
+
+    // Taken before opening so that orphans can be swept, and held until
+    // this function returns, which ends the mount
+    let lock = if args.read_only {
+        None
+    } else {
+        Some(MountLock::acquire(&db_path)?)
+    };
 
     // Open AgentFS
-    let agentfs = match open_agentfs(opts).await {
+    let agentfs = match open_for_mount(opts, args.read_only).await {
         Ok(fs) => fs,
         Err(SdkError::SchemaVersionMismatch { found, expected }) => {
             exit_schema_version_mismatch(&found, &expected, &args.id_or_path);
         }
         Err(e) => return Err(e.into()),
     };
+    if let Some(lock) = &lock {
+        sweep_orphans(&agentfs, lock).await?;
+    }
 
     // Check for overlay configuration
     // Query base_path in a separate scope so connection is released before load_whiteouts
@@ -246,7 +540,11 @@ async fn mount_nfs_backend(args: MountArgs) -> Result<()> {
         }
     }; // conn is dropped here
 
-    let fs: Arc<Mutex<dyn FileSystem + Send>> = if let Some(base_path) = base_path {
+    let fs: Arc<Mutex<dyn FileSystem + Send>> = if let Some(base) = &args.base {
+        eprintln!("Using overlay filesystem with base database: {}", base);
+        let overlay = open_overlay(base, agentfs).await?;
+        Arc::new(Mutex::new(overlay)) as Arc<Mutex<dyn FileSystem + Send>>
+    } else if let Some(base_path) = base_path {
         // Create OverlayFS with HostFS base, loading existing whiteouts
         eprintln!("Using overlay filesystem with base: {}", base_path);
         let hostfs = HostFS::new(&base_path)?;
@@ -258,23 +556,59 @@ async fn mount_nfs_backend(args: MountArgs) -> Result<()> {
         Arc::new(Mutex::new(agentfs.fs)) as Arc<Mutex<dyn FileSystem + Send>>
     };
 
+    {
+        use anyhow::Context as _;
+        fs.lock()
+            .await
+            .health()
+            .await
+            .context("Filesystem health check failed")?;
+    }
+
+    let info = args
+        .json
+        .then(|| MountInfo::new(&args, &mountpoint, MountBackend::Nfs, &fsname));
+
     if args.foreground {
         // Use the unified mount API for foreground mode
         let mount_opts = MountOpts {
             mountpoint: mountpoint.clone(),
             backend: MountBackend::Nfs,
             fsname,
+            subtype: Some(DEFAULT_SUBTYPE.to_string()),
             uid: args.uid,
             gid: args.gid,
             allow_other: args.allow_other,
             allow_root: args.allow_root,
             auto_unmount: args.auto_unmount,
             lazy_unmount: true,
+            extra_options: args.extra_options.clone(),
+            op_timeout: args.op_timeout,
+            max_read: args.max_read,
+            max_write: args.max_write,
+            attr_timeout: args.attr_timeout,
+            entry_timeout: args.entry_timeout,
+            allowed_clients: None,
+            checkpoint_interval: None,
+            // The mount lock is already held
+            database: None,
+            read_only: args.read_only,
+            root_subdir: args.subdir.clone(),
+            runtime: None,
             timeout: std::time::Duration::from_secs(10),
+            cancel: CancellationToken::new(),
         };
 
-        let _mount_handle = mount_fs(fs, mount_opts).await?;
+        // Dropping the mount future on Ctrl+C tears down a half-established mount
+        let mount_handle = tokio::select! {
+            result = mount_fs(fs, mount_opts) => result?,
+            _ = tokio::signal::ctrl_c() => anyhow::bail!("Mount cancelled"),
+        };
 
+        if let Some(mut info) = info {
+            info.nfs_port = mount_handle.nfs_port();
+            info.print()?;
+        }
         eprintln!("Mounted at {}", mountpoint.display());
         eprintln!("Press Ctrl+C to unmount and exit.");
         tokio::signal::ctrl_c().await?;
@@ -282,7 +616,8 @@ async fn mount_nfs_backend(args: MountArgs) -> Result<()> {
         // Handle drops automatically when we exit this scope
     } else {
         // Daemon mode: use manual NFS server setup for persistent background operation
-        let nfs = AgentNFS::new(fs);
+        let fs = with_root_subdir(fs, args.subdir.as_deref()).await?;
+        let nfs = AgentNFS::new(fs).with_fs_generation().await;
         let port = find_available_port(DEFAULT_NFS_PORT)?;
 
         let bind_addr = format!("127.0.0.1:{}", port);
@@ -299,8 +634,16 @@ async fn mount_nfs_backend(args: MountArgs) -> Result<()> {
         });
 
         tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-        nfs_mount(port, &mountpoint)?;
-
+        let mut options = crate::mount::nfs_cache_options(args.attr_timeout, args.entry_timeout);
+        options.extend(args.extra_options.iter().cloned());
+        nfs_mount(port, &mountpoint, &options)?;
+
+        if let Some(mut info) = info {
+            // The server keeps running in this process
+            info.pid = Some(std::process::id());
+            info.nfs_port = Some(port);
+            info.print()?;
+        }
         eprintln!("Mounted at {}", mountpoint.display());
         eprintln!(
             "Running in background. Use 'umount {}' to unmount.",
@@ -330,15 +673,18 @@ fn find_available_port(start_port: u32) -> Result<u32> {
 
 /// Mount the NFS filesystem (Linux version).
 #[cfg(target_os = "linux")]
-fn nfs_mount(port: u32, mountpoint: &Path) -> Result<()> {
+fn nfs_mount(port: u32, mountpoint: &Path, extra_options: &[String]) -> Result<()> {
     let output = Command::new("mount")
         .args([
             "-t",
             "nfs",
             "-o",
-            &format!(
-                "vers=3,tcp,port={},mountport={},nolock,soft,timeo=10,retrans=2",
-                port, port
+            &crate::mount::join_mount_options(
+                format!(
+                    "vers=3,tcp,port={},mountport={},nolock,soft,timeo=10,retrans=2",
+                    port, port
+                ),
+                extra_options,
             ),
             "127.0.0.1:/",
             mountpoint.to_str().unwrap(),
@@ -359,13 +705,16 @@ fn nfs_mount(port: u32, mountpoint: &Path) -> Result<()> {
 
 /// Mount the NFS filesystem (macOS version).
 #[cfg(target_os = "macos")]
-fn nfs_mount(port: u32, mountpoint: &Path) -> Result<()> {
+fn nfs_mount(port: u32, mountpoint: &Path, extra_options: &[String]) -> Result<()> {
     let output = Command::new("/sbin/mount_nfs")
         .args([
             "-o",
-            &format!(
-                "locallocks,vers=3,tcp,port={},mountport={},soft,timeo=10,retrans=2",
-                port, port
+            &crate::mount::join_mount_options(
+                format!(
+                    "locallocks,vers=3,tcp,port={},mountport={},soft,timeo=10,retrans=2",
+                    port, port
+                ),
+                extra_options,
             ),
             "127.0.0.1:/",
             mountpoint.to_str().unwrap(),
@@ -611,6 +960,116 @@ pub fn prune_mounts(_force: bool) -> Result<()> {
     anyhow::bail!("Mount pruning is only available on Linux")
 }
 
+/// Check whether a mount has lost its filesystem server.
+///
+/// A FUSE mount whose process died fails every access with `ENOTCONN`. The
+/// check runs on a helper thread so that a hung server counts as stale rather
+/// than blocking the caller.
+#[cfg(target_os = "linux")]
+fn is_mount_stale(mountpoint: &Path) -> bool {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let path = mountpoint.to_path_buf();
+    std::thread::spawn(move || {
+        let _ = tx.send(std::fs::metadata(&path).is_err());
+    });
+    rx.recv_timeout(STALE_CHECK_TIMEOUT).unwrap_or(true)
+}
+
+/// Lazily unmount agentfs mounts left behind by processes that are gone.
+///
+/// Scans /proc/self/mounts for agentfs filesystems and detaches the ones
+/// whose mountpoint no longer responds. Healthy mounts are left alone.
+#[cfg(target_os = "linux")]
+pub fn cleanup_stale_mounts() -> Result<()> {
+    let stale: Vec<Mount> = get_mounts()
+        .into_iter()
+        .filter(|m| is_mount_stale(&m.mountpoint))
+        .collect();
+
+    if stale.is_empty() {
+        println!("No stale mounts found.");
+        return Ok(());
+    }
+
+    let mut errors = Vec::new();
+    for mount in &stale {
+        print!("Unmounting stale {}... ", mount.mountpoint.display());
+        let _ = io::stdout().flush();
+
+        match crate::mount::unmount(&mount.mountpoint, MountBackend::Fuse, true) {
+            Ok(()) => println!("done"),
+            Err(e) => {
+                println!("failed");
+                errors.push(format!("{}: {}", mount.mountpoint.display(), e));
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        eprintln!();
+        eprintln!("Some stale mounts could not be unmounted:");
+        for error in &errors {
+            eprintln!("  {}", error);
+        }
+        anyhow::bail!("Failed to unmount {} stale mount(s)", errors.len());
+    }
+
+    Ok(())
+}
+
+/// Lazily unmount stale agentfs mounts (macOS stub).
+#[cfg(target_os = "macos")]
+pub fn cleanup_stale_mounts() -> Result<()> {
+    anyhow::bail!("Stale mount cleanup is only available on Linux")
+}
+
+/// Check that the filesystem mounted at `mountpoint` is the database
+/// `id_or_path`, by comparing the id the mount reports with the database's.
+#[cfg(target_os = "linux")]
+pub fn verify_mount(id_or_path: &str, mountpoint: &Path) -> Result<()> {
+    let expected = crate::get_runtime().block_on(read_database_fs_id(id_or_path))?;
+    let Some(mounted) = crate::mount::read_fs_id(mountpoint)? else {
+        anyhow::bail!(
+            "{} is not an agentfs FUSE mount, so it cannot be verified",
+            mountpoint.display()
+        );
+    };
+    if mounted != expected {
+        anyhow::bail!(
+            "{} serves filesystem {:016x}, not `{}` (filesystem {:016x})",
+            mountpoint.display(),
+            mounted,
+            id_or_path,
+            expected
+        );
+    }
+    println!(
+        "{} serves `{}` (filesystem {:016x})",
+        mountpoint.display(),
+        id_or_path,
+        expected
+    );
+    Ok(())
+}
+
+/// Check that a mount serves the expected database (macOS stub).
+#[cfg(target_os = "macos")]
+pub fn verify_mount(_id_or_path: &str, _mountpoint: &Path) -> Result<()> {
+    anyhow::bail!("Mount verification is only available on Linux")
+}
+
+/// Read the filesystem id of a database without opening it as a filesystem,
+/// so a database that is mounted can be checked alongside the mount.
+#[cfg(target_os = "linux")]
+async fn read_database_fs_id(id_or_path: &str) -> Result<u64> {
+    let db_path = resolve_mount_options(id_or_path, false)?.db_path()?;
+    let db = turso::Builder::new_local(&db_path).build().await?;
+    let conn = db.connect()?;
+    agentfs_sdk::filesystem::AgentFS::read_inode_generation(&conn)
+        .await
+        .with_context(|| format!("Failed to read filesystem id of `{}`", id_or_path))
+}
+
 /// Print schema version mismatch error and exit.
 fn exit_schema_version_mismatch(found: &str, expected: &str, id_or_path: &str) -> ! {
     eprintln!("Error: Filesystem `{}` requires migration", id_or_path);
@@ -626,3 +1085,191 @@ fn exit_schema_version_mismatch(found: &str, expected: &str, id_or_path: &str) -
     eprintln!();
     std::process::exit(1);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(target_os = "linux")]
+    use crate::mount::mount_for_test;
+
+    #[test]
+    fn test_resolve_missing_database_requires_create() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("missing.db");
+        let db_path = db_path.to_str().unwrap();
+
+        let err = resolve_mount_options(db_path, false).unwrap_err();
+        assert!(err.to_string().contains("--create"), "{}", err);
+        assert!(!Path::new(db_path).exists());
+
+        let opts = resolve_mount_options(db_path, true).unwrap();
+        assert_eq!(opts.db_path().unwrap(), db_path);
+    }
+
+    #[test]
+    fn test_mount_info_json_keys() {
+        let args = MountArgs {
+            id_or_path: "agent".to_string(),
+            mountpoint: PathBuf::from("/mnt/agent"),
+            auto_unmount: false,
+            allow_root: false,
+            allow_other: false,
+            foreground: false,
+            uid: Some(1000),
+            gid: None,
+            backend: MountBackend::Nfs,
+            read_only: true,
+            create: false,
+            base: None,
+            extra_options: vec!["noexec".to_string()],
+            op_timeout: Some(std::time::Duration::from_secs(5)),
+            max_read: None,
+            max_write: Some(65536),
+            attr_timeout: Some(std::time::Duration::ZERO),
+            entry_timeout: None,
+            subdir: Some(PathBuf::from("/projects/foo")),
+            fsname: None,
+            json: true,
+        };
+        let mut info = MountInfo::new(
+            &args,
+            Path::new("/mnt/agent"),
+            MountBackend::Nfs,
+            "agentfs:/tmp/agent.db",
+        );
+        info.pid = Some(4242);
+        info.nfs_port = Some(11111);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&info).unwrap()).unwrap();
+        assert_eq!(json["mountpoint"], "/mnt/agent");
+        assert_eq!(json["backend"], "nfs");
+        assert_eq!(json["pid"], 4242);
+        assert_eq!(json["nfs_port"], 11111);
+
+        let options = json["options"].as_object().unwrap();
+        for key in [
+            "fsname",
+            "subtype",
+            "uid",
+            "gid",
+            "allow_other",
+            "allow_root",
+            "auto_unmount",
+            "read_only",
+            "foreground",
+            "base",
+            "extra_options",
+            "op_timeout_secs",
+            "max_read",
+            "max_write",
+            "attr_timeout_secs",
+            "entry_timeout_secs",
+            "subdir",
+        ] {
+            assert!(options.contains_key(key), "missing option {}", key);
+        }
+        assert_eq!(options["fsname"], "agentfs:/tmp/agent.db");
+        assert_eq!(options["read_only"], true);
+        assert_eq!(options["extra_options"], serde_json::json!(["noexec"]));
+        assert_eq!(options["op_timeout_secs"], 5.0);
+        assert_eq!(options["max_write"], 65536);
+        assert_eq!(options["attr_timeout_secs"], 0.0);
+        assert!(options["entry_timeout_secs"].is_null());
+        assert_eq!(options["subdir"], "/projects/foo");
+        assert!(options["gid"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_overlay_writes_go_to_delta() {
+        const ROOT_INO: i64 = 1;
+
+        let dir = tempfile::tempdir().unwrap();
+        let base_path = dir.path().join("base.db");
+        let delta_path = dir.path().join("delta.db");
+        let base_path = base_path.to_str().unwrap();
+        let delta_path = delta_path.to_str().unwrap();
+
+        let base = AgentFS::open(AgentFSOptions::with_path(base_path))
+            .await
+            .unwrap();
+        base.fs.pwrite("/base.txt", 0, b"base").await.unwrap();
+        drop(base);
+
+        let delta = open_for_mount(resolve_mount_options(delta_path, true).unwrap(), false)
+            .await
+            .unwrap();
+        let overlay = open_overlay(base_path, delta).await.unwrap();
+        assert!(overlay
+            .lookup(ROOT_INO, "base.txt")
+            .await
+            .unwrap()
+            .is_some());
+        let (_, file) = overlay
+            .create_file(ROOT_INO, "new.txt", 0o644, 0, 0)
+            .await
+            .unwrap();
+        file.pwrite(0, b"new").await.unwrap();
+        drop(file);
+        drop(overlay);
+
+        // The new file is in the delta, and the base is untouched
+        let base = AgentFS::open(AgentFSOptions::with_path(base_path))
+            .await
+            .unwrap();
+        assert!(base.fs.read_file("/new.txt").await.unwrap().is_none());
+        let delta = AgentFS::open(AgentFSOptions::with_path(delta_path))
+            .await
+            .unwrap();
+        assert_eq!(
+            delta.fs.read_file("/new.txt").await.unwrap().unwrap(),
+            b"new"
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    #[ignore = "mounts FUSE, which needs access to /dev/fuse"]
+    async fn test_verify_mount_checks_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let mountpoint = dir.path().join("mnt");
+        std::fs::create_dir(&mountpoint).unwrap();
+        let mounted = dir.path().join("mounted.db");
+        let other = dir.path().join("other.db");
+        let mounted = mounted.to_str().unwrap().to_string();
+        let other = other.to_str().unwrap().to_string();
+
+        drop(
+            AgentFS::open(AgentFSOptions::with_path(&other))
+                .await
+                .unwrap(),
+        );
+        let agent = AgentFS::open(AgentFSOptions::with_path(&mounted))
+            .await
+            .unwrap();
+        let fs: Arc<Mutex<dyn FileSystem + Send>> = Arc::new(Mutex::new(agent.fs));
+
+        let opts = MountOpts::new(mountpoint.clone(), MountBackend::Fuse);
+        let handle = mount_for_test(fs, opts).await;
+
+        // Verification opens its own runtime, so it runs off this one
+        let verify = |db: String| {
+            let mountpoint = mountpoint.clone();
+            tokio::task::spawn_blocking(move || verify_mount(&db, &mountpoint))
+        };
+        verify(mounted.clone()).await.unwrap().unwrap();
+        let id = std::fs::read_to_string(mountpoint.join(crate::fuse::FS_ID_FILE)).unwrap();
+        assert_eq!(id.len(), 17);
+        // The id file is not listed, so copies of the mount leave it behind
+        assert!(std::fs::read_dir(&mountpoint)
+            .unwrap()
+            .all(|entry| entry.unwrap().file_name() != crate::fuse::FS_ID_FILE));
+        let err = verify(other).await.unwrap().unwrap_err();
+        assert!(err.to_string().contains("not `"));
+
+        // A directory that is not a mount has no id to compare
+        drop(handle);
+        let err = verify(mounted).await.unwrap().unwrap_err();
+        assert!(err.to_string().contains("not an agentfs FUSE mount"));
+    }
+}
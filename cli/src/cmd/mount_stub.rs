@@ -1,5 +1,8 @@
 use anyhow::Result;
-use std::{io::Write, path::PathBuf};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
 
 pub use crate::opts::MountBackend;
 
@@ -25,6 +28,21 @@ pub struct MountArgs {
     pub gid: Option<u32>,
     /// The mount backend to use (fuse or nfs).
     pub backend: MountBackend,
+    /// Open the database read-only and mount without write access.
+    pub read_only: bool,
+    /// Create a new, empty filesystem if the database does not exist.
+    pub create: bool,
+    /// Raw mount options appended to the backend's option string.
+    pub extra_options: Vec<String>,
+    /// Fail filesystem operations that take longer than this with EIO.
+    pub op_timeout: Option<std::time::Duration>,
+    /// Directory of the filesystem to mount as the mount root.
+    pub subdir: Option<PathBuf>,
+    /// Filesystem name shown by `mount` and `df`, instead of one derived
+    /// from the database path.
+    pub fsname: Option<String>,
+    /// Print the mount details as JSON on stdout once mounted.
+    pub json: bool,
 }
 
 /// List all currently mounted agentfs filesystems
@@ -41,3 +59,13 @@ pub fn mount(_args: MountArgs) -> Result<()> {
 pub fn prune_mounts(_force: bool) -> Result<()> {
     anyhow::bail!("Mount pruning is only available on Unix")
 }
+
+/// Lazily unmount stale agentfs mounts.
+pub fn cleanup_stale_mounts() -> Result<()> {
+    anyhow::bail!("Stale mount cleanup is only available on Unix")
+}
+
+/// Check that a mount serves the expected database.
+pub fn verify_mount(_id_or_path: &str, _mountpoint: &Path) -> Result<()> {
+    anyhow::bail!("Mount verification is only available on Unix")
+}
@@ -6,6 +6,7 @@
 
 use agentfs_sdk::{agentfs_dir, AgentFSOptions, FileSystem, HostFS, OverlayFS};
 use anyhow::{Context, Result};
+use std::net::IpAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::signal;
@@ -15,7 +16,15 @@ use crate::cmd::init::open_agentfs;
 use crate::nfs::AgentNFS;
 
 /// Handle the `nfs` command - start a standalone NFS server.
-pub async fn handle_nfs_command(id_or_path: String, bind: String, port: u32) -> Result<()> {
+///
+/// If `allow_clients` is non-empty, connections from other addresses are
+/// refused.
+pub async fn handle_nfs_command(
+    id_or_path: String,
+    bind: String,
+    port: u32,
+    allow_clients: Vec<IpAddr>,
+) -> Result<()> {
     // Resolve database path
     let db_path = resolve_db_path(&id_or_path)?;
 
@@ -47,13 +56,16 @@ pub async fn handle_nfs_command(id_or_path: String, bind: String, port: u32) ->
     };
 
     // Create NFS adapter
-    let nfs = AgentNFS::new(fs);
+    let nfs = AgentNFS::new(fs).with_fs_generation().await;
 
     // Bind NFS server
     let bind_addr_str = format!("{}:{}", bind, port);
-    let listener = crate::nfsserve::tcp::NFSTcpListener::bind(&bind_addr_str, nfs)
+    let mut listener = crate::nfsserve::tcp::NFSTcpListener::bind(&bind_addr_str, nfs)
         .await
         .with_context(|| format!("Failed to bind NFS server to {}", bind_addr_str))?;
+    if !allow_clients.is_empty() {
+        listener.with_allowed_clients(allow_clients.clone());
+    }
 
     // Print server info
     eprintln!();
@@ -61,6 +73,10 @@ pub async fn handle_nfs_command(id_or_path: String, bind: String, port: u32) ->
     eprintln!("  Database: {}", db_path.display());
     eprintln!("  Listening: {}", bind_addr_str);
     eprintln!("  Export: /");
+    if !allow_clients.is_empty() {
+        let clients: Vec<String> = allow_clients.iter().map(|ip| ip.to_string()).collect();
+        eprintln!("  Allowed clients: {}", clients.join(", "));
+    }
     eprintln!();
     eprintln!("Mount from client:");
     eprintln!(
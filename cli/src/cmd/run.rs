@@ -23,6 +23,15 @@ pub async fn handle_run_command(
     no_default_allows: bool,
     experimental_sandbox: bool,
     strace: bool,
+    events: Option<PathBuf>,
+    audit_log: Option<PathBuf>,
+    capture_stdout: Option<PathBuf>,
+    capture_stderr: Option<PathBuf>,
+    delta_in: Option<PathBuf>,
+    delta_out: Option<PathBuf>,
+    workdir: Option<PathBuf>,
+    create_workdir: bool,
+    pid_namespace: bool,
     session: Option<String>,
     system: bool,
     encryption: Option<(String, String)>,
@@ -34,6 +43,15 @@ pub async fn handle_run_command(
         no_default_allows,
         experimental_sandbox,
         strace,
+        events,
+        audit_log,
+        capture_stdout,
+        capture_stderr,
+        delta_in,
+        delta_out,
+        workdir,
+        create_workdir,
+        pid_namespace,
         session,
         system,
         encryption,
@@ -32,12 +32,42 @@ pub async fn run(
     no_default_allows: bool,
     _experimental_sandbox: bool,
     _strace: bool,
+    events: Option<PathBuf>,
+    audit_log: Option<PathBuf>,
+    capture_stdout: Option<PathBuf>,
+    capture_stderr: Option<PathBuf>,
+    delta_in: Option<PathBuf>,
+    delta_out: Option<PathBuf>,
+    workdir: Option<PathBuf>,
+    _create_workdir: bool,
+    pid_namespace: bool,
     session_id: Option<String>,
     _system: bool,
     encryption: Option<(String, String)>,
     command: PathBuf,
     args: Vec<String>,
 ) -> Result<()> {
+    if events.is_some() {
+        eprintln!("Warning: --events is only supported on Linux, ignoring");
+    }
+    if audit_log.is_some() {
+        eprintln!("Warning: --audit-log is only supported on Linux, ignoring");
+    }
+    if capture_stdout.is_some() || capture_stderr.is_some() {
+        eprintln!(
+            "Warning: --capture-stdout and --capture-stderr are only supported on Linux, ignoring"
+        );
+    }
+    if delta_in.is_some() || delta_out.is_some() {
+        eprintln!("Warning: --delta-in and --delta-out are only supported on Linux, ignoring");
+    }
+    if workdir.is_some() {
+        eprintln!("Warning: --workdir is only supported on Linux, ignoring");
+    }
+    if pid_namespace {
+        eprintln!("Warning: --pid-namespace is only supported on Linux, ignoring");
+    }
+
     let cwd = std::env::current_dir().context("Failed to get current directory")?;
     let home = dirs::home_dir().context("Failed to get home directory")?;
 
@@ -90,7 +120,7 @@ pub async fn run(
     let fs: Arc<Mutex<dyn FileSystem>> = Arc::new(Mutex::new(overlay));
 
     // Create NFS adapter
-    let nfs = AgentNFS::new(fs);
+    let nfs = AgentNFS::new(fs).with_fs_generation().await;
 
     // Find an available port
     let port = find_available_port(DEFAULT_NFS_PORT)?;
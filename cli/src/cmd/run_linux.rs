@@ -3,6 +3,7 @@
 //! Dispatches to either the FUSE+namespace sandbox (default) or the experimental
 //! ptrace-based sandbox based on command-line flags.
 
+use crate::sandbox::linux::StdioCapture;
 use anyhow::Result;
 use std::path::PathBuf;
 
@@ -13,6 +14,15 @@ pub async fn run(
     no_default_allows: bool,
     experimental_sandbox: bool,
     strace: bool,
+    events: Option<PathBuf>,
+    audit_log: Option<PathBuf>,
+    capture_stdout: Option<PathBuf>,
+    capture_stderr: Option<PathBuf>,
+    delta_in: Option<PathBuf>,
+    delta_out: Option<PathBuf>,
+    workdir: Option<PathBuf>,
+    create_workdir: bool,
+    pid_namespace: bool,
     session: Option<String>,
     system: bool,
     encryption: Option<(String, String)>,
@@ -29,21 +39,58 @@ pub async fn run(
         if encryption.is_some() {
             eprintln!("Warning: --key is not supported with --experimental-sandbox, ignoring");
         }
+        if events.is_some() {
+            eprintln!("Warning: --events is not supported with --experimental-sandbox, ignoring");
+        }
+        if audit_log.is_some() {
+            eprintln!(
+                "Warning: --audit-log is not supported with --experimental-sandbox, ignoring"
+            );
+        }
+        if capture_stdout.is_some() || capture_stderr.is_some() {
+            eprintln!("Warning: --capture-stdout and --capture-stderr are not supported with --experimental-sandbox, ignoring");
+        }
+        if delta_in.is_some() || delta_out.is_some() {
+            eprintln!("Warning: --delta-in and --delta-out are not supported with --experimental-sandbox, ignoring");
+        }
+        if workdir.is_some() {
+            eprintln!("Warning: --workdir is not supported with --experimental-sandbox, ignoring");
+        }
+        if pid_namespace {
+            eprintln!(
+                "Warning: --pid-namespace is not supported with --experimental-sandbox, ignoring"
+            );
+        }
         crate::sandbox::linux_ptrace::run_cmd(strace, command, args).await;
     } else {
         if strace {
             eprintln!("Warning: --strace is only supported with --experimental-sandbox, ignoring");
         }
-        crate::sandbox::linux::run_cmd(
+        let output = crate::sandbox::linux::run_cmd(
             allow,
             no_default_allows,
             session,
             system,
             encryption,
+            events,
+            audit_log,
+            capture(capture_stdout),
+            capture(capture_stderr),
+            delta_in,
+            delta_out,
+            workdir,
+            create_workdir,
+            pid_namespace,
             command,
             args,
         )
         .await?;
+        std::process::exit(output.exit_code);
     }
     Ok(())
 }
+
+/// Capture to `path` if given, otherwise pass the stream through.
+fn capture(path: Option<PathBuf>) -> StdioCapture {
+    path.map(StdioCapture::File).unwrap_or_default()
+}
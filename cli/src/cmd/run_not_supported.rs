@@ -11,6 +11,15 @@ pub async fn run(
     _no_default_allows: bool,
     _experimental_sandbox: bool,
     _strace: bool,
+    _events: Option<PathBuf>,
+    _audit_log: Option<PathBuf>,
+    _capture_stdout: Option<PathBuf>,
+    _capture_stderr: Option<PathBuf>,
+    _delta_in: Option<PathBuf>,
+    _delta_out: Option<PathBuf>,
+    _workdir: Option<PathBuf>,
+    _create_workdir: bool,
+    _pid_namespace: bool,
     _session: Option<String>,
     _system: bool,
     _encryption: Option<(String, String)>,
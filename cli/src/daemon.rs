@@ -7,6 +7,32 @@ use std::{
 /// Maximum length for error messages sent through the daemon pipe.
 const MAX_ERROR_MSG_LEN: usize = 4096;
 
+/// Default interval between readiness checks.
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How the daemon's readiness is waited for.
+#[derive(Debug, Clone)]
+pub struct DaemonOpts {
+    /// How long to wait for the ready check to succeed
+    pub timeout: Duration,
+    /// Interval between ready checks
+    pub poll_interval: Duration,
+    /// Double the interval after every failed check, up to this maximum.
+    /// Without it, checks are made every `poll_interval`.
+    pub max_poll_interval: Option<Duration>,
+}
+
+impl DaemonOpts {
+    /// Wait up to `timeout`, checking every 50ms.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            max_poll_interval: None,
+        }
+    }
+}
+
 /// Daemonize the current process and run a function in the daemon.
 ///
 /// This function forks the process, detaches from the terminal, and runs the
@@ -16,12 +42,12 @@ const MAX_ERROR_MSG_LEN: usize = 4096;
 /// # Arguments
 /// * `daemon_fn` - The function to run in the daemon process (should block until done)
 /// * `ready_check` - A function that polls for readiness (returns true when ready)
-/// * `timeout` - How long to wait for the ready_check to succeed
+/// * `opts` - How long and how often to poll the ready_check
 ///
 /// # Returns
-/// * `Ok(())` in the parent process if the daemon started successfully
+/// * `Ok(pid)` in the parent process if the daemon started successfully
 /// * Never returns in the child process (exits with appropriate code)
-pub fn daemonize<F, R>(daemon_fn: F, ready_check: R, timeout: Duration) -> Result<()>
+pub fn daemonize<F, R>(daemon_fn: F, ready_check: R, opts: &DaemonOpts) -> Result<u32>
 where
     F: FnOnce() -> Result<()> + Send + 'static,
     R: Fn() -> bool,
@@ -54,19 +80,7 @@ where
             let (daemon_thread, error_msg) = start_daemon(daemon_fn);
 
             // Wait for readiness, but fail early if daemon thread exits
-            let start = std::time::Instant::now();
-            let ready = loop {
-                if ready_check() {
-                    break true;
-                }
-                if daemon_thread.is_finished() {
-                    break false;
-                }
-                if start.elapsed() >= timeout {
-                    break false;
-                }
-                std::thread::sleep(Duration::from_millis(50));
-            };
+            let ready = wait_until_ready(&ready_check, || daemon_thread.is_finished(), opts);
 
             // Signal parent with result
             let signal_result = if ready {
@@ -93,7 +107,7 @@ where
                 _ => std::process::exit(1),
             }
         }
-        _child_pid => {
+        child_pid => {
             // Parent process
             unsafe { libc::close(write_fd) };
 
@@ -102,13 +116,43 @@ where
             unsafe { libc::close(read_fd) };
 
             match result {
-                Ok(()) => Ok(()),
+                Ok(()) => Ok(child_pid as u32),
                 Err(msg) => anyhow::bail!("{}", msg),
             }
         }
     }
 }
 
+/// Poll `ready_check` as configured by `opts` until it succeeds.
+///
+/// Returns false if the check does not succeed within the timeout, or if
+/// `failed` reports that the daemon gave up first.
+fn wait_until_ready(
+    ready_check: impl Fn() -> bool,
+    failed: impl Fn() -> bool,
+    opts: &DaemonOpts,
+) -> bool {
+    let start = std::time::Instant::now();
+    let mut interval = opts.poll_interval;
+    loop {
+        if ready_check() {
+            return true;
+        }
+        if failed() {
+            return false;
+        }
+        let elapsed = start.elapsed();
+        if elapsed >= opts.timeout {
+            return false;
+        }
+        // Don't sleep past the timeout for a last check
+        std::thread::sleep(interval.min(opts.timeout - elapsed));
+        if let Some(max) = opts.max_poll_interval {
+            interval = interval.saturating_mul(2).min(max);
+        }
+    }
+}
+
 /// Signal parent process via pipe with optional error message.
 ///
 /// Retries on EINTR to handle signal interruption during write.
@@ -257,3 +301,67 @@ fn redirect_stdio() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Instant;
+
+    /// A ready check that succeeds once `delay` has passed, counting calls.
+    fn ready_after(delay: Duration) -> (impl Fn() -> bool, Arc<AtomicUsize>) {
+        let start = Instant::now();
+        let checks = Arc::new(AtomicUsize::new(0));
+        let counter = checks.clone();
+        let check = move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            start.elapsed() >= delay
+        };
+        (check, checks)
+    }
+
+    #[test]
+    fn test_wait_until_ready_polls_at_interval() {
+        let (check, checks) = ready_after(Duration::from_millis(300));
+        let opts = DaemonOpts {
+            poll_interval: Duration::from_millis(100),
+            ..DaemonOpts::new(Duration::from_secs(5))
+        };
+        assert!(wait_until_ready(check, || false, &opts));
+        // Immediately, then about every 100ms until 300ms have passed
+        let checks = checks.load(Ordering::SeqCst);
+        assert!((2..=5).contains(&checks), "{} checks", checks);
+    }
+
+    #[test]
+    fn test_wait_until_ready_backs_off() {
+        let (check, checks) = ready_after(Duration::from_millis(700));
+        let opts = DaemonOpts {
+            poll_interval: Duration::from_millis(50),
+            max_poll_interval: Some(Duration::from_millis(200)),
+            ..DaemonOpts::new(Duration::from_secs(5))
+        };
+        assert!(wait_until_ready(check, || false, &opts));
+        // Checks at 0, 50, 150, 350, 550, 750ms instead of every 50ms
+        let checks = checks.load(Ordering::SeqCst);
+        assert!((4..=7).contains(&checks), "{} checks", checks);
+    }
+
+    #[test]
+    fn test_wait_until_ready_gives_up() {
+        let opts = DaemonOpts::new(Duration::from_millis(200));
+        let start = Instant::now();
+        assert!(!wait_until_ready(|| false, || false, &opts));
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        // A daemon that already failed is not waited for
+        let start = Instant::now();
+        assert!(!wait_until_ready(
+            || false,
+            || true,
+            &DaemonOpts::new(Duration::from_secs(5))
+        ));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
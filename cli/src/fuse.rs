@@ -1,27 +1,31 @@
 use crate::fuser::{
     consts::{
-        FUSE_ASYNC_READ, FUSE_CACHE_SYMLINKS, FUSE_NO_OPENDIR_SUPPORT, FUSE_PARALLEL_DIROPS,
-        FUSE_WRITEBACK_CACHE,
+        FOPEN_DIRECT_IO, FUSE_ASYNC_READ, FUSE_CACHE_SYMLINKS, FUSE_NO_OPENDIR_SUPPORT,
+        FUSE_PARALLEL_DIROPS, FUSE_POLL_SCHEDULE_NOTIFY, FUSE_WRITEBACK_CACHE,
     },
-    fuse_forget_one, FileAttr, FileType, Filesystem, KernelConfig, MountOption, ReplyAttr,
-    ReplyCreate, ReplyData, ReplyDirectory, ReplyDirectoryPlus, ReplyEmpty, ReplyEntry, ReplyOpen,
-    ReplyStatfs, ReplyWrite, Request,
+    deferred_notify::DeferredNotifier,
+    fuse_forget_one, FileAttr, FileType, Filesystem, InFlightOp, InterruptTracker, KernelConfig,
+    MountOption, PollHandle, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory, ReplyDirectoryPlus,
+    ReplyEmpty, ReplyEntry, ReplyOpen, ReplyPoll, ReplyStatfs, ReplyWrite, Request, FUSE_ROOT_ID,
 };
 use agentfs_sdk::error::Error as SdkError;
-use agentfs_sdk::filesystem::{S_IFBLK, S_IFCHR, S_IFDIR, S_IFIFO, S_IFLNK, S_IFMT, S_IFSOCK};
+use agentfs_sdk::filesystem::{
+    FsError, S_IFBLK, S_IFCHR, S_IFDIR, S_IFIFO, S_IFLNK, S_IFMT, S_IFREG, S_IFSOCK,
+};
 use agentfs_sdk::{BoxedFile, FileSystem, Stats, TimeChange};
 use parking_lot::Mutex;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     ffi::OsStr,
     path::PathBuf,
     sync::{
         atomic::{AtomicU64, Ordering},
-        Arc,
+        Arc, OnceLock, Weak,
     },
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use tokio::runtime::Runtime;
+use tokio_util::sync::CancellationToken;
 use tracing;
 
 /// Convert an SDK error to an errno code for FUSE replies.
@@ -30,7 +34,7 @@ use tracing;
 /// errno code (ENOENT, EEXIST, ENOTDIR, etc.). Database busy errors and
 /// connection pool timeouts return EAGAIN to signal the caller should retry.
 /// Otherwise falls back to EIO.
-fn error_to_errno(e: &SdkError) -> i32 {
+pub(crate) fn error_to_errno(e: &SdkError) -> i32 {
     match e {
         SdkError::Fs(fs_err) => fs_err.to_errno(),
         SdkError::Io(io_err) => io_err.raw_os_error().unwrap_or(libc::EIO),
@@ -73,6 +77,21 @@ fn maximize_fd_limit() {
 /// This is safe because we are the only writer to the filesystem.
 const TTL: Duration = Duration::MAX;
 
+/// Name of the virtual file in the mount root holding the filesystem id,
+/// the database's inode generation in hex. It is not listed by readdir and
+/// hides a real file of the same name.
+pub const FS_ID_FILE: &str = ".agentfs-id";
+
+/// Inode number of the `FS_ID_FILE`, outside the range of `i64` inode
+/// numbers the filesystem hands out
+const FS_ID_INO: u64 = u64::MAX;
+
+/// Whether `name` under `parent` is the `FS_ID_FILE`, which cannot be
+/// created, replaced, renamed or removed
+fn is_fs_id_file(parent: u64, name: &OsStr) -> bool {
+    parent == FUSE_ROOT_ID && name == FS_ID_FILE
+}
+
 /// Options for mounting an agent filesystem via FUSE.
 #[derive(Debug, Clone)]
 pub struct FuseMountOptions {
@@ -87,10 +106,23 @@ pub struct FuseMountOptions {
     pub allow_other: bool,
     /// Filesystem name shown in mount output.
     pub fsname: String,
+    /// Filesystem subtype, shown as `fuse.<subtype>` in mount output.
+    pub subtype: Option<String>,
     /// User ID to report for all files (defaults to current user).
     pub uid: Option<u32>,
     /// Group ID to report for all files (defaults to current group).
     pub gid: Option<u32>,
+    /// Mount read-only.
+    pub read_only: bool,
+    /// Raw mount options appended after the ones above (e.g. `noexec`).
+    pub extra_options: Vec<String>,
+    /// Fail a filesystem operation with EIO if it takes longer than this,
+    /// instead of blocking the calling process indefinitely.
+    pub op_timeout: Option<Duration>,
+    /// Largest read the kernel sends in a single request.
+    pub max_read: Option<u32>,
+    /// Largest write the kernel sends in a single request.
+    pub max_write: Option<u32>,
 }
 
 /// Tracks an open file handle
@@ -99,13 +131,91 @@ struct OpenFile {
     file: BoxedFile,
 }
 
+/// A `poll()` waiting to be woken by a change to an inode
+struct PollWaiter {
+    /// The inode polled
+    ino: u64,
+    /// Kernel poll handle to notify
+    kh: u64,
+}
+
+/// Reaches into a running FUSE session from outside its upcalls.
+#[derive(Default)]
+pub(crate) struct FuseControl {
+    /// Notification queue of the session, set once it is mounted
+    notifier: OnceLock<Weak<DeferredNotifier>>,
+    /// Files the kernel holds open through the mount
+    open_files: OnceLock<Arc<Mutex<HashMap<u64, OpenFile>>>>,
+    /// Requests in flight in the session, set once it is mounted
+    in_flight: OnceLock<Weak<InterruptTracker>>,
+    /// Lookups the kernel holds through the mount
+    lookups: OnceLock<Arc<Mutex<HashMap<u64, u64>>>>,
+    /// Inodes unlinked while the kernel held lookups of them
+    orphans: OnceLock<Arc<Mutex<HashSet<u64>>>>,
+}
+
+impl FuseControl {
+    /// Queue of kernel notifications of the session, while it is running.
+    pub(crate) fn notifier(&self) -> Option<Arc<DeferredNotifier>> {
+        self.notifier.get()?.upgrade()
+    }
+
+    /// Number of files currently open through the mount.
+    pub(crate) fn open_files(&self) -> usize {
+        self.open_files.get().map_or(0, |files| files.lock().len())
+    }
+
+    /// Stop tracking the kernel's lookups, when the filesystem they are of
+    /// is swapped out.
+    ///
+    /// Returns the inodes unlinked while in use, which the swapped out
+    /// filesystem should release: once the kernel forgets them, its FORGETs
+    /// reach the filesystem swapped in.
+    pub(crate) fn take_lookups(&self) -> Vec<u64> {
+        if let Some(lookups) = self.lookups.get() {
+            lookups.lock().clear();
+        }
+        self.orphans
+            .get()
+            .map_or_else(Vec::new, |orphans| orphans.lock().drain().collect())
+    }
+
+    /// Requests the session is serving, oldest first. Empty once the session
+    /// has ended.
+    pub(crate) fn in_flight_ops(&self) -> Vec<InFlightOp> {
+        self.in_flight
+            .get()
+            .and_then(Weak::upgrade)
+            .map_or_else(Vec::new, |tracker| tracker.in_flight())
+    }
+
+    /// Cancel an in-flight request as if the kernel had interrupted it, so
+    /// that the caller gets EINTR. Returns false if it is no longer in flight.
+    pub(crate) fn cancel_op(&self, id: u64) -> bool {
+        self.in_flight
+            .get()
+            .and_then(Weak::upgrade)
+            .is_some_and(|tracker| tracker.interrupt(id))
+    }
+}
+
 struct AgentFSFuse {
     fs: Arc<dyn FileSystem>,
     runtime: Runtime,
     /// Maps file handle -> open file state
     open_files: Arc<Mutex<HashMap<u64, OpenFile>>>,
+    /// Maps file handle -> poll registered through it
+    pollers: Mutex<HashMap<u64, PollWaiter>>,
     /// Next file handle to allocate
     next_fh: AtomicU64,
+    /// Upper bound on how long a single SDK call may block an upcall
+    op_timeout: Option<Duration>,
+    /// Largest write to accept from the kernel, if not the default
+    max_write: Option<u32>,
+    /// Maps inode -> lookups the kernel holds, which it releases with FORGET
+    lookups: Arc<Mutex<HashMap<u64, u64>>>,
+    /// Inodes unlinked while the kernel still held lookups of them
+    orphans: Arc<Mutex<HashSet<u64>>>,
 }
 
 impl Filesystem for AgentFSFuse {
@@ -121,6 +231,8 @@ impl Filesystem for AgentFSFuse {
     ///   for symlink resolution.
     /// - No opendir support: skips opendir/releasedir calls since we don't track
     ///   directory handles, reducing round-trips for directory operations.
+    ///
+    /// A configured `max_write` replaces the default write request size.
     fn init(&mut self, _req: &Request, config: &mut KernelConfig) -> Result<(), libc::c_int> {
         tracing::debug!("FUSE::init");
         let _ = config.add_capabilities(
@@ -130,6 +242,12 @@ impl Filesystem for AgentFSFuse {
                 | FUSE_CACHE_SYMLINKS
                 | FUSE_NO_OPENDIR_SUPPORT,
         );
+        if let Some(max_write) = self.max_write {
+            if let Err(limit) = config.set_max_write(max_write) {
+                tracing::error!("FUSE max_write {} exceeds limit {}", max_write, limit);
+                return Err(libc::EINVAL);
+            }
+        }
         Ok(())
     }
 
@@ -140,9 +258,17 @@ impl Filesystem for AgentFSFuse {
     /// Looks up a directory entry by name within a parent directory.
     ///
     /// Resolves `name` under the directory identified by `parent` inode.
-    fn lookup(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
+    fn lookup(&mut self, req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
         tracing::debug!("FUSE::lookup: parent={}, name={:?}", parent, name);
 
+        if is_fs_id_file(parent, name) {
+            match self.fs_id_file(req.interrupted()) {
+                Ok((stats, _)) => reply.entry(&TTL, &fillattr(&stats), 0),
+                Err(e) => reply.error(error_to_errno(&e)),
+            }
+            return;
+        }
+
         let Some(name_str) = name.to_str() else {
             reply.error(libc::EINVAL);
             return;
@@ -150,14 +276,15 @@ impl Filesystem for AgentFSFuse {
 
         let fs = self.fs.clone();
         let name_owned = name_str.to_string();
-        let result = self
-            .runtime
-            .block_on(async move { fs.lookup(parent as i64, &name_owned).await });
+        let result = self.block_on(req.interrupted(), async move {
+            fs.lookup(parent as i64, &name_owned).await
+        });
 
         match result {
             Ok(Some(stats)) => {
                 let attr = fillattr(&stats);
-                reply.entry(&TTL, &attr, 0);
+                self.remember(stats.ino as u64);
+                reply.entry(&TTL, &attr, stats.generation);
             }
             Ok(None) => reply.error(libc::ENOENT),
             Err(e) => reply.error(error_to_errno(&e)),
@@ -167,16 +294,21 @@ impl Filesystem for AgentFSFuse {
     /// Retrieves file attributes for a given inode.
     ///
     /// Returns metadata (size, permissions, timestamps, etc.) for the file or
-    /// directory identified by `ino`. Root inode (1) is handled specially.
-    fn getattr(&mut self, _req: &Request, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
-        tracing::debug!("FUSE::getattr: ino={}", ino);
-
-        let fs = self.fs.clone();
-        let result = self
-            .runtime
-            .block_on(async move { fs.getattr(ino as i64).await });
+    /// directory identified by `ino`. When the kernel passes a file handle
+    /// (e.g. `fstat()` on an open fd), the attributes come from the open file
+    /// instead of resolving the inode again.
+    fn getattr(&mut self, req: &Request, ino: u64, fh: Option<u64>, reply: ReplyAttr) {
+        tracing::debug!("FUSE::getattr: ino={}, fh={:?}", ino, fh);
+
+        if ino == FS_ID_INO {
+            match self.fs_id_file(req.interrupted()) {
+                Ok((stats, _)) => reply.attr(&TTL, &fillattr(&stats)),
+                Err(e) => reply.error(error_to_errno(&e)),
+            }
+            return;
+        }
 
-        match result {
+        match self.getattr_with_handle(req.interrupted(), ino, fh) {
             Ok(Some(stats)) => reply.attr(&TTL, &fillattr(&stats)),
             Ok(None) => reply.error(libc::ENOENT),
             Err(e) => reply.error(error_to_errno(&e)),
@@ -187,13 +319,14 @@ impl Filesystem for AgentFSFuse {
     ///
     /// Returns the path that the symlink points to. This is called by operations
     /// like `ls -l` to display symlink targets.
-    fn readlink(&mut self, _req: &Request, ino: u64, reply: ReplyData) {
+    fn readlink(&mut self, req: &Request, ino: u64, reply: ReplyData) {
         tracing::debug!("FUSE::readlink: ino={}", ino);
 
         let fs = self.fs.clone();
-        let result = self
-            .runtime
-            .block_on(async move { fs.readlink(ino as i64).await });
+        let result = self.block_on(
+            req.interrupted(),
+            async move { fs.readlink(ino as i64).await },
+        );
 
         match result {
             Ok(Some(target)) => reply.data(target.as_bytes()),
@@ -208,7 +341,7 @@ impl Filesystem for AgentFSFuse {
     /// Other attribute changes (uid, gid, timestamps) are accepted but ignored.
     fn setattr(
         &mut self,
-        _req: &Request,
+        req: &Request,
         ino: u64,
         mode: Option<u32>,
         uid: Option<u32>,
@@ -233,12 +366,18 @@ impl Filesystem for AgentFSFuse {
             size
         );
 
+        // The filesystem id cannot be changed
+        if ino == FS_ID_INO {
+            reply.error(libc::EPERM);
+            return;
+        }
+
         // Handle chmod
         if let Some(new_mode) = mode {
             let fs = self.fs.clone();
-            let result = self
-                .runtime
-                .block_on(async move { fs.chmod(ino as i64, new_mode).await });
+            let result = self.block_on(req.interrupted(), async move {
+                fs.chmod(ino as i64, new_mode).await
+            });
 
             if let Err(e) = result {
                 reply.error(error_to_errno(&e));
@@ -249,9 +388,9 @@ impl Filesystem for AgentFSFuse {
         // Handle chown
         if uid.is_some() || gid.is_some() {
             let fs = self.fs.clone();
-            let result = self
-                .runtime
-                .block_on(async move { fs.chown(ino as i64, uid, gid).await });
+            let result = self.block_on(req.interrupted(), async move {
+                fs.chown(ino as i64, uid, gid).await
+            });
 
             if let Err(e) = result {
                 reply.error(error_to_errno(&e));
@@ -269,8 +408,10 @@ impl Filesystem for AgentFSFuse {
                 };
 
                 if let Some(file) = file {
-                    self.runtime
-                        .block_on(async move { file.truncate(new_size).await })
+                    self.block_on(
+                        req.interrupted(),
+                        async move { file.truncate(new_size).await },
+                    )
                 } else {
                     reply.error(libc::EBADF);
                     return;
@@ -278,7 +419,7 @@ impl Filesystem for AgentFSFuse {
             } else {
                 // Open file and truncate via file handle
                 let fs = self.fs.clone();
-                self.runtime.block_on(async move {
+                self.block_on(req.interrupted(), async move {
                     let file = fs.open(ino as i64, libc::O_RDWR).await?;
                     file.truncate(new_size).await
                 })
@@ -309,9 +450,9 @@ impl Filesystem for AgentFSFuse {
                 None => TimeChange::Omit,
             };
             let fs = self.fs.clone();
-            let result = self
-                .runtime
-                .block_on(async move { fs.utimens(ino as i64, new_atime, new_mtime).await });
+            let result = self.block_on(req.interrupted(), async move {
+                fs.utimens(ino as i64, new_atime, new_mtime).await
+            });
             if let Err(e) = result {
                 reply.error(error_to_errno(&e));
                 return;
@@ -319,12 +460,7 @@ impl Filesystem for AgentFSFuse {
         }
 
         // Return updated attributes
-        let fs = self.fs.clone();
-        let result = self
-            .runtime
-            .block_on(async move { fs.getattr(ino as i64).await });
-
-        match result {
+        match self.getattr_with_handle(req.interrupted(), ino, fh) {
             Ok(Some(stats)) => reply.attr(&TTL, &fillattr(&stats)),
             Ok(None) => reply.error(libc::ENOENT),
             Err(e) => reply.error(error_to_errno(&e)),
@@ -335,6 +471,19 @@ impl Filesystem for AgentFSFuse {
     // Directory Operations
     // ─────────────────────────────────────────────────────────────
 
+    /// Opens a directory for reading.
+    ///
+    /// Only called by kernels that do not honor `FUSE_NO_OPENDIR_SUPPORT`.
+    /// Opening anything other than a directory fails with ENOTDIR, so listing
+    /// a file never produces empty or bogus results.
+    fn opendir(&mut self, req: &Request, ino: u64, _flags: i32, reply: ReplyOpen) {
+        tracing::debug!("FUSE::opendir: ino={}", ino);
+        match self.check_directory(req.interrupted(), ino) {
+            Ok(()) => reply.opened(0, 0),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
     /// Reads directory entries for the given inode.
     ///
     /// Returns "." and ".." entries followed by the directory contents.
@@ -344,7 +493,7 @@ impl Filesystem for AgentFSFuse {
     /// avoiding N+1 database queries.
     fn readdir(
         &mut self,
-        _req: &Request,
+        req: &Request,
         ino: u64,
         _fh: u64,
         offset: i64,
@@ -353,9 +502,9 @@ impl Filesystem for AgentFSFuse {
         tracing::debug!("FUSE::readdir: ino={}, offset={}", ino, offset);
 
         let fs = self.fs.clone();
-        let entries_result = self
-            .runtime
-            .block_on(async move { fs.readdir_plus(ino as i64).await });
+        let entries_result = self.block_on(req.interrupted(), async move {
+            fs.readdir_plus(ino as i64).await
+        });
 
         let entries = match entries_result {
             Ok(Some(entries)) => entries,
@@ -409,7 +558,7 @@ impl Filesystem for AgentFSFuse {
     /// Uses readdir_plus to fetch entries with stats in a single database query.
     fn readdirplus(
         &mut self,
-        _req: &Request,
+        req: &Request,
         ino: u64,
         _fh: u64,
         offset: i64,
@@ -418,9 +567,9 @@ impl Filesystem for AgentFSFuse {
         tracing::debug!("FUSE::readdirplus: ino={}, offset={}", ino, offset);
 
         let fs = self.fs.clone();
-        let entries_result = self
-            .runtime
-            .block_on(async move { fs.readdir_plus(ino as i64).await });
+        let entries_result = self.block_on(req.interrupted(), async move {
+            fs.readdir_plus(ino as i64).await
+        });
 
         let entries = match entries_result {
             Ok(Some(entries)) => entries,
@@ -437,8 +586,10 @@ impl Filesystem for AgentFSFuse {
         // Get current directory stats for "."
         let fs = self.fs.clone();
         let dir_stats = self
-            .runtime
-            .block_on(async move { fs.getattr(ino as i64).await })
+            .block_on(
+                req.interrupted(),
+                async move { fs.getattr(ino as i64).await },
+            )
             .ok()
             .flatten();
 
@@ -451,8 +602,7 @@ impl Filesystem for AgentFSFuse {
             // Use root inode as fallback for parent
             let fs = self.fs.clone();
             let parent_stats = self
-                .runtime
-                .block_on(async move { fs.getattr(1).await })
+                .block_on(req.interrupted(), async move { fs.getattr(1).await })
                 .ok()
                 .flatten();
             (1u64, parent_stats)
@@ -465,7 +615,7 @@ impl Filesystem for AgentFSFuse {
         if offset <= offset_counter {
             if let Some(ref stats) = dir_stats {
                 let attr = fillattr(stats);
-                if reply.add(ino, offset_counter + 1, ".", &TTL, &attr, 0) {
+                if reply.add(ino, offset_counter + 1, ".", &TTL, &attr, stats.generation) {
                     reply.ok();
                     return;
                 }
@@ -477,7 +627,14 @@ impl Filesystem for AgentFSFuse {
         if offset <= offset_counter {
             if let Some(ref stats) = parent_stats {
                 let attr = fillattr(stats);
-                if reply.add(parent_ino, offset_counter + 1, "..", &TTL, &attr, 0) {
+                if reply.add(
+                    parent_ino,
+                    offset_counter + 1,
+                    "..",
+                    &TTL,
+                    &attr,
+                    stats.generation,
+                ) {
                     reply.ok();
                     return;
                 }
@@ -496,11 +653,13 @@ impl Filesystem for AgentFSFuse {
                     &entry.name,
                     &TTL,
                     &attr,
-                    0,
+                    entry.stats.generation,
                 ) {
                     reply.ok();
                     return;
                 }
+                // Unlike "." and "..", every entry returned counts as a lookup
+                self.remember(entry.stats.ino as u64);
             }
             offset_counter += 1;
         }
@@ -530,6 +689,11 @@ impl Filesystem for AgentFSFuse {
             rdev
         );
 
+        if is_fs_id_file(parent, name) {
+            reply.error(libc::EPERM);
+            return;
+        }
+
         let Some(name_str) = name.to_str() else {
             reply.error(libc::EINVAL);
             return;
@@ -539,7 +703,7 @@ impl Filesystem for AgentFSFuse {
         let gid = req.gid();
         let fs = self.fs.clone();
         let name_owned = name_str.to_string();
-        let result = self.runtime.block_on(async move {
+        let result = self.block_on(req.interrupted(), async move {
             fs.mknod(parent as i64, &name_owned, mode, rdev as u64, uid, gid)
                 .await
         });
@@ -547,7 +711,8 @@ impl Filesystem for AgentFSFuse {
         match result {
             Ok(stats) => {
                 let attr = fillattr(&stats);
-                reply.entry(&TTL, &attr, 0);
+                self.remember(stats.ino as u64);
+                reply.entry(&TTL, &attr, stats.generation);
             }
             Err(e) => {
                 reply.error(error_to_errno(&e));
@@ -575,6 +740,11 @@ impl Filesystem for AgentFSFuse {
             mode
         );
 
+        if is_fs_id_file(parent, name) {
+            reply.error(libc::EPERM);
+            return;
+        }
+
         let Some(name_str) = name.to_str() else {
             reply.error(libc::EINVAL);
             return;
@@ -584,14 +754,15 @@ impl Filesystem for AgentFSFuse {
         let gid = req.gid();
         let fs = self.fs.clone();
         let name_owned = name_str.to_string();
-        let result = self
-            .runtime
-            .block_on(async move { fs.mkdir(parent as i64, &name_owned, mode, uid, gid).await });
+        let result = self.block_on(req.interrupted(), async move {
+            fs.mkdir(parent as i64, &name_owned, mode, uid, gid).await
+        });
 
         match result {
             Ok(stats) => {
                 let attr = fillattr(&stats);
-                reply.entry(&TTL, &attr, 0);
+                self.remember(stats.ino as u64);
+                reply.entry(&TTL, &attr, stats.generation);
             }
             Err(e) => {
                 reply.error(error_to_errno(&e));
@@ -606,6 +777,11 @@ impl Filesystem for AgentFSFuse {
     fn rmdir(&mut self, req: &Request, parent: u64, name: &OsStr, reply: ReplyEmpty) {
         tracing::debug!("FUSE::rmdir: parent={}, name={:?}", parent, name);
 
+        if is_fs_id_file(parent, name) {
+            reply.error(libc::EPERM);
+            return;
+        }
+
         let Some(name_str) = name.to_str() else {
             reply.error(libc::EINVAL);
             return;
@@ -613,9 +789,9 @@ impl Filesystem for AgentFSFuse {
 
         let fs = self.fs.clone();
         let name_owned = name_str.to_string();
-        let result = self
-            .runtime
-            .block_on(async move { fs.rmdir(parent as i64, &name_owned).await });
+        let result = self.block_on(req.interrupted(), async move {
+            fs.rmdir(parent as i64, &name_owned).await
+        });
 
         match result {
             Ok(()) => {
@@ -651,6 +827,11 @@ impl Filesystem for AgentFSFuse {
             mode
         );
 
+        if is_fs_id_file(parent, name) {
+            reply.error(libc::EPERM);
+            return;
+        }
+
         let Some(name_str) = name.to_str() else {
             reply.error(libc::EINVAL);
             return;
@@ -661,7 +842,7 @@ impl Filesystem for AgentFSFuse {
         let gid = req.gid();
         let fs = self.fs.clone();
         let name_owned = name_str.to_string();
-        let result = self.runtime.block_on(async move {
+        let result = self.block_on(req.interrupted(), async move {
             fs.create_file(parent as i64, &name_owned, mode, uid, gid)
                 .await
         });
@@ -673,7 +854,8 @@ impl Filesystem for AgentFSFuse {
                 let fh = self.alloc_fh();
                 self.open_files.lock().insert(fh, OpenFile { file });
 
-                reply.created(&TTL, &attr, 0, fh, 0);
+                self.remember(stats.ino as u64);
+                reply.created(&TTL, &attr, stats.generation, fh, 0);
             }
             Err(e) => {
                 reply.error(error_to_errno(&e));
@@ -699,6 +881,11 @@ impl Filesystem for AgentFSFuse {
             target
         );
 
+        if is_fs_id_file(parent, link_name) {
+            reply.error(libc::EPERM);
+            return;
+        }
+
         let Some(name_str) = link_name.to_str() else {
             reply.error(libc::EINVAL);
             return;
@@ -714,7 +901,7 @@ impl Filesystem for AgentFSFuse {
         let fs = self.fs.clone();
         let name_owned = name_str.to_string();
         let target_owned = target_str.to_string();
-        let result = self.runtime.block_on(async move {
+        let result = self.block_on(req.interrupted(), async move {
             fs.symlink(parent as i64, &name_owned, &target_owned, uid, gid)
                 .await
         });
@@ -722,7 +909,8 @@ impl Filesystem for AgentFSFuse {
         match result {
             Ok(stats) => {
                 let attr = fillattr(&stats);
-                reply.entry(&TTL, &attr, 0);
+                self.remember(stats.ino as u64);
+                reply.entry(&TTL, &attr, stats.generation);
             }
             Err(e) => {
                 reply.error(error_to_errno(&e));
@@ -736,7 +924,7 @@ impl Filesystem for AgentFSFuse {
     /// same inode as `ino`. The link count of the inode is incremented.
     fn link(
         &mut self,
-        _req: &Request,
+        req: &Request,
         ino: u64,
         newparent: u64,
         newname: &OsStr,
@@ -749,6 +937,11 @@ impl Filesystem for AgentFSFuse {
             newname
         );
 
+        if ino == FS_ID_INO || is_fs_id_file(newparent, newname) {
+            reply.error(libc::EPERM);
+            return;
+        }
+
         let Some(name_str) = newname.to_str() else {
             reply.error(libc::EINVAL);
             return;
@@ -756,14 +949,15 @@ impl Filesystem for AgentFSFuse {
 
         let fs = self.fs.clone();
         let name_owned = name_str.to_string();
-        let result = self
-            .runtime
-            .block_on(async move { fs.link(ino as i64, newparent as i64, &name_owned).await });
+        let result = self.block_on(req.interrupted(), async move {
+            fs.link(ino as i64, newparent as i64, &name_owned).await
+        });
 
         match result {
             Ok(stats) => {
                 let attr = fillattr(&stats);
-                reply.entry(&TTL, &attr, 0);
+                self.remember(stats.ino as u64);
+                reply.entry(&TTL, &attr, stats.generation);
             }
             Err(e) => {
                 reply.error(error_to_errno(&e));
@@ -777,18 +971,17 @@ impl Filesystem for AgentFSFuse {
     fn unlink(&mut self, req: &Request, parent: u64, name: &OsStr, reply: ReplyEmpty) {
         tracing::debug!("FUSE::unlink: parent={}, name={:?}", parent, name);
 
+        if is_fs_id_file(parent, name) {
+            reply.error(libc::EPERM);
+            return;
+        }
+
         let Some(name_str) = name.to_str() else {
             reply.error(libc::EINVAL);
             return;
         };
 
-        let fs = self.fs.clone();
-        let name_owned = name_str.to_string();
-        let result = self
-            .runtime
-            .block_on(async move { fs.unlink(parent as i64, &name_owned).await });
-
-        match result {
+        match self.unlink_entry(req.interrupted(), parent, name_str) {
             Ok(()) => {
                 reply.ok();
                 req.deferred_notifier().inval_entry(parent, name);
@@ -799,7 +992,8 @@ impl Filesystem for AgentFSFuse {
 
     /// Renames a file or directory.
     ///
-    /// Moves `name` from `parent` to `newname` under `newparent`.
+    /// Moves `name` from `parent` to `newname` under `newparent`. `flags`
+    /// carries the `renameat2` flags (`RENAME_NOREPLACE`, `RENAME_EXCHANGE`).
     fn rename(
         &mut self,
         req: &Request,
@@ -807,17 +1001,23 @@ impl Filesystem for AgentFSFuse {
         name: &OsStr,
         newparent: u64,
         newname: &OsStr,
-        _flags: u32,
+        flags: u32,
         reply: ReplyEmpty,
     ) {
         tracing::debug!(
-            "FUSE::rename: parent={}, name={:?}, newparent={}, newname={:?}",
+            "FUSE::rename: parent={}, name={:?}, newparent={}, newname={:?}, flags={:#x}",
             parent,
             name,
             newparent,
-            newname
+            newname,
+            flags
         );
 
+        if is_fs_id_file(parent, name) || is_fs_id_file(newparent, newname) {
+            reply.error(libc::EPERM);
+            return;
+        }
+
         let Some(old_name_str) = name.to_str() else {
             reply.error(libc::EINVAL);
             return;
@@ -828,18 +1028,14 @@ impl Filesystem for AgentFSFuse {
             return;
         };
 
-        let fs = self.fs.clone();
-        let old_name_owned = old_name_str.to_string();
-        let new_name_owned = new_name_str.to_string();
-        let result = self.runtime.block_on(async move {
-            fs.rename(
-                parent as i64,
-                &old_name_owned,
-                newparent as i64,
-                &new_name_owned,
-            )
-            .await
-        });
+        let result = self.rename_entry(
+            req.interrupted(),
+            parent,
+            old_name_str,
+            newparent,
+            new_name_str,
+            flags,
+        );
 
         match result {
             Ok(()) => {
@@ -859,13 +1055,24 @@ impl Filesystem for AgentFSFuse {
     /// Opens a file for reading or writing.
     ///
     /// Allocates a file handle and opens the file in the filesystem layer.
-    fn open(&mut self, _req: &Request, ino: u64, flags: i32, reply: ReplyOpen) {
+    fn open(&mut self, req: &Request, ino: u64, flags: i32, reply: ReplyOpen) {
         tracing::debug!("FUSE::open: ino={}, flags={}", ino, flags);
 
+        // The filesystem id is read afresh on every read, so it bypasses the
+        // page cache and needs no handle
+        if ino == FS_ID_INO {
+            if flags & libc::O_ACCMODE != libc::O_RDONLY {
+                reply.error(libc::EACCES);
+            } else {
+                reply.opened(0, FOPEN_DIRECT_IO);
+            }
+            return;
+        }
+
         let fs = self.fs.clone();
-        let result = self
-            .runtime
-            .block_on(async move { fs.open(ino as i64, flags).await });
+        let result = self.block_on(req.interrupted(), async move {
+            fs.open(ino as i64, flags).await
+        });
 
         match result {
             Ok(file) => {
@@ -880,8 +1087,8 @@ impl Filesystem for AgentFSFuse {
     /// Reads data using the file handle.
     fn read(
         &mut self,
-        _req: &Request,
-        _ino: u64,
+        req: &Request,
+        ino: u64,
         fh: u64,
         offset: i64,
         size: u32,
@@ -890,6 +1097,18 @@ impl Filesystem for AgentFSFuse {
         reply: ReplyData,
     ) {
         tracing::debug!("FUSE::read: fh={}, offset={}, size={}", fh, offset, size);
+        if ino == FS_ID_INO {
+            match self.fs_id_file(req.interrupted()) {
+                Ok((_, contents)) => {
+                    let start = (offset as usize).min(contents.len());
+                    let end = start.saturating_add(size as usize).min(contents.len());
+                    reply.data(&contents.as_bytes()[start..end]);
+                }
+                Err(e) => reply.error(error_to_errno(&e)),
+            }
+            return;
+        }
+
         let file = {
             let open_files = self.open_files.lock();
             let Some(open_file) = open_files.get(&fh) else {
@@ -899,9 +1118,9 @@ impl Filesystem for AgentFSFuse {
             open_file.file.clone()
         };
 
-        let result = self
-            .runtime
-            .block_on(async move { file.pread(offset as u64, size as u64).await });
+        let result = self.block_on(req.interrupted(), async move {
+            file.pread(offset as u64, size as u64).await
+        });
 
         match result {
             Ok(data) => reply.data(&data),
@@ -912,8 +1131,8 @@ impl Filesystem for AgentFSFuse {
     /// Writes data using the file handle.
     fn write(
         &mut self,
-        _req: &Request,
-        _ino: u64,
+        req: &Request,
+        ino: u64,
         fh: u64,
         offset: i64,
         data: &[u8],
@@ -939,26 +1158,45 @@ impl Filesystem for AgentFSFuse {
 
         let data_len = data.len();
         let data_vec = data.to_vec();
-        let result = self
-            .runtime
-            .block_on(async move { file.pwrite(offset as u64, &data_vec).await });
+        let result = self.block_on(req.interrupted(), async move {
+            file.pwrite(offset as u64, &data_vec).await
+        });
 
         match result {
-            Ok(()) => reply.written(data_len as u32),
+            Ok(()) => {
+                self.wake_pollers(req.deferred_notifier(), ino);
+                reply.written(data_len as u32)
+            }
             Err(e) => reply.error(error_to_errno(&e)),
         }
     }
 
-    /// Flushes data to the backend storage.
+    /// Flushes buffered writes when a file descriptor is closed.
     ///
-    /// Since writes go directly to the database, this is a no-op.
-    fn flush(&mut self, _req: &Request, _ino: u64, fh: u64, _lock_owner: u64, reply: ReplyEmpty) {
+    /// Called on every `close(2)`. Errors returned here are reported by
+    /// `close(2)`; durability is left to `fsync`.
+    fn flush(&mut self, req: &Request, ino: u64, fh: u64, _lock_owner: u64, reply: ReplyEmpty) {
         tracing::debug!("FUSE::flush: fh={}", fh);
-        let open_files = self.open_files.lock();
-        if open_files.contains_key(&fh) {
+        if ino == FS_ID_INO {
             reply.ok();
-        } else {
-            reply.error(libc::EBADF);
+            return;
+        }
+        let file = {
+            let open_files = self.open_files.lock();
+            match open_files.get(&fh) {
+                Some(open_file) => open_file.file.clone(),
+                None => {
+                    reply.error(libc::EBADF);
+                    return;
+                }
+            }
+        };
+
+        let result = self.block_on(req.interrupted(), async move { file.flush().await });
+
+        match result {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(error_to_errno(&e)),
         }
     }
 
@@ -966,7 +1204,8 @@ impl Filesystem for AgentFSFuse {
     ///
     /// This now uses the file handle's fsync which knows which layer(s) the
     /// file exists in, avoiding errors when a file only exists in one layer.
-    fn fsync(&mut self, _req: &Request, _ino: u64, fh: u64, _datasync: bool, reply: ReplyEmpty) {
+    /// With `datasync` set (`fdatasync(2)`), only file data is synchronized.
+    fn fsync(&mut self, req: &Request, _ino: u64, fh: u64, datasync: bool, reply: ReplyEmpty) {
         tracing::debug!("FUSE::fsync: fh={}", fh);
         let file = {
             let open_files = self.open_files.lock();
@@ -979,7 +1218,13 @@ impl Filesystem for AgentFSFuse {
             }
         };
 
-        let result = self.runtime.block_on(async move { file.fsync().await });
+        let result = self.block_on(req.interrupted(), async move {
+            if datasync {
+                file.fdatasync().await
+            } else {
+                file.fsync().await
+            }
+        });
 
         match result {
             Ok(()) => reply.ok(),
@@ -1003,20 +1248,56 @@ impl Filesystem for AgentFSFuse {
     ) {
         tracing::debug!("FUSE::release: fh={}", fh);
         self.open_files.lock().remove(&fh);
+        self.pollers.lock().remove(&fh);
         reply.ok();
     }
 
+    /// Reports I/O readiness, registering for a wakeup if asked to.
+    ///
+    /// Files are always ready for reading and writing, as regular files are
+    /// on any local filesystem. When the kernel asks to be notified (e.g. for
+    /// edge-triggered epoll), the poll handle is kept until the next write to
+    /// the inode wakes it. Files whose readiness changes over time build on
+    /// the same registration.
+    fn poll(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        fh: u64,
+        ph: PollHandle,
+        events: u32,
+        flags: u32,
+        reply: ReplyPoll,
+    ) {
+        tracing::debug!(
+            "FUSE::poll: ino={}, fh={}, events={:#x}, flags={:#x}",
+            ino,
+            fh,
+            events,
+            flags
+        );
+        if !self.open_files.lock().contains_key(&fh) {
+            reply.error(libc::EBADF);
+            return;
+        }
+        if flags & FUSE_POLL_SCHEDULE_NOTIFY != 0 {
+            self.register_poll(fh, ino, ph.into());
+        }
+        let ready = (libc::POLLIN | libc::POLLOUT | libc::POLLRDNORM | libc::POLLWRNORM) as u32;
+        reply.poll(events & ready);
+    }
+
     /// Returns filesystem statistics.
     ///
     /// Queries actual usage from the SDK and reports it to tools like `df`.
-    fn statfs(&mut self, _req: &Request, _ino: u64, reply: ReplyStatfs) {
+    fn statfs(&mut self, req: &Request, _ino: u64, reply: ReplyStatfs) {
         tracing::debug!("FUSE::statfs");
         const BLOCK_SIZE: u64 = 4096;
         const TOTAL_INODES: u64 = 1_000_000; // Virtual limit
         const MAX_NAMELEN: u32 = 255;
 
         let fs = self.fs.clone();
-        let result = self.runtime.block_on(async move { fs.statfs().await });
+        let result = self.block_on(req.interrupted(), async move { fs.statfs().await });
 
         let (used_blocks, used_inodes) = match result {
             Ok(stats) => {
@@ -1052,12 +1333,18 @@ impl Filesystem for AgentFSFuse {
     /// Called when the kernel removes an inode from its cache. For passthrough
     /// filesystems (like HostFS), this allows releasing O_PATH file descriptors
     /// that were cached for the inode, preventing file descriptor exhaustion.
+    /// A file unlinked while in use is released once its last lookup is
+    /// forgotten.
     fn forget(&mut self, _req: &Request, ino: u64, nlookup: u64) {
         tracing::debug!("FUSE::forget: ino={}, nlookup={}", ino, nlookup);
+        if ino == FS_ID_INO {
+            return;
+        }
         let fs = self.fs.clone();
         self.runtime.block_on(async move {
             fs.forget(ino as i64, nlookup).await;
         });
+        self.forget_lookups(ino, nlookup);
     }
 
     /// Batch forget multiple inodes at once.
@@ -1066,13 +1353,28 @@ impl Filesystem for AgentFSFuse {
     fn batch_forget(&mut self, _req: &Request, nodes: &[fuse_forget_one]) {
         tracing::debug!("FUSE::batch_forget: {} nodes", nodes.len());
         let fs = self.fs.clone();
-        let nodes_vec: Vec<(i64, u64)> =
-            nodes.iter().map(|n| (n.nodeid as i64, n.nlookup)).collect();
+        let nodes_vec: Vec<(i64, u64)> = nodes
+            .iter()
+            .filter(|n| n.nodeid != FS_ID_INO)
+            .map(|n| (n.nodeid as i64, n.nlookup))
+            .collect();
         self.runtime.block_on(async move {
             for (ino, nlookup) in nodes_vec {
                 fs.forget(ino, nlookup).await;
             }
         });
+        for node in nodes {
+            self.forget_lookups(node.nodeid, node.nlookup);
+        }
+    }
+
+    /// Release the files unlinked while in use that are still kept, as the
+    /// kernel forgets nothing once the session ends.
+    fn destroy(&mut self) {
+        let orphans: Vec<u64> = self.orphans.lock().drain().collect();
+        for ino in orphans {
+            self.release_orphan(ino);
+        }
     }
 }
 
@@ -1086,7 +1388,259 @@ impl AgentFSFuse {
             fs,
             runtime,
             open_files: Arc::new(Mutex::new(HashMap::new())),
+            pollers: Mutex::new(HashMap::new()),
             next_fh: AtomicU64::new(1),
+            op_timeout: None,
+            max_write: None,
+            lookups: Arc::new(Mutex::new(HashMap::new())),
+            orphans: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Count a lookup of `ino` handed to the kernel in an entry reply.
+    fn remember(&self, ino: u64) {
+        *self.lookups.lock().entry(ino).or_insert(0) += 1;
+    }
+
+    /// Drop `nlookup` of the kernel's lookups of `ino`, releasing the inode
+    /// if it was unlinked and this was the last of them.
+    fn forget_lookups(&self, ino: u64, nlookup: u64) {
+        let forgotten = {
+            let mut lookups = self.lookups.lock();
+            match lookups.get_mut(&ino) {
+                Some(count) if *count > nlookup => {
+                    *count -= nlookup;
+                    false
+                }
+                Some(_) => {
+                    lookups.remove(&ino);
+                    true
+                }
+                None => false,
+            }
+        };
+        if forgotten && self.orphans.lock().remove(&ino) {
+            self.release_orphan(ino);
+        }
+    }
+
+    /// Remove `name` from `parent`, keeping its inode while the kernel holds
+    /// lookups of it.
+    ///
+    /// A file unlinked while open stays readable and writable through its
+    /// handles, as POSIX requires: the kernel only forgets the inode once it
+    /// is closed, and the inode is released then.
+    fn unlink_entry(
+        &self,
+        interrupted: &CancellationToken,
+        parent: u64,
+        name: &str,
+    ) -> Result<(), SdkError> {
+        let fs = self.fs.clone();
+        let name = name.to_string();
+        let orphan = self.block_on(interrupted, async move {
+            fs.unlink_keep_orphan(parent as i64, &name).await
+        })?;
+        if let Some(ino) = orphan {
+            self.keep_orphan(ino as u64);
+        }
+        Ok(())
+    }
+
+    /// Rename `name` in `parent` to `newname` in `newparent`, keeping the
+    /// inode it replaces while the kernel holds lookups of it, as
+    /// `unlink_entry()` does.
+    fn rename_entry(
+        &self,
+        interrupted: &CancellationToken,
+        parent: u64,
+        name: &str,
+        newparent: u64,
+        newname: &str,
+        flags: u32,
+    ) -> Result<(), SdkError> {
+        let fs = self.fs.clone();
+        let name = name.to_string();
+        let newname = newname.to_string();
+        let orphan = self.block_on(interrupted, async move {
+            fs.rename_keep_orphan(parent as i64, &name, newparent as i64, &newname, flags)
+                .await
+        })?;
+        if let Some(ino) = orphan {
+            self.keep_orphan(ino as u64);
+        }
+        Ok(())
+    }
+
+    /// Keep an unlinked inode until the kernel forgets it, or release it
+    /// now if the kernel holds no lookups of it.
+    fn keep_orphan(&self, ino: u64) {
+        if self.lookups.lock().contains_key(&ino) {
+            self.orphans.lock().insert(ino);
+        } else {
+            self.release_orphan(ino);
+        }
+    }
+
+    /// Delete an unlinked inode that nothing refers to any longer.
+    fn release_orphan(&self, ino: u64) {
+        let fs = self.fs.clone();
+        let result = self
+            .runtime
+            .block_on(async move { fs.remove_orphan(ino as i64).await });
+        if let Err(e) = result {
+            tracing::warn!("Failed to release unlinked inode {}: {}", ino, e);
+        }
+    }
+
+    /// Remember a poll handle to wake on the next change to `ino`.
+    ///
+    /// A file handle holds at most one registration; the kernel reuses the
+    /// same poll handle for every poll of an open file.
+    fn register_poll(&self, fh: u64, ino: u64, kh: u64) {
+        self.pollers.lock().insert(fh, PollWaiter { ino, kh });
+    }
+
+    /// Wake every poll waiting on `ino`, returning how many were woken.
+    ///
+    /// Registrations are one-shot: after a wakeup the kernel polls again and
+    /// registers anew if it still wants to be notified.
+    fn wake_pollers(&self, notifier: &DeferredNotifier, ino: u64) -> usize {
+        let mut woken = 0;
+        self.pollers.lock().retain(|_, waiter| {
+            if waiter.ino != ino {
+                return true;
+            }
+            notifier.notify_poll(waiter.kh);
+            woken += 1;
+            false
+        });
+        woken
+    }
+
+    /// Expose the open files and lookups of this filesystem through
+    /// `control`.
+    fn with_control(self, control: &FuseControl) -> Self {
+        let _ = control.open_files.set(self.open_files.clone());
+        let _ = control.lookups.set(self.lookups.clone());
+        let _ = control.orphans.set(self.orphans.clone());
+        self
+    }
+
+    /// Accept writes of up to `max_write` bytes per request.
+    fn with_max_write(mut self, max_write: Option<u32>) -> Self {
+        self.max_write = max_write;
+        self
+    }
+
+    /// Bound every SDK call made by an upcall to `timeout`.
+    fn with_op_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.op_timeout = timeout;
+        self
+    }
+
+    /// Run an SDK call to completion on the runtime.
+    ///
+    /// If the call does not finish within the per-operation timeout it is
+    /// dropped and an I/O error is returned, so a stalled backend (e.g. a
+    /// hung sync) surfaces as EIO rather than freezing every process that
+    /// touches the mount.
+    ///
+    /// If the kernel interrupts the request first (the caller was signalled,
+    /// e.g. killed with Ctrl-C), the call is dropped as well, releasing
+    /// whatever it holds, and EINTR is returned.
+    fn block_on<T>(
+        &self,
+        interrupted: &CancellationToken,
+        fut: impl std::future::Future<Output = Result<T, SdkError>>,
+    ) -> Result<T, SdkError> {
+        let op_timeout = self.op_timeout;
+        let op = async move {
+            let Some(timeout) = op_timeout else {
+                return fut.await;
+            };
+            tokio::time::timeout(timeout, fut)
+                .await
+                .unwrap_or_else(|_| {
+                    tracing::warn!("FUSE operation timed out after {:?}", timeout);
+                    Err(SdkError::Io(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        format!("operation timed out after {:?}", timeout),
+                    )))
+                })
+        };
+        self.runtime.block_on(async move {
+            tokio::select! {
+                // An interrupt that arrived before dispatch skips the call
+                biased;
+                _ = interrupted.cancelled() => {
+                    tracing::debug!("FUSE operation interrupted");
+                    Err(SdkError::Io(std::io::Error::from_raw_os_error(libc::EINTR)))
+                }
+                result = op => result,
+            }
+        })
+    }
+
+    /// Get attributes via the open file handle if there is one, falling back
+    /// to an inode lookup otherwise.
+    ///
+    /// The open file knows the authoritative size, and stat'ing it directly
+    /// avoids re-resolving the inode in the filesystem layer.
+    fn getattr_with_handle(
+        &self,
+        interrupted: &CancellationToken,
+        ino: u64,
+        fh: Option<u64>,
+    ) -> Result<Option<Stats>, SdkError> {
+        let file = fh.and_then(|fh| {
+            let open_files = self.open_files.lock();
+            open_files.get(&fh).map(|open_file| open_file.file.clone())
+        });
+
+        match file {
+            Some(file) => self
+                .block_on(interrupted, async move { file.fstat().await })
+                .map(Some),
+            None => {
+                let fs = self.fs.clone();
+                self.block_on(interrupted, async move { fs.getattr(ino as i64).await })
+            }
+        }
+    }
+
+    /// Get the attributes and contents of the `FS_ID_FILE`.
+    ///
+    /// The id is that of the filesystem currently served, read from the root
+    /// so that it follows `swap_fs`. The file takes its ownership and times
+    /// from the root and is read-only.
+    fn fs_id_file(&self, interrupted: &CancellationToken) -> Result<(Stats, String), SdkError> {
+        let fs = self.fs.clone();
+        let root = self
+            .block_on(
+                interrupted,
+                async move { fs.getattr(FUSE_ROOT_ID as i64).await },
+            )?
+            .ok_or(FsError::NotFound)?;
+        let contents = format!("{:016x}\n", root.generation);
+        let stats = Stats {
+            ino: FS_ID_INO as i64,
+            mode: S_IFREG | 0o444,
+            nlink: 1,
+            size: contents.len() as i64,
+            rdev: 0,
+            ..root
+        };
+        Ok((stats, contents))
+    }
+
+    /// Check that `ino` is a directory, or get the errno to fail with.
+    fn check_directory(&self, interrupted: &CancellationToken, ino: u64) -> Result<(), i32> {
+        match self.getattr_with_handle(interrupted, ino, None) {
+            Ok(Some(stats)) if stats.is_directory() => Ok(()),
+            Ok(Some(_)) => Err(libc::ENOTDIR),
+            Ok(None) => Err(libc::ENOENT),
+            Err(e) => Err(error_to_errno(&e)),
         }
     }
 
@@ -1135,7 +1689,7 @@ fn fillattr(stats: &Stats) -> FileAttr {
         atime: UNIX_EPOCH + Duration::new(stats.atime as u64, stats.atime_nsec),
         mtime: UNIX_EPOCH + Duration::new(stats.mtime as u64, stats.mtime_nsec),
         ctime: UNIX_EPOCH + Duration::new(stats.ctime as u64, stats.ctime_nsec),
-        crtime: UNIX_EPOCH,
+        crtime: UNIX_EPOCH + Duration::new(stats.btime as u64, stats.btime_nsec),
         kind,
         perm: (stats.mode & 0o7777) as u16,
         nlink: stats.nlink,
@@ -1178,19 +1732,51 @@ pub fn mount(
     fs: Arc<dyn FileSystem>,
     opts: FuseMountOptions,
     runtime: Runtime,
+) -> anyhow::Result<()> {
+    mount_with_control(fs, opts, runtime, &FuseControl::default())
+}
+
+/// Like `mount()`, exposing the running session through `control`.
+pub(crate) fn mount_with_control(
+    fs: Arc<dyn FileSystem>,
+    opts: FuseMountOptions,
+    runtime: Runtime,
+    control: &FuseControl,
 ) -> anyhow::Result<()> {
     // Raise fd limit to hard limit to prevent "too many open files" errors
     // when passthrough filesystems cache O_PATH file descriptors
     maximize_fd_limit();
 
-    let fs = AgentFSFuse::new(fs, runtime);
+    let mount_opts = mount_options(&opts)?;
+    let fs = AgentFSFuse::new(fs, runtime)
+        .with_op_timeout(opts.op_timeout)
+        .with_max_write(opts.max_write)
+        .with_control(control);
+
+    crate::fuser::check_option_conflicts(&mount_opts)?;
+    let mut session = crate::fuser::Session::new(fs, &opts.mountpoint, &mount_opts)?;
+    let _ = control.notifier.set(session.deferred_notifier());
+    let _ = control.in_flight.set(session.in_flight());
+    session.run()?;
+
+    Ok(())
+}
+
+/// Build the FUSE mount option list, rejecting names that would corrupt it.
+fn mount_options(opts: &FuseMountOptions) -> anyhow::Result<Vec<MountOption>> {
+    crate::mount::validate_fsname(&opts.fsname)?;
 
     let mut mount_opts = vec![
-        MountOption::FSName(opts.fsname),
+        MountOption::FSName(opts.fsname.clone()),
         // Enable kernel-level permission checking based on file mode/uid/gid
         MountOption::DefaultPermissions,
     ];
 
+    if let Some(subtype) = &opts.subtype {
+        crate::mount::validate_subtype(subtype)?;
+        mount_opts.push(MountOption::Subtype(subtype.clone()));
+    }
+
     // Allow users other than the one who mounted the filesystem to access it.
     // This requires either running as root or having user_allow_other enabled
     // in /etc/fuse.conf.
@@ -1210,8 +1796,679 @@ pub fn mount(
     if opts.allow_root {
         mount_opts.push(MountOption::AllowRoot);
     }
+    if opts.read_only {
+        mount_opts.push(MountOption::RO);
+    }
+    if let Some(max_read) = opts.max_read {
+        crate::mount::validate_fuse_io_size("max_read", max_read)?;
+        mount_opts.push(MountOption::CUSTOM(format!("max_read={}", max_read)));
+    }
+    if let Some(max_write) = opts.max_write {
+        crate::mount::validate_fuse_io_size("max_write", max_write)?;
+    }
 
-    crate::fuser::mount2(fs, &opts.mountpoint, &mount_opts)?;
+    for option in &opts.extra_options {
+        crate::mount::validate_extra_option(option)?;
+        mount_opts.push(MountOption::from_str(option));
+    }
 
-    Ok(())
+    Ok(mount_opts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentfs_sdk::{AgentFS, AgentFSOptions, DirEntry, FilesystemStats, DEFAULT_FILE_MODE};
+    use std::sync::atomic::AtomicUsize;
+
+    type Result<T> = std::result::Result<T, SdkError>;
+
+    /// Wraps a filesystem and counts how often inodes are resolved through it.
+    struct CountingFs {
+        inner: Arc<dyn FileSystem>,
+        resolutions: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl FileSystem for CountingFs {
+        async fn lookup(&self, parent_ino: i64, name: &str) -> Result<Option<Stats>> {
+            self.resolutions.fetch_add(1, Ordering::SeqCst);
+            self.inner.lookup(parent_ino, name).await
+        }
+
+        async fn getattr(&self, ino: i64) -> Result<Option<Stats>> {
+            self.resolutions.fetch_add(1, Ordering::SeqCst);
+            self.inner.getattr(ino).await
+        }
+
+        async fn readlink(&self, ino: i64) -> Result<Option<String>> {
+            self.inner.readlink(ino).await
+        }
+
+        async fn readdir(&self, ino: i64) -> Result<Option<Vec<String>>> {
+            self.inner.readdir(ino).await
+        }
+
+        async fn readdir_plus(&self, ino: i64) -> Result<Option<Vec<DirEntry>>> {
+            self.inner.readdir_plus(ino).await
+        }
+
+        async fn chmod(&self, ino: i64, mode: u32) -> Result<()> {
+            self.inner.chmod(ino, mode).await
+        }
+
+        async fn chown(&self, ino: i64, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+            self.inner.chown(ino, uid, gid).await
+        }
+
+        async fn utimens(&self, ino: i64, atime: TimeChange, mtime: TimeChange) -> Result<()> {
+            self.inner.utimens(ino, atime, mtime).await
+        }
+
+        async fn open(&self, ino: i64, flags: i32) -> Result<BoxedFile> {
+            self.inner.open(ino, flags).await
+        }
+
+        async fn mkdir(
+            &self,
+            parent_ino: i64,
+            name: &str,
+            mode: u32,
+            uid: u32,
+            gid: u32,
+        ) -> Result<Stats> {
+            self.inner.mkdir(parent_ino, name, mode, uid, gid).await
+        }
+
+        async fn create_file(
+            &self,
+            parent_ino: i64,
+            name: &str,
+            mode: u32,
+            uid: u32,
+            gid: u32,
+        ) -> Result<(Stats, BoxedFile)> {
+            self.inner
+                .create_file(parent_ino, name, mode, uid, gid)
+                .await
+        }
+
+        async fn mknod(
+            &self,
+            parent_ino: i64,
+            name: &str,
+            mode: u32,
+            rdev: u64,
+            uid: u32,
+            gid: u32,
+        ) -> Result<Stats> {
+            self.inner
+                .mknod(parent_ino, name, mode, rdev, uid, gid)
+                .await
+        }
+
+        async fn symlink(
+            &self,
+            parent_ino: i64,
+            name: &str,
+            target: &str,
+            uid: u32,
+            gid: u32,
+        ) -> Result<Stats> {
+            self.inner.symlink(parent_ino, name, target, uid, gid).await
+        }
+
+        async fn unlink(&self, parent_ino: i64, name: &str) -> Result<()> {
+            self.inner.unlink(parent_ino, name).await
+        }
+
+        async fn rmdir(&self, parent_ino: i64, name: &str) -> Result<()> {
+            self.inner.rmdir(parent_ino, name).await
+        }
+
+        async fn link(&self, ino: i64, newparent_ino: i64, newname: &str) -> Result<Stats> {
+            self.inner.link(ino, newparent_ino, newname).await
+        }
+
+        async fn rename(
+            &self,
+            oldparent_ino: i64,
+            oldname: &str,
+            newparent_ino: i64,
+            newname: &str,
+        ) -> Result<()> {
+            self.inner
+                .rename(oldparent_ino, oldname, newparent_ino, newname)
+                .await
+        }
+
+        async fn rename_with_flags(
+            &self,
+            oldparent_ino: i64,
+            oldname: &str,
+            newparent_ino: i64,
+            newname: &str,
+            flags: u32,
+        ) -> Result<()> {
+            self.inner
+                .rename_with_flags(oldparent_ino, oldname, newparent_ino, newname, flags)
+                .await
+        }
+
+        async fn statfs(&self) -> Result<FilesystemStats> {
+            self.inner.statfs().await
+        }
+    }
+
+    #[test]
+    fn test_getattr_with_handle_skips_inode_resolution() {
+        let runtime = Runtime::new().unwrap();
+        let agent = runtime
+            .block_on(AgentFS::open(AgentFSOptions::ephemeral()))
+            .unwrap();
+        let resolutions = Arc::new(AtomicUsize::new(0));
+        let fs: Arc<dyn FileSystem> = Arc::new(CountingFs {
+            inner: Arc::new(agent.fs),
+            resolutions: resolutions.clone(),
+        });
+
+        let (stats, file) = runtime
+            .block_on(fs.create_file(1, "file.txt", DEFAULT_FILE_MODE, 0, 0))
+            .unwrap();
+        runtime.block_on(file.pwrite(0, b"hello")).unwrap();
+
+        let fuse = AgentFSFuse::new(fs, runtime);
+        let fh = fuse.alloc_fh();
+        fuse.open_files.lock().insert(fh, OpenFile { file });
+
+        resolutions.store(0, Ordering::SeqCst);
+        for _ in 0..100 {
+            let attr = fuse
+                .getattr_with_handle(&CancellationToken::new(), stats.ino as u64, Some(fh))
+                .unwrap()
+                .unwrap();
+            assert_eq!(attr.ino, stats.ino);
+            assert_eq!(attr.size, 5);
+        }
+        assert_eq!(resolutions.load(Ordering::SeqCst), 0);
+
+        // Without a handle the inode is resolved through the filesystem
+        fuse.getattr_with_handle(&CancellationToken::new(), stats.ino as u64, None)
+            .unwrap();
+        assert_eq!(resolutions.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_readdir_on_file_fails_with_enotdir() {
+        let runtime = Runtime::new().unwrap();
+        let agent = runtime
+            .block_on(AgentFS::open(AgentFSOptions::ephemeral()))
+            .unwrap();
+        let fs: Arc<dyn FileSystem> = Arc::new(agent.fs);
+        let (stats, _) = runtime
+            .block_on(fs.create_file(1, "file.txt", DEFAULT_FILE_MODE, 0, 0))
+            .unwrap();
+        let fuse = AgentFSFuse::new(fs.clone(), runtime);
+        let interrupted = CancellationToken::new();
+
+        assert_eq!(fuse.check_directory(&interrupted, 1), Ok(()));
+        assert_eq!(
+            fuse.check_directory(&interrupted, stats.ino as u64),
+            Err(libc::ENOTDIR)
+        );
+        assert_eq!(fuse.check_directory(&interrupted, 999), Err(libc::ENOENT));
+
+        // Listing the file fails the same way
+        let err = fuse
+            .block_on(
+                &interrupted,
+                async move { fs.readdir_plus(stats.ino).await },
+            )
+            .unwrap_err();
+        assert_eq!(error_to_errno(&err), libc::ENOTDIR);
+    }
+
+    #[test]
+    fn test_change_hook_invalidates_kernel_cache() {
+        use crate::fuser::deferred_notify::{self, NotifyOp, NotifyQueueConfig};
+
+        let runtime = Runtime::new().unwrap();
+        let agent = runtime
+            .block_on(AgentFS::open(AgentFSOptions::ephemeral()))
+            .unwrap();
+        let (notifier, rx) = deferred_notify::channel(NotifyQueueConfig::default());
+        let fs = agent
+            .fs
+            .with_change_hook(Arc::new(move |ino| notifier.inval_inode(ino as u64, 0, -1)));
+
+        let (stats, writer) = runtime
+            .block_on(FileSystem::create_file(
+                &fs,
+                1,
+                "shared.txt",
+                DEFAULT_FILE_MODE,
+                0,
+                0,
+            ))
+            .unwrap();
+        let reader = runtime
+            .block_on(FileSystem::open(&fs, stats.ino, libc::O_RDONLY))
+            .unwrap();
+        assert!(rx.try_recv().is_err());
+
+        // A write through one handle invalidates what readers cached
+        runtime.block_on(writer.pwrite(0, b"new")).unwrap();
+        match rx.try_recv() {
+            Ok(NotifyOp::InvalInode { ino, offset, len }) => {
+                assert_eq!((ino, offset, len), (stats.ino as u64, 0, -1));
+            }
+            other => panic!("expected inode invalidation, got {:?}", other),
+        }
+        assert_eq!(runtime.block_on(reader.pread(0, 3)).unwrap(), b"new");
+    }
+
+    #[test]
+    fn test_poll_wakeup_is_delivered_once() {
+        use crate::fuser::deferred_notify::{self, NotifyOp, NotifyQueueConfig};
+
+        let runtime = Runtime::new().unwrap();
+        let agent = runtime
+            .block_on(AgentFS::open(AgentFSOptions::ephemeral()))
+            .unwrap();
+        let fuse = AgentFSFuse::new(Arc::new(agent.fs), runtime);
+        let (notifier, rx) = deferred_notify::channel(NotifyQueueConfig::default());
+
+        fuse.register_poll(1, 2, 42);
+        fuse.register_poll(3, 4, 43);
+
+        // Only pollers of the changed inode are woken
+        assert_eq!(fuse.wake_pollers(&notifier, 2), 1);
+        assert!(matches!(rx.try_recv(), Ok(NotifyOp::Poll { kh: 42 })));
+        assert!(rx.try_recv().is_err());
+
+        // The registration is consumed by the wakeup
+        assert_eq!(fuse.wake_pollers(&notifier, 2), 0);
+        assert!(rx.try_recv().is_err());
+        assert_eq!(fuse.pollers.lock().len(), 1);
+    }
+
+    /// A file whose every operation stalls, like a backend stuck on sync.
+    struct StalledFile;
+
+    #[async_trait::async_trait]
+    impl agentfs_sdk::File for StalledFile {
+        async fn pread(&self, _offset: u64, _size: u64) -> Result<Vec<u8>> {
+            std::future::pending().await
+        }
+
+        async fn pwrite(&self, _offset: u64, _data: &[u8]) -> Result<()> {
+            std::future::pending().await
+        }
+
+        async fn truncate(&self, _size: u64) -> Result<()> {
+            std::future::pending().await
+        }
+
+        async fn fsync(&self) -> Result<()> {
+            std::future::pending().await
+        }
+
+        async fn fstat(&self) -> Result<Stats> {
+            std::future::pending().await
+        }
+    }
+
+    #[test]
+    fn test_op_timeout_fails_stalled_upcall() {
+        let runtime = Runtime::new().unwrap();
+        let agent = runtime
+            .block_on(AgentFS::open(AgentFSOptions::ephemeral()))
+            .unwrap();
+        let timeout = Duration::from_millis(100);
+        let fuse = AgentFSFuse::new(Arc::new(agent.fs), runtime).with_op_timeout(Some(timeout));
+        let fh = fuse.alloc_fh();
+        fuse.open_files.lock().insert(
+            fh,
+            OpenFile {
+                file: Arc::new(StalledFile),
+            },
+        );
+
+        let interrupted = CancellationToken::new();
+        let start = std::time::Instant::now();
+        let err = fuse
+            .getattr_with_handle(&interrupted, 2, Some(fh))
+            .unwrap_err();
+        assert_eq!(error_to_errno(&err), libc::EIO);
+        assert!(start.elapsed() >= timeout);
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // Operations that finish in time are unaffected
+        assert!(fuse
+            .getattr_with_handle(&interrupted, 1, None)
+            .unwrap()
+            .is_some());
+    }
+
+    /// A file whose reads stall while holding `held`, like an operation
+    /// waiting on a lock.
+    struct HoldingFile {
+        held: Arc<()>,
+    }
+
+    #[async_trait::async_trait]
+    impl agentfs_sdk::File for HoldingFile {
+        async fn pread(&self, _offset: u64, _size: u64) -> Result<Vec<u8>> {
+            let _guard = self.held.clone();
+            std::future::pending().await
+        }
+
+        async fn pwrite(&self, _offset: u64, _data: &[u8]) -> Result<()> {
+            Ok(())
+        }
+
+        async fn truncate(&self, _size: u64) -> Result<()> {
+            Ok(())
+        }
+
+        async fn fsync(&self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn fstat(&self) -> Result<Stats> {
+            std::future::pending().await
+        }
+    }
+
+    #[test]
+    fn test_interrupt_cancels_inflight_upcall() {
+        let runtime = Runtime::new().unwrap();
+        let agent = runtime
+            .block_on(AgentFS::open(AgentFSOptions::ephemeral()))
+            .unwrap();
+        let fuse = AgentFSFuse::new(Arc::new(agent.fs), runtime);
+        let held = Arc::new(());
+        let file = Arc::new(HoldingFile { held: held.clone() });
+
+        // The kernel interrupts the request once the caller is killed
+        let interrupted = CancellationToken::new();
+        let interrupter = {
+            let interrupted = interrupted.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(100));
+                interrupted.cancel();
+            })
+        };
+
+        let start = std::time::Instant::now();
+        let err = fuse
+            .block_on(&interrupted, async move { file.pread(0, 4096).await })
+            .unwrap_err();
+        interrupter.join().unwrap();
+        assert_eq!(error_to_errno(&err), libc::EINTR);
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // The abandoned call no longer holds anything
+        assert_eq!(Arc::strong_count(&held), 1);
+
+        // A request interrupted before dispatch is not started at all
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = calls.clone();
+        let err = fuse
+            .block_on(&interrupted, async move {
+                counted.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            })
+            .unwrap_err();
+        assert_eq!(error_to_errno(&err), libc::EINTR);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_unlinked_file_is_kept_until_forgotten() {
+        let runtime = Runtime::new().unwrap();
+        let agent = runtime
+            .block_on(AgentFS::open(AgentFSOptions::ephemeral()))
+            .unwrap();
+        let fs: Arc<dyn FileSystem> = Arc::new(agent.fs);
+        let (stats, file) = runtime
+            .block_on(fs.create_file(1, "file.txt", DEFAULT_FILE_MODE, 0, 0))
+            .unwrap();
+        let mut fuse = AgentFSFuse::new(fs.clone(), runtime);
+        let interrupted = CancellationToken::new();
+        let exists = |fuse: &AgentFSFuse, ino: i64| {
+            fuse.runtime.block_on(fs.getattr(ino)).unwrap().is_some()
+        };
+
+        // The kernel got the file from create and a later lookup, and has it open
+        fuse.remember(stats.ino as u64);
+        fuse.remember(stats.ino as u64);
+        fuse.unlink_entry(&interrupted, 1, "file.txt").unwrap();
+        assert!(fuse
+            .runtime
+            .block_on(fs.lookup(1, "file.txt"))
+            .unwrap()
+            .is_none());
+
+        // The open file stays usable
+        fuse.runtime
+            .block_on(file.pwrite(0, b"still here"))
+            .unwrap();
+        assert_eq!(
+            fuse.runtime.block_on(file.pread(0, 10)).unwrap(),
+            b"still here"
+        );
+
+        // It is released once closed and every lookup is forgotten
+        drop(file);
+        fuse.forget_lookups(stats.ino as u64, 1);
+        assert!(exists(&fuse, stats.ino));
+        fuse.forget_lookups(stats.ino as u64, 1);
+        assert!(!exists(&fuse, stats.ino));
+
+        // Without lookups held by the kernel, unlinking releases right away
+        let (other, _) = fuse
+            .runtime
+            .block_on(fs.create_file(1, "other.txt", DEFAULT_FILE_MODE, 0, 0))
+            .unwrap();
+        fuse.unlink_entry(&interrupted, 1, "other.txt").unwrap();
+        assert!(!exists(&fuse, other.ino));
+
+        // Files still kept when the session ends are released then
+        let (last, _) = fuse
+            .runtime
+            .block_on(fs.create_file(1, "last.txt", DEFAULT_FILE_MODE, 0, 0))
+            .unwrap();
+        fuse.remember(last.ino as u64);
+        fuse.unlink_entry(&interrupted, 1, "last.txt").unwrap();
+        assert!(exists(&fuse, last.ino));
+        fuse.destroy();
+        assert!(!exists(&fuse, last.ino));
+    }
+
+    #[test]
+    fn test_replaced_file_is_kept_until_forgotten() {
+        let runtime = Runtime::new().unwrap();
+        let agent = runtime
+            .block_on(AgentFS::open(AgentFSOptions::ephemeral()))
+            .unwrap();
+        let fs: Arc<dyn FileSystem> = Arc::new(agent.fs);
+        runtime
+            .block_on(fs.create_file(1, "new.txt", DEFAULT_FILE_MODE, 0, 0))
+            .unwrap();
+        let (old, file) = runtime
+            .block_on(fs.create_file(1, "old.txt", DEFAULT_FILE_MODE, 0, 0))
+            .unwrap();
+        let fuse = AgentFSFuse::new(fs.clone(), runtime);
+        let interrupted = CancellationToken::new();
+        let exists = |fuse: &AgentFSFuse, ino: i64| {
+            fuse.runtime.block_on(fs.getattr(ino)).unwrap().is_some()
+        };
+
+        // Renaming over an open file leaves it usable through its handle
+        fuse.remember(old.ino as u64);
+        fuse.rename_entry(&interrupted, 1, "new.txt", 1, "old.txt", 0)
+            .unwrap();
+        fuse.runtime.block_on(file.pwrite(0, b"kept")).unwrap();
+        assert_eq!(fuse.runtime.block_on(file.pread(0, 4)).unwrap(), b"kept");
+
+        drop(file);
+        fuse.forget_lookups(old.ino as u64, 1);
+        assert!(!exists(&fuse, old.ino));
+    }
+
+    #[test]
+    fn test_take_lookups_hands_over_orphans() {
+        let runtime = Runtime::new().unwrap();
+        let agent = runtime
+            .block_on(AgentFS::open(AgentFSOptions::ephemeral()))
+            .unwrap();
+        let fs: Arc<dyn FileSystem> = Arc::new(agent.fs);
+        let (kept, _) = runtime
+            .block_on(fs.create_file(1, "kept.txt", DEFAULT_FILE_MODE, 0, 0))
+            .unwrap();
+        let (open, _) = runtime
+            .block_on(fs.create_file(1, "open.txt", DEFAULT_FILE_MODE, 0, 0))
+            .unwrap();
+        let control = FuseControl::default();
+        let fuse = AgentFSFuse::new(fs.clone(), runtime).with_control(&control);
+        let interrupted = CancellationToken::new();
+
+        fuse.remember(kept.ino as u64);
+        fuse.remember(open.ino as u64);
+        fuse.unlink_entry(&interrupted, 1, "open.txt").unwrap();
+
+        // A swap leaves the unlinked file to the previous filesystem, and
+        // later FORGETs of either inode are not taken for the new one
+        assert_eq!(control.take_lookups(), vec![open.ino as u64]);
+        assert!(fuse.lookups.lock().is_empty());
+        fuse.forget_lookups(open.ino as u64, 1);
+        assert!(fuse
+            .runtime
+            .block_on(fs.getattr(open.ino))
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn test_mock_faults_map_to_errno() {
+        use agentfs_sdk::filesystem::{Fault, FsError, MockFileSystem};
+
+        let runtime = Runtime::new().unwrap();
+        let agent = runtime
+            .block_on(AgentFS::open(AgentFSOptions::ephemeral()))
+            .unwrap();
+        let mock = Arc::new(MockFileSystem::new(Arc::new(agent.fs)));
+        let (stats, file) = runtime
+            .block_on(mock.create_file(1, "file.txt", DEFAULT_FILE_MODE, 0, 0))
+            .unwrap();
+        let timeout = Duration::from_millis(100);
+        let fuse = AgentFSFuse::new(mock.clone(), runtime).with_op_timeout(Some(timeout));
+        let interrupted = CancellationToken::new();
+
+        mock.inject("pwrite", Fault::Error(FsError::NoSpace));
+        let err = fuse
+            .block_on(&interrupted, async move { file.pwrite(0, b"data").await })
+            .unwrap_err();
+        assert_eq!(error_to_errno(&err), libc::ENOSPC);
+
+        mock.inject("getattr", Fault::Io(libc::EIO));
+        let err = fuse
+            .getattr_with_handle(&interrupted, stats.ino as u64, None)
+            .unwrap_err();
+        assert_eq!(error_to_errno(&err), libc::EIO);
+
+        // A hung backend is cut off by the operation timeout
+        mock.inject("getattr", Fault::Hang);
+        let err = fuse
+            .getattr_with_handle(&interrupted, stats.ino as u64, None)
+            .unwrap_err();
+        assert_eq!(error_to_errno(&err), libc::EIO);
+
+        mock.clear("getattr");
+        let attr = fuse
+            .getattr_with_handle(&interrupted, stats.ino as u64, None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(attr.size, 0);
+        assert_eq!(mock.count("getattr"), 3);
+    }
+
+    fn test_mount_options(fsname: &str, subtype: Option<&str>) -> FuseMountOptions {
+        FuseMountOptions {
+            mountpoint: PathBuf::from("/mnt/agent"),
+            auto_unmount: false,
+            allow_root: false,
+            allow_other: false,
+            fsname: fsname.to_string(),
+            subtype: subtype.map(str::to_string),
+            uid: None,
+            gid: None,
+            read_only: false,
+            extra_options: Vec::new(),
+            op_timeout: None,
+            max_read: None,
+            max_write: None,
+        }
+    }
+
+    #[test]
+    fn test_mount_options_include_subtype() {
+        let opts = test_mount_options("agentfs:/tmp/agent.db", Some("agentfs"));
+        let mount_opts = mount_options(&opts).unwrap();
+        assert!(mount_opts.contains(&MountOption::FSName("agentfs:/tmp/agent.db".to_string())));
+        assert!(mount_opts.contains(&MountOption::Subtype("agentfs".to_string())));
+
+        let opts = test_mount_options("agentfs", None);
+        let mount_opts = mount_options(&opts).unwrap();
+        assert!(!mount_opts
+            .iter()
+            .any(|opt| matches!(opt, MountOption::Subtype(_))));
+    }
+
+    #[test]
+    fn test_mount_options_read_only() {
+        let mut opts = test_mount_options("agentfs", None);
+        assert!(!mount_options(&opts).unwrap().contains(&MountOption::RO));
+
+        opts.read_only = true;
+        assert!(mount_options(&opts).unwrap().contains(&MountOption::RO));
+    }
+
+    #[test]
+    fn test_mount_options_extra_options() {
+        let mut opts = test_mount_options("agentfs", None);
+        opts.extra_options = vec!["noexec".to_string(), "max_read=131072".to_string()];
+        let mount_opts = mount_options(&opts).unwrap();
+        assert!(mount_opts.contains(&MountOption::NoExec));
+        assert!(mount_opts.contains(&MountOption::CUSTOM("max_read=131072".to_string())));
+
+        opts.extra_options = vec!["allow_other".to_string()];
+        let err = mount_options(&opts).unwrap_err();
+        assert!(err.to_string().contains("Invalid mount option"));
+    }
+
+    #[test]
+    fn test_mount_options_request_sizes() {
+        let mut opts = test_mount_options("agentfs", None);
+        opts.max_read = Some(64 * 1024);
+        opts.max_write = Some(64 * 1024);
+        let mount_opts = mount_options(&opts).unwrap();
+        assert!(mount_opts.contains(&MountOption::CUSTOM("max_read=65536".to_string())));
+
+        opts.max_write = Some(1024);
+        let err = mount_options(&opts).unwrap_err();
+        assert!(err.to_string().contains("Invalid max_write"), "{}", err);
+    }
+
+    #[test]
+    fn test_mount_options_reject_invalid_fsname() {
+        let opts = test_mount_options("agentfs,dev,suid", Some("agentfs"));
+        let err = mount_options(&opts).unwrap_err();
+        assert!(err.to_string().contains("Invalid fsname"));
+
+        let opts = test_mount_options("agentfs", Some("agent fs"));
+        let err = mount_options(&opts).unwrap_err();
+        assert!(err.to_string().contains("Invalid subtype"));
+    }
 }
@@ -13,7 +13,7 @@ use libc::{c_int, c_void, size_t};
 use super::reply::ReplySender;
 
 /// A raw communication channel to the FUSE kernel driver
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Channel(Arc<File>);
 
 impl AsFd for Channel {
@@ -60,19 +60,27 @@ impl Channel {
 pub struct ChannelSender(Arc<File>);
 
 impl ReplySender for ChannelSender {
+    /// Write a reply or notification to the kernel driver.
+    ///
+    /// Retries on EINTR, so a signal delivered to the sending thread does
+    /// not lose the reply.
     fn send(&self, bufs: &[io::IoSlice<'_>]) -> io::Result<()> {
-        let rc = unsafe {
-            libc::writev(
-                self.0.as_raw_fd(),
-                bufs.as_ptr() as *const libc::iovec,
-                bufs.len() as c_int,
-            )
-        };
-        if rc < 0 {
-            Err(io::Error::last_os_error())
-        } else {
-            debug_assert_eq!(bufs.iter().map(|b| b.len()).sum::<usize>(), rc as usize);
-            Ok(())
+        loop {
+            let rc = unsafe {
+                libc::writev(
+                    self.0.as_raw_fd(),
+                    bufs.as_ptr() as *const libc::iovec,
+                    bufs.len() as c_int,
+                )
+            };
+            if rc >= 0 {
+                debug_assert_eq!(bufs.iter().map(|b| b.len()).sum::<usize>(), rc as usize);
+                return Ok(());
+            }
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::EINTR) {
+                return Err(err);
+            }
         }
     }
 }
@@ -1,16 +1,33 @@
 use log::debug;
 use std::{
     ffi::{OsStr, OsString},
-    sync::mpsc,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        mpsc::{self, TrySendError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
-/// A queued invalidation operation to be flushed by the notify thread.
+/// Default number of notifications that can wait for the notify thread
+pub const DEFAULT_NOTIFY_QUEUE_CAPACITY: usize = 4096;
+
+/// Default time a producer waits for room in a full queue
+pub const DEFAULT_NOTIFY_QUEUE_WAIT: Duration = Duration::from_millis(50);
+
+/// How often a producer waiting on a full queue checks for room
+const NOTIFY_RETRY_INTERVAL: Duration = Duration::from_millis(1);
+
+/// A queued notification to be flushed by the notify thread.
 #[derive(Debug)]
 pub enum NotifyOp {
     InvalEntry { parent: u64, name: OsString },
+    InvalInode { ino: u64, offset: i64, len: i64 },
+    Poll { kh: u64 },
 }
 
-/// Queues kernel cache invalidation requests for deferred execution.
+/// Queues kernel cache invalidations and poll wakeups for deferred execution.
 ///
 /// FUSE notification writes to /dev/fuse cannot be issued from the session
 /// loop thread — even outside callbacks — because the kernel processes
@@ -22,22 +39,202 @@ pub enum NotifyOp {
 /// DeferredNotifier solves this by sending operations over an mpsc channel
 /// to a dedicated background thread that writes to /dev/fuse independently
 /// of the session loop.
+///
+/// The channel is bounded, so a flood of invalidations (such as `rm -rf` of
+/// a large tree) cannot outgrow the notify thread. A producer finding the
+/// queue full waits up to [`NotifyQueueConfig::max_wait`] for room, then
+/// drops the notification and counts it. The wait is bounded, so a notify
+/// thread stuck in writev() slows the session loop down but cannot
+/// deadlock it.
 #[derive(Debug, Clone)]
 pub struct DeferredNotifier {
-    tx: mpsc::Sender<NotifyOp>,
+    tx: mpsc::SyncSender<NotifyOp>,
+    max_wait: Duration,
+    stats: Arc<NotifyQueueStats>,
 }
 
-impl DeferredNotifier {
-    pub(crate) fn new(tx: mpsc::Sender<NotifyOp>) -> Self {
-        Self { tx }
+/// Bounds of the deferred notification queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotifyQueueConfig {
+    /// Number of notifications that can be queued.
+    pub capacity: usize,
+    /// How long a producer waits for room in a full queue before dropping
+    /// the notification. Zero drops it at once.
+    pub max_wait: Duration,
+}
+
+impl Default for NotifyQueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_NOTIFY_QUEUE_CAPACITY,
+            max_wait: DEFAULT_NOTIFY_QUEUE_WAIT,
+        }
+    }
+}
+
+/// Depth and drop counts of a deferred notification queue.
+#[derive(Debug, Default)]
+pub struct NotifyQueueStats {
+    queued: AtomicUsize,
+    dropped: AtomicU64,
+}
+
+impl NotifyQueueStats {
+    /// Notifications waiting for the notify thread.
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    /// Notifications dropped because the queue stayed full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Receiving end of a deferred notification queue, drained by the notify
+/// thread.
+#[derive(Debug)]
+pub(crate) struct NotifyQueue {
+    rx: mpsc::Receiver<NotifyOp>,
+    stats: Arc<NotifyQueueStats>,
+}
+
+impl NotifyQueue {
+    /// Take a queued notification without waiting.
+    #[cfg(test)]
+    pub(crate) fn try_recv(&self) -> Result<NotifyOp, mpsc::TryRecvError> {
+        let op = self.rx.try_recv()?;
+        self.stats.queued.fetch_sub(1, Ordering::Relaxed);
+        Ok(op)
+    }
+}
+
+impl Iterator for NotifyQueue {
+    type Item = NotifyOp;
+
+    /// Wait for the next notification, until every notifier is dropped.
+    fn next(&mut self) -> Option<NotifyOp> {
+        let op = self.rx.recv().ok()?;
+        self.stats.queued.fetch_sub(1, Ordering::Relaxed);
+        Some(op)
     }
+}
+
+/// Create a bounded notification queue and the notifier that feeds it.
+pub(crate) fn channel(config: NotifyQueueConfig) -> (DeferredNotifier, NotifyQueue) {
+    let (tx, rx) = mpsc::sync_channel(config.capacity);
+    let stats = Arc::new(NotifyQueueStats::default());
+    let notifier = DeferredNotifier {
+        tx,
+        max_wait: config.max_wait,
+        stats: stats.clone(),
+    };
+    (notifier, NotifyQueue { rx, stats })
+}
 
+impl DeferredNotifier {
     pub fn inval_entry(&self, parent: u64, name: &OsStr) {
-        if let Err(e) = self.tx.send(NotifyOp::InvalEntry {
+        self.send(NotifyOp::InvalEntry {
             parent,
             name: name.to_os_string(),
-        }) {
-            debug!("deferred inval_entry send failed (notify thread gone?): {e}");
+        });
+    }
+
+    /// Invalidate the kernel's cached attributes and data of an inode.
+    ///
+    /// Drops cached pages in `offset..offset + len`, or to the end of the
+    /// file if `len` is negative.
+    pub fn inval_inode(&self, ino: u64, offset: i64, len: i64) {
+        self.send(NotifyOp::InvalInode { ino, offset, len });
+    }
+
+    /// Notify the kernel that the file behind poll handle `kh` is ready.
+    pub fn notify_poll(&self, kh: u64) {
+        self.send(NotifyOp::Poll { kh });
+    }
+
+    /// Depth and drop counts of the queue.
+    pub fn stats(&self) -> &Arc<NotifyQueueStats> {
+        &self.stats
+    }
+
+    fn send(&self, mut op: NotifyOp) {
+        // Counted before sending, so the notify thread never sees the
+        // count go below zero
+        self.stats.queued.fetch_add(1, Ordering::Relaxed);
+        let deadline = Instant::now() + self.max_wait;
+        loop {
+            match self.tx.try_send(op) {
+                Ok(()) => return,
+                Err(TrySendError::Full(full)) if Instant::now() < deadline => {
+                    op = full;
+                    thread::sleep(NOTIFY_RETRY_INTERVAL);
+                }
+                Err(TrySendError::Full(full)) => {
+                    self.stats.queued.fetch_sub(1, Ordering::Relaxed);
+                    self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+                    debug!("deferred notify queue full, dropping {full:?}");
+                    return;
+                }
+                Err(TrySendError::Disconnected(op)) => {
+                    self.stats.queued.fetch_sub(1, Ordering::Relaxed);
+                    debug!("deferred {op:?} send failed (notify thread gone?)");
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{channel, NotifyOp, NotifyQueueConfig};
+    use std::time::Duration;
+
+    #[test]
+    fn flood_is_bounded_and_drops_are_counted() {
+        let (notifier, queue) = channel(NotifyQueueConfig {
+            capacity: 16,
+            max_wait: Duration::ZERO,
+        });
+
+        // Nothing drains the queue, as if the notify thread were stuck
+        for ino in 0..10_000 {
+            notifier.inval_inode(ino, 0, -1);
+        }
+        assert_eq!(notifier.stats().queued(), 16);
+        assert_eq!(notifier.stats().dropped(), 10_000 - 16);
+
+        // The oldest notifications are the ones kept
+        for ino in 0..16 {
+            assert!(matches!(
+                queue.try_recv(),
+                Ok(NotifyOp::InvalInode { ino: queued, .. }) if queued == ino
+            ));
+        }
+        assert!(queue.try_recv().is_err());
+        assert_eq!(notifier.stats().queued(), 0);
+
+        notifier.notify_poll(7);
+        assert!(matches!(queue.try_recv(), Ok(NotifyOp::Poll { kh: 7 })));
+    }
+
+    #[test]
+    fn full_queue_waits_for_the_notify_thread() {
+        let (notifier, queue) = channel(NotifyQueueConfig {
+            capacity: 1,
+            max_wait: Duration::from_secs(10),
+        });
+        let drain = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            queue.take(100).count()
+        });
+
+        for kh in 0..100 {
+            notifier.notify_poll(kh);
         }
+        assert_eq!(drain.join().unwrap(), 100);
+        assert_eq!(notifier.stats().dropped(), 0);
+        assert_eq!(notifier.stats().queued(), 0);
     }
 }
@@ -0,0 +1,152 @@
+//! Interrupted request tracking (FUSE_INTERRUPT)
+//!
+//! When a process blocked on a filesystem request receives a signal, the
+//! kernel sends an INTERRUPT request naming the original one. The session
+//! reads requests on a dedicated thread, so interrupts arrive even while the
+//! session loop is busy dispatching the request they target.
+//!
+//! Every request is registered here when it is read and finished once its
+//! dispatch returns. An interrupt cancels the token of a registered request;
+//! the filesystem observes it through [`Request::interrupted`] and is
+//! expected to abandon the operation and reply with `EINTR`. Interrupts for
+//! requests that have already finished are ignored, as the protocol allows.
+//!
+//! The same tracking lets the mount owner list what is in flight and cancel a
+//! request itself, exactly as if the kernel had interrupted it.
+//!
+//! [`Request::interrupted`]: super::Request::interrupted
+
+use super::ll::fuse_abi::fuse_opcode;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tokio_util::sync::CancellationToken;
+
+/// A request that has been read from the kernel and not yet answered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InFlightOp {
+    /// Unique ID of the request, used to cancel it
+    pub id: u64,
+    /// Operation requested, e.g. "LOOKUP" or "READ"
+    pub op: String,
+    /// Inode the request is for
+    pub ino: u64,
+    /// When the request was read
+    pub started: SystemTime,
+}
+
+#[derive(Debug)]
+struct Tracked {
+    token: CancellationToken,
+    opcode: u32,
+    ino: u64,
+    started: SystemTime,
+}
+
+/// In-flight requests that the kernel may interrupt, keyed by unique ID.
+#[derive(Debug, Default)]
+pub(crate) struct InterruptTracker {
+    inflight: Mutex<HashMap<u64, Tracked>>,
+}
+
+impl InterruptTracker {
+    /// Start tracking a request, returning the token its interrupt cancels.
+    pub(crate) fn register(&self, unique: u64, opcode: u32, ino: u64) -> CancellationToken {
+        let token = CancellationToken::new();
+        let tracked = Tracked {
+            token: token.clone(),
+            opcode,
+            ino,
+            started: SystemTime::now(),
+        };
+        self.inflight.lock().unwrap().insert(unique, tracked);
+        token
+    }
+
+    /// Interrupt a request. Returns false if it is no longer in flight.
+    pub(crate) fn interrupt(&self, unique: u64) -> bool {
+        match self.inflight.lock().unwrap().get(&unique) {
+            Some(tracked) => {
+                tracked.token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Requests in flight, oldest first.
+    pub(crate) fn in_flight(&self) -> Vec<InFlightOp> {
+        let mut ops: Vec<_> = self
+            .inflight
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(&id, tracked)| InFlightOp {
+                id,
+                op: opcode_name(tracked.opcode),
+                ino: tracked.ino,
+                started: tracked.started,
+            })
+            .collect();
+        ops.sort_by_key(|op| (op.started, op.id));
+        ops
+    }
+
+    /// Stop tracking a request once it has been dispatched.
+    pub(crate) fn finish(&self, unique: u64) {
+        self.inflight.lock().unwrap().remove(&unique);
+    }
+}
+
+/// Name of a FUSE opcode without its `FUSE_` prefix.
+fn opcode_name(opcode: u32) -> String {
+    match fuse_opcode::try_from(opcode) {
+        Ok(op) => {
+            let name = format!("{op:?}");
+            name.strip_prefix("FUSE_").unwrap_or(&name).to_string()
+        }
+        Err(_) => format!("UNKNOWN({opcode})"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::InterruptTracker;
+
+    #[test]
+    fn interrupt_cancels_inflight_request() {
+        let tracker = InterruptTracker::default();
+        let token = tracker.register(7, 15, 2);
+        let other = tracker.register(8, 15, 3);
+        assert!(tracker.interrupt(7));
+        assert!(token.is_cancelled());
+        assert!(!other.is_cancelled());
+    }
+
+    #[test]
+    fn interrupt_after_finish_is_ignored() {
+        let tracker = InterruptTracker::default();
+        let token = tracker.register(7, 15, 2);
+        tracker.finish(7);
+        assert!(!tracker.interrupt(7));
+        assert!(!token.is_cancelled());
+    }
+
+    #[test]
+    fn in_flight_lists_registered_requests() {
+        let tracker = InterruptTracker::default();
+        tracker.register(7, 9, 1);
+        tracker.register(8, 15, 2);
+        tracker.register(9, 4242, 3);
+        tracker.finish(8);
+
+        let ops = tracker.in_flight();
+        assert_eq!(ops.len(), 2);
+        assert_eq!((ops[0].id, ops[0].op.as_str(), ops[0].ino), (7, "MKDIR", 1));
+        assert_eq!(
+            (ops[1].id, ops[1].op.as_str(), ops[1].ino),
+            (9, "UNKNOWN(4242)", 3)
+        );
+        assert!(ops[0].started <= ops[1].started);
+    }
+}
@@ -2112,6 +2112,11 @@ pub struct AnyRequest<'a> {
 impl_request!(AnyRequest<'_>);
 
 impl<'a> AnyRequest<'a> {
+    /// Raw opcode of the request, whether or not it is a known one.
+    pub fn opcode(&self) -> u32 {
+        self.header.opcode
+    }
+
     pub fn operation(&self) -> Result<Operation<'a>, RequestError> {
         // Parse/check opcode
         let opcode = fuse_opcode::try_from(self.header.opcode)
@@ -24,6 +24,8 @@ use std::io::ErrorKind;
 use std::path::Path;
 use std::time::SystemTime;
 
+pub use deferred_notify::{NotifyQueueConfig, NotifyQueueStats};
+pub use interrupt::InFlightOp;
 pub use ll::fuse_abi::consts;
 pub use ll::fuse_abi::fuse_forget_one;
 pub use ll::fuse_abi::FUSE_ROOT_ID;
@@ -40,9 +42,11 @@ pub use reply::{
 pub use request::Request;
 pub use session::{BackgroundSession, Session, SessionACL, SessionUnmounter};
 
+pub(crate) use interrupt::InterruptTracker;
 use ll::fuse_abi::consts::*;
-use mnt::mount_options::check_option_conflicts;
+pub(crate) use mnt::mount_options::check_option_conflicts;
 use session::MAX_WRITE_SIZE;
+pub(crate) use session::READER_THREAD_NAME;
 
 mod channel;
 #[allow(
@@ -52,6 +56,7 @@ mod channel;
     clippy::manual_is_multiple_of
 )]
 pub(crate) mod deferred_notify;
+mod interrupt;
 mod ll;
 #[allow(clippy::io_other_error)]
 mod mnt;
@@ -10,6 +10,7 @@ use log::{debug, error, warn};
 use std::convert::TryFrom;
 use std::convert::TryInto;
 use std::path::Path;
+use tokio_util::sync::CancellationToken;
 
 use super::channel::ChannelSender;
 use super::deferred_notify::DeferredNotifier;
@@ -33,6 +34,8 @@ pub struct Request<'a> {
     data: &'a [u8],
     /// Parsed request
     request: ll::AnyRequest<'a>,
+    /// Cancelled when the kernel interrupts this request
+    interrupted: CancellationToken,
 }
 
 impl<'a> Request<'a> {
@@ -41,6 +44,7 @@ impl<'a> Request<'a> {
         ch: ChannelSender,
         deferred: &'a DeferredNotifier,
         data: &'a [u8],
+        interrupted: CancellationToken,
     ) -> Option<Request<'a>> {
         let request = match ll::AnyRequest::try_from(data) {
             Ok(request) => request,
@@ -55,6 +59,7 @@ impl<'a> Request<'a> {
             deferred,
             data,
             request,
+            interrupted,
         })
     }
 
@@ -185,8 +190,9 @@ impl<'a> Request<'a> {
             }
 
             ll::Operation::Interrupt(_) => {
-                // TODO: handle FUSE_INTERRUPT
-                return Err(Errno::ENOSYS);
+                // Interrupts are consumed by the session's reader thread, see
+                // `interrupt`. Interrupt requests take no reply.
+                return Ok(None);
             }
 
             ll::Operation::Lookup(x) => {
@@ -652,4 +658,12 @@ impl<'a> Request<'a> {
     pub fn pid(&self) -> u32 {
         self.request.pid()
     }
+
+    /// Returns a token that is cancelled if the kernel interrupts this
+    /// request, e.g. because the calling process was killed. An interrupted
+    /// request should be abandoned and replied to with `EINTR`.
+    #[inline]
+    pub fn interrupted(&self) -> &CancellationToken {
+        &self.interrupted
+    }
 }
@@ -11,13 +11,18 @@ use nix::unistd::geteuid;
 use std::io;
 use std::os::fd::{AsFd, BorrowedFd, OwnedFd};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, Weak};
 use std::thread::{self, JoinHandle};
 
 use std::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 
-use super::deferred_notify::{DeferredNotifier, NotifyOp};
+use super::deferred_notify::{
+    self, DeferredNotifier, NotifyOp, NotifyQueue, NotifyQueueConfig, NotifyQueueStats,
+};
+use super::interrupt::InterruptTracker;
 use super::ll::fuse_abi as abi;
+use super::ll::{self, Request as _};
 use super::request::Request;
 use super::Filesystem;
 use super::MountOption;
@@ -29,6 +34,9 @@ use super::{channel::ChannelSender, notify::Notifier};
 /// and 128k on other systems.
 pub const MAX_WRITE_SIZE: usize = 16 * 1024 * 1024;
 
+/// Name of the thread reading requests from the kernel driver.
+pub(crate) const READER_THREAD_NAME: &str = "fuse-reader";
+
 /// Size of the buffer for reading a request from the kernel. Since the kernel may send
 /// up to `MAX_WRITE_SIZE` bytes in a write request, we use that value plus some extra space.
 const BUFFER_SIZE: usize = MAX_WRITE_SIZE + 4096;
@@ -68,9 +76,11 @@ pub struct Session<FS: Filesystem> {
     /// True if the filesystem was destroyed (destroy operation done)
     pub(crate) destroyed: bool,
     /// Sender half of the deferred notification queue
-    notify_tx: Option<mpsc::Sender<NotifyOp>>,
+    notify_tx: Option<Arc<DeferredNotifier>>,
+    /// Requests read from the kernel and not yet dispatched to completion
+    interrupts: Arc<InterruptTracker>,
     /// Receiver half — moved to the notify thread in run()
-    notify_rx: Option<mpsc::Receiver<NotifyOp>>,
+    notify_rx: Option<NotifyQueue>,
 }
 
 impl<FS: Filesystem> AsFd for Session<FS> {
@@ -116,7 +126,7 @@ impl<FS: Filesystem> Session<FS> {
             SessionACL::Owner
         };
 
-        let (notify_tx, notify_rx) = mpsc::channel();
+        let (notify_tx, notify_rx) = deferred_notify::channel(NotifyQueueConfig::default());
 
         Ok(Session {
             filesystem,
@@ -128,7 +138,8 @@ impl<FS: Filesystem> Session<FS> {
             proto_minor: 0,
             initialized: false,
             destroyed: false,
-            notify_tx: Some(notify_tx),
+            notify_tx: Some(Arc::new(notify_tx)),
+            interrupts: Arc::new(InterruptTracker::default()),
             notify_rx: Some(notify_rx),
         })
     }
@@ -137,7 +148,7 @@ impl<FS: Filesystem> Session<FS> {
     /// filesystem anywhere; that must be done separately.
     pub fn from_fd(filesystem: FS, fd: OwnedFd, acl: SessionACL) -> Self {
         let ch = Channel::new(Arc::new(fd.into()));
-        let (notify_tx, notify_rx) = mpsc::channel();
+        let (notify_tx, notify_rx) = deferred_notify::channel(NotifyQueueConfig::default());
         Session {
             filesystem,
             ch,
@@ -148,15 +159,51 @@ impl<FS: Filesystem> Session<FS> {
             proto_minor: 0,
             initialized: false,
             destroyed: false,
-            notify_tx: Some(notify_tx),
+            notify_tx: Some(Arc::new(notify_tx)),
+            interrupts: Arc::new(InterruptTracker::default()),
             notify_rx: Some(notify_rx),
         }
     }
 
+    /// Bound the queue of deferred kernel notifications, replacing the default
+    /// one. Must be called before `run()`.
+    pub fn set_notify_queue(&mut self, config: NotifyQueueConfig) {
+        assert!(self.notify_rx.is_some(), "run() already called");
+        let (notify_tx, notify_rx) = deferred_notify::channel(config);
+        self.notify_tx = Some(Arc::new(notify_tx));
+        self.notify_rx = Some(notify_rx);
+    }
+
+    /// Depth and drop counts of the deferred kernel notification queue.
+    pub fn notify_queue_stats(&self) -> Arc<NotifyQueueStats> {
+        self.notify_tx
+            .as_ref()
+            .expect("notify_tx missing")
+            .stats()
+            .clone()
+    }
+
+    /// Queue kernel notifications from outside request dispatch, e.g. from
+    /// another thread.
+    ///
+    /// The handle does not keep the queue open, so it no longer upgrades once
+    /// the session has ended. A later `set_notify_queue()` detaches it too.
+    pub fn deferred_notifier(&self) -> Weak<DeferredNotifier> {
+        Arc::downgrade(self.notify_tx.as_ref().expect("notify_tx missing"))
+    }
+
+    /// Track the requests in flight from outside the session, e.g. to list
+    /// or cancel them from another thread.
+    ///
+    /// The handle no longer upgrades once the session has been dropped.
+    pub(crate) fn in_flight(&self) -> Weak<InterruptTracker> {
+        Arc::downgrade(&self.interrupts)
+    }
+
     /// Run the session loop that receives kernel requests and dispatches them to method
-    /// calls into the filesystem. This read-dispatch-loop is non-concurrent to prevent
-    /// having multiple buffers (which take up much memory), but the filesystem methods
-    /// may run concurrent by spawning threads.
+    /// calls into the filesystem. Requests are read on a dedicated thread so that
+    /// interrupts are seen while a request is being dispatched; dispatching itself is
+    /// non-concurrent, but the filesystem methods may run concurrent by spawning threads.
     /// # Errors
     /// Returns any final error when the session comes to an end.
     pub fn run(&mut self) -> io::Result<()> {
@@ -168,6 +215,10 @@ impl<FS: Filesystem> Session<FS> {
                     NotifyOp::InvalEntry { parent, ref name } => {
                         notifier.inval_entry(parent, name.as_os_str())
                     }
+                    NotifyOp::InvalInode { ino, offset, len } => {
+                        notifier.inval_inode(ino, offset, len)
+                    }
+                    NotifyOp::Poll { kh } => notifier.poll(kh),
                 };
                 if let Err(e) = res {
                     debug!("FUSE notify failed: {e}");
@@ -177,49 +228,55 @@ impl<FS: Filesystem> Session<FS> {
 
         // A single DeferredNotifier shared by all requests in this session,
         // avoiding a Sender clone on every FUSE request dispatch.
-        let deferred =
-            DeferredNotifier::new(self.notify_tx.as_ref().expect("notify_tx missing").clone());
+        let deferred = self.notify_tx.as_ref().expect("notify_tx missing").clone();
+
+        // The reader thread exits by itself once the filesystem is unmounted, so
+        // it is not joined.
+        let interrupts = self.interrupts.clone();
+        let (request_tx, request_rx) = mpsc::channel();
+        let reader_ch = self.ch.clone();
+        let reader_interrupts = interrupts.clone();
+        thread::Builder::new()
+            .name(READER_THREAD_NAME.to_string())
+            .spawn(move || read_requests(&reader_ch, &reader_interrupts, &request_tx))?;
 
-        // Buffer for receiving requests from the kernel. Only one is allocated and
-        // it is reused immediately after dispatching to conserve memory and allocations.
-        let mut buffer = vec![0; BUFFER_SIZE];
-        let buf = aligned_sub_buf(&mut buffer, std::mem::align_of::<abi::fuse_in_header>());
         let mut result = Ok(());
-        loop {
-            // Read the next request from the given channel to kernel driver
-            // The kernel driver makes sure that we get exactly one request per read
-            match self.ch.receive(buf) {
-                Ok(size) => {
-                    match Request::new(self.ch.sender(), &deferred, &buf[..size]) {
-                        // Dispatch request
-                        Some(req) => req.dispatch(self),
-                        // Quit loop on illegal request
-                        None => break,
-                    }
+        for received in request_rx {
+            let received = match received {
+                Ok(received) => received,
+                Err(err) => {
+                    result = Err(err);
+                    break;
                 }
-                Err(err) => match err.raw_os_error() {
-                    Some(
-                          ENOENT // Operation interrupted. Accordingly to FUSE, this is safe to retry
-                        | EINTR // Interrupted system call, retry
-                        | EAGAIN // Explicitly instructed to try again
-                    ) => continue,
-                    Some(ENODEV) => break,
-                    // Unhandled error
-                    _ => {
-                        result = Err(err);
-                        break;
-                    }
-                },
+            };
+            match Request::new(
+                self.ch.sender(),
+                &deferred,
+                received.data(),
+                received.interrupted.clone(),
+            ) {
+                // Dispatch request
+                Some(req) => req.dispatch(self),
+                // Quit loop on illegal request
+                None => break,
             }
+            interrupts.finish(received.unique);
         }
 
         // Drop all senders to close the channel, then join the notify thread
         // to ensure in-flight invalidations are flushed before returning.
+        let stats = deferred.stats().clone();
         drop(deferred);
         self.notify_tx.take();
         if let Err(e) = notify_handle.join() {
             warn!("notify thread panicked: {e:?}");
         }
+        if stats.dropped() > 0 {
+            warn!(
+                "{} kernel cache notifications were dropped because the queue was full",
+                stats.dropped()
+            );
+        }
 
         result
     }
@@ -256,6 +313,99 @@ impl SessionUnmounter {
     }
 }
 
+/// A request read from the kernel, copied out of the reader's buffer.
+struct ReceivedRequest {
+    /// Request bytes, stored as words so they stay aligned for `fuse_in_header`
+    words: Vec<u64>,
+    len: usize,
+    unique: u64,
+    interrupted: CancellationToken,
+}
+
+const _: () = assert!(std::mem::align_of::<abi::fuse_in_header>() <= std::mem::align_of::<u64>());
+
+impl ReceivedRequest {
+    fn new(data: &[u8], unique: u64, interrupted: CancellationToken) -> Self {
+        let mut words = vec![0u64; data.len().div_ceil(8)];
+        // SAFETY: `words` spans at least `data.len()` bytes
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), words.as_mut_ptr().cast(), data.len());
+        }
+        Self {
+            words,
+            len: data.len(),
+            unique,
+            interrupted,
+        }
+    }
+
+    fn data(&self) -> &[u8] {
+        // SAFETY: The first `len` bytes of `words` were initialized in `new`
+        unsafe { std::slice::from_raw_parts(self.words.as_ptr().cast(), self.len) }
+    }
+}
+
+/// Read requests from the kernel until the filesystem is unmounted.
+///
+/// A read interrupted by a signal is retried, so signals delivered to the
+/// reader thread never end the session. Interrupts are applied to `interrupts` right away. Every other request is
+/// registered there and forwarded to the session loop, in kernel order.
+fn read_requests(
+    ch: &Channel,
+    interrupts: &InterruptTracker,
+    tx: &mpsc::Sender<io::Result<ReceivedRequest>>,
+) {
+    // Buffer for receiving requests from the kernel. Only one is allocated and
+    // it is reused immediately after a request is copied out.
+    let mut buffer = vec![0; BUFFER_SIZE];
+    let buf = aligned_sub_buf(&mut buffer, std::mem::align_of::<abi::fuse_in_header>());
+    loop {
+        // Read the next request from the given channel to kernel driver
+        // The kernel driver makes sure that we get exactly one request per read
+        let size = match ch.receive(buf) {
+            Ok(size) => size,
+            Err(err) => match err.raw_os_error() {
+                Some(
+                      ENOENT // Operation interrupted. Accordingly to FUSE, this is safe to retry
+                    | EINTR // Interrupted system call, retry
+                    | EAGAIN // Explicitly instructed to try again
+                ) => continue,
+                Some(ENODEV) => return,
+                // Unhandled error
+                _ => {
+                    let _ = tx.send(Err(err));
+                    return;
+                }
+            },
+        };
+        let data = &buf[..size];
+
+        // Illegal requests are forwarded as well; the session loop reports them
+        let (unique, opcode, ino) = match ll::AnyRequest::try_from(data) {
+            Ok(req) => {
+                if let Ok(ll::Operation::Interrupt(x)) = req.operation() {
+                    let target = x.unique().into();
+                    if !interrupts.interrupt(target) {
+                        debug!("INTERRUPT for finished request {target}");
+                    }
+                    continue;
+                }
+                (req.unique().into(), req.opcode(), req.nodeid().into())
+            }
+            Err(_) => (0, 0, 0),
+        };
+
+        let interrupted = interrupts.register(unique, opcode, ino);
+        if tx
+            .send(Ok(ReceivedRequest::new(data, unique, interrupted)))
+            .is_err()
+        {
+            // The session loop has stopped
+            return;
+        }
+    }
+}
+
 fn aligned_sub_buf(buf: &mut [u8], alignment: usize) -> &mut [u8] {
     let off = alignment - (buf.as_ptr() as usize) % alignment;
     if off == alignment {
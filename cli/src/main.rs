@@ -106,6 +106,15 @@ fn main() {
             no_default_allows,
             experimental_sandbox,
             strace,
+            events,
+            audit_log,
+            capture_stdout,
+            capture_stderr,
+            delta_in,
+            delta_out,
+            workdir,
+            create_workdir,
+            pid_namespace,
             session,
             system,
             key,
@@ -121,6 +130,15 @@ fn main() {
                 no_default_allows,
                 experimental_sandbox,
                 strace,
+                events,
+                audit_log,
+                capture_stdout,
+                capture_stderr,
+                delta_in,
+                delta_out,
+                workdir,
+                create_workdir,
+                pid_namespace,
                 session,
                 system,
                 encryption,
@@ -159,31 +177,88 @@ fn main() {
             uid,
             gid,
             backend,
-        } => match (id_or_path, mountpoint) {
-            (Some(id_or_path), Some(mountpoint)) => {
-                if let Err(e) = cmd::mount(cmd::MountArgs {
-                    id_or_path,
-                    mountpoint,
-                    auto_unmount,
-                    allow_root,
-                    allow_other: system,
-                    foreground,
-                    uid,
-                    gid,
-                    backend,
-                }) {
-                    eprintln!("Error: {}", e);
+            read_only,
+            create,
+            base,
+            delta,
+            options,
+            op_timeout,
+            max_read,
+            max_write,
+            attr_timeout,
+            entry_timeout,
+            subdir,
+            fsname,
+            json,
+            cleanup_stale,
+            verify,
+        } => {
+            // A --base overlay mounts its delta, which may not exist yet, and
+            // the only positional argument is the mountpoint
+            let (id_or_path, mountpoint, create) = match delta {
+                Some(delta) => match (id_or_path, mountpoint) {
+                    (Some(mountpoint), None) => (
+                        Some(delta),
+                        Some(std::path::PathBuf::from(mountpoint)),
+                        true,
+                    ),
+                    _ => {
+                        eprintln!("Error: --base and --delta take the place of ID_OR_PATH; give only MOUNTPOINT");
+                        std::process::exit(1);
+                    }
+                },
+                None => (id_or_path, mountpoint, create),
+            };
+            match (id_or_path, mountpoint) {
+                _ if cleanup_stale => {
+                    if let Err(e) = cmd::mount::cleanup_stale_mounts() {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                (Some(id_or_path), Some(mountpoint)) if verify => {
+                    if let Err(e) = cmd::mount::verify_mount(&id_or_path, &mountpoint) {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                (Some(id_or_path), Some(mountpoint)) => {
+                    if let Err(e) = cmd::mount(cmd::MountArgs {
+                        id_or_path,
+                        mountpoint,
+                        auto_unmount,
+                        allow_root,
+                        allow_other: system,
+                        foreground,
+                        uid,
+                        gid,
+                        backend,
+                        read_only,
+                        create,
+                        base,
+                        extra_options: options,
+                        op_timeout: op_timeout.map(std::time::Duration::from_secs),
+                        max_read,
+                        max_write,
+                        attr_timeout: attr_timeout.map(std::time::Duration::from_secs),
+                        entry_timeout: entry_timeout.map(std::time::Duration::from_secs),
+                        subdir,
+                        fsname,
+                        json,
+                    }) {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                (None, None) => {
+                    cmd::mount::list_mounts(&mut std::io::stdout());
+                }
+                _ => {
+                    eprintln!("Error: both ID_OR_PATH and MOUNTPOINT are required to mount");
                     std::process::exit(1);
                 }
             }
-            (None, None) => {
-                cmd::mount::list_mounts(&mut std::io::stdout());
-            }
-            _ => {
-                eprintln!("Error: both ID_OR_PATH and MOUNTPOINT are required to mount");
-                std::process::exit(1);
-            }
-        },
+        }
         Command::Diff { id_or_path } => {
             let rt = get_runtime();
             if let Err(e) = rt.block_on(cmd::fs::diff_filesystem(id_or_path)) {
@@ -256,6 +331,17 @@ fn main() {
                         std::process::exit(1);
                     }
                 }
+                FsCommand::Optimize { output, checkpoint } => {
+                    if let Err(e) = rt.block_on(cmd::fs::optimize_filesystem(
+                        id_or_path,
+                        &output,
+                        checkpoint,
+                        encryption.as_ref(),
+                    )) {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                }
             }
         }
         Command::Completions { command } => handle_completions(command),
@@ -264,10 +350,16 @@ fn main() {
             id_or_path,
             bind,
             port,
+            allow_clients,
         } => {
             eprintln!("Warning: `agentfs nfs` is deprecated, use `agentfs serve nfs` instead");
             let rt = get_runtime();
-            if let Err(e) = rt.block_on(cmd::nfs::handle_nfs_command(id_or_path, bind, port)) {
+            if let Err(e) = rt.block_on(cmd::nfs::handle_nfs_command(
+                id_or_path,
+                bind,
+                port,
+                allow_clients,
+            )) {
                 eprintln!("Error: {}", e);
                 std::process::exit(1);
             }
@@ -290,9 +382,15 @@ fn main() {
                 id_or_path,
                 bind,
                 port,
+                allow_clients,
             } => {
                 let rt = get_runtime();
-                if let Err(e) = rt.block_on(cmd::nfs::handle_nfs_command(id_or_path, bind, port)) {
+                if let Err(e) = rt.block_on(cmd::nfs::handle_nfs_command(
+                    id_or_path,
+                    bind,
+                    port,
+                    allow_clients,
+                )) {
                     eprintln!("Error: {}", e);
                     std::process::exit(1);
                 }
@@ -0,0 +1,328 @@
+//! Adapter exposing a filesystem shared behind a mutex as a plain
+//! `FileSystem`, for code that takes `Arc<dyn FileSystem>`.
+
+use std::ops::Deref;
+use std::sync::Arc;
+use tokio::sync::{Mutex, OwnedMutexGuard, RwLock, RwLockReadGuard};
+
+/// Adapter to use `Arc<Mutex<dyn FileSystem>>` as `Arc<dyn FileSystem>`.
+///
+/// The filesystem can be replaced while the adapter is in use; see `swap()`.
+pub(crate) struct MutexFsAdapter {
+    inner: RwLock<Arc<Mutex<dyn agentfs_sdk::FileSystem + Send>>>,
+}
+
+/// The adapted filesystem, locked for one operation.
+struct AdapterGuard<'a> {
+    fs: OwnedMutexGuard<dyn agentfs_sdk::FileSystem + Send>,
+    /// Holds off `swap()` until the operation is done
+    _slot: RwLockReadGuard<'a, Arc<Mutex<dyn agentfs_sdk::FileSystem + Send>>>,
+}
+
+impl Deref for AdapterGuard<'_> {
+    type Target = dyn agentfs_sdk::FileSystem + Send;
+
+    fn deref(&self) -> &Self::Target {
+        &self.fs
+    }
+}
+
+impl MutexFsAdapter {
+    pub(crate) fn new(fs: Arc<Mutex<dyn agentfs_sdk::FileSystem + Send>>) -> Self {
+        Self {
+            inner: RwLock::new(fs),
+        }
+    }
+
+    async fn lock(&self) -> AdapterGuard<'_> {
+        let slot = self.inner.read().await;
+        let fs = slot.clone().lock_owned().await;
+        AdapterGuard { fs, _slot: slot }
+    }
+
+    /// Replace the adapted filesystem, returning the previous one.
+    ///
+    /// Waits for operations already running to finish and holds off new ones
+    /// until the swap is done. `check` runs in between, with nothing in
+    /// flight; if it fails, the filesystem is left in place.
+    #[cfg(target_os = "linux")]
+    pub(crate) async fn swap(
+        &self,
+        fs: Arc<Mutex<dyn agentfs_sdk::FileSystem + Send>>,
+        check: impl FnOnce() -> anyhow::Result<()>,
+    ) -> anyhow::Result<Arc<Mutex<dyn agentfs_sdk::FileSystem + Send>>> {
+        let mut current = self.inner.write().await;
+        check()?;
+        Ok(std::mem::replace(&mut *current, fs))
+    }
+}
+
+#[async_trait::async_trait]
+impl agentfs_sdk::FileSystem for MutexFsAdapter {
+    async fn lookup(
+        &self,
+        parent_ino: i64,
+        name: &str,
+    ) -> std::result::Result<Option<agentfs_sdk::Stats>, agentfs_sdk::error::Error> {
+        self.lock().await.lookup(parent_ino, name).await
+    }
+
+    async fn exists(
+        &self,
+        parent_ino: i64,
+        name: &str,
+    ) -> std::result::Result<bool, agentfs_sdk::error::Error> {
+        self.lock().await.exists(parent_ino, name).await
+    }
+
+    async fn getattr(
+        &self,
+        ino: i64,
+    ) -> std::result::Result<Option<agentfs_sdk::Stats>, agentfs_sdk::error::Error> {
+        self.lock().await.getattr(ino).await
+    }
+
+    async fn readlink(
+        &self,
+        ino: i64,
+    ) -> std::result::Result<Option<String>, agentfs_sdk::error::Error> {
+        self.lock().await.readlink(ino).await
+    }
+
+    async fn readdir(
+        &self,
+        ino: i64,
+    ) -> std::result::Result<Option<Vec<String>>, agentfs_sdk::error::Error> {
+        self.lock().await.readdir(ino).await
+    }
+
+    async fn readdir_plus(
+        &self,
+        ino: i64,
+    ) -> std::result::Result<Option<Vec<agentfs_sdk::DirEntry>>, agentfs_sdk::error::Error> {
+        self.lock().await.readdir_plus(ino).await
+    }
+
+    async fn chmod(
+        &self,
+        ino: i64,
+        mode: u32,
+    ) -> std::result::Result<(), agentfs_sdk::error::Error> {
+        self.lock().await.chmod(ino, mode).await
+    }
+
+    async fn chown(
+        &self,
+        ino: i64,
+        uid: Option<u32>,
+        gid: Option<u32>,
+    ) -> std::result::Result<(), agentfs_sdk::error::Error> {
+        self.lock().await.chown(ino, uid, gid).await
+    }
+
+    async fn utimens(
+        &self,
+        ino: i64,
+        atime: agentfs_sdk::TimeChange,
+        mtime: agentfs_sdk::TimeChange,
+    ) -> std::result::Result<(), agentfs_sdk::error::Error> {
+        self.lock().await.utimens(ino, atime, mtime).await
+    }
+
+    async fn open(
+        &self,
+        ino: i64,
+        flags: i32,
+    ) -> std::result::Result<agentfs_sdk::BoxedFile, agentfs_sdk::error::Error> {
+        self.lock().await.open(ino, flags).await
+    }
+
+    async fn mkdir(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        mode: u32,
+        uid: u32,
+        gid: u32,
+    ) -> std::result::Result<agentfs_sdk::Stats, agentfs_sdk::error::Error> {
+        self.lock()
+            .await
+            .mkdir(parent_ino, name, mode, uid, gid)
+            .await
+    }
+
+    async fn create_file(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        mode: u32,
+        uid: u32,
+        gid: u32,
+    ) -> std::result::Result<(agentfs_sdk::Stats, agentfs_sdk::BoxedFile), agentfs_sdk::error::Error>
+    {
+        self.lock()
+            .await
+            .create_file(parent_ino, name, mode, uid, gid)
+            .await
+    }
+
+    async fn create_writer(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        mode: u32,
+        uid: u32,
+        gid: u32,
+    ) -> std::result::Result<agentfs_sdk::filesystem::FileWriter, agentfs_sdk::error::Error> {
+        self.lock()
+            .await
+            .create_writer(parent_ino, name, mode, uid, gid)
+            .await
+    }
+
+    async fn create_tmpfile(
+        &self,
+        mode: u32,
+        uid: u32,
+        gid: u32,
+    ) -> std::result::Result<(agentfs_sdk::Stats, agentfs_sdk::BoxedFile), agentfs_sdk::error::Error>
+    {
+        self.lock().await.create_tmpfile(mode, uid, gid).await
+    }
+
+    async fn remove_orphan(&self, ino: i64) -> std::result::Result<(), agentfs_sdk::error::Error> {
+        self.lock().await.remove_orphan(ino).await
+    }
+
+    async fn mknod(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        mode: u32,
+        rdev: u64,
+        uid: u32,
+        gid: u32,
+    ) -> std::result::Result<agentfs_sdk::Stats, agentfs_sdk::error::Error> {
+        self.lock()
+            .await
+            .mknod(parent_ino, name, mode, rdev, uid, gid)
+            .await
+    }
+
+    async fn symlink(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        target: &str,
+        uid: u32,
+        gid: u32,
+    ) -> std::result::Result<agentfs_sdk::Stats, agentfs_sdk::error::Error> {
+        self.lock()
+            .await
+            .symlink(parent_ino, name, target, uid, gid)
+            .await
+    }
+
+    async fn unlink(
+        &self,
+        parent_ino: i64,
+        name: &str,
+    ) -> std::result::Result<(), agentfs_sdk::error::Error> {
+        self.lock().await.unlink(parent_ino, name).await
+    }
+
+    async fn unlink_keep_orphan(
+        &self,
+        parent_ino: i64,
+        name: &str,
+    ) -> std::result::Result<Option<i64>, agentfs_sdk::error::Error> {
+        self.lock().await.unlink_keep_orphan(parent_ino, name).await
+    }
+
+    async fn rmdir(
+        &self,
+        parent_ino: i64,
+        name: &str,
+    ) -> std::result::Result<(), agentfs_sdk::error::Error> {
+        self.lock().await.rmdir(parent_ino, name).await
+    }
+
+    async fn link(
+        &self,
+        ino: i64,
+        newparent_ino: i64,
+        newname: &str,
+    ) -> std::result::Result<agentfs_sdk::Stats, agentfs_sdk::error::Error> {
+        self.lock().await.link(ino, newparent_ino, newname).await
+    }
+
+    async fn rename(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+    ) -> std::result::Result<(), agentfs_sdk::error::Error> {
+        self.lock()
+            .await
+            .rename(oldparent_ino, oldname, newparent_ino, newname)
+            .await
+    }
+
+    async fn rename_with_flags(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+        flags: u32,
+    ) -> std::result::Result<(), agentfs_sdk::error::Error> {
+        self.lock()
+            .await
+            .rename_with_flags(oldparent_ino, oldname, newparent_ino, newname, flags)
+            .await
+    }
+
+    async fn rename_keep_orphan(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+        flags: u32,
+    ) -> std::result::Result<Option<i64>, agentfs_sdk::error::Error> {
+        self.lock()
+            .await
+            .rename_keep_orphan(oldparent_ino, oldname, newparent_ino, newname, flags)
+            .await
+    }
+
+    async fn get_inode_flags(
+        &self,
+        ino: i64,
+    ) -> std::result::Result<u32, agentfs_sdk::error::Error> {
+        self.lock().await.get_inode_flags(ino).await
+    }
+
+    async fn set_inode_flags(
+        &self,
+        ino: i64,
+        flags: u32,
+    ) -> std::result::Result<(), agentfs_sdk::error::Error> {
+        self.lock().await.set_inode_flags(ino, flags).await
+    }
+
+    async fn statfs(
+        &self,
+    ) -> std::result::Result<agentfs_sdk::FilesystemStats, agentfs_sdk::error::Error> {
+        self.lock().await.statfs().await
+    }
+
+    async fn health(&self) -> std::result::Result<(), agentfs_sdk::error::Error> {
+        self.lock().await.health().await
+    }
+
+    async fn checkpoint(&self) -> std::result::Result<(), agentfs_sdk::error::Error> {
+        self.lock().await.checkpoint().await
+    }
+}
@@ -0,0 +1,114 @@
+//! Background write-ahead log checkpointing for mounted filesystems.
+
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+
+/// Periodically checkpoints a mounted filesystem's write-ahead log.
+///
+/// The task runs on the current Tokio runtime until the checkpointer is
+/// dropped. Cancellation is only observed between checkpoints, so a
+/// checkpoint that is already running always completes.
+pub(crate) struct Checkpointer {
+    cancel: CancellationToken,
+    interval: Duration,
+}
+
+impl Checkpointer {
+    /// Start checkpointing `fs` every `interval`.
+    pub(crate) fn spawn(
+        fs: Arc<Mutex<dyn agentfs_sdk::FileSystem + Send>>,
+        interval: Duration,
+    ) -> Self {
+        let cancel = CancellationToken::new();
+        let token = cancel.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            // The first tick completes immediately, before anything was written
+            ticker.tick().await;
+            loop {
+                tokio::select! {
+                    _ = token.cancelled() => break,
+                    _ = ticker.tick() => {}
+                }
+                if let Err(e) = fs.lock().await.checkpoint().await {
+                    tracing::warn!("WAL checkpoint failed: {}", e);
+                }
+            }
+        });
+        Self { cancel, interval }
+    }
+
+    /// Time between checkpoints.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn interval(&self) -> Duration {
+        self.interval
+    }
+}
+
+impl Drop for Checkpointer {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentfs_sdk::{AgentFS, AgentFSOptions, FileSystem, DEFAULT_FILE_MODE};
+
+    #[tokio::test]
+    async fn test_checkpointer_bounds_wal_growth() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("agent.db");
+        let wal_path = dir.path().join("agent.db-wal");
+        let agent = AgentFS::open(AgentFSOptions::with_path(db_path.to_str().unwrap()))
+            .await
+            .unwrap();
+        let fs: Arc<Mutex<dyn FileSystem + Send>> = Arc::new(Mutex::new(agent.fs));
+
+        let checkpointer = Checkpointer::spawn(fs.clone(), Duration::from_millis(10));
+
+        let (_, file) = fs
+            .lock()
+            .await
+            .create_file(1, "data", DEFAULT_FILE_MODE, 0, 0)
+            .await
+            .unwrap();
+
+        // Stay below SQLite's automatic checkpoint threshold, so only the
+        // checkpointer can keep the log from holding every write
+        let chunk = vec![0xa5u8; 64 * 1024];
+        let mut written = 0u64;
+        let mut max_wal = 0u64;
+        for round in 0..48u64 {
+            file.pwrite(round * chunk.len() as u64, &chunk)
+                .await
+                .unwrap();
+            written += chunk.len() as u64;
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            let wal = std::fs::metadata(&wal_path).map(|m| m.len()).unwrap_or(0);
+            max_wal = max_wal.max(wal);
+        }
+        assert!(
+            max_wal < written / 2,
+            "WAL grew to {} bytes after writing {} bytes",
+            max_wal,
+            written
+        );
+
+        // Once stopped, the task releases its reference to the filesystem
+        drop(checkpointer);
+        let start = std::time::Instant::now();
+        while Arc::strong_count(&fs) > 1 {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "task did not stop"
+            );
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+}
@@ -1,12 +1,18 @@
 //! FUSE backend implementation for the mount infrastructure.
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use std::path::Path;
 use std::process::Command;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
 
-use super::{wait_for_mount, MountBackend, MountHandle, MountHandleInner, MountOpts};
+use crate::fuse::{FuseControl, FS_ID_FILE};
+
+use super::{
+    wait_for_mount_cancellable, MountBackend, MountHandle, MountHandleInner, MountOpts,
+    MutexFsAdapter, PendingMount,
+};
 
 /// FUSE unmount implementation using fusermount.
 pub(super) fn unmount_fuse(mountpoint: &Path, lazy: bool) -> Result<()> {
@@ -33,8 +39,28 @@ pub(super) fn unmount_fuse(mountpoint: &Path, lazy: bool) -> Result<()> {
     )
 }
 
+/// Read the filesystem id an agentfs FUSE mount reports in its root.
+///
+/// Returns `None` if the mountpoint does not report one, as is the case for
+/// anything other than an agentfs FUSE mount.
+pub(super) fn read_fs_id(mountpoint: &Path) -> Result<Option<u64>> {
+    let path = mountpoint.join(FS_ID_FILE);
+    let id = match std::fs::read_to_string(&path) {
+        Ok(id) => id,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(e).with_context(|| {
+                format!("Failed to read filesystem id of {}", mountpoint.display())
+            })
+        }
+    };
+    let id = u64::from_str_radix(id.trim_end(), 16)
+        .with_context(|| format!("Invalid filesystem id in {}", path.display()))?;
+    Ok(Some(id))
+}
+
 /// Internal FUSE mount implementation.
-pub(super) fn mount_fuse(
+pub(super) async fn mount_fuse(
     fs: Arc<Mutex<dyn agentfs_sdk::FileSystem + Send>>,
     opts: MountOpts,
 ) -> Result<MountHandle> {
@@ -46,25 +72,41 @@ pub(super) fn mount_fuse(
         allow_root: opts.allow_root,
         allow_other: opts.allow_other,
         fsname: opts.fsname.clone(),
+        subtype: opts.subtype.clone(),
         uid: opts.uid,
         gid: opts.gid,
+        read_only: opts.read_only,
+        extra_options: opts.extra_options.clone(),
+        op_timeout: opts.op_timeout,
+        max_read: opts.max_read,
+        max_write: opts.max_write,
     };
 
     let mountpoint = opts.mountpoint.clone();
     let timeout = opts.timeout;
     let lazy_unmount = opts.lazy_unmount;
 
-    let fs_adapter = MutexFsAdapter { inner: fs };
-    let fs_arc: Arc<dyn agentfs_sdk::FileSystem> = Arc::new(fs_adapter);
+    let root_subdir = opts.root_subdir.clone();
+
+    let fs_adapter = Arc::new(MutexFsAdapter::new(fs));
+    let fs_arc: Arc<dyn agentfs_sdk::FileSystem> = fs_adapter.clone();
+    let control = Arc::new(FuseControl::default());
+    let session_control = control.clone();
 
-    let fuse_handle = std::thread::spawn(move || {
+    let mut pending = PendingMount::new(&opts);
+    pending.set_fuse_thread(std::thread::spawn(move || {
         let rt = crate::get_runtime();
-        crate::fuse::mount(fs_arc, fuse_opts, rt)
-    });
+        crate::fuse::mount_with_control(fs_arc, fuse_opts, rt, &session_control)
+    }));
 
-    if !wait_for_mount(&mountpoint, timeout) {
-        anyhow::bail!("FUSE mount did not become ready within {:?}", timeout);
-    }
+    wait_for_mount_cancellable(&mountpoint, timeout, &opts.cancel)
+        .await
+        .context("FUSE mount failed")?;
+
+    let fuse_handle = pending
+        .take_fuse_thread()
+        .expect("FUSE thread was just spawned");
+    pending.disarm();
 
     Ok(MountHandle {
         mountpoint,
@@ -72,195 +114,12 @@ pub(super) fn mount_fuse(
         lazy_unmount,
         inner: MountHandleInner::Fuse {
             _thread: fuse_handle,
+            fs: fs_adapter,
+            control,
+            root_subdir,
         },
+        checkpointer: None,
+        lock: None,
+        ready_duration: Duration::ZERO,
     })
 }
-
-/// Adapter to use `Arc<Mutex<dyn FileSystem>>` as `Arc<dyn FileSystem>`.
-struct MutexFsAdapter {
-    inner: Arc<Mutex<dyn agentfs_sdk::FileSystem + Send>>,
-}
-
-#[async_trait::async_trait]
-impl agentfs_sdk::FileSystem for MutexFsAdapter {
-    async fn lookup(
-        &self,
-        parent_ino: i64,
-        name: &str,
-    ) -> std::result::Result<Option<agentfs_sdk::Stats>, agentfs_sdk::error::Error> {
-        self.inner.lock().await.lookup(parent_ino, name).await
-    }
-
-    async fn getattr(
-        &self,
-        ino: i64,
-    ) -> std::result::Result<Option<agentfs_sdk::Stats>, agentfs_sdk::error::Error> {
-        self.inner.lock().await.getattr(ino).await
-    }
-
-    async fn readlink(
-        &self,
-        ino: i64,
-    ) -> std::result::Result<Option<String>, agentfs_sdk::error::Error> {
-        self.inner.lock().await.readlink(ino).await
-    }
-
-    async fn readdir(
-        &self,
-        ino: i64,
-    ) -> std::result::Result<Option<Vec<String>>, agentfs_sdk::error::Error> {
-        self.inner.lock().await.readdir(ino).await
-    }
-
-    async fn readdir_plus(
-        &self,
-        ino: i64,
-    ) -> std::result::Result<Option<Vec<agentfs_sdk::DirEntry>>, agentfs_sdk::error::Error> {
-        self.inner.lock().await.readdir_plus(ino).await
-    }
-
-    async fn chmod(
-        &self,
-        ino: i64,
-        mode: u32,
-    ) -> std::result::Result<(), agentfs_sdk::error::Error> {
-        self.inner.lock().await.chmod(ino, mode).await
-    }
-
-    async fn chown(
-        &self,
-        ino: i64,
-        uid: Option<u32>,
-        gid: Option<u32>,
-    ) -> std::result::Result<(), agentfs_sdk::error::Error> {
-        self.inner.lock().await.chown(ino, uid, gid).await
-    }
-
-    async fn utimens(
-        &self,
-        ino: i64,
-        atime: agentfs_sdk::TimeChange,
-        mtime: agentfs_sdk::TimeChange,
-    ) -> std::result::Result<(), agentfs_sdk::error::Error> {
-        self.inner.lock().await.utimens(ino, atime, mtime).await
-    }
-
-    async fn open(
-        &self,
-        ino: i64,
-        flags: i32,
-    ) -> std::result::Result<agentfs_sdk::BoxedFile, agentfs_sdk::error::Error> {
-        self.inner.lock().await.open(ino, flags).await
-    }
-
-    async fn mkdir(
-        &self,
-        parent_ino: i64,
-        name: &str,
-        mode: u32,
-        uid: u32,
-        gid: u32,
-    ) -> std::result::Result<agentfs_sdk::Stats, agentfs_sdk::error::Error> {
-        self.inner
-            .lock()
-            .await
-            .mkdir(parent_ino, name, mode, uid, gid)
-            .await
-    }
-
-    async fn create_file(
-        &self,
-        parent_ino: i64,
-        name: &str,
-        mode: u32,
-        uid: u32,
-        gid: u32,
-    ) -> std::result::Result<(agentfs_sdk::Stats, agentfs_sdk::BoxedFile), agentfs_sdk::error::Error>
-    {
-        self.inner
-            .lock()
-            .await
-            .create_file(parent_ino, name, mode, uid, gid)
-            .await
-    }
-
-    async fn mknod(
-        &self,
-        parent_ino: i64,
-        name: &str,
-        mode: u32,
-        rdev: u64,
-        uid: u32,
-        gid: u32,
-    ) -> std::result::Result<agentfs_sdk::Stats, agentfs_sdk::error::Error> {
-        self.inner
-            .lock()
-            .await
-            .mknod(parent_ino, name, mode, rdev, uid, gid)
-            .await
-    }
-
-    async fn symlink(
-        &self,
-        parent_ino: i64,
-        name: &str,
-        target: &str,
-        uid: u32,
-        gid: u32,
-    ) -> std::result::Result<agentfs_sdk::Stats, agentfs_sdk::error::Error> {
-        self.inner
-            .lock()
-            .await
-            .symlink(parent_ino, name, target, uid, gid)
-            .await
-    }
-
-    async fn unlink(
-        &self,
-        parent_ino: i64,
-        name: &str,
-    ) -> std::result::Result<(), agentfs_sdk::error::Error> {
-        self.inner.lock().await.unlink(parent_ino, name).await
-    }
-
-    async fn rmdir(
-        &self,
-        parent_ino: i64,
-        name: &str,
-    ) -> std::result::Result<(), agentfs_sdk::error::Error> {
-        self.inner.lock().await.rmdir(parent_ino, name).await
-    }
-
-    async fn link(
-        &self,
-        ino: i64,
-        newparent_ino: i64,
-        newname: &str,
-    ) -> std::result::Result<agentfs_sdk::Stats, agentfs_sdk::error::Error> {
-        self.inner
-            .lock()
-            .await
-            .link(ino, newparent_ino, newname)
-            .await
-    }
-
-    async fn rename(
-        &self,
-        oldparent_ino: i64,
-        oldname: &str,
-        newparent_ino: i64,
-        newname: &str,
-    ) -> std::result::Result<(), agentfs_sdk::error::Error> {
-        self.inner
-            .lock()
-            .await
-            .rename(oldparent_ino, oldname, newparent_ino, newname)
-            .await
-    }
-
-    async fn statfs(
-        &self,
-    ) -> std::result::Result<agentfs_sdk::FilesystemStats, agentfs_sdk::error::Error> {
-        self.inner.lock().await.statfs().await
-    }
-}
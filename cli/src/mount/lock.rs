@@ -0,0 +1,97 @@
+//! Exclusive locking of databases mounted read-write.
+//!
+//! Two read-write mounts of the same database cache and write the same
+//! inodes independently, which corrupts the filesystem. A read-write mount
+//! therefore holds an advisory `flock(2)` on `<database>.mount-lock` for as
+//! long as it is active. The kernel drops the lock when its holder exits,
+//! so a mount that crashed never blocks the next one. The lock file itself
+//! is left behind and reused.
+
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+/// Suffix appended to the database path to name its lock file.
+const LOCK_SUFFIX: &str = ".mount-lock";
+
+/// Mount lock of a database, released when dropped.
+#[derive(Debug)]
+pub(crate) struct MountLock {
+    _file: File,
+}
+
+impl MountLock {
+    /// Take the mount lock of the database at `db_path`.
+    ///
+    /// Fails without waiting if another read-write mount holds it.
+    pub(crate) fn acquire(db_path: &Path) -> Result<Self> {
+        let db_path = std::fs::canonicalize(db_path).unwrap_or_else(|_| db_path.to_path_buf());
+        let path = lock_path(&db_path);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .with_context(|| format!("Failed to open mount lock {}", path.display()))?;
+
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            let err = std::io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::EWOULDBLOCK) {
+                return Err(err)
+                    .with_context(|| format!("Failed to take mount lock {}", path.display()));
+            }
+            let mut holder = String::new();
+            let _ = file.read_to_string(&mut holder);
+            let holder = match holder.trim() {
+                "" => String::new(),
+                pid => format!(" by process {}", pid),
+            };
+            anyhow::bail!(
+                "Database {} is already mounted read-write{}. Unmount it first, or mount it read-only.",
+                db_path.display(),
+                holder
+            );
+        }
+
+        // Record the holder for the error shown to later mounts
+        file.set_len(0)
+            .and_then(|_| file.write_all(format!("{}\n", std::process::id()).as_bytes()))
+            .with_context(|| format!("Failed to write mount lock {}", path.display()))?;
+
+        Ok(Self { _file: file })
+    }
+}
+
+/// Path of the lock file guarding `db_path`.
+fn lock_path(db_path: &Path) -> PathBuf {
+    let mut path = db_path.as_os_str().to_owned();
+    path.push(LOCK_SUFFIX);
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mount_lock_is_exclusive_until_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("agent.db");
+
+        let lock = MountLock::acquire(&db_path).unwrap();
+        let err = MountLock::acquire(&db_path).unwrap_err();
+        assert!(err.to_string().contains("already mounted read-write"));
+        assert!(err
+            .to_string()
+            .contains(&format!("by process {}", std::process::id())));
+
+        // The lock file outlives the lock, as it would after a crash, and
+        // does not block the next mount
+        drop(lock);
+        assert!(lock_path(&db_path).exists());
+        MountLock::acquire(&db_path).unwrap();
+    }
+}
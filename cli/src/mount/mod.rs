@@ -13,23 +13,60 @@
 //! // ... use the mounted filesystem ...
 //! drop(handle); // auto-unmounts
 //! ```
+//!
+//! # Threading model
+//!
+//! `mount_fs()` itself runs on the caller's runtime, but neither backend
+//! serves requests there. The FUSE session runs on a dedicated thread with
+//! its own multi-threaded runtime. The NFS server, including the tasks it
+//! spawns per connection, runs on the runtime given by `MountOpts::runtime`,
+//! or on a multi-threaded runtime owned by the `MountHandle` if none is
+//! given. A mount therefore keeps serving requests even when the caller's
+//! runtime is single-threaded and busy, or blocked waiting on the mount.
+//! Only the write-ahead log checkpointer runs on the caller's runtime.
 
+mod adapter;
+mod checkpoint;
 #[cfg(target_os = "linux")]
 mod fuse;
+mod lock;
 mod nfs;
+mod subdir;
 
 use anyhow::Result;
+use std::net::IpAddr;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 use tokio_util::sync::CancellationToken;
 
 pub use crate::opts::MountBackend;
+pub(crate) use adapter::MutexFsAdapter;
+use checkpoint::Checkpointer;
+pub(crate) use lock::MountLock;
+pub(crate) use subdir::with_root_subdir;
+#[cfg(target_os = "linux")]
+pub(crate) use subdir::SubdirFs;
 
 /// Default timeout for mount to become ready.
 const DEFAULT_MOUNT_TIMEOUT: Duration = Duration::from_secs(10);
 
+/// Default filesystem subtype, shown as `fuse.agentfs` in mount output.
+pub const DEFAULT_SUBTYPE: &str = "agentfs";
+
+/// FUSE device checked when resolving `MountBackend::Auto`.
+const FUSE_DEVICE: &str = "/dev/fuse";
+
+/// Smallest `max_read` and `max_write` the kernel accepts for a FUSE mount.
+pub const MIN_FUSE_IO_SIZE: u32 = 4096;
+
+/// Pages a single FUSE request may span.
+///
+/// The session speaks FUSE ABI 7.18, which predates negotiating
+/// `max_pages`, so the kernel keeps its default of 32 pages per request.
+const FUSE_MAX_PAGES_PER_REQ: u32 = 32;
+
 /// Options for mounting a filesystem.
 ///
 /// This struct provides a unified configuration for both FUSE and NFS backends.
@@ -42,6 +79,8 @@ pub struct MountOpts {
     pub backend: MountBackend,
     /// Filesystem name shown in mount output.
     pub fsname: String,
+    /// Filesystem subtype shown in mount output (FUSE only).
+    pub subtype: Option<String>,
     /// User ID to report for all files.
     pub uid: Option<u32>,
     /// Group ID to report for all files.
@@ -54,8 +93,78 @@ pub struct MountOpts {
     pub auto_unmount: bool,
     /// Use lazy unmount on cleanup.
     pub lazy_unmount: bool,
+    /// Raw mount options appended to the backend's option string (e.g.
+    /// `noexec`, `max_read=131072`).
+    ///
+    /// Options managed by other fields or by the backend are rejected by
+    /// `validate()`. The rest are passed through unchecked, so an option the
+    /// backend does not understand makes the mount fail.
+    pub extra_options: Vec<String>,
+    /// Fail a filesystem operation with EIO if it takes longer than this, so
+    /// a stalled backend cannot hang processes using the mount (FUSE only).
+    pub op_timeout: Option<Duration>,
+    /// Largest read the kernel sends in a single request (FUSE only).
+    ///
+    /// Must be between `MIN_FUSE_IO_SIZE` and `max_fuse_io_size()`. If
+    /// `None`, reads are as large as the kernel allows.
+    pub max_read: Option<u32>,
+    /// Largest write the kernel sends in a single request (FUSE only).
+    ///
+    /// Larger writes mean fewer requests for large sequential I/O. Must be
+    /// between `MIN_FUSE_IO_SIZE` and `max_fuse_io_size()`. If `None`,
+    /// writes are as large as the kernel allows.
+    pub max_write: Option<u32>,
+    /// How long NFS clients may cache file attributes before asking the
+    /// server again (NFS only).
+    ///
+    /// Shorter timeouts make writes by other clients visible sooner at the
+    /// cost of more requests. Zero disables attribute caching. If `None`,
+    /// the client's defaults apply.
+    pub attr_timeout: Option<Duration>,
+    /// How long NFS clients may cache directory entries and directory
+    /// attributes before asking the server again (NFS only).
+    ///
+    /// Zero disables the lookup cache, so names created or removed by other
+    /// clients are seen at once. If `None`, the client's defaults apply.
+    pub entry_timeout: Option<Duration>,
+    /// Client addresses allowed to connect to the NFS server (NFS only).
+    ///
+    /// `None` accepts every client that can reach the listener.
+    pub allowed_clients: Option<Vec<IpAddr>>,
+    /// Checkpoint the filesystem's write-ahead log this often while mounted,
+    /// so the log stays small on long-lived mounts. Disabled if `None`.
+    pub checkpoint_interval: Option<Duration>,
+    /// Database backing the filesystem.
+    ///
+    /// A read-write mount takes the database's mount lock and fails if
+    /// another read-write mount already holds it. Not locked if `None`.
+    pub database: Option<PathBuf>,
+    /// Mount without write access.
+    ///
+    /// Read-only mounts do not take the database's mount lock, so any
+    /// number of them can share a database.
+    pub read_only: bool,
+    /// Directory of the filesystem to mount as the mount root, instead of
+    /// the filesystem root.
+    ///
+    /// Resolved once at mount time; it must exist and be a directory. Names
+    /// that would lead out of it, such as `..` in the mount root, stay
+    /// inside it.
+    pub root_subdir: Option<PathBuf>,
+    /// Runtime to run the NFS server on (NFS only).
+    ///
+    /// If `None`, the server gets a dedicated multi-threaded runtime that is
+    /// shut down with the mount. Passing a handle shares an existing runtime
+    /// instead; it should be multi-threaded, or the server competes with the
+    /// caller's own tasks.
+    pub runtime: Option<tokio::runtime::Handle>,
     /// Timeout for mount to become ready.
     pub timeout: Duration,
+    /// Token to abort the mount while it is being established.
+    ///
+    /// Cancelling it before `mount_fs()` returns tears down any partially
+    /// created mount. It has no effect on an already returned `MountHandle`.
+    pub cancel: CancellationToken,
 }
 
 impl MountOpts {
@@ -65,23 +174,120 @@ impl MountOpts {
             mountpoint,
             backend,
             fsname: "agentfs".to_string(),
+            subtype: Some(DEFAULT_SUBTYPE.to_string()),
             uid: None,
             gid: None,
             allow_other: false,
             allow_root: false,
             auto_unmount: false,
             lazy_unmount: false,
+            extra_options: Vec::new(),
+            op_timeout: None,
+            max_read: None,
+            max_write: None,
+            attr_timeout: None,
+            entry_timeout: None,
+            allowed_clients: None,
+            checkpoint_interval: None,
+            database: None,
+            read_only: false,
+            root_subdir: None,
+            runtime: None,
             timeout: DEFAULT_MOUNT_TIMEOUT,
+            cancel: CancellationToken::new(),
         }
     }
 }
 
+impl MountOpts {
+    /// Take the mount lock of `database`, unless the mount is read-only.
+    fn lock_database(&self) -> Result<Option<MountLock>> {
+        match &self.database {
+            Some(database) if !self.read_only => Ok(Some(MountLock::acquire(database)?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Check that the filesystem name, subtype, request sizes and extra
+    /// options can be passed safely as mount options.
+    pub fn validate(&self) -> Result<()> {
+        validate_fsname(&self.fsname)?;
+        if let Some(subtype) = &self.subtype {
+            validate_subtype(subtype)?;
+        }
+        if let Some(max_read) = self.max_read {
+            validate_fuse_io_size("max_read", max_read)?;
+            if self
+                .extra_options
+                .iter()
+                .any(|option| option.starts_with("max_read="))
+            {
+                anyhow::bail!("max_read is set both in max_read and as an extra mount option");
+            }
+        }
+        if let Some(max_write) = self.max_write {
+            validate_fuse_io_size("max_write", max_write)?;
+        }
+        if self.attr_timeout.is_some() || self.entry_timeout.is_some() {
+            validate_nfs_cache_options(&self.extra_options)?;
+        }
+        for option in &self.extra_options {
+            validate_extra_option(option)?;
+        }
+        Ok(())
+    }
+}
+
 impl Default for MountOpts {
     fn default() -> Self {
         Self::new(PathBuf::new(), MountBackend::default())
     }
 }
 
+impl MountBackend {
+    /// Resolve `Auto` to a concrete backend.
+    ///
+    /// `Auto` picks FUSE on Linux when `/dev/fuse` is accessible, and NFS
+    /// otherwise. Concrete backends are returned unchanged.
+    pub fn resolve(self) -> MountBackend {
+        self.resolve_with(Path::new(FUSE_DEVICE))
+    }
+
+    fn resolve_with(self, fuse_device: &Path) -> MountBackend {
+        if !matches!(self, MountBackend::Auto) {
+            return self;
+        }
+
+        let fuse_available = cfg!(target_os = "linux") && is_accessible(fuse_device);
+        let backend = if fuse_available {
+            MountBackend::Fuse
+        } else {
+            MountBackend::Nfs
+        };
+        tracing::info!(
+            "Auto-selected {} mount backend ({} {})",
+            backend,
+            fuse_device.display(),
+            if fuse_available {
+                "is accessible"
+            } else {
+                "is not accessible"
+            }
+        );
+        backend
+    }
+}
+
+/// Check whether the current user can open a file for reading and writing.
+fn is_accessible(path: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    unsafe { libc::access(path.as_ptr(), libc::R_OK | libc::W_OK) == 0 }
+}
+
 /// A mounted filesystem handle. Automatically unmounts when dropped.
 ///
 /// This handle represents an active mount and provides RAII-style cleanup.
@@ -91,16 +297,28 @@ pub struct MountHandle {
     backend: MountBackend,
     lazy_unmount: bool,
     inner: MountHandleInner,
+    checkpointer: Option<Checkpointer>,
+    /// Released only after the unmount in `drop()`.
+    lock: Option<MountLock>,
+    /// Time from the start of the mount until it was ready.
+    ready_duration: Duration,
 }
 
 pub(crate) enum MountHandleInner {
     #[cfg(target_os = "linux")]
     Fuse {
         _thread: std::thread::JoinHandle<anyhow::Result<()>>,
+        /// Filesystem served by the session, replaceable by `swap_fs()`
+        fs: Arc<MutexFsAdapter>,
+        /// Notifications and open files of the session
+        control: Arc<crate::fuse::FuseControl>,
+        root_subdir: Option<PathBuf>,
     },
     Nfs {
         shutdown: CancellationToken,
         _server_handle: tokio::task::JoinHandle<()>,
+        _runtime: nfs::ServerRuntime,
+        port: u32,
     },
 }
 
@@ -109,10 +327,170 @@ impl MountHandle {
     pub fn mountpoint(&self) -> &Path {
         &self.mountpoint
     }
+
+    /// Start checkpointing `fs` every `interval` for as long as the mount
+    /// is active.
+    fn with_checkpointer(
+        mut self,
+        fs: Arc<Mutex<dyn agentfs_sdk::FileSystem + Send>>,
+        interval: Option<Duration>,
+    ) -> Self {
+        self.checkpointer = interval.map(|interval| Checkpointer::spawn(fs, interval));
+        self
+    }
+
+    /// Hold the database's mount lock for as long as the mount is active.
+    fn with_lock(mut self, lock: Option<MountLock>) -> Self {
+        self.lock = lock;
+        self
+    }
+
+    /// Record that the mount, started at `start`, is now ready.
+    fn ready_since(mut self, start: Instant) -> Self {
+        self.ready_duration = start.elapsed();
+        tracing::info!(
+            "{} mount at {} ready after {:?}",
+            self.backend,
+            self.mountpoint.display(),
+            self.ready_duration
+        );
+        self
+    }
+
+    /// How long the mount took to become ready.
+    ///
+    /// This is the time from the start of `mount_fs()` (or
+    /// `mount_nfs_with_listener()`) until the mountpoint showed up, which
+    /// includes starting the FUSE session or NFS server and the kernel mount.
+    pub fn ready_duration(&self) -> Duration {
+        self.ready_duration
+    }
+
+    /// Replace the filesystem served by the mount with `fs`, without
+    /// unmounting.
+    ///
+    /// Operations already running on the current filesystem finish first,
+    /// and new ones wait until the swap is done. The kernel's caches of the
+    /// previous filesystem are then invalidated, so processes using the mount
+    /// see the contents of `fs` from then on. The root subdirectory and
+    /// checkpoint interval of the mount apply to `fs` as well; the mount lock
+    /// stays with the original database. Files unlinked while in use are
+    /// released from the previous filesystem.
+    ///
+    /// Fails, leaving the mount unchanged, if the backend is not FUSE, the
+    /// FUSE session is not running, or files are open through the mount, as
+    /// their handles would keep using the previous filesystem.
+    #[cfg(target_os = "linux")]
+    pub async fn swap_fs(
+        &mut self,
+        fs: Arc<Mutex<dyn agentfs_sdk::FileSystem + Send>>,
+    ) -> Result<()> {
+        let (adapter, control, root_subdir) = match &self.inner {
+            MountHandleInner::Fuse {
+                fs: adapter,
+                control,
+                root_subdir,
+                ..
+            } => (adapter, control, root_subdir),
+            MountHandleInner::Nfs { .. } => {
+                anyhow::bail!("Swapping the filesystem of a mount requires the FUSE backend")
+            }
+        };
+        let Some(notifier) = control.notifier() else {
+            anyhow::bail!(
+                "FUSE session at {} is not running",
+                self.mountpoint.display()
+            );
+        };
+        let served = with_root_subdir(fs.clone(), root_subdir.as_deref()).await?;
+
+        let mut orphans = Vec::new();
+        let previous = adapter
+            .swap(served, || match control.open_files() {
+                0 => {
+                    orphans = control.take_lookups();
+                    Ok(())
+                }
+                open => anyhow::bail!(
+                    "{} files are open at {}; close them before swapping the filesystem",
+                    open,
+                    self.mountpoint.display()
+                ),
+            })
+            .await?;
+
+        // Files unlinked while in use belong to the previous filesystem
+        {
+            let previous = previous.lock().await;
+            for ino in orphans {
+                if let Err(e) = previous.remove_orphan(ino as i64).await {
+                    tracing::warn!("Failed to release unlinked inode {}: {}", ino, e);
+                }
+            }
+        }
+
+        // Invalidating an entry drops the dentries below it as well, but the
+        // kernel may keep its inode, pages included, and reuse it for a
+        // lookup in `fs` that returns the same inode number
+        let (entries, inodes) = cached_tree(previous).await;
+        for (parent, name) in entries {
+            notifier.inval_entry(parent as u64, std::ffi::OsStr::new(&name));
+        }
+        for ino in inodes {
+            notifier.inval_inode(ino as u64, 0, -1);
+        }
+
+        if let Some(interval) = self.checkpointer.as_ref().map(Checkpointer::interval) {
+            self.checkpointer = Some(Checkpointer::spawn(fs, interval));
+        }
+        Ok(())
+    }
+
+    /// List the FUSE requests the mount is serving, oldest first.
+    ///
+    /// Each entry names the operation, the inode it is for and when the
+    /// kernel sent it; its `id` can be passed to `cancel_op()`. Fails if the
+    /// backend is not FUSE.
+    #[cfg(target_os = "linux")]
+    pub fn in_flight_ops(&self) -> Result<Vec<crate::fuser::InFlightOp>> {
+        match &self.inner {
+            MountHandleInner::Fuse { control, .. } => Ok(control.in_flight_ops()),
+            MountHandleInner::Nfs { .. } => {
+                anyhow::bail!("Listing in-flight operations requires the FUSE backend")
+            }
+        }
+    }
+
+    /// Cancel an in-flight FUSE request, as listed by `in_flight_ops()`.
+    ///
+    /// The operation is abandoned and the process that issued it gets EINTR,
+    /// as if it had been interrupted by a signal. Returns false if the
+    /// request has already finished. Fails if the backend is not FUSE.
+    #[cfg(target_os = "linux")]
+    pub fn cancel_op(&self, id: u64) -> Result<bool> {
+        match &self.inner {
+            MountHandleInner::Fuse { control, .. } => Ok(control.cancel_op(id)),
+            MountHandleInner::Nfs { .. } => {
+                anyhow::bail!("Cancelling operations requires the FUSE backend")
+            }
+        }
+    }
+
+    /// Get the port of the NFS server backing the mount, if any.
+    pub fn nfs_port(&self) -> Option<u32> {
+        match &self.inner {
+            MountHandleInner::Nfs { port, .. } => Some(*port),
+            #[cfg(target_os = "linux")]
+            MountHandleInner::Fuse { .. } => None,
+        }
+    }
 }
 
 impl Drop for MountHandle {
     fn drop(&mut self) {
+        // No checkpoints once unmounting starts
+        self.checkpointer = None;
+
         // Move away from mountpoint before unmounting to avoid EBUSY
         let _ = std::env::set_current_dir("/");
 
@@ -144,10 +522,144 @@ impl Drop for MountHandle {
     }
 }
 
+/// Entries and inodes of `fs` the kernel may have cached, root included.
+///
+/// Directories that cannot be listed are skipped.
+#[cfg(target_os = "linux")]
+async fn cached_tree(
+    fs: Arc<Mutex<dyn agentfs_sdk::FileSystem + Send>>,
+) -> (Vec<(i64, String)>, Vec<i64>) {
+    const ROOT_INO: i64 = 1;
+
+    let fs = fs.lock().await;
+    let mut entries = Vec::new();
+    let mut inodes = std::collections::HashSet::from([ROOT_INO]);
+    let mut dirs = vec![ROOT_INO];
+    while let Some(dir) = dirs.pop() {
+        let listing = match fs.readdir_plus(dir).await {
+            Ok(listing) => listing.unwrap_or_default(),
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to list directory {} of swapped out filesystem: {}",
+                    dir,
+                    e
+                );
+                continue;
+            }
+        };
+        for entry in listing {
+            if entry.name == "." || entry.name == ".." {
+                continue;
+            }
+            if entry.stats.is_directory() {
+                dirs.push(entry.stats.ino);
+            }
+            inodes.insert(entry.stats.ino);
+            entries.push((dir, entry.name));
+        }
+    }
+    (entries, inodes.into_iter().collect())
+}
+
+/// Tears down a mount that is still being established.
+///
+/// `mount_fs()` arms this guard before starting a backend and disarms it once
+/// the `MountHandle` is built. If the mount future fails, is cancelled through
+/// `MountOpts::cancel`, or is dropped mid-flight, the guard unmounts whatever
+/// was set up so far and stops the backend.
+pub(crate) struct PendingMount {
+    mountpoint: PathBuf,
+    backend: MountBackend,
+    #[cfg(target_os = "linux")]
+    timeout: Duration,
+    #[cfg(target_os = "linux")]
+    fuse_thread: Option<std::thread::JoinHandle<anyhow::Result<()>>>,
+    nfs_server: Option<tokio::task::AbortHandle>,
+    armed: bool,
+}
+
+impl PendingMount {
+    pub(crate) fn new(opts: &MountOpts) -> Self {
+        Self {
+            mountpoint: opts.mountpoint.clone(),
+            backend: opts.backend,
+            #[cfg(target_os = "linux")]
+            timeout: opts.timeout,
+            #[cfg(target_os = "linux")]
+            fuse_thread: None,
+            nfs_server: None,
+            armed: true,
+        }
+    }
+
+    /// Track the thread running the FUSE session.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn set_fuse_thread(&mut self, thread: std::thread::JoinHandle<anyhow::Result<()>>) {
+        self.fuse_thread = Some(thread);
+    }
+
+    /// Take back the FUSE session thread once the mount is ready.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn take_fuse_thread(
+        &mut self,
+    ) -> Option<std::thread::JoinHandle<anyhow::Result<()>>> {
+        self.fuse_thread.take()
+    }
+
+    /// Track the task running the NFS server.
+    pub(crate) fn set_nfs_server(&mut self, server: tokio::task::AbortHandle) {
+        self.nfs_server = Some(server);
+    }
+
+    /// The mount is fully established; ownership passes to `MountHandle`.
+    pub(crate) fn disarm(&mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for PendingMount {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+
+        if let Some(server) = self.nfs_server.take() {
+            server.abort();
+        }
+
+        if is_mountpoint(&self.mountpoint) {
+            let _ = unmount(&self.mountpoint, self.backend, true);
+            return;
+        }
+
+        // The FUSE session thread may still be about to mount. Watch for it in
+        // the background and unmount as soon as it shows up, so an aborted
+        // mount never lingers.
+        #[cfg(target_os = "linux")]
+        if let Some(thread) = self.fuse_thread.take() {
+            let mountpoint = self.mountpoint.clone();
+            let backend = self.backend;
+            let timeout = self.timeout;
+            std::thread::spawn(move || {
+                let start = std::time::Instant::now();
+                while start.elapsed() < timeout && !thread.is_finished() {
+                    if is_mountpoint(&mountpoint) {
+                        let _ = unmount(&mountpoint, backend, true);
+                        break;
+                    }
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+            });
+        }
+    }
+}
+
 /// Unmount a filesystem at the given mountpoint.
 ///
 /// This function handles unmounting for both FUSE and NFS backends.
 /// If `lazy` is true, uses lazy unmount which detaches immediately even if busy.
+/// `MountBackend::Auto` unmounts with the backend the mount was made with, as
+/// recorded in the mount table, since `/dev/fuse` may have changed since.
 pub fn unmount(mountpoint: &Path, backend: MountBackend, lazy: bool) -> Result<()> {
     match backend {
         #[cfg(target_os = "linux")]
@@ -155,30 +667,103 @@ pub fn unmount(mountpoint: &Path, backend: MountBackend, lazy: bool) -> Result<(
         #[cfg(not(target_os = "linux"))]
         MountBackend::Fuse => anyhow::bail!("FUSE is not supported on this platform"),
         MountBackend::Nfs => nfs::unmount_nfs(mountpoint, lazy),
+        MountBackend::Auto => match mounted_backend(mountpoint) {
+            Some(backend) => unmount(mountpoint, backend, lazy),
+            None => anyhow::bail!("{} is not mounted", mountpoint.display()),
+        },
+    }
+}
+
+/// Find the backend serving the topmost mount at `mountpoint`.
+#[cfg(target_os = "linux")]
+fn mounted_backend(mountpoint: &Path) -> Option<MountBackend> {
+    let mountpoint = mountpoint.canonicalize().ok()?;
+    let contents = std::fs::read_to_string("/proc/self/mounts").ok()?;
+    backend_from_mounts(&contents, &mountpoint)
+}
+
+/// Only NFS mounts are made on other platforms.
+#[cfg(not(target_os = "linux"))]
+fn mounted_backend(_mountpoint: &Path) -> Option<MountBackend> {
+    Some(MountBackend::Nfs)
+}
+
+/// Find the backend of the last entry for `mountpoint` in a mount table in
+/// `/proc/mounts` format.
+#[cfg(target_os = "linux")]
+fn backend_from_mounts(contents: &str, mountpoint: &Path) -> Option<MountBackend> {
+    let fstype = contents
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let mounted_at = agentfs_sdk::unescape_mount_field(parts.get(1)?);
+            (Path::new(&mounted_at) == mountpoint).then_some(*parts.get(2)?)
+        })
+        .last()?;
+    if fstype == "fuse" || fstype.starts_with("fuse.") {
+        Some(MountBackend::Fuse)
+    } else if fstype.starts_with("nfs") {
+        Some(MountBackend::Nfs)
+    } else {
+        None
     }
 }
 
+/// Read the id of the filesystem served at `mountpoint`.
+///
+/// The id is the database's inode generation, chosen when the database was
+/// created or copied by a snapshot. Returns `None` if the mountpoint does
+/// not report one; only FUSE mounts do.
+#[cfg(target_os = "linux")]
+pub fn read_fs_id(mountpoint: &Path) -> Result<Option<u64>> {
+    fuse::read_fs_id(mountpoint)
+}
+
 /// Mount a filesystem with the given options.
 ///
 /// Returns a handle that automatically unmounts when dropped.
 /// The filesystem must be wrapped in `Arc<Mutex<dyn FileSystem + Send>>`.
+///
+/// The returned future is cancel-safe: if it is dropped, or `opts.cancel` is
+/// cancelled before the mount becomes ready, any partially established mount
+/// is torn down and no mount is left behind.
 #[cfg(target_os = "linux")]
 pub async fn mount_fs(
     fs: Arc<Mutex<dyn agentfs_sdk::FileSystem + Send>>,
-    opts: MountOpts,
+    mut opts: MountOpts,
 ) -> Result<MountHandle> {
-    match opts.backend {
-        MountBackend::Fuse => fuse::mount_fuse(fs, opts),
-        MountBackend::Nfs => nfs::mount_nfs(fs, opts).await,
+    let start = Instant::now();
+    if opts.cancel.is_cancelled() {
+        anyhow::bail!("Mount of {} was cancelled", opts.mountpoint.display());
     }
+    opts.validate()?;
+    opts.backend = opts.backend.resolve();
+    let lock = opts.lock_database()?;
+    let checkpoint_interval = opts.checkpoint_interval;
+    let served = with_root_subdir(fs.clone(), opts.root_subdir.as_deref()).await?;
+    let handle = match opts.backend {
+        MountBackend::Fuse => fuse::mount_fuse(served, opts).await?,
+        MountBackend::Nfs => nfs::mount_nfs(served, opts).await?,
+        MountBackend::Auto => unreachable!("backend was resolved above"),
+    };
+    Ok(handle
+        .ready_since(start)
+        .with_checkpointer(fs, checkpoint_interval)
+        .with_lock(lock))
 }
 
 /// Mount a filesystem with the given options (macOS version).
 #[cfg(target_os = "macos")]
 pub async fn mount_fs(
     fs: Arc<Mutex<dyn agentfs_sdk::FileSystem + Send>>,
-    opts: MountOpts,
+    mut opts: MountOpts,
 ) -> Result<MountHandle> {
+    let start = Instant::now();
+    if opts.cancel.is_cancelled() {
+        anyhow::bail!("Mount of {} was cancelled", opts.mountpoint.display());
+    }
+    opts.validate()?;
+    opts.backend = opts.backend.resolve();
     match opts.backend {
         MountBackend::Fuse => {
             anyhow::bail!(
@@ -186,8 +771,265 @@ pub async fn mount_fs(
                  Use --backend nfs (default) instead."
             );
         }
-        MountBackend::Nfs => nfs::mount_nfs(fs, opts).await,
+        MountBackend::Nfs => {
+            let lock = opts.lock_database()?;
+            let checkpoint_interval = opts.checkpoint_interval;
+            let served = with_root_subdir(fs.clone(), opts.root_subdir.as_deref()).await?;
+            let handle = nfs::mount_nfs(served, opts).await?;
+            Ok(handle
+                .ready_since(start)
+                .with_checkpointer(fs, checkpoint_interval)
+                .with_lock(lock))
+        }
+        MountBackend::Auto => unreachable!("backend was resolved above"),
+    }
+}
+
+/// Mount a filesystem over NFS, serving it on an already bound listener.
+///
+/// Instead of binding its own localhost port, the NFS server accepts
+/// connections on `listener`, for instance a socket passed in by systemd
+/// socket activation or by a larger server embedding AgentFS. A listener on
+/// the wildcard address is mounted over loopback.
+///
+/// `opts.backend` must be NFS or `Auto`. As with `mount_fs()`, the returned
+/// handle stops the server and unmounts when dropped.
+pub async fn mount_nfs_with_listener(
+    fs: Arc<Mutex<dyn agentfs_sdk::FileSystem + Send>>,
+    mut opts: MountOpts,
+    listener: std::net::TcpListener,
+) -> Result<MountHandle> {
+    let start = Instant::now();
+    if opts.cancel.is_cancelled() {
+        anyhow::bail!("Mount of {} was cancelled", opts.mountpoint.display());
+    }
+    opts.validate()?;
+    if matches!(opts.backend, MountBackend::Fuse) {
+        anyhow::bail!("Serving over an existing listener requires the NFS backend");
+    }
+    opts.backend = MountBackend::Nfs;
+    let lock = opts.lock_database()?;
+    let checkpoint_interval = opts.checkpoint_interval;
+    let served = with_root_subdir(fs.clone(), opts.root_subdir.as_deref()).await?;
+    let handle = nfs::mount_nfs_with_listener(served, opts, listener).await?;
+    Ok(handle
+        .ready_since(start)
+        .with_checkpointer(fs, checkpoint_interval)
+        .with_lock(lock))
+}
+
+/// Validate a filesystem name for use in the `fsname=` mount option.
+///
+/// Mount options are a comma-separated list, so a comma would split the name
+/// into a separate option. Control characters and backslashes are mangled by
+/// the escaping in `/proc/mounts`.
+pub fn validate_fsname(fsname: &str) -> Result<()> {
+    if fsname.is_empty() {
+        anyhow::bail!("Invalid fsname: must not be empty");
+    }
+    if let Some(c) = fsname.chars().find(|c| is_fsname_forbidden(*c)) {
+        anyhow::bail!("Invalid fsname {:?}: must not contain {:?}", fsname, c);
+    }
+    Ok(())
+}
+
+/// Whether `c` could end the `fsname=` option and start another.
+fn is_fsname_forbidden(c: char) -> bool {
+    c == ',' || c == '\\' || c.is_control()
+}
+
+/// Default filesystem name for a mount of the database at `db_path`, so
+/// that `mount` and `df` output tell mounts of different databases apart.
+///
+/// The path is made absolute when possible. Characters `validate_fsname`
+/// rejects are replaced with `_`, so the name is always a valid fsname.
+pub fn default_fsname(db_path: &Path) -> String {
+    let path = std::fs::canonicalize(db_path).unwrap_or_else(|_| db_path.to_path_buf());
+    let path: String = path
+        .to_string_lossy()
+        .chars()
+        .map(|c| if is_fsname_forbidden(c) { '_' } else { c })
+        .collect();
+    format!("agentfs:{}", path)
+}
+
+/// Validate a filesystem subtype for use in the `subtype=` mount option.
+///
+/// The subtype becomes part of the filesystem type (`fuse.<subtype>`), so it
+/// is restricted to ASCII alphanumerics, `-` and `_`.
+pub fn validate_subtype(subtype: &str) -> Result<()> {
+    if subtype.is_empty() {
+        anyhow::bail!("Invalid subtype: must not be empty");
+    }
+    if let Some(c) = subtype
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '_'))
+    {
+        anyhow::bail!("Invalid subtype {:?}: must not contain {:?}", subtype, c);
+    }
+    Ok(())
+}
+
+/// Largest `max_read` and `max_write` the kernel honours for a FUSE mount.
+///
+/// Every request is capped at `FUSE_MAX_PAGES_PER_REQ` pages, so larger
+/// values would be silently cut down to this.
+pub fn max_fuse_io_size() -> u32 {
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    let page_size = u32::try_from(page_size).unwrap_or(MIN_FUSE_IO_SIZE);
+    FUSE_MAX_PAGES_PER_REQ * page_size
+}
+
+/// Validate a `max_read` or `max_write` request size for a FUSE mount.
+pub fn validate_fuse_io_size(name: &str, size: u32) -> Result<()> {
+    let max = max_fuse_io_size();
+    if !(MIN_FUSE_IO_SIZE..=max).contains(&size) {
+        anyhow::bail!(
+            "Invalid {} {}: must be between {} and {} bytes",
+            name,
+            size,
+            MIN_FUSE_IO_SIZE,
+            max
+        );
+    }
+    Ok(())
+}
+
+/// NFS client options controlling attribute and lookup caching.
+///
+/// They are set from `MountOpts::attr_timeout` and `MountOpts::entry_timeout`
+/// when either is given, and may be passed as extra options otherwise.
+const NFS_CACHE_OPTIONS: &[&str] = &[
+    "ac",
+    "noac",
+    "actimeo",
+    "acregmin",
+    "acregmax",
+    "acdirmin",
+    "acdirmax",
+    "lookupcache",
+    "negnamecache",
+    "nonegnamecache",
+];
+
+/// NFS client options for the given attribute and entry cache timeouts.
+///
+/// NFSv3 gives the server no say in how long clients cache, so the timeouts
+/// are applied as client mount options. Timeouts are whole seconds; a
+/// fraction of a second is rounded up, so only zero disables caching. A zero
+/// entry timeout also turns off the client's cache of name lookups, which is
+/// otherwise kept for as long as the directory's attributes.
+pub fn nfs_cache_options(
+    attr_timeout: Option<Duration>,
+    entry_timeout: Option<Duration>,
+) -> Vec<String> {
+    let secs = |timeout: Duration| timeout.as_secs() + u64::from(timeout.subsec_nanos() > 0);
+    let mut options = Vec::new();
+    if let Some(timeout) = attr_timeout {
+        let secs = secs(timeout);
+        options.push(format!("acregmin={}", secs));
+        options.push(format!("acregmax={}", secs));
+    }
+    if let Some(timeout) = entry_timeout {
+        let secs = secs(timeout);
+        options.push(format!("acdirmin={}", secs));
+        options.push(format!("acdirmax={}", secs));
+        if secs == 0 {
+            if cfg!(target_os = "macos") {
+                options.push("nonegnamecache".to_string());
+            } else {
+                options.push("lookupcache=none".to_string());
+            }
+        }
+    }
+    options
+}
+
+/// Check that no extra option sets NFS caching, when the cache timeouts
+/// are set by agentfs.
+pub fn validate_nfs_cache_options(extra_options: &[String]) -> Result<()> {
+    for option in extra_options {
+        let name = option.split('=').next().unwrap_or(option);
+        if NFS_CACHE_OPTIONS.contains(&name) {
+            anyhow::bail!(
+                "Invalid mount option {:?}: conflicts with the attribute and entry cache timeouts",
+                option
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Mount options set by `MountOpts` fields or by the backends themselves.
+///
+/// Passing one of these as an extra option would conflict with (or silently
+/// override) the value agentfs relies on, so they are rejected.
+const RESERVED_OPTIONS: &[&str] = &[
+    // FUSE
+    "fsname",
+    "subtype",
+    "allow_other",
+    "allow_root",
+    "auto_unmount",
+    "default_permissions",
+    "fd",
+    "rootmode",
+    "user_id",
+    "group_id",
+    "ro",
+    "rw",
+    // NFS
+    "vers",
+    "nfsvers",
+    "proto",
+    "tcp",
+    "udp",
+    "port",
+    "mountport",
+    "nolock",
+    "locallocks",
+    "soft",
+    "hard",
+    "timeo",
+    "retrans",
+];
+
+/// Validate a raw mount option passed through `MountOpts::extra_options`.
+///
+/// Each entry must be a single `name` or `name=value` option: separators and
+/// control characters could smuggle in further options, and options in
+/// `RESERVED_OPTIONS` conflict with ones agentfs manages.
+pub fn validate_extra_option(option: &str) -> Result<()> {
+    if option.is_empty() {
+        anyhow::bail!("Invalid mount option: must not be empty");
+    }
+    if let Some(c) = option
+        .chars()
+        .find(|c| *c == ',' || *c == '\\' || c.is_whitespace() || c.is_control())
+    {
+        anyhow::bail!(
+            "Invalid mount option {:?}: must not contain {:?}",
+            option,
+            c
+        );
     }
+    let name = option.split('=').next().unwrap_or(option);
+    if RESERVED_OPTIONS.contains(&name) {
+        anyhow::bail!(
+            "Invalid mount option {:?}: {} is managed by agentfs",
+            option,
+            name
+        );
+    }
+    Ok(())
+}
+
+/// Append already validated extra options to a backend's option string.
+pub fn join_mount_options(base: String, extra_options: &[String]) -> String {
+    std::iter::once(base)
+        .chain(extra_options.iter().cloned())
+        .collect::<Vec<_>>()
+        .join(",")
 }
 
 /// Wait for a path to become a mountpoint.
@@ -204,6 +1046,31 @@ pub fn wait_for_mount(path: &Path, timeout: Duration) -> bool {
     false
 }
 
+/// Wait for a path to become a mountpoint, giving up early on cancellation.
+///
+/// Unlike `wait_for_mount()`, this does not block the async runtime.
+pub(crate) async fn wait_for_mount_cancellable(
+    path: &Path,
+    timeout: Duration,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    let start = std::time::Instant::now();
+    let interval = Duration::from_millis(50);
+
+    while start.elapsed() < timeout {
+        if is_mountpoint(path) {
+            return Ok(());
+        }
+        tokio::select! {
+            _ = cancel.cancelled() => {
+                anyhow::bail!("Mount of {} was cancelled", path.display());
+            }
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+    anyhow::bail!("Mount did not become ready within {:?}", timeout)
+}
+
 /// Check if a path is a mountpoint by comparing device IDs with parent.
 pub fn is_mountpoint(path: &Path) -> bool {
     #[cfg(unix)]
@@ -234,3 +1101,764 @@ pub fn is_mountpoint(path: &Path) -> bool {
         false
     }
 }
+
+/// Mount `fs` for a test, failing with the reason if it cannot be mounted.
+///
+/// Tests that mount are `#[ignore]`d, since FUSE needs access to `/dev/fuse`
+/// and NFS needs root; `cargo test -- --ignored` runs them.
+#[cfg(all(test, target_os = "linux"))]
+pub(crate) async fn mount_for_test(
+    fs: Arc<Mutex<dyn agentfs_sdk::FileSystem + Send>>,
+    opts: MountOpts,
+) -> MountHandle {
+    if matches!(opts.backend, MountBackend::Fuse) {
+        assert!(
+            is_accessible(Path::new(FUSE_DEVICE)),
+            "cannot mount FUSE: {} is not accessible",
+            FUSE_DEVICE
+        );
+    }
+    let mountpoint = opts.mountpoint.clone();
+    mount_fs(fs, opts)
+        .await
+        .unwrap_or_else(|e| panic!("cannot mount {}: {:#}", mountpoint.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentfs_sdk::{AgentFS, AgentFSOptions, FileSystem, DEFAULT_FILE_MODE};
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    #[ignore = "mounts NFS, which needs root"]
+    async fn test_cancelled_nfs_mount_leaves_no_mount() {
+        use agentfs_sdk::filesystem::{Fault, MockFileSystem};
+
+        let dir = tempfile::tempdir().unwrap();
+        let agent = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        // Counted outside the mount's lock, so calls can be watched while
+        // the server is handling them
+        let backend = Arc::new(MockFileSystem::new(Arc::new(agent.fs)));
+        backend.inject("getattr", Fault::Delay(Duration::from_millis(200)));
+        let fs: Arc<Mutex<dyn FileSystem + Send>> =
+            Arc::new(Mutex::new(MockFileSystem::new(backend.clone())));
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let opts = MountOpts::new(dir.path().to_path_buf(), MountBackend::Nfs);
+        let cancel = opts.cancel.clone();
+        let mount = tokio::spawn(mount_nfs_with_listener(fs, opts, listener));
+
+        // The first getattr reads the filesystem generation before serving.
+        // Later ones come from the kernel's NFS client while the mount
+        // command waits for them, so the mount is half established.
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while backend.count("getattr") < 2 {
+            if mount.is_finished() {
+                let result = mount.await.unwrap();
+                panic!("cannot mount {}: {:?}", dir.path().display(), result.err());
+            }
+            assert!(Instant::now() < deadline, "the NFS client never called");
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        cancel.cancel();
+
+        let err = mount.await.unwrap().err().unwrap();
+        assert!(err.to_string().contains("cancelled"), "{:#}", err);
+        assert!(!is_mountpoint(dir.path()));
+
+        // The server was stopped, releasing its port
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while std::net::TcpListener::bind(("127.0.0.1", port)).is_err() {
+            assert!(Instant::now() < deadline, "port {} is still in use", port);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mount_fs_rejects_invalid_fsname() {
+        let dir = tempfile::tempdir().unwrap();
+        let agent = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        let fs: Arc<Mutex<dyn FileSystem + Send>> = Arc::new(Mutex::new(agent.fs));
+
+        let mut opts = MountOpts::new(dir.path().to_path_buf(), MountBackend::Nfs);
+        opts.fsname = "agentfs,allow_other".to_string();
+
+        let err = mount_fs(fs, opts).await.err().unwrap();
+        assert!(err.to_string().contains("Invalid fsname"));
+        assert!(!is_mountpoint(dir.path()));
+    }
+
+    #[tokio::test]
+    async fn test_mount_fs_rejects_missing_root_subdir() {
+        let dir = tempfile::tempdir().unwrap();
+        let agent = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        let fs: Arc<Mutex<dyn FileSystem + Send>> = Arc::new(Mutex::new(agent.fs));
+
+        let mut opts = MountOpts::new(dir.path().to_path_buf(), MountBackend::Nfs);
+        opts.root_subdir = Some(PathBuf::from("/projects/foo"));
+
+        let err = mount_fs(fs, opts).await.err().unwrap();
+        assert!(err.to_string().contains("does not exist"));
+        assert!(!is_mountpoint(dir.path()));
+    }
+
+    /// Ephemeral filesystem holding `/data` with `contents`.
+    #[cfg(target_os = "linux")]
+    async fn fs_with_data(contents: &[u8]) -> Arc<Mutex<dyn FileSystem + Send>> {
+        let agent = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        let (_, file) = FileSystem::create_file(&agent.fs, 1, "data", DEFAULT_FILE_MODE, 0, 0)
+            .await
+            .unwrap();
+        file.pwrite(0, contents).await.unwrap();
+        Arc::new(Mutex::new(agent.fs))
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    #[ignore = "mounts FUSE, which needs access to /dev/fuse"]
+    async fn test_swap_fs_under_fuse_mount() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data");
+
+        let opts = MountOpts::new(dir.path().to_path_buf(), MountBackend::Fuse);
+        let mut handle = mount_for_test(fs_with_data(b"first").await, opts).await;
+        assert_eq!(std::fs::read(&path).unwrap(), b"first");
+
+        handle
+            .swap_fs(fs_with_data(b"second, and longer").await)
+            .await
+            .unwrap();
+        assert!(is_mountpoint(dir.path()));
+        assert_eq!(std::fs::read(&path).unwrap(), b"second, and longer");
+
+        // An open file would keep using the filesystem it was opened on
+        let open = std::fs::File::open(&path).unwrap();
+        let err = handle
+            .swap_fs(fs_with_data(b"third").await)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("files are open"));
+        assert_eq!(std::fs::read(&path).unwrap(), b"second, and longer");
+        drop(open);
+
+        drop(handle);
+        assert!(!is_mountpoint(dir.path()));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    #[ignore = "mounts FUSE, which needs access to /dev/fuse"]
+    async fn test_mount_reports_ready_duration() {
+        let dir = tempfile::tempdir().unwrap();
+        let opts = MountOpts::new(dir.path().to_path_buf(), MountBackend::Fuse);
+        let timeout = opts.timeout;
+
+        let handle = mount_for_test(fs_with_data(b"data").await, opts).await;
+        assert!(handle.ready_duration() > Duration::ZERO);
+        assert!(handle.ready_duration() < timeout);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    #[ignore = "mounts FUSE, which needs access to /dev/fuse"]
+    async fn test_mmap_of_truncated_file_raises_sigbus() {
+        use std::os::fd::AsRawFd;
+
+        let dir = tempfile::tempdir().unwrap();
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let opts = MountOpts::new(dir.path().to_path_buf(), MountBackend::Fuse);
+
+        let _handle = mount_for_test(fs_with_data(&vec![7u8; 2 * page]).await, opts).await;
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(dir.path().join("data"))
+            .unwrap();
+        let map = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                2 * page,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        assert_ne!(map, libc::MAP_FAILED);
+        let bytes = map as *const u8;
+        assert_eq!(unsafe { bytes.add(page).read_volatile() }, 7);
+
+        file.set_len((page / 2) as u64).unwrap();
+
+        // The page holding the new end of file stays mapped, zeroed past it
+        assert_eq!(unsafe { bytes.read_volatile() }, 7);
+        assert_eq!(unsafe { bytes.add(page / 2).read_volatile() }, 0);
+
+        // Touching the removed page raises SIGBUS, so it is done in a child
+        let pid = unsafe { libc::fork() };
+        assert!(pid >= 0);
+        if pid == 0 {
+            unsafe {
+                let no_core = libc::rlimit {
+                    rlim_cur: 0,
+                    rlim_max: 0,
+                };
+                libc::setrlimit(libc::RLIMIT_CORE, &no_core);
+                bytes.add(page).read_volatile();
+                libc::_exit(0);
+            }
+        }
+        let mut status = 0;
+        assert_eq!(unsafe { libc::waitpid(pid, &mut status, 0) }, pid);
+        assert!(libc::WIFSIGNALED(status));
+        assert_eq!(libc::WTERMSIG(status), libc::SIGBUS);
+
+        unsafe { libc::munmap(map, 2 * page) };
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    #[ignore = "mounts FUSE, which needs access to /dev/fuse"]
+    async fn test_unlinked_open_file_under_fuse_mount() {
+        use std::io::{Read, Seek, SeekFrom, Write};
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data");
+        let fs = fs_with_data(b"data").await;
+
+        let opts = MountOpts::new(dir.path().to_path_buf(), MountBackend::Fuse);
+        let _handle = mount_for_test(fs.clone(), opts).await;
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+        let ino = file.metadata().unwrap().ino() as i64;
+        std::fs::remove_file(&path).unwrap();
+        assert!(!path.exists());
+
+        // The open file keeps working, and its inode stays while it is open
+        file.seek(SeekFrom::End(0)).unwrap();
+        file.write_all(b", and more").unwrap();
+        file.sync_all().unwrap();
+        let mut contents = String::new();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "data, and more");
+        assert!(fs.lock().await.getattr(ino).await.unwrap().is_some());
+
+        // Closing it lets the kernel forget the inode, which is then released
+        drop(file);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while fs.lock().await.getattr(ino).await.unwrap().is_some() {
+            assert!(
+                Instant::now() < deadline,
+                "unlinked inode was never released"
+            );
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    #[ignore = "mounts FUSE, which needs access to /dev/fuse"]
+    async fn test_cancel_in_flight_op() {
+        use agentfs_sdk::filesystem::{Fault, MockFileSystem};
+
+        let dir = tempfile::tempdir().unwrap();
+        let agent = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        let mock = MockFileSystem::new(Arc::new(agent.fs));
+        mock.inject("mkdir", Fault::Hang);
+        let fs: Arc<Mutex<dyn FileSystem + Send>> = Arc::new(Mutex::new(mock));
+
+        let opts = MountOpts::new(dir.path().to_path_buf(), MountBackend::Fuse);
+        let handle = mount_for_test(fs, opts).await;
+        let slow = dir.path().join("slow");
+        let mkdir = std::thread::spawn(move || std::fs::create_dir(slow));
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let op = loop {
+            let ops = handle.in_flight_ops().unwrap();
+            if let Some(op) = ops.into_iter().find(|op| op.op == "MKDIR") {
+                break op;
+            }
+            assert!(Instant::now() < deadline, "MKDIR never showed up in flight");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+        assert_eq!(op.ino, 1);
+        assert!(op.started <= std::time::SystemTime::now());
+
+        assert!(handle.cancel_op(op.id).unwrap());
+        let err = mkdir.join().unwrap().unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EINTR));
+    }
+
+    /// Thread IDs of this process's threads named `name`.
+    #[cfg(target_os = "linux")]
+    fn threads_named(name: &str) -> Vec<libc::pid_t> {
+        std::fs::read_dir("/proc/self/task")
+            .unwrap()
+            .filter_map(|task| {
+                let task = task.ok()?;
+                let comm = std::fs::read_to_string(task.path().join("comm")).ok()?;
+                if comm.trim_end() != name {
+                    return None;
+                }
+                task.file_name().to_str()?.parse().ok()
+            })
+            .collect()
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    #[ignore = "mounts FUSE, which needs access to /dev/fuse"]
+    async fn test_fuse_mount_survives_signals() {
+        extern "C" fn ignore_signal(_: libc::c_int) {}
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data");
+
+        let opts = MountOpts::new(dir.path().to_path_buf(), MountBackend::Fuse);
+        let handle = mount_for_test(fs_with_data(b"before").await, opts).await;
+
+        // A handler without SA_RESTART makes the blocked read of /dev/fuse
+        // fail with EINTR instead of being restarted by the kernel
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = ignore_signal as usize;
+            libc::sigemptyset(&mut action.sa_mask);
+            assert_eq!(
+                libc::sigaction(libc::SIGUSR2, &action, std::ptr::null_mut()),
+                0
+            );
+        }
+        let readers = threads_named(crate::fuser::READER_THREAD_NAME);
+        assert!(!readers.is_empty());
+        for _ in 0..10 {
+            for &tid in &readers {
+                unsafe { libc::syscall(libc::SYS_tgkill, libc::getpid(), tid, libc::SIGUSR2) };
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        assert!(is_mountpoint(dir.path()));
+        assert_eq!(std::fs::read(&path).unwrap(), b"before");
+        std::fs::write(&path, b"after").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"after");
+
+        drop(handle);
+        assert!(!is_mountpoint(dir.path()));
+    }
+
+    #[tokio::test]
+    async fn test_mount_nfs_with_listener_serves_on_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let agent = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        let fs: Arc<Mutex<dyn FileSystem + Send>> = Arc::new(Mutex::new(agent.fs));
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let opts = MountOpts::new(dir.path().to_path_buf(), MountBackend::Auto);
+        let cancel = opts.cancel.clone();
+        let mount = tokio::spawn(mount_nfs_with_listener(fs, opts, listener));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        assert_eq!(nfs_null_call(&mut stream, 0x1234).await.unwrap(), 0x1234);
+
+        // Without NFS client privileges the mount itself fails; either way
+        // the server must be torn down afterwards
+        cancel.cancel();
+        if let Ok(handle) = mount.await.unwrap() {
+            drop(handle);
+        }
+        assert!(!is_mountpoint(dir.path()));
+    }
+
+    /// Record-marked NFSPROC3_NULL call with the given xid.
+    fn nfs_null_request(xid: u32) -> Vec<u8> {
+        // xid, CALL, RPC v2, NFS program v3, proc 0, AUTH_NULL credentials
+        // and verifier
+        let call: [u32; 10] = [xid, 0, 2, 100003, 3, 0, 0, 0, 0, 0];
+        let mut request = (0x8000_0000u32 | 40).to_be_bytes().to_vec();
+        for word in call {
+            request.extend_from_slice(&word.to_be_bytes());
+        }
+        request
+    }
+
+    /// Send an NFSPROC3_NULL call and return the xid of the reply.
+    async fn nfs_null_call(stream: &mut tokio::net::TcpStream, xid: u32) -> std::io::Result<u32> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        stream.write_all(&nfs_null_request(xid)).await?;
+        let mut reply = [0u8; 8];
+        stream.read_exact(&mut reply).await?;
+        Ok(u32::from_be_bytes(reply[4..].try_into().unwrap()))
+    }
+
+    /// Blocking variant of `nfs_null_call()` on a fresh connection.
+    fn nfs_null_call_blocking(addr: std::net::SocketAddr, xid: u32) -> std::io::Result<u32> {
+        use std::io::{Read, Write};
+
+        let mut stream = std::net::TcpStream::connect(addr)?;
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        stream.write_all(&nfs_null_request(xid))?;
+        let mut reply = [0u8; 8];
+        stream.read_exact(&mut reply)?;
+        Ok(u32::from_be_bytes(reply[4..].try_into().unwrap()))
+    }
+
+    /// Connect to `addr` from the given loopback source address.
+    async fn connect_from(source: &str, addr: std::net::SocketAddr) -> tokio::net::TcpStream {
+        let socket = tokio::net::TcpSocket::new_v4().unwrap();
+        socket
+            .bind(format!("{}:0", source).parse().unwrap())
+            .unwrap();
+        socket.connect(addr).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_mount_nfs_serves_concurrent_clients_in_isolation() {
+        let dir = tempfile::tempdir().unwrap();
+        let agent = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        let fs: Arc<Mutex<dyn FileSystem + Send>> = Arc::new(Mutex::new(agent.fs));
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut opts = MountOpts::new(dir.path().to_path_buf(), MountBackend::Nfs);
+        opts.allowed_clients = Some(vec!["127.0.0.2".parse().unwrap()]);
+        let cancel = opts.cancel.clone();
+        let mount = tokio::spawn(mount_nfs_with_listener(fs, opts, listener));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        // Two connections from the allowed client, held open at the same
+        // time, reuse the same xid. Retransmission tracking is per
+        // connection, so neither call is mistaken for a duplicate of the
+        // other and both are answered.
+        let mut first = connect_from("127.0.0.2", addr).await;
+        let mut second = connect_from("127.0.0.2", addr).await;
+        let (a, b) = tokio::join!(
+            nfs_null_call(&mut first, 0x42),
+            nfs_null_call(&mut second, 0x42)
+        );
+        assert_eq!(a.unwrap(), 0x42);
+        assert_eq!(b.unwrap(), 0x42);
+
+        // A client outside the allow list is disconnected without a reply
+        let mut refused = connect_from("127.0.0.1", addr).await;
+        assert!(nfs_null_call(&mut refused, 0x43).await.is_err());
+
+        // The allowed connections are unaffected
+        assert_eq!(nfs_null_call(&mut first, 0x44).await.unwrap(), 0x44);
+
+        cancel.cancel();
+        if let Ok(handle) = mount.await.unwrap() {
+            drop(handle);
+        }
+        assert!(!is_mountpoint(dir.path()));
+    }
+
+    #[test]
+    fn test_mount_nfs_serves_while_current_thread_runtime_is_blocked() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let agent = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+            let fs: Arc<Mutex<dyn FileSystem + Send>> = Arc::new(Mutex::new(agent.fs));
+
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap();
+
+            let opts = MountOpts::new(dir.path().to_path_buf(), MountBackend::Nfs);
+            let cancel = opts.cancel.clone();
+            let mount = tokio::spawn(mount_nfs_with_listener(fs, opts, listener));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+
+            // Block the caller's only runtime thread while several clients
+            // call concurrently. The server runs on its own runtime, so all
+            // of them are still answered.
+            let clients: Vec<_> = (0..4u32)
+                .map(|xid| std::thread::spawn(move || nfs_null_call_blocking(addr, xid)))
+                .collect();
+            for (xid, client) in clients.into_iter().enumerate() {
+                assert_eq!(client.join().unwrap().unwrap(), xid as u32);
+            }
+
+            cancel.cancel();
+            if let Ok(handle) = mount.await.unwrap() {
+                drop(handle);
+            }
+            assert!(!is_mountpoint(dir.path()));
+        });
+    }
+
+    #[tokio::test]
+    async fn test_mount_fs_rejects_second_read_write_mount() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("agent.db");
+        let agent = AgentFS::open(AgentFSOptions::with_path(db_path.to_str().unwrap()))
+            .await
+            .unwrap();
+        let fs: Arc<Mutex<dyn FileSystem + Send>> = Arc::new(Mutex::new(agent.fs));
+        let first_mountpoint = tempfile::tempdir().unwrap();
+        let second_mountpoint = tempfile::tempdir().unwrap();
+
+        // The first mount holds the lock while it is being established
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut opts = MountOpts::new(first_mountpoint.path().to_path_buf(), MountBackend::Nfs);
+        opts.database = Some(db_path.clone());
+        let cancel = opts.cancel.clone();
+        let first = tokio::spawn(mount_nfs_with_listener(fs.clone(), opts, listener));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let mut opts = MountOpts::new(second_mountpoint.path().to_path_buf(), MountBackend::Nfs);
+        opts.database = Some(db_path.clone());
+        let err = mount_fs(fs.clone(), opts.clone()).await.err().unwrap();
+        assert!(err.to_string().contains("already mounted read-write"));
+        assert!(!is_mountpoint(second_mountpoint.path()));
+
+        // Read-only mounts may share the database
+        opts.read_only = true;
+        assert!(opts.lock_database().unwrap().is_none());
+
+        // The lock is released once the first mount is gone
+        cancel.cancel();
+        if let Ok(handle) = first.await.unwrap() {
+            drop(handle);
+        }
+        opts.read_only = false;
+        assert!(opts.lock_database().unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_mount_nfs_with_listener_rejects_fuse() {
+        let dir = tempfile::tempdir().unwrap();
+        let agent = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        let fs: Arc<Mutex<dyn FileSystem + Send>> = Arc::new(Mutex::new(agent.fs));
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let opts = MountOpts::new(dir.path().to_path_buf(), MountBackend::Fuse);
+        let err = mount_nfs_with_listener(fs, opts, listener)
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("requires the NFS backend"));
+    }
+
+    #[test]
+    fn test_auto_backend_resolution() {
+        let dir = tempfile::tempdir().unwrap();
+        let device = dir.path().join("fuse");
+        std::fs::write(&device, b"").unwrap();
+
+        #[cfg(target_os = "linux")]
+        assert!(matches!(
+            MountBackend::Auto.resolve_with(&device),
+            MountBackend::Fuse
+        ));
+        #[cfg(not(target_os = "linux"))]
+        assert!(matches!(
+            MountBackend::Auto.resolve_with(&device),
+            MountBackend::Nfs
+        ));
+
+        let missing = dir.path().join("missing");
+        assert!(matches!(
+            MountBackend::Auto.resolve_with(&missing),
+            MountBackend::Nfs
+        ));
+
+        // Explicit backends are never overridden
+        assert!(matches!(
+            MountBackend::Fuse.resolve_with(&missing),
+            MountBackend::Fuse
+        ));
+        assert!(matches!(
+            MountBackend::Nfs.resolve_with(&device),
+            MountBackend::Nfs
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_backend_from_mounts() {
+        let contents = "\
+/dev/sda1 / ext4 rw 0 0
+agentfs:/data/a.db /mnt/my\\040agent fuse.agentfs rw,nosuid 0 0
+agentfs:/data/b.db /mnt/nfs nfs rw,vers=3 0 0
+agentfs:/data/c.db /mnt/shadowed nfs rw,vers=3 0 0
+tmpfs /mnt/shadowed tmpfs rw 0 0
+";
+        assert!(matches!(
+            backend_from_mounts(contents, Path::new("/mnt/my agent")),
+            Some(MountBackend::Fuse)
+        ));
+        assert!(matches!(
+            backend_from_mounts(contents, Path::new("/mnt/nfs")),
+            Some(MountBackend::Nfs)
+        ));
+        // Only the topmost mount counts
+        assert!(backend_from_mounts(contents, Path::new("/mnt/shadowed")).is_none());
+        assert!(backend_from_mounts(contents, Path::new("/mnt/missing")).is_none());
+    }
+
+    #[test]
+    fn test_default_fsname_reflects_database_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("agent.db");
+        std::fs::write(&db_path, b"").unwrap();
+
+        // Relative paths are resolved, so the name is the same from any cwd
+        let canonical = std::fs::canonicalize(&db_path).unwrap();
+        assert_eq!(
+            default_fsname(&db_path),
+            format!("agentfs:{}", canonical.display())
+        );
+
+        // A path cannot smuggle extra mount options into the option string
+        let fsname = default_fsname(Path::new("/data/foo,allow_other\\x\n.db"));
+        assert_eq!(fsname, "agentfs:/data/foo_allow_other_x_.db");
+        assert!(validate_fsname(&fsname).is_ok());
+    }
+
+    #[test]
+    fn test_validate_fsname_and_subtype() {
+        assert!(validate_fsname("agentfs:/home/user/.agentfs/my-agent.db").is_ok());
+        assert!(validate_fsname("").is_err());
+        assert!(validate_fsname("agent\nfs").is_err());
+        assert!(validate_fsname("agent\\fs").is_err());
+
+        assert!(validate_subtype(DEFAULT_SUBTYPE).is_ok());
+        assert!(validate_subtype("agent_fs-2").is_ok());
+        assert!(validate_subtype("agent.fs").is_err());
+        assert!(validate_subtype("agentfs,rw").is_err());
+    }
+
+    #[test]
+    fn test_validate_extra_option() {
+        assert!(validate_extra_option("noexec").is_ok());
+        assert!(validate_extra_option("max_read=131072").is_ok());
+        assert!(validate_extra_option("").is_err());
+        assert!(validate_extra_option("noexec,allow_other").is_err());
+        assert!(validate_extra_option("noexec allow_other").is_err());
+        assert!(validate_extra_option("allow_other").is_err());
+        assert!(validate_extra_option("fsname=evil").is_err());
+        assert!(validate_extra_option("port=2049").is_err());
+
+        let mut opts = MountOpts::new(PathBuf::from("/mnt/agent"), MountBackend::Fuse);
+        opts.extra_options = vec!["nosuid".to_string(), "noexec".to_string()];
+        assert!(opts.validate().is_ok());
+        opts.extra_options.push("subtype=other".to_string());
+        assert!(opts.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_fuse_io_size() {
+        let max = max_fuse_io_size();
+        assert!(max >= 128 * 1024);
+        assert!(validate_fuse_io_size("max_write", MIN_FUSE_IO_SIZE).is_ok());
+        assert!(validate_fuse_io_size("max_write", max).is_ok());
+        assert!(validate_fuse_io_size("max_write", MIN_FUSE_IO_SIZE - 1).is_err());
+        let err = validate_fuse_io_size("max_write", max + 1).unwrap_err();
+        assert!(err.to_string().contains("max_write"), "{}", err);
+
+        let mut opts = MountOpts::new(PathBuf::from("/mnt/agent"), MountBackend::Fuse);
+        opts.max_read = Some(64 * 1024);
+        opts.max_write = Some(max);
+        assert!(opts.validate().is_ok());
+        opts.max_write = Some(16 * 1024 * 1024);
+        assert!(opts.validate().is_err());
+
+        // The request size and a raw max_read option would conflict
+        opts.max_write = None;
+        opts.extra_options = vec!["max_read=131072".to_string()];
+        assert!(opts.validate().is_err());
+        opts.max_read = None;
+        assert!(opts.validate().is_ok());
+    }
+
+    #[test]
+    fn test_nfs_cache_options() {
+        assert!(nfs_cache_options(None, None).is_empty());
+        assert_eq!(
+            nfs_cache_options(Some(Duration::from_secs(30)), None),
+            ["acregmin=30", "acregmax=30"]
+        );
+        // Fractions round up, so only zero disables caching
+        assert_eq!(
+            nfs_cache_options(None, Some(Duration::from_millis(1500)))[..2],
+            ["acdirmin=2", "acdirmax=2"]
+        );
+        let options = nfs_cache_options(Some(Duration::ZERO), Some(Duration::ZERO));
+        assert_eq!(
+            options[..4],
+            ["acregmin=0", "acregmax=0", "acdirmin=0", "acdirmax=0"]
+        );
+        assert_eq!(options.len(), 5);
+
+        let mut opts = MountOpts::new(PathBuf::from("/mnt/agent"), MountBackend::Nfs);
+        opts.extra_options = vec!["actimeo=60".to_string()];
+        assert!(opts.validate().is_ok());
+        opts.attr_timeout = Some(Duration::ZERO);
+        let err = opts.validate().unwrap_err();
+        assert!(err.to_string().contains("actimeo=60"), "{}", err);
+        opts.extra_options = vec!["noexec".to_string()];
+        assert!(opts.validate().is_ok());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    #[ignore = "mounts NFS, which needs root"]
+    async fn test_nfs_zero_attr_timeout_shows_writes_immediately() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data");
+        let fs = fs_with_data(b"first").await;
+        let writer = fs.clone();
+
+        let mut opts = MountOpts::new(dir.path().to_path_buf(), MountBackend::Nfs);
+        opts.attr_timeout = Some(Duration::ZERO);
+        opts.entry_timeout = Some(Duration::ZERO);
+        let handle = mount_for_test(fs, opts).await;
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 5);
+
+        // Write on the server side, behind the client's back
+        {
+            let writer = writer.lock().await;
+            let stats = writer.lookup(1, "data").await.unwrap().unwrap();
+            let file = writer.open(stats.ino, libc::O_RDWR).await.unwrap();
+            file.pwrite(5, b", and more").await.unwrap();
+            writer
+                .create_file(1, "new", DEFAULT_FILE_MODE, 0, 0)
+                .await
+                .unwrap();
+        }
+
+        // No cached attributes or lookups hide the change
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 15);
+        assert!(dir.path().join("new").exists());
+
+        drop(handle);
+        assert!(!is_mountpoint(dir.path()));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_mount_cancellable() {
+        let dir = tempfile::tempdir().unwrap();
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+
+        let start = std::time::Instant::now();
+        let result = wait_for_mount_cancellable(dir.path(), Duration::from_secs(10), &cancel).await;
+        assert!(result.is_err());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
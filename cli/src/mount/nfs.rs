@@ -1,19 +1,65 @@
 //! NFS backend implementation for the mount infrastructure.
 
 use anyhow::{Context, Result};
+use std::net::IpAddr;
 use std::path::Path;
 use std::process::Command;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
 
 use crate::nfs::AgentNFS;
-use crate::nfsserve::tcp::NFSTcp;
+use crate::nfsserve::tcp::{NFSTcp, NFSTcpListener};
 
-use super::{MountBackend, MountHandle, MountHandleInner, MountOpts};
+use super::{MountBackend, MountHandle, MountHandleInner, MountOpts, PendingMount};
 
 /// Default NFS port to try (use a high port to avoid needing root).
 const DEFAULT_NFS_PORT: u32 = 11111;
 
+/// Runtime the NFS server runs on.
+pub(crate) enum ServerRuntime {
+    /// A runtime provided through `MountOpts::runtime`.
+    Shared(tokio::runtime::Handle),
+    /// A runtime created for this mount alone.
+    Owned(Option<tokio::runtime::Runtime>),
+}
+
+impl ServerRuntime {
+    /// Use the runtime from `opts`, or create a dedicated one.
+    fn new(opts: &MountOpts) -> Result<Self> {
+        match &opts.runtime {
+            Some(handle) => Ok(ServerRuntime::Shared(handle.clone())),
+            None => {
+                let runtime = tokio::runtime::Builder::new_multi_thread()
+                    .thread_name("agentfs-nfs")
+                    .enable_all()
+                    .build()
+                    .context("Failed to create NFS server runtime")?;
+                Ok(ServerRuntime::Owned(Some(runtime)))
+            }
+        }
+    }
+
+    fn handle(&self) -> &tokio::runtime::Handle {
+        match self {
+            ServerRuntime::Shared(handle) => handle,
+            ServerRuntime::Owned(runtime) => runtime.as_ref().unwrap().handle(),
+        }
+    }
+}
+
+impl Drop for ServerRuntime {
+    fn drop(&mut self) {
+        // Dropping a runtime blocks, which panics inside the caller's async
+        // context, so stop the server's tasks without waiting for them
+        if let ServerRuntime::Owned(runtime) = self {
+            if let Some(runtime) = runtime.take() {
+                runtime.shutdown_background();
+            }
+        }
+    }
+}
+
 /// NFS unmount implementation (Linux).
 #[cfg(target_os = "linux")]
 pub(super) fn unmount_nfs(mountpoint: &Path, lazy: bool) -> Result<()> {
@@ -81,29 +127,92 @@ pub(super) async fn mount_nfs(
     fs: Arc<Mutex<dyn agentfs_sdk::FileSystem + Send>>,
     opts: MountOpts,
 ) -> Result<MountHandle> {
-    use tokio_util::sync::CancellationToken;
-
-    let nfs = AgentNFS::new(fs);
+    let nfs = AgentNFS::new(fs).with_fs_generation().await;
 
     let port = find_available_port(DEFAULT_NFS_PORT)?;
+    let runtime = ServerRuntime::new(&opts)?;
 
+    // Bind on the server runtime, whose reactor then drives the listener
     let bind_addr = format!("127.0.0.1:{}", port);
-    let listener = crate::nfsserve::tcp::NFSTcpListener::bind(&bind_addr, nfs)
+    let listener = runtime
+        .handle()
+        .spawn(async move { NFSTcpListener::bind(&bind_addr, nfs).await })
         .await
+        .context("Failed to bind NFS server")?
         .context("Failed to bind NFS server")?;
 
+    serve_and_mount(listener, runtime, opts).await
+}
+
+/// NFS mount implementation serving on a caller-provided listener.
+pub(super) async fn mount_nfs_with_listener(
+    fs: Arc<Mutex<dyn agentfs_sdk::FileSystem + Send>>,
+    opts: MountOpts,
+    listener: std::net::TcpListener,
+) -> Result<MountHandle> {
+    let nfs = AgentNFS::new(fs).with_fs_generation().await;
+    let runtime = ServerRuntime::new(&opts)?;
+
+    listener
+        .set_nonblocking(true)
+        .context("Failed to configure NFS listener")?;
+    let listener = {
+        // Register with the server runtime's reactor, not the caller's
+        let _guard = runtime.handle().enter();
+        tokio::net::TcpListener::from_std(listener).context("Failed to register NFS listener")?
+    };
+    let listener =
+        NFSTcpListener::from_listener(listener, nfs).context("Failed to set up NFS server")?;
+
+    serve_and_mount(listener, runtime, opts).await
+}
+
+/// Run the NFS server on `listener` within `runtime` and mount it at the
+/// mountpoint.
+async fn serve_and_mount(
+    mut listener: NFSTcpListener<AgentNFS>,
+    runtime: ServerRuntime,
+    opts: MountOpts,
+) -> Result<MountHandle> {
+    use tokio_util::sync::CancellationToken;
+
+    if let Some(clients) = &opts.allowed_clients {
+        listener.with_allowed_clients(clients.clone());
+    }
+
+    let port = listener.get_listen_port() as u32;
+    let host = mount_host(listener.get_listen_ip());
+
     // CancellationToken is kept for API compatibility, but the vendored nfsserve
     // doesn't support graceful shutdown. The task will be aborted on drop.
     let shutdown = CancellationToken::new();
-    let server_handle = tokio::spawn(async move {
+    let mut pending = PendingMount::new(&opts);
+    let server_handle = runtime.handle().spawn(async move {
         if let Err(e) = listener.handle_forever().await {
             eprintln!("NFS server error: {}", e);
         }
     });
+    pending.set_nfs_server(server_handle.abort_handle());
+
+    tokio::select! {
+        _ = opts.cancel.cancelled() => {
+            anyhow::bail!("Mount of {} was cancelled", opts.mountpoint.display());
+        }
+        _ = tokio::time::sleep(tokio::time::Duration::from_millis(100)) => {}
+    }
 
-    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    let mut options = super::nfs_cache_options(opts.attr_timeout, opts.entry_timeout);
+    options.extend(opts.extra_options.iter().cloned());
+    if opts.read_only {
+        options.insert(0, "ro".to_string());
+    }
+    nfs_mount(&host, port, &opts.mountpoint, &options)?;
 
-    nfs_mount(port, &opts.mountpoint)?;
+    // The mount command blocks, so a cancellation may have arrived meanwhile.
+    if opts.cancel.is_cancelled() {
+        anyhow::bail!("Mount of {} was cancelled", opts.mountpoint.display());
+    }
+    pending.disarm();
 
     Ok(MountHandle {
         mountpoint: opts.mountpoint,
@@ -112,10 +221,26 @@ pub(super) async fn mount_nfs(
         inner: MountHandleInner::Nfs {
             shutdown,
             _server_handle: server_handle,
+            _runtime: runtime,
+            port,
         },
+        checkpointer: None,
+        lock: None,
+        ready_duration: Duration::ZERO,
     })
 }
 
+/// Address the NFS client uses to reach a server listening on `ip`.
+///
+/// A listener bound to the wildcard address is reached over loopback.
+fn mount_host(ip: IpAddr) -> String {
+    match ip {
+        ip if ip.is_unspecified() => "127.0.0.1".to_string(),
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("[{}]", ip),
+    }
+}
+
 /// Find an available TCP port starting from the given port.
 fn find_available_port(start_port: u32) -> Result<u32> {
     for port in start_port..start_port + 100 {
@@ -132,17 +257,20 @@ fn find_available_port(start_port: u32) -> Result<u32> {
 
 /// Mount the NFS filesystem (Linux version).
 #[cfg(target_os = "linux")]
-fn nfs_mount(port: u32, mountpoint: &Path) -> Result<()> {
+fn nfs_mount(host: &str, port: u32, mountpoint: &Path, extra_options: &[String]) -> Result<()> {
     let output = Command::new("mount")
         .args([
             "-t",
             "nfs",
             "-o",
-            &format!(
-                "vers=3,tcp,port={},mountport={},nolock,soft,timeo=10,retrans=2",
-                port, port
+            &super::join_mount_options(
+                format!(
+                    "vers=3,tcp,port={},mountport={},nolock,soft,timeo=10,retrans=2",
+                    port, port
+                ),
+                extra_options,
             ),
-            "127.0.0.1:/",
+            &format!("{}:/", host),
             mountpoint.to_str().unwrap(),
         ])
         .output()
@@ -161,15 +289,18 @@ fn nfs_mount(port: u32, mountpoint: &Path) -> Result<()> {
 
 /// Mount the NFS filesystem (macOS version).
 #[cfg(target_os = "macos")]
-fn nfs_mount(port: u32, mountpoint: &Path) -> Result<()> {
+fn nfs_mount(host: &str, port: u32, mountpoint: &Path, extra_options: &[String]) -> Result<()> {
     let output = Command::new("/sbin/mount_nfs")
         .args([
             "-o",
-            &format!(
-                "locallocks,vers=3,tcp,port={},mountport={},soft,timeo=10,retrans=2",
-                port, port
+            &super::join_mount_options(
+                format!(
+                    "locallocks,vers=3,tcp,port={},mountport={},soft,timeo=10,retrans=2",
+                    port, port
+                ),
+                extra_options,
             ),
-            "127.0.0.1:/",
+            &format!("{}:/", host),
             mountpoint.to_str().unwrap(),
         ])
         .output()
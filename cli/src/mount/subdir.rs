@@ -0,0 +1,390 @@
+//! Mounting a subtree of a filesystem as the mount root.
+//!
+//! `SubdirFs` resolves a directory to its inode once, when the mount is set
+//! up, and presents that inode as the root inode to the backends. Every
+//! inode number passed in is translated before reaching the wrapped
+//! filesystem, and the subtree root is reported back as the root inode, so
+//! the kernel never learns the inode numbers of the real root or its
+//! ancestors. Looking up `..` in the subtree root yields the subtree root
+//! itself, as it does at the root of any filesystem.
+//!
+//! Nothing stops an NFS client from forging a file handle for an inode
+//! outside the subtree; the subtree limits what can be reached by name, not
+//! by inode number.
+
+use agentfs_sdk::error::Result as FsResult;
+use agentfs_sdk::{BoxedFile, DirEntry, FileSystem, FilesystemStats, Stats, TimeChange};
+use anyhow::Result;
+use std::path::{Component, Path};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use super::MutexFsAdapter;
+
+/// Inode number of the root directory
+const ROOT_INO: i64 = 1;
+
+/// A filesystem restricted to the subtree below one of its directories.
+pub(crate) struct SubdirFs<F: FileSystem + ?Sized> {
+    inner: Arc<F>,
+    /// Inode of the subtree root in `inner`.
+    root: i64,
+}
+
+impl<F: FileSystem + ?Sized> SubdirFs<F> {
+    /// Restrict `inner` to the subtree below `subdir`.
+    ///
+    /// `subdir` is resolved from the root of `inner`, whether or not it is
+    /// absolute. It must name an existing directory and must not contain
+    /// `..`. Symlinks along the way are not followed.
+    pub(crate) async fn new(inner: Arc<F>, subdir: &Path) -> Result<Self> {
+        let mut root = ROOT_INO;
+        for component in subdir.components() {
+            let name = match component {
+                Component::RootDir | Component::CurDir => continue,
+                Component::Normal(name) => name
+                    .to_str()
+                    .ok_or_else(|| anyhow::anyhow!("Invalid subdirectory {}", subdir.display()))?,
+                Component::ParentDir | Component::Prefix(_) => anyhow::bail!(
+                    "Invalid subdirectory {}: must not contain '..'",
+                    subdir.display()
+                ),
+            };
+            let stats = inner.lookup(root, name).await?.ok_or_else(|| {
+                anyhow::anyhow!("Subdirectory {} does not exist", subdir.display())
+            })?;
+            if !stats.is_directory() {
+                anyhow::bail!("Subdirectory {} is not a directory", subdir.display());
+            }
+            root = stats.ino;
+        }
+        Ok(Self { inner, root })
+    }
+
+    /// Translate an inode number seen by the backends to one of `inner`.
+    fn inner_ino(&self, ino: i64) -> i64 {
+        if ino == ROOT_INO {
+            self.root
+        } else {
+            ino
+        }
+    }
+
+    /// Translate stats of `inner` to what the backends see.
+    fn outer_stats(&self, mut stats: Stats) -> Stats {
+        if stats.ino == self.root {
+            stats.ino = ROOT_INO;
+        }
+        stats
+    }
+}
+
+/// Serve `fs` with `subdir` as its root, or unchanged if `subdir` is `None`.
+pub(crate) async fn with_root_subdir(
+    fs: Arc<Mutex<dyn FileSystem + Send>>,
+    subdir: Option<&Path>,
+) -> Result<Arc<Mutex<dyn FileSystem + Send>>> {
+    let Some(subdir) = subdir else {
+        return Ok(fs);
+    };
+    let subdir_fs = SubdirFs::new(Arc::new(MutexFsAdapter::new(fs)), subdir).await?;
+    Ok(Arc::new(Mutex::new(subdir_fs)))
+}
+
+#[async_trait::async_trait]
+impl<F: FileSystem + ?Sized> FileSystem for SubdirFs<F> {
+    async fn lookup(&self, parent_ino: i64, name: &str) -> FsResult<Option<Stats>> {
+        let parent_ino = self.inner_ino(parent_ino);
+        // The parent of the subtree root is outside the subtree
+        let stats = if parent_ino == self.root && name == ".." {
+            self.inner.getattr(parent_ino).await?
+        } else {
+            self.inner.lookup(parent_ino, name).await?
+        };
+        Ok(stats.map(|stats| self.outer_stats(stats)))
+    }
+
+    async fn getattr(&self, ino: i64) -> FsResult<Option<Stats>> {
+        let stats = self.inner.getattr(self.inner_ino(ino)).await?;
+        Ok(stats.map(|stats| self.outer_stats(stats)))
+    }
+
+    async fn readlink(&self, ino: i64) -> FsResult<Option<String>> {
+        self.inner.readlink(self.inner_ino(ino)).await
+    }
+
+    async fn readdir(&self, ino: i64) -> FsResult<Option<Vec<String>>> {
+        self.inner.readdir(self.inner_ino(ino)).await
+    }
+
+    async fn readdir_plus(&self, ino: i64) -> FsResult<Option<Vec<DirEntry>>> {
+        let entries = self.inner.readdir_plus(self.inner_ino(ino)).await?;
+        Ok(entries.map(|entries| {
+            entries
+                .into_iter()
+                .map(|entry| DirEntry {
+                    name: entry.name,
+                    stats: self.outer_stats(entry.stats),
+                })
+                .collect()
+        }))
+    }
+
+    async fn chmod(&self, ino: i64, mode: u32) -> FsResult<()> {
+        self.inner.chmod(self.inner_ino(ino), mode).await
+    }
+
+    async fn chown(&self, ino: i64, uid: Option<u32>, gid: Option<u32>) -> FsResult<()> {
+        self.inner.chown(self.inner_ino(ino), uid, gid).await
+    }
+
+    async fn utimens(&self, ino: i64, atime: TimeChange, mtime: TimeChange) -> FsResult<()> {
+        self.inner.utimens(self.inner_ino(ino), atime, mtime).await
+    }
+
+    async fn open(&self, ino: i64, flags: i32) -> FsResult<BoxedFile> {
+        self.inner.open(self.inner_ino(ino), flags).await
+    }
+
+    async fn mkdir(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        mode: u32,
+        uid: u32,
+        gid: u32,
+    ) -> FsResult<Stats> {
+        let stats = self
+            .inner
+            .mkdir(self.inner_ino(parent_ino), name, mode, uid, gid)
+            .await?;
+        Ok(self.outer_stats(stats))
+    }
+
+    async fn create_file(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        mode: u32,
+        uid: u32,
+        gid: u32,
+    ) -> FsResult<(Stats, BoxedFile)> {
+        let (stats, file) = self
+            .inner
+            .create_file(self.inner_ino(parent_ino), name, mode, uid, gid)
+            .await?;
+        Ok((self.outer_stats(stats), file))
+    }
+
+    async fn create_tmpfile(&self, mode: u32, uid: u32, gid: u32) -> FsResult<(Stats, BoxedFile)> {
+        self.inner.create_tmpfile(mode, uid, gid).await
+    }
+
+    async fn remove_orphan(&self, ino: i64) -> FsResult<()> {
+        self.inner.remove_orphan(self.inner_ino(ino)).await
+    }
+
+    async fn mknod(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        mode: u32,
+        rdev: u64,
+        uid: u32,
+        gid: u32,
+    ) -> FsResult<Stats> {
+        let stats = self
+            .inner
+            .mknod(self.inner_ino(parent_ino), name, mode, rdev, uid, gid)
+            .await?;
+        Ok(self.outer_stats(stats))
+    }
+
+    async fn symlink(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        target: &str,
+        uid: u32,
+        gid: u32,
+    ) -> FsResult<Stats> {
+        let stats = self
+            .inner
+            .symlink(self.inner_ino(parent_ino), name, target, uid, gid)
+            .await?;
+        Ok(self.outer_stats(stats))
+    }
+
+    async fn unlink(&self, parent_ino: i64, name: &str) -> FsResult<()> {
+        self.inner.unlink(self.inner_ino(parent_ino), name).await
+    }
+
+    async fn unlink_keep_orphan(&self, parent_ino: i64, name: &str) -> FsResult<Option<i64>> {
+        // The root is a directory, so it is never the inode left behind
+        self.inner
+            .unlink_keep_orphan(self.inner_ino(parent_ino), name)
+            .await
+    }
+
+    async fn rmdir(&self, parent_ino: i64, name: &str) -> FsResult<()> {
+        self.inner.rmdir(self.inner_ino(parent_ino), name).await
+    }
+
+    async fn link(&self, ino: i64, newparent_ino: i64, newname: &str) -> FsResult<Stats> {
+        let stats = self
+            .inner
+            .link(self.inner_ino(ino), self.inner_ino(newparent_ino), newname)
+            .await?;
+        Ok(self.outer_stats(stats))
+    }
+
+    async fn rename(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+    ) -> FsResult<()> {
+        self.inner
+            .rename(
+                self.inner_ino(oldparent_ino),
+                oldname,
+                self.inner_ino(newparent_ino),
+                newname,
+            )
+            .await
+    }
+
+    async fn rename_with_flags(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+        flags: u32,
+    ) -> FsResult<()> {
+        self.inner
+            .rename_with_flags(
+                self.inner_ino(oldparent_ino),
+                oldname,
+                self.inner_ino(newparent_ino),
+                newname,
+                flags,
+            )
+            .await
+    }
+
+    async fn rename_keep_orphan(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+        flags: u32,
+    ) -> FsResult<Option<i64>> {
+        // Only a non-directory is kept, so never the root
+        self.inner
+            .rename_keep_orphan(
+                self.inner_ino(oldparent_ino),
+                oldname,
+                self.inner_ino(newparent_ino),
+                newname,
+                flags,
+            )
+            .await
+    }
+
+    async fn get_inode_flags(&self, ino: i64) -> FsResult<u32> {
+        self.inner.get_inode_flags(self.inner_ino(ino)).await
+    }
+
+    async fn set_inode_flags(&self, ino: i64, flags: u32) -> FsResult<()> {
+        self.inner.set_inode_flags(self.inner_ino(ino), flags).await
+    }
+
+    async fn statfs(&self) -> FsResult<FilesystemStats> {
+        self.inner.statfs().await
+    }
+
+    async fn health(&self) -> FsResult<()> {
+        self.inner.health().await
+    }
+
+    async fn checkpoint(&self) -> FsResult<()> {
+        self.inner.checkpoint().await
+    }
+
+    async fn forget(&self, ino: i64, nlookup: u64) {
+        self.inner.forget(self.inner_ino(ino), nlookup).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentfs_sdk::{AgentFS, AgentFSOptions, DEFAULT_DIR_MODE, DEFAULT_FILE_MODE};
+
+    #[tokio::test]
+    async fn test_subdir_root_hides_its_parent() {
+        let agent = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        let fs = Arc::new(agent.fs);
+        let projects = FileSystem::mkdir(&*fs, ROOT_INO, "projects", DEFAULT_DIR_MODE, 0, 0)
+            .await
+            .unwrap();
+        let foo = FileSystem::mkdir(&*fs, projects.ino, "foo", DEFAULT_DIR_MODE, 0, 0)
+            .await
+            .unwrap();
+        FileSystem::create_file(&*fs, foo.ino, "inside", DEFAULT_FILE_MODE, 0, 0)
+            .await
+            .unwrap();
+        FileSystem::create_file(&*fs, projects.ino, "outside", DEFAULT_FILE_MODE, 0, 0)
+            .await
+            .unwrap();
+
+        let subdir = SubdirFs::new(fs.clone(), Path::new("/projects/foo"))
+            .await
+            .unwrap();
+
+        // The subtree root is the root inode, and its contents are visible
+        assert_eq!(
+            subdir.getattr(ROOT_INO).await.unwrap().unwrap().ino,
+            ROOT_INO
+        );
+        let names = subdir.readdir(ROOT_INO).await.unwrap().unwrap();
+        assert_eq!(names, vec!["inside".to_string()]);
+        assert!(subdir.lookup(ROOT_INO, "inside").await.unwrap().is_some());
+
+        // Its parent cannot be reached by name
+        let parent = subdir.lookup(ROOT_INO, "..").await.unwrap().unwrap();
+        assert_eq!(parent.ino, ROOT_INO);
+        assert!(subdir.lookup(ROOT_INO, "outside").await.unwrap().is_none());
+        assert!(subdir.lookup(ROOT_INO, "projects").await.unwrap().is_none());
+
+        // Entries created at the root land in the subtree
+        FileSystem::create_file(&subdir, ROOT_INO, "new", DEFAULT_FILE_MODE, 0, 0)
+            .await
+            .unwrap();
+        assert!(fs.lookup(foo.ino, "new").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_subdir_must_be_an_existing_directory() {
+        let agent = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        let fs = Arc::new(agent.fs);
+        FileSystem::create_file(&*fs, ROOT_INO, "file", DEFAULT_FILE_MODE, 0, 0)
+            .await
+            .unwrap();
+
+        for (subdir, error) in [
+            ("/missing", "does not exist"),
+            ("/file", "is not a directory"),
+            ("/../etc", "must not contain '..'"),
+        ] {
+            let err = SubdirFs::new(fs.clone(), Path::new(subdir))
+                .await
+                .err()
+                .unwrap();
+            assert!(err.to_string().contains(error), "{}: {}", subdir, err);
+        }
+    }
+}
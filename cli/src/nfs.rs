@@ -9,8 +9,8 @@ use std::sync::Arc;
 use libc::{O_RDONLY, O_RDWR};
 
 use crate::nfsserve::nfs::{
-    fattr3, fileid3, filename3, ftype3, nfspath3, nfsstat3, nfstime3, sattr3, set_atime, set_gid3,
-    set_mode3, set_mtime, set_size3, set_uid3, specdata3,
+    fattr3, fileid3, filename3, ftype3, nfs_fh3, nfspath3, nfsstat3, nfstime3, sattr3, set_atime,
+    set_gid3, set_mode3, set_mtime, set_size3, set_uid3, specdata3,
 };
 use crate::nfsserve::vfs::{auth_unix, DirEntry, NFSFileSystem, ReadDirResult, VFSCapabilities};
 use agentfs_sdk::error::Error as SdkError;
@@ -25,6 +25,17 @@ use tokio::sync::Mutex;
 /// Root directory inode number
 const ROOT_INO: fileid3 = 1;
 
+/// Default generation embedded in NFS file handles.
+///
+/// Unlike nfsserve's default (the server start time), this is stable across
+/// server restarts, so handles held by clients keep resolving instead of
+/// failing with ESTALE. AgentFS never reuses inode numbers, so the inode
+/// alone identifies a file within a database.
+const DEFAULT_HANDLE_GENERATION: u64 = 1;
+
+/// Size of an NFS file handle: generation (8 bytes) + inode number (8 bytes).
+const FH_LEN: usize = 16;
+
 /// Convert a fileid3 to a filesystem inode number.
 fn id_to_fs_ino(id: fileid3) -> i64 {
     id as i64
@@ -44,6 +55,12 @@ fn error_to_nfsstat(e: SdkError) -> nfsstat3 {
             FsError::IsADirectory => nfsstat3::NFS3ERR_ISDIR,
             FsError::NameTooLong => nfsstat3::NFS3ERR_NAMETOOLONG,
             FsError::RootOperation => nfsstat3::NFS3ERR_ACCES,
+            FsError::NotSupported => nfsstat3::NFS3ERR_NOTSUPP,
+            FsError::NoSpace => nfsstat3::NFS3ERR_NOSPC,
+            FsError::FileTooLarge => nfsstat3::NFS3ERR_FBIG,
+            FsError::ReadOnly => nfsstat3::NFS3ERR_ROFS,
+            FsError::NotPermitted => nfsstat3::NFS3ERR_PERM,
+            FsError::CrossDevice => nfsstat3::NFS3ERR_XDEV,
             _ => nfsstat3::NFS3ERR_IO,
         },
         SdkError::ConnectionPoolTimeout => nfsstat3::NFS3ERR_JUKEBOX,
@@ -55,12 +72,47 @@ fn error_to_nfsstat(e: SdkError) -> nfsstat3 {
 pub struct AgentNFS {
     /// The underlying filesystem (wrapped in Mutex to serialize operations)
     fs: Arc<Mutex<dyn FileSystem>>,
+    /// Generation embedded in file handles to reject handles from another export
+    handle_generation: u64,
 }
 
 impl AgentNFS {
     /// Create a new NFS adapter wrapping the given filesystem.
     pub fn new(fs: Arc<Mutex<dyn FileSystem>>) -> Self {
-        AgentNFS { fs }
+        AgentNFS {
+            fs,
+            handle_generation: DEFAULT_HANDLE_GENERATION,
+        }
+    }
+
+    /// Set the generation embedded in file handles.
+    ///
+    /// Handles carrying a different generation are rejected with
+    /// NFS3ERR_STALE. Change it when the exported filesystem is replaced by
+    /// one whose inode numbers may refer to different files.
+    pub fn with_handle_generation(mut self, generation: u64) -> Self {
+        self.handle_generation = generation;
+        self
+    }
+
+    /// Derive the handle generation from the exported filesystem.
+    ///
+    /// Uses the root inode's generation, so handles issued for a database
+    /// that has since been recreated (and may reuse inode numbers) are
+    /// rejected as stale. Filesystems that don't report generations keep
+    /// the current handle generation.
+    pub async fn with_fs_generation(self) -> Self {
+        let generation = {
+            let fs = self.fs.lock().await;
+            match fs.getattr(id_to_fs_ino(ROOT_INO)).await {
+                Ok(Some(stats)) => stats.generation,
+                _ => 0,
+            }
+        };
+        if generation == 0 {
+            return self;
+        }
+        self.with_handle_generation(generation)
     }
 
     /// Convert AgentFS Stats to NFS fattr3.
@@ -119,6 +171,28 @@ impl NFSFileSystem for AgentNFS {
         VFSCapabilities::ReadWrite
     }
 
+    /// File handles are derived from the AgentFS inode number and a stable
+    /// generation, so they survive server restarts.
+    fn id_to_fh(&self, id: fileid3) -> nfs_fh3 {
+        let mut data = Vec::with_capacity(FH_LEN);
+        data.extend_from_slice(&self.handle_generation.to_le_bytes());
+        data.extend_from_slice(&id.to_le_bytes());
+        nfs_fh3 { data }
+    }
+
+    /// Resolve a file handle straight back to its inode without walking paths.
+    fn fh_to_id(&self, fh: &nfs_fh3) -> Result<fileid3, nfsstat3> {
+        if fh.data.len() != FH_LEN {
+            return Err(nfsstat3::NFS3ERR_BADHANDLE);
+        }
+        let generation = u64::from_le_bytes(fh.data[0..8].try_into().unwrap());
+        let id = u64::from_le_bytes(fh.data[8..16].try_into().unwrap());
+        if generation != self.handle_generation {
+            return Err(nfsstat3::NFS3ERR_STALE);
+        }
+        Ok(id)
+    }
+
     async fn lookup(&self, dirid: fileid3, filename: &filename3) -> Result<fileid3, nfsstat3> {
         let name = std::str::from_utf8(filename).map_err(|_| nfsstat3::NFS3ERR_INVAL)?;
 
@@ -561,3 +635,81 @@ impl NFSFileSystem for AgentNFS {
         Ok(target.into_bytes().into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentfs_sdk::{AgentFS, AgentFSOptions};
+
+    #[tokio::test]
+    async fn test_file_handle_survives_restart() {
+        let agent = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        let fs: Arc<Mutex<dyn FileSystem>> = Arc::new(Mutex::new(agent.fs));
+
+        let (ino, fh) = {
+            let nfs = AgentNFS::new(fs.clone());
+            let auth = auth_unix::default();
+            let (ino, _) = nfs
+                .create(
+                    ROOT_INO,
+                    &b"file.txt".as_slice().into(),
+                    sattr3::default(),
+                    &auth,
+                )
+                .await
+                .unwrap();
+            (ino, nfs.id_to_fh(ino))
+        };
+
+        // Simulate a server restart: a fresh adapter over the same filesystem
+        let nfs = AgentNFS::new(fs);
+        let resolved = nfs.fh_to_id(&fh).unwrap();
+        assert_eq!(resolved, ino);
+        let attr = nfs.getattr(resolved).await.unwrap();
+        assert_eq!(attr.fileid, ino);
+    }
+
+    #[tokio::test]
+    async fn test_file_handle_generation_mismatch_is_stale() {
+        let agent = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        let fs: Arc<Mutex<dyn FileSystem>> = Arc::new(Mutex::new(agent.fs));
+
+        let fh = AgentNFS::new(fs.clone()).id_to_fh(ROOT_INO);
+        let nfs = AgentNFS::new(fs).with_handle_generation(DEFAULT_HANDLE_GENERATION + 1);
+        assert!(matches!(nfs.fh_to_id(&fh), Err(nfsstat3::NFS3ERR_STALE)));
+
+        let bad = nfs_fh3 { data: vec![0; 4] };
+        assert!(matches!(
+            nfs.fh_to_id(&bad),
+            Err(nfsstat3::NFS3ERR_BADHANDLE)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_file_handle_from_recreated_database_is_stale() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("export.db");
+        let db_path = db_path.to_str().unwrap();
+
+        let fh = {
+            let agent = AgentFS::open(AgentFSOptions::with_path(db_path))
+                .await
+                .unwrap();
+            let fs: Arc<Mutex<dyn FileSystem>> = Arc::new(Mutex::new(agent.fs));
+            let nfs = AgentNFS::new(fs).with_fs_generation().await;
+            let fh = nfs.id_to_fh(ROOT_INO);
+            assert!(nfs.fh_to_id(&fh).is_ok());
+            fh
+        };
+
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", db_path, suffix));
+        }
+        let agent = AgentFS::open(AgentFSOptions::with_path(db_path))
+            .await
+            .unwrap();
+        let fs: Arc<Mutex<dyn FileSystem>> = Arc::new(Mutex::new(agent.fs));
+        let nfs = AgentNFS::new(fs).with_fs_generation().await;
+        assert!(matches!(nfs.fh_to_id(&fh), Err(nfsstat3::NFS3ERR_STALE)));
+    }
+}
@@ -11,7 +11,7 @@ use std::{io, net::IpAddr};
 use tokio::io::AsyncWriteExt;
 use tokio::net::TcpListener;
 use tokio::sync::mpsc;
-use tracing::{debug, error};
+use tracing::{debug, error, warn};
 
 /// A NFS Tcp Connection Handler
 pub struct NFSTcpListener<T: NFSFileSystem + Send + Sync + 'static> {
@@ -21,6 +21,7 @@ pub struct NFSTcpListener<T: NFSFileSystem + Send + Sync + 'static> {
     mount_signal: Option<mpsc::Sender<bool>>,
     export_name: Arc<String>,
     transaction_tracker: Arc<TransactionTracker>,
+    allowed_clients: Option<Vec<IpAddr>>,
 }
 
 pub fn generate_host_ip(hostnum: u16) -> String {
@@ -154,12 +155,23 @@ impl<T: NFSFileSystem + Send + Sync + 'static> NFSTcpListener<T> {
         }
     }
 
+    /// Serves on an already bound listener, for instance one inherited
+    /// through systemd socket activation. fs is an instance of an
+    /// implementation of NFSFileSystem.
+    pub fn from_listener(listener: TcpListener, fs: T) -> io::Result<NFSTcpListener<T>> {
+        debug!("Listening on {:?}", listener.local_addr()?);
+        NFSTcpListener::with_listener(listener, Arc::new(fs))
+    }
+
     async fn bind_internal(ip: &str, port: u16, arcfs: Arc<T>) -> io::Result<NFSTcpListener<T>> {
         let ipstr = format!("{ip}:{port}");
         let listener = TcpListener::bind(&ipstr).await?;
         debug!("Listening on {:?}", &ipstr);
+        NFSTcpListener::with_listener(listener, arcfs)
+    }
 
-        let port = match listener.local_addr().unwrap() {
+    fn with_listener(listener: TcpListener, arcfs: Arc<T>) -> io::Result<NFSTcpListener<T>> {
+        let port = match listener.local_addr()? {
             SocketAddr::V4(s) => s.port(),
             SocketAddr::V6(s) => s.port(),
         };
@@ -170,6 +182,7 @@ impl<T: NFSFileSystem + Send + Sync + 'static> NFSTcpListener<T> {
             mount_signal: None,
             export_name: Arc::from("/".to_string()),
             transaction_tracker: Arc::new(TransactionTracker::new(Duration::from_secs(60))),
+            allowed_clients: None,
         })
     }
 
@@ -188,6 +201,23 @@ impl<T: NFSFileSystem + Send + Sync + 'static> NFSTcpListener<T> {
                 .trim_start_matches('/')
         ))
     }
+
+    /// Restricts which client addresses may connect.
+    ///
+    /// Connections from any other address are closed as soon as they are
+    /// accepted. All clients are allowed if this is never called.
+    pub fn with_allowed_clients(&mut self, clients: Vec<IpAddr>) {
+        self.allowed_clients = Some(clients);
+    }
+
+    fn is_client_allowed(&self, addr: IpAddr) -> bool {
+        match &self.allowed_clients {
+            None => true,
+            Some(clients) => clients
+                .iter()
+                .any(|allowed| allowed.to_canonical() == addr.to_canonical()),
+        }
+    }
 }
 
 #[async_trait]
@@ -212,10 +242,17 @@ impl<T: NFSFileSystem + Send + Sync + 'static> NFSTcp for NFSTcpListener<T> {
     /// Loops forever and never returns handling all incoming connections.
     async fn handle_forever(&self) -> io::Result<()> {
         loop {
-            let (socket, _) = self.listener.accept().await?;
+            let (socket, peer) = self.listener.accept().await?;
+            if !self.is_client_allowed(peer.ip()) {
+                warn!("Refusing connection from disallowed client {}", peer);
+                drop(socket);
+                continue;
+            }
+            // Each connection gets its own context; retransmission tracking
+            // is keyed by the client address, so clients never share state.
             let context = RPCContext {
                 local_port: self.port,
-                client_addr: socket.peer_addr().unwrap().to_string(),
+                client_addr: peer.to_string(),
                 auth: super::rpc::auth_unix::default(),
                 vfs: self.arcfs.clone(),
                 mount_signal: self.mount_signal.clone(),
@@ -13,6 +13,8 @@ pub enum MountBackend {
     Fuse,
     /// NFS over localhost
     Nfs,
+    /// FUSE if /dev/fuse is accessible (Linux only), NFS otherwise
+    Auto,
 }
 
 // Platform-specific default: FUSE on Linux, NFS elsewhere
@@ -35,6 +37,7 @@ impl std::fmt::Display for MountBackend {
         match self {
             MountBackend::Fuse => write!(f, "fuse"),
             MountBackend::Nfs => write!(f, "nfs"),
+            MountBackend::Auto => write!(f, "auto"),
         }
     }
 }
@@ -153,6 +156,54 @@ pub enum Command {
         #[arg(long = "strace")]
         strace: bool,
 
+        /// Record filesystem and process events as newline-delimited JSON to FILE.
+        /// Only supported with the default FUSE overlay sandbox on Linux.
+        #[arg(long = "events", value_name = "FILE")]
+        events: Option<PathBuf>,
+
+        /// Record the privileged actions the sandbox takes to set up the run
+        /// (mounts, namespaces, user mappings) as newline-delimited JSON to FILE.
+        /// Only supported with the default FUSE overlay sandbox on Linux.
+        #[arg(long = "audit-log", value_name = "FILE")]
+        audit_log: Option<PathBuf>,
+
+        /// Write the command's standard output to FILE instead of the terminal.
+        /// Only supported with the default FUSE overlay sandbox on Linux.
+        #[arg(long = "capture-stdout", value_name = "FILE")]
+        capture_stdout: Option<PathBuf>,
+
+        /// Write the command's standard error to FILE instead of the terminal.
+        /// Only supported with the default FUSE overlay sandbox on Linux.
+        #[arg(long = "capture-stderr", value_name = "FILE")]
+        capture_stderr: Option<PathBuf>,
+
+        /// Start the run from a delta database previously saved with
+        /// --delta-out, instead of an empty delta.
+        /// Only supported with the default FUSE overlay sandbox on Linux.
+        #[arg(long = "delta-in", value_name = "DB")]
+        delta_in: Option<PathBuf>,
+
+        /// Save the run's delta layer to DB when the command exits.
+        /// Only supported with the default FUSE overlay sandbox on Linux.
+        #[arg(long = "delta-out", value_name = "DB")]
+        delta_out: Option<PathBuf>,
+
+        /// Start the command in PATH, a directory of the sandboxed working
+        /// directory given relative to its root (e.g. /workspace).
+        /// Only supported with the default FUSE overlay sandbox on Linux.
+        #[arg(long = "workdir", value_name = "PATH")]
+        workdir: Option<PathBuf>,
+
+        /// Create the --workdir directory if it does not exist, instead of failing.
+        #[arg(long = "create-workdir", requires = "workdir")]
+        create_workdir: bool,
+
+        /// Run the command in its own PID namespace, under a small init
+        /// process that forwards signals to it and reaps orphaned processes.
+        /// Only supported with the default FUSE overlay sandbox on Linux.
+        #[arg(long = "pid-namespace")]
+        pid_namespace: bool,
+
         /// Session identifier for sharing delta layer across multiple runs.
         /// If not provided, a unique session ID is generated for each run.
         /// Use the same session ID to share the delta layer between runs.
@@ -251,6 +302,90 @@ pub enum Command {
         /// Backend to use for mounting
         #[arg(long, default_value_t = MountBackend::default())]
         backend: MountBackend,
+
+        /// Mount read-only. Several read-only mounts of the same database can
+        /// run at once, as long as no writer is active.
+        #[arg(long)]
+        read_only: bool,
+
+        /// Create a new, empty filesystem if the database does not exist.
+        /// Without this flag, mounting a missing database is an error.
+        #[arg(long, conflicts_with = "read_only")]
+        create: bool,
+
+        /// Mount an overlay of this base database, which is opened read-only,
+        /// with all writes going to the --delta database. Takes the place of
+        /// ID_OR_PATH, so only MOUNTPOINT is given.
+        #[arg(
+            long,
+            value_name = "ID_OR_PATH",
+            requires = "delta",
+            conflicts_with_all = ["read_only", "create"]
+        )]
+        base: Option<String>,
+
+        /// Database that receives the writes of a --base overlay mount; it is
+        /// created if it does not exist
+        #[arg(long, value_name = "ID_OR_PATH", requires = "base")]
+        delta: Option<String>,
+
+        /// Extra mount options passed to the backend as-is (e.g. -o noexec,nosuid).
+        /// Options agentfs sets itself are rejected; unsupported ones fail the mount.
+        #[arg(
+            short = 'o',
+            long = "option",
+            value_name = "OPTION",
+            value_delimiter = ','
+        )]
+        options: Vec<String>,
+
+        /// Fail filesystem operations that take longer than this many seconds with
+        /// EIO instead of blocking (FUSE only)
+        #[arg(long, value_name = "SECONDS")]
+        op_timeout: Option<u64>,
+
+        /// Largest read the kernel sends to the filesystem in one request, in
+        /// bytes (FUSE only; defaults to the largest the kernel allows)
+        #[arg(long, value_name = "BYTES")]
+        max_read: Option<u32>,
+
+        /// Largest write the kernel sends to the filesystem in one request, in
+        /// bytes (FUSE only; defaults to the largest the kernel allows)
+        #[arg(long, value_name = "BYTES")]
+        max_write: Option<u32>,
+
+        /// Let NFS clients cache file attributes for this many seconds; 0 makes
+        /// every stat ask the server (NFS only; defaults to the client's setting)
+        #[arg(long, value_name = "SECONDS")]
+        attr_timeout: Option<u64>,
+
+        /// Let NFS clients cache directory entries for this many seconds; 0 makes
+        /// every lookup ask the server (NFS only; defaults to the client's setting)
+        #[arg(long, value_name = "SECONDS")]
+        entry_timeout: Option<u64>,
+
+        /// Mount this directory of the filesystem (e.g. /projects/foo) as the
+        /// mount root; the rest of the filesystem is not reachable through the mount
+        #[arg(long, value_name = "PATH")]
+        subdir: Option<PathBuf>,
+
+        /// Filesystem name shown by `mount` and `df` (defaults to agentfs:<database path>)
+        #[arg(long, value_name = "NAME")]
+        fsname: Option<String>,
+
+        /// Print the mountpoint, backend, daemon PID, NFS port and resolved options
+        /// as a JSON object on stdout once the filesystem is mounted
+        #[arg(long)]
+        json: bool,
+
+        /// Lazily unmount agentfs mounts whose process has died, then exit
+        #[arg(long, conflicts_with_all = ["id_or_path", "mountpoint"])]
+        cleanup_stale: bool,
+
+        /// Check that MOUNTPOINT is an existing mount of ID_OR_PATH, by comparing
+        /// the filesystem id it reports with the database's, then exit (FUSE only)
+        #[arg(long, requires_all = ["id_or_path", "mountpoint"], conflicts_with = "base")]
+        verify: bool,
     },
     /// Show differences between base filesystem and delta (overlay mode only)
     Diff {
@@ -295,6 +430,11 @@ pub enum Command {
         /// Port to listen on
         #[arg(long, default_value = "11111")]
         port: u32,
+
+        /// Only accept connections from this client address (repeatable).
+        /// All clients are accepted if not given.
+        #[arg(long = "allow-client", value_name = "IP", value_delimiter = ',')]
+        allow_clients: Vec<std::net::IpAddr>,
     },
 
     /// Start an MCP server exposing filesystem and KV-store tools
@@ -356,6 +496,18 @@ pub enum FsCommand {
         /// Content of the file
         content: String,
     },
+    /// Write a compact copy of the filesystem for distribution
+    ///
+    /// Inodes are renumbered contiguously from the root and unreachable ones
+    /// are dropped, producing e.g. a small read-only base layer to publish.
+    Optimize {
+        /// Path of the new database to write
+        output: PathBuf,
+
+        /// Also checkpoint the source database's write-ahead log
+        #[arg(long)]
+        checkpoint: bool,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -386,6 +538,11 @@ pub enum ServeCommand {
         /// Port to listen on
         #[arg(long, default_value = "11111")]
         port: u32,
+
+        /// Only accept connections from this client address (repeatable).
+        /// All clients are accepted if not given.
+        #[arg(long = "allow-client", value_name = "IP", value_delimiter = ',')]
+        allow_clients: Vec<std::net::IpAddr>,
     },
 
     /// Start an MCP server exposing filesystem and KV-store tools
@@ -0,0 +1,169 @@
+//! Audit log of privileged sandbox operations.
+//!
+//! `AuditLog` appends one JSON object per line for every privileged action
+//! the overlay sandbox takes to set up a run: mounting the FUSE overlay,
+//! creating namespaces, writing user namespace mappings, and bind mounting
+//! or remounting filesystems. Unlike `--events`, which records what the
+//! sandboxed command does to files, entries describe what the sandbox itself
+//! did, with the parameters it used.
+
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+/// A single audited action, serialized as one line of JSON.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    /// RFC 3339 timestamp with microsecond precision
+    pub timestamp: String,
+    /// Action taken (e.g. "unshare", "bind_mount", "write_uid_map")
+    pub action: &'static str,
+    /// Process that took the action
+    pub pid: u32,
+    /// "ok" or "error"
+    pub result: &'static str,
+    /// Why the action failed, when `result` is "error"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Parameters of the action
+    pub params: BTreeMap<&'static str, Value>,
+}
+
+impl AuditEntry {
+    /// Create a successful entry stamped with the current time.
+    pub fn new(action: &'static str) -> Self {
+        Self {
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true),
+            action,
+            pid: std::process::id(),
+            result: "ok",
+            error: None,
+            params: BTreeMap::new(),
+        }
+    }
+
+    /// Add a parameter of the action.
+    pub fn param(mut self, name: &'static str, value: impl Into<Value>) -> Self {
+        self.params.insert(name, value.into());
+        self
+    }
+
+    /// Add a path parameter of the action.
+    pub fn path(self, name: &'static str, path: &Path) -> Self {
+        self.param(name, path.display().to_string())
+    }
+
+    /// Record the outcome of the action.
+    pub fn outcome<T, E: Display>(mut self, result: &Result<T, E>) -> Self {
+        if let Err(e) = result {
+            self.result = "error";
+            self.error = Some(e.to_string());
+        }
+        self
+    }
+}
+
+/// Newline-delimited JSON sink for audit entries.
+///
+/// The file is opened for appending and every entry is a single write, so
+/// the sandbox's forked child can record to it alongside the parent without
+/// interleaving lines. A disabled log records nothing.
+pub struct AuditLog {
+    file: Option<std::fs::File>,
+}
+
+impl AuditLog {
+    /// Create (or truncate) the audit log at `path`.
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        // OpenOptions refuses to both append and truncate, so O_APPEND
+        // is passed as a raw flag
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .custom_flags(libc::O_APPEND)
+            .open(path)?;
+        Ok(Self { file: Some(file) })
+    }
+
+    /// A log that records nothing.
+    pub fn disabled() -> Self {
+        Self { file: None }
+    }
+
+    /// Append an entry to the log.
+    pub fn record(&self, entry: AuditEntry) {
+        let Some(mut file) = self.file.as_ref() else {
+            return;
+        };
+        let Ok(mut line) = serde_json::to_vec(&entry) else {
+            return;
+        };
+        line.push(b'\n');
+        if let Err(e) = file.write_all(&line) {
+            tracing::warn!("Failed to record audit entry: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_entries(path: &Path) -> Vec<Value> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_audit_log_records_actions_and_outcomes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        std::fs::write(&path, "stale\n").unwrap();
+        let log = AuditLog::create(&path).unwrap();
+
+        log.record(
+            AuditEntry::new("bind_mount")
+                .path("source", Path::new("/tmp/fuse"))
+                .path("target", Path::new("/work"))
+                .outcome(&Ok::<(), std::io::Error>(())),
+        );
+        log.record(
+            AuditEntry::new("write_uid_map")
+                .param("map", "1000 1000 1")
+                .outcome(&Err::<(), _>(std::io::Error::from_raw_os_error(
+                    libc::EPERM,
+                ))),
+        );
+        AuditLog::disabled().record(AuditEntry::new("unshare"));
+
+        let entries = read_entries(&path);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["action"], "bind_mount");
+        assert_eq!(entries[0]["result"], "ok");
+        assert_eq!(entries[0]["params"]["source"], "/tmp/fuse");
+        assert_eq!(entries[0]["params"]["target"], "/work");
+        assert_eq!(entries[0]["pid"], std::process::id());
+        assert!(entries[0].get("error").is_none());
+        assert_eq!(entries[1]["action"], "write_uid_map");
+        assert_eq!(entries[1]["result"], "error");
+        assert_eq!(entries[1]["params"]["map"], "1000 1000 1");
+        assert!(entries[1]["error"]
+            .as_str()
+            .unwrap()
+            .contains("not permitted"));
+        assert!(entries
+            .iter()
+            .all(
+                |e| chrono::DateTime::parse_from_rfc3339(e["timestamp"].as_str().unwrap()).is_ok()
+            ));
+    }
+}
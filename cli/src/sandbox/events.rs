@@ -0,0 +1,671 @@
+//! Machine-readable event stream for sandboxed runs.
+//!
+//! `EventFs` wraps the overlay filesystem and appends one JSON object per line
+//! to an `EventSink` for every filesystem-semantic operation the sandboxed
+//! process performs (open, create, write, delete, ...). The run command adds
+//! process `spawn` and `exit` events around them. Unlike `--strace`, events
+//! describe what happened to files, not which system calls were made.
+
+use crate::fuse::error_to_errno;
+use agentfs_sdk::error::Result;
+use agentfs_sdk::filesystem::FileWriter;
+use agentfs_sdk::{BoxedFile, DirEntry, File, FileSystem, FilesystemStats, Stats, TimeChange};
+use async_trait::async_trait;
+use chrono::{SecondsFormat, Utc};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Inode number of the filesystem root.
+const ROOT_INO: i64 = 1;
+
+/// A single event, serialized as one line of JSON.
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    /// RFC 3339 timestamp with microsecond precision
+    pub timestamp: String,
+    /// Operation name (e.g. "create", "write", "unlink", "spawn", "exit")
+    pub op: &'static str,
+    /// Path the operation applied to, if known
+    pub path: Option<String>,
+    /// "ok" or "error"
+    pub result: &'static str,
+    /// Error number when `result` is "error"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errno: Option<i32>,
+    /// Destination path for renames and links
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// Bytes written, or the new size for truncates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+    /// Process ID for spawn events
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<i32>,
+    /// Exit code for exit events
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+}
+
+impl Event {
+    /// Create a successful event stamped with the current time.
+    pub fn new(op: &'static str, path: Option<String>) -> Self {
+        Self {
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true),
+            op,
+            path,
+            result: "ok",
+            errno: None,
+            target: None,
+            bytes: None,
+            pid: None,
+            exit_code: None,
+        }
+    }
+
+    /// Record the outcome of the operation.
+    fn outcome<T>(mut self, result: &Result<T>) -> Self {
+        if let Err(e) = result {
+            self.result = "error";
+            self.errno = Some(error_to_errno(e));
+        }
+        self
+    }
+
+    fn target(mut self, target: Option<String>) -> Self {
+        self.target = target;
+        self
+    }
+
+    fn bytes(mut self, bytes: u64) -> Self {
+        self.bytes = Some(bytes);
+        self
+    }
+
+    /// Set the process ID (for spawn events).
+    pub fn pid(mut self, pid: i32) -> Self {
+        self.pid = Some(pid);
+        self
+    }
+
+    /// Set the exit code (for exit events).
+    pub fn exit_code(mut self, exit_code: i32) -> Self {
+        self.exit_code = Some(exit_code);
+        self
+    }
+}
+
+/// Newline-delimited JSON sink for events.
+///
+/// Each event is written with a single unbuffered write, so the file is
+/// complete even if the process exits without dropping the sink.
+pub struct EventSink {
+    file: Mutex<std::fs::File>,
+}
+
+impl EventSink {
+    /// Create (or truncate) the events file at `path`.
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        let file = std::fs::File::create(path)?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Append an event to the sink.
+    pub fn record(&self, event: Event) {
+        let Ok(mut line) = serde_json::to_vec(&event) else {
+            return;
+        };
+        line.push(b'\n');
+        if let Err(e) = self.file.lock().write_all(&line) {
+            tracing::warn!("Failed to record event: {}", e);
+        }
+    }
+}
+
+/// Filesystem wrapper that records operations to an `EventSink`.
+///
+/// Paths are reconstructed from the names seen in lookups and creations and
+/// reported relative to `root`, the directory the overlay is mounted on.
+pub struct EventFs {
+    inner: Arc<dyn FileSystem>,
+    sink: Arc<EventSink>,
+    paths: Mutex<HashMap<i64, String>>,
+}
+
+impl EventFs {
+    /// Wrap `inner`, whose root inode appears at `root` in the sandbox.
+    pub fn new(inner: Arc<dyn FileSystem>, sink: Arc<EventSink>, root: &str) -> Self {
+        let mut paths = HashMap::new();
+        paths.insert(ROOT_INO, root.to_string());
+        Self {
+            inner,
+            sink,
+            paths: Mutex::new(paths),
+        }
+    }
+
+    fn path_of(&self, ino: i64) -> Option<String> {
+        self.paths.lock().get(&ino).cloned()
+    }
+
+    fn child_path(&self, parent_ino: i64, name: &str) -> Option<String> {
+        let parent = self.path_of(parent_ino)?;
+        if parent.ends_with('/') {
+            Some(format!("{}{}", parent, name))
+        } else {
+            Some(format!("{}/{}", parent, name))
+        }
+    }
+
+    fn remember(&self, ino: i64, path: Option<String>) {
+        if let Some(path) = path {
+            self.paths.lock().insert(ino, path);
+        }
+    }
+
+    /// Record the rename `rename` makes, then refresh the moved entries so
+    /// later events report their new paths.
+    async fn record_rename<T>(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+        rename: impl std::future::Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let path = self.child_path(oldparent_ino, oldname);
+        let target = self.child_path(newparent_ino, newname);
+        let result = rename.await;
+        self.sink.record(
+            Event::new("rename", path.clone())
+                .target(target.clone())
+                .outcome(&result),
+        );
+        let value = result?;
+        if let Ok(Some(stats)) = self.inner.lookup(newparent_ino, newname).await {
+            self.remember(stats.ino, target);
+        }
+        if let Ok(Some(stats)) = self.inner.lookup(oldparent_ino, oldname).await {
+            self.remember(stats.ino, path);
+        }
+        Ok(value)
+    }
+
+    fn wrap_file(&self, file: BoxedFile, path: Option<String>) -> BoxedFile {
+        Arc::new(EventFile {
+            inner: file,
+            sink: self.sink.clone(),
+            path,
+        })
+    }
+}
+
+#[async_trait]
+impl FileSystem for EventFs {
+    async fn lookup(&self, parent_ino: i64, name: &str) -> Result<Option<Stats>> {
+        let result = self.inner.lookup(parent_ino, name).await;
+        if let Ok(Some(stats)) = &result {
+            self.remember(stats.ino, self.child_path(parent_ino, name));
+        }
+        result
+    }
+
+    async fn exists(&self, parent_ino: i64, name: &str) -> Result<bool> {
+        self.inner.exists(parent_ino, name).await
+    }
+
+    async fn getattr(&self, ino: i64) -> Result<Option<Stats>> {
+        self.inner.getattr(ino).await
+    }
+
+    async fn readlink(&self, ino: i64) -> Result<Option<String>> {
+        self.inner.readlink(ino).await
+    }
+
+    async fn readdir(&self, ino: i64) -> Result<Option<Vec<String>>> {
+        self.inner.readdir(ino).await
+    }
+
+    async fn readdir_plus(&self, ino: i64) -> Result<Option<Vec<DirEntry>>> {
+        let result = self.inner.readdir_plus(ino).await;
+        if let Ok(Some(entries)) = &result {
+            for entry in entries {
+                self.remember(entry.stats.ino, self.child_path(ino, &entry.name));
+            }
+        }
+        result
+    }
+
+    async fn chmod(&self, ino: i64, mode: u32) -> Result<()> {
+        let result = self.inner.chmod(ino, mode).await;
+        self.sink
+            .record(Event::new("chmod", self.path_of(ino)).outcome(&result));
+        result
+    }
+
+    async fn chown(&self, ino: i64, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        let result = self.inner.chown(ino, uid, gid).await;
+        self.sink
+            .record(Event::new("chown", self.path_of(ino)).outcome(&result));
+        result
+    }
+
+    async fn utimens(&self, ino: i64, atime: TimeChange, mtime: TimeChange) -> Result<()> {
+        self.inner.utimens(ino, atime, mtime).await
+    }
+
+    async fn open(&self, ino: i64, flags: i32) -> Result<BoxedFile> {
+        let path = self.path_of(ino);
+        let result = self.inner.open(ino, flags).await;
+        self.sink
+            .record(Event::new("open", path.clone()).outcome(&result));
+        Ok(self.wrap_file(result?, path))
+    }
+
+    async fn mkdir(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        mode: u32,
+        uid: u32,
+        gid: u32,
+    ) -> Result<Stats> {
+        let path = self.child_path(parent_ino, name);
+        let result = self.inner.mkdir(parent_ino, name, mode, uid, gid).await;
+        self.sink
+            .record(Event::new("mkdir", path.clone()).outcome(&result));
+        let stats = result?;
+        self.remember(stats.ino, path);
+        Ok(stats)
+    }
+
+    async fn create_file(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        mode: u32,
+        uid: u32,
+        gid: u32,
+    ) -> Result<(Stats, BoxedFile)> {
+        let path = self.child_path(parent_ino, name);
+        let result = self
+            .inner
+            .create_file(parent_ino, name, mode, uid, gid)
+            .await;
+        self.sink
+            .record(Event::new("create", path.clone()).outcome(&result));
+        let (stats, file) = result?;
+        self.remember(stats.ino, path.clone());
+        Ok((stats, self.wrap_file(file, path)))
+    }
+
+    async fn create_writer(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        mode: u32,
+        uid: u32,
+        gid: u32,
+    ) -> Result<FileWriter> {
+        let path = self.child_path(parent_ino, name);
+        let result = self
+            .inner
+            .create_writer(parent_ino, name, mode, uid, gid)
+            .await;
+        self.sink
+            .record(Event::new("create", path.clone()).outcome(&result));
+        let writer = result?;
+        if let Ok(Some(stats)) = self.inner.lookup(parent_ino, name).await {
+            self.remember(stats.ino, path.clone());
+        }
+        // Streamed writes are recorded like writes through a created file
+        writer.map_file(|file| self.wrap_file(file, path))
+    }
+
+    async fn create_tmpfile(&self, mode: u32, uid: u32, gid: u32) -> Result<(Stats, BoxedFile)> {
+        let (stats, file) = self.inner.create_tmpfile(mode, uid, gid).await?;
+        Ok((stats, self.wrap_file(file, None)))
+    }
+
+    async fn remove_orphan(&self, ino: i64) -> Result<()> {
+        self.inner.remove_orphan(ino).await
+    }
+
+    async fn mknod(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        mode: u32,
+        rdev: u64,
+        uid: u32,
+        gid: u32,
+    ) -> Result<Stats> {
+        let path = self.child_path(parent_ino, name);
+        let result = self
+            .inner
+            .mknod(parent_ino, name, mode, rdev, uid, gid)
+            .await;
+        self.sink
+            .record(Event::new("mknod", path.clone()).outcome(&result));
+        let stats = result?;
+        self.remember(stats.ino, path);
+        Ok(stats)
+    }
+
+    async fn symlink(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        target: &str,
+        uid: u32,
+        gid: u32,
+    ) -> Result<Stats> {
+        let path = self.child_path(parent_ino, name);
+        let result = self.inner.symlink(parent_ino, name, target, uid, gid).await;
+        self.sink.record(
+            Event::new("symlink", path.clone())
+                .target(Some(target.to_string()))
+                .outcome(&result),
+        );
+        let stats = result?;
+        self.remember(stats.ino, path);
+        Ok(stats)
+    }
+
+    async fn unlink(&self, parent_ino: i64, name: &str) -> Result<()> {
+        let result = self.inner.unlink(parent_ino, name).await;
+        self.sink
+            .record(Event::new("unlink", self.child_path(parent_ino, name)).outcome(&result));
+        result
+    }
+
+    async fn unlink_keep_orphan(&self, parent_ino: i64, name: &str) -> Result<Option<i64>> {
+        let result = self.inner.unlink_keep_orphan(parent_ino, name).await;
+        self.sink
+            .record(Event::new("unlink", self.child_path(parent_ino, name)).outcome(&result));
+        result
+    }
+
+    async fn rmdir(&self, parent_ino: i64, name: &str) -> Result<()> {
+        let result = self.inner.rmdir(parent_ino, name).await;
+        self.sink
+            .record(Event::new("rmdir", self.child_path(parent_ino, name)).outcome(&result));
+        result
+    }
+
+    async fn link(&self, ino: i64, newparent_ino: i64, newname: &str) -> Result<Stats> {
+        let target = self.child_path(newparent_ino, newname);
+        let result = self.inner.link(ino, newparent_ino, newname).await;
+        self.sink.record(
+            Event::new("link", self.path_of(ino))
+                .target(target)
+                .outcome(&result),
+        );
+        result
+    }
+
+    async fn rename(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+    ) -> Result<()> {
+        self.rename_with_flags(oldparent_ino, oldname, newparent_ino, newname, 0)
+            .await
+    }
+
+    async fn rename_with_flags(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+        flags: u32,
+    ) -> Result<()> {
+        let rename =
+            self.inner
+                .rename_with_flags(oldparent_ino, oldname, newparent_ino, newname, flags);
+        self.record_rename(oldparent_ino, oldname, newparent_ino, newname, rename)
+            .await
+    }
+
+    async fn rename_keep_orphan(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+        flags: u32,
+    ) -> Result<Option<i64>> {
+        let rename =
+            self.inner
+                .rename_keep_orphan(oldparent_ino, oldname, newparent_ino, newname, flags);
+        self.record_rename(oldparent_ino, oldname, newparent_ino, newname, rename)
+            .await
+    }
+
+    async fn get_inode_flags(&self, ino: i64) -> Result<u32> {
+        self.inner.get_inode_flags(ino).await
+    }
+
+    async fn set_inode_flags(&self, ino: i64, flags: u32) -> Result<()> {
+        let result = self.inner.set_inode_flags(ino, flags).await;
+        self.sink
+            .record(Event::new("setflags", self.path_of(ino)).outcome(&result));
+        result
+    }
+
+    async fn statfs(&self) -> Result<FilesystemStats> {
+        self.inner.statfs().await
+    }
+
+    async fn health(&self) -> Result<()> {
+        self.inner.health().await
+    }
+
+    async fn checkpoint(&self) -> Result<()> {
+        self.inner.checkpoint().await
+    }
+
+    async fn forget(&self, ino: i64, nlookup: u64) {
+        self.inner.forget(ino, nlookup).await
+    }
+}
+
+/// File handle wrapper that records writes and truncates.
+struct EventFile {
+    inner: BoxedFile,
+    sink: Arc<EventSink>,
+    path: Option<String>,
+}
+
+#[async_trait]
+impl File for EventFile {
+    async fn pread(&self, offset: u64, size: u64) -> Result<Vec<u8>> {
+        self.inner.pread(offset, size).await
+    }
+
+    async fn pwrite(&self, offset: u64, data: &[u8]) -> Result<()> {
+        let result = self.inner.pwrite(offset, data).await;
+        self.sink.record(
+            Event::new("write", self.path.clone())
+                .bytes(data.len() as u64)
+                .outcome(&result),
+        );
+        result
+    }
+
+    async fn truncate(&self, size: u64) -> Result<()> {
+        let result = self.inner.truncate(size).await;
+        self.sink.record(
+            Event::new("truncate", self.path.clone())
+                .bytes(size)
+                .outcome(&result),
+        );
+        result
+    }
+
+    async fn fsync(&self) -> Result<()> {
+        self.inner.fsync().await
+    }
+
+    async fn fdatasync(&self) -> Result<()> {
+        self.inner.fdatasync().await
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.inner.flush().await
+    }
+
+    async fn fstat(&self) -> Result<Stats> {
+        self.inner.fstat().await
+    }
+
+    async fn seek_data(&self, offset: u64) -> Result<u64> {
+        self.inner.seek_data(offset).await
+    }
+
+    async fn seek_hole(&self, offset: u64) -> Result<u64> {
+        self.inner.seek_hole(offset).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentfs_sdk::{AgentFS, AgentFSOptions, DEFAULT_DIR_MODE, DEFAULT_FILE_MODE};
+
+    fn read_events(path: &Path) -> Vec<serde_json::Value> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_events_record_filesystem_operations() {
+        let dir = tempfile::tempdir().unwrap();
+        let events_path = dir.path().join("events.jsonl");
+        let sink = Arc::new(EventSink::create(&events_path).unwrap());
+
+        let agent = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        let fs = EventFs::new(Arc::new(agent.fs), sink.clone(), "/work");
+
+        let dir_stats = fs
+            .mkdir(ROOT_INO, "src", DEFAULT_DIR_MODE, 0, 0)
+            .await
+            .unwrap();
+        let (_, file) = fs
+            .create_file(dir_stats.ino, "hello.txt", DEFAULT_FILE_MODE, 0, 0)
+            .await
+            .unwrap();
+        file.pwrite(0, b"hello").await.unwrap();
+        assert!(fs.exists(dir_stats.ino, "hello.txt").await.unwrap());
+        assert!(fs.unlink(ROOT_INO, "missing").await.is_err());
+        sink.record(Event::new("exit", Some("/bin/true".to_string())).exit_code(0));
+
+        let events = read_events(&events_path);
+        let summary: Vec<_> = events
+            .iter()
+            .map(|e| {
+                (
+                    e["op"].as_str().unwrap(),
+                    e["path"].as_str().unwrap(),
+                    e["result"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("mkdir", "/work/src", "ok"),
+                ("create", "/work/src/hello.txt", "ok"),
+                ("write", "/work/src/hello.txt", "ok"),
+                ("unlink", "/work/missing", "error"),
+                ("exit", "/bin/true", "ok"),
+            ]
+        );
+        assert_eq!(events[2]["bytes"], 5);
+        assert_eq!(events[3]["errno"], libc::ENOENT);
+        assert_eq!(events[4]["exit_code"], 0);
+        assert!(events
+            .iter()
+            .all(
+                |e| chrono::DateTime::parse_from_rfc3339(e["timestamp"].as_str().unwrap()).is_ok()
+            ));
+    }
+
+    #[tokio::test]
+    async fn test_events_record_streamed_writes() {
+        use tokio::io::AsyncWriteExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let events_path = dir.path().join("events.jsonl");
+        let sink = Arc::new(EventSink::create(&events_path).unwrap());
+
+        let agent = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        let fs = EventFs::new(Arc::new(agent.fs), sink.clone(), "/work");
+
+        let mut writer = fs
+            .create_writer(ROOT_INO, "log.txt", DEFAULT_FILE_MODE, 0, 0)
+            .await
+            .unwrap();
+        writer.write_all(b"streamed").await.unwrap();
+        writer.shutdown().await.unwrap();
+
+        let events = read_events(&events_path);
+        let summary: Vec<_> = events
+            .iter()
+            .map(|e| (e["op"].as_str().unwrap(), e["path"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![("create", "/work/log.txt"), ("write", "/work/log.txt")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_events_record_inode_flag_changes() {
+        use agentfs_sdk::filesystem::FS_APPEND_FL;
+
+        let dir = tempfile::tempdir().unwrap();
+        let events_path = dir.path().join("events.jsonl");
+        let sink = Arc::new(EventSink::create(&events_path).unwrap());
+
+        let agent = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        let fs = EventFs::new(Arc::new(agent.fs), sink.clone(), "/work");
+
+        let (stats, _) = fs
+            .create_file(ROOT_INO, "log.txt", DEFAULT_FILE_MODE, 0, 0)
+            .await
+            .unwrap();
+        fs.set_inode_flags(stats.ino, FS_APPEND_FL).await.unwrap();
+        assert_eq!(fs.get_inode_flags(stats.ino).await.unwrap(), FS_APPEND_FL);
+
+        let events = read_events(&events_path);
+        let summary: Vec<_> = events
+            .iter()
+            .map(|e| (e["op"].as_str().unwrap(), e["path"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![("create", "/work/log.txt"), ("setflags", "/work/log.txt")]
+        );
+    }
+
+    #[test]
+    fn test_event_errno_matches_fuse() {
+        use agentfs_sdk::error::Error as SdkError;
+
+        // Errors are reported with the errno FUSE replies with
+        let event = Event::new("open", None).outcome::<()>(&Err(SdkError::ConnectionPoolTimeout));
+        assert_eq!(event.errno, Some(libc::EAGAIN));
+    }
+}
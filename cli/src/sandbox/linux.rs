@@ -15,24 +15,27 @@
 //! The HostFS base layer then accesses files through `/proc/self/fd/N`,
 //! bypassing the FUSE mount entirely.
 
+use super::audit::{AuditEntry, AuditLog};
+use super::events::{Event, EventFs, EventSink};
 use super::group_paths_by_parent;
-use agentfs_sdk::{AgentFS, AgentFSOptions, EncryptionConfig, HostFS, OverlayFS};
+use agentfs_sdk::{AgentFS, AgentFSOptions, EncryptionConfig, FileSystem, HostFS, OverlayFS};
 use anyhow::{bail, Context, Result};
 use std::{
     cmp::Reverse,
     ffi::CString,
     fs,
-    io::BufRead,
+    io::{BufRead, Read, Write},
     os::unix::ffi::OsStrExt,
     os::unix::fs::MetadataExt,
     os::unix::io::AsRawFd,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
     sync::{
         atomic::{AtomicI32, Ordering},
         Arc,
     },
 };
 use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
 
 /// Global child PID for signal forwarding.
 /// Set by the parent before installing signal handlers.
@@ -42,7 +45,9 @@ static CHILD_PID: AtomicI32 = AtomicI32::new(0);
 /// First signal forwards to child, second signal sends SIGKILL.
 static TERM_SIGNAL_COUNT: AtomicI32 = AtomicI32::new(0);
 
-use crate::mount::{is_mountpoint, mount_fs, MountBackend, MountHandle, MountOpts};
+use crate::mount::{
+    is_mountpoint, mount_fs, MountBackend, MountHandle, MountOpts, DEFAULT_SUBTYPE,
+};
 
 /// Exit code returned when exec fails (standard shell convention for "command not found")
 const EXIT_COMMAND_NOT_FOUND: i32 = 127;
@@ -137,21 +142,202 @@ fn install_signal_handlers() {
     }
 }
 
+/// Where a standard stream of the sandboxed command goes.
+#[derive(Debug, Clone, Default)]
+pub enum StdioCapture {
+    /// Share the stream with the calling process.
+    #[default]
+    Inherit,
+    /// Write the stream to a host file, truncating it first.
+    ///
+    /// The file is opened before the sandbox is set up, so the path refers to
+    /// the host filesystem even when it lies inside the overlaid directory.
+    File(PathBuf),
+    /// Collect the stream in memory and return it in [`RunOutput`].
+    Buffer,
+}
+
+/// Result of a sandboxed run.
+#[derive(Debug)]
+pub struct RunOutput {
+    /// Exit code of the command (128 + signal number if it was killed).
+    pub exit_code: i32,
+    /// Standard output, if it was captured with [`StdioCapture::Buffer`].
+    pub stdout: Option<Vec<u8>>,
+    /// Standard error, if it was captured with [`StdioCapture::Buffer`].
+    pub stderr: Option<Vec<u8>>,
+}
+
+/// An opened [`StdioCapture`].
+///
+/// Holds the descriptor the child installs as its stream and, for buffers,
+/// the read end the parent drains.
+enum StdioRedirect {
+    Inherit,
+    File(fs::File),
+    Pipe { read: fs::File, write: fs::File },
+}
+
+impl StdioRedirect {
+    fn open(capture: &StdioCapture) -> Result<Self> {
+        match capture {
+            StdioCapture::Inherit => Ok(Self::Inherit),
+            StdioCapture::File(path) => fs::File::create(path)
+                .map(Self::File)
+                .with_context(|| format!("Failed to create capture file {}", path.display())),
+            StdioCapture::Buffer => {
+                let mut fds: [libc::c_int; 2] = [0; 2];
+                // SAFETY: pipe2() with a valid array; O_CLOEXEC keeps the read
+                // end out of the executed command
+                if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+                    bail!("Failed to create pipe: {}", std::io::Error::last_os_error());
+                }
+                // SAFETY: Both fds were just returned by pipe2() and are owned here
+                let (read, write) = unsafe {
+                    use std::os::unix::io::FromRawFd;
+                    (fs::File::from_raw_fd(fds[0]), fs::File::from_raw_fd(fds[1]))
+                };
+                Ok(Self::Pipe { read, write })
+            }
+        }
+    }
+
+    /// Install the redirect as `target_fd` in the forked child.
+    fn install(&self, target_fd: libc::c_int) {
+        let fd = match self {
+            Self::Inherit => return,
+            Self::File(file) => file.as_raw_fd(),
+            Self::Pipe { write, .. } => write.as_raw_fd(),
+        };
+        // SAFETY: Both fds are valid; dup2() clears O_CLOEXEC on the target
+        if unsafe { libc::dup2(fd, target_fd) } < 0 {
+            child_exit(&format!(
+                "Failed to redirect fd {}: {}",
+                target_fd,
+                std::io::Error::last_os_error()
+            ));
+        }
+    }
+
+    /// Release the child's end in the parent and start collecting buffers.
+    fn into_collector(self) -> StdioCollector {
+        match self {
+            Self::Inherit => StdioCollector::None,
+            Self::File(file) => StdioCollector::File(file),
+            Self::Pipe { mut read, write } => {
+                // The reader only sees EOF once every write end is closed
+                drop(write);
+                StdioCollector::Buffer(std::thread::spawn(move || {
+                    let mut buf = Vec::new();
+                    if let Err(e) = read.read_to_end(&mut buf) {
+                        eprintln!("Warning: Failed to read captured output: {}", e);
+                    }
+                    buf
+                }))
+            }
+        }
+    }
+}
+
+/// The parent's side of a redirected stream while the child runs.
+enum StdioCollector {
+    None,
+    File(fs::File),
+    Buffer(std::thread::JoinHandle<Vec<u8>>),
+}
+
+impl StdioCollector {
+    /// Finish collecting once the child has exited.
+    ///
+    /// Waits for the stream to reach EOF, which also covers output from
+    /// background processes the command left running.
+    fn finish(self) -> Option<Vec<u8>> {
+        match self {
+            Self::None => None,
+            Self::File(file) => {
+                if let Err(e) = file.sync_all() {
+                    eprintln!("Warning: Failed to sync capture file: {}", e);
+                }
+                None
+            }
+            Self::Buffer(reader) => Some(reader.join().unwrap_or_default()),
+        }
+    }
+}
+
+/// Redirected stdout and stderr of a sandboxed command.
+struct ChildStdio {
+    stdout: StdioRedirect,
+    stderr: StdioRedirect,
+}
+
+impl ChildStdio {
+    fn open(stdout: &StdioCapture, stderr: &StdioCapture) -> Result<Self> {
+        Ok(Self {
+            stdout: StdioRedirect::open(stdout)?,
+            stderr: StdioRedirect::open(stderr)?,
+        })
+    }
+
+    /// Install the redirects in the forked child.
+    fn install(&self) {
+        self.stdout.install(libc::STDOUT_FILENO);
+        self.stderr.install(libc::STDERR_FILENO);
+    }
+
+    /// Split off the parent's collectors after fork.
+    fn into_collectors(self) -> (StdioCollector, StdioCollector) {
+        (self.stdout.into_collector(), self.stderr.into_collector())
+    }
+}
+
+/// Flush our own buffered output so the forked child does not inherit it.
+fn flush_stdio() {
+    let _ = std::io::stdout().flush();
+    let _ = std::io::stderr().flush();
+}
+
 /// Run a command in an overlay sandbox.
+///
+/// The command's stdout and stderr are passed through unless `stdout` or
+/// `stderr` asks for them to be captured. It starts in `workdir`, resolved
+/// within the overlay, or in the overlay's root. With `pid_namespace`, it runs
+/// in a new PID namespace under an init process that reaps orphans.
+#[allow(clippy::too_many_arguments)]
 pub async fn run_cmd(
     allow: Vec<PathBuf>,
     no_default_allows: bool,
     session_id: Option<String>,
     system: bool,
     encryption: Option<(String, String)>,
+    events: Option<PathBuf>,
+    audit_log: Option<PathBuf>,
+    stdout: StdioCapture,
+    stderr: StdioCapture,
+    delta_in: Option<PathBuf>,
+    delta_out: Option<PathBuf>,
+    workdir: Option<PathBuf>,
+    create_workdir: bool,
+    pid_namespace: bool,
     command: PathBuf,
     args: Vec<String>,
-) -> Result<()> {
+) -> Result<RunOutput> {
     let cwd = std::env::current_dir().context("Failed to get current directory")?;
 
+    // Snapshots are plain databases, so they can't seed or hold an encrypted delta
+    if encryption.is_some() && (delta_in.is_some() || delta_out.is_some()) {
+        bail!("--delta-in and --delta-out cannot be used with --key");
+    }
+
     // Build the list of allowed writable paths
     let allowed_paths = build_allowed_paths(&allow, no_default_allows)?;
 
+    let audit = match audit_log {
+        Some(path) => AuditLog::create(&path)
+            .with_context(|| format!("Failed to create audit log {}", path.display()))?,
+        None => AuditLog::disabled(),
+    };
+
     // Check if we're joining an existing session
     let session = setup_run_directory(session_id)?;
 
@@ -164,18 +350,34 @@ pub async fn run_cmd(
 
         eprintln!("Joining existing session: {}", session.run_id);
         eprintln!();
+        if events.is_some() {
+            eprintln!("Warning: --events is not supported when joining a session, ignoring");
+        }
+        if delta_in.is_some() || delta_out.is_some() {
+            eprintln!("Warning: --delta-in and --delta-out are not supported when joining a session, ignoring");
+        }
+        let stdio = ChildStdio::open(&stdout, &stderr)?;
+        let workdir = resolve_workdir(&overlay_base, workdir, create_workdir)?;
         return run_in_existing_session(
             &overlay_base,
             &session.fuse_mountpoint,
             &allowed_paths,
+            workdir.as_ref(),
+            pid_namespace,
             command,
             args,
             &session.run_id,
+            stdio,
+            &audit,
         );
     }
 
     print_welcome_banner(&cwd, &allowed_paths, &session.run_id, encryption.is_some());
 
+    // Open capture files up front, so a bad path fails before anything is mounted
+    let stdio = ChildStdio::open(&stdout, &stderr)?;
+    let workdir = resolve_workdir(&cwd, workdir, create_workdir)?;
+
     // Open the directory BEFORE mounting FUSE on top of it.
     // This fd lets us access the underlying directory through /proc/self/fd/N,
     // bypassing the FUSE mount that will be placed on top.
@@ -187,17 +389,6 @@ pub async fn run_cmd(
         .db_path
         .to_str()
         .context("Database path contains non-UTF8 characters")?;
-    let mut options = AgentFSOptions::with_path(db_path_str);
-    if let Some((key, cipher)) = encryption {
-        options = options.with_encryption(EncryptionConfig {
-            hex_key: key,
-            cipher,
-        });
-    }
-    let agentfs = AgentFS::open(options)
-        .await
-        .context("Failed to create delta AgentFS")?;
-
     let hostfs = HostFS::new(&fd_path).context("Failed to create HostFS")?;
     #[cfg(target_family = "unix")]
     let hostfs = {
@@ -208,7 +399,35 @@ pub async fn run_cmd(
     };
 
     let base = Arc::new(hostfs);
-    let overlay = OverlayFS::new(base, agentfs.fs);
+    let overlay = match &delta_in {
+        Some(snapshot) => {
+            if session.db_path.exists() {
+                bail!(
+                    "--delta-in cannot be used with session {}, which already has a delta",
+                    session.run_id
+                );
+            }
+            let snapshot = snapshot
+                .to_str()
+                .context("Delta path contains non-UTF8 characters")?;
+            OverlayFS::from_snapshot(base, snapshot, db_path_str)
+                .await
+                .with_context(|| format!("Failed to restore delta from {}", snapshot))?
+        }
+        None => {
+            let mut options = AgentFSOptions::with_path(db_path_str);
+            if let Some((key, cipher)) = encryption {
+                options = options.with_encryption(EncryptionConfig {
+                    hex_key: key,
+                    cipher,
+                });
+            }
+            let agentfs = AgentFS::open(options)
+                .await
+                .context("Failed to create delta AgentFS")?;
+            OverlayFS::new(base, agentfs.fs)
+        }
+    };
 
     let cwd_str = cwd
         .to_str()
@@ -222,6 +441,24 @@ pub async fn run_cmd(
     std::fs::write(&session.base_path_file, cwd_str)
         .context("Failed to write session base path")?;
 
+    // The delta outlives the mount, so it can be saved once the command exits
+    let delta = delta_out.as_ref().map(|_| overlay.delta().clone());
+
+    let events = match events {
+        Some(path) => Some(Arc::new(EventSink::create(&path).with_context(|| {
+            format!("Failed to create events file {}", path.display())
+        })?)),
+        None => None,
+    };
+    let fs: Arc<Mutex<dyn FileSystem + Send>> = match &events {
+        Some(sink) => Arc::new(Mutex::new(EventFs::new(
+            Arc::new(overlay),
+            sink.clone(),
+            cwd_str,
+        ))),
+        None => Arc::new(Mutex::new(overlay)),
+    };
+
     // SAFETY: getuid/getgid are always safe
     let uid = unsafe { libc::getuid() };
     let gid = unsafe { libc::getgid() };
@@ -230,22 +467,44 @@ pub async fn run_cmd(
         mountpoint: session.fuse_mountpoint.clone(),
         backend: MountBackend::Fuse,
         fsname: format!("agentfs:{}", session.run_id),
+        subtype: Some(DEFAULT_SUBTYPE.to_string()),
         uid: Some(uid),
         gid: Some(gid),
         allow_other: system,
         allow_root: false,
         auto_unmount: false,
         lazy_unmount: true,
+        extra_options: Vec::new(),
+        op_timeout: None,
+        max_read: None,
+        max_write: None,
+        attr_timeout: None,
+        entry_timeout: None,
+        allowed_clients: None,
+        checkpoint_interval: None,
+        database: None,
+        read_only: false,
+        root_subdir: None,
+        runtime: None,
         timeout: FUSE_MOUNT_TIMEOUT,
+        cancel: CancellationToken::new(),
     };
 
     // Mount the overlay filesystem
-    let mount_handle = mount_fs(Arc::new(Mutex::new(overlay)), mount_opts).await?;
+    let audit_entry = AuditEntry::new("mount_fuse")
+        .path("mountpoint", &mount_opts.mountpoint)
+        .param("fsname", mount_opts.fsname.clone())
+        .param("allow_other", mount_opts.allow_other);
+    let mount_handle = mount_fs(fs, mount_opts).await;
+    audit.record(audit_entry.outcome(&mount_handle));
+    let mount_handle = mount_handle?;
 
     // Create pipes for parent-child coordination.
     // The parent needs to write uid_map/gid_map for the child after unshare.
     let (pipe_to_child, pipe_to_parent) = create_sync_pipes()?;
 
+    flush_stdio();
+
     // SAFETY: fork() is safe when called from a single-threaded context before
     // the child performs any async-signal-unsafe operations. Our child immediately
     // closes unused fds and calls exec after namespace setup.
@@ -268,11 +527,15 @@ pub async fn run_cmd(
             &cwd,
             &session.fuse_mountpoint,
             &allowed_paths,
+            workdir.as_ref(),
+            pid_namespace,
             command,
             args,
             &session.run_id,
             pipe_to_child[0],
             pipe_to_parent[1],
+            &stdio,
+            &audit,
         );
     } else {
         // SAFETY: Closing unused pipe ends in parent; these fds are valid from pipe()
@@ -280,6 +543,7 @@ pub async fn run_cmd(
             libc::close(pipe_to_child[0]); // Close read end
             libc::close(pipe_to_parent[1]); // Close write end
         }
+        let collectors = stdio.into_collectors();
 
         // Wait for child to signal it has called unshare
         if !wait_for_pipe_signal(pipe_to_parent[0]) {
@@ -288,7 +552,7 @@ pub async fn run_cmd(
         }
 
         // Configure user namespace mappings for the child
-        write_namespace_mappings(child_pid, uid, gid, pipe_to_child[1]);
+        write_namespace_mappings(child_pid, uid, gid, pipe_to_child[1], &audit);
 
         // Signal child that mappings are done
         // SAFETY: Writing to and closing valid pipe fds
@@ -305,23 +569,67 @@ pub async fn run_cmd(
             eprintln!("Warning: Failed to write proc file: {}", e);
         }
 
+        if let Some(sink) = &events {
+            sink.record(
+                Event::new("spawn", Some(command.to_string_lossy().to_string())).pid(child_pid),
+            );
+        }
+
         // Keep cwd_fd alive - it's needed by HostFS in the FUSE thread
-        run_parent(child_pid, cwd_fd, mount_handle, &session.run_id);
+        let output = run_parent(
+            child_pid,
+            cwd_fd,
+            mount_handle,
+            &session.run_id,
+            &command,
+            events,
+            collectors,
+        );
+
+        if let (Some(delta), Some(path)) = (delta, delta_out) {
+            save_delta(&delta, &path).await?;
+            eprintln!("Delta saved to {}", path.display());
+        }
+        Ok(output)
     }
 }
 
+/// Write a snapshot of the delta layer to `path`, replacing any existing file.
+///
+/// The snapshot is written next to `path` and renamed into place, so a failed
+/// save leaves a previous snapshot intact.
+async fn save_delta(delta: &agentfs_sdk::filesystem::AgentFS, path: &Path) -> Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    let tmp = tmp_path
+        .to_str()
+        .context("Delta path contains non-UTF8 characters")?;
+    let _ = std::fs::remove_file(&tmp_path);
+    delta
+        .snapshot(tmp)
+        .await
+        .with_context(|| format!("Failed to save delta to {}", path.display()))?;
+    std::fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to save delta to {}", path.display()))?;
+    Ok(())
+}
+
 /// Run a command in an existing session's FUSE mount.
 ///
 /// This is used when joining an existing session that already has a FUSE mount active.
 /// We don't need to start a new FUSE server, just run the command in the existing mount.
+#[allow(clippy::too_many_arguments)]
 fn run_in_existing_session(
     cwd: &Path,
     fuse_mountpoint: &Path,
     allowed_paths: &[PathBuf],
+    workdir: Option<&Workdir>,
+    pid_namespace: bool,
     command: PathBuf,
     args: Vec<String>,
     session_id: &str,
-) -> Result<()> {
+    stdio: ChildStdio,
+    audit: &AuditLog,
+) -> Result<RunOutput> {
     // SAFETY: getuid/getgid are always safe
     let uid = unsafe { libc::getuid() };
     let gid = unsafe { libc::getgid() };
@@ -329,6 +637,8 @@ fn run_in_existing_session(
     // Create pipes for parent-child coordination.
     let (pipe_to_child, pipe_to_parent) = create_sync_pipes()?;
 
+    flush_stdio();
+
     // SAFETY: fork() is safe here
     let child_pid = unsafe { libc::fork() };
 
@@ -347,11 +657,15 @@ fn run_in_existing_session(
             cwd,
             fuse_mountpoint,
             allowed_paths,
+            workdir,
+            pid_namespace,
             command,
             args,
             session_id,
             pipe_to_child[0],
             pipe_to_parent[1],
+            &stdio,
+            audit,
         );
     } else {
         // Parent process
@@ -359,6 +673,7 @@ fn run_in_existing_session(
             libc::close(pipe_to_child[0]);
             libc::close(pipe_to_parent[1]);
         }
+        let (stdout, stderr) = stdio.into_collectors();
 
         // Wait for child to signal it has called unshare
         if !wait_for_pipe_signal(pipe_to_parent[0]) {
@@ -367,7 +682,7 @@ fn run_in_existing_session(
         }
 
         // Configure user namespace mappings for the child
-        write_namespace_mappings(child_pid, uid, gid, pipe_to_child[1]);
+        write_namespace_mappings(child_pid, uid, gid, pipe_to_child[1], audit);
 
         // Signal child that mappings are done
         unsafe {
@@ -394,8 +709,49 @@ fn run_in_existing_session(
         // Clean up proc file
         crate::cmd::ps::remove_proc_file(session_id);
 
-        std::process::exit(exit_code);
+        Ok(RunOutput {
+            exit_code,
+            stdout: stdout.finish(),
+            stderr: stderr.finish(),
+        })
+    }
+}
+
+/// Directory of the overlay that the command starts in.
+struct Workdir {
+    /// Path of the directory within the overlay, as given by the user.
+    virtual_path: PathBuf,
+    /// Where the directory appears once the overlay is mounted.
+    path: PathBuf,
+    /// Create the directory (and its parents) if it does not exist.
+    create: bool,
+}
+
+/// Resolve `workdir`, a path relative to the root of the overlay mounted
+/// on `base`.
+///
+/// The path may be written as absolute (`/workspace`) or relative
+/// (`workspace`), but cannot leave the overlay through `..`.
+fn resolve_workdir(base: &Path, workdir: Option<PathBuf>, create: bool) -> Result<Option<Workdir>> {
+    let Some(virtual_path) = workdir else {
+        return Ok(None);
+    };
+    let mut path = base.to_path_buf();
+    for component in virtual_path.components() {
+        match component {
+            Component::RootDir | Component::CurDir => {}
+            Component::Normal(name) => path.push(name),
+            Component::ParentDir | Component::Prefix(_) => bail!(
+                "Working directory {} must be within the sandbox",
+                virtual_path.display()
+            ),
+        }
     }
+    Ok(Some(Workdir {
+        virtual_path,
+        path,
+        create,
+    }))
 }
 
 /// Print the welcome banner showing sandbox configuration.
@@ -510,31 +866,50 @@ fn write_namespace_mappings(
     uid: libc::uid_t,
     gid: libc::gid_t,
     pipe_write_fd: libc::c_int,
+    audit: &AuditLog,
 ) {
-    let uid_map_path = format!("/proc/{}/uid_map", child_pid);
-    let gid_map_path = format!("/proc/{}/gid_map", child_pid);
-    let setgroups_path = format!("/proc/{}/setgroups", child_pid);
+    let write = |action: &'static str, file: &str, contents: String| {
+        let path = format!("/proc/{}/{}", child_pid, file);
+        let result = std::fs::write(&path, &contents);
+        audit.record(
+            AuditEntry::new(action)
+                .param("child_pid", child_pid)
+                .param("path", path)
+                .param("contents", contents.trim_end())
+                .outcome(&result),
+        );
+        result
+    };
 
     // Map the user's UID to itself (inside_uid outside_uid count)
-    if let Err(e) = std::fs::write(&uid_map_path, format!("{} {} 1\n", uid, uid)) {
+    if let Err(e) = write("write_uid_map", "uid_map", format!("{} {} 1\n", uid, uid)) {
         eprintln!("Error: Could not write uid_map: {}", e);
         eprintln!("This may indicate missing unprivileged user namespace support.");
         abort_child(pipe_write_fd, child_pid);
     }
 
     // Disable setgroups (required before writing gid_map on unprivileged systems)
-    if let Err(e) = std::fs::write(&setgroups_path, "deny") {
+    if let Err(e) = write("write_setgroups", "setgroups", "deny".to_string()) {
         eprintln!("Error: Could not write setgroups: {}", e);
         abort_child(pipe_write_fd, child_pid);
     }
 
     // Map the user's GID to itself (inside_gid outside_gid count)
-    if let Err(e) = std::fs::write(&gid_map_path, format!("{} {} 1\n", gid, gid)) {
+    if let Err(e) = write("write_gid_map", "gid_map", format!("{} {} 1\n", gid, gid)) {
         eprintln!("Error: Could not write gid_map: {}", e);
         abort_child(pipe_write_fd, child_pid);
     }
 }
 
+/// Result of a libc call that returns 0 on success and sets errno otherwise.
+fn os_result(ret: libc::c_int) -> std::io::Result<()> {
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
 /// Convert a path to a CString, exiting the child process on failure.
 ///
 /// Used in the child process context where we cannot return errors normally.
@@ -575,20 +950,37 @@ fn run_child(
     cwd: &Path,
     fuse_mountpoint: &Path,
     allowed_paths: &[PathBuf],
+    workdir: Option<&Workdir>,
+    pid_namespace: bool,
     command: PathBuf,
     args: Vec<String>,
     session_id: &str,
     pipe_from_parent: libc::c_int,
     pipe_to_parent: libc::c_int,
+    stdio: &ChildStdio,
+    audit: &AuditLog,
 ) -> ! {
     // Step 1: Create new user + mount namespaces for unprivileged isolation.
     // User namespace gives us CAP_SYS_ADMIN within the namespace to manipulate mounts.
+    // A new PID namespace only applies to children, so the init process forked
+    // in step 10 becomes its PID 1.
+    let (flags, namespaces) = if pid_namespace {
+        (
+            libc::CLONE_NEWUSER | libc::CLONE_NEWNS | libc::CLONE_NEWPID,
+            "user,mount,pid",
+        )
+    } else {
+        (libc::CLONE_NEWUSER | libc::CLONE_NEWNS, "user,mount")
+    };
     // SAFETY: unshare() with valid flags is safe; we handle the error case.
-    if unsafe { libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNS) } != 0 {
-        child_exit(&format!(
-            "Failed to unshare namespaces: {}",
-            std::io::Error::last_os_error()
-        ));
+    let result = os_result(unsafe { libc::unshare(flags) });
+    audit.record(
+        AuditEntry::new("unshare")
+            .param("namespaces", namespaces)
+            .outcome(&result),
+    );
+    if let Err(e) = result {
+        child_exit(&format!("Failed to unshare namespaces: {}", e));
     }
 
     // Step 2: Signal parent that unshare is complete so it can write uid_map/gid_map.
@@ -608,7 +1000,7 @@ fn run_child(
     // Step 4: Make all mounts private to prevent propagation to parent namespace.
     let root = CString::new("/").unwrap();
     // SAFETY: mount() with MS_PRIVATE on "/" is safe; changes only affect this namespace.
-    if unsafe {
+    let result = os_result(unsafe {
         libc::mount(
             std::ptr::null(),
             root.as_ptr(),
@@ -616,12 +1008,14 @@ fn run_child(
             libc::MS_REC | libc::MS_PRIVATE,
             std::ptr::null(),
         )
-    } != 0
-    {
-        child_exit(&format!(
-            "Failed to make mounts private: {}",
-            std::io::Error::last_os_error()
-        ));
+    });
+    audit.record(
+        AuditEntry::new("make_mounts_private")
+            .param("target", "/")
+            .outcome(&result),
+    );
+    if let Err(e) = result {
+        child_exit(&format!("Failed to make mounts private: {}", e));
     }
 
     // Step 5: Bind mount the FUSE overlay from temp dir onto cwd.
@@ -630,7 +1024,7 @@ fn run_child(
     let cwd_cstr = path_to_cstring(cwd, "working directory path");
 
     // SAFETY: mount() with MS_BIND and valid paths is safe.
-    if unsafe {
+    let result = os_result(unsafe {
         libc::mount(
             fuse_cstr.as_ptr(),
             cwd_cstr.as_ptr(),
@@ -638,12 +1032,15 @@ fn run_child(
             libc::MS_BIND,
             std::ptr::null(),
         )
-    } != 0
-    {
-        child_exit(&format!(
-            "Failed to bind mount FUSE overlay: {}",
-            std::io::Error::last_os_error()
-        ));
+    });
+    audit.record(
+        AuditEntry::new("bind_mount")
+            .path("source", fuse_mountpoint)
+            .path("target", cwd)
+            .outcome(&result),
+    );
+    if let Err(e) = result {
+        child_exit(&format!("Failed to bind mount FUSE overlay: {}", e));
     }
 
     // Step 6: Change to cwd to ensure we're using the overlay.
@@ -652,14 +1049,139 @@ fn run_child(
     }
 
     // Step 7: Remount all other filesystems as read-only.
-    if let Err(e) = remount_all_readonly_except(cwd, allowed_paths) {
+    if let Err(e) = remount_all_readonly_except(cwd, allowed_paths, audit) {
         child_exit(&format!("Failed to remount filesystems read-only: {}", e));
     }
 
-    // Step 8: Execute the command (does not return).
+    // Step 8: Move to the requested working directory. Directories created
+    // here go through the overlay, into the delta.
+    if let Some(workdir) = workdir {
+        if workdir.create {
+            if let Err(e) = fs::create_dir_all(&workdir.path) {
+                child_exit(&format!(
+                    "Failed to create working directory {}: {}",
+                    workdir.virtual_path.display(),
+                    e
+                ));
+            }
+        }
+        if let Err(e) = std::env::set_current_dir(&workdir.path) {
+            child_exit(&format!(
+                "Failed to change to working directory {}: {}",
+                workdir.virtual_path.display(),
+                e
+            ));
+        }
+    }
+
+    // Step 9: Redirect stdio as requested, so that exec failures are
+    // reported on the command's own stderr.
+    stdio.install();
+
+    // Step 10: Execute the command (does not return), under an init process
+    // when it runs in its own PID namespace.
+    if pid_namespace {
+        run_pid_namespace(command, args, session_id, audit);
+    }
     exec_command(command, args, session_id);
 }
 
+/// Fork the init process of the new PID namespace and exit with its status.
+///
+/// This process stays outside the namespace, so it forwards the signals the
+/// parent sends it on to the init process.
+fn run_pid_namespace(command: PathBuf, args: Vec<String>, session_id: &str, audit: &AuditLog) -> ! {
+    // SAFETY: fork() in the single-threaded child is safe.
+    let init_pid = unsafe { libc::fork() };
+    if init_pid < 0 {
+        child_exit(&format!(
+            "Failed to fork init process: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    if init_pid == 0 {
+        run_init(command, args, session_id, audit);
+    }
+
+    CHILD_PID.store(init_pid, Ordering::SeqCst);
+    install_signal_handlers();
+    let exit_code = wait_for_child(init_pid);
+    // SAFETY: In forked child, _exit() is the correct way to terminate.
+    unsafe { libc::_exit(exit_code) }
+}
+
+/// Init process (PID 1) of the sandbox's PID namespace.
+///
+/// Runs the command as its only child, forwards SIGTERM and SIGINT to it, and
+/// reaps every process that gets reparented here until the command exits. It
+/// then exits with the command's status, which makes the kernel kill whatever
+/// is left in the namespace.
+fn run_init(command: PathBuf, args: Vec<String>, session_id: &str, audit: &AuditLog) -> ! {
+    // Don't outlive the process waiting on us, which would leave the
+    // namespace running without anyone to report its status to.
+    // SAFETY: prctl() with PR_SET_PDEATHSIG and a valid signal is safe.
+    unsafe { libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) };
+
+    // Mount a /proc that shows this namespace's processes. It is not
+    // required for the command to run, so a failure is only reported.
+    let proc_cstr = CString::new("/proc").unwrap();
+    let fstype = CString::new("proc").unwrap();
+    // SAFETY: mount() with a valid target is safe; it only affects this namespace.
+    let result = os_result(unsafe {
+        libc::mount(
+            proc_cstr.as_ptr(),
+            proc_cstr.as_ptr(),
+            fstype.as_ptr(),
+            libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+            std::ptr::null(),
+        )
+    });
+    audit.record(
+        AuditEntry::new("mount_proc")
+            .param("target", "/proc")
+            .outcome(&result),
+    );
+    if let Err(e) = result {
+        eprintln!("Warning: Failed to mount /proc in the PID namespace: {}", e);
+    }
+
+    // SAFETY: fork() in the single-threaded init process is safe.
+    let command_pid = unsafe { libc::fork() };
+    if command_pid < 0 {
+        child_exit(&format!(
+            "Failed to fork command: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    if command_pid == 0 {
+        exec_command(command, args, session_id);
+    }
+
+    // As PID 1, signals without a handler are ignored, so the forwarding
+    // handlers are the only way to reach the command.
+    CHILD_PID.store(command_pid, Ordering::SeqCst);
+    install_signal_handlers();
+
+    let mut exit_code = 1;
+    loop {
+        let mut status: libc::c_int = 0;
+        // SAFETY: waitpid on any child with a valid status pointer is safe.
+        let pid = unsafe { libc::waitpid(-1, &mut status, 0) };
+        if pid == -1 {
+            if std::io::Error::last_os_error().raw_os_error() == Some(libc::EINTR) {
+                continue;
+            }
+            break;
+        }
+        if pid == command_pid {
+            exit_code = wait_status_to_exit_code(status);
+            break;
+        }
+    }
+    // SAFETY: In forked child, _exit() is the correct way to terminate.
+    unsafe { libc::_exit(exit_code) }
+}
+
 /// Remount all filesystems as read-only, except for the specified paths.
 ///
 /// The correct sequence to keep allowed paths writable:
@@ -672,6 +1194,7 @@ fn run_child(
 fn remount_all_readonly_except(
     writable_path: &Path,
     allowed_paths: &[PathBuf],
+    audit: &AuditLog,
 ) -> std::io::Result<()> {
     // Step 1: Bind-mount allowed paths to themselves FIRST
     // This creates independent mountpoints that will survive the ro remount
@@ -683,7 +1206,7 @@ fn remount_all_readonly_except(
 
         // Bind mount to itself to establish new mountpoint (inherits rw)
         // SAFETY: mount() with valid paths
-        let bind_result = unsafe {
+        let bind_result = os_result(unsafe {
             libc::mount(
                 path_cstr.as_ptr(),
                 path_cstr.as_ptr(),
@@ -691,12 +1214,18 @@ fn remount_all_readonly_except(
                 libc::MS_BIND,
                 std::ptr::null(),
             )
-        };
+        });
+        audit.record(
+            AuditEntry::new("bind_mount")
+                .path("source", allowed)
+                .path("target", allowed)
+                .outcome(&bind_result),
+        );
 
-        if bind_result == 0 {
+        if bind_result.is_ok() {
             // Step 2: Explicitly remount with rw,bind to lock in the rw flag
             // SAFETY: mount() with valid path
-            let _ = unsafe {
+            let remount_result = os_result(unsafe {
                 libc::mount(
                     std::ptr::null(),
                     path_cstr.as_ptr(),
@@ -704,7 +1233,13 @@ fn remount_all_readonly_except(
                     libc::MS_BIND | libc::MS_REMOUNT,
                     std::ptr::null(),
                 )
-            };
+            });
+            audit.record(
+                AuditEntry::new("remount")
+                    .path("target", allowed)
+                    .param("read_only", false)
+                    .outcome(&remount_result),
+            );
         }
     }
 
@@ -766,7 +1301,7 @@ fn remount_all_readonly_except(
 
         // First bind mount on itself to create a distinct mount point.
         // SAFETY: mount() with valid CString path; failures are expected and handled.
-        let bind_result = unsafe {
+        let bind_result = os_result(unsafe {
             libc::mount(
                 mount_cstr.as_ptr(),
                 mount_cstr.as_ptr(),
@@ -774,17 +1309,24 @@ fn remount_all_readonly_except(
                 libc::MS_BIND | libc::MS_REC,
                 std::ptr::null(),
             )
-        };
+        });
+        audit.record(
+            AuditEntry::new("bind_mount")
+                .path("source", mount_point)
+                .path("target", mount_point)
+                .param("recursive", true)
+                .outcome(&bind_result),
+        );
 
-        if bind_result != 0 {
+        if bind_result.is_err() {
             // Some mounts can't be bind-mounted (e.g., already bind mounts), skip them
             continue;
         }
 
         // Remount the bind mount as read-only.
-        // SAFETY: mount() with valid path; failures silently ignored as some
+        // SAFETY: mount() with valid path; failures are only audited as some
         // filesystems (e.g., tmpfs with running processes) cannot be remounted.
-        let _ = unsafe {
+        let remount_result = os_result(unsafe {
             libc::mount(
                 std::ptr::null(),
                 mount_cstr.as_ptr(),
@@ -792,7 +1334,13 @@ fn remount_all_readonly_except(
                 libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY,
                 std::ptr::null(),
             )
-        };
+        });
+        audit.record(
+            AuditEntry::new("remount")
+                .path("target", mount_point)
+                .param("read_only", true)
+                .outcome(&remount_result),
+        );
     }
 
     Ok(())
@@ -883,19 +1431,33 @@ fn unescape_mountinfo(s: &str) -> String {
 /// Parent process: wait for child to exit, then clean up.
 ///
 /// The MountHandle automatically unmounts when dropped. We explicitly drop it
-/// before calling exit() to ensure cleanup happens.
+/// here, so the mount is gone by the time the caller sees the exit code.
+/// Captured output is collected before the session summary is printed.
 fn run_parent(
     child_pid: i32,
     cwd_fd: std::fs::File,
     mount_handle: MountHandle,
     session_id: &str,
-) -> ! {
+    command: &Path,
+    events: Option<Arc<EventSink>>,
+    (stdout, stderr): (StdioCollector, StdioCollector),
+) -> RunOutput {
     // Store child PID and install signal handlers before waiting
     CHILD_PID.store(child_pid, Ordering::SeqCst);
     install_signal_handlers();
 
     // Wait for child process to exit, retrying on EINTR (signal interruption)
     let exit_code = wait_for_child(child_pid);
+    let stdout = stdout.finish();
+    let stderr = stderr.finish();
+
+    if let Some(sink) = &events {
+        sink.record(
+            Event::new("exit", Some(command.to_string_lossy().to_string()))
+                .pid(child_pid)
+                .exit_code(exit_code),
+        );
+    }
 
     // Clean up proc file
     crate::cmd::ps::remove_proc_file(session_id);
@@ -932,7 +1494,11 @@ fn run_parent(
     eprintln!("To see what changed:");
     eprintln!("  agentfs diff {}", session_id);
 
-    std::process::exit(exit_code);
+    RunOutput {
+        exit_code,
+        stdout,
+        stderr,
+    }
 }
 
 /// Execute the command, replacing the current process.
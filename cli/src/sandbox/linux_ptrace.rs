@@ -32,7 +32,7 @@ pub async fn run_cmd(strace: bool, command: PathBuf, args: Vec<String>) {
     let vfs = SqliteVfs::new(&db_path, mount_point.clone())
         .await
         .expect("Failed to create AgentFS VFS");
-    mount_table.add_mount(mount_point, Arc::new(vfs));
+    mount_table.add_mount(mount_point, Arc::new(vfs.clone()));
 
     init_mount_table(mount_table);
     init_fd_tables();
@@ -46,5 +46,17 @@ pub async fn run_cmd(strace: bool, command: PathBuf, args: Vec<String>) {
     let tracer = TracerBuilder::<Sandbox>::new(cmd).spawn().await.unwrap();
 
     let (status, _) = tracer.wait().await.unwrap();
+
+    // Handles the command never closed point at leaks in the fd tracking
+    if strace {
+        for handle in vfs.open_handles() {
+            eprintln!(
+                "[agentfs] still open: {} (ino {}, flags {:#o})",
+                handle.path.display(),
+                handle.ino,
+                handle.flags
+            );
+        }
+    }
     status.raise_or_exit()
 }
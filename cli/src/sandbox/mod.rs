@@ -4,10 +4,19 @@
 //! - `linux`: FUSE + namespace-based sandbox with copy-on-write filesystem
 //! - `linux_ptrace`: ptrace-based syscall interception sandbox (experimental)
 //! - `darwin`: Kernel-enforced sandbox using sandbox-exec
+//!
+//! `events` records a newline-delimited JSON event stream for `run --events`,
+//! and `audit` the privileged setup actions of the sandbox for `run --audit-log`.
 
 use std::collections::BTreeMap;
 use std::path::PathBuf;
 
+#[cfg(all(target_os = "linux", feature = "sandbox"))]
+pub mod audit;
+
+#[cfg(all(target_os = "linux", feature = "sandbox"))]
+pub mod events;
+
 #[cfg(all(target_os = "linux", feature = "sandbox"))]
 pub mod linux;
 
@@ -18,8 +18,18 @@ DIR="$(dirname "$0")"
 "$DIR/test-run-syscalls.sh" || true  # Requires user namespaces (may fail in CI)
 
 "$DIR/test-run-bash.sh" || true  # Requires user namespaces (may fail in CI)
+"$DIR/test-run-events.sh" || true  # Requires user namespaces (may fail in CI)
+"$DIR/test-run-capture.sh" || true  # Requires user namespaces (may fail in CI)
+"$DIR/test-run-delta.sh" || true  # Requires user namespaces (may fail in CI)
+"$DIR/test-run-workdir.sh" || true  # Requires user namespaces (may fail in CI)
+"$DIR/test-run-audit.sh" || true  # Requires user namespaces (may fail in CI)
+"$DIR/test-run-pid-namespace.sh" || true  # Requires user namespaces (may fail in CI)
 "$DIR/test-run-git.sh" || true  # Requires user namespaces (may fail in CI)
 "$DIR/test-mount.sh"
+"$DIR/test-mount-options.sh"
+"$DIR/test-mount-create.sh"
+"$DIR/test-mount-base-delta.sh"
+"$DIR/test-mount-cleanup-stale.sh"
 "$DIR/test-overlay-whiteout.sh"
 "$DIR/test-overlay-delta-in-base-dir.sh"
 "$DIR/test-fuse-cache-invalidation.sh"
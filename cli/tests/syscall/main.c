@@ -8,6 +8,11 @@ typedef struct {
 } test_case_t;
 
 int main(int argc, char *argv[]) {
+    /* Re-exec'd by the cloexec test to inspect the inherited fds */
+    if (argc > 1 && strcmp(argv[1], "--cloexec-child") == 0) {
+        return cloexec_child(argc, argv);
+    }
+
     if (argc != 2) {
         fprintf(stderr, "Usage: %s <base_path>\n", argv[0]);
         fprintf(stderr, "Example: %s /sandbox\n", argv[0]);
@@ -39,6 +44,7 @@ int main(int argc, char *argv[]) {
         {"mkfifo", test_mkfifo},
         {"copyup_permissions", test_copyup_permissions},
         {"open_readonly", test_open_readonly},
+        {"cloexec", test_cloexec},
     };
 
     int num_tests = sizeof(tests) / sizeof(tests[0]);
@@ -0,0 +1,73 @@
+#define _GNU_SOURCE
+#include "test-common.h"
+#include <fcntl.h>
+#include <unistd.h>
+#include <sys/wait.h>
+
+/*
+ * Runs in the program exec'd by test_cloexec, with the fds to check as
+ * arguments. Exits 0 if the close-on-exec fds are gone and the other is not.
+ */
+int cloexec_child(int argc, char *argv[]) {
+    if (argc != 5) {
+        return 2;
+    }
+    int opened = atoi(argv[2]);
+    int set = atoi(argv[3]);
+    int kept = atoi(argv[4]);
+
+    if (fcntl(opened, F_GETFD) != -1 || errno != EBADF) {
+        return 3;
+    }
+    if (fcntl(set, F_GETFD) != -1 || errno != EBADF) {
+        return 4;
+    }
+    if (fcntl(kept, F_GETFD) < 0) {
+        return 5;
+    }
+    return 0;
+}
+
+int test_cloexec(const char *base_path) {
+    char path[512];
+    char opened_arg[16], set_arg[16], kept_arg[16];
+    int opened, set, kept, status;
+    pid_t pid;
+
+    snprintf(path, sizeof(path), "%s/test.txt", base_path);
+
+    /* Test 1: O_CLOEXEC at open time */
+    opened = open(path, O_RDONLY | O_CLOEXEC);
+    TEST_ASSERT_ERRNO(opened >= 0, "open with O_CLOEXEC should succeed");
+    TEST_ASSERT(fcntl(opened, F_GETFD) == FD_CLOEXEC, "O_CLOEXEC fd should report FD_CLOEXEC");
+
+    /* Test 2: FD_CLOEXEC set with fcntl */
+    set = open(path, O_RDONLY);
+    TEST_ASSERT_ERRNO(set >= 0, "open should succeed");
+    TEST_ASSERT_ERRNO(fcntl(set, F_SETFD, FD_CLOEXEC) == 0, "F_SETFD should succeed");
+    TEST_ASSERT(fcntl(set, F_GETFD) == FD_CLOEXEC, "F_GETFD should report FD_CLOEXEC");
+
+    kept = open(path, O_RDONLY);
+    TEST_ASSERT_ERRNO(kept >= 0, "open should succeed");
+
+    /* Test 3: exec closes the close-on-exec fds and keeps the other */
+    snprintf(opened_arg, sizeof(opened_arg), "%d", opened);
+    snprintf(set_arg, sizeof(set_arg), "%d", set);
+    snprintf(kept_arg, sizeof(kept_arg), "%d", kept);
+    pid = fork();
+    TEST_ASSERT_ERRNO(pid >= 0, "fork should succeed");
+    if (pid == 0) {
+        execl("/proc/self/exe", "test-syscalls", "--cloexec-child",
+              opened_arg, set_arg, kept_arg, (char *)NULL);
+        _exit(127);
+    }
+    TEST_ASSERT_ERRNO(waitpid(pid, &status, 0) == pid, "waitpid should succeed");
+    TEST_ASSERT(WIFEXITED(status) && WEXITSTATUS(status) == 0,
+                "exec should close the close-on-exec fds and keep the other");
+
+    close(opened);
+    close(set);
+    close(kept);
+
+    return 0;
+}
@@ -57,5 +57,7 @@ int test_mknod(const char *base_path);
 int test_mkfifo(const char *base_path);
 int test_copyup_permissions(const char *base_path);
 int test_open_readonly(const char *base_path);
+int test_cloexec(const char *base_path);
+int cloexec_child(int argc, char *argv[]);
 
 #endif /* TEST_COMMON_H */
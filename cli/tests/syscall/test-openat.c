@@ -1,6 +1,7 @@
 #define _GNU_SOURCE
 #include "test-common.h"
 #include <fcntl.h>
+#include <sys/stat.h>
 #include <unistd.h>
 
 int test_openat(const char *base_path) {
@@ -20,6 +21,28 @@ int test_openat(const char *base_path) {
     fd = openat(dirfd, "test.txt", O_RDONLY);
     TEST_ASSERT_ERRNO(fd >= 0, "openat with directory fd should succeed");
     close(fd);
+
+    /* Test 2b: Relative paths are composed with the directory fd's path */
+    fd = openat(dirfd, "./test.txt", O_RDONLY);
+    TEST_ASSERT_ERRNO(fd >= 0, "openat with ./ relative to directory fd should succeed");
+    close(fd);
+
+    struct stat st;
+    TEST_ASSERT_ERRNO(fstatat(dirfd, "test.txt", &st, 0) == 0,
+                      "fstatat relative to directory fd should succeed");
+    TEST_ASSERT(S_ISREG(st.st_mode), "fstatat relative to directory fd should find a regular file");
+
+    /* Test 2c: Create a file relative to the directory fd */
+    fd = openat(dirfd, "created-at.txt", O_WRONLY | O_CREAT | O_TRUNC, 0644);
+    TEST_ASSERT_ERRNO(fd >= 0, "openat with O_CREAT relative to directory fd should succeed");
+    close(fd);
+
+    snprintf(path, sizeof(path), "%s/created-at.txt", base_path);
+    TEST_ASSERT_ERRNO(stat(path, &st) == 0, "file created relative to directory fd should exist");
+
+    /* Test 2d: An invalid directory fd fails with EBADF */
+    fd = openat(9999, "test.txt", O_RDONLY);
+    TEST_ASSERT(fd < 0 && errno == EBADF, "openat with invalid directory fd should fail with EBADF");
     close(dirfd);
 
     /* Test 3: Open with O_CREAT flag */
@@ -0,0 +1,77 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST mount --base --delta... "
+
+DB_DIR="$(mktemp -d)"
+BASE="$DB_DIR/base.db"
+DELTA="$DB_DIR/delta.db"
+MOUNTPOINT="/tmp/agentfs-test-mount-base-delta-$$"
+
+cleanup() {
+    fusermount -u "$MOUNTPOINT" 2>/dev/null || true
+    rmdir "$MOUNTPOINT" 2>/dev/null || true
+    rm -rf "$DB_DIR"
+}
+
+trap cleanup EXIT
+
+mkdir -p "$MOUNTPOINT"
+
+# Mount in the background with the given arguments and wait until ready
+mount_bg() {
+    cargo run -- mount "$@" "$MOUNTPOINT" --foreground &
+    MOUNT_PID=$!
+
+    WAITED=0
+    while [ $WAITED -lt 10 ]; do
+        if mountpoint -q "$MOUNTPOINT" 2>/dev/null; then
+            return 0
+        fi
+        sleep 0.5
+        WAITED=$((WAITED + 1))
+    done
+    echo "FAILED: mount did not become ready in time"
+    kill $MOUNT_PID 2>/dev/null || true
+    exit 1
+}
+
+unmount() {
+    fusermount -u "$MOUNTPOINT"
+    wait $MOUNT_PID 2>/dev/null || true
+}
+
+# Populate the base
+mount_bg "$BASE" --create
+echo "base" > "$MOUNTPOINT/base.txt"
+unmount
+
+# The overlay shows the base and takes writes
+mount_bg --base "$BASE" --delta "$DELTA"
+if [ "$(cat "$MOUNTPOINT/base.txt")" != "base" ]; then
+    echo "FAILED: base file not visible through the overlay"
+    unmount
+    exit 1
+fi
+echo "new" > "$MOUNTPOINT/new.txt"
+unmount
+
+# The write landed in the delta...
+mount_bg "$DELTA"
+if [ "$(cat "$MOUNTPOINT/new.txt" 2>/dev/null)" != "new" ]; then
+    echo "FAILED: write did not land in the delta"
+    unmount
+    exit 1
+fi
+unmount
+
+# ...and not in the base
+mount_bg "$BASE" --read-only
+if [ -e "$MOUNTPOINT/new.txt" ]; then
+    echo "FAILED: write reached the base"
+    unmount
+    exit 1
+fi
+unmount
+
+echo "OK"
@@ -0,0 +1,66 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST mount --cleanup-stale... "
+
+TEST_AGENT_ID="test-mount-stale-agent"
+MOUNTPOINT="/tmp/agentfs-test-mount-stale-$$"
+
+cleanup() {
+    # Unmount if still mounted
+    fusermount -uz "$MOUNTPOINT" 2>/dev/null || true
+    rmdir "$MOUNTPOINT" 2>/dev/null || true
+    rm -f ".agentfs/${TEST_AGENT_ID}.db" ".agentfs/${TEST_AGENT_ID}.db-shm" ".agentfs/${TEST_AGENT_ID}.db-wal"
+}
+
+trap cleanup EXIT
+
+cleanup
+
+cargo run -- init "$TEST_AGENT_ID" > /dev/null 2>&1
+
+mkdir -p "$MOUNTPOINT"
+
+cargo run -- mount ".agentfs/${TEST_AGENT_ID}.db" "$MOUNTPOINT" --foreground > /dev/null 2>&1 &
+
+MAX_WAIT=20
+WAITED=0
+while [ $WAITED -lt $MAX_WAIT ]; do
+    if mountpoint -q "$MOUNTPOINT" 2>/dev/null; then
+        break
+    fi
+    sleep 0.5
+    WAITED=$((WAITED + 1))
+done
+
+if ! mountpoint -q "$MOUNTPOINT" 2>/dev/null; then
+    echo "FAILED: mount did not become ready in time"
+    exit 1
+fi
+
+# Kill the mount process without giving it a chance to unmount, leaving an
+# orphaned FUSE mount behind
+pkill -9 -f "mount .agentfs/${TEST_AGENT_ID}.db" || true
+sleep 0.5
+
+if ! grep -q " $MOUNTPOINT " /proc/self/mounts; then
+    echo "FAILED: orphaned mount is missing from /proc/self/mounts"
+    exit 1
+fi
+
+if ! cargo run -- mount 2>/dev/null | grep -q "$MOUNTPOINT"; then
+    echo "FAILED: 'agentfs mount' did not list the orphaned mount"
+    exit 1
+fi
+
+if ! cargo run -- mount --cleanup-stale > /dev/null 2>&1; then
+    echo "FAILED: 'agentfs mount --cleanup-stale' returned an error"
+    exit 1
+fi
+
+if grep -q " $MOUNTPOINT " /proc/self/mounts; then
+    echo "FAILED: orphaned mount was not cleaned up"
+    exit 1
+fi
+
+echo "OK"
@@ -0,0 +1,71 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST mount --create... "
+
+DB_DIR="$(mktemp -d)"
+DB="$DB_DIR/new.db"
+MOUNTPOINT="/tmp/agentfs-test-mount-create-$$"
+
+cleanup() {
+    fusermount -u "$MOUNTPOINT" 2>/dev/null || true
+    rmdir "$MOUNTPOINT" 2>/dev/null || true
+    rm -rf "$DB_DIR"
+}
+
+trap cleanup EXIT
+
+mkdir -p "$MOUNTPOINT"
+
+# Without --create, a missing database is an error and nothing is created
+if output=$(cargo run -- mount "$DB" "$MOUNTPOINT" --foreground 2>&1); then
+    echo "FAILED: mounted a missing database without --create"
+    exit 1
+fi
+if ! echo "$output" | grep -q "does not exist"; then
+    echo "FAILED: unclear error for missing database: $output"
+    exit 1
+fi
+if [ -e "$DB" ]; then
+    echo "FAILED: database was created without --create"
+    exit 1
+fi
+
+# With --create, an empty filesystem is created and mounted
+cargo run -- mount "$DB" "$MOUNTPOINT" --foreground --create &
+MOUNT_PID=$!
+
+MAX_WAIT=10
+WAITED=0
+while [ $WAITED -lt $MAX_WAIT ]; do
+    if mountpoint -q "$MOUNTPOINT" 2>/dev/null; then
+        break
+    fi
+    sleep 0.5
+    WAITED=$((WAITED + 1))
+done
+
+if ! mountpoint -q "$MOUNTPOINT" 2>/dev/null; then
+    echo "FAILED: mount did not become ready in time"
+    kill $MOUNT_PID 2>/dev/null || true
+    exit 1
+fi
+
+if [ -n "$(ls -A "$MOUNTPOINT")" ]; then
+    echo "FAILED: new filesystem is not empty"
+    kill $MOUNT_PID 2>/dev/null || true
+    exit 1
+fi
+
+echo "hello" > "$MOUNTPOINT/hello.txt"
+
+fusermount -u "$MOUNTPOINT"
+
+wait $MOUNT_PID 2>/dev/null || true
+
+if [ ! -f "$DB" ]; then
+    echo "FAILED: database was not created"
+    exit 1
+fi
+
+echo "OK"
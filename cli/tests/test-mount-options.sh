@@ -0,0 +1,73 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST mount options... "
+
+TEST_AGENT_ID="test-mount-options-agent"
+MOUNTPOINT="/tmp/agentfs-test-mount-options-$$"
+
+cleanup() {
+    fusermount -u "$MOUNTPOINT" 2>/dev/null || true
+    rmdir "$MOUNTPOINT" 2>/dev/null || true
+    rm -f ".agentfs/${TEST_AGENT_ID}.db" ".agentfs/${TEST_AGENT_ID}.db-shm" ".agentfs/${TEST_AGENT_ID}.db-wal"
+}
+
+trap cleanup EXIT
+
+cleanup
+
+cargo run -- init "$TEST_AGENT_ID" > /dev/null 2>&1
+
+mkdir -p "$MOUNTPOINT"
+
+# Options that agentfs manages itself must be rejected before mounting
+if cargo run -- mount ".agentfs/${TEST_AGENT_ID}.db" "$MOUNTPOINT" --foreground -o allow_other > /dev/null 2>&1; then
+    echo "FAILED: conflicting option allow_other was accepted"
+    exit 1
+fi
+
+cargo run -- mount ".agentfs/${TEST_AGENT_ID}.db" "$MOUNTPOINT" --foreground -o noexec &
+MOUNT_PID=$!
+
+MAX_WAIT=10
+WAITED=0
+while [ $WAITED -lt $MAX_WAIT ]; do
+    if mountpoint -q "$MOUNTPOINT" 2>/dev/null; then
+        break
+    fi
+    sleep 0.5
+    WAITED=$((WAITED + 1))
+done
+
+if ! mountpoint -q "$MOUNTPOINT" 2>/dev/null; then
+    echo "FAILED: mount did not become ready in time"
+    kill $MOUNT_PID 2>/dev/null || true
+    exit 1
+fi
+
+if ! grep " $MOUNTPOINT " /proc/self/mounts | grep -q "noexec"; then
+    echo "FAILED: noexec missing from mount options"
+    kill $MOUNT_PID 2>/dev/null || true
+    exit 1
+fi
+
+printf '#!/bin/sh\necho executed\n' > "$MOUNTPOINT/script.sh"
+chmod +x "$MOUNTPOINT/script.sh"
+
+# execve() on a noexec mount fails with EACCES ("Permission denied")
+if OUTPUT=$("$MOUNTPOINT/script.sh" 2>&1); then
+    echo "FAILED: executed a file on a noexec mount"
+    kill $MOUNT_PID 2>/dev/null || true
+    exit 1
+fi
+if ! echo "$OUTPUT" | grep -q "Permission denied"; then
+    echo "FAILED: expected EACCES, got: $OUTPUT"
+    kill $MOUNT_PID 2>/dev/null || true
+    exit 1
+fi
+
+fusermount -u "$MOUNTPOINT"
+
+wait $MOUNT_PID 2>/dev/null || true
+
+echo "OK"
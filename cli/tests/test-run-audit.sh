@@ -0,0 +1,37 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST run --audit-log... "
+
+AUDIT="$(mktemp)"
+EVENTS="$(mktemp)"
+trap 'rm -f "$AUDIT" "$EVENTS"' EXIT
+
+output=$(cargo run -- run --audit-log "$AUDIT" --events "$EVENTS" /bin/true 2>&1) || {
+    echo "FAILED"
+    echo "$output"
+    exit 1
+}
+
+# Verify the expected setup actions were recorded and succeeded
+for action in mount_fuse unshare write_uid_map write_setgroups write_gid_map make_mounts_private bind_mount remount; do
+    grep "\"action\":\"$action\"" "$AUDIT" | grep -q "\"result\":\"ok\"" || {
+        echo "FAILED: missing $action entry"
+        cat "$AUDIT"
+        exit 1
+    }
+done
+grep "\"action\":\"bind_mount\"" "$AUDIT" | grep -q "\"target\":\"$PWD\"" || {
+    echo "FAILED: bind mount of the overlay onto $PWD not recorded"
+    cat "$AUDIT"
+    exit 1
+}
+
+# The audit log is separate from the event stream
+if grep -q "\"action\"" "$EVENTS"; then
+    echo "FAILED: audit entries leaked into the event stream"
+    cat "$EVENTS"
+    exit 1
+fi
+
+echo "OK"
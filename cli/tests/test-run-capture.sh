@@ -0,0 +1,42 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST run --capture-stdout... "
+
+STDOUT="$(mktemp)"
+STDERR="$(mktemp)"
+trap 'rm -f "$STDOUT" "$STDERR"' EXIT
+
+# Write to both streams; the last line is printed right before exiting to
+# check that nothing is lost when the command exits
+output=$(cargo run -- run --capture-stdout "$STDOUT" --capture-stderr "$STDERR" /bin/bash -c '
+echo "first line"
+echo "to stderr" >&2
+printf "last line"
+' 2>&1) || {
+    echo "FAILED"
+    echo "$output"
+    exit 1
+}
+
+expected="$(printf 'first line\nlast line')"
+if [ "$(cat "$STDOUT")" != "$expected" ]; then
+    echo "FAILED: unexpected captured stdout"
+    cat "$STDOUT"
+    exit 1
+fi
+
+if [ "$(cat "$STDERR")" != "to stderr" ]; then
+    echo "FAILED: unexpected captured stderr"
+    cat "$STDERR"
+    exit 1
+fi
+
+# Captured output must not also reach the terminal
+if echo "$output" | grep -q "first line"; then
+    echo "FAILED: captured stdout was also passed through"
+    echo "$output"
+    exit 1
+fi
+
+echo "OK"
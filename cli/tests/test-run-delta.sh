@@ -0,0 +1,45 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST run --delta-out/--delta-in... "
+
+DELTA_DIR="$(mktemp -d)"
+trap 'rm -rf "$DELTA_DIR"' EXIT
+DELTA="$DELTA_DIR/delta.db"
+
+# First run writes a file into the sandbox and saves its delta
+output=$(cargo run -- run --delta-out "$DELTA" /bin/bash -c '
+echo "from the first run" > delta-test-file.txt
+' 2>&1) || {
+    echo "FAILED"
+    echo "$output"
+    exit 1
+}
+
+if [ -e delta-test-file.txt ]; then
+    echo "FAILED: sandboxed write reached the host"
+    exit 1
+fi
+
+if [ ! -f "$DELTA" ]; then
+    echo "FAILED: delta was not saved"
+    echo "$output"
+    exit 1
+fi
+
+# Second run starts from the saved delta and sees the file
+output=$(cargo run -- run --delta-in "$DELTA" /bin/bash -c '
+cat delta-test-file.txt
+' 2>&1) || {
+    echo "FAILED"
+    echo "$output"
+    exit 1
+}
+
+if ! echo "$output" | grep -q "from the first run"; then
+    echo "FAILED: file from the first run is missing"
+    echo "$output"
+    exit 1
+fi
+
+echo "OK"
@@ -0,0 +1,37 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST run --events... "
+
+EVENTS="$(mktemp)"
+trap 'rm -f "$EVENTS"' EXIT
+
+# Create and write a file in the overlay, recording events
+output=$(cargo run -- run --events "$EVENTS" /bin/bash -c '
+echo "hello from agent" > events-test.txt
+' 2>&1) || {
+    echo "FAILED"
+    echo "$output"
+    exit 1
+}
+
+# Verify the expected create/write entries for the file were recorded
+for op in spawn create write exit; do
+    grep -q "\"op\":\"$op\"" "$EVENTS" || {
+        echo "FAILED: missing $op event"
+        cat "$EVENTS"
+        exit 1
+    }
+done
+grep "\"op\":\"create\"" "$EVENTS" | grep -q "\"path\":\"$PWD/events-test.txt\",\"result\":\"ok\"" || {
+    echo "FAILED: create event does not name events-test.txt"
+    cat "$EVENTS"
+    exit 1
+}
+grep "\"op\":\"write\"" "$EVENTS" | grep -q "\"path\":\"$PWD/events-test.txt\"" || {
+    echo "FAILED: write event does not name events-test.txt"
+    cat "$EVENTS"
+    exit 1
+}
+
+echo "OK"
@@ -0,0 +1,49 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST run --pid-namespace... "
+
+# The inner shell backgrounds a sleep and exits, orphaning it. Once the sleep
+# has finished it must have been reaped, not left behind as a zombie.
+output=$(cargo run -- run --pid-namespace /bin/sh -c '
+    sh -c "sleep 0.1 &"
+    sleep 1
+    echo "pid=$$"
+    for stat in /proc/[0-9]*/stat; do
+        state=$(cut -d" " -f3 "$stat" 2>/dev/null) || continue
+        [ "$state" = "Z" ] && echo "zombie: $stat"
+    done
+    true
+' 2>&1) || {
+    echo "FAILED"
+    echo "$output"
+    exit 1
+}
+
+if echo "$output" | grep -q "^zombie:"; then
+    echo "FAILED: orphaned process was not reaped"
+    echo "$output"
+    exit 1
+fi
+
+# The command runs as the only child of the namespace's init process
+if ! echo "$output" | grep -q "^pid=2$"; then
+    echo "FAILED: command did not run in a new PID namespace"
+    echo "$output"
+    exit 1
+fi
+
+# The command's exit status is passed through the init process
+if cargo run -- run --pid-namespace /bin/sh -c 'exit 3' >/dev/null 2>&1; then
+    echo "FAILED: exit status was lost"
+    exit 1
+else
+    status=$?
+fi
+
+if [ "$status" -ne 3 ]; then
+    echo "FAILED: expected exit status 3, got $status"
+    exit 1
+fi
+
+echo "OK"
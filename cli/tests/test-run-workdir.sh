@@ -0,0 +1,37 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST run --workdir... "
+
+# The directory is created within the sandbox and the command starts there
+output=$(cargo run -- run --workdir /sub --create-workdir /bin/pwd 2>&1) || {
+    echo "FAILED"
+    echo "$output"
+    exit 1
+}
+
+if [ "$(echo "$output" | tail -n 1)" != "$(pwd)/sub" ]; then
+    echo "FAILED: command did not start in the working directory"
+    echo "$output"
+    exit 1
+fi
+
+if [ -e sub ]; then
+    echo "FAILED: sandboxed mkdir reached the host"
+    exit 1
+fi
+
+# Without --create-workdir, a missing directory is an error
+if output=$(cargo run -- run --workdir /missing-workdir /bin/pwd 2>&1); then
+    echo "FAILED: missing working directory was accepted"
+    echo "$output"
+    exit 1
+fi
+
+if ! echo "$output" | grep -q "Failed to change to working directory /missing-workdir"; then
+    echo "FAILED: unexpected error for missing working directory"
+    echo "$output"
+    exit 1
+fi
+
+echo "OK"
@@ -0,0 +1,82 @@
+//! Sequential read throughput of SQLite VFS files.
+//!
+//! Compares streaming a file with large reads, whose size drives readahead,
+//! against streaming it with fixed page-sized reads.
+//!
+//! Run with: cargo bench --bench readahead
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use agentfs_sandbox::vfs::file::FileOps;
+    use agentfs_sandbox::{SqliteVfs, Vfs};
+    use criterion::{BenchmarkId, Criterion, Throughput};
+    use std::path::{Path, PathBuf};
+    use tempfile::tempdir;
+
+    /// Size of the file streamed in each iteration
+    const FILE_SIZE: usize = 16 * 1024 * 1024;
+
+    pub fn bench_sequential_read(c: &mut Criterion) {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let dir = tempdir().expect("Failed to create temp dir");
+        let path = Path::new("/agent/stream.bin");
+
+        let vfs = rt.block_on(async {
+            let vfs = SqliteVfs::new(dir.path().join("bench.db"), PathBuf::from("/agent"))
+                .await
+                .expect("Failed to create SqliteVfs");
+            let file = vfs
+                .open(path, libc::O_CREAT | libc::O_WRONLY, 0o644)
+                .await
+                .expect("Failed to create file");
+            file.write(&vec![0x5a; FILE_SIZE])
+                .await
+                .expect("Failed to write file");
+            file.close().await.expect("Failed to close file");
+            vfs
+        });
+
+        let mut group = c.benchmark_group("sequential_read");
+        group.throughput(Throughput::Bytes(FILE_SIZE as u64));
+        group.sample_size(10);
+
+        // 4 KiB matches a naive reader; 128 KiB is the kernel's default
+        // maximum FUSE read and a typical streaming buffer size
+        for read_size in [4 * 1024, 128 * 1024] {
+            group.bench_with_input(
+                BenchmarkId::from_parameter(read_size),
+                &read_size,
+                |b, &read_size| {
+                    let vfs = &vfs;
+                    b.to_async(&rt).iter(|| async move {
+                        // A fresh handle starts with a cold page cache
+                        let file = vfs
+                            .open(path, libc::O_RDONLY, 0)
+                            .await
+                            .expect("Failed to open file");
+                        let mut buf = vec![0u8; read_size];
+                        let mut total = 0;
+                        loop {
+                            let n = file.read(&mut buf).await.expect("Failed to read");
+                            if n == 0 {
+                                break;
+                            }
+                            total += n;
+                        }
+                        assert_eq!(total, FILE_SIZE);
+                        file.close().await.expect("Failed to close file");
+                    });
+                },
+            );
+        }
+        group.finish();
+    }
+}
+
+#[cfg(target_os = "linux")]
+criterion::criterion_group!(benches, linux::bench_sequential_read);
+#[cfg(target_os = "linux")]
+criterion::criterion_main!(benches);
+
+#[cfg(not(target_os = "linux"))]
+fn main() {}
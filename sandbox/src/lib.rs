@@ -13,4 +13,4 @@ pub use vfs::{
 };
 
 #[cfg(target_os = "linux")]
-pub use vfs::sqlite::SqliteVfs;
+pub use vfs::sqlite::{Durability, OpenHandleInfo, PinnedDir, SqliteVfs};
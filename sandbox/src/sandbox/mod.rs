@@ -2,7 +2,7 @@ use crate::{
     syscall,
     vfs::{fdtable::FdTable, mount::MountTable},
 };
-use reverie::{syscalls::Syscall, Error, Guest, Tool};
+use reverie::{syscalls::Syscall, Errno, Error, Guest, Tool};
 use std::collections::HashMap;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
@@ -127,4 +127,10 @@ impl Tool for Sandbox {
 
         result
     }
+
+    async fn handle_post_exec<T: Guest<Self>>(&self, guest: &mut T) -> Result<(), Errno> {
+        let fd_table = get_fd_table(guest.pid().as_raw());
+        syscall::process::handle_post_exec(&fd_table).await;
+        Ok(())
+    }
 }
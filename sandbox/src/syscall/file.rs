@@ -1,13 +1,13 @@
 use crate::{
     sandbox::Sandbox,
-    syscall::translate_path,
+    syscall::{read_at_path, translate_at_path, translate_path, translate_resolved_at_path},
     vfs::{
         fdtable::{FdEntry, FdTable},
         mount::MountTable,
     },
 };
 use reverie::{
-    syscalls::{MemoryAccess, ReadAddr, Syscall},
+    syscalls::{MemoryAccess, ReadAddr, Syscall, SyscallInfo},
     Error, Guest, Stack,
 };
 use std::mem::MaybeUninit;
@@ -26,37 +26,9 @@ pub async fn handle_openat<T: Guest<Sandbox>>(
     fd_table: &FdTable,
 ) -> Result<Option<i64>, Error> {
     if let Some(path_addr) = args.path() {
-        // Read the original path from guest memory
-        let mut path: std::path::PathBuf = path_addr.read(&guest.memory())?;
-
-        // Handle dirfd resolution for relative paths
-        let dirfd = args.dirfd();
-        let kernel_dirfd = if dirfd == libc::AT_FDCWD {
-            dirfd
-        } else if path.is_relative() {
-            // For relative paths, resolve against dirfd
-            if let Some(dir_entry) = fd_table.get(dirfd) {
-                // Check if this is a passthrough directory with a kernel FD first
-                if let Some(kfd) = dir_entry.kernel_fd() {
-                    // Passthrough directory - use the kernel FD and keep path as-is
-                    kfd
-                } else if let Some(dir_path) = dir_entry.path() {
-                    // Virtual directory - resolve relative path against the directory's path
-                    path = dir_path.join(&path);
-                    // For virtual directories, we'll use AT_FDCWD since we have the full path now
-                    libc::AT_FDCWD
-                } else {
-                    // Virtual file without a path - this shouldn't happen for directories
-                    return Ok(Some(-libc::EBADF as i64));
-                }
-            } else {
-                // dirfd not in table - will likely fail
-                dirfd
-            }
-        } else {
-            // Absolute path - dirfd is ignored, use AT_FDCWD
-            libc::AT_FDCWD
-        };
+        // Resolve the dirfd, composing relative paths against virtual directories
+        let at = read_at_path(guest, args.dirfd(), path_addr, fd_table)?;
+        let path = at.path.clone();
 
         // Check if this path matches a mount point
         if let Some((vfs, _translated_path)) = mount_table.resolve(&path) {
@@ -77,21 +49,20 @@ pub async fn handle_openat<T: Guest<Sandbox>>(
                     }
                     Err(e) => {
                         // Map VFS errors to errno
-                        let errno = match e {
-                            crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
-                            crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
-                            _ => -libc::EIO as i64,
-                        };
+                        let errno = -(e.to_errno() as i64);
                         return Ok(Some(errno));
                     }
                 }
             } else {
                 // For passthrough VFS, translate the path and call the kernel
-                let new_path_addr = translate_path(guest, path_addr, mount_table).await?;
+                let (kernel_dirfd, new_path_addr) =
+                    translate_resolved_at_path(guest, args.dirfd(), path_addr, &at, mount_table)
+                        .await?
+                        .unwrap_or((at.dirfd, path_addr));
 
                 let new_syscall = reverie::syscalls::Openat::new()
                     .with_dirfd(kernel_dirfd)
-                    .with_path(new_path_addr.or(Some(path_addr)))
+                    .with_path(Some(new_path_addr))
                     .with_flags(args.flags())
                     .with_mode(args.mode());
 
@@ -111,10 +82,14 @@ pub async fn handle_openat<T: Guest<Sandbox>>(
                 }
             }
         } else {
-            // No mount point matches - pass through to kernel with original path
+            // No mount point matches - pass through to kernel with the resolved path
+            let (kernel_dirfd, new_path_addr) =
+                translate_resolved_at_path(guest, args.dirfd(), path_addr, &at, mount_table)
+                    .await?
+                    .unwrap_or((at.dirfd, path_addr));
             let new_syscall = reverie::syscalls::Openat::new()
                 .with_dirfd(kernel_dirfd)
-                .with_path(Some(path_addr))
+                .with_path(Some(new_path_addr))
                 .with_flags(args.flags())
                 .with_mode(args.mode());
 
@@ -180,11 +155,7 @@ pub async fn handle_read<T: Guest<Sandbox>>(
                     }
                     Err(e) => {
                         // Map VFS errors to errno
-                        let errno = match e {
-                            crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
-                            crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
-                            _ => -libc::EIO as i64,
-                        };
+                        let errno = -(e.to_errno() as i64);
                         return Ok(crate::syscall::SyscallResult::Value(errno));
                     }
                 }
@@ -238,11 +209,7 @@ pub async fn handle_write<T: Guest<Sandbox>>(
                     }
                     Err(e) => {
                         // Map VFS errors to errno
-                        let errno = match e {
-                            crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
-                            crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
-                            _ => -libc::EIO as i64,
-                        };
+                        let errno = -(e.to_errno() as i64);
                         return Ok(crate::syscall::SyscallResult::Value(errno));
                     }
                 }
@@ -278,9 +245,16 @@ pub async fn handle_close<T: Guest<Sandbox>>(
                 )));
             }
             FdEntry::Virtual { file_ops, .. } => {
-                // Virtualized file - just call close on the FileOps
-                file_ops.close().await.ok();
-                return Ok(crate::syscall::SyscallResult::Value(0)); // Success
+                // Virtualized file - just call close on the FileOps. The FD is
+                // released either way, but errors writing back buffered data
+                // are reported like the kernel does for delayed writeback.
+                let result = match file_ops.close().await {
+                    Ok(()) => 0,
+                    Err(crate::vfs::VfsError::NoSpace) => -libc::ENOSPC as i64,
+                    Err(crate::vfs::VfsError::NotPermitted) => -libc::EPERM as i64,
+                    Err(_) => -libc::EIO as i64,
+                };
+                return Ok(crate::syscall::SyscallResult::Value(result));
             }
         }
     }
@@ -315,11 +289,12 @@ pub async fn handle_dup<T: Guest<Sandbox>>(
                     return Ok(Some(result));
                 }
 
-                // Create a new passthrough FD entry with the new kernel FD
+                // Create a new passthrough FD entry with the new kernel FD,
+                // which like any duplicate is not close-on-exec
                 let new_kernel_fd = result as i32;
                 let entry = FdEntry::Passthrough {
                     kernel_fd: new_kernel_fd,
-                    flags,
+                    flags: flags & !libc::O_CLOEXEC,
                     path,
                 };
 
@@ -527,10 +502,18 @@ pub async fn handle_dup3<T: Guest<Sandbox>>(
     }
 }
 
+/// `FS_IOC_GETFLAGS`: read inode flags into an `int`
+const FS_IOC_GETFLAGS: usize = 0x80086601;
+
+/// `FS_IOC_SETFLAGS`: set inode flags from an `int`
+const FS_IOC_SETFLAGS: usize = 0x40086602;
+
 /// The `ioctl` system call.
 ///
 /// This intercepts `ioctl` system calls and translates virtual FDs to kernel FDs.
 /// This is crucial for terminal control operations like job control in shells.
+/// Virtual files handle the inode flag requests through FileOps; any other
+/// request goes to FileOps::ioctl().
 pub async fn handle_ioctl<T: Guest<Sandbox>>(
     guest: &mut T,
     args: &reverie::syscalls::Ioctl,
@@ -538,6 +521,38 @@ pub async fn handle_ioctl<T: Guest<Sandbox>>(
 ) -> Result<Option<i64>, Error> {
     let virtual_fd = args.fd();
 
+    if let Some(FdEntry::Virtual { file_ops, .. }) = fd_table.get(virtual_fd) {
+        let (_, raw) = args.into_parts();
+        let result = match raw.arg1 {
+            FS_IOC_GETFLAGS => match file_ops.get_inode_flags().await {
+                Ok(flags) => match reverie::syscalls::AddrMut::<i32>::from_raw(raw.arg2) {
+                    Some(addr) => {
+                        guest.memory().write_value(addr, &(flags as i32))?;
+                        0
+                    }
+                    None => -libc::EFAULT as i64,
+                },
+                Err(e) => inode_flags_errno(e),
+            },
+            FS_IOC_SETFLAGS => match reverie::syscalls::Addr::<i32>::from_raw(raw.arg2) {
+                Some(addr) => {
+                    let flags: i32 = guest.memory().read_value(addr)?;
+                    match file_ops.set_inode_flags(flags as u32).await {
+                        Ok(()) => 0,
+                        Err(e) => inode_flags_errno(e),
+                    }
+                }
+                None => -libc::EFAULT as i64,
+            },
+            request => match file_ops.ioctl(request as u64, raw.arg2 as u64) {
+                Ok(result) => result,
+                Err(crate::vfs::VfsError::BadFileDescriptor) => -libc::EBADF as i64,
+                Err(_) => -libc::ENOTTY as i64,
+            },
+        };
+        return Ok(Some(result));
+    }
+
     // Translate virtual FD to kernel FD
     if let Some(kernel_fd) = fd_table.translate(virtual_fd) {
         // If FDs are identical (common for stdin/stdout/stderr), pass through
@@ -558,6 +573,16 @@ pub async fn handle_ioctl<T: Guest<Sandbox>>(
     Ok(None)
 }
 
+/// Map an inode flags error to the errno the ioctl reports
+///
+/// Flags the file cannot hold are reported as unsupported, like ext4 does.
+fn inode_flags_errno(e: crate::vfs::VfsError) -> i64 {
+    match e {
+        crate::vfs::VfsError::InvalidInput(_) => -libc::EOPNOTSUPP as i64,
+        e => -(e.to_errno() as i64),
+    }
+}
+
 /// The `fcntl` system call.
 ///
 /// This intercepts `fcntl` system calls and handles virtual FD operations.
@@ -627,11 +652,62 @@ pub async fn handle_fcntl<T: Guest<Sandbox>>(
                     .with_cmd(args.cmd());
 
                 let result = guest.inject(Syscall::Fcntl(new_syscall)).await?;
+                // Record close-on-exec so the FD is dropped from the table on execve
+                if let FcntlCmd::F_SETFD(arg) = args.cmd() {
+                    if result >= 0 {
+                        fd_table.set_cloexec(virtual_fd, arg & libc::FD_CLOEXEC != 0);
+                    }
+                }
                 return Ok(Some(result));
             }
         }
     }
 
+    // Virtual FDs have no kernel FD, so the commands are emulated via FileOps,
+    // except for the descriptor flags, which duplicates do not share
+    if let Some(FdEntry::Virtual {
+        file_ops,
+        flags,
+        path,
+    }) = fd_table.get(virtual_fd)
+    {
+        let (cmd, arg) = match args.cmd() {
+            FcntlCmd::F_DUPFD(arg) | FcntlCmd::F_DUPFD_CLOEXEC(arg) => {
+                let is_cloexec = matches!(args.cmd(), FcntlCmd::F_DUPFD_CLOEXEC(_));
+                let flags = if is_cloexec { libc::O_CLOEXEC } else { 0 };
+
+                // The duplicate shares the same open file description
+                let entry = FdEntry::Virtual {
+                    file_ops,
+                    flags,
+                    path,
+                };
+                let new_vfd = fd_table.allocate_min(arg, entry);
+                return Ok(Some(new_vfd as i64));
+            }
+            FcntlCmd::F_GETFD => {
+                let fd_flags = if flags & libc::O_CLOEXEC != 0 {
+                    libc::FD_CLOEXEC
+                } else {
+                    0
+                };
+                return Ok(Some(fd_flags as i64));
+            }
+            FcntlCmd::F_SETFD(arg) => {
+                fd_table.set_cloexec(virtual_fd, arg & libc::FD_CLOEXEC != 0);
+                return Ok(Some(0));
+            }
+            FcntlCmd::F_GETFL => (libc::F_GETFL, 0),
+            FcntlCmd::F_SETFL(arg) => (libc::F_SETFL, arg as i64),
+            _ => return Ok(Some(-libc::EINVAL as i64)),
+        };
+
+        return match file_ops.fcntl(cmd, arg) {
+            Ok(result) => Ok(Some(result)),
+            Err(_) => Ok(Some(-libc::EINVAL as i64)),
+        };
+    }
+
     // FD not in table, let the original syscall through (will likely fail with EBADF)
     Ok(None)
 }
@@ -1019,10 +1095,15 @@ pub async fn handle_getdents64<T: Guest<Sandbox>>(
 
                         return Ok(crate::syscall::SyscallResult::Value(buf.len() as i64));
                     }
-                    Err(_) => {
-                        // Not a directory or error
+                    Err(crate::vfs::VfsError::BadFileDescriptor) => {
+                        return Ok(crate::syscall::SyscallResult::Value(-libc::EBADF as i64));
+                    }
+                    Err(crate::vfs::VfsError::NotADirectory) => {
                         return Ok(crate::syscall::SyscallResult::Value(-libc::ENOTDIR as i64));
                     }
+                    Err(_) => {
+                        return Ok(crate::syscall::SyscallResult::Value(-libc::EIO as i64));
+                    }
                 }
             }
         }
@@ -1076,11 +1157,7 @@ pub async fn handle_fstat<T: Guest<Sandbox>>(
                     }
                     Err(e) => {
                         // Map VFS errors to errno
-                        let errno = match e {
-                            crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
-                            crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
-                            _ => -libc::EIO as i64,
-                        };
+                        let errno = -(e.to_errno() as i64);
                         return Ok(crate::syscall::SyscallResult::Value(errno));
                     }
                 }
@@ -1092,6 +1169,62 @@ pub async fn handle_fstat<T: Guest<Sandbox>>(
     Ok(crate::syscall::SyscallResult::Syscall(syscall))
 }
 
+/// The `fadvise64` system call.
+///
+/// Passthrough FDs are forwarded to the kernel, while virtual files apply the
+/// hint to their own page cache via FileOps::fadvise().
+pub async fn handle_fadvise64(
+    syscall: Syscall,
+    args: &reverie::syscalls::Fadvise64,
+    fd_table: &FdTable,
+) -> Result<crate::syscall::SyscallResult, Error> {
+    match fd_table.get(args.fd()) {
+        Some(FdEntry::Passthrough { kernel_fd, .. }) => Ok(crate::syscall::SyscallResult::Syscall(
+            Syscall::Fadvise64(args.with_fd(kernel_fd)),
+        )),
+        Some(FdEntry::Virtual { file_ops, .. }) => {
+            let result = match file_ops
+                .fadvise(args.offset() as i64, args.len() as i64, args.advice())
+                .await
+            {
+                Ok(()) => 0,
+                Err(e) => -(e.to_errno() as i64),
+            };
+            Ok(crate::syscall::SyscallResult::Value(result))
+        }
+        // FD not in table, let the original syscall through (will likely fail with EBADF)
+        None => Ok(crate::syscall::SyscallResult::Syscall(syscall)),
+    }
+}
+
+/// The `ftruncate` system call.
+///
+/// Passthrough FDs are forwarded to the kernel, while virtual files are
+/// resized via FileOps::ftruncate().
+pub async fn handle_ftruncate(
+    syscall: Syscall,
+    args: &reverie::syscalls::Ftruncate,
+    fd_table: &FdTable,
+) -> Result<crate::syscall::SyscallResult, Error> {
+    match fd_table.get(args.fd()) {
+        Some(FdEntry::Passthrough { kernel_fd, .. }) => Ok(crate::syscall::SyscallResult::Syscall(
+            Syscall::Ftruncate(args.with_fd(kernel_fd)),
+        )),
+        Some(FdEntry::Virtual { file_ops, .. }) => {
+            if args.length() < 0 {
+                return Ok(crate::syscall::SyscallResult::Value(-libc::EINVAL as i64));
+            }
+            let result = match file_ops.ftruncate(args.length() as u64).await {
+                Ok(()) => 0,
+                Err(e) => -(e.to_errno() as i64),
+            };
+            Ok(crate::syscall::SyscallResult::Value(result))
+        }
+        // FD not in table, let the original syscall through (will likely fail with EBADF)
+        None => Ok(crate::syscall::SyscallResult::Syscall(syscall)),
+    }
+}
+
 /// The `fstatat` system call.
 ///
 /// This intercepts `fstatat` system calls and translates virtual FDs to kernel FDs,
@@ -1105,46 +1238,16 @@ pub async fn handle_fstatat<T: Guest<Sandbox>>(
     mount_table: &MountTable,
 ) -> Result<crate::syscall::SyscallResult, Error> {
     if let Some(path_addr) = args.path() {
-        // Read the original path from guest memory
-        let mut path: std::path::PathBuf = path_addr.read(&guest.memory())?;
-
-        // Handle dirfd resolution for relative paths
-        let dirfd = args.dirfd();
-        let kernel_dirfd = if dirfd == libc::AT_FDCWD {
-            dirfd
-        } else if path.is_relative() {
-            // For relative paths, resolve against dirfd
-            if let Some(dir_entry) = fd_table.get(dirfd) {
-                // Check if this is a passthrough directory with a kernel FD first
-                if let Some(kfd) = dir_entry.kernel_fd() {
-                    // Passthrough directory - use the kernel FD and keep path as-is
-                    kfd
-                } else if let Some(dir_path) = dir_entry.path() {
-                    // Virtual directory - resolve relative path against the directory's path
-                    path = dir_path.join(&path);
-                    // For virtual directories, we'll use AT_FDCWD since we have the full path now
-                    libc::AT_FDCWD
-                } else {
-                    // Virtual file without a path - this shouldn't happen for directories
-
-                    use reverie::Errno;
-                    return Err(Error::Errno(Errno::EBADFD));
-                }
-            } else {
-                // dirfd not in table - will likely fail
-                dirfd
-            }
-        } else {
-            // Absolute path - dirfd is ignored, use AT_FDCWD
-            libc::AT_FDCWD
-        };
+        // Resolve the dirfd, composing relative paths against virtual directories
+        let at = read_at_path(guest, args.dirfd(), path_addr, fd_table)?;
+        let path = &at.path;
 
         // Check if this path matches a mount point
-        if let Some((vfs, _translated_path)) = mount_table.resolve(&path) {
+        if let Some((vfs, _translated_path)) = mount_table.resolve(path) {
             // Check if this is a virtual VFS (like SQLite)
             if vfs.is_virtual() {
                 // For virtual VFS, open the file directly without going to the kernel
-                match vfs.stat(&path).await {
+                match vfs.stat(path).await {
                     Ok(stat_buf) => {
                         // Write the stat result to guest memory
                         if let Some(stat_addr) = args.stat() {
@@ -1163,18 +1266,18 @@ pub async fn handle_fstatat<T: Guest<Sandbox>>(
                     }
                     Err(e) => {
                         // Map VFS errors to errno
-                        let errno = match e {
-                            crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
-                            crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
-                            _ => -libc::EIO as i64,
-                        };
+                        let errno = -(e.to_errno() as i64);
                         return Ok(crate::syscall::SyscallResult::Value(errno));
                     }
                 }
             }
         }
-        // No mount point matches - pass through to kernel with original path
-        let new_syscall = args.with_dirfd(kernel_dirfd).with_path(Some(path_addr));
+        // Not on a virtual VFS - pass through to kernel with the translated path
+        let (kernel_dirfd, new_path_addr) =
+            translate_resolved_at_path(guest, args.dirfd(), path_addr, &at, mount_table)
+                .await?
+                .unwrap_or((at.dirfd, path_addr));
+        let new_syscall = args.with_dirfd(kernel_dirfd).with_path(Some(new_path_addr));
 
         return Ok(crate::syscall::SyscallResult::Syscall(Syscall::Fstatat(
             new_syscall,
@@ -1278,11 +1381,7 @@ pub async fn handle_lseek<T: Guest<Sandbox>>(
                     }
                     Err(e) => {
                         // Map VFS errors to errno
-                        let errno = match e {
-                            crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
-                            crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
-                            _ => -libc::EIO as i64,
-                        };
+                        let errno = -(e.to_errno() as i64);
                         return Ok(crate::syscall::SyscallResult::Value(errno));
                     }
                 }
@@ -1298,6 +1397,11 @@ pub async fn handle_lseek<T: Guest<Sandbox>>(
 ///
 /// This intercepts `mmap` system calls and translates virtual FDs to kernel FDs
 /// when mapping files. Anonymous mappings (fd == -1) pass through unchanged.
+///
+/// Files of a virtual VFS have no kernel FD, and so no pages the kernel could
+/// map, fault in, or drop when the file is truncated under the mapping. Rather
+/// than mapping whatever kernel file shares the FD number, mapping them fails
+/// with ENODEV, as for any filesystem that does not support memory mapping.
 pub async fn handle_mmap<T: Guest<Sandbox>>(
     guest: &mut T,
     args: &reverie::syscalls::Mmap,
@@ -1324,6 +1428,10 @@ pub async fn handle_mmap<T: Guest<Sandbox>>(
         return Ok(Some(result));
     }
 
+    if fd_table.get(virtual_fd).is_some() {
+        return Ok(Some(-libc::ENODEV as i64));
+    }
+
     // FD not in table, let the original syscall through (will likely fail with EBADF)
     Ok(None)
 }
@@ -1518,7 +1626,8 @@ pub async fn handle_unlink<T: Guest<Sandbox>>(
 
 /// The `readv` system call.
 ///
-/// This intercepts `readv` system calls and translates virtual FDs to kernel FDs.
+/// This intercepts `readv` system calls and translates virtual FDs to kernel FDs,
+/// or calls FileOps::readv() for virtual files.
 pub async fn handle_readv<T: Guest<Sandbox>>(
     guest: &mut T,
     args: &reverie::syscalls::Readv,
@@ -1526,23 +1635,58 @@ pub async fn handle_readv<T: Guest<Sandbox>>(
 ) -> Result<Option<i64>, Error> {
     let virtual_fd = args.fd();
 
-    // Translate virtual FD to kernel FD
-    if let Some(kernel_fd) = fd_table.translate(virtual_fd) {
-        let new_syscall = reverie::syscalls::Readv::new()
-            .with_fd(kernel_fd)
-            .with_iov(args.iov());
+    match fd_table.get(virtual_fd) {
+        Some(FdEntry::Passthrough { kernel_fd, .. }) => {
+            // Passthrough file - rewrite FD only, keeping the iovec array
+            let new_syscall = args.with_fd(kernel_fd);
 
-        let result = guest.inject(Syscall::Readv(new_syscall)).await?;
-        return Ok(Some(result));
-    }
+            let result = guest.inject(Syscall::Readv(new_syscall)).await?;
+            Ok(Some(result))
+        }
+        Some(FdEntry::Virtual { file_ops, .. }) => {
+            // Virtual file - read all segments with one FileOps call
+            let iovecs = match read_iovecs(guest, args.iov(), args.len())? {
+                Ok(iovecs) => iovecs,
+                Err(errno) => return Ok(Some(errno)),
+            };
 
-    // FD not in table, let the original syscall through (will likely fail with EBADF)
-    Ok(None)
+            let mut bufs: Vec<Vec<u8>> = iovecs.iter().map(|&(_, len)| vec![0u8; len]).collect();
+            let mut slices: Vec<&mut [u8]> =
+                bufs.iter_mut().map(|buf| buf.as_mut_slice()).collect();
+
+            match file_ops.readv(&mut slices).await {
+                Ok(n) => {
+                    // Copy the data back to guest memory, segment by segment
+                    let mut remaining = n;
+                    for (&(base, _), buf) in iovecs.iter().zip(&bufs) {
+                        if remaining == 0 {
+                            break;
+                        }
+                        let len = buf.len().min(remaining);
+                        let Some(addr) = reverie::syscalls::AddrMut::<u8>::from_raw(base) else {
+                            return Ok(Some(-libc::EFAULT as i64));
+                        };
+                        guest.memory().write_exact(addr, &buf[..len])?;
+                        remaining -= len;
+                    }
+                    Ok(Some(n as i64))
+                }
+                Err(e) => {
+                    // Map VFS errors to errno
+                    let errno = -(e.to_errno() as i64);
+                    Ok(Some(errno))
+                }
+            }
+        }
+        // FD not in table, let the original syscall through (will likely fail with EBADF)
+        None => Ok(None),
+    }
 }
 
 /// The `writev` system call.
 ///
-/// This intercepts `writev` system calls and translates virtual FDs to kernel FDs.
+/// This intercepts `writev` system calls and translates virtual FDs to kernel FDs,
+/// or calls FileOps::writev() for virtual files.
 pub async fn handle_writev<T: Guest<Sandbox>>(
     guest: &mut T,
     args: &reverie::syscalls::Writev,
@@ -1550,18 +1694,84 @@ pub async fn handle_writev<T: Guest<Sandbox>>(
 ) -> Result<Option<i64>, Error> {
     let virtual_fd = args.fd();
 
-    // Translate virtual FD to kernel FD
-    if let Some(kernel_fd) = fd_table.translate(virtual_fd) {
-        let new_syscall = reverie::syscalls::Writev::new()
-            .with_fd(kernel_fd)
-            .with_iov(args.iov());
+    match fd_table.get(virtual_fd) {
+        Some(FdEntry::Passthrough { kernel_fd, .. }) => {
+            // Passthrough file - rewrite FD only, keeping the iovec array
+            let new_syscall = args.with_fd(kernel_fd);
 
-        let result = guest.inject(Syscall::Writev(new_syscall)).await?;
-        return Ok(Some(result));
+            let result = guest.inject(Syscall::Writev(new_syscall)).await?;
+            Ok(Some(result))
+        }
+        Some(FdEntry::Virtual { file_ops, .. }) => {
+            // Virtual file - gather all segments and write them with one FileOps call
+            let iovecs = match read_iovecs(guest, args.iov(), args.len())? {
+                Ok(iovecs) => iovecs,
+                Err(errno) => return Ok(Some(errno)),
+            };
+
+            let mut bufs = Vec::with_capacity(iovecs.len());
+            for &(base, len) in &iovecs {
+                let mut buf = vec![0u8; len];
+                if len > 0 {
+                    let Some(addr) = reverie::syscalls::Addr::<u8>::from_raw(base) else {
+                        return Ok(Some(-libc::EFAULT as i64));
+                    };
+                    guest.memory().read_exact(addr, &mut buf)?;
+                }
+                bufs.push(buf);
+            }
+            let slices: Vec<&[u8]> = bufs.iter().map(|buf| buf.as_slice()).collect();
+
+            match file_ops.writev(&slices).await {
+                Ok(n) => Ok(Some(n as i64)),
+                Err(e) => {
+                    // Map VFS errors to errno
+                    let errno = -(e.to_errno() as i64);
+                    Ok(Some(errno))
+                }
+            }
+        }
+        // FD not in table, let the original syscall through (will likely fail with EBADF)
+        None => Ok(None),
     }
+}
 
-    // FD not in table, let the original syscall through (will likely fail with EBADF)
-    Ok(None)
+/// Read an iovec array from guest memory as (base, len) pairs.
+///
+/// Returns an errno as the inner error if the array is too long, missing, or
+/// its total length overflows, as readv(2) and writev(2) would.
+fn read_iovecs<T: Guest<Sandbox>>(
+    guest: &mut T,
+    iov: Option<reverie::syscalls::Addr<libc::iovec>>,
+    count: usize,
+) -> Result<Result<Vec<(usize, usize)>, i64>, Error> {
+    const IOV_MAX: usize = 1024;
+    if count > IOV_MAX {
+        return Ok(Err(-libc::EINVAL as i64));
+    }
+    if count == 0 {
+        return Ok(Ok(Vec::new()));
+    }
+    let Some(iov) = iov else {
+        return Ok(Err(-libc::EFAULT as i64));
+    };
+
+    let mut raw = vec![0u8; count * std::mem::size_of::<libc::iovec>()];
+    guest.memory().read_exact(iov.cast::<u8>(), &mut raw)?;
+
+    let word = std::mem::size_of::<usize>();
+    let mut iovecs = Vec::with_capacity(count);
+    let mut total: usize = 0;
+    for chunk in raw.chunks_exact(2 * word) {
+        let base = usize::from_ne_bytes(chunk[..word].try_into().unwrap());
+        let len = usize::from_ne_bytes(chunk[word..].try_into().unwrap());
+        total = match total.checked_add(len) {
+            Some(total) if total <= isize::MAX as usize => total,
+            _ => return Ok(Err(-libc::EINVAL as i64)),
+        };
+        iovecs.push((base, len));
+    }
+    Ok(Ok(iovecs))
 }
 
 /// The `pipe2` system call.
@@ -1757,50 +1967,148 @@ pub async fn handle_rmdir<T: Guest<Sandbox>>(
 
 /// The `unlinkat` system call (used for `unlink` and `rmdir` on ARM).
 ///
-/// This intercepts `unlinkat` system calls and translates paths according to the mount table.
+/// This intercepts `unlinkat` system calls, translates paths according to the mount table,
+/// and virtualizes the dirfd parameter.
 /// Signature: int unlinkat(int dirfd, const char *pathname, int flags);
 /// Note: On ARM (aarch64), both unlink and rmdir are implemented via unlinkat:
 ///   - unlink: unlinkat(AT_FDCWD, pathname, 0)
 ///   - rmdir: unlinkat(AT_FDCWD, pathname, AT_REMOVEDIR)
-#[cfg(target_arch = "aarch64")]
 pub async fn handle_unlinkat<T: Guest<Sandbox>>(
     guest: &mut T,
     args: &reverie::syscalls::Unlinkat,
     mount_table: &MountTable,
+    fd_table: &FdTable,
 ) -> Result<Option<Syscall>, Error> {
-    use libc::AT_FDCWD;
-
-    let dirfd = args.dirfd();
-    let flags = args.flags();
-
-    // Only handle AT_FDCWD for now (same as handle_chmod)
-    if dirfd != AT_FDCWD {
-        return Ok(None);
+    if let Some(path_addr) = args.path() {
+        if let Some((kernel_dirfd, new_path_addr)) =
+            translate_at_path(guest, args.dirfd(), path_addr, mount_table, fd_table).await?
+        {
+            let new_syscall = args.with_dirfd(kernel_dirfd).with_path(Some(new_path_addr));
+            return Ok(Some(Syscall::Unlinkat(new_syscall)));
+        }
     }
+    Ok(None)
+}
 
+/// The `mkdirat` system call (used for `mkdir` on ARM).
+///
+/// This intercepts `mkdirat` system calls, translates paths according to the mount table,
+/// and virtualizes the dirfd parameter.
+/// Signature: int mkdirat(int dirfd, const char *pathname, mode_t mode);
+pub async fn handle_mkdirat<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Mkdirat,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<Option<Syscall>, Error> {
     if let Some(path_addr) = args.path() {
-        if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
-            let new_syscall = args
-                .with_dirfd(AT_FDCWD)
-                .with_path(Some(new_path_addr))
-                .with_flags(flags);
-            return Ok(Some(Syscall::Unlinkat(new_syscall)));
+        if let Some((kernel_dirfd, new_path_addr)) =
+            translate_at_path(guest, args.dirfd(), path_addr, mount_table, fd_table).await?
+        {
+            let new_syscall = args.with_dirfd(kernel_dirfd).with_path(Some(new_path_addr));
+            return Ok(Some(Syscall::Mkdirat(new_syscall)));
         }
     }
     Ok(None)
 }
 
+/// Translate the old and new dirfd/path pairs of a `renameat` or `renameat2` system call.
+///
+/// Returns the kernel arguments for both pairs, or `None` if neither needs translation.
+async fn translate_rename_paths<'a, T: Guest<Sandbox>>(
+    guest: &mut T,
+    old: (i32, Option<reverie::syscalls::PathPtr<'a>>),
+    new: (i32, Option<reverie::syscalls::PathPtr<'a>>),
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<Option<[(i32, Option<reverie::syscalls::PathPtr<'a>>); 2]>, Error> {
+    let mut translated = [old, new];
+    let mut modified = false;
+    for (dirfd, path_addr) in translated.iter_mut() {
+        if let Some(addr) = *path_addr {
+            if let Some((kernel_dirfd, new_path_addr)) =
+                translate_at_path(guest, *dirfd, addr, mount_table, fd_table).await?
+            {
+                *dirfd = kernel_dirfd;
+                *path_addr = Some(new_path_addr);
+                modified = true;
+            }
+        }
+    }
+    Ok(modified.then_some(translated))
+}
+
+/// The `renameat` system call.
+///
+/// This intercepts `renameat` system calls, translates both paths according to the mount
+/// table, and virtualizes both dirfd parameters.
+#[cfg(not(target_arch = "aarch64"))]
+pub async fn handle_renameat<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Renameat,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<Option<Syscall>, Error> {
+    let Some([(olddirfd, oldpath), (newdirfd, newpath)]) = translate_rename_paths(
+        guest,
+        (args.olddirfd(), args.oldpath()),
+        (args.newdirfd(), args.newpath()),
+        mount_table,
+        fd_table,
+    )
+    .await?
+    else {
+        return Ok(None);
+    };
+    let new_syscall = args
+        .with_olddirfd(olddirfd)
+        .with_oldpath(oldpath)
+        .with_newdirfd(newdirfd)
+        .with_newpath(newpath);
+    Ok(Some(Syscall::Renameat(new_syscall)))
+}
+
+/// The `renameat2` system call (used for `rename` on ARM).
+///
+/// This intercepts `renameat2` system calls, translates both paths according to the mount
+/// table, and virtualizes both dirfd parameters. The flags are passed on unchanged.
+pub async fn handle_renameat2<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Renameat2,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<Option<Syscall>, Error> {
+    let Some([(olddirfd, oldpath), (newdirfd, newpath)]) = translate_rename_paths(
+        guest,
+        (args.olddirfd(), args.oldpath()),
+        (args.newdirfd(), args.newpath()),
+        mount_table,
+        fd_table,
+    )
+    .await?
+    else {
+        return Ok(None);
+    };
+    let new_syscall = args
+        .with_olddirfd(olddirfd)
+        .with_oldpath(oldpath)
+        .with_newdirfd(newdirfd)
+        .with_newpath(newpath);
+    Ok(Some(Syscall::Renameat2(new_syscall)))
+}
+
 /// The `fchmodat` system call (used for `chmod` on ARM).
 ///
-/// This intercepts `fchmodat` system calls and translates paths according to the mount table.
+/// This intercepts `fchmodat` system calls, translates paths according to the mount table,
+/// and virtualizes the dirfd parameter.
 /// Signature: int fchmodat(int dirfd, const char *pathname, mode_t mode, int flags);
 /// Note: On ARM (aarch64), chmod is implemented via fchmodat with AT_FDCWD.
 pub async fn handle_chmod<T: Guest<Sandbox>>(
     guest: &mut T,
     syscall_args: &reverie::syscalls::SyscallArgs,
     mount_table: &MountTable,
+    fd_table: &FdTable,
 ) -> Result<Option<i64>, Error> {
-    use libc::AT_FDCWD;
     use reverie::syscalls::{AtFlags, Mode, PathPtr, Syscall};
 
     let dirfd = syscall_args.arg0 as i32;
@@ -1813,13 +2121,11 @@ pub async fn handle_chmod<T: Guest<Sandbox>>(
     let mode = syscall_args.arg2 as u32;
     let flags = syscall_args.arg3 as i32;
 
-    if dirfd != libc::AT_FDCWD {
-        return Ok(None);
-    }
-
-    if let Some(new_path_addr) = translate_path(guest, pathname_addr, mount_table).await? {
+    if let Some((kernel_dirfd, new_path_addr)) =
+        translate_at_path(guest, dirfd, pathname_addr, mount_table, fd_table).await?
+    {
         let new_syscall = reverie::syscalls::Fchmodat::new()
-            .with_dirfd(AT_FDCWD)
+            .with_dirfd(kernel_dirfd)
             .with_path(Some(new_path_addr))
             .with_mode(Mode::from_bits_truncate(mode))
             .with_flags(AtFlags::from_bits_truncate(flags));
@@ -1874,3 +2180,72 @@ pub async fn handle_fchownat<T: Guest<Sandbox>>(
 
     Ok(Some(result))
 }
+
+/// The `utimensat` system call.
+///
+/// Paths on a virtual VFS have their times set through `Vfs::set_times()`,
+/// which honors `AT_SYMLINK_NOFOLLOW`. Other paths are translated according
+/// to the mount table, and the dirfd parameter is virtualized.
+pub async fn handle_utimensat<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Utimensat,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    let Some(pathname_addr) = args.path() else {
+        return Ok(None);
+    };
+    let path: std::path::PathBuf = pathname_addr.read(&guest.memory())?;
+
+    if let Some((vfs, _translated_path)) = mount_table.resolve(&path) {
+        if vfs.is_virtual() {
+            let (_, raw) = args.into_parts();
+            // A NULL times pointer sets both times to the current time
+            let now = libc::timespec {
+                tv_sec: 0,
+                tv_nsec: libc::UTIME_NOW,
+            };
+            let (atime, mtime) =
+                match reverie::syscalls::Addr::<[libc::timespec; 2]>::from_raw(raw.arg2) {
+                    Some(addr) => {
+                        let times: [libc::timespec; 2] = guest.memory().read_value(addr)?;
+                        (times[0], times[1])
+                    }
+                    None => (now, now),
+                };
+            let follow_symlinks = (raw.arg3 as i32) & libc::AT_SYMLINK_NOFOLLOW == 0;
+
+            let result = match vfs.set_times(&path, atime, mtime, follow_symlinks).await {
+                Ok(()) => 0,
+                Err(e) => -(e.to_errno() as i64),
+            };
+            return Ok(Some(result));
+        }
+    }
+
+    let dirfd = args.dirfd();
+
+    // Check if dirfd needs virtualization
+    let dirfd_needs_translation = dirfd != libc::AT_FDCWD && fd_table.translate(dirfd).is_some();
+
+    // Check if path needs virtualization
+    let translated_path_opt = translate_path(guest, pathname_addr, mount_table).await?;
+
+    // If nothing needs virtualization, let the original syscall pass through
+    if !dirfd_needs_translation && translated_path_opt.is_none() {
+        return Ok(None);
+    }
+
+    let kernel_dirfd = if dirfd == libc::AT_FDCWD {
+        dirfd
+    } else {
+        fd_table.translate(dirfd).unwrap_or(dirfd)
+    };
+
+    let new_path_addr = translated_path_opt.unwrap_or(pathname_addr);
+    let new_syscall =
+        Syscall::Utimensat(args.with_dirfd(kernel_dirfd).with_path(Some(new_path_addr)));
+    let result = guest.inject(new_syscall).await?;
+
+    Ok(Some(result))
+}
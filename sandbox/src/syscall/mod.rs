@@ -5,13 +5,19 @@ pub mod xattr;
 
 use crate::{
     sandbox::Sandbox,
-    vfs::{fdtable::FdTable, mount::MountTable},
+    vfs::{
+        fdtable::{AtPath, FdTable},
+        mount::MountTable,
+    },
 };
 use reverie::{
-    syscalls::{MemoryAccess, PathPtr, ReadAddr, Syscall},
+    syscalls::{Errno, MemoryAccess, PathPtr, ReadAddr, Syscall},
     Error, Guest, Stack,
 };
-use std::{ffi::CString, path::PathBuf};
+use std::{
+    ffi::CString,
+    path::{Path, PathBuf},
+};
 
 /// Common path translation logic for syscalls.
 ///
@@ -58,9 +64,20 @@ pub(crate) async fn translate_path<'a, T: Guest<Sandbox>>(
         None => return Ok(None), // No mount point matches, use original path
     };
 
-    // Convert translated path to a C string for the syscall
-    let new_path_str = translated_path.to_string_lossy().to_string();
-    let new_path_cstr = CString::new(new_path_str).map_err(|_| reverie::syscalls::Errno::EINVAL)?;
+    Ok(Some(write_path(guest, &translated_path).await?))
+}
+
+/// Write a path to the guest stack for use as a syscall argument.
+///
+/// Like `translate_path()`, the memory is reclaimed when the guest unwinds
+/// its stack after the syscall returns.
+pub(crate) async fn write_path<'a, T: Guest<Sandbox>>(
+    guest: &mut T,
+    path: &Path,
+) -> Result<PathPtr<'a>, Error> {
+    // Convert the path to a C string for the syscall
+    let new_path_str = path.to_string_lossy().to_string();
+    let new_path_cstr = CString::new(new_path_str).map_err(|_| Errno::EINVAL)?;
 
     // Allocate space on the guest stack and write the new path
     let bytes = new_path_cstr.as_bytes_with_nul();
@@ -78,11 +95,73 @@ pub(crate) async fn translate_path<'a, T: Guest<Sandbox>>(
     // 3. Reverie treats these pointer types as thin wrappers around raw pointers
     // 4. PathPtr is a newtype around CStrPtr, which is compatible with a char* pointer
     // 5. The guest will read this as a const char* pointer for the syscall path argument
-    Ok(Some(unsafe {
+    Ok(unsafe {
         std::mem::transmute::<reverie::syscalls::AddrMut<'_, u8>, reverie::syscalls::PathPtr<'_>>(
             byte_addr,
         )
-    }))
+    })
+}
+
+/// Read the dirfd and path arguments of an `*at` syscall.
+///
+/// The virtual dirfd is resolved through the FD table: relative paths against a
+/// virtual directory are composed with the directory's path, so the result can
+/// be checked against the mount table like an absolute path. Fails with `EBADF`
+/// if the dirfd can't be used.
+pub(crate) fn read_at_path<T: Guest<Sandbox>>(
+    guest: &T,
+    dirfd: i32,
+    path_addr: PathPtr<'_>,
+    fd_table: &FdTable,
+) -> Result<AtPath, Error> {
+    let path: PathBuf = path_addr.read(&guest.memory())?;
+    fd_table
+        .resolve_at(dirfd, &path)
+        .map_err(|errno| Error::Errno(Errno::new(errno)))
+}
+
+/// Translate the dirfd and path arguments of an `*at` syscall for the kernel.
+///
+/// This is the `*at` counterpart of `translate_path()`. The path resolved by
+/// `read_at_path()` is translated through the mount table, and the kernel dirfd
+/// replaces the virtual one.
+///
+/// # Returns
+/// * `Ok(Some((dirfd, addr)))` - Arguments to inject in place of the originals
+/// * `Ok(None)` - Neither argument needs translation
+/// * `Err(e)` - The dirfd is invalid or an error occurred during translation
+pub(crate) async fn translate_at_path<'a, T: Guest<Sandbox>>(
+    guest: &mut T,
+    dirfd: i32,
+    path_addr: PathPtr<'a>,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<Option<(i32, PathPtr<'a>)>, Error> {
+    let at = read_at_path(guest, dirfd, path_addr, fd_table)?;
+    translate_resolved_at_path(guest, dirfd, path_addr, &at, mount_table).await
+}
+
+/// Translate an `*at` path already resolved by `read_at_path()`.
+///
+/// Handlers that first check the resolved path against a virtual VFS use this
+/// to pass the remaining calls on to the kernel.
+pub(crate) async fn translate_resolved_at_path<'a, T: Guest<Sandbox>>(
+    guest: &mut T,
+    dirfd: i32,
+    path_addr: PathPtr<'a>,
+    at: &AtPath,
+    mount_table: &MountTable,
+) -> Result<Option<(i32, PathPtr<'a>)>, Error> {
+    if let Some((_vfs, translated_path)) = mount_table.resolve(&at.path) {
+        return Ok(Some((at.dirfd, write_path(guest, &translated_path).await?)));
+    }
+    if at.composed {
+        return Ok(Some((at.dirfd, write_path(guest, &at.path).await?)));
+    }
+    if at.dirfd != dirfd {
+        return Ok(Some((at.dirfd, path_addr)));
+    }
+    Ok(None)
 }
 
 /// System call dispatch.
@@ -141,9 +220,18 @@ pub async fn dispatch_syscall<T: Guest<Sandbox>>(
                 Ok(SyscallResult::Syscall(syscall))
             }
         }
-        #[cfg(target_arch = "aarch64")]
         Syscall::Unlinkat(args) => {
-            if let Some(modified) = file::handle_unlinkat(guest, args, mount_table).await? {
+            if let Some(modified) =
+                file::handle_unlinkat(guest, args, mount_table, fd_table).await?
+            {
+                Ok(SyscallResult::Syscall(modified))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Mkdirat(args) => {
+            if let Some(modified) = file::handle_mkdirat(guest, args, mount_table, fd_table).await?
+            {
                 Ok(SyscallResult::Syscall(modified))
             } else {
                 Ok(SyscallResult::Syscall(syscall))
@@ -288,6 +376,8 @@ pub async fn dispatch_syscall<T: Guest<Sandbox>>(
         }
         Syscall::Getdents64(args) => file::handle_getdents64(guest, syscall, args, fd_table).await,
         Syscall::Fstat(args) => file::handle_fstat(guest, syscall, args, fd_table).await,
+        Syscall::Fadvise64(args) => file::handle_fadvise64(syscall, args, fd_table).await,
+        Syscall::Ftruncate(args) => file::handle_ftruncate(syscall, args, fd_table).await,
         #[cfg(target_arch = "aarch64")]
         Syscall::Fstatat(args) => {
             file::handle_fstatat(guest, syscall, args, fd_table, mount_table).await
@@ -419,6 +509,25 @@ pub async fn dispatch_syscall<T: Guest<Sandbox>>(
             }
         }
         #[cfg(not(target_arch = "aarch64"))]
+        Syscall::Renameat(args) => {
+            if let Some(modified) =
+                file::handle_renameat(guest, args, mount_table, fd_table).await?
+            {
+                Ok(SyscallResult::Syscall(modified))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Renameat2(args) => {
+            if let Some(modified) =
+                file::handle_renameat2(guest, args, mount_table, fd_table).await?
+            {
+                Ok(SyscallResult::Syscall(modified))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        #[cfg(not(target_arch = "aarch64"))]
         Syscall::Unlink(args) => {
             if let Some(modified) = file::handle_unlink(guest, args, mount_table).await? {
                 Ok(SyscallResult::Syscall(modified))
@@ -440,6 +549,14 @@ pub async fn dispatch_syscall<T: Guest<Sandbox>>(
                 Ok(SyscallResult::Syscall(syscall))
             }
         }
+        Syscall::Utimensat(args) => {
+            if let Some(result) = file::handle_utimensat(guest, args, mount_table, fd_table).await?
+            {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
         // Threading and synchronization - passthrough
         Syscall::SetTidAddress(_) => Ok(SyscallResult::Syscall(syscall)),
         Syscall::SetRobustList(_) => Ok(SyscallResult::Syscall(syscall)),
@@ -480,7 +597,9 @@ pub async fn dispatch_syscall<T: Guest<Sandbox>>(
                 Sysno::rseq => Ok(SyscallResult::Syscall(syscall)), // rseq - passthrough
                 Sysno::lseek => Ok(SyscallResult::Syscall(syscall)),
                 Sysno::fchmodat => {
-                    if let Some(result) = file::handle_chmod(guest, args, mount_table).await? {
+                    if let Some(result) =
+                        file::handle_chmod(guest, args, mount_table, fd_table).await?
+                    {
                         Ok(SyscallResult::Value(result))
                     } else {
                         Ok(SyscallResult::Syscall(syscall))
@@ -1,4 +1,8 @@
-use crate::{sandbox, sandbox::Sandbox, vfs::fdtable::FdTable};
+use crate::{
+    sandbox,
+    sandbox::Sandbox,
+    vfs::fdtable::{FdEntry, FdTable},
+};
 use reverie::{syscalls::Syscall, Error, Guest};
 
 /// The `fork` system call.
@@ -120,3 +124,17 @@ pub async fn handle_clone3<T: Guest<Sandbox>>(
 
     Ok(Some(result))
 }
+
+/// A successful `execve`.
+///
+/// The kernel closes close-on-exec kernel FDs when a process execs, so the
+/// matching virtual FDs are dropped from its FD table as well, and the
+/// virtual files among them are closed. Otherwise the new program would
+/// inherit virtual FDs it should never see.
+pub async fn handle_post_exec(fd_table: &FdTable) {
+    for entry in fd_table.close_on_exec() {
+        if let FdEntry::Virtual { file_ops, .. } = entry {
+            file_ops.close().await.ok();
+        }
+    }
+}
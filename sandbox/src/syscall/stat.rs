@@ -1,11 +1,14 @@
 use crate::{
     sandbox::Sandbox,
-    syscall::translate_path,
-    vfs::{fdtable::FdTable, mount::MountTable},
+    syscall::{read_at_path, translate_path, translate_resolved_at_path},
+    vfs::{
+        fdtable::{FdEntry, FdTable},
+        mount::MountTable,
+    },
 };
 use reverie::{
     syscalls::{MemoryAccess, ReadAddr, Syscall},
-    Error, Guest, Stack,
+    Error, Guest,
 };
 
 /// The `statx` system call.
@@ -20,20 +23,12 @@ pub async fn handle_statx<T: Guest<Sandbox>>(
     mount_table: &MountTable,
     fd_table: &FdTable,
 ) -> Result<Option<i64>, Error> {
-    let dirfd = args.dirfd();
-    // AT_FDCWD is -100
-    let kernel_dirfd = if dirfd == -100 {
-        dirfd
-    } else {
-        fd_table.translate(dirfd).unwrap_or(dirfd)
-    };
-
     if let Some(path_addr) = args.path() {
-        // Read the original path from guest memory
-        let path: std::path::PathBuf = path_addr.read(&guest.memory())?;
+        // Resolve the dirfd, composing relative paths against virtual directories
+        let at = read_at_path(guest, args.dirfd(), path_addr, fd_table)?;
 
         // Check if this path matches a mount point
-        if let Some((vfs, _translated_path)) = mount_table.resolve(&path) {
+        if let Some((vfs, _translated_path)) = mount_table.resolve(&at.path) {
             // Check if this is a virtual VFS (like SQLite)
             if vfs.is_virtual() {
                 // For virtual VFS, statx is not supported - return ENOSYS
@@ -42,7 +37,9 @@ pub async fn handle_statx<T: Guest<Sandbox>>(
             }
         }
 
-        if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
+        if let Some((kernel_dirfd, new_path_addr)) =
+            translate_resolved_at_path(guest, args.dirfd(), path_addr, &at, mount_table).await?
+        {
             let new_syscall = reverie::syscalls::Statx::new()
                 .with_dirfd(kernel_dirfd)
                 .with_path(Some(new_path_addr))
@@ -71,29 +68,23 @@ pub async fn handle_newfstatat<T: Guest<Sandbox>>(
     fd_table: &FdTable,
 ) -> Result<Option<i64>, Error> {
     use reverie::syscalls::AtFlags;
-    let dirfd = args.dirfd();
-    // AT_FDCWD is -100
-    let kernel_dirfd = if dirfd == -100 {
-        dirfd
-    } else {
-        fd_table.translate(dirfd).unwrap_or(dirfd)
-    };
 
     if let Some(path_addr) = args.path() {
-        // Read the original path from guest memory
-        let path: std::path::PathBuf = path_addr.read(&guest.memory())?;
+        // Resolve the dirfd, composing relative paths against virtual directories
+        let at = read_at_path(guest, args.dirfd(), path_addr, fd_table)?;
+        let path = &at.path;
 
         // Check if this path matches a mount point
-        if let Some((vfs, _translated_path)) = mount_table.resolve(&path) {
+        if let Some((vfs, _translated_path)) = mount_table.resolve(path) {
             // Check if this is a virtual VFS (like SQLite)
             if vfs.is_virtual() {
                 let flags = args.flags();
                 let follow_symlinks = !flags.contains(AtFlags::AT_SYMLINK_NOFOLLOW);
 
                 let stat_result = if follow_symlinks {
-                    vfs.stat(&path).await
+                    vfs.stat(path).await
                 } else {
-                    vfs.lstat(&path).await
+                    vfs.lstat(path).await
                 };
 
                 match stat_result {
@@ -118,6 +109,7 @@ pub async fn handle_newfstatat<T: Guest<Sandbox>>(
                         let errno = match e {
                             crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
                             crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
+                            crate::vfs::VfsError::NotADirectory => -libc::ENOTDIR as i64,
                             _ => -libc::EIO as i64,
                         };
                         return Ok(Some(errno));
@@ -126,7 +118,9 @@ pub async fn handle_newfstatat<T: Guest<Sandbox>>(
             }
         }
 
-        if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
+        if let Some((kernel_dirfd, new_path_addr)) =
+            translate_resolved_at_path(guest, args.dirfd(), path_addr, &at, mount_table).await?
+        {
             let new_syscall = reverie::syscalls::Newfstatat::new()
                 .with_dirfd(kernel_dirfd)
                 .with_path(Some(new_path_addr))
@@ -198,6 +192,7 @@ pub async fn handle_readlink<T: Guest<Sandbox>>(
                         let errno = match e {
                             crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
                             crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
+                            crate::vfs::VfsError::NotADirectory => -libc::ENOTDIR as i64,
                             _ => -libc::EINVAL as i64,
                         };
                         return Ok(Some(errno));
@@ -268,6 +263,7 @@ pub async fn handle_readlinkat<T: Guest<Sandbox>>(
                         let errno = match e {
                             crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
                             crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
+                            crate::vfs::VfsError::NotADirectory => -libc::ENOTDIR as i64,
                             _ => -libc::EINVAL as i64,
                         };
                         return Ok(Some(errno));
@@ -322,6 +318,7 @@ pub async fn handle_symlink<T: Guest<Sandbox>>(
                             let errno = match e {
                                 crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
                                 crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
+                                crate::vfs::VfsError::NotADirectory => -libc::ENOTDIR as i64,
                                 crate::vfs::VfsError::AlreadyExists => -libc::EEXIST as i64,
                                 _ => -libc::EIO as i64,
                             };
@@ -386,6 +383,7 @@ pub async fn handle_symlinkat<T: Guest<Sandbox>>(
                             let errno = match e {
                                 crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
                                 crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
+                                crate::vfs::VfsError::NotADirectory => -libc::ENOTDIR as i64,
                                 crate::vfs::VfsError::AlreadyExists => -libc::EEXIST as i64,
                                 _ => -libc::EIO as i64,
                             };
@@ -423,128 +421,84 @@ pub async fn handle_linkat<T: Guest<Sandbox>>(
     mount_table: &MountTable,
     fd_table: &FdTable,
 ) -> Result<Option<i64>, Error> {
+    use reverie::syscalls::AtFlags;
+
     let olddirfd = args.olddirfd();
     let newdirfd = args.newdirfd();
 
-    // AT_FDCWD is -100
-    let kernel_olddirfd = if olddirfd == -100 {
-        olddirfd
-    } else {
-        fd_table.translate(olddirfd).unwrap_or(olddirfd)
-    };
-    let kernel_newdirfd = if newdirfd == -100 {
-        newdirfd
-    } else {
-        fd_table.translate(newdirfd).unwrap_or(newdirfd)
-    };
-
-    // Read oldpath and newpath from guest memory
-    if let Some(oldpath_addr) = args.oldpath() {
-        let oldpath: std::path::PathBuf = oldpath_addr.read(&guest.memory())?;
-
-        if let Some(newpath_addr) = args.newpath() {
-            let newpath: std::path::PathBuf = newpath_addr.read(&guest.memory())?;
-
-            // Check if newpath matches a mount point with virtual VFS
-            if let Some((vfs, _translated_path)) = mount_table.resolve(&newpath) {
-                // Check if this is a virtual VFS (like SQLite)
-                if vfs.is_virtual() {
-                    // Call VFS link method directly
-                    match vfs.link(&oldpath, &newpath).await {
-                        Ok(()) => return Ok(Some(0)), // Success
-                        Err(e) => {
-                            // Map VFS errors to errno
-                            let errno = match e {
-                                crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
-                                crate::vfs::VfsError::PermissionDenied => -libc::EPERM as i64,
-                                crate::vfs::VfsError::AlreadyExists => -libc::EEXIST as i64,
-                                _ => -libc::EIO as i64,
-                            };
-                            return Ok(Some(errno));
-                        }
+    // linkat(fd, "", newdirfd, newpath, AT_EMPTY_PATH) names an open file, which is
+    // how O_TMPFILE files are materialized. Virtual FDs have no kernel file to link.
+    if args.flags().contains(AtFlags::AT_EMPTY_PATH) {
+        if let Some(FdEntry::Virtual { file_ops, .. }) = fd_table.get(olddirfd) {
+            let Some(newpath_addr) = args.newpath() else {
+                return Ok(Some(-libc::EFAULT as i64));
+            };
+            let newpath = read_at_path(guest, newdirfd, newpath_addr, fd_table)?.path;
+
+            let errno = match mount_table.resolve(&newpath) {
+                Some((vfs, _)) if vfs.is_virtual() => {
+                    match vfs.link_file(&file_ops, &newpath).await {
+                        Ok(()) => return Ok(Some(0)),
+                        Err(crate::vfs::VfsError::NotFound) => -libc::ENOENT as i64,
+                        Err(crate::vfs::VfsError::PermissionDenied) => -libc::EPERM as i64,
+                        Err(crate::vfs::VfsError::AlreadyExists) => -libc::EEXIST as i64,
+                        Err(_) => -libc::EIO as i64,
                     }
                 }
-            }
+                // Linking a virtual file onto another filesystem
+                _ => -libc::EXDEV as i64,
+            };
+            return Ok(Some(errno));
+        }
+    }
 
-            // Check if either path needs translation by consulting the mount table.
-            // We resolve both paths first (without guest memory allocation) to determine
-            // what needs translation, then allocate and inject if needed.
-            let oldpath_translated = mount_table.resolve(&oldpath);
-            let newpath_translated = mount_table.resolve(&newpath);
-
-            match (oldpath_translated, newpath_translated) {
-                (Some((_vfs1, translated_oldpath)), Some((_vfs2, translated_newpath))) => {
-                    // Both paths need translation
-                    use std::ffi::CString;
-
-                    let old_cstr = CString::new(translated_oldpath.to_string_lossy().to_string())
-                        .map_err(|_| reverie::syscalls::Errno::EINVAL)?;
-                    let new_cstr = CString::new(translated_newpath.to_string_lossy().to_string())
-                        .map_err(|_| reverie::syscalls::Errno::EINVAL)?;
-
-                    // Allocate space for both paths on guest stack
-                    let old_bytes = old_cstr.as_bytes_with_nul();
-                    let new_bytes = new_cstr.as_bytes_with_nul();
-
-                    let mut stack = guest.stack().await;
-                    let old_addr: reverie::syscalls::AddrMut<std::path::PathBuf> = stack.reserve();
-                    let new_addr: reverie::syscalls::AddrMut<std::path::PathBuf> = stack.reserve();
-                    stack.commit()?;
-
-                    let old_byte_addr = old_addr.cast::<u8>();
-                    let new_byte_addr = new_addr.cast::<u8>();
-                    guest.memory().write_exact(old_byte_addr, old_bytes)?;
-                    guest.memory().write_exact(new_byte_addr, new_bytes)?;
-
-                    let new_oldpath_ptr: reverie::syscalls::PathPtr =
-                        unsafe { std::mem::transmute(old_byte_addr) };
-                    let new_newpath_ptr: reverie::syscalls::PathPtr =
-                        unsafe { std::mem::transmute(new_byte_addr) };
-
-                    let new_syscall = reverie::syscalls::Linkat::new()
-                        .with_olddirfd(kernel_olddirfd)
-                        .with_oldpath(Some(new_oldpath_ptr))
-                        .with_newdirfd(kernel_newdirfd)
-                        .with_newpath(Some(new_newpath_ptr))
-                        .with_flags(args.flags());
-                    let result = guest.inject(Syscall::Linkat(new_syscall)).await?;
-                    return Ok(Some(result));
-                }
-                (Some(_), None) => {
-                    // Only oldpath needs translation
-                    if let Some(new_oldpath_addr) =
-                        translate_path(guest, oldpath_addr, mount_table).await?
-                    {
-                        let new_syscall = reverie::syscalls::Linkat::new()
-                            .with_olddirfd(kernel_olddirfd)
-                            .with_oldpath(Some(new_oldpath_addr))
-                            .with_newdirfd(kernel_newdirfd)
-                            .with_newpath(Some(newpath_addr))
-                            .with_flags(args.flags());
-                        let result = guest.inject(Syscall::Linkat(new_syscall)).await?;
-                        return Ok(Some(result));
-                    }
-                }
-                (None, Some(_)) => {
-                    // Only newpath needs translation
-                    if let Some(new_newpath_addr) =
-                        translate_path(guest, newpath_addr, mount_table).await?
-                    {
-                        let new_syscall = reverie::syscalls::Linkat::new()
-                            .with_olddirfd(kernel_olddirfd)
-                            .with_oldpath(Some(oldpath_addr))
-                            .with_newdirfd(kernel_newdirfd)
-                            .with_newpath(Some(new_newpath_addr))
-                            .with_flags(args.flags());
-                        let result = guest.inject(Syscall::Linkat(new_syscall)).await?;
-                        return Ok(Some(result));
-                    }
-                }
-                (None, None) => {
-                    // Neither path needs translation
+    let (Some(oldpath_addr), Some(newpath_addr)) = (args.oldpath(), args.newpath()) else {
+        return Ok(None);
+    };
+
+    // Resolve both dirfds, composing relative paths against virtual directories
+    let old_at = read_at_path(guest, olddirfd, oldpath_addr, fd_table)?;
+    let new_at = read_at_path(guest, newdirfd, newpath_addr, fd_table)?;
+
+    // Check if newpath matches a mount point with virtual VFS
+    if let Some((vfs, _translated_path)) = mount_table.resolve(&new_at.path) {
+        // Check if this is a virtual VFS (like SQLite)
+        if vfs.is_virtual() {
+            // Call VFS link method directly
+            match vfs.link(&old_at.path, &new_at.path).await {
+                Ok(()) => return Ok(Some(0)), // Success
+                Err(e) => {
+                    // Map VFS errors to errno
+                    let errno = match e {
+                        crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
+                        crate::vfs::VfsError::PermissionDenied => -libc::EPERM as i64,
+                        crate::vfs::VfsError::NotADirectory => -libc::ENOTDIR as i64,
+                        crate::vfs::VfsError::AlreadyExists => -libc::EEXIST as i64,
+                        _ => -libc::EIO as i64,
+                    };
+                    return Ok(Some(errno));
                 }
             }
         }
     }
-    Ok(None)
+
+    // Translate each dirfd/path pair, and pass the call through unchanged if neither needs it
+    let old =
+        translate_resolved_at_path(guest, olddirfd, oldpath_addr, &old_at, mount_table).await?;
+    let new =
+        translate_resolved_at_path(guest, newdirfd, newpath_addr, &new_at, mount_table).await?;
+    if old.is_none() && new.is_none() {
+        return Ok(None);
+    }
+    let (kernel_olddirfd, new_oldpath_addr) = old.unwrap_or((olddirfd, oldpath_addr));
+    let (kernel_newdirfd, new_newpath_addr) = new.unwrap_or((newdirfd, newpath_addr));
+
+    let new_syscall = reverie::syscalls::Linkat::new()
+        .with_olddirfd(kernel_olddirfd)
+        .with_oldpath(Some(new_oldpath_addr))
+        .with_newdirfd(kernel_newdirfd)
+        .with_newpath(Some(new_newpath_addr))
+        .with_flags(args.flags());
+    let result = guest.inject(Syscall::Linkat(new_syscall)).await?;
+    Ok(Some(result))
 }
@@ -1,5 +1,6 @@
 use super::file::BoxedFileOps;
 use std::collections::{BinaryHeap, HashMap};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
 /// Standard file descriptor constants
@@ -50,6 +51,24 @@ impl FdEntry {
         }
     }
 
+    /// Whether this FD is closed on exec (FD_CLOEXEC)
+    pub fn is_cloexec(&self) -> bool {
+        self.flags() & libc::O_CLOEXEC != 0
+    }
+
+    /// Set or clear the close-on-exec flag of this FD
+    fn set_cloexec(&mut self, cloexec: bool) {
+        let flags = match self {
+            FdEntry::Passthrough { flags, .. } => flags,
+            FdEntry::Virtual { flags, .. } => flags,
+        };
+        if cloexec {
+            *flags |= libc::O_CLOEXEC;
+        } else {
+            *flags &= !libc::O_CLOEXEC;
+        }
+    }
+
     /// Get the file_ops for virtual files
     pub fn file_ops(&self) -> Option<&BoxedFileOps> {
         match self {
@@ -59,6 +78,18 @@ impl FdEntry {
     }
 }
 
+/// The dirfd and path arguments of an `*at` system call, resolved against the
+/// FD table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AtPath {
+    /// Kernel dirfd that `path` is relative to (or `AT_FDCWD`)
+    pub dirfd: i32,
+    /// Path to look up, relative to `dirfd` unless absolute
+    pub path: PathBuf,
+    /// Whether `path` was composed from the path of a virtual directory
+    pub composed: bool,
+}
+
 /// Inner state of the FD table, protected by a single mutex
 struct FdTableInner {
     /// Mapping from virtual FD to kernel FD
@@ -270,6 +301,43 @@ impl FdTable {
         inner.entries.get(&vfd).cloned()
     }
 
+    /// Resolve the dirfd and path arguments of an `*at` system call
+    ///
+    /// Absolute paths ignore the dirfd and relative paths with `AT_FDCWD` are
+    /// left to the kernel. A relative path against a passthrough directory
+    /// stays relative to its kernel FD, while one against a virtual directory
+    /// has no kernel FD to use, so it is joined to the directory's path.
+    /// Returns `EBADF` for a dirfd that isn't open, or that is virtual without
+    /// a path.
+    pub fn resolve_at(&self, dirfd: i32, path: &Path) -> Result<AtPath, i32> {
+        if dirfd == libc::AT_FDCWD || path.is_absolute() {
+            return Ok(AtPath {
+                dirfd: libc::AT_FDCWD,
+                path: path.to_path_buf(),
+                composed: false,
+            });
+        }
+        let entry = self.get(dirfd).ok_or(libc::EBADF)?;
+        if let Some(kernel_fd) = entry.kernel_fd() {
+            return Ok(AtPath {
+                dirfd: kernel_fd,
+                path: path.to_path_buf(),
+                composed: false,
+            });
+        }
+        let dir_path = entry.path().ok_or(libc::EBADF)?;
+        Ok(AtPath {
+            dirfd: libc::AT_FDCWD,
+            // An empty path names the directory itself (AT_EMPTY_PATH)
+            path: if path.as_os_str().is_empty() {
+                dir_path.clone()
+            } else {
+                dir_path.join(path)
+            },
+            composed: true,
+        })
+    }
+
     /// Deallocate a virtual FD and mark it as available for reuse
     pub fn deallocate(&self, vfd: i32) -> Option<FdEntry> {
         let mut inner = self
@@ -287,9 +355,58 @@ impl FdTable {
         Some(entry)
     }
 
+    /// Set or clear the close-on-exec flag of a virtual FD (for F_SETFD)
+    ///
+    /// The flag belongs to the descriptor, not to the open file it shares
+    /// with its duplicates. Returns false if the VFD doesn't exist.
+    pub fn set_cloexec(&self, vfd: i32, cloexec: bool) -> bool {
+        let mut inner = self
+            .inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match inner.entries.get_mut(&vfd) {
+            Some(entry) => {
+                entry.set_cloexec(cloexec);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Deallocate every virtual FD marked close-on-exec (after a successful execve)
+    ///
+    /// Returns the removed entries. The kernel has already closed their
+    /// kernel FDs; the caller should close the FileOps of virtual ones.
+    pub fn close_on_exec(&self) -> Vec<FdEntry> {
+        let mut inner = self
+            .inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let vfds: Vec<i32> = inner
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.is_cloexec())
+            .map(|(&vfd, _)| vfd)
+            .collect();
+
+        let mut removed = Vec::with_capacity(vfds.len());
+        for vfd in vfds {
+            if let Some(entry) = inner.entries.remove(&vfd) {
+                if vfd >= FIRST_USER_FD {
+                    inner.free_fds.push(std::cmp::Reverse(vfd));
+                }
+                removed.push(entry);
+            }
+        }
+        removed
+    }
+
     /// Duplicate a virtual FD (for dup syscall)
+    ///
+    /// Like the kernel, the duplicate is not close-on-exec.
     pub fn duplicate(&self, old_vfd: i32) -> Option<i32> {
-        let entry = self.get(old_vfd)?;
+        let mut entry = self.get(old_vfd)?;
+        entry.set_cloexec(false);
         // Allocate a new virtual FD pointing to the same file operations
         Some(self.allocate(entry))
     }
@@ -298,7 +415,8 @@ impl FdTable {
     ///
     /// Returns the old entry that was at new_vfd if it existed (caller should close it)
     pub fn duplicate_at(&self, old_vfd: i32, new_vfd: i32) -> Option<FdEntry> {
-        let entry = self.get(old_vfd)?;
+        let mut entry = self.get(old_vfd)?;
+        entry.set_cloexec(false);
         self.allocate_at(new_vfd, entry)
     }
 }
@@ -409,6 +527,117 @@ mod tests {
         assert!(result.is_none());
         assert_eq!(table.translate(10), Some(100));
     }
+
+    #[test]
+    fn test_close_on_exec() {
+        let table = FdTable::new();
+
+        let opened = table.allocate(FdEntry::Passthrough {
+            kernel_fd: 100,
+            flags: libc::O_RDONLY | libc::O_CLOEXEC,
+            path: None,
+        });
+        let set = table.allocate(FdEntry::Passthrough {
+            kernel_fd: 101,
+            flags: libc::O_RDONLY,
+            path: None,
+        });
+        assert!(table.set_cloexec(set, true));
+        let cleared = table.allocate(FdEntry::Passthrough {
+            kernel_fd: 102,
+            flags: libc::O_RDONLY | libc::O_CLOEXEC,
+            path: None,
+        });
+        assert!(table.set_cloexec(cleared, false));
+        // A duplicate of a close-on-exec FD is not close-on-exec
+        let dup = table.duplicate(opened).unwrap();
+
+        let removed = table.close_on_exec();
+        let mut kernel_fds: Vec<_> = removed.iter().filter_map(|e| e.kernel_fd()).collect();
+        kernel_fds.sort();
+        assert_eq!(kernel_fds, vec![100, 101]);
+        assert_eq!(table.translate(opened), None);
+        assert_eq!(table.translate(set), None);
+        assert_eq!(table.translate(cleared), Some(102));
+        assert_eq!(table.translate(dup), Some(100));
+        assert_eq!(table.translate(0), Some(0));
+
+        // The freed FDs are reused
+        let next = table.allocate(FdEntry::Passthrough {
+            kernel_fd: 103,
+            flags: 0,
+            path: None,
+        });
+        assert_eq!(next, opened);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_at() {
+        use crate::vfs::{sqlite::SqliteVfs, Vfs};
+
+        let table = FdTable::new();
+        let passthrough = table.allocate(FdEntry::Passthrough {
+            kernel_fd: 100,
+            flags: libc::O_RDONLY | libc::O_DIRECTORY,
+            path: Some(PathBuf::from("/home/user")),
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = SqliteVfs::new(dir.path().join("test.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap();
+        let dir_ops = vfs
+            .open(Path::new("/agent"), libc::O_RDONLY | libc::O_DIRECTORY, 0)
+            .await
+            .unwrap();
+        let virtual_dir = table.allocate(FdEntry::Virtual {
+            file_ops: dir_ops.clone(),
+            flags: libc::O_RDONLY | libc::O_DIRECTORY,
+            path: Some(PathBuf::from("/agent")),
+        });
+        let pathless = table.allocate(FdEntry::Virtual {
+            file_ops: dir_ops,
+            flags: libc::O_RDONLY,
+            path: None,
+        });
+
+        let at = |dirfd: i32, path: &str| table.resolve_at(dirfd, Path::new(path));
+        let expect = |dirfd: i32, path: &str, composed: bool| {
+            Ok(AtPath {
+                dirfd,
+                path: PathBuf::from(path),
+                composed,
+            })
+        };
+
+        // AT_FDCWD and absolute paths need no directory
+        assert_eq!(
+            at(libc::AT_FDCWD, "a.txt"),
+            expect(libc::AT_FDCWD, "a.txt", false)
+        );
+        assert_eq!(
+            at(virtual_dir, "/etc/passwd"),
+            expect(libc::AT_FDCWD, "/etc/passwd", false)
+        );
+        assert_eq!(at(999, "/tmp"), expect(libc::AT_FDCWD, "/tmp", false));
+
+        // Relative to a passthrough directory: use its kernel FD
+        assert_eq!(
+            at(passthrough, "src/main.rs"),
+            expect(100, "src/main.rs", false)
+        );
+
+        // Relative to a virtual directory: compose the path
+        assert_eq!(
+            at(virtual_dir, "sub/file.txt"),
+            expect(libc::AT_FDCWD, "/agent/sub/file.txt", true)
+        );
+        assert_eq!(at(virtual_dir, ""), expect(libc::AT_FDCWD, "/agent", true));
+
+        // Unknown and pathless dirfds
+        assert_eq!(at(999, "a.txt"), Err(libc::EBADF));
+        assert_eq!(at(pathless, "a.txt"), Err(libc::EBADF));
+    }
 }
 
 /// Property tests for `FdTable` correctness.
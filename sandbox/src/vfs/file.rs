@@ -16,6 +16,33 @@ pub trait FileOps: Send + Sync {
     /// Write to the file at the current offset
     async fn write(&self, buf: &[u8]) -> VfsResult<usize>;
 
+    /// Read into several buffers at the current offset, filling each in turn
+    ///
+    /// Implements readv(2) as a single read of the combined length, so the
+    /// backing store sees one request however many buffers there are.
+    async fn readv(&self, bufs: &mut [&mut [u8]]) -> VfsResult<usize> {
+        let mut data = vec![0u8; bufs.iter().map(|buf| buf.len()).sum()];
+        let n = self.read(&mut data).await?;
+        let mut remaining = &data[..n];
+        for buf in bufs.iter_mut() {
+            if remaining.is_empty() {
+                break;
+            }
+            let len = buf.len().min(remaining.len());
+            buf[..len].copy_from_slice(&remaining[..len]);
+            remaining = &remaining[len..];
+        }
+        Ok(n)
+    }
+
+    /// Write several buffers at the current offset, one after another
+    ///
+    /// Implements writev(2) as a single write of the concatenated buffers, so
+    /// the data lands contiguously even with `O_APPEND`.
+    async fn writev(&self, bufs: &[&[u8]]) -> VfsResult<usize> {
+        self.write(&bufs.concat()).await
+    }
+
     /// Seek to a position in the file
     async fn seek(&self, offset: i64, whence: i32) -> VfsResult<i64>;
 
@@ -28,6 +55,47 @@ pub trait FileOps: Send + Sync {
     /// Sync file data (but not metadata) to storage
     async fn fdatasync(&self) -> VfsResult<()>;
 
+    /// Write back buffered data, as done when a file descriptor is closed
+    ///
+    /// Unlike `fsync`, this makes no durability guarantee. It exists so that
+    /// write errors can be reported by close(2). Files without buffered data
+    /// have nothing to flush.
+    async fn flush(&self) -> VfsResult<()> {
+        Ok(())
+    }
+
+    /// Truncate or extend the file to `len` bytes
+    ///
+    /// Implements ftruncate(2); extending leaves a hole that reads as zeros.
+    /// Files that cannot be resized fail with `VfsError::InvalidInput`.
+    async fn ftruncate(&self, _len: u64) -> VfsResult<()> {
+        Err(super::VfsError::InvalidInput(
+            "File cannot be truncated".to_string(),
+        ))
+    }
+
+    /// Get the inode flags, as reported by the `FS_IOC_GETFLAGS` ioctl
+    ///
+    /// Files without inode flags fail with `VfsError::NotSupported`.
+    async fn get_inode_flags(&self) -> VfsResult<u32> {
+        Err(super::VfsError::NotSupported)
+    }
+
+    /// Replace the inode flags, as the `FS_IOC_SETFLAGS` ioctl does
+    ///
+    /// Files without inode flags fail with `VfsError::NotSupported`.
+    async fn set_inode_flags(&self, _flags: u32) -> VfsResult<()> {
+        Err(super::VfsError::NotSupported)
+    }
+
+    /// Advise on the expected access pattern for a range of the file
+    ///
+    /// Implements posix_fadvise(2); a `len` of 0 extends to the end of the
+    /// file. Files without a cache of their own ignore the hint.
+    async fn fadvise(&self, _offset: i64, _len: i64, _advice: i32) -> VfsResult<()> {
+        Ok(())
+    }
+
     /// Perform file control operations
     fn fcntl(&self, cmd: i32, arg: i64) -> VfsResult<i64>;
 
@@ -52,9 +120,9 @@ pub trait FileOps: Send + Sync {
     /// Read directory entries (for directories only)
     ///
     /// This is used to implement getdents64. Returns a vector of (inode, name, type) tuples.
-    /// Returns an error if this is not a directory.
+    /// Fails with `VfsError::NotADirectory` if this is not a directory.
     async fn getdents(&self) -> VfsResult<Vec<(u64, String, u8)>> {
-        Err(super::VfsError::Other("Not a directory".to_string()))
+        Err(super::VfsError::NotADirectory)
     }
 }
 
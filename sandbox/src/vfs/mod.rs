@@ -15,11 +15,65 @@ pub enum VfsError {
     NotFound,
     PermissionDenied,
     AlreadyExists,
+    /// The storage backing the VFS is full (ENOSPC)
+    NoSpace,
+    /// The VFS's limit on open files was reached (EMFILE)
+    TooManyOpenFiles,
+    /// No data or hole at or past a `SEEK_DATA`/`SEEK_HOLE` offset (ENXIO)
+    NoSuchOffset,
+    /// A path component or `O_DIRECTORY` target is not a directory (ENOTDIR)
+    NotADirectory,
+    /// The operation is not permitted on this handle, e.g. one opened with
+    /// `O_PATH` (EBADF)
+    BadFileDescriptor,
+    /// Inode flags forbid the operation, e.g. writing an immutable file (EPERM)
+    NotPermitted,
+    /// The file does not support the operation, e.g. an ioctl (ENOTTY)
+    NotSupported,
     InvalidInput(String),
     IoError(std::io::Error),
+    /// The storage backing the VFS failed. `source` is the original error,
+    /// which callers can downcast instead of matching on its message.
+    Backend {
+        context: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
     Other(String),
 }
 
+impl VfsError {
+    /// Wrap an error from the storage backing the VFS, noting what the VFS
+    /// was doing when it occurred
+    pub fn backend(
+        context: impl Into<String>,
+        source: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+    ) -> Self {
+        VfsError::Backend {
+            context: context.into(),
+            source: source.into(),
+        }
+    }
+
+    /// Get the errno a system call on the VFS fails with
+    pub fn to_errno(&self) -> i32 {
+        match self {
+            VfsError::NotFound => libc::ENOENT,
+            VfsError::PermissionDenied => libc::EACCES,
+            VfsError::AlreadyExists => libc::EEXIST,
+            VfsError::NoSpace => libc::ENOSPC,
+            VfsError::TooManyOpenFiles => libc::EMFILE,
+            VfsError::NoSuchOffset => libc::ENXIO,
+            VfsError::NotADirectory => libc::ENOTDIR,
+            VfsError::BadFileDescriptor => libc::EBADF,
+            VfsError::NotPermitted => libc::EPERM,
+            VfsError::NotSupported => libc::ENOTTY,
+            VfsError::InvalidInput(_) => libc::EINVAL,
+            VfsError::IoError(err) => err.raw_os_error().unwrap_or(libc::EIO),
+            VfsError::Backend { .. } | VfsError::Other(_) => libc::EIO,
+        }
+    }
+}
+
 impl From<std::io::Error> for VfsError {
     fn from(err: std::io::Error) -> Self {
         VfsError::IoError(err)
@@ -32,14 +86,30 @@ impl std::fmt::Display for VfsError {
             VfsError::NotFound => write!(f, "Not found"),
             VfsError::PermissionDenied => write!(f, "Permission denied"),
             VfsError::AlreadyExists => write!(f, "Already exists"),
+            VfsError::NoSpace => write!(f, "No space left on device"),
+            VfsError::TooManyOpenFiles => write!(f, "Too many open files"),
+            VfsError::NoSuchOffset => write!(f, "No such device or address"),
+            VfsError::NotADirectory => write!(f, "Not a directory"),
+            VfsError::BadFileDescriptor => write!(f, "Bad file descriptor"),
+            VfsError::NotPermitted => write!(f, "Operation not permitted"),
+            VfsError::NotSupported => write!(f, "Operation not supported"),
             VfsError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
             VfsError::IoError(err) => write!(f, "IO error: {}", err),
+            VfsError::Backend { context, source } => write!(f, "{}: {}", context, source),
             VfsError::Other(msg) => write!(f, "{}", msg),
         }
     }
 }
 
-impl std::error::Error for VfsError {}
+impl std::error::Error for VfsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            VfsError::IoError(err) => Some(err),
+            VfsError::Backend { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 pub type VfsResult<T> = StdResult<T, VfsError>;
 
@@ -94,6 +164,20 @@ pub trait Vfs: Send + Sync {
         ))
     }
 
+    /// Check whether a path exists, without following symlinks in its final
+    /// component (for virtual filesystems)
+    ///
+    /// Implementations should answer without building the full stats that
+    /// `lstat` returns. The default implementation calls `lstat`.
+    /// This is only called for virtual VFS implementations.
+    async fn exists(&self, path: &Path) -> VfsResult<bool> {
+        match self.lstat(path).await {
+            Ok(_) => Ok(true),
+            Err(VfsError::NotFound) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
     /// Create a symbolic link (for virtual filesystems)
     ///
     /// This is only called for virtual VFS implementations.
@@ -112,6 +196,25 @@ pub trait Vfs: Send + Sync {
         ))
     }
 
+    /// Set access and modification times (for virtual filesystems)
+    ///
+    /// Implements `utimensat`: `tv_nsec` of each time may be `UTIME_NOW` or
+    /// `UTIME_OMIT`. Symlinks in the final component are followed unless
+    /// `follow_symlinks` is false (`AT_SYMLINK_NOFOLLOW`), in which case the
+    /// link's own times are set.
+    /// This is only called for virtual VFS implementations.
+    async fn set_times(
+        &self,
+        _path: &Path,
+        _atime: libc::timespec,
+        _mtime: libc::timespec,
+        _follow_symlinks: bool,
+    ) -> VfsResult<()> {
+        Err(VfsError::Other(
+            "set_times() not supported by this VFS".to_string(),
+        ))
+    }
+
     /// Create a hard link (for virtual filesystems)
     ///
     /// Creates a new directory entry `newpath` that refers to the same inode as `oldpath`.
@@ -121,6 +224,17 @@ pub trait Vfs: Send + Sync {
             "link() not supported by this VFS".to_string(),
         ))
     }
+
+    /// Create a hard link to an open file (for virtual filesystems)
+    ///
+    /// Implements `linkat(fd, "", newdirfd, newpath, AT_EMPTY_PATH)`, which is how
+    /// files opened with `O_TMPFILE` are given a name.
+    /// This is only called for virtual VFS implementations.
+    async fn link_file(&self, _file: &BoxedFileOps, _newpath: &Path) -> VfsResult<()> {
+        Err(VfsError::Other(
+            "link_file() not supported by this VFS".to_string(),
+        ))
+    }
 }
 
 /// A boxed VFS trait object for dynamic dispatch
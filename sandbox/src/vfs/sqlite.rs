@@ -1,13 +1,233 @@
 use super::file::{BoxedFileOps, FileOps};
 use super::{Vfs, VfsError, VfsResult};
-use agentfs_sdk::{filesystem::AgentFS, FileSystem};
+use agentfs_sdk::{
+    filesystem::{AgentFS, ChunkCache, FsError, FS_APPEND_FL, FS_IMMUTABLE_FL},
+    FileSystem, Stats, TimeChange,
+};
+use std::collections::HashMap;
 use std::os::unix::io::RawFd;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
 
 /// Root inode number
 const ROOT_INO: i64 = 1;
 
+/// Granularity at which file contents are loaded and cached
+const PAGE_SIZE: usize = 4096;
+
+/// Pages read ahead of a cache miss by default
+const DEFAULT_READAHEAD_PAGES: usize = 4;
+
+/// Pages read ahead of a cache miss after `POSIX_FADV_SEQUENTIAL`
+const SEQUENTIAL_READAHEAD_PAGES: usize = 32;
+
+/// Largest readahead window that large reads can grow to
+const MAX_READAHEAD_PAGES: usize = 64;
+
+/// Symlinks followed while resolving a single path before failing with ELOOP
+const MAX_SYMLINK_FOLLOWS: usize = 40;
+
+/// Default limit on files open at once through a `SqliteVfs`
+const DEFAULT_MAX_OPEN_FILES: usize = 1024;
+
+/// Default dirty data a `SqliteVfs` buffers before forcing write-back
+const DEFAULT_DIRTY_HIGH_WATER: usize = 256 * 1024 * 1024;
+
+/// Page cache counters, shared by all files opened through a `SqliteVfs`
+#[derive(Debug, Default)]
+pub struct CacheMetrics {
+    /// Pages loaded because a read or write touched them
+    pub pages_read: AtomicU64,
+    /// Pages loaded ahead of access (readahead and `POSIX_FADV_WILLNEED`)
+    pub pages_prefetched: AtomicU64,
+    /// Resident pages released by `POSIX_FADV_DONTNEED`
+    pub pages_dropped: AtomicU64,
+    /// Files written back because dirty data passed the high-water mark
+    pub forced_flushes: AtomicU64,
+    /// Database syncs made by closing files, as the `Durability` mode asks
+    pub close_syncs: AtomicU64,
+    /// Page loads that read a different length than the stored file size
+    /// promised. Only counted with `SqliteVfs::with_size_checks`.
+    pub size_mismatches: AtomicU64,
+}
+
+/// When data written through a `SqliteVfs` is made durable
+///
+/// Closing a file commits its data to the database's write-ahead log, but
+/// the log is only synced to disk by `fsync`/`fdatasync` and checkpoints.
+/// An application crash loses nothing that was closed, since the log is
+/// recovered on the next open; a power loss or kernel crash can lose
+/// everything written since the last sync. The modes trade sync cost per
+/// close against that window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Durability {
+    /// Never sync on close. Fastest; a power loss can lose any data not
+    /// explicitly synced.
+    #[default]
+    None,
+    /// Sync on every close of a file that was written. A file closed before
+    /// a power loss survives it, at the cost of a disk sync per close.
+    SyncOnClose,
+    /// Sync on the close of a written file at most once per interval. Bounds
+    /// the syncs a close-heavy workload makes; a power loss can lose files
+    /// closed since the last sync.
+    SyncPeriodic(Duration),
+}
+
+/// Durability mode of a `SqliteVfs`, shared with the files opened through it
+#[derive(Default)]
+struct ClosePolicy {
+    durability: Durability,
+    /// When a close last synced, for `SyncPeriodic`
+    last_sync: Mutex<Option<Instant>>,
+}
+
+impl ClosePolicy {
+    fn new(durability: Durability) -> Self {
+        Self {
+            durability,
+            last_sync: Mutex::new(None),
+        }
+    }
+
+    /// Whether closing a written file should sync the database now
+    fn sync_due(&self) -> bool {
+        match self.durability {
+            Durability::None => false,
+            Durability::SyncOnClose => true,
+            Durability::SyncPeriodic(interval) => {
+                let mut last_sync = self.last_sync.lock().unwrap();
+                if last_sync.is_some_and(|at| at.elapsed() < interval) {
+                    return false;
+                }
+                *last_sync = Some(Instant::now());
+                true
+            }
+        }
+    }
+}
+
+/// Dirty data buffered by the files opened through a `SqliteVfs`
+///
+/// Writes only reach the database when a file is flushed, so each open file
+/// holds its modified pages in memory. Once their total passes the high-water
+/// mark, the file with the most dirty data is written back.
+struct DirtyTracker {
+    /// Dirty bytes across all open files, counted in whole pages
+    bytes: AtomicUsize,
+    /// Level of `bytes` above which writes force a flush
+    high_water: usize,
+    /// Files that may hold dirty data; dropped files are pruned on open
+    files: Mutex<Vec<Weak<SqliteFileOps>>>,
+}
+
+impl DirtyTracker {
+    fn new(high_water: usize) -> Self {
+        Self {
+            bytes: AtomicUsize::new(0),
+            high_water,
+            files: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Start tracking a newly opened file
+    fn register(&self, file: &Arc<SqliteFileOps>) {
+        let mut files = self.files.lock().unwrap();
+        files.retain(|file| file.strong_count() > 0);
+        files.push(Arc::downgrade(file));
+    }
+
+    /// The open file holding the most dirty data, if any holds some
+    fn largest(&self) -> Option<Arc<SqliteFileOps>> {
+        let files: Vec<_> = self
+            .files
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect();
+        files
+            .into_iter()
+            .map(|file| (file.dirty_bytes(), file))
+            .filter(|(bytes, _)| *bytes > 0)
+            .max_by_key(|(bytes, _)| *bytes)
+            .map(|(_, file)| file)
+    }
+}
+
+/// A handle open through a `SqliteVfs`, as listed by `open_handles()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenHandleInfo {
+    /// Path the handle was opened at; for `O_TMPFILE`, its directory
+    pub path: PathBuf,
+    /// Inode number, or 0 for a new file not yet written back
+    pub ino: i64,
+    /// Flags passed to open
+    pub flags: i32,
+}
+
+/// Handles open through a `SqliteVfs`, for debugging
+#[derive(Default)]
+struct HandleTable {
+    next_id: AtomicU64,
+    handles: Mutex<HashMap<u64, OpenHandleInfo>>,
+}
+
+/// Slot in a `SqliteVfs`'s open file count, released when the handle is dropped
+struct OpenHandle {
+    count: Arc<AtomicUsize>,
+    table: Arc<HandleTable>,
+    id: u64,
+}
+
+impl OpenHandle {
+    /// Record what the handle refers to, for `SqliteVfs::open_handles()`
+    fn describe(self, path: PathBuf, ino: i64, flags: i32) -> Self {
+        let info = OpenHandleInfo { path, ino, flags };
+        self.table.handles.lock().unwrap().insert(self.id, info);
+        self
+    }
+}
+
+impl Drop for OpenHandle {
+    fn drop(&mut self) {
+        self.table.handles.lock().unwrap().remove(&self.id);
+        self.count.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A directory resolved once by `SqliteVfs::pin_dir`
+///
+/// Relative operations through `stat_at()` and `open_at()` start from the
+/// pinned inode instead of walking the directory's path from the root again,
+/// which makes it cheap to use as a sandboxed command's working directory.
+/// Lookups keep following the pinned inode if the directory is renamed, but
+/// files created through `open_at()` are named by the path it was pinned at.
+#[derive(Debug, Clone)]
+pub struct PinnedDir {
+    ino: i64,
+    /// Path relative to the mount point at the time the directory was pinned
+    path: String,
+}
+
+impl PinnedDir {
+    /// Get the inode number of the pinned directory
+    pub fn ino(&self) -> i64 {
+        self.ino
+    }
+
+    /// Path of `relative` below the pinned directory, for creating new files
+    fn join(&self, relative: &str) -> String {
+        match (self.path.as_str(), relative) {
+            (path, "") => path.to_string(),
+            ("/", relative) => format!("/{}", relative),
+            (path, relative) => format!("{}/{}", path, relative),
+        }
+    }
+}
+
 /// A SQLite-backed virtual filesystem using the AgentFS SDK
 ///
 /// This implements a full POSIX-like filesystem stored in a SQLite database,
@@ -16,8 +236,24 @@ const ROOT_INO: i64 = 1;
 pub struct SqliteVfs {
     /// The filesystem from the SDK
     fs: Arc<dyn FileSystem>,
+    /// Device ID reported as `st_dev` for every file in the filesystem
+    dev: u64,
     /// The virtual path as seen by the sandboxed process
     mount_point: PathBuf,
+    /// Page cache counters for files opened through this VFS
+    metrics: Arc<CacheMetrics>,
+    /// Number of file and directory handles currently open
+    open_files: Arc<AtomicUsize>,
+    /// Limit on `open_files`; opening past it fails with EMFILE
+    max_open_files: usize,
+    /// What each open handle refers to
+    handles: Arc<HandleTable>,
+    /// Dirty data buffered across open files
+    dirty: Arc<DirtyTracker>,
+    /// When closing a file syncs the database
+    close_policy: Arc<ClosePolicy>,
+    /// Cross-check the bytes loaded from the database against the file size
+    check_sizes: bool,
 }
 
 impl SqliteVfs {
@@ -27,26 +263,215 @@ impl SqliteVfs {
     /// * `db_path` - Path to the SQLite database file
     /// * `mount_point` - The virtual path seen by the guest (e.g., "/agent")
     pub async fn new(db_path: impl AsRef<Path>, mount_point: PathBuf) -> VfsResult<Self> {
+        Self::open_db(db_path.as_ref(), mount_point, None).await
+    }
+
+    /// Create a SQLite VFS whose file reads go through a shared chunk cache
+    ///
+    /// VFS instances on the same database can share one `ChunkCache`, so
+    /// chunks read through one are served from memory to the others. See
+    /// `ChunkCache` for when sharing is safe.
+    pub async fn new_with_chunk_cache(
+        db_path: impl AsRef<Path>,
+        mount_point: PathBuf,
+        cache: Arc<ChunkCache>,
+    ) -> VfsResult<Self> {
+        Self::open_db(db_path.as_ref(), mount_point, Some(cache)).await
+    }
+
+    async fn open_db(
+        db_path: &Path,
+        mount_point: PathBuf,
+        cache: Option<Arc<ChunkCache>>,
+    ) -> VfsResult<Self> {
         let db_path_str = db_path
-            .as_ref()
             .to_str()
             .ok_or_else(|| VfsError::InvalidInput("Invalid database path".to_string()))?;
 
-        let fs = AgentFS::new(db_path_str)
+        let mut fs = AgentFS::new(db_path_str)
             .await
-            .map_err(|e| VfsError::Other(format!("Failed to create filesystem: {}", e)))?;
+            .map_err(|e| backend_error("Failed to create filesystem", e))?;
+        if let Some(cache) = cache {
+            fs = fs.with_chunk_cache(cache);
+        }
 
         Ok(Self {
             fs: Arc::new(fs) as Arc<dyn FileSystem>,
+            dev: device_id(db_path),
             mount_point,
+            metrics: Arc::new(CacheMetrics::default()),
+            open_files: Arc::new(AtomicUsize::new(0)),
+            max_open_files: DEFAULT_MAX_OPEN_FILES,
+            handles: Arc::new(HandleTable::default()),
+            dirty: Arc::new(DirtyTracker::new(DEFAULT_DIRTY_HIGH_WATER)),
+            close_policy: Arc::default(),
+            check_sizes: cfg!(debug_assertions),
         })
     }
 
+    /// Set the maximum number of files and directories open at once
+    ///
+    /// Every open handle buffers file contents, so this bounds the memory a
+    /// runaway guest can pin. Opens beyond the limit fail with EMFILE.
+    pub fn with_max_open_files(mut self, max_open_files: usize) -> Self {
+        self.max_open_files = max_open_files;
+        self
+    }
+
+    /// Set the dirty data buffered across open files before write-back is forced
+    ///
+    /// Writes stay in memory until their file is flushed. When a write takes
+    /// the total past `high_water` bytes, the file holding the most dirty data
+    /// is flushed to the database, bounding memory use under heavy writing.
+    /// Call before opening any files.
+    pub fn with_dirty_high_water(mut self, high_water: usize) -> Self {
+        self.dirty = Arc::new(DirtyTracker::new(high_water));
+        self
+    }
+
+    /// Set when data written through the VFS is synced to disk
+    ///
+    /// See `Durability` for what each mode protects against. Call before
+    /// opening any files.
+    pub fn with_durability(mut self, durability: Durability) -> Self {
+        self.close_policy = Arc::new(ClosePolicy::new(durability));
+        self
+    }
+
+    /// Check that page loads read as many bytes as the file size promised
+    ///
+    /// A file's size is looked up when it is opened and its contents are
+    /// loaded later, page by page. If the stored data doesn't match the size,
+    /// reads silently return zeros or short data; with checks on, every such
+    /// load prints a warning and counts in `CacheMetrics::size_mismatches`.
+    /// On by default in debug builds.
+    pub fn with_size_checks(mut self, enabled: bool) -> Self {
+        self.check_sizes = enabled;
+        self
+    }
+
+    /// Get the dirty bytes buffered across open files, in whole pages
+    pub fn dirty_bytes(&self) -> usize {
+        self.dirty.bytes.load(Ordering::SeqCst)
+    }
+
+    /// Get the number of file and directory handles currently open
+    pub fn open_file_count(&self) -> usize {
+        self.open_files.load(Ordering::SeqCst)
+    }
+
+    /// List the handles currently open, ordered by path
+    ///
+    /// Meant for debugging leaked handles and unmounts failing with EBUSY.
+    pub fn open_handles(&self) -> Vec<OpenHandleInfo> {
+        let table = self.handles.handles.lock().unwrap();
+        let mut handles: Vec<_> = table.values().cloned().collect();
+        handles.sort_by(|a, b| a.path.cmp(&b.path).then(a.ino.cmp(&b.ino)));
+        handles
+    }
+
     /// Get the mount point path
     pub fn mount_point(&self) -> &Path {
         &self.mount_point
     }
 
+    /// Get the page cache counters
+    pub fn cache_metrics(&self) -> &CacheMetrics {
+        &self.metrics
+    }
+
+    /// Resolve a directory once so later relative operations can skip its path
+    pub async fn pin_dir(&self, path: &Path) -> VfsResult<PinnedDir> {
+        let relative_path = self.translate_to_relative(path)?;
+        let ino = self.resolve_path(&relative_path).await?;
+        let stats = self
+            .fs
+            .getattr(ino)
+            .await
+            .map_err(|e| backend_error("Failed to getattr", e))?
+            .ok_or(VfsError::NotFound)?;
+        if !stats.is_directory() {
+            return Err(VfsError::InvalidInput("Not a directory".to_string()));
+        }
+
+        Ok(PinnedDir {
+            ino,
+            path: relative_path,
+        })
+    }
+
+    /// Stat a path relative to a pinned directory
+    pub async fn stat_at(&self, dir: &PinnedDir, path: &Path) -> VfsResult<libc::stat> {
+        let relative_path = Self::pinned_relative(path)?;
+
+        let ino = self.resolve_from(dir.ino, &relative_path).await?;
+        let stats = self
+            .fs
+            .getattr(ino)
+            .await
+            .map_err(|e| backend_error("Failed to getattr", e))?
+            .ok_or(VfsError::NotFound)?;
+
+        Ok(stats_to_stat(&stats, self.dev))
+    }
+
+    /// Open a path relative to a pinned directory
+    pub async fn open_at(
+        &self,
+        dir: &PinnedDir,
+        path: &Path,
+        flags: i32,
+        mode: u32,
+    ) -> VfsResult<BoxedFileOps> {
+        let relative_path = Self::pinned_relative(path)?;
+        let handle = self.acquire_handle()?;
+
+        if flags & libc::O_TMPFILE == libc::O_TMPFILE {
+            return self
+                .open_tmpfile(dir.join(&relative_path), flags, mode, handle)
+                .await;
+        }
+
+        let stats_result = if relative_path.is_empty() {
+            self.fs.getattr(dir.ino).await
+        } else {
+            let (parent_path, name) = Self::split_path(&relative_path)?;
+            let parent_ino = self.resolve_dir_from(dir.ino, &parent_path).await?;
+            self.fs.lookup(parent_ino, &name).await
+        };
+
+        let stats = stats_result.map_err(|e| backend_error("Failed to stat", e))?;
+        self.check_inode_flags(stats.as_ref(), flags).await?;
+
+        self.open_entry(dir.join(&relative_path), stats, flags, handle)
+    }
+
+    /// Box an opened file, tracking its dirty data
+    fn track(&self, file: SqliteFileOps) -> BoxedFileOps {
+        let file = Arc::new(file);
+        self.dirty.register(&file);
+        file
+    }
+
+    /// Reserve a slot for a new open handle, failing if the limit is reached
+    fn acquire_handle(&self) -> VfsResult<OpenHandle> {
+        self.open_files
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                (count < self.max_open_files).then_some(count + 1)
+            })
+            .map_err(|_| VfsError::TooManyOpenFiles)?;
+        Ok(OpenHandle {
+            count: self.open_files.clone(),
+            table: self.handles.clone(),
+            id: self.handles.next_id.fetch_add(1, Ordering::Relaxed),
+        })
+    }
+
+    /// Sandbox path of `relative_path`, for listing open handles
+    fn sandbox_path(&self, relative_path: &str) -> PathBuf {
+        self.mount_point.join(relative_path.trim_start_matches('/'))
+    }
+
     /// Translate a sandbox path to a relative path for the SDK
     fn translate_to_relative(&self, path: &Path) -> VfsResult<String> {
         let path_str = path
@@ -76,17 +501,116 @@ impl SqliteVfs {
             return Ok(ROOT_INO);
         }
 
-        let mut current_ino = ROOT_INO;
-        for component in path.split('/').filter(|s| !s.is_empty()) {
-            let stats = self.fs.lookup(current_ino, component).await
-                .map_err(|e| VfsError::Other(format!("Failed to lookup: {}", e)))?
+        self.resolve_from(ROOT_INO, path).await
+    }
+
+    /// Resolve a path that must name a directory, such as the parent of an
+    /// entry being looked up or created
+    async fn resolve_dir(&self, path: &str) -> VfsResult<i64> {
+        if path == "/" {
+            return Ok(ROOT_INO);
+        }
+
+        self.walk(ROOT_INO, path, true).await
+    }
+
+    /// Resolve a path to an inode by walking from the directory `start`
+    async fn resolve_from(&self, start: i64, path: &str) -> VfsResult<i64> {
+        self.walk(start, path, false).await
+    }
+
+    /// Resolve a path that must name a directory, walking from `start`
+    async fn resolve_dir_from(&self, start: i64, path: &str) -> VfsResult<i64> {
+        self.walk(start, path, true).await
+    }
+
+    /// Walk `path` from the directory `start` one component at a time.
+    ///
+    /// Fails with `VfsError::NotADirectory` if a component other than the
+    /// last one, or the last one too when `want_dir` is set, names a
+    /// regular file or other non-directory.
+    async fn walk(&self, start: i64, path: &str, want_dir: bool) -> VfsResult<i64> {
+        let mut current_ino = start;
+        let mut components = path.split('/').filter(|s| !s.is_empty()).peekable();
+        while let Some(component) = components.next() {
+            let stats = self
+                .fs
+                .lookup(current_ino, component)
+                .await
+                .map_err(|e| backend_error("Failed to lookup", e))?
                 .ok_or(VfsError::NotFound)?;
+            // Symlinks are not followed here, so only they may stand in
+            // for a directory without being one
+            let must_be_dir = want_dir || components.peek().is_some();
+            if must_be_dir && !stats.is_directory() && !stats.is_symlink() {
+                return Err(VfsError::NotADirectory);
+            }
             current_ino = stats.ino;
         }
 
         Ok(current_ino)
     }
 
+    /// Resolve a path to an inode, following symlinks in its final component
+    async fn resolve_path_following(&self, path: &str) -> VfsResult<i64> {
+        let mut path = path.to_string();
+        for _ in 0..=MAX_SYMLINK_FOLLOWS {
+            let ino = self.resolve_path(&path).await?;
+            let stats = self
+                .fs
+                .getattr(ino)
+                .await
+                .map_err(|e| backend_error("Failed to getattr", e))?
+                .ok_or(VfsError::NotFound)?;
+            if !stats.is_symlink() {
+                return Ok(ino);
+            }
+
+            let target = self
+                .fs
+                .readlink(ino)
+                .await
+                .map_err(|e| backend_error("Failed to read symlink", e))?
+                .ok_or(VfsError::NotFound)?;
+            path = if target.starts_with('/') {
+                self.translate_to_relative(Path::new(&target))?
+            } else {
+                let (parent, _) = Self::split_path(&path)?;
+                format!("{}/{}", parent.trim_end_matches('/'), target)
+            };
+        }
+
+        Err(VfsError::IoError(std::io::Error::from_raw_os_error(
+            libc::ELOOP,
+        )))
+    }
+
+    /// Normalize a path for use relative to a pinned directory
+    ///
+    /// `.` components are dropped. Absolute paths and `..` components are
+    /// rejected, since they leave the pinned directory; callers resolve those
+    /// through the regular path-based operations.
+    fn pinned_relative(path: &Path) -> VfsResult<String> {
+        let path_str = path
+            .to_str()
+            .ok_or_else(|| VfsError::InvalidInput("Invalid path".to_string()))?;
+        if path_str.starts_with('/') {
+            return Err(VfsError::InvalidInput("Path is not relative".to_string()));
+        }
+
+        let mut components = Vec::new();
+        for component in path_str.split('/').filter(|s| !s.is_empty() && *s != ".") {
+            if component == ".." {
+                return Err(VfsError::InvalidInput(
+                    "Path leaves the pinned directory".to_string(),
+                ));
+            }
+            components.push(component);
+        }
+
+        Ok(components.join("/"))
+    }
+
     /// Resolve a path to (parent_ino, name)
     fn split_path(path: &str) -> VfsResult<(String, String)> {
         if path == "/" {
@@ -102,95 +626,141 @@ impl SqliteVfs {
             Ok(("/".to_string(), path.to_string()))
         }
     }
-}
-
-#[async_trait::async_trait]
-impl Vfs for SqliteVfs {
-    fn translate_path(&self, path: &Path) -> VfsResult<PathBuf> {
-        // For virtual VFS, we just validate the path is under our mount point
-        let path_str = path
-            .to_str()
-            .ok_or_else(|| VfsError::InvalidInput("Invalid path".to_string()))?;
 
-        let mount_str = self
-            .mount_point
-            .to_str()
-            .ok_or_else(|| VfsError::InvalidInput("Invalid mount point".to_string()))?;
+    /// Open an unnamed file in the directory at `dir_path` (O_TMPFILE)
+    async fn open_tmpfile(
+        &self,
+        dir_path: String,
+        flags: i32,
+        mode: u32,
+        handle: OpenHandle,
+    ) -> VfsResult<BoxedFileOps> {
+        // O_TMPFILE requires write access
+        if flags & libc::O_ACCMODE == libc::O_RDONLY {
+            return Err(VfsError::InvalidInput(
+                "O_TMPFILE requires O_WRONLY or O_RDWR".to_string(),
+            ));
+        }
 
-        if path_str.starts_with(mount_str) {
-            Ok(path.to_path_buf())
-        } else {
-            Err(VfsError::NotFound)
+        let dir_ino = self.resolve_path(&dir_path).await?;
+        let dir_stats = self
+            .fs
+            .getattr(dir_ino)
+            .await
+            .map_err(|e| backend_error("Failed to getattr", e))?
+            .ok_or(VfsError::NotFound)?;
+        if !dir_stats.is_directory() {
+            return Err(VfsError::InvalidInput("Not a directory".to_string()));
         }
-    }
 
-    fn is_virtual(&self) -> bool {
-        true
+        let (stats, _file) = self
+            .fs
+            .create_tmpfile(mode & 0o7777, 0, 0)
+            .await
+            .map_err(|e| backend_error("Failed to create file", e))?;
+
+        let handle = handle.describe(self.sandbox_path(&dir_path), stats.ino, flags);
+        Ok(self.track(SqliteFileOps {
+            fs: self.fs.clone(),
+            dev: self.dev,
+            ino: stats.ino,
+            path: dir_path,
+            state: Mutex::new(FileState::new(0, flags, false)),
+            metrics: self.metrics.clone(),
+            dirty: self.dirty.clone(),
+            close_policy: self.close_policy.clone(),
+            check_sizes: self.check_sizes,
+            unsynced: AtomicBool::new(false),
+            tmpfile: true,
+            created_ino: tokio::sync::OnceCell::new(),
+            _handle: handle,
+        }))
     }
 
-    async fn open(&self, path: &Path, flags: i32, _mode: u32) -> VfsResult<BoxedFileOps> {
-        let relative_path = self.translate_to_relative(path)?;
-
-        // Try to resolve the path to get stats
-        let stats_result = if relative_path == "/" {
-            self.fs.getattr(ROOT_INO).await
-        } else {
-            let (parent_path, name) = Self::split_path(&relative_path)?;
-            let parent_ino = self.resolve_path(&parent_path).await?;
-            self.fs.lookup(parent_ino, &name).await
-        };
-
-        let stats = stats_result
-            .map_err(|e| VfsError::Other(format!("Failed to stat: {}", e)))?;
+    /// Open the entry at `relative_path` given its looked-up stats
+    ///
+    /// A missing entry is opened as a new file when `O_CREAT` is set; the file
+    /// itself is created on first flush.
+    fn open_entry(
+        &self,
+        relative_path: String,
+        stats: Option<Stats>,
+        flags: i32,
+        handle: OpenHandle,
+    ) -> VfsResult<BoxedFileOps> {
+        if flags & libc::O_DIRECTORY != 0 && stats.as_ref().is_some_and(|s| !s.is_directory()) {
+            return Err(VfsError::NotADirectory);
+        }
+        let ino = stats.as_ref().map_or(0, |s| s.ino);
+        let handle = handle.describe(self.sandbox_path(&relative_path), ino, flags);
+
+        // O_PATH only names the entry; O_CREAT and O_TRUNC are ignored
+        if flags & libc::O_PATH != 0 {
+            let stats = stats.ok_or(VfsError::NotFound)?;
+            return Ok(Arc::new(SqlitePathOps {
+                fs: self.fs.clone(),
+                dev: self.dev,
+                ino: stats.ino,
+                flags: Mutex::new(flags),
+                _handle: handle,
+            }));
+        }
 
         match stats {
             Some(stats) => {
                 if stats.is_directory() {
                     Ok(Arc::new(SqliteDirectoryOps {
                         fs: self.fs.clone(),
+                        dev: self.dev,
                         ino: stats.ino,
                         path: relative_path,
                         flags: Mutex::new(flags),
                         entries: Arc::new(Mutex::new(None)),
                         position: Arc::new(Mutex::new(0)),
+                        _handle: handle,
                     }))
                 } else {
-                    // If O_TRUNC is set, skip reading the file and use empty data
-                    let data = if flags & libc::O_TRUNC != 0 {
-                        Vec::new()
-                    } else {
-                        // Read file content using open + pread
-                        let file = self.fs.open(stats.ino, libc::O_RDONLY).await
-                            .map_err(|e| VfsError::Other(format!("Failed to open file: {}", e)))?;
-                        file.pread(0, stats.size as u64).await
-                            .map_err(|e| VfsError::Other(format!("Failed to read file: {}", e)))?
-                    };
-                    Ok(Arc::new(SqliteFileOps {
+                    // If O_TRUNC is set, start from empty data. Otherwise the
+                    // contents are loaded page by page as they are accessed.
+                    let truncate = flags & libc::O_TRUNC != 0;
+                    let len = if truncate { 0 } else { stats.size as usize };
+                    Ok(self.track(SqliteFileOps {
                         fs: self.fs.clone(),
+                        dev: self.dev,
                         ino: stats.ino,
                         path: relative_path,
-                        data: Arc::new(Mutex::new(data)),
-                        offset: Arc::new(Mutex::new(0)),
-                        flags: Mutex::new(flags),
-                        dirty: Arc::new(Mutex::new(flags & libc::O_TRUNC != 0)),
+                        state: Mutex::new(FileState::new(len, flags, truncate)),
+                        metrics: self.metrics.clone(),
+                        dirty: self.dirty.clone(),
+                        close_policy: self.close_policy.clone(),
+                        check_sizes: self.check_sizes,
+                        unsynced: AtomicBool::new(false),
+                        tmpfile: false,
+                        created_ino: tokio::sync::OnceCell::new(),
+                        _handle: handle,
                     }))
                 }
             }
             None => {
                 // File doesn't exist - check if O_CREAT is set
                 if flags & libc::O_CREAT != 0 {
-                    let data = Vec::new();
-
                     // We don't have an inode yet - use 0 as placeholder
                     // The actual file will be created on fsync/close
-                    Ok(Arc::new(SqliteFileOps {
+                    Ok(self.track(SqliteFileOps {
                         fs: self.fs.clone(),
+                        dev: self.dev,
                         ino: 0, // Will be assigned when created
                         path: relative_path,
-                        data: Arc::new(Mutex::new(data)),
-                        offset: Arc::new(Mutex::new(0)),
-                        flags: Mutex::new(flags),
-                        dirty: Arc::new(Mutex::new(true)), // Mark as dirty so it gets written on close
+                        // Mark as dirty so it gets written on close
+                        state: Mutex::new(FileState::new(0, flags, true)),
+                        metrics: self.metrics.clone(),
+                        dirty: self.dirty.clone(),
+                        close_policy: self.close_policy.clone(),
+                        check_sizes: self.check_sizes,
+                        unsynced: AtomicBool::new(false),
+                        tmpfile: false,
+                        created_ino: tokio::sync::OnceCell::new(),
+                        _handle: handle,
                     }))
                 } else {
                     // File doesn't exist and O_CREAT not set
@@ -200,76 +770,146 @@ impl Vfs for SqliteVfs {
         }
     }
 
-    async fn stat(&self, path: &Path) -> VfsResult<libc::stat> {
-        let relative_path = self.translate_to_relative(path)?;
-
-        let ino = self.resolve_path(&relative_path).await?;
-        let stats = self.fs.getattr(ino).await
-            .map_err(|e| VfsError::Other(format!("Failed to getattr: {}", e)))?
-            .ok_or(VfsError::NotFound)?;
+    /// Refuse to open a file for writing if its inode flags forbid it
+    ///
+    /// Like the kernel, immutable files can't be opened for writing at all,
+    /// and append-only files only with `O_APPEND` and without `O_TRUNC`.
+    async fn check_inode_flags(&self, stats: Option<&Stats>, flags: i32) -> VfsResult<()> {
+        let Some(stats) = stats else {
+            return Ok(());
+        };
+        let writing = flags & libc::O_ACCMODE != libc::O_RDONLY || flags & libc::O_TRUNC != 0;
+        if !writing || flags & libc::O_PATH != 0 {
+            return Ok(());
+        }
 
-        // Use MaybeUninit to construct libc::stat safely
-        let mut stat: std::mem::MaybeUninit<libc::stat> = std::mem::MaybeUninit::zeroed();
-        unsafe {
-            let stat_ptr = stat.as_mut_ptr();
-            (*stat_ptr).st_dev = 0;
-            (*stat_ptr).st_ino = stats.ino as u64;
-            (*stat_ptr).st_nlink = stats.nlink.into();
-            (*stat_ptr).st_mode = stats.mode;
-            (*stat_ptr).st_uid = stats.uid;
-            (*stat_ptr).st_gid = stats.gid;
-            (*stat_ptr).st_rdev = 0;
-            (*stat_ptr).st_size = stats.size;
-            (*stat_ptr).st_blksize = 4096;
-            (*stat_ptr).st_blocks = (stats.size + 4095) / 4096;
-            (*stat_ptr).st_atime = stats.atime;
-            (*stat_ptr).st_atime_nsec = 0;
-            (*stat_ptr).st_mtime = stats.mtime;
-            (*stat_ptr).st_mtime_nsec = 0;
-            (*stat_ptr).st_ctime = stats.ctime;
-            (*stat_ptr).st_ctime_nsec = 0;
-            Ok(stat.assume_init())
+        let inode_flags = get_inode_flags(self.fs.as_ref(), stats.ino).await?;
+        let append_only = flags & libc::O_APPEND != 0 && flags & libc::O_TRUNC == 0;
+        if inode_flags & FS_IMMUTABLE_FL != 0 || (inode_flags & FS_APPEND_FL != 0 && !append_only) {
+            return Err(VfsError::NotPermitted);
         }
+        Ok(())
     }
 
-    async fn lstat(&self, path: &Path) -> VfsResult<libc::stat> {
-        let relative_path = self.translate_to_relative(path)?;
+    /// Create a directory entry at `newpath_rel` for an existing inode
+    async fn link_inode(&self, ino: i64, newpath_rel: &str) -> VfsResult<()> {
+        let (new_parent_path, new_name) = Self::split_path(newpath_rel)?;
+        let new_parent_ino = self.resolve_dir(&new_parent_path).await?;
 
-        // For lstat, we use lookup which doesn't follow symlinks
-        let stats = if relative_path == "/" {
-            self.fs.getattr(ROOT_INO).await
-                .map_err(|e| VfsError::Other(format!("Failed to getattr: {}", e)))?
-                .ok_or(VfsError::NotFound)?
-        } else {
-            let (parent_path, name) = Self::split_path(&relative_path)?;
-            let parent_ino = self.resolve_path(&parent_path).await?;
-            self.fs.lookup(parent_ino, &name).await
-                .map_err(|e| VfsError::Other(format!("Failed to lookup: {}", e)))?
-                .ok_or(VfsError::NotFound)?
-        };
+        self.fs
+            .link(ino, new_parent_ino, &new_name)
+            .await
+            .map_err(|e| match e {
+                // Directories cannot be hard linked
+                agentfs_sdk::error::Error::Fs(FsError::IsADirectory) => VfsError::PermissionDenied,
+                e => backend_error("Failed to create hard link", e),
+            })?;
 
-        // Use MaybeUninit to construct libc::stat safely
-        let mut stat: std::mem::MaybeUninit<libc::stat> = std::mem::MaybeUninit::zeroed();
-        unsafe {
-            let stat_ptr = stat.as_mut_ptr();
-            (*stat_ptr).st_dev = 0;
-            (*stat_ptr).st_ino = stats.ino as u64;
-            (*stat_ptr).st_nlink = stats.nlink.into();
-            (*stat_ptr).st_mode = stats.mode;
-            (*stat_ptr).st_uid = stats.uid;
-            (*stat_ptr).st_gid = stats.gid;
-            (*stat_ptr).st_rdev = 0;
-            (*stat_ptr).st_size = stats.size;
-            (*stat_ptr).st_blksize = 4096;
-            (*stat_ptr).st_blocks = (stats.size + 4095) / 4096;
-            (*stat_ptr).st_atime = stats.atime;
-            (*stat_ptr).st_atime_nsec = 0;
-            (*stat_ptr).st_mtime = stats.mtime;
-            (*stat_ptr).st_mtime_nsec = 0;
-            (*stat_ptr).st_ctime = stats.ctime;
-            (*stat_ptr).st_ctime_nsec = 0;
-            Ok(stat.assume_init())
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Vfs for SqliteVfs {
+    fn translate_path(&self, path: &Path) -> VfsResult<PathBuf> {
+        // For virtual VFS, we just validate the path is under our mount point
+        let path_str = path
+            .to_str()
+            .ok_or_else(|| VfsError::InvalidInput("Invalid path".to_string()))?;
+
+        let mount_str = self
+            .mount_point
+            .to_str()
+            .ok_or_else(|| VfsError::InvalidInput("Invalid mount point".to_string()))?;
+
+        if path_str.starts_with(mount_str) {
+            Ok(path.to_path_buf())
+        } else {
+            Err(VfsError::NotFound)
+        }
+    }
+
+    fn is_virtual(&self) -> bool {
+        true
+    }
+
+    async fn open(&self, path: &Path, flags: i32, mode: u32) -> VfsResult<BoxedFileOps> {
+        let relative_path = self.translate_to_relative(path)?;
+        let handle = self.acquire_handle()?;
+
+        // O_TMPFILE creates an unnamed file in the given directory
+        if flags & libc::O_TMPFILE == libc::O_TMPFILE {
+            return self.open_tmpfile(relative_path, flags, mode, handle).await;
+        }
+
+        // Try to resolve the path to get stats
+        let stats_result = if relative_path == "/" {
+            self.fs.getattr(ROOT_INO).await
+        } else {
+            let (parent_path, name) = Self::split_path(&relative_path)?;
+            let parent_ino = self.resolve_dir(&parent_path).await?;
+            self.fs.lookup(parent_ino, &name).await
+        };
+
+        let stats = stats_result.map_err(|e| backend_error("Failed to stat", e))?;
+        self.check_inode_flags(stats.as_ref(), flags).await?;
+
+        self.open_entry(relative_path, stats, flags, handle)
+    }
+
+    async fn stat(&self, path: &Path) -> VfsResult<libc::stat> {
+        let relative_path = self.translate_to_relative(path)?;
+
+        let ino = self.resolve_path(&relative_path).await?;
+        let stats = self
+            .fs
+            .getattr(ino)
+            .await
+            .map_err(|e| backend_error("Failed to getattr", e))?
+            .ok_or(VfsError::NotFound)?;
+
+        Ok(stats_to_stat(&stats, self.dev))
+    }
+
+    async fn lstat(&self, path: &Path) -> VfsResult<libc::stat> {
+        let relative_path = self.translate_to_relative(path)?;
+
+        // For lstat, we use lookup which doesn't follow symlinks
+        let stats = if relative_path == "/" {
+            self.fs
+                .getattr(ROOT_INO)
+                .await
+                .map_err(|e| backend_error("Failed to getattr", e))?
+                .ok_or(VfsError::NotFound)?
+        } else {
+            let (parent_path, name) = Self::split_path(&relative_path)?;
+            let parent_ino = self.resolve_dir(&parent_path).await?;
+            self.fs
+                .lookup(parent_ino, &name)
+                .await
+                .map_err(|e| backend_error("Failed to lookup", e))?
+                .ok_or(VfsError::NotFound)?
+        };
+
+        Ok(stats_to_stat(&stats, self.dev))
+    }
+
+    async fn exists(&self, path: &Path) -> VfsResult<bool> {
+        let relative_path = self.translate_to_relative(path)?;
+        if relative_path == "/" {
+            return Ok(true);
         }
+
+        let (parent_path, name) = Self::split_path(&relative_path)?;
+        let parent_ino = match self.resolve_dir(&parent_path).await {
+            Ok(ino) => ino,
+            Err(VfsError::NotFound) => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        self.fs
+            .exists(parent_ino, &name)
+            .await
+            .map_err(|e| backend_error("Failed to lookup", e))
     }
 
     async fn symlink(&self, target: &Path, linkpath: &Path) -> VfsResult<()> {
@@ -279,19 +919,12 @@ impl Vfs for SqliteVfs {
             .ok_or_else(|| VfsError::InvalidInput("Invalid target path".to_string()))?;
 
         let (parent_path, name) = Self::split_path(&linkpath_rel)?;
-        let parent_ino = self.resolve_path(&parent_path).await?;
+        let parent_ino = self.resolve_dir(&parent_path).await?;
 
         self.fs
             .symlink(parent_ino, &name, target_str, 0, 0)
             .await
-            .map_err(|e| {
-                let err_msg = e.to_string();
-                if err_msg.contains("already exists") {
-                    VfsError::AlreadyExists
-                } else {
-                    VfsError::Other(format!("Failed to create symlink: {}", e))
-                }
-            })?;
+            .map_err(|e| backend_error("Failed to create symlink", e))?;
 
         Ok(())
     }
@@ -304,243 +937,923 @@ impl Vfs for SqliteVfs {
             .fs
             .readlink(ino)
             .await
-            .map_err(|e| VfsError::Other(format!("Failed to read symlink: {}", e)))?
+            .map_err(|e| backend_error("Failed to read symlink", e))?
             .ok_or(VfsError::NotFound)?;
 
         Ok(PathBuf::from(target))
     }
 
+    async fn set_times(
+        &self,
+        path: &Path,
+        atime: libc::timespec,
+        mtime: libc::timespec,
+        follow_symlinks: bool,
+    ) -> VfsResult<()> {
+        let relative_path = self.translate_to_relative(path)?;
+
+        let ino = if follow_symlinks {
+            self.resolve_path_following(&relative_path).await?
+        } else {
+            self.resolve_path(&relative_path).await?
+        };
+        self.fs
+            .utimens(ino, time_change(&atime), time_change(&mtime))
+            .await
+            .map_err(|e| backend_error("Failed to set times", e))
+    }
+
     async fn link(&self, oldpath: &Path, newpath: &Path) -> VfsResult<()> {
         let oldpath_rel = self.translate_to_relative(oldpath)?;
         let newpath_rel = self.translate_to_relative(newpath)?;
 
         let old_ino = self.resolve_path(&oldpath_rel).await?;
-        let (new_parent_path, new_name) = Self::split_path(&newpath_rel)?;
-        let new_parent_ino = self.resolve_path(&new_parent_path).await?;
-
-        self.fs.link(old_ino, new_parent_ino, &new_name).await.map_err(|e| {
-            let err_msg = e.to_string();
-            if err_msg.contains("does not exist") {
-                VfsError::NotFound
-            } else if err_msg.contains("already exists") {
-                VfsError::AlreadyExists
-            } else if err_msg.contains("directory") {
-                VfsError::PermissionDenied
-            } else {
-                VfsError::Other(format!("Failed to create hard link: {}", e))
-            }
-        })?;
+        self.link_inode(old_ino, &newpath_rel).await
+    }
 
-        Ok(())
+    async fn link_file(&self, file: &BoxedFileOps, newpath: &Path) -> VfsResult<()> {
+        let newpath_rel = self.translate_to_relative(newpath)?;
+
+        // Flush buffered data so the new name sees the file content
+        file.fsync().await?;
+        let ino = file.fstat().await?.st_ino as i64;
+
+        self.link_inode(ino, &newpath_rel).await
     }
 }
 
-/// File operations for SQLite VFS files
-struct SqliteFileOps {
-    fs: Arc<dyn FileSystem>,
-    ino: i64,
-    path: String,
-    data: Arc<Mutex<Vec<u8>>>,
-    offset: Arc<Mutex<i64>>,
-    flags: Mutex<i32>,
-    dirty: Arc<Mutex<bool>>,
+/// Device ID of the filesystem stored in the database at `db_path`
+///
+/// Derived from the database's canonical path, so it is stable across runs,
+/// nonzero, and differs between databases, whose inode numbers overlap.
+/// VFS instances on the same database share the inode space and the ID.
+fn device_id(db_path: &Path) -> u64 {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::fs::canonicalize(db_path).unwrap_or_else(|_| db_path.to_path_buf());
+    // FNV-1a, which unlike the std hasher is fixed across Rust releases
+    let hash = path
+        .as_os_str()
+        .as_bytes()
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325u64, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+    hash.max(1)
 }
 
-impl SqliteFileOps {
-    /// Resolve the path and get the inode (for new files)
-    async fn get_or_create_ino(&self) -> VfsResult<i64> {
-        if self.ino != 0 {
-            return Ok(self.ino);
-        }
-
-        // Need to create the file
-        let (parent_path, name) = SqliteVfs::split_path(&self.path)?;
+/// Convert SDK stats to a `libc::stat`
+fn stats_to_stat(stats: &Stats, dev: u64) -> libc::stat {
+    // Use MaybeUninit to construct libc::stat safely
+    let mut stat: std::mem::MaybeUninit<libc::stat> = std::mem::MaybeUninit::zeroed();
+    unsafe {
+        let stat_ptr = stat.as_mut_ptr();
+        (*stat_ptr).st_dev = dev;
+        (*stat_ptr).st_ino = stats.ino as u64;
+        (*stat_ptr).st_nlink = stats.nlink.into();
+        (*stat_ptr).st_mode = stats.mode;
+        (*stat_ptr).st_uid = stats.uid;
+        (*stat_ptr).st_gid = stats.gid;
+        (*stat_ptr).st_rdev = 0;
+        (*stat_ptr).st_size = stats.size;
+        (*stat_ptr).st_blksize = 4096;
+        (*stat_ptr).st_blocks = (stats.size + 4095) / 4096;
+        (*stat_ptr).st_atime = stats.atime;
+        (*stat_ptr).st_atime_nsec = 0;
+        (*stat_ptr).st_mtime = stats.mtime;
+        (*stat_ptr).st_mtime_nsec = 0;
+        (*stat_ptr).st_ctime = stats.ctime;
+        (*stat_ptr).st_ctime_nsec = 0;
+        stat.assume_init()
+    }
+}
 
-        // Walk to parent
-        let mut parent_ino = ROOT_INO;
-        for component in parent_path.split('/').filter(|s| !s.is_empty()) {
-            let stats = self.fs.lookup(parent_ino, component).await
-                .map_err(|e| VfsError::Other(format!("Failed to lookup: {}", e)))?
-                .ok_or(VfsError::NotFound)?;
-            parent_ino = stats.ino;
+/// Handle the fcntl commands shared by virtual files and directories
+///
+/// F_DUPFD, F_DUPFD_CLOEXEC, F_GETFD and F_SETFD are not handled here: they
+/// concern the descriptor rather than the open file, so the syscall layer
+/// handles them with the FD table.
+fn virtual_fcntl(ops: &dyn FileOps, cmd: i32, arg: i64) -> VfsResult<i64> {
+    match cmd {
+        libc::F_GETFL => Ok(ops.get_flags() as i64),
+        libc::F_SETFL => {
+            ops.set_flags(arg as i32)?;
+            Ok(0)
         }
+        _ => Err(VfsError::Other(format!(
+            "Unsupported fcntl command: {}",
+            cmd
+        ))),
+    }
+}
 
-        // Create the file
-        let (stats, _file) = self.fs.create_file(parent_ino, &name, 0o644, 0, 0).await
-            .map_err(|e| VfsError::Other(format!("Failed to create file: {}", e)))?;
+/// Map an SDK error to a VFS error.
+///
+/// Filesystem errors with a VFS counterpart, such as a missing entry or a
+/// full disk, become that variant. Anything else is kept as the source of
+/// a `VfsError::Backend`.
+fn backend_error(context: &str, e: agentfs_sdk::error::Error) -> VfsError {
+    match e {
+        agentfs_sdk::error::Error::Fs(FsError::NotFound) => VfsError::NotFound,
+        agentfs_sdk::error::Error::Fs(FsError::AlreadyExists) => VfsError::AlreadyExists,
+        agentfs_sdk::error::Error::Fs(FsError::NotADirectory) => VfsError::NotADirectory,
+        agentfs_sdk::error::Error::Fs(FsError::NoSpace) => VfsError::NoSpace,
+        agentfs_sdk::error::Error::Fs(FsError::NotPermitted) => VfsError::NotPermitted,
+        agentfs_sdk::error::Error::Fs(FsError::NoSuchOffset) => VfsError::NoSuchOffset,
+        e => VfsError::backend(context, e),
+    }
+}
 
-        Ok(stats.ino)
+/// Convert a `utimensat` timestamp to an SDK time change
+fn time_change(time: &libc::timespec) -> TimeChange {
+    match time.tv_nsec {
+        libc::UTIME_OMIT => TimeChange::Omit,
+        libc::UTIME_NOW => TimeChange::Now,
+        nsec => TimeChange::Set(time.tv_sec, nsec as u32),
     }
 }
 
-#[async_trait::async_trait]
-impl FileOps for SqliteFileOps {
-    async fn read(&self, buf: &mut [u8]) -> VfsResult<usize> {
-        let data = self.data.lock().unwrap();
-        let mut offset = self.offset.lock().unwrap();
+/// Get the inode flags of `ino` for `FS_IOC_GETFLAGS`
+async fn get_inode_flags(fs: &dyn FileSystem, ino: i64) -> VfsResult<u32> {
+    fs.get_inode_flags(ino)
+        .await
+        .map_err(|e| backend_error("Failed to get inode flags", e))
+}
 
-        let start = *offset as usize;
-        if start >= data.len() {
-            return Ok(0);
+/// Replace the inode flags of `ino` for `FS_IOC_SETFLAGS`
+async fn set_inode_flags(fs: &dyn FileSystem, ino: i64, flags: u32) -> VfsResult<()> {
+    fs.set_inode_flags(ino, flags).await.map_err(|e| match e {
+        agentfs_sdk::error::Error::Fs(FsError::NotSupported) => {
+            VfsError::InvalidInput(format!("Unsupported inode flags: {:#x}", flags))
         }
+        e => backend_error("Failed to set inode flags", e),
+    })
+}
 
-        let end = std::cmp::min(start + buf.len(), data.len());
-        let bytes_read = end - start;
-        buf[..bytes_read].copy_from_slice(&data[start..end]);
-        *offset += bytes_read as i64;
+/// Tracks which pages of a file's buffer hold its contents
+struct PageCache {
+    /// Per-page residency. Pages that aren't resident still have to be
+    /// loaded from the database; pages added by extending the file are
+    /// always resident.
+    resident: Vec<bool>,
+    /// Per-page modification since the last write-back
+    dirty: Vec<bool>,
+    /// Number of set entries in `dirty`
+    dirty_pages: usize,
+    /// Pages to load past a cache miss
+    readahead: usize,
+}
 
-        Ok(bytes_read)
+impl PageCache {
+    /// Track a file of `len` stored bytes, none of them loaded yet
+    fn new(len: usize) -> Self {
+        Self {
+            resident: vec![false; len.div_ceil(PAGE_SIZE)],
+            dirty: vec![false; len.div_ceil(PAGE_SIZE)],
+            dirty_pages: 0,
+            readahead: DEFAULT_READAHEAD_PAGES,
+        }
     }
 
-    async fn write(&self, buf: &[u8]) -> VfsResult<usize> {
-        let mut data = self.data.lock().unwrap();
-        let mut offset = self.offset.lock().unwrap();
-        let flags = *self.flags.lock().unwrap();
-
-        // Handle O_APPEND: always write at the end of the file
-        let start = if flags & libc::O_APPEND != 0 {
-            data.len()
-        } else {
-            *offset as usize
-        };
+    /// Widen the readahead window to at least the size of a `len` byte read
+    ///
+    /// Streaming readers (and the kernel, on their behalf) issue large reads,
+    /// so the request size predicts how much will be read next. The window
+    /// never shrinks here, and stays off after `POSIX_FADV_RANDOM`.
+    fn scale_readahead(&mut self, len: usize) {
+        if self.readahead == 0 {
+            return;
+        }
+        let pages = std::cmp::min(len / PAGE_SIZE, MAX_READAHEAD_PAGES);
+        self.readahead = std::cmp::max(self.readahead, pages);
+    }
 
-        // Extend the buffer if necessary
-        if start + buf.len() > data.len() {
-            data.resize(start + buf.len(), 0);
+    /// Extend tracking to cover `len` bytes
+    fn grow(&mut self, len: usize) {
+        let pages = len.div_ceil(PAGE_SIZE);
+        if pages > self.resident.len() {
+            self.resident.resize(pages, true);
+            self.dirty.resize(pages, false);
         }
+    }
 
-        data[start..start + buf.len()].copy_from_slice(buf);
-        *offset = (start + buf.len()) as i64;
+    /// Track `len` bytes after the stored file was resized, returning how
+    /// many dirty pages were dropped
+    ///
+    /// Unlike `grow`, added pages are not resident: they are holes in the
+    /// database, so they read back as zeros and are never written back.
+    fn resize(&mut self, len: usize) -> usize {
+        let pages = len.div_ceil(PAGE_SIZE);
+        let dropped = self.dirty.iter().skip(pages).filter(|d| **d).count();
+        self.resident.resize(pages, false);
+        self.dirty.resize(pages, false);
+        self.dirty_pages -= dropped;
+        dropped
+    }
 
-        // Mark as dirty since we modified the data
-        *self.dirty.lock().unwrap() = true;
+    /// Mark `pages` as modified, returning how many weren't already
+    fn mark_dirty(&mut self, pages: std::ops::Range<usize>) -> usize {
+        let mut marked = 0;
+        for dirty in &mut self.dirty[pages] {
+            if !*dirty {
+                *dirty = true;
+                marked += 1;
+            }
+        }
+        self.dirty_pages += marked;
+        marked
+    }
 
-        Ok(buf.len())
+    /// Clear the modified pages for a write-back, returning them
+    fn take_dirty(&mut self) -> Vec<bool> {
+        self.dirty_pages = 0;
+        std::mem::replace(&mut self.dirty, vec![false; self.resident.len()])
     }
 
-    async fn seek(&self, offset: i64, whence: i32) -> VfsResult<i64> {
-        let data = self.data.lock().unwrap();
-        let mut current_offset = self.offset.lock().unwrap();
+    /// Mark pages from a failed write-back as modified again, returning how
+    /// many weren't already
+    fn restore_dirty(&mut self, dirty: &[bool]) -> usize {
+        let mut marked = 0;
+        for (page, _) in dirty.iter().enumerate().filter(|(_, d)| **d) {
+            if page < self.dirty.len() && !self.dirty[page] {
+                self.dirty[page] = true;
+                marked += 1;
+            }
+        }
+        self.dirty_pages += marked;
+        marked
+    }
 
-        let new_offset = match whence {
-            libc::SEEK_SET => offset,
-            libc::SEEK_CUR => *current_offset + offset,
-            libc::SEEK_END => data.len() as i64 + offset,
-            _ => return Err(VfsError::Other("Invalid whence".to_string())),
-        };
+    /// Page range spanning the non-resident pages among bytes `start..end`
+    fn missing(&self, start: usize, end: usize) -> Option<(usize, usize)> {
+        let pages = start / PAGE_SIZE..end.div_ceil(PAGE_SIZE).min(self.resident.len());
+        let first = pages.clone().find(|&page| !self.resident[page])?;
+        let last = pages.rev().find(|&page| !self.resident[page])?;
+        Some((first, last + 1))
+    }
 
-        if new_offset < 0 {
-            return Err(VfsError::Other("Invalid offset".to_string()));
+    /// Byte ranges covered by the set entries of `pages` within the first
+    /// `len` bytes
+    fn runs(pages: &[bool], len: usize) -> Vec<(usize, usize)> {
+        let mut runs: Vec<(usize, usize)> = Vec::new();
+        for (page, _) in pages.iter().enumerate().filter(|(_, p)| **p) {
+            let start = page * PAGE_SIZE;
+            if start >= len {
+                break;
+            }
+            let end = std::cmp::min(start + PAGE_SIZE, len);
+            match runs.last_mut() {
+                Some(run) if run.1 == start => run.1 = end,
+                _ => runs.push((start, end)),
+            }
         }
-
-        *current_offset = new_offset;
-        Ok(new_offset)
+        runs
     }
+}
 
-    async fn fstat(&self) -> VfsResult<libc::stat> {
-        // Get the actual file stats from the filesystem
-        let ino = self.get_or_create_ino().await?;
-        let stats = self
-            .fs
-            .getattr(ino)
-            .await
-            .map_err(|e| VfsError::Other(format!("Failed to getattr: {}", e)))?
-            .ok_or(VfsError::NotFound)?;
+/// Why file pages are being loaded
+#[derive(Clone, Copy, PartialEq)]
+enum PageLoad {
+    /// A read touched them; also read ahead past the miss
+    Read,
+    /// A write partially overwrites them
+    Write,
+    /// `POSIX_FADV_WILLNEED` asked for them ahead of access
+    WillNeed,
+}
 
-        let data = self.data.lock().unwrap();
+/// Mutable state of an open SQLite VFS file
+///
+/// The contents are not copied when the file is opened. The size is taken
+/// then, and each page is read from the database the first time it is
+/// accessed, so an open file sees what others wrote to the stored file up
+/// to that point, page by page. Once loaded, a page keeps its contents
+/// until it is dropped by `POSIX_FADV_DONTNEED`; a read that finds its page
+/// dropped loads it again rather than returning stale bytes. Writes through
+/// this file stay in memory until they are written back on flush or close,
+/// and are never replaced by a load. If the stored file shrank since it was
+/// opened, the missing bytes read as zeros up to the size taken at open;
+/// `SqliteVfs::with_size_checks` reports such mismatches.
+struct FileState {
+    /// File contents, loaded lazily one page at a time
+    data: Vec<u8>,
+    offset: i64,
+    flags: i32,
+    /// Modified since the last sync to the database
+    dirty: bool,
+    /// Which pages of `data` are loaded, and the readahead window
+    cache: PageCache,
+    /// Size of the stored file as of opening or the last write-back
+    stored_len: usize,
+}
 
-        // Use MaybeUninit to construct libc::stat safely
-        let mut stat: std::mem::MaybeUninit<libc::stat> = std::mem::MaybeUninit::zeroed();
-        unsafe {
-            let stat_ptr = stat.as_mut_ptr();
-            (*stat_ptr).st_dev = 0;
-            (*stat_ptr).st_ino = stats.ino as u64;
-            (*stat_ptr).st_nlink = stats.nlink.into();
-            (*stat_ptr).st_mode = stats.mode;
-            (*stat_ptr).st_uid = stats.uid;
-            (*stat_ptr).st_gid = stats.gid;
-            (*stat_ptr).st_rdev = 0;
-            (*stat_ptr).st_size = data.len() as i64;
-            (*stat_ptr).st_blksize = 4096;
-            (*stat_ptr).st_blocks = (data.len() as i64 + 4095) / 4096;
-            (*stat_ptr).st_atime = stats.atime;
-            (*stat_ptr).st_atime_nsec = 0;
-            (*stat_ptr).st_mtime = stats.mtime;
-            (*stat_ptr).st_mtime_nsec = 0;
-            (*stat_ptr).st_ctime = stats.ctime;
-            (*stat_ptr).st_ctime_nsec = 0;
-            Ok(stat.assume_init())
+impl FileState {
+    /// State for a file of `len` stored bytes, none of them loaded yet
+    fn new(len: usize, flags: i32, dirty: bool) -> Self {
+        Self {
+            data: vec![0; len],
+            offset: 0,
+            flags,
+            dirty,
+            cache: PageCache::new(len),
+            stored_len: len,
         }
     }
+}
 
-    async fn fsync(&self) -> VfsResult<()> {
-        // For virtual file, sync means write to database
-        let dirty = *self.dirty.lock().unwrap();
-        if !dirty {
-            return Ok(());
+/// File operations for SQLite VFS files
+///
+/// All mutable state sits behind the single `state` lock. It is never held
+/// across an await, so there is no lock ordering between methods to get
+/// wrong.
+struct SqliteFileOps {
+    fs: Arc<dyn FileSystem>,
+    dev: u64,
+    ino: i64,
+    path: String,
+    state: Mutex<FileState>,
+    metrics: Arc<CacheMetrics>,
+    /// Dirty data accounting shared with the VFS
+    dirty: Arc<DirtyTracker>,
+    /// Durability mode shared with the VFS
+    close_policy: Arc<ClosePolicy>,
+    /// Cross-check loaded pages against `FileState::stored_len`
+    check_sizes: bool,
+    /// Data was written to the database since it was last synced
+    unsynced: AtomicBool,
+    /// Opened with O_TMPFILE: the inode is released on close unless it was linked
+    tmpfile: bool,
+    /// Inode created for a file opened with O_CREAT (`ino` is 0 then)
+    created_ino: tokio::sync::OnceCell<i64>,
+    /// Counts this file against the VFS's open file limit
+    _handle: OpenHandle,
+}
+
+impl SqliteFileOps {
+    /// Bytes modified since the last write-back, in whole pages
+    fn dirty_bytes(&self) -> usize {
+        self.state.lock().unwrap().cache.dirty_pages * PAGE_SIZE
+    }
+
+    /// Write back the file with the most dirty data if the VFS holds too much
+    ///
+    /// The flushed file may be this one or any other open file. A failed
+    /// write-back leaves its data dirty, so the error is reported by that
+    /// file's own flush or close rather than by the write that triggered it.
+    async fn relieve_dirty_pressure(&self) {
+        if self.dirty.bytes.load(Ordering::SeqCst) <= self.dirty.high_water {
+            return;
         }
+        if let Some(file) = self.dirty.largest() {
+            let _ = file.flush().await;
+            self.metrics.forced_flushes.fetch_add(1, Ordering::Relaxed);
+        }
+    }
 
-        let data = self.data.lock().unwrap().clone();
-        let ino = self.get_or_create_ino().await?;
+    /// Load the non-resident pages among bytes `start..end` from the database
+    async fn load_pages(&self, start: usize, end: usize, reason: PageLoad) -> VfsResult<()> {
+        let (first, needed, last, stored_len) = {
+            let state = self.state.lock().unwrap();
+            let cache = &state.cache;
+            let Some((first, needed)) = cache.missing(start, end) else {
+                return Ok(());
+            };
+            let readahead = if reason == PageLoad::Read {
+                cache.readahead
+            } else {
+                0
+            };
+            let last = std::cmp::min(needed + readahead, cache.resident.len());
+            (first, needed, last, state.stored_len)
+        };
 
-        // Write the data to the database
-        let file = self.fs
-            .open(ino, libc::O_RDWR)
-            .await
-            .map_err(|e| VfsError::Other(format!("Failed to open file: {}", e)))?;
-        file.pwrite(0, &data)
+        let base = first * PAGE_SIZE;
+        let file = self
+            .fs
+            .open(self.ino, libc::O_RDONLY)
             .await
-            .map_err(|e| VfsError::Other(format!("Failed to write file: {}", e)))?;
-        file.truncate(data.len() as u64)
+            .map_err(|e| backend_error("Failed to open file", e))?;
+        let bytes = file
+            .pread(base as u64, ((last - first) * PAGE_SIZE) as u64)
             .await
-            .map_err(|e| VfsError::Other(format!("Failed to truncate file: {}", e)))?;
+            .map_err(|e| backend_error("Failed to read file", e))?;
+        if self.check_sizes {
+            let expected = std::cmp::min(last * PAGE_SIZE, stored_len).saturating_sub(base);
+            if bytes.len() != expected {
+                eprintln!(
+                    "WARNING: {}: read {} bytes at offset {}, but the file size of {} promised {}",
+                    self.path,
+                    bytes.len(),
+                    base,
+                    stored_len,
+                    expected
+                );
+                self.metrics.size_mismatches.fetch_add(1, Ordering::Relaxed);
+            }
+        }
 
-        // Clear dirty flag after successful write
-        *self.dirty.lock().unwrap() = false;
+        let mut state = self.state.lock().unwrap();
+        let FileState { data, cache, .. } = &mut *state;
+        let (mut read, mut prefetched) = (0, 0);
+        for page in first..std::cmp::min(last, cache.resident.len()) {
+            // Pages written while the database read was in flight win
+            if cache.resident[page] {
+                continue;
+            }
+            let page_start = page * PAGE_SIZE;
+            let page_end = std::cmp::min(page_start + PAGE_SIZE, data.len());
+            let src_end = std::cmp::min(page_end.saturating_sub(base), bytes.len());
+            let src_start = page_start - base;
+            if src_start < src_end {
+                data[page_start..page_start + src_end - src_start]
+                    .copy_from_slice(&bytes[src_start..src_end]);
+            }
+            cache.resident[page] = true;
+            if page < needed && reason != PageLoad::WillNeed {
+                read += 1;
+            } else {
+                prefetched += 1;
+            }
+        }
+        self.metrics.pages_read.fetch_add(read, Ordering::Relaxed);
+        self.metrics
+            .pages_prefetched
+            .fetch_add(prefetched, Ordering::Relaxed);
 
         Ok(())
     }
 
-    async fn fdatasync(&self) -> VfsResult<()> {
-        // For virtual file, same as fsync
-        self.fsync().await
-    }
+    /// Release the loaded pages that lie entirely within bytes `start..end`
+    ///
+    /// Dirty contents only live in memory, so nothing is dropped while the
+    /// file has unsynced writes.
+    fn drop_pages(&self, start: usize, end: usize) {
+        let mut state = self.state.lock().unwrap();
+        if state.dirty || self.ino == 0 {
+            return;
+        }
+        let FileState { data, cache, .. } = &mut *state;
 
-    fn fcntl(&self, cmd: i32, arg: i64) -> VfsResult<i64> {
-        match cmd {
-            libc::F_GETFL => Ok(self.get_flags() as i64),
-            libc::F_SETFL => {
-                self.set_flags(arg as i32)?;
-                Ok(0)
+        // A range reaching EOF includes the partial last page
+        let last = if end >= data.len() {
+            data.len().div_ceil(PAGE_SIZE)
+        } else {
+            end / PAGE_SIZE
+        };
+        let mut dropped = 0;
+        for page in start.div_ceil(PAGE_SIZE)..std::cmp::min(last, cache.resident.len()) {
+            if cache.resident[page] {
+                cache.resident[page] = false;
+                dropped += 1;
             }
-            _ => Err(VfsError::Other(format!(
-                "Unsupported fcntl command: {}",
-                cmd
-            ))),
         }
+        self.metrics
+            .pages_dropped
+            .fetch_add(dropped, Ordering::Relaxed);
     }
 
-    fn ioctl(&self, _request: u64, _arg: u64) -> VfsResult<i64> {
-        // Virtual file doesn't support ioctl
-        Err(VfsError::Other("ioctl not supported".to_string()))
-    }
+    /// Write the modified byte ranges of a file of `len` bytes to the database
+    async fn write_back(&self, len: usize, runs: Vec<(usize, Vec<u8>)>) -> VfsResult<()> {
+        let ino = self.get_or_create_ino().await?;
 
-    fn as_raw_fd(&self) -> Option<RawFd> {
-        // No real kernel FD for virtual files
-        None
-    }
+        let file = self
+            .fs
+            .open(ino, libc::O_RDWR)
+            .await
+            .map_err(|e| backend_error("Failed to open file", e))?;
+        for (start, bytes) in runs {
+            file.pwrite(start as u64, &bytes)
+                .await
+                .map_err(|e| backend_error("Failed to write file", e))?;
+        }
+        file.truncate(len as u64)
+            .await
+            .map_err(|e| backend_error("Failed to truncate file", e))?;
 
-    async fn close(&self) -> VfsResult<()> {
-        // Ensure all data is written to the database before closing
-        self.fsync().await
+        Ok(())
     }
 
-    fn get_flags(&self) -> i32 {
-        *self.flags.lock().unwrap()
+    /// Ask the storage to make written data durable
+    async fn sync_storage(&self, datasync: bool) -> VfsResult<()> {
+        let ino = self.get_or_create_ino().await?;
+        let file = match self.fs.open(ino, libc::O_RDWR).await {
+            Ok(file) => file,
+            // Unlinked since it was opened: no data is left to make durable
+            Err(agentfs_sdk::error::Error::Fs(FsError::NotFound)) => return Ok(()),
+            Err(e) => return Err(backend_error("Failed to open file", e)),
+        };
+        let result = if datasync {
+            file.fdatasync().await
+        } else {
+            file.fsync().await
+        };
+        result.map_err(|e| backend_error("Failed to sync file", e))?;
+        self.unsynced.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Find the next data region or hole for `SEEK_DATA` / `SEEK_HOLE`
+    async fn seek_sparse(&self, offset: i64, whence: i32) -> VfsResult<i64> {
+        if offset < 0 {
+            return Err(VfsError::NoSuchOffset);
+        }
+
+        // Holes are tracked by the storage, so buffered writes go there first
+        self.flush().await?;
+        let ino = self.get_or_create_ino().await?;
+        let file = self
+            .fs
+            .open(ino, libc::O_RDONLY)
+            .await
+            .map_err(|e| backend_error("Failed to open file", e))?;
+        let result = if whence == libc::SEEK_DATA {
+            file.seek_data(offset as u64).await
+        } else {
+            file.seek_hole(offset as u64).await
+        };
+        let new_offset = result.map_err(|e| backend_error("Failed to seek", e))? as i64;
+
+        self.state.lock().unwrap().offset = new_offset;
+        Ok(new_offset)
+    }
+
+    /// Resolve the path and get the inode (for new files)
+    async fn get_or_create_ino(&self) -> VfsResult<i64> {
+        if self.ino != 0 {
+            return Ok(self.ino);
+        }
+        self.created_ino
+            .get_or_try_init(|| self.create_ino())
+            .await
+            .copied()
+    }
+
+    /// Create the file at `path`, returning its inode
+    async fn create_ino(&self) -> VfsResult<i64> {
+        // Need to create the file
+        let (parent_path, name) = SqliteVfs::split_path(&self.path)?;
+
+        // Walk to parent
+        let mut parent_ino = ROOT_INO;
+        for component in parent_path.split('/').filter(|s| !s.is_empty()) {
+            let stats = self
+                .fs
+                .lookup(parent_ino, component)
+                .await
+                .map_err(|e| backend_error("Failed to lookup", e))?
+                .ok_or(VfsError::NotFound)?;
+            parent_ino = stats.ino;
+        }
+
+        // Create the file
+        let (stats, _file) = self
+            .fs
+            .create_file(parent_ino, &name, 0o644, 0, 0)
+            .await
+            .map_err(|e| backend_error("Failed to create file", e))?;
+
+        Ok(stats.ino)
+    }
+}
+
+impl Drop for SqliteFileOps {
+    fn drop(&mut self) {
+        // Dirty data in a file dropped without closing is discarded
+        let state = self
+            .state
+            .get_mut()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let dirty_pages = state.cache.dirty_pages;
+        self.dirty
+            .bytes
+            .fetch_sub(dirty_pages * PAGE_SIZE, Ordering::SeqCst);
+    }
+}
+
+#[async_trait::async_trait]
+impl FileOps for SqliteFileOps {
+    async fn read(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        self.state.lock().unwrap().cache.scale_readahead(buf.len());
+        loop {
+            let (start, end) = {
+                let state = self.state.lock().unwrap();
+                let start = state.offset as usize;
+                let end = std::cmp::min(start.saturating_add(buf.len()), state.data.len());
+                (start, end)
+            };
+            if start < end {
+                self.load_pages(start, end, PageLoad::Read).await?;
+            }
+
+            let mut state = self.state.lock().unwrap();
+
+            // Only the end of the file stops a read short: holes within it
+            // are resident zeros or are loaded as zeros from the database
+            let start = state.offset as usize;
+            if start >= state.data.len() {
+                return Ok(0);
+            }
+            let end = std::cmp::min(start.saturating_add(buf.len()), state.data.len());
+
+            // The offset moved or pages were dropped while loading
+            if state.cache.missing(start, end).is_some() {
+                continue;
+            }
+
+            let bytes_read = end - start;
+            buf[..bytes_read].copy_from_slice(&state.data[start..end]);
+            state.offset += bytes_read as i64;
+
+            return Ok(bytes_read);
+        }
+    }
+
+    async fn write(&self, buf: &[u8]) -> VfsResult<usize> {
+        // Load the stored pages at either end of the write, which it only
+        // partially overwrites
+        let (start, end) = {
+            let state = self.state.lock().unwrap();
+            let start = if state.flags & libc::O_APPEND != 0 {
+                state.data.len()
+            } else {
+                state.offset as usize
+            };
+            (start, start + buf.len())
+        };
+        if start < end {
+            self.load_pages(start, start + 1, PageLoad::Write).await?;
+            self.load_pages(end - 1, end, PageLoad::Write).await?;
+        }
+
+        {
+            let mut state = self.state.lock().unwrap();
+
+            // Handle O_APPEND: always write at the end of the file
+            let start = if state.flags & libc::O_APPEND != 0 {
+                state.data.len()
+            } else {
+                state.offset as usize
+            };
+
+            // Extend the buffer if necessary
+            if start + buf.len() > state.data.len() {
+                state.data.resize(start + buf.len(), 0);
+            }
+
+            state.data[start..start + buf.len()].copy_from_slice(buf);
+            state.offset = (start + buf.len()) as i64;
+
+            // Mark as dirty since we modified the data
+            state.dirty = true;
+
+            // The written pages now hold the file's contents
+            if !buf.is_empty() {
+                let len = state.data.len();
+                state.cache.grow(len);
+                let pages = start / PAGE_SIZE..(start + buf.len()).div_ceil(PAGE_SIZE);
+                state.cache.resident[pages.clone()].fill(true);
+                let marked = state.cache.mark_dirty(pages);
+                self.dirty
+                    .bytes
+                    .fetch_add(marked * PAGE_SIZE, Ordering::SeqCst);
+            }
+        }
+
+        self.relieve_dirty_pressure().await;
+        Ok(buf.len())
+    }
+
+    async fn seek(&self, offset: i64, whence: i32) -> VfsResult<i64> {
+        if whence == libc::SEEK_DATA || whence == libc::SEEK_HOLE {
+            return self.seek_sparse(offset, whence).await;
+        }
+
+        let mut state = self.state.lock().unwrap();
+
+        let new_offset = match whence {
+            libc::SEEK_SET => offset,
+            libc::SEEK_CUR => state.offset + offset,
+            libc::SEEK_END => state.data.len() as i64 + offset,
+            _ => return Err(VfsError::Other("Invalid whence".to_string())),
+        };
+
+        if new_offset < 0 {
+            return Err(VfsError::Other("Invalid offset".to_string()));
+        }
+
+        state.offset = new_offset;
+        Ok(new_offset)
+    }
+
+    async fn fstat(&self) -> VfsResult<libc::stat> {
+        // Get the actual file stats from the filesystem
+        let ino = self.get_or_create_ino().await?;
+        let stats = self
+            .fs
+            .getattr(ino)
+            .await
+            .map_err(|e| backend_error("Failed to getattr", e))?
+            .ok_or(VfsError::NotFound)?;
+
+        let size = self.state.lock().unwrap().data.len() as i64;
+
+        // Use MaybeUninit to construct libc::stat safely
+        let mut stat: std::mem::MaybeUninit<libc::stat> = std::mem::MaybeUninit::zeroed();
+        unsafe {
+            let stat_ptr = stat.as_mut_ptr();
+            (*stat_ptr).st_dev = self.dev;
+            (*stat_ptr).st_ino = stats.ino as u64;
+            (*stat_ptr).st_nlink = stats.nlink.into();
+            (*stat_ptr).st_mode = stats.mode;
+            (*stat_ptr).st_uid = stats.uid;
+            (*stat_ptr).st_gid = stats.gid;
+            (*stat_ptr).st_rdev = 0;
+            (*stat_ptr).st_size = size;
+            (*stat_ptr).st_blksize = 4096;
+            (*stat_ptr).st_blocks = (size + 4095) / 4096;
+            (*stat_ptr).st_atime = stats.atime;
+            (*stat_ptr).st_atime_nsec = 0;
+            (*stat_ptr).st_mtime = stats.mtime;
+            (*stat_ptr).st_mtime_nsec = 0;
+            (*stat_ptr).st_ctime = stats.ctime;
+            (*stat_ptr).st_ctime_nsec = 0;
+            Ok(stat.assume_init())
+        }
+    }
+
+    async fn fsync(&self) -> VfsResult<()> {
+        self.flush().await?;
+        self.sync_storage(false).await
+    }
+
+    async fn fdatasync(&self) -> VfsResult<()> {
+        self.flush().await?;
+        self.sync_storage(true).await
+    }
+
+    async fn flush(&self) -> VfsResult<()> {
+        // For virtual file, flushing means writing to the database. The dirty
+        // flag is cleared together with taking the snapshot, so a write racing
+        // with the flush marks the file dirty again.
+        let (len, runs, dirty_pages) = {
+            let mut state = self.state.lock().unwrap();
+            if !state.dirty {
+                return Ok(());
+            }
+            state.dirty = false;
+            let released = state.cache.dirty_pages * PAGE_SIZE;
+            let dirty_pages = state.cache.take_dirty();
+            self.dirty.bytes.fetch_sub(released, Ordering::SeqCst);
+
+            // Only modified pages are written, so a loaded page that went
+            // stale never overwrites newer data in the database
+            let len = state.data.len();
+            let runs = PageCache::runs(&dirty_pages, len)
+                .into_iter()
+                .map(|(start, end)| (start, state.data[start..end].to_vec()))
+                .collect();
+            (len, runs, dirty_pages)
+        };
+
+        let result = self.write_back(len, runs).await;
+        if result.is_ok() {
+            self.unsynced.store(true, Ordering::SeqCst);
+            self.state.lock().unwrap().stored_len = len;
+        } else {
+            let mut state = self.state.lock().unwrap();
+            state.dirty = true;
+            let marked = state.cache.restore_dirty(&dirty_pages);
+            self.dirty
+                .bytes
+                .fetch_add(marked * PAGE_SIZE, Ordering::SeqCst);
+        }
+        result
+    }
+
+    async fn ftruncate(&self, len: u64) -> VfsResult<()> {
+        if self.state.lock().unwrap().flags & libc::O_ACCMODE == libc::O_RDONLY {
+            return Err(VfsError::InvalidInput(
+                "File not open for writing".to_string(),
+            ));
+        }
+        // Resize the stored file rather than the buffer, so extending leaves
+        // a hole instead of zeros for the next write-back to store
+        self.flush().await?;
+        let ino = self.get_or_create_ino().await?;
+        let file = self
+            .fs
+            .open(ino, libc::O_RDWR)
+            .await
+            .map_err(|e| backend_error("Failed to open file", e))?;
+        file.truncate(len)
+            .await
+            .map_err(|e| backend_error("Failed to truncate file", e))?;
+        self.unsynced.store(true, Ordering::SeqCst);
+
+        let mut state = self.state.lock().unwrap();
+        state.data.resize(len as usize, 0);
+        state.stored_len = len as usize;
+        let dropped = state.cache.resize(len as usize);
+        self.dirty
+            .bytes
+            .fetch_sub(dropped * PAGE_SIZE, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn fadvise(&self, offset: i64, len: i64, advice: i32) -> VfsResult<()> {
+        if offset < 0 || len < 0 {
+            return Err(VfsError::InvalidInput("Invalid fadvise range".to_string()));
+        }
+        let start = offset as usize;
+        let end = if len == 0 {
+            usize::MAX
+        } else {
+            start.saturating_add(len as usize)
+        };
+
+        match advice {
+            libc::POSIX_FADV_NORMAL => {
+                self.state.lock().unwrap().cache.readahead = DEFAULT_READAHEAD_PAGES;
+            }
+            libc::POSIX_FADV_SEQUENTIAL => {
+                self.state.lock().unwrap().cache.readahead = SEQUENTIAL_READAHEAD_PAGES;
+            }
+            libc::POSIX_FADV_RANDOM => {
+                self.state.lock().unwrap().cache.readahead = 0;
+            }
+            libc::POSIX_FADV_WILLNEED => {
+                let end = std::cmp::min(end, self.state.lock().unwrap().data.len());
+                if start < end {
+                    self.load_pages(start, end, PageLoad::WillNeed).await?;
+                }
+            }
+            libc::POSIX_FADV_DONTNEED => {
+                // Like the kernel, write back dirty data before dropping pages
+                self.flush().await?;
+                self.drop_pages(start, end);
+            }
+            libc::POSIX_FADV_NOREUSE => {}
+            _ => {
+                return Err(VfsError::InvalidInput(format!(
+                    "Invalid fadvise advice: {}",
+                    advice
+                )))
+            }
+        }
+
+        Ok(())
+    }
+
+    fn fcntl(&self, cmd: i32, arg: i64) -> VfsResult<i64> {
+        virtual_fcntl(self, cmd, arg)
+    }
+
+    async fn get_inode_flags(&self) -> VfsResult<u32> {
+        let ino = self.get_or_create_ino().await?;
+        get_inode_flags(self.fs.as_ref(), ino).await
+    }
+
+    async fn set_inode_flags(&self, flags: u32) -> VfsResult<()> {
+        let ino = self.get_or_create_ino().await?;
+        set_inode_flags(self.fs.as_ref(), ino, flags).await
+    }
+
+    fn ioctl(&self, _request: u64, _arg: u64) -> VfsResult<i64> {
+        // Virtual file doesn't support ioctl
+        Err(VfsError::Other("ioctl not supported".to_string()))
+    }
+
+    fn as_raw_fd(&self) -> Option<RawFd> {
+        // No real kernel FD for virtual files
+        None
+    }
+
+    async fn close(&self) -> VfsResult<()> {
+        // Write back buffered data; errors are reported by close(2)
+        self.flush().await?;
+
+        if !self.tmpfile && self.unsynced.load(Ordering::SeqCst) && self.close_policy.sync_due() {
+            self.sync_storage(false).await?;
+            self.metrics.close_syncs.fetch_add(1, Ordering::Relaxed);
+        }
+
+        if self.tmpfile {
+            // Drop the inode unless linkat() gave it a name
+            self.fs
+                .remove_orphan(self.ino)
+                .await
+                .map_err(|e| backend_error("Failed to remove orphan", e))?;
+        }
+
+        Ok(())
+    }
+
+    fn get_flags(&self) -> i32 {
+        self.state.lock().unwrap().flags
     }
 
     fn set_flags(&self, flags: i32) -> VfsResult<()> {
-        *self.flags.lock().unwrap() = flags;
+        self.state.lock().unwrap().flags = flags;
         Ok(())
     }
 }
@@ -548,9 +1861,31 @@ impl FileOps for SqliteFileOps {
 /// Type alias for directory entry list: (inode, name, type)
 type DirEntryList = Vec<(u64, String, u8)>;
 
+/// Map an entry's file type to its `d_type` for getdents64
+fn dirent_type(stats: &Stats) -> u8 {
+    if stats.is_directory() {
+        libc::DT_DIR
+    } else if stats.is_symlink() {
+        libc::DT_LNK
+    } else if stats.is_fifo() {
+        libc::DT_FIFO
+    } else if stats.is_socket() {
+        libc::DT_SOCK
+    } else if stats.is_char_device() {
+        libc::DT_CHR
+    } else if stats.is_block_device() {
+        libc::DT_BLK
+    } else if stats.is_file() {
+        libc::DT_REG
+    } else {
+        libc::DT_UNKNOWN
+    }
+}
+
 /// Directory operations for SQLite VFS directories
 struct SqliteDirectoryOps {
     fs: Arc<dyn FileSystem>,
+    dev: u64,
     ino: i64,
     path: String,
     flags: Mutex<i32>,
@@ -558,6 +1893,8 @@ struct SqliteDirectoryOps {
     entries: Arc<Mutex<Option<DirEntryList>>>,
     /// Current position in the directory listing
     position: Arc<Mutex<usize>>,
+    /// Counts this directory against the VFS's open file limit
+    _handle: OpenHandle,
 }
 
 #[async_trait::async_trait]
@@ -583,31 +1920,10 @@ impl FileOps for SqliteDirectoryOps {
             .fs
             .getattr(self.ino)
             .await
-            .map_err(|e| VfsError::Other(format!("Failed to getattr: {}", e)))?
+            .map_err(|e| backend_error("Failed to getattr", e))?
             .ok_or(VfsError::NotFound)?;
 
-        // Use MaybeUninit to construct libc::stat safely
-        let mut stat: std::mem::MaybeUninit<libc::stat> = std::mem::MaybeUninit::zeroed();
-        unsafe {
-            let stat_ptr = stat.as_mut_ptr();
-            (*stat_ptr).st_dev = 0;
-            (*stat_ptr).st_ino = stats.ino as u64;
-            (*stat_ptr).st_nlink = stats.nlink.into();
-            (*stat_ptr).st_mode = stats.mode;
-            (*stat_ptr).st_uid = stats.uid;
-            (*stat_ptr).st_gid = stats.gid;
-            (*stat_ptr).st_rdev = 0;
-            (*stat_ptr).st_size = stats.size;
-            (*stat_ptr).st_blksize = 4096;
-            (*stat_ptr).st_blocks = (stats.size + 4095) / 4096;
-            (*stat_ptr).st_atime = stats.atime;
-            (*stat_ptr).st_atime_nsec = 0;
-            (*stat_ptr).st_mtime = stats.mtime;
-            (*stat_ptr).st_mtime_nsec = 0;
-            (*stat_ptr).st_ctime = stats.ctime;
-            (*stat_ptr).st_ctime_nsec = 0;
-            Ok(stat.assume_init())
-        }
+        Ok(stats_to_stat(&stats, self.dev))
     }
 
     async fn fsync(&self) -> VfsResult<()> {
@@ -621,17 +1937,15 @@ impl FileOps for SqliteDirectoryOps {
     }
 
     fn fcntl(&self, cmd: i32, arg: i64) -> VfsResult<i64> {
-        match cmd {
-            libc::F_GETFL => Ok(self.get_flags() as i64),
-            libc::F_SETFL => {
-                self.set_flags(arg as i32)?;
-                Ok(0)
-            }
-            _ => Err(VfsError::Other(format!(
-                "Unsupported fcntl command: {}",
-                cmd
-            ))),
-        }
+        virtual_fcntl(self, cmd, arg)
+    }
+
+    async fn get_inode_flags(&self) -> VfsResult<u32> {
+        get_inode_flags(self.fs.as_ref(), self.ino).await
+    }
+
+    async fn set_inode_flags(&self, flags: u32) -> VfsResult<()> {
+        set_inode_flags(self.fs.as_ref(), self.ino, flags).await
     }
 
     fn ioctl(&self, _request: u64, _arg: u64) -> VfsResult<i64> {
@@ -671,7 +1985,7 @@ impl FileOps for SqliteDirectoryOps {
                 .fs
                 .readdir_plus(self.ino)
                 .await
-                .map_err(|e| VfsError::Other(format!("Failed to read directory: {}", e)))?
+                .map_err(|e| backend_error("Failed to read directory", e))?
                 .ok_or(VfsError::NotFound)?;
 
             // Convert to the format expected by getdents64
@@ -682,7 +1996,7 @@ impl FileOps for SqliteDirectoryOps {
                 .fs
                 .getattr(self.ino)
                 .await
-                .map_err(|e| VfsError::Other(format!("Failed to getattr current dir: {}", e)))?
+                .map_err(|e| backend_error("Failed to getattr current dir", e))?
                 .ok_or(VfsError::NotFound)?;
 
             // Get parent directory inode for ".."
@@ -694,13 +2008,21 @@ impl FileOps for SqliteDirectoryOps {
                     .parent()
                     .map(|p| p.to_str().unwrap_or("/").to_string())
                     .unwrap_or("/".to_string());
-                let parent_path = if parent_path.is_empty() { "/" } else { &parent_path };
+                let parent_path = if parent_path.is_empty() {
+                    "/"
+                } else {
+                    &parent_path
+                };
 
                 // Walk to find parent inode
                 let mut ino = ROOT_INO;
                 for component in parent_path.split('/').filter(|s| !s.is_empty()) {
-                    if let Some(stats) = self.fs.lookup(ino, component).await
-                        .map_err(|e| VfsError::Other(format!("Failed to lookup: {}", e)))? {
+                    if let Some(stats) = self
+                        .fs
+                        .lookup(ino, component)
+                        .await
+                        .map_err(|e| backend_error("Failed to lookup", e))?
+                    {
                         ino = stats.ino;
                     }
                 }
@@ -711,13 +2033,7 @@ impl FileOps for SqliteDirectoryOps {
             result.push((parent_ino as u64, "..".to_string(), libc::DT_DIR));
 
             for entry in dir_entries {
-                let d_type = if entry.stats.is_directory() {
-                    libc::DT_DIR
-                } else if entry.stats.is_symlink() {
-                    libc::DT_LNK
-                } else {
-                    libc::DT_REG
-                };
+                let d_type = dirent_type(&entry.stats);
                 result.push((entry.stats.ino as u64, entry.name, d_type));
             }
 
@@ -742,3 +2058,1388 @@ impl FileOps for SqliteDirectoryOps {
         }
     }
 }
+
+/// Handle for an entry opened with `O_PATH`
+///
+/// It identifies the entry for `fstat` and `*at` calls but grants no access to
+/// its contents, so I/O fails with EBADF like on Linux.
+struct SqlitePathOps {
+    fs: Arc<dyn FileSystem>,
+    dev: u64,
+    ino: i64,
+    flags: Mutex<i32>,
+    /// Counts this handle against the VFS's open file limit
+    _handle: OpenHandle,
+}
+
+#[async_trait::async_trait]
+impl FileOps for SqlitePathOps {
+    async fn read(&self, _buf: &mut [u8]) -> VfsResult<usize> {
+        Err(VfsError::BadFileDescriptor)
+    }
+
+    async fn write(&self, _buf: &[u8]) -> VfsResult<usize> {
+        Err(VfsError::BadFileDescriptor)
+    }
+
+    async fn seek(&self, _offset: i64, _whence: i32) -> VfsResult<i64> {
+        Err(VfsError::BadFileDescriptor)
+    }
+
+    async fn fstat(&self) -> VfsResult<libc::stat> {
+        let stats = self
+            .fs
+            .getattr(self.ino)
+            .await
+            .map_err(|e| backend_error("Failed to getattr", e))?
+            .ok_or(VfsError::NotFound)?;
+
+        Ok(stats_to_stat(&stats, self.dev))
+    }
+
+    async fn fsync(&self) -> VfsResult<()> {
+        Err(VfsError::BadFileDescriptor)
+    }
+
+    async fn fdatasync(&self) -> VfsResult<()> {
+        Err(VfsError::BadFileDescriptor)
+    }
+
+    async fn ftruncate(&self, _len: u64) -> VfsResult<()> {
+        Err(VfsError::BadFileDescriptor)
+    }
+
+    async fn fadvise(&self, _offset: i64, _len: i64, _advice: i32) -> VfsResult<()> {
+        Err(VfsError::BadFileDescriptor)
+    }
+
+    fn fcntl(&self, cmd: i32, arg: i64) -> VfsResult<i64> {
+        virtual_fcntl(self, cmd, arg)
+    }
+
+    async fn get_inode_flags(&self) -> VfsResult<u32> {
+        Err(VfsError::BadFileDescriptor)
+    }
+
+    async fn set_inode_flags(&self, _flags: u32) -> VfsResult<()> {
+        Err(VfsError::BadFileDescriptor)
+    }
+
+    fn ioctl(&self, _request: u64, _arg: u64) -> VfsResult<i64> {
+        Err(VfsError::BadFileDescriptor)
+    }
+
+    fn as_raw_fd(&self) -> Option<RawFd> {
+        None
+    }
+
+    async fn close(&self) -> VfsResult<()> {
+        Ok(())
+    }
+
+    fn get_flags(&self) -> i32 {
+        *self.flags.lock().unwrap()
+    }
+
+    fn set_flags(&self, flags: i32) -> VfsResult<()> {
+        *self.flags.lock().unwrap() = flags;
+        Ok(())
+    }
+
+    async fn getdents(&self) -> VfsResult<DirEntryList> {
+        Err(VfsError::BadFileDescriptor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentfs_sdk::testing::TinyTmpfs;
+
+    async fn new_vfs(dir: &tempfile::TempDir) -> SqliteVfs {
+        SqliteVfs::new(dir.path().join("test.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_tmpfile_link() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+
+        let file = vfs
+            .open(Path::new("/agent"), libc::O_TMPFILE | libc::O_RDWR, 0o644)
+            .await
+            .unwrap();
+        file.write(b"object code").await.unwrap();
+        assert_eq!(file.fstat().await.unwrap().st_nlink, 0);
+
+        // The file has no name until it is linked
+        assert!(matches!(
+            vfs.stat(Path::new("/agent/out.o")).await,
+            Err(VfsError::NotFound)
+        ));
+
+        vfs.link_file(&file, Path::new("/agent/out.o"))
+            .await
+            .unwrap();
+        file.close().await.unwrap();
+
+        let linked = vfs
+            .open(Path::new("/agent/out.o"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        let mut buf = [0u8; 64];
+        let n = linked.read(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"object code");
+        assert_eq!(linked.fstat().await.unwrap().st_nlink, 1);
+    }
+
+    #[tokio::test]
+    async fn test_tmpfile_requires_write_access() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+
+        let result = vfs
+            .open(Path::new("/agent"), libc::O_TMPFILE | libc::O_RDONLY, 0o644)
+            .await;
+        assert!(matches!(result, Err(VfsError::InvalidInput(_))));
+    }
+
+    #[tokio::test]
+    async fn test_open_directory_flag_on_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+
+        let file = vfs
+            .open(
+                Path::new("/agent/file.txt"),
+                libc::O_CREAT | libc::O_WRONLY,
+                0o644,
+            )
+            .await
+            .unwrap();
+        file.close().await.unwrap();
+
+        let result = vfs
+            .open(
+                Path::new("/agent/file.txt"),
+                libc::O_RDONLY | libc::O_DIRECTORY,
+                0,
+            )
+            .await;
+        assert!(matches!(result, Err(VfsError::NotADirectory)));
+    }
+
+    #[tokio::test]
+    async fn test_open_path_flag_on_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+
+        let file = vfs
+            .open(
+                Path::new("/agent/file.txt"),
+                libc::O_CREAT | libc::O_WRONLY,
+                0o644,
+            )
+            .await
+            .unwrap();
+        file.write(b"contents").await.unwrap();
+        file.close().await.unwrap();
+
+        let path = vfs
+            .open(Path::new("/agent/file.txt"), libc::O_PATH, 0)
+            .await
+            .unwrap();
+        let stat = path.fstat().await.unwrap();
+        assert_eq!(stat.st_size, 8);
+        assert_eq!(stat.st_mode & libc::S_IFMT, libc::S_IFREG);
+
+        let mut buf = [0u8; 8];
+        assert!(matches!(
+            path.read(&mut buf).await,
+            Err(VfsError::BadFileDescriptor)
+        ));
+        assert!(matches!(
+            path.write(b"x").await,
+            Err(VfsError::BadFileDescriptor)
+        ));
+        path.close().await.unwrap();
+
+        // O_PATH neither creates nor truncates
+        assert!(matches!(
+            vfs.open(
+                Path::new("/agent/missing.txt"),
+                libc::O_PATH | libc::O_CREAT,
+                0o644
+            )
+            .await,
+            Err(VfsError::NotFound)
+        ));
+        let path = vfs
+            .open(
+                Path::new("/agent/file.txt"),
+                libc::O_PATH | libc::O_TRUNC,
+                0,
+            )
+            .await
+            .unwrap();
+        path.close().await.unwrap();
+        assert_eq!(
+            vfs.stat(Path::new("/agent/file.txt"))
+                .await
+                .unwrap()
+                .st_size,
+            8
+        );
+    }
+
+    #[tokio::test]
+    async fn test_getdents_on_file_is_not_a_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+
+        let file = vfs
+            .open(
+                Path::new("/agent/file.txt"),
+                libc::O_CREAT | libc::O_RDWR,
+                0o644,
+            )
+            .await
+            .unwrap();
+        assert!(matches!(
+            file.getdents().await,
+            Err(VfsError::NotADirectory)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_getdents_reports_entry_types() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+
+        vfs.open(
+            Path::new("/agent/file.txt"),
+            libc::O_CREAT | libc::O_RDWR,
+            0o644,
+        )
+        .await
+        .unwrap();
+        vfs.fs
+            .mknod(ROOT_INO, "pipe", agentfs_sdk::S_IFIFO | 0o644, 0, 0, 0)
+            .await
+            .unwrap();
+        vfs.fs
+            .mknod(ROOT_INO, "sock", agentfs_sdk::S_IFSOCK | 0o644, 0, 0, 0)
+            .await
+            .unwrap();
+
+        let dir_ops = vfs
+            .open(Path::new("/agent"), libc::O_RDONLY | libc::O_DIRECTORY, 0)
+            .await
+            .unwrap();
+        let entries = dir_ops.getdents().await.unwrap();
+        let d_type = |name: &str| {
+            entries
+                .iter()
+                .find(|(_, entry, _)| entry == name)
+                .map(|(_, _, d_type)| *d_type)
+        };
+        assert_eq!(d_type("."), Some(libc::DT_DIR));
+        assert_eq!(d_type("file.txt"), Some(libc::DT_REG));
+        assert_eq!(d_type("pipe"), Some(libc::DT_FIFO));
+        assert_eq!(d_type("sock"), Some(libc::DT_SOCK));
+    }
+
+    #[tokio::test]
+    async fn test_fadvise_sequential_enlarges_readahead() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+        let path = Path::new("/agent/big.bin");
+        let metrics = vfs.cache_metrics();
+        let counter = |c: &AtomicU64| c.load(Ordering::Relaxed);
+
+        let file = vfs
+            .open(path, libc::O_CREAT | libc::O_RDWR, 0o644)
+            .await
+            .unwrap();
+        file.write(&vec![7u8; 64 * PAGE_SIZE]).await.unwrap();
+        file.close().await.unwrap();
+
+        // Default readahead after a one-page read
+        let mut buf = vec![0u8; PAGE_SIZE];
+        let file = vfs.open(path, libc::O_RDONLY, 0).await.unwrap();
+        file.read(&mut buf).await.unwrap();
+        assert_eq!(counter(&metrics.pages_read), 1);
+        assert_eq!(
+            counter(&metrics.pages_prefetched),
+            DEFAULT_READAHEAD_PAGES as u64
+        );
+        file.close().await.unwrap();
+
+        // A sequential hint widens the window
+        let file = vfs.open(path, libc::O_RDONLY, 0).await.unwrap();
+        file.fadvise(0, 0, libc::POSIX_FADV_SEQUENTIAL)
+            .await
+            .unwrap();
+        file.read(&mut buf).await.unwrap();
+        assert_eq!(counter(&metrics.pages_read), 2);
+        assert_eq!(
+            counter(&metrics.pages_prefetched),
+            (DEFAULT_READAHEAD_PAGES + SEQUENTIAL_READAHEAD_PAGES) as u64
+        );
+
+        // Subsequent reads within the window don't go to the database
+        for _ in 0..SEQUENTIAL_READAHEAD_PAGES {
+            file.read(&mut buf).await.unwrap();
+            assert_eq!(buf, vec![7u8; PAGE_SIZE]);
+        }
+        assert_eq!(counter(&metrics.pages_read), 2);
+
+        // DONTNEED drops the cached pages, so reading again reloads them
+        file.fadvise(0, 0, libc::POSIX_FADV_DONTNEED).await.unwrap();
+        assert_eq!(
+            counter(&metrics.pages_dropped),
+            (SEQUENTIAL_READAHEAD_PAGES + 1) as u64
+        );
+        file.seek(0, libc::SEEK_SET).await.unwrap();
+        file.read(&mut buf).await.unwrap();
+        assert_eq!(counter(&metrics.pages_read), 3);
+        assert_eq!(buf, vec![7u8; PAGE_SIZE]);
+
+        assert!(matches!(
+            file.fadvise(0, 0, -1).await,
+            Err(VfsError::InvalidInput(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pages_load_when_first_accessed() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+        let path = Path::new("/agent/big.bin");
+
+        let file = vfs
+            .open(path, libc::O_CREAT | libc::O_RDWR, 0o644)
+            .await
+            .unwrap();
+        file.write(&vec![7u8; 64 * PAGE_SIZE]).await.unwrap();
+        file.close().await.unwrap();
+
+        let reader = vfs.open(path, libc::O_RDONLY, 0).await.unwrap();
+        let mut buf = vec![0u8; PAGE_SIZE];
+        reader.read(&mut buf).await.unwrap();
+
+        // Overwrite a loaded page and one that was not loaded yet
+        let writer = vfs.open(path, libc::O_WRONLY, 0).await.unwrap();
+        writer.write(&vec![9u8; PAGE_SIZE]).await.unwrap();
+        writer
+            .seek((40 * PAGE_SIZE) as i64, libc::SEEK_SET)
+            .await
+            .unwrap();
+        writer.write(&vec![9u8; PAGE_SIZE]).await.unwrap();
+        writer.close().await.unwrap();
+
+        // The loaded page keeps what it was loaded with, the other one is
+        // read from the database as it is now
+        reader.seek(0, libc::SEEK_SET).await.unwrap();
+        reader.read(&mut buf).await.unwrap();
+        assert_eq!(buf, vec![7u8; PAGE_SIZE]);
+        reader
+            .seek((40 * PAGE_SIZE) as i64, libc::SEEK_SET)
+            .await
+            .unwrap();
+        reader.read(&mut buf).await.unwrap();
+        assert_eq!(buf, vec![9u8; PAGE_SIZE]);
+
+        // Dropping the loaded page makes the next read see the write too
+        reader
+            .fadvise(0, 0, libc::POSIX_FADV_DONTNEED)
+            .await
+            .unwrap();
+        reader.seek(0, libc::SEEK_SET).await.unwrap();
+        reader.read(&mut buf).await.unwrap();
+        assert_eq!(buf, vec![9u8; PAGE_SIZE]);
+    }
+
+    #[tokio::test]
+    async fn test_flush_writes_only_modified_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+        let path = Path::new("/agent/shared.bin");
+
+        let file = vfs
+            .open(path, libc::O_CREAT | libc::O_RDWR, 0o644)
+            .await
+            .unwrap();
+        file.write(&vec![7u8; 4 * PAGE_SIZE]).await.unwrap();
+        file.close().await.unwrap();
+
+        // Load every page through one handle, then change one through another
+        let first = vfs.open(path, libc::O_RDWR, 0).await.unwrap();
+        let mut buf = vec![0u8; 4 * PAGE_SIZE];
+        first.read(&mut buf).await.unwrap();
+        let second = vfs.open(path, libc::O_WRONLY, 0).await.unwrap();
+        second
+            .seek((2 * PAGE_SIZE) as i64, libc::SEEK_SET)
+            .await
+            .unwrap();
+        second.write(&vec![9u8; PAGE_SIZE]).await.unwrap();
+        second.close().await.unwrap();
+
+        // Flushing the first handle keeps the other handle's page
+        first.seek(0, libc::SEEK_SET).await.unwrap();
+        first.write(&vec![8u8; PAGE_SIZE]).await.unwrap();
+        assert_eq!(vfs.dirty_bytes(), PAGE_SIZE);
+        first.close().await.unwrap();
+
+        let reader = vfs.open(path, libc::O_RDONLY, 0).await.unwrap();
+        reader.read(&mut buf).await.unwrap();
+        assert_eq!(buf[..PAGE_SIZE], vec![8u8; PAGE_SIZE]);
+        assert_eq!(buf[PAGE_SIZE..2 * PAGE_SIZE], vec![7u8; PAGE_SIZE]);
+        assert_eq!(buf[2 * PAGE_SIZE..3 * PAGE_SIZE], vec![9u8; PAGE_SIZE]);
+    }
+
+    #[tokio::test]
+    async fn test_size_check_detects_stale_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await.with_size_checks(true);
+        let path = Path::new("/agent/data.bin");
+        let mismatches = || vfs.cache_metrics().size_mismatches.load(Ordering::Relaxed);
+
+        let file = vfs
+            .open(path, libc::O_CREAT | libc::O_RDWR, 0o644)
+            .await
+            .unwrap();
+        file.write(&vec![5u8; 3 * PAGE_SIZE + 10]).await.unwrap();
+        file.close().await.unwrap();
+
+        // Loads that match the size are not reported
+        let mut buf = vec![0u8; PAGE_SIZE];
+        let file = vfs.open(path, libc::O_RDONLY, 0).await.unwrap();
+        file.read(&mut buf).await.unwrap();
+        file.close().await.unwrap();
+        assert_eq!(mismatches(), 0);
+
+        // Shrink the stored file behind the back of an open handle, so the
+        // size it was opened with is stale
+        let file = vfs.open(path, libc::O_RDONLY, 0).await.unwrap();
+        let ino = vfs.stat(path).await.unwrap().st_ino as i64;
+        vfs.fs
+            .open(ino, libc::O_RDWR)
+            .await
+            .unwrap()
+            .truncate(PAGE_SIZE as u64)
+            .await
+            .unwrap();
+        file.read(&mut buf).await.unwrap();
+        assert_eq!(mismatches(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_exists_skips_getattr() {
+        use agentfs_sdk::filesystem::MockFileSystem;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut vfs = new_vfs(&dir).await;
+        vfs.fs.mkdir(ROOT_INO, "sub", 0o755, 0, 0).await.unwrap();
+        let path = Path::new("/agent/sub/file");
+        let file = vfs
+            .open(path, libc::O_CREAT | libc::O_WRONLY, 0o644)
+            .await
+            .unwrap();
+        file.close().await.unwrap();
+
+        // Count the calls reaching the backend
+        let mock = Arc::new(MockFileSystem::new(vfs.fs.clone()));
+        vfs.fs = mock.clone();
+
+        // A stat looks up every component, then reads the attributes again
+        vfs.stat(path).await.unwrap();
+        assert_eq!((mock.count("lookup"), mock.count("getattr")), (2, 1));
+
+        // An existence check only asks the backend about the final entry
+        assert!(vfs.exists(path).await.unwrap());
+        assert_eq!((mock.count("lookup"), mock.count("getattr")), (3, 1));
+        assert_eq!(mock.count("exists"), 1);
+
+        assert!(vfs.exists(Path::new("/agent")).await.unwrap());
+        assert!(vfs.exists(Path::new("/agent/sub")).await.unwrap());
+        assert!(!vfs.exists(Path::new("/agent/sub/other")).await.unwrap());
+        assert!(!vfs.exists(Path::new("/agent/missing/file")).await.unwrap());
+        assert_eq!(mock.count("getattr"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_large_reads_grow_readahead() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+        let path = Path::new("/agent/stream.bin");
+        let metrics = vfs.cache_metrics();
+        let counter = |c: &AtomicU64| c.load(Ordering::Relaxed);
+
+        let file = vfs
+            .open(path, libc::O_CREAT | libc::O_RDWR, 0o644)
+            .await
+            .unwrap();
+        file.write(&vec![3u8; 256 * PAGE_SIZE]).await.unwrap();
+        file.close().await.unwrap();
+
+        // A 16-page read prefetches the next 16 pages instead of the default
+        let mut buf = vec![0u8; 16 * PAGE_SIZE];
+        let file = vfs.open(path, libc::O_RDONLY, 0).await.unwrap();
+        file.read(&mut buf).await.unwrap();
+        assert_eq!(counter(&metrics.pages_read), 16);
+        assert_eq!(counter(&metrics.pages_prefetched), 16);
+
+        // The next read of that size is served entirely from the cache
+        file.read(&mut buf).await.unwrap();
+        assert_eq!(buf, vec![3u8; 16 * PAGE_SIZE]);
+        assert_eq!(counter(&metrics.pages_read), 16);
+
+        // The window is capped for very large reads
+        let mut huge = vec![0u8; 96 * PAGE_SIZE];
+        file.read(&mut huge).await.unwrap();
+        assert_eq!(counter(&metrics.pages_read), 16 + 96);
+        assert_eq!(
+            counter(&metrics.pages_prefetched),
+            (16 + MAX_READAHEAD_PAGES) as u64
+        );
+        file.close().await.unwrap();
+
+        // Random access keeps readahead off regardless of read size
+        let file = vfs.open(path, libc::O_RDONLY, 0).await.unwrap();
+        file.fadvise(0, 0, libc::POSIX_FADV_RANDOM).await.unwrap();
+        file.read(&mut buf).await.unwrap();
+        assert_eq!(
+            counter(&metrics.pages_prefetched),
+            (16 + MAX_READAHEAD_PAGES) as u64
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_reads_writes_and_seeks() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+        let path = Path::new("/agent/shared.bin");
+        let size = 16 * PAGE_SIZE;
+
+        let file = vfs
+            .open(path, libc::O_CREAT | libc::O_RDWR, 0o644)
+            .await
+            .unwrap();
+        file.write(&vec![1u8; size]).await.unwrap();
+        file.close().await.unwrap();
+
+        // Reopen so reads have to load pages while other tasks write
+        let file = vfs.open(path, libc::O_RDWR, 0).await.unwrap();
+        let tasks: Vec<_> = (0..8u8)
+            .map(|i| {
+                let file = file.clone();
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 1024];
+                    for round in 0..200usize {
+                        let offset = (i as usize * 7 + round * 13) % 63 * 1024;
+                        file.seek(offset as i64, libc::SEEK_SET).await.unwrap();
+                        if round % 3 == 0 {
+                            file.write(&[i + 2; 512]).await.unwrap();
+                        } else {
+                            file.read(&mut buf).await.unwrap();
+                        }
+                        if round % 50 == 0 {
+                            file.fsync().await.unwrap();
+                        }
+                    }
+                })
+            })
+            .collect();
+        let all = async {
+            for task in tasks {
+                task.await.unwrap();
+            }
+        };
+        tokio::time::timeout(std::time::Duration::from_secs(60), all)
+            .await
+            .expect("concurrent file operations deadlocked");
+
+        // Every byte holds the original contents or one task's pattern
+        let mut contents = vec![0u8; size + 1];
+        file.seek(0, libc::SEEK_SET).await.unwrap();
+        assert_eq!(file.read(&mut contents).await.unwrap(), size);
+        contents.truncate(size);
+        assert!(contents.iter().all(|b| (1..=9).contains(b)));
+        file.close().await.unwrap();
+
+        // And the database agrees with what the file showed
+        let file = vfs.open(path, libc::O_RDONLY, 0).await.unwrap();
+        let mut stored = vec![0u8; size];
+        assert_eq!(file.read(&mut stored).await.unwrap(), size);
+        assert_eq!(stored, contents);
+    }
+
+    #[tokio::test]
+    async fn test_pinned_dir_relative_lookups() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+
+        let work = vfs.fs.mkdir(ROOT_INO, "work", 0o755, 0, 0).await.unwrap();
+        vfs.fs.mkdir(work.ino, "src", 0o755, 0, 0).await.unwrap();
+        let file = vfs
+            .open(
+                Path::new("/agent/work/src/main.rs"),
+                libc::O_CREAT | libc::O_RDWR,
+                0o644,
+            )
+            .await
+            .unwrap();
+        file.write(b"fn main() {}").await.unwrap();
+        file.close().await.unwrap();
+
+        let cwd = vfs.pin_dir(Path::new("/agent/work")).await.unwrap();
+        assert_eq!(cwd.ino(), work.ino);
+
+        let stat = vfs.stat_at(&cwd, Path::new("./src/main.rs")).await.unwrap();
+        assert_eq!(stat.st_size, 12);
+        let by_path = vfs
+            .stat(Path::new("/agent/work/src/main.rs"))
+            .await
+            .unwrap();
+        assert_eq!(stat.st_ino, by_path.st_ino);
+        assert!(matches!(
+            vfs.stat_at(&cwd, Path::new("missing")).await,
+            Err(VfsError::NotFound)
+        ));
+        assert!(matches!(
+            vfs.stat_at(&cwd, Path::new("../work")).await,
+            Err(VfsError::InvalidInput(_))
+        ));
+
+        // Files created relative to the pinned directory land inside it
+        let created = vfs
+            .open_at(
+                &cwd,
+                Path::new("src/lib.rs"),
+                libc::O_CREAT | libc::O_RDWR,
+                0o644,
+            )
+            .await
+            .unwrap();
+        created.write(b"pub fn lib() {}").await.unwrap();
+        created.close().await.unwrap();
+        let reopened = vfs
+            .open(Path::new("/agent/work/src/lib.rs"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        let mut buf = [0u8; 64];
+        let n = reopened.read(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"pub fn lib() {}");
+
+        // The pinned inode survives a rename of the directory
+        vfs.fs
+            .rename(ROOT_INO, "work", ROOT_INO, "moved")
+            .await
+            .unwrap();
+        assert!(vfs.stat_at(&cwd, Path::new("src/main.rs")).await.is_ok());
+
+        assert!(matches!(
+            vfs.pin_dir(Path::new("/agent/moved/src/main.rs")).await,
+            Err(VfsError::InvalidInput(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_seek_data_and_hole() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+
+        // Data in the first and fourth pages, with a hole between them
+        let page = PAGE_SIZE as i64;
+        let file = vfs
+            .open(
+                Path::new("/agent/sparse.bin"),
+                libc::O_CREAT | libc::O_RDWR,
+                0o644,
+            )
+            .await
+            .unwrap();
+        file.write(&[1u8; PAGE_SIZE]).await.unwrap();
+        file.seek(3 * page, libc::SEEK_SET).await.unwrap();
+        file.write(&[2u8; PAGE_SIZE]).await.unwrap();
+
+        assert_eq!(file.seek(10, libc::SEEK_DATA).await.unwrap(), 10);
+        assert_eq!(file.seek(0, libc::SEEK_HOLE).await.unwrap(), page);
+        assert_eq!(file.seek(page, libc::SEEK_DATA).await.unwrap(), 3 * page);
+        assert_eq!(
+            file.seek(3 * page, libc::SEEK_HOLE).await.unwrap(),
+            4 * page
+        );
+        // The file offset moves to the result
+        assert_eq!(file.seek(0, libc::SEEK_CUR).await.unwrap(), 4 * page);
+
+        assert!(matches!(
+            file.seek(4 * page, libc::SEEK_DATA).await,
+            Err(VfsError::NoSuchOffset)
+        ));
+        assert!(matches!(
+            file.seek(-1, libc::SEEK_HOLE).await,
+            Err(VfsError::NoSuchOffset)
+        ));
+        file.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_gap_after_seek_past_end() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+
+        let file = vfs
+            .open(
+                Path::new("/agent/gap.bin"),
+                libc::O_CREAT | libc::O_RDWR,
+                0o644,
+            )
+            .await
+            .unwrap();
+        file.write(b"abc").await.unwrap();
+        file.seek(2 * PAGE_SIZE as i64 + 5, libc::SEEK_SET)
+            .await
+            .unwrap();
+        file.write(b"xyz").await.unwrap();
+        let size = 2 * PAGE_SIZE + 8;
+
+        // The gap reads back as zeros in a single read, up to the data
+        // written after it
+        file.seek(3, libc::SEEK_SET).await.unwrap();
+        let mut buf = vec![0xffu8; 2 * PAGE_SIZE + 2];
+        assert_eq!(file.read(&mut buf).await.unwrap(), 2 * PAGE_SIZE + 2);
+        assert!(buf.iter().all(|&b| b == 0));
+
+        // Only the true end of the file stops a read
+        let mut buf = vec![0xffu8; 16];
+        assert_eq!(file.read(&mut buf).await.unwrap(), 3);
+        assert_eq!(&buf[..3], b"xyz");
+        assert_eq!(file.read(&mut buf).await.unwrap(), 0);
+
+        // The same holds once the pages were written back and dropped
+        file.fsync().await.unwrap();
+        file.fadvise(0, 0, libc::POSIX_FADV_DONTNEED).await.unwrap();
+        file.seek(0, libc::SEEK_SET).await.unwrap();
+        let mut buf = vec![0xffu8; size + 16];
+        assert_eq!(file.read(&mut buf).await.unwrap(), size);
+        assert_eq!(&buf[..3], b"abc");
+        assert!(buf[3..size - 3].iter().all(|&b| b == 0));
+        assert_eq!(&buf[size - 3..size], b"xyz");
+        file.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_ftruncate_extend_leaves_hole() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+
+        let page = PAGE_SIZE as i64;
+        let file = vfs
+            .open(
+                Path::new("/agent/grown.bin"),
+                libc::O_CREAT | libc::O_RDWR,
+                0o644,
+            )
+            .await
+            .unwrap();
+        file.write(b"abc").await.unwrap();
+        file.ftruncate(4 * PAGE_SIZE as u64 + 10).await.unwrap();
+        assert_eq!(file.fstat().await.unwrap().st_size, 4 * page + 10);
+
+        // Only the first page holds data; the rest reads back as zeros
+        assert_eq!(file.seek(0, libc::SEEK_HOLE).await.unwrap(), page);
+        file.seek(0, libc::SEEK_SET).await.unwrap();
+        let mut buf = vec![0xffu8; 4 * PAGE_SIZE + 20];
+        let mut total = 0;
+        loop {
+            let n = file.read(&mut buf[total..]).await.unwrap();
+            if n == 0 {
+                break;
+            }
+            total += n;
+        }
+        assert_eq!(total, 4 * PAGE_SIZE + 10);
+        assert_eq!(&buf[..3], b"abc");
+        assert!(buf[3..total].iter().all(|&b| b == 0));
+
+        // Shrinking discards data written past the new end
+        file.seek(2 * page, libc::SEEK_SET).await.unwrap();
+        file.write(b"dropped").await.unwrap();
+        file.ftruncate(2).await.unwrap();
+        assert_eq!(vfs.dirty_bytes(), 0);
+        file.close().await.unwrap();
+        let stats = vfs.stat(Path::new("/agent/grown.bin")).await.unwrap();
+        assert_eq!(stats.st_size, 2);
+
+        let readonly = vfs
+            .open(Path::new("/agent/grown.bin"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        assert!(matches!(
+            readonly.ftruncate(0).await,
+            Err(VfsError::InvalidInput(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_inode_flags_restrict_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+        let path = Path::new("/agent/locked.txt");
+
+        let file = vfs
+            .open(path, libc::O_CREAT | libc::O_WRONLY, 0o644)
+            .await
+            .unwrap();
+        file.write(b"data").await.unwrap();
+        file.close().await.unwrap();
+
+        let file = vfs.open(path, libc::O_RDONLY, 0).await.unwrap();
+        assert_eq!(file.get_inode_flags().await.unwrap(), 0);
+
+        // Immutable files can only be opened for reading
+        file.set_inode_flags(FS_IMMUTABLE_FL).await.unwrap();
+        assert_eq!(file.get_inode_flags().await.unwrap(), FS_IMMUTABLE_FL);
+        for flags in [libc::O_WRONLY, libc::O_RDWR | libc::O_APPEND, libc::O_TRUNC] {
+            assert!(matches!(
+                vfs.open(path, flags, 0).await,
+                Err(VfsError::NotPermitted)
+            ));
+        }
+        vfs.open(path, libc::O_RDONLY, 0).await.unwrap();
+
+        // Append-only files can only be opened for appending, failing with
+        // EPERM otherwise
+        file.set_inode_flags(FS_APPEND_FL).await.unwrap();
+        let Err(e) = vfs.open(path, libc::O_WRONLY, 0).await else {
+            panic!("opened an append-only file for writing");
+        };
+        assert_eq!(e.to_errno(), libc::EPERM);
+        let appender = vfs
+            .open(path, libc::O_WRONLY | libc::O_APPEND, 0)
+            .await
+            .unwrap();
+        appender.write(b"+more").await.unwrap();
+        appender.close().await.unwrap();
+
+        // Clearing the flags lifts the restrictions
+        file.set_inode_flags(0).await.unwrap();
+        let writer = vfs
+            .open(path, libc::O_WRONLY | libc::O_TRUNC, 0)
+            .await
+            .unwrap();
+        writer.write(b"new").await.unwrap();
+        writer.close().await.unwrap();
+        let reader = vfs.open(path, libc::O_RDONLY, 0).await.unwrap();
+        let mut buf = [0u8; 16];
+        let n = reader.read(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"new");
+
+        assert!(matches!(
+            file.set_inode_flags(0x80000).await,
+            Err(VfsError::InvalidInput(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_set_times_on_symlink_without_following() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+        let target = Path::new("/agent/target.txt");
+        let link = Path::new("/agent/link");
+
+        let file = vfs
+            .open(target, libc::O_CREAT | libc::O_WRONLY, 0o644)
+            .await
+            .unwrap();
+        file.close().await.unwrap();
+        vfs.symlink(Path::new("target.txt"), link).await.unwrap();
+        let target_mtime = vfs.lstat(target).await.unwrap().st_mtime;
+
+        let time = |sec| libc::timespec {
+            tv_sec: sec,
+            tv_nsec: 0,
+        };
+        let omit = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: libc::UTIME_OMIT,
+        };
+
+        // AT_SYMLINK_NOFOLLOW sets the link's own times
+        vfs.set_times(link, omit, time(1_000_000_000), false)
+            .await
+            .unwrap();
+        assert_eq!(vfs.lstat(link).await.unwrap().st_mtime, 1_000_000_000);
+        assert_eq!(vfs.lstat(target).await.unwrap().st_mtime, target_mtime);
+
+        // Without it, the target's times are set
+        vfs.set_times(link, omit, time(1_500_000_000), true)
+            .await
+            .unwrap();
+        assert_eq!(vfs.lstat(target).await.unwrap().st_mtime, 1_500_000_000);
+        assert_eq!(vfs.lstat(link).await.unwrap().st_mtime, 1_000_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_dev_and_ino_identify_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+        let first = Path::new("/agent/first.txt");
+        let second = Path::new("/agent/second.txt");
+        let linked = Path::new("/agent/linked.txt");
+
+        for path in [first, second] {
+            let file = vfs
+                .open(path, libc::O_CREAT | libc::O_WRONLY, 0o644)
+                .await
+                .unwrap();
+            file.close().await.unwrap();
+        }
+        vfs.link(first, linked).await.unwrap();
+
+        let id = |stat: libc::stat| (stat.st_dev, stat.st_ino);
+        let first_id = id(vfs.stat(first).await.unwrap());
+        assert_ne!(first_id.0, 0);
+        assert_ne!(first_id, id(vfs.stat(second).await.unwrap()));
+        assert_eq!(first_id, id(vfs.stat(linked).await.unwrap()));
+
+        // Open files report the same identity
+        let file = vfs.open(linked, libc::O_RDONLY, 0).await.unwrap();
+        assert_eq!(first_id, id(file.fstat().await.unwrap()));
+
+        // Another database reuses the inode numbers under a different device
+        let other_dir = tempfile::tempdir().unwrap();
+        let other = new_vfs(&other_dir).await;
+        let file = other
+            .open(first, libc::O_CREAT | libc::O_WRONLY, 0o644)
+            .await
+            .unwrap();
+        file.close().await.unwrap();
+        let other_id = id(other.stat(first).await.unwrap());
+        assert_ne!(other_id.0, first_id.0);
+    }
+
+    #[tokio::test]
+    async fn test_open_file_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await.with_max_open_files(3);
+
+        let mut files = Vec::new();
+        for i in 0..3 {
+            let path = format!("/agent/file{}.txt", i);
+            let file = vfs
+                .open(Path::new(&path), libc::O_CREAT | libc::O_RDWR, 0o644)
+                .await
+                .unwrap();
+            files.push(file);
+        }
+        assert_eq!(vfs.open_file_count(), 3);
+
+        assert!(matches!(
+            vfs.open(
+                Path::new("/agent/file3.txt"),
+                libc::O_CREAT | libc::O_RDWR,
+                0o644
+            )
+            .await,
+            Err(VfsError::TooManyOpenFiles)
+        ));
+        // Directories count against the limit too
+        assert!(matches!(
+            vfs.open(Path::new("/agent"), libc::O_RDONLY, 0).await,
+            Err(VfsError::TooManyOpenFiles)
+        ));
+        assert_eq!(vfs.open_file_count(), 3);
+
+        // Closing a handle frees its slot
+        let file = files.pop().unwrap();
+        file.close().await.unwrap();
+        drop(file);
+        assert_eq!(vfs.open_file_count(), 2);
+
+        let file = vfs
+            .open(Path::new("/agent/file0.txt"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        assert_eq!(vfs.open_file_count(), 3);
+
+        drop(file);
+        drop(files);
+        assert_eq!(vfs.open_file_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_open_handles_lists_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+
+        let existing = vfs
+            .open(
+                Path::new("/agent/a.txt"),
+                libc::O_CREAT | libc::O_RDWR,
+                0o644,
+            )
+            .await
+            .unwrap();
+        existing.close().await.unwrap();
+        drop(existing);
+        let ino = vfs.stat(Path::new("/agent/a.txt")).await.unwrap().st_ino as i64;
+
+        let file = vfs
+            .open(Path::new("/agent/a.txt"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        let created = vfs
+            .open(
+                Path::new("/agent/b.txt"),
+                libc::O_CREAT | libc::O_WRONLY,
+                0o644,
+            )
+            .await
+            .unwrap();
+        let root = vfs
+            .open(Path::new("/agent"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+
+        let handles = vfs.open_handles();
+        assert_eq!(
+            handles,
+            vec![
+                OpenHandleInfo {
+                    path: PathBuf::from("/agent"),
+                    ino: ROOT_INO,
+                    flags: libc::O_RDONLY,
+                },
+                OpenHandleInfo {
+                    path: PathBuf::from("/agent/a.txt"),
+                    ino,
+                    flags: libc::O_RDONLY,
+                },
+                OpenHandleInfo {
+                    path: PathBuf::from("/agent/b.txt"),
+                    ino: 0,
+                    flags: libc::O_CREAT | libc::O_WRONLY,
+                },
+            ]
+        );
+
+        // Closed handles leave the listing
+        drop(file);
+        drop(root);
+        created.close().await.unwrap();
+        drop(created);
+        assert!(vfs.open_handles().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_dirty_high_water_forces_flush() {
+        let dir = tempfile::tempdir().unwrap();
+        let high_water = 8 * PAGE_SIZE;
+        let vfs = new_vfs(&dir).await.with_dirty_high_water(high_water);
+
+        // Each file takes 3 dirty pages; nothing is closed or flushed
+        let chunk = vec![0x5a; 3 * PAGE_SIZE];
+        let mut files = Vec::new();
+        for i in 0..10 {
+            let path = format!("/agent/file{}.bin", i);
+            let file = vfs
+                .open(Path::new(&path), libc::O_CREAT | libc::O_RDWR, 0o644)
+                .await
+                .unwrap();
+            file.write(&chunk).await.unwrap();
+            assert!(
+                vfs.dirty_bytes() <= high_water,
+                "{} dirty bytes after file {}",
+                vfs.dirty_bytes(),
+                i
+            );
+            files.push(file);
+        }
+        let forced = vfs.cache_metrics().forced_flushes.load(Ordering::Relaxed);
+        assert!(forced >= 7, "only {} forced flushes", forced);
+
+        // Each forced flush wrote a whole file to the database
+        let mut stored = 0;
+        for i in 0..10 {
+            let name = format!("file{}.bin", i);
+            if let Some(stats) = vfs.fs.lookup(ROOT_INO, &name).await.unwrap() {
+                assert_eq!(stats.size, chunk.len() as i64);
+                stored += 1;
+            }
+        }
+        assert_eq!(stored, forced);
+
+        // Closing releases the rest
+        for file in &files {
+            file.close().await.unwrap();
+        }
+        assert_eq!(vfs.dirty_bytes(), 0);
+
+        // Dirty data of a file dropped without closing is no longer counted
+        let file = vfs
+            .open(
+                Path::new("/agent/dropped.bin"),
+                libc::O_CREAT | libc::O_RDWR,
+                0o644,
+            )
+            .await
+            .unwrap();
+        file.write(&chunk).await.unwrap();
+        assert_eq!(vfs.dirty_bytes(), chunk.len());
+        drop(file);
+        assert_eq!(vfs.dirty_bytes(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_flush_error_propagates_to_close() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+        vfs.fs.mkdir(ROOT_INO, "dir", 0o755, 0, 0).await.unwrap();
+
+        let file = vfs
+            .open(
+                Path::new("/agent/dir/new.txt"),
+                libc::O_CREAT | libc::O_RDWR,
+                0o644,
+            )
+            .await
+            .unwrap();
+        file.write(b"buffered").await.unwrap();
+
+        // The file can no longer be created once its directory is gone
+        vfs.fs.rmdir(ROOT_INO, "dir").await.unwrap();
+
+        assert!(file.flush().await.is_err());
+        // The data is still buffered, so close retries and reports the error
+        assert!(file.close().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_flush_then_fsync_created_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+        let path = Path::new("/agent/new.txt");
+
+        let file = vfs
+            .open(path, libc::O_CREAT | libc::O_RDWR, 0o644)
+            .await
+            .unwrap();
+        file.write(b"hello").await.unwrap();
+        file.flush().await.unwrap();
+        file.write(b", world").await.unwrap();
+        file.fsync().await.unwrap();
+        file.fdatasync().await.unwrap();
+        file.close().await.unwrap();
+
+        let file = vfs.open(path, libc::O_RDONLY, 0).await.unwrap();
+        let mut buf = vec![0u8; 32];
+        let n = file.read(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"hello, world");
+    }
+
+    #[tokio::test]
+    async fn test_writev_then_readv() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+        let path = Path::new("/agent/vectored.txt");
+
+        let file = vfs
+            .open(path, libc::O_CREAT | libc::O_RDWR, 0o644)
+            .await
+            .unwrap();
+        let n = file
+            .writev(&[b"hello".as_slice(), b", ".as_slice(), b"world".as_slice()])
+            .await
+            .unwrap();
+        assert_eq!(n, 12);
+        file.close().await.unwrap();
+
+        let file = vfs.open(path, libc::O_RDONLY, 0).await.unwrap();
+        let mut buf = vec![0u8; 32];
+        let n = file.read(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"hello, world");
+
+        // Reading back into segments fills each in turn and stops at EOF
+        file.seek(0, libc::SEEK_SET).await.unwrap();
+        let (mut a, mut b, mut c) = ([0u8; 4], [0u8; 4], [0u8; 8]);
+        let n = file
+            .readv(&mut [a.as_mut_slice(), b.as_mut_slice(), c.as_mut_slice()])
+            .await
+            .unwrap();
+        assert_eq!(n, 12);
+        assert_eq!(&a, b"hell");
+        assert_eq!(&b, b"o, w");
+        assert_eq!(&c[..4], b"orld");
+        file.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "mounts a tmpfs, which requires root"]
+    async fn test_write_past_disk_capacity_returns_no_space() {
+        let dir = tempfile::tempdir().unwrap();
+        let tmpfs = TinyTmpfs::mount(dir.path()).unwrap();
+
+        let result = async {
+            let vfs = new_vfs(&dir).await;
+            let file = vfs
+                .open(
+                    Path::new("/agent/big.bin"),
+                    libc::O_CREAT | libc::O_RDWR,
+                    0o644,
+                )
+                .await?;
+            file.write(&vec![0xAB; 4 * 1024 * 1024]).await?;
+            file.close().await
+        }
+        .await;
+        drop(tmpfs);
+
+        assert!(
+            matches!(result, Err(VfsError::NoSpace)),
+            "unexpected result: {:?}",
+            result
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_as_path_component_is_not_a_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+        let file = vfs
+            .open(
+                Path::new("/agent/somefile"),
+                libc::O_CREAT | libc::O_WRONLY,
+                0o644,
+            )
+            .await
+            .unwrap();
+        file.close().await.unwrap();
+
+        let child = Path::new("/agent/somefile/child");
+        assert!(matches!(
+            vfs.stat(child).await,
+            Err(VfsError::NotADirectory)
+        ));
+        assert!(matches!(
+            vfs.lstat(child).await,
+            Err(VfsError::NotADirectory)
+        ));
+        assert!(matches!(
+            vfs.open(child, libc::O_CREAT | libc::O_WRONLY, 0o644).await,
+            Err(VfsError::NotADirectory)
+        ));
+        assert!(matches!(
+            vfs.stat(Path::new("/agent/somefile/child/deeper")).await,
+            Err(VfsError::NotADirectory)
+        ));
+
+        // A missing component is still reported as missing
+        assert!(matches!(
+            vfs.stat(Path::new("/agent/missing/child")).await,
+            Err(VfsError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_backend_errors_are_typed() {
+        // A filesystem error with a VFS counterpart maps to it by variant,
+        // whatever its message says
+        let exists = agentfs_sdk::error::Error::Fs(FsError::AlreadyExists);
+        assert!(matches!(
+            backend_error("Failed to create symlink", exists),
+            VfsError::AlreadyExists
+        ));
+
+        // Other errors keep their source for callers to inspect
+        let err = backend_error(
+            "Failed to create file",
+            agentfs_sdk::error::Error::Fs(FsError::NameTooLong),
+        );
+        assert_eq!(err.to_string(), "Failed to create file: Filename too long");
+        let source = std::error::Error::source(&err)
+            .and_then(|source| source.downcast_ref::<agentfs_sdk::error::Error>());
+        assert!(matches!(
+            source,
+            Some(agentfs_sdk::error::Error::Fs(FsError::NameTooLong))
+        ));
+
+        // End to end, creating an existing symlink reports EEXIST
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await;
+        let link = Path::new("/agent/link");
+        vfs.symlink(Path::new("target"), link).await.unwrap();
+        assert!(matches!(
+            vfs.symlink(Path::new("target"), link).await,
+            Err(VfsError::AlreadyExists)
+        ));
+    }
+
+    /// Write `data` to a new file at `path` and close it.
+    async fn write_and_close(vfs: &SqliteVfs, path: &str, data: &[u8]) {
+        let file = vfs
+            .open(Path::new(path), libc::O_CREAT | libc::O_WRONLY, 0o644)
+            .await
+            .unwrap();
+        file.write(data).await.unwrap();
+        file.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sync_on_close_survives_crash() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = new_vfs(&dir).await.with_durability(Durability::SyncOnClose);
+        let close_syncs = || vfs.cache_metrics().close_syncs.load(Ordering::Relaxed);
+
+        write_and_close(&vfs, "/agent/data.txt", b"durable").await;
+        assert_eq!(close_syncs(), 1);
+
+        // Closing a file that was only read has nothing to sync
+        let file = vfs
+            .open(Path::new("/agent/data.txt"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        file.close().await.unwrap();
+        assert_eq!(close_syncs(), 1);
+
+        // Crash right after the close: the VFS is never shut down, and the
+        // next process opens the database afresh
+        std::mem::forget(vfs);
+        let recovered = new_vfs(&dir).await;
+        let file = recovered
+            .open(Path::new("/agent/data.txt"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        let mut buf = [0u8; 16];
+        let n = file.read(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"durable");
+    }
+
+    #[tokio::test]
+    async fn test_durability_modes_sync_on_close() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let vfs = new_vfs(&dir).await;
+        write_and_close(&vfs, "/agent/none.txt", b"data").await;
+        assert_eq!(vfs.cache_metrics().close_syncs.load(Ordering::Relaxed), 0);
+
+        // Closes within one interval share a sync
+        let vfs = new_vfs(&dir)
+            .await
+            .with_durability(Durability::SyncPeriodic(Duration::from_secs(3600)));
+        write_and_close(&vfs, "/agent/first.txt", b"data").await;
+        write_and_close(&vfs, "/agent/second.txt", b"data").await;
+        assert_eq!(vfs.cache_metrics().close_syncs.load(Ordering::Relaxed), 1);
+
+        let vfs = new_vfs(&dir)
+            .await
+            .with_durability(Durability::SyncPeriodic(Duration::ZERO));
+        write_and_close(&vfs, "/agent/third.txt", b"data").await;
+        write_and_close(&vfs, "/agent/fourth.txt", b"data").await;
+        assert_eq!(vfs.cache_metrics().close_syncs.load(Ordering::Relaxed), 2);
+    }
+}
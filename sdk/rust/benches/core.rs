@@ -0,0 +1,277 @@
+//! Performance benchmarks for core AgentFS operations.
+//!
+//! Covers file creation, sequential and random reads and writes, lookups of
+//! deep paths, listing large directories and getting attributes, as a
+//! baseline for changes to block storage, caching and streaming.
+//!
+//! Run with: cargo bench --bench core
+
+use agentfs_sdk::filesystem::{AgentFS, BatchEntry, BoxedFile, FileSystem};
+use agentfs_sdk::{DEFAULT_DIR_MODE, DEFAULT_FILE_MODE};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rand::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tempfile::{tempdir, TempDir};
+use tokio::runtime::Runtime;
+
+/// Inode number of the root directory
+const ROOT_INO: i64 = 1;
+
+/// Size of the file read and written by the I/O benchmarks
+const FILE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Create an empty filesystem in a temporary directory.
+fn new_fs(rt: &Runtime) -> (AgentFS, TempDir) {
+    rt.block_on(async {
+        let dir = tempdir().expect("Failed to create temp dir");
+        let db_path = dir.path().join("bench.db");
+        let fs = AgentFS::new(db_path.to_str().unwrap())
+            .await
+            .expect("Failed to create AgentFS");
+        (fs, dir)
+    })
+}
+
+/// Create `/data` filled with `FILE_SIZE` bytes and return an open handle.
+fn new_data_file(rt: &Runtime, fs: &AgentFS) -> BoxedFile {
+    rt.block_on(async {
+        let (_, file) = FileSystem::create_file(fs, ROOT_INO, "data", DEFAULT_FILE_MODE, 0, 0)
+            .await
+            .expect("Failed to create file");
+        file.pwrite(0, &vec![0x5a; FILE_SIZE])
+            .await
+            .expect("Failed to write file");
+        file
+    })
+}
+
+/// Offsets of the `block_size` blocks of a `FILE_SIZE` file, in file order
+/// or shuffled.
+fn block_offsets(block_size: usize, random: bool) -> Vec<u64> {
+    let mut offsets: Vec<u64> = (0..FILE_SIZE / block_size)
+        .map(|block| (block * block_size) as u64)
+        .collect();
+    if random {
+        offsets.shuffle(&mut StdRng::seed_from_u64(42));
+    }
+    offsets
+}
+
+fn bench_create_file(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let (fs, _dir) = new_fs(&rt);
+    let next = AtomicUsize::new(0);
+
+    let mut group = c.benchmark_group("create_file");
+    for size in [0usize, 4 * 1024, 64 * 1024] {
+        let data = vec![0x5a; size];
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            let (fs, next, data) = (&fs, &next, &data);
+            b.to_async(&rt).iter(move || {
+                let name = format!("file{}", next.fetch_add(1, Ordering::Relaxed));
+                async move {
+                    let (_, file) =
+                        FileSystem::create_file(fs, ROOT_INO, &name, DEFAULT_FILE_MODE, 0, 0)
+                            .await
+                            .expect("Failed to create file");
+                    if !data.is_empty() {
+                        file.pwrite(0, data).await.expect("Failed to write file");
+                    }
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_pwrite(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let (fs, _dir) = new_fs(&rt);
+    let file = new_data_file(&rt, &fs);
+
+    let mut group = c.benchmark_group("pwrite");
+    group.throughput(Throughput::Bytes(FILE_SIZE as u64));
+    group.sample_size(10);
+    for (pattern, random) in [("sequential", false), ("random", true)] {
+        for block_size in [4 * 1024, 64 * 1024] {
+            let offsets = block_offsets(block_size, random);
+            let block = vec![0xa5; block_size];
+            group.bench_with_input(
+                BenchmarkId::new(pattern, block_size),
+                &block_size,
+                |b, _| {
+                    let (file, offsets, block) = (&file, &offsets, &block);
+                    b.to_async(&rt).iter(|| async move {
+                        for &offset in offsets {
+                            file.pwrite(offset, block)
+                                .await
+                                .expect("Failed to write file");
+                        }
+                    });
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+fn bench_pread(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let (fs, _dir) = new_fs(&rt);
+    let file = new_data_file(&rt, &fs);
+
+    let mut group = c.benchmark_group("pread");
+    group.throughput(Throughput::Bytes(FILE_SIZE as u64));
+    group.sample_size(10);
+    for (pattern, random) in [("sequential", false), ("random", true)] {
+        for block_size in [4 * 1024, 64 * 1024] {
+            let offsets = block_offsets(block_size, random);
+            group.bench_with_input(
+                BenchmarkId::new(pattern, block_size),
+                &block_size,
+                |b, &block_size| {
+                    let (file, offsets) = (&file, &offsets);
+                    b.to_async(&rt).iter(|| async move {
+                        for &offset in offsets {
+                            let data = file
+                                .pread(offset, block_size as u64)
+                                .await
+                                .expect("Failed to read file");
+                            assert_eq!(data.len(), block_size);
+                        }
+                    });
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+fn bench_lookup(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let (fs, _dir) = new_fs(&rt);
+
+    let mut group = c.benchmark_group("lookup");
+    for depth in [1usize, 4, 16] {
+        // Each depth gets its own chain of directories below the root
+        let names: Vec<String> = (0..depth)
+            .map(|level| format!("d{}_{}", depth, level))
+            .collect();
+        rt.block_on(async {
+            let mut parent = ROOT_INO;
+            for name in &names {
+                parent = FileSystem::mkdir(&fs, parent, name, DEFAULT_DIR_MODE, 0, 0)
+                    .await
+                    .expect("Failed to create directory")
+                    .ino;
+            }
+        });
+
+        group.throughput(Throughput::Elements(depth as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(depth), &depth, |b, _| {
+            let (fs, names) = (&fs, &names);
+            b.to_async(&rt).iter(|| async move {
+                // Resolve the path one component at a time, like the kernel
+                let mut ino = ROOT_INO;
+                for name in names {
+                    ino = fs
+                        .lookup(ino, name)
+                        .await
+                        .expect("Failed to look up")
+                        .expect("Missing directory")
+                        .ino;
+                }
+                ino
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_readdir_plus(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let (fs, _dir) = new_fs(&rt);
+
+    let mut group = c.benchmark_group("readdir_plus");
+    group.sample_size(20);
+    for entries in [100usize, 1_000, 10_000] {
+        let dir_path = format!("/dir{}", entries);
+        let mut batch = vec![BatchEntry::Dir {
+            path: dir_path.clone(),
+            mode: DEFAULT_DIR_MODE,
+        }];
+        batch.extend((0..entries).map(|i| BatchEntry::File {
+            path: format!("{}/file{}", dir_path, i),
+            mode: DEFAULT_FILE_MODE,
+            data: Vec::new(),
+        }));
+        let dir_ino = rt.block_on(async {
+            fs.create_batch(&batch, 0, 0)
+                .await
+                .expect("Failed to create directory")[0]
+        });
+
+        group.throughput(Throughput::Elements(entries as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(entries), &entries, |b, _| {
+            let fs = &fs;
+            b.to_async(&rt).iter(|| async move {
+                let listing = fs
+                    .readdir_plus(dir_ino)
+                    .await
+                    .expect("Failed to list directory")
+                    .expect("Missing directory");
+                assert_eq!(listing.len(), entries);
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_getattr(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let (fs, _dir) = new_fs(&rt);
+
+    // Attributes of a file among many, so lookups are not served from a
+    // trivially small table
+    let mut group = c.benchmark_group("getattr");
+    for files in [1usize, 10_000] {
+        let dir_path = format!("/files{}", files);
+        let mut batch = vec![BatchEntry::Dir {
+            path: dir_path.clone(),
+            mode: DEFAULT_DIR_MODE,
+        }];
+        batch.extend((0..files).map(|i| BatchEntry::File {
+            path: format!("{}/file{}", dir_path, i),
+            mode: DEFAULT_FILE_MODE,
+            data: Vec::new(),
+        }));
+        let inos = rt.block_on(async {
+            fs.create_batch(&batch, 0, 0)
+                .await
+                .expect("Failed to create files")
+        });
+        let ino = inos[1 + files / 2];
+
+        group.bench_with_input(BenchmarkId::from_parameter(files), &files, |b, _| {
+            let fs = &fs;
+            b.to_async(&rt).iter(|| async move {
+                fs.getattr(ino)
+                    .await
+                    .expect("Failed to get attributes")
+                    .expect("Missing file")
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_create_file,
+    bench_pwrite,
+    bench_pread,
+    bench_lookup,
+    bench_readdir_plus,
+    bench_getattr
+);
+criterion_main!(benches);
@@ -0,0 +1,95 @@
+//! Seeding a filesystem with many small files.
+//!
+//! Compares creating each file in its own transaction against creating the
+//! whole tree with `AgentFS::create_batch`.
+//!
+//! Run with: cargo bench --bench import
+
+use agentfs_sdk::filesystem::{AgentFS, BatchEntry};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use tempfile::tempdir;
+
+/// Number of files imported in each iteration
+const FILE_COUNT: usize = 10_000;
+/// Files per directory, so the tree is not one huge directory
+const FILES_PER_DIR: usize = 100;
+
+fn tree() -> Vec<BatchEntry> {
+    let mut entries = Vec::new();
+    for d in 0..FILE_COUNT / FILES_PER_DIR {
+        entries.push(BatchEntry::Dir {
+            path: format!("/dir{}", d),
+            mode: 0o755,
+        });
+        for f in 0..FILES_PER_DIR {
+            entries.push(BatchEntry::File {
+                path: format!("/dir{}/file{}", d, f),
+                mode: 0o644,
+                data: format!("contents of file {} in dir {}\n", f, d).into_bytes(),
+            });
+        }
+    }
+    entries
+}
+
+async fn import_one_by_one(fs: &AgentFS, entries: &[BatchEntry]) {
+    for entry in entries {
+        match entry {
+            BatchEntry::Dir { path, .. } => fs.mkdir(path, 0, 0).await.expect("Failed to mkdir"),
+            BatchEntry::File { path, data, .. } => fs
+                .pwrite(path, 0, data)
+                .await
+                .expect("Failed to write file"),
+            BatchEntry::Symlink { path, target } => fs
+                .symlink(target, path, 0, 0)
+                .await
+                .expect("Failed to create symlink"),
+        }
+    }
+}
+
+fn bench_import(c: &mut Criterion) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let entries = tree();
+
+    let mut group = c.benchmark_group("import");
+    group.throughput(Throughput::Elements(FILE_COUNT as u64));
+    group.sample_size(10);
+
+    for batched in [false, true] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(if batched { "batched" } else { "one_by_one" }),
+            &batched,
+            |b, &batched| {
+                b.iter_batched(
+                    || {
+                        rt.block_on(async {
+                            let dir = tempdir().expect("Failed to create temp dir");
+                            let db_path = dir.path().join("import.db");
+                            let fs = AgentFS::new(db_path.to_str().unwrap())
+                                .await
+                                .expect("Failed to create AgentFS");
+                            (fs, dir)
+                        })
+                    },
+                    |(fs, _dir)| {
+                        rt.block_on(async {
+                            if batched {
+                                fs.create_batch(&entries, 0, 0)
+                                    .await
+                                    .expect("Failed to import batch");
+                            } else {
+                                import_one_by_one(&fs, &entries).await;
+                            }
+                        });
+                    },
+                    criterion::BatchSize::PerIteration,
+                );
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_import);
+criterion_main!(benches);
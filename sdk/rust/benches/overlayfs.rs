@@ -1,11 +1,55 @@
 //! Performance benchmarks for OverlayFS operations.
 //!
+//! Lookups, listings and attributes are measured on entries that live in the
+//! base layer, in the delta layer, or in both, since merging the layers is
+//! where OverlayFS adds cost over a plain AgentFS (see the `core` bench).
+//!
 //! Run with: cargo bench --bench overlayfs
 
 use agentfs_sdk::filesystem::{AgentFS, FileSystem, HostFS, OverlayFS};
-use criterion::{criterion_group, criterion_main, Criterion};
+use agentfs_sdk::DEFAULT_FILE_MODE;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use tempfile::tempdir;
+use tempfile::{tempdir, TempDir};
+use tokio::runtime::Runtime;
+
+/// Inode number of the root directory
+const ROOT_INO: i64 = 1;
+
+/// Create an overlay whose base layer is a host directory filled in by
+/// `populate`, with an empty delta.
+fn new_overlay(rt: &Runtime, populate: impl FnOnce(&Path)) -> (OverlayFS, TempDir, TempDir) {
+    let base_dir = tempdir().expect("Failed to create base temp dir");
+    let delta_dir = tempdir().expect("Failed to create delta temp dir");
+    populate(base_dir.path());
+
+    rt.block_on(async {
+        let base = Arc::new(HostFS::new(base_dir.path()).expect("Failed to create HostFS"));
+        let db_path = delta_dir.path().join("delta.db");
+        let delta = AgentFS::new(db_path.to_str().unwrap())
+            .await
+            .expect("Failed to create AgentFS");
+
+        let overlay = OverlayFS::new(base, delta);
+        overlay
+            .init(base_dir.path().to_str().unwrap())
+            .await
+            .expect("Failed to init overlay");
+        (overlay, base_dir, delta_dir)
+    })
+}
+
+/// Look up `name` in the directory `parent`, which must contain it.
+async fn lookup(overlay: &OverlayFS, parent: i64, name: &str) -> i64 {
+    overlay
+        .lookup(parent, name)
+        .await
+        .expect("Failed to look up")
+        .expect("Missing entry")
+        .ino
+}
 
 fn bench_remove_file(c: &mut Criterion) {
     let rt = tokio::runtime::Runtime::new().unwrap();
@@ -13,34 +57,17 @@ fn bench_remove_file(c: &mut Criterion) {
     c.bench_function("remove_file", |b| {
         b.iter_batched(
             || {
-                // Setup: create overlay with one file
-                rt.block_on(async {
-                    let base_dir = tempdir().expect("Failed to create base temp dir");
-                    let delta_dir = tempdir().expect("Failed to create delta temp dir");
-
-                    // Create a file in base
-                    let path = base_dir.path().join("test.txt");
-                    std::fs::write(&path, "test content").expect("Failed to write file");
-
-                    let base =
-                        Arc::new(HostFS::new(base_dir.path()).expect("Failed to create HostFS"));
-                    let db_path = delta_dir.path().join("delta.db");
-                    let delta = AgentFS::new(db_path.to_str().unwrap())
-                        .await
-                        .expect("Failed to create AgentFS");
-
-                    let overlay = OverlayFS::new(base, delta);
-                    overlay
-                        .init(base_dir.path().to_str().unwrap())
-                        .await
-                        .expect("Failed to init overlay");
-
-                    (overlay, base_dir, delta_dir)
+                // Setup: create overlay with one file in the base
+                new_overlay(&rt, |base| {
+                    std::fs::write(base.join("test.txt"), "test content")
+                        .expect("Failed to write file");
                 })
             },
             |(overlay, _base_dir, _delta_dir)| {
                 rt.block_on(async {
-                    let _ = overlay.remove("/test.txt").await;
+                    FileSystem::unlink(&overlay, ROOT_INO, "test.txt")
+                        .await
+                        .expect("Failed to remove file");
                 });
             },
             criterion::BatchSize::SmallInput,
@@ -48,5 +75,167 @@ fn bench_remove_file(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, bench_remove_file);
+fn bench_lookup(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+
+    let mut group = c.benchmark_group("overlay_lookup");
+    for depth in [1usize, 4, 16] {
+        let names: Vec<String> = (0..depth).map(|level| format!("d{}", level)).collect();
+        let (overlay, _base_dir, _delta_dir) = new_overlay(&rt, |base| {
+            std::fs::create_dir_all(base.join(names.join("/")))
+                .expect("Failed to create directories");
+        });
+
+        group.throughput(Throughput::Elements(depth as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(depth), &depth, |b, _| {
+            let (overlay, names) = (&overlay, &names);
+            b.to_async(&rt).iter(|| async move {
+                let mut ino = ROOT_INO;
+                for name in names {
+                    ino = lookup(overlay, ino, name).await;
+                }
+                ino
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_readdir_plus(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+
+    let mut group = c.benchmark_group("overlay_readdir_plus");
+    group.sample_size(20);
+    for entries in [100usize, 1_000] {
+        let (overlay, _base_dir, _delta_dir) = new_overlay(&rt, |base| {
+            std::fs::create_dir(base.join("dir")).expect("Failed to create directory");
+            for i in 0..entries {
+                std::fs::write(base.join(format!("dir/file{}", i)), b"")
+                    .expect("Failed to write file");
+            }
+        });
+
+        // A tenth of the listing comes from the delta layer
+        let delta_entries = entries / 10;
+        let dir_ino = rt.block_on(async {
+            let dir_ino = lookup(&overlay, ROOT_INO, "dir").await;
+            for i in 0..delta_entries {
+                overlay
+                    .create_file(dir_ino, &format!("new{}", i), DEFAULT_FILE_MODE, 0, 0)
+                    .await
+                    .expect("Failed to create file");
+            }
+            dir_ino
+        });
+
+        group.throughput(Throughput::Elements(entries as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(entries), &entries, |b, _| {
+            let overlay = &overlay;
+            b.to_async(&rt).iter(|| async move {
+                let listing = overlay
+                    .readdir_plus(dir_ino)
+                    .await
+                    .expect("Failed to list directory")
+                    .expect("Missing directory");
+                assert_eq!(listing.len(), entries + delta_entries);
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_getattr(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let (overlay, _base_dir, _delta_dir) = new_overlay(&rt, |base| {
+        std::fs::write(base.join("base.txt"), b"base").expect("Failed to write file");
+    });
+    let (base_ino, delta_ino) = rt.block_on(async {
+        let base_ino = lookup(&overlay, ROOT_INO, "base.txt").await;
+        let (stats, _) = overlay
+            .create_file(ROOT_INO, "delta.txt", DEFAULT_FILE_MODE, 0, 0)
+            .await
+            .expect("Failed to create file");
+        (base_ino, stats.ino)
+    });
+
+    let mut group = c.benchmark_group("overlay_getattr");
+    for (layer, ino) in [("base", base_ino), ("delta", delta_ino)] {
+        group.bench_with_input(BenchmarkId::from_parameter(layer), &ino, |b, &ino| {
+            let overlay = &overlay;
+            b.to_async(&rt).iter(|| async move {
+                overlay
+                    .getattr(ino)
+                    .await
+                    .expect("Failed to get attributes")
+                    .expect("Missing file")
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_create_file(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    // New files go below a directory that only exists in the base layer
+    let (overlay, _base_dir, _delta_dir) = new_overlay(&rt, |base| {
+        std::fs::create_dir(base.join("dir")).expect("Failed to create directory");
+    });
+    let dir_ino = rt.block_on(lookup(&overlay, ROOT_INO, "dir"));
+    let next = AtomicUsize::new(0);
+
+    c.bench_function("overlay_create_file", |b| {
+        let (overlay, next) = (&overlay, &next);
+        b.to_async(&rt).iter(move || {
+            let name = format!("file{}", next.fetch_add(1, Ordering::Relaxed));
+            async move {
+                overlay
+                    .create_file(dir_ino, &name, DEFAULT_FILE_MODE, 0, 0)
+                    .await
+                    .expect("Failed to create file");
+            }
+        });
+    });
+}
+
+fn bench_copy_up(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+
+    // Opening a base file copies it to the delta layer
+    let mut group = c.benchmark_group("overlay_copy_up");
+    group.sample_size(20);
+    for size in [4 * 1024usize, 1024 * 1024] {
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            b.iter_batched(
+                || {
+                    let (overlay, base_dir, delta_dir) = new_overlay(&rt, |base| {
+                        std::fs::write(base.join("data"), vec![0x5a; size])
+                            .expect("Failed to write file");
+                    });
+                    let ino = rt.block_on(lookup(&overlay, ROOT_INO, "data"));
+                    (overlay, ino, base_dir, delta_dir)
+                },
+                |(overlay, ino, _base_dir, _delta_dir)| {
+                    rt.block_on(async {
+                        FileSystem::open(&overlay, ino, libc::O_RDWR)
+                            .await
+                            .expect("Failed to open file");
+                    });
+                },
+                criterion::BatchSize::PerIteration,
+            );
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_remove_file,
+    bench_lookup,
+    bench_readdir_plus,
+    bench_getattr,
+    bench_create_file,
+    bench_copy_up
+);
 criterion_main!(benches);
@@ -302,27 +302,60 @@ impl WorkloadGenerator {
     }
 }
 
+/// Inode number of the root directory
+const ROOT_INO: i64 = 1;
+
+/// Look up each component of `path` in turn, starting at `ino`, the way the
+/// kernel resolves a path. Returns `None` if a component does not exist.
+async fn walk(overlay: &OverlayFS, mut ino: i64, path: &str) -> Option<i64> {
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        ino = overlay.lookup(ino, name).await.ok()??.ino;
+    }
+    Some(ino)
+}
+
+/// Resolve the directory containing `path`, returning it with the last
+/// component of `path`.
+async fn walk_parent<'a>(overlay: &OverlayFS, path: &'a str) -> Option<(i64, &'a str)> {
+    let (dir, name) = path.rsplit_once('/')?;
+    Some((walk(overlay, ROOT_INO, dir).await?, name))
+}
+
 /// Execute a single operation on the overlay filesystem.
+///
+/// Errors are ignored: generated paths often name entries, or parents, that
+/// do not exist, which is expected.
 async fn execute_operation(overlay: &OverlayFS, op: Operation, path: &str) {
     match op {
         Operation::CreateFile => {
-            // Ignore errors - path may not exist, which is expected
-            let _ = overlay.create_file(path, 0o100644, 0, 0).await;
+            if let Some((parent, name)) = walk_parent(overlay, path).await {
+                let _ = overlay.create_file(parent, name, 0o100644, 0, 0).await;
+            }
         }
         Operation::Lstat => {
-            let _ = overlay.lstat(path).await;
+            if let Some((parent, name)) = walk_parent(overlay, path).await {
+                let _ = overlay.lookup(parent, name).await;
+            }
         }
         Operation::Mkdir => {
-            let _ = overlay.mkdir(path, 0, 0).await;
+            if let Some((parent, name)) = walk_parent(overlay, path).await {
+                let _ = overlay.mkdir(parent, name, 0o755, 0, 0).await;
+            }
         }
         Operation::Open => {
-            let _ = overlay.open(path).await;
+            if let Some(ino) = walk(overlay, ROOT_INO, path).await {
+                let _ = overlay.open(ino, libc::O_RDONLY).await;
+            }
         }
         Operation::ReaddirPlus => {
-            let _ = overlay.readdir_plus(path).await;
+            if let Some(ino) = walk(overlay, ROOT_INO, path).await {
+                let _ = overlay.readdir_plus(ino).await;
+            }
         }
         Operation::Stat => {
-            let _ = overlay.stat(path).await;
+            if let Some(ino) = walk(overlay, ROOT_INO, path).await {
+                let _ = overlay.getattr(ino).await;
+            }
         }
     }
 }
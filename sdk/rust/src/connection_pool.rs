@@ -3,10 +3,30 @@
 //! This module provides a thread-safe connection pool that manages database
 //! connections with a maximum limit. When the pool is exhausted, callers block
 //! until a connection becomes available or timeout occurs.
+//!
+//! Connections to a sync database are retried with backoff. If the remote
+//! stays unreachable, a pool configured with an offline fallback serves
+//! read-only connections to the local replica until sync comes back.
+//!
+//! A pool of a sync database can also keep its replica fresh by pulling
+//! remote changes before handing out connections, per its `SyncRefresh`
+//! policy. Caches built on the pool register a hook with `on_sync()` to be
+//! dropped after every pull.
+//!
+//! `begin()` returns a pool bound to a transaction on the pooled connection.
+//! It hands out that connection to every caller until the transaction is
+//! committed or rolled back, while the original pool waits for it.
 
-use std::{sync::Arc, time::Duration};
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
-use turso::{Connection, Database};
+use turso::{Builder, Connection, Database};
 
 use crate::error::{Error, Result};
 
@@ -20,6 +40,96 @@ const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
 enum DatabaseType {
     Local(Database),
     Sync(turso::sync::Database),
+    /// Local database whose first connects fail, standing in for an
+    /// unreachable sync remote
+    #[cfg(test)]
+    Flaky {
+        db: Database,
+        failures: std::sync::atomic::AtomicUsize,
+    },
+    /// Local database standing in for a sync replica, whose pulls run the
+    /// statements queued as remote changes
+    #[cfg(test)]
+    MockSync {
+        db: Database,
+        remote: std::sync::Mutex<Vec<String>>,
+    },
+}
+
+/// When a pool of a sync database pulls remote changes into its replica.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SyncRefresh {
+    /// Only when `sync_now()` is called
+    #[default]
+    Manual,
+    /// Before handing out a connection, if the last pull is older than this
+    Interval(Duration),
+    /// Before handing out every connection
+    OnCheckout,
+}
+
+/// Callback run after every pull, to drop data cached from the replica
+pub type SyncHook = Arc<dyn Fn() + Send + Sync>;
+
+/// Retry-with-backoff policy for establishing sync database connections.
+///
+/// The delay starts at `initial_backoff` and doubles after every failed
+/// attempt, up to `max_backoff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay after the first failed attempt
+    pub initial_backoff: Duration,
+    /// Upper bound on the delay between attempts
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that tries exactly once.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Run `op` until it succeeds or the attempts are used up.
+    async fn run<T, F, Fut>(&self, mut op: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut backoff = self.initial_backoff;
+        let mut attempt = 1;
+        loop {
+            match op().await {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt < self.max_attempts => {
+                    tracing::warn!(
+                        attempt,
+                        error = %e,
+                        "sync connection failed, retrying in {:?}",
+                        backoff
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.max_backoff);
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
 }
 
 /// A pool of database connections with a maximum limit.
@@ -30,6 +140,8 @@ enum DatabaseType {
 #[derive(Clone)]
 pub struct ConnectionPool {
     inner: Arc<ConnectionPoolInner>,
+    /// Transaction this pool is bound to, if returned by `begin()`
+    tx: Option<Arc<PoolTransaction>>,
 }
 
 struct ConnectionPoolInner {
@@ -40,6 +152,22 @@ struct ConnectionPoolInner {
     semaphore: Arc<Semaphore>,
     /// Timeout for acquiring a connection
     timeout: Duration,
+    /// PRAGMA statements run on every newly created connection
+    pragmas: Vec<String>,
+    /// Retry policy for sync database connections
+    retry: RetryPolicy,
+    /// Local replica to read from while the sync database is unreachable
+    offline_fallback: Option<String>,
+    /// Whether pooled connections are read-only fallback connections
+    offline: AtomicBool,
+    /// When the pool last tried to leave offline mode
+    last_reconnect: std::sync::Mutex<Option<Instant>>,
+    /// When to pull remote changes, for sync databases
+    refresh: std::sync::Mutex<SyncRefresh>,
+    /// When the pool last pulled remote changes
+    last_pull: std::sync::Mutex<Option<Instant>>,
+    /// Called after every pull
+    sync_hooks: std::sync::Mutex<Vec<SyncHook>>,
 }
 
 impl ConnectionPool {
@@ -49,19 +177,184 @@ impl ConnectionPool {
     }
 
     /// Create a new connection pool from a sync database.
+    ///
+    /// Connecting is retried with the default `RetryPolicy`.
     pub fn new_sync(db: turso::sync::Database) -> Self {
-        Self::with_timeout(DatabaseType::Sync(db), DEFAULT_TIMEOUT)
+        Self::new_sync_with_pragmas(db, Vec::new())
+    }
+
+    /// Create a new connection pool that runs `pragmas` on every connection.
+    ///
+    /// Each statement (e.g. `"PRAGMA cache_size = -65536"`) is executed once
+    /// when a connection is created, before it is handed out.
+    pub fn new_with_pragmas(db: Database, pragmas: Vec<String>) -> Self {
+        Self::build(DatabaseType::Local(db), DEFAULT_TIMEOUT, pragmas)
+    }
+
+    /// Create a new connection pool from a sync database that runs `pragmas`
+    /// on every connection.
+    pub fn new_sync_with_pragmas(db: turso::sync::Database, pragmas: Vec<String>) -> Self {
+        Self::build_with_retry(
+            DatabaseType::Sync(db),
+            DEFAULT_TIMEOUT,
+            pragmas,
+            RetryPolicy::default(),
+            None,
+        )
     }
 
     /// Create a connection pool with a custom timeout.
     fn with_timeout(db: DatabaseType, timeout: Duration) -> Self {
+        Self::build(db, timeout, Vec::new())
+    }
+
+    /// Create a connection pool from a sync database with a custom retry
+    /// policy and optional offline fallback.
+    ///
+    /// Connecting is retried according to `retry`. If every attempt fails and
+    /// `offline_fallback` is the path of the local replica (the path the sync
+    /// database was built from), the pool goes offline: it hands out
+    /// read-only connections to the replica and tries to reconnect at most
+    /// every `retry.max_backoff`. Writes fail while offline.
+    /// `metrics().offline` reports the current mode.
+    pub fn new_sync_with_retry(
+        db: turso::sync::Database,
+        pragmas: Vec<String>,
+        retry: RetryPolicy,
+        offline_fallback: Option<String>,
+    ) -> Self {
+        Self::build_with_retry(
+            DatabaseType::Sync(db),
+            DEFAULT_TIMEOUT,
+            pragmas,
+            retry,
+            offline_fallback,
+        )
+    }
+
+    fn build(db: DatabaseType, timeout: Duration, pragmas: Vec<String>) -> Self {
+        Self::build_with_retry(db, timeout, pragmas, RetryPolicy::none(), None)
+    }
+
+    fn build_with_retry(
+        db: DatabaseType,
+        timeout: Duration,
+        pragmas: Vec<String>,
+        retry: RetryPolicy,
+        offline_fallback: Option<String>,
+    ) -> Self {
         Self {
             inner: Arc::new(ConnectionPoolInner {
                 db,
                 pool: Mutex::new(Vec::new()),
                 semaphore: Arc::new(Semaphore::new(MAX_CONNECTIONS)),
                 timeout,
+                pragmas,
+                retry,
+                offline_fallback,
+                offline: AtomicBool::new(false),
+                last_reconnect: std::sync::Mutex::new(None),
+                refresh: std::sync::Mutex::new(SyncRefresh::Manual),
+                last_pull: std::sync::Mutex::new(None),
+                sync_hooks: std::sync::Mutex::new(Vec::new()),
             }),
+            tx: None,
+        }
+    }
+
+    /// Pull remote changes according to `refresh`.
+    ///
+    /// Pulls happen when a connection is handed out, before the caller can
+    /// use it, and never while the pool is offline. A failed pull is logged
+    /// and the replica is served as it is. Has no effect on pools of local
+    /// databases.
+    pub fn with_sync_refresh(self, refresh: SyncRefresh) -> Self {
+        *self.inner.refresh.lock().unwrap() = refresh;
+        self
+    }
+
+    /// Call `hook` after every pull of remote changes.
+    ///
+    /// Caches of data read through the pool use it to drop entries the pull
+    /// may have made stale. It runs while the pulling caller holds a pool
+    /// permit, so it must not get a connection.
+    pub fn on_sync(&self, hook: SyncHook) {
+        self.inner.sync_hooks.lock().unwrap().push(hook);
+    }
+
+    /// Pull remote changes into the replica now.
+    ///
+    /// Waits for a connection slot like `get_connection()`, so the pull
+    /// never lands in the middle of another caller's transaction.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::SyncNotEnabled` if the pool is not backed by a sync
+    /// database, or the pull's error if it fails.
+    pub async fn sync_now(&self) -> Result<()> {
+        let _permit = self.acquire_permit().await?;
+        self.pull().await
+    }
+
+    /// Wait for a connection slot.
+    async fn acquire_permit(&self) -> Result<OwnedSemaphorePermit> {
+        acquire(&self.inner.semaphore, self.inner.timeout).await
+    }
+
+    /// Whether the pool is backed by a sync database.
+    fn is_sync(&self) -> bool {
+        match &self.inner.db {
+            DatabaseType::Local(_) => false,
+            DatabaseType::Sync(_) => true,
+            #[cfg(test)]
+            DatabaseType::Flaky { .. } => false,
+            #[cfg(test)]
+            DatabaseType::MockSync { .. } => true,
+        }
+    }
+
+    /// Pull remote changes and run the sync hooks. Callers hold a permit.
+    async fn pull(&self) -> Result<()> {
+        *self.inner.last_pull.lock().unwrap() = Some(Instant::now());
+        match &self.inner.db {
+            DatabaseType::Local(_) => return Err(Error::SyncNotEnabled),
+            DatabaseType::Sync(db) => {
+                db.pull().await?;
+            }
+            #[cfg(test)]
+            DatabaseType::Flaky { .. } => return Err(Error::SyncNotEnabled),
+            #[cfg(test)]
+            DatabaseType::MockSync { db, remote } => {
+                let changes = std::mem::take(&mut *remote.lock().unwrap());
+                let conn = db.connect()?;
+                for sql in changes {
+                    conn.execute(&sql, ()).await?;
+                }
+            }
+        }
+        let hooks = self.inner.sync_hooks.lock().unwrap().clone();
+        for hook in hooks {
+            hook();
+        }
+        Ok(())
+    }
+
+    /// Pull remote changes if the refresh policy calls for it. Callers hold
+    /// a permit.
+    async fn refresh_if_due(&self) {
+        let due = match *self.inner.refresh.lock().unwrap() {
+            SyncRefresh::Manual => false,
+            SyncRefresh::OnCheckout => true,
+            SyncRefresh::Interval(interval) => !matches!(
+                *self.inner.last_pull.lock().unwrap(),
+                Some(at) if at.elapsed() < interval
+            ),
+        };
+        if !due || self.is_offline() || !self.is_sync() {
+            return;
+        }
+        if let Err(e) = self.pull().await {
+            tracing::warn!(error = %e, "failed to pull remote changes, serving the replica as is");
         }
     }
 
@@ -77,14 +370,13 @@ impl ConnectionPool {
     /// Returns `Error::ConnectionPoolTimeout` if no connection becomes
     /// available within the timeout period.
     pub async fn get_connection(&self) -> Result<PooledConnection> {
+        if let Some(tx) = &self.tx {
+            return self.transaction_connection(tx).await;
+        }
+
         // Try to acquire a permit with timeout
-        let permit = tokio::time::timeout(
-            self.inner.timeout,
-            Arc::clone(&self.inner.semaphore).acquire_owned(),
-        )
-        .await
-        .map_err(|_| Error::ConnectionPoolTimeout)?
-        .map_err(|_| Error::Internal("semaphore closed".to_string()))?;
+        let permit = self.acquire_permit().await?;
+        self.refresh_if_due().await;
 
         // We have a permit - try to get an existing connection or create new one
         let conn = {
@@ -93,26 +385,253 @@ impl ConnectionPool {
         };
 
         let conn = match conn {
+            Some(c) if self.is_offline() => self.reconnect().await.unwrap_or(c),
             Some(c) => c,
-            None => match &self.inner.db {
-                DatabaseType::Local(db) => db.connect()?,
-                DatabaseType::Sync(db) => db.connect().await?,
-            },
+            None => self.connect().await?,
         };
 
         Ok(PooledConnection {
             conn: Some(conn),
             pool: self.inner.clone(),
-            _permit: permit,
+            _permit: Some(permit),
+            tx: None,
+        })
+    }
+
+    /// Hand out the connection of `tx`, once its previous user is done.
+    async fn transaction_connection(&self, tx: &Arc<PoolTransaction>) -> Result<PooledConnection> {
+        let permit = acquire(&tx.semaphore, self.inner.timeout).await?;
+        if tx.finished.load(Ordering::SeqCst) {
+            return Err(Error::Internal("transaction already finished".to_string()));
+        }
+        let conn = tx
+            .conn
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| Error::Internal("transaction connection was lost".to_string()))?;
+        Ok(PooledConnection {
+            conn: Some(conn),
+            pool: self.inner.clone(),
+            _permit: Some(permit),
+            tx: Some(tx.clone()),
+        })
+    }
+
+    /// Start a transaction, returning a pool bound to it.
+    ///
+    /// The returned pool, and its clones, hand out the connection the
+    /// transaction runs on, one caller at a time. Everything done through
+    /// them takes effect with `commit()`, or not at all. Until then, the
+    /// transaction holds this pool's connection slot: callers of this pool
+    /// wait for it and time out like when the pool is exhausted.
+    ///
+    /// A transaction dropped without `commit()` or `rollback()` is rolled
+    /// back.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Internal` if this pool is already bound to a
+    /// transaction, as transactions do not nest.
+    pub async fn begin(&self) -> Result<ConnectionPool> {
+        if self.tx.is_some() {
+            return Err(Error::Internal("transactions cannot be nested".to_string()));
+        }
+        let mut conn = self.get_connection().await?;
+        conn.execute("BEGIN IMMEDIATE", ()).await?;
+        // The transaction keeps the slot and the connection, which stays out
+        // of the pool until the transaction ends
+        let permit = conn._permit.take();
+        let conn = conn.conn.take();
+        Ok(ConnectionPool {
+            inner: self.inner.clone(),
+            tx: Some(Arc::new(PoolTransaction {
+                pool: self.inner.clone(),
+                conn: std::sync::Mutex::new(conn),
+                semaphore: Arc::new(Semaphore::new(1)),
+                permit: std::sync::Mutex::new(permit),
+                finished: AtomicBool::new(false),
+                aborted: AtomicBool::new(false),
+            })),
+        })
+    }
+
+    /// Commit the transaction this pool is bound to.
+    ///
+    /// # Errors
+    ///
+    /// Rolls the transaction back instead, and returns an error, if the
+    /// commit fails or an operation was aborted part way through it (see
+    /// `PooledConnection::abort_transaction()`).
+    pub async fn commit(&self) -> Result<()> {
+        self.finish(true).await
+    }
+
+    /// Roll back the transaction this pool is bound to.
+    pub async fn rollback(&self) -> Result<()> {
+        self.finish(false).await
+    }
+
+    /// End the transaction this pool is bound to and give up its slot.
+    async fn finish(&self, commit: bool) -> Result<()> {
+        let tx = self
+            .tx
+            .as_ref()
+            .ok_or_else(|| Error::Internal("no transaction in progress".to_string()))?;
+        let mut conn = self.get_connection().await?;
+        tx.finished.store(true, Ordering::SeqCst);
+        let aborted = tx.aborted.load(Ordering::SeqCst);
+        let result = if commit && !aborted {
+            match conn.execute("COMMIT", ()).await {
+                Ok(_) => Ok(()),
+                Err(e) => {
+                    let _ = conn.execute("ROLLBACK", ()).await;
+                    Err(e.into())
+                }
+            }
+        } else {
+            conn.execute("ROLLBACK", ())
+                .await
+                .map(|_| ())
+                .map_err(Into::into)
+        };
+        // Only a connection that is out of the transaction is reused;
+        // closing any other rolls back what is left of it
+        if let Some(c) = conn.conn.take() {
+            if c.is_autocommit().unwrap_or(false) {
+                self.inner.pool.lock().await.push(c);
+            }
+        }
+        drop(conn);
+        tx.permit.lock().unwrap().take();
+        match result {
+            Ok(()) if commit && aborted => Err(Error::Internal(
+                "transaction rolled back: an operation in it failed".to_string(),
+            )),
+            result => result,
+        }
+    }
+
+    /// Eagerly create up to `n` configured connections and park them in the pool.
+    ///
+    /// Call this right after construction so the first requests don't pay for
+    /// opening connections and running PRAGMAs. The pool never holds more than
+    /// its concurrency limit, counting connections currently checked out.
+    pub async fn warmup(&self, n: usize) -> Result<()> {
+        let existing = self.metrics();
+        let wanted = n
+            .min(MAX_CONNECTIONS)
+            .saturating_sub(existing.idle + existing.in_use);
+
+        let mut conns = Vec::with_capacity(wanted);
+        for _ in 0..wanted {
+            conns.push(self.connect().await?);
+        }
+        self.inner.pool.lock().await.extend(conns);
+        Ok(())
+    }
+
+    /// Get a snapshot of the pool's connection counts.
+    pub fn metrics(&self) -> PoolMetrics {
+        let idle = self
+            .inner
+            .pool
+            .try_lock()
+            .map(|pool| pool.len())
+            .unwrap_or(0);
+        PoolMetrics {
+            idle,
+            in_use: MAX_CONNECTIONS - self.inner.semaphore.available_permits(),
+            max: MAX_CONNECTIONS,
+            offline: self.is_offline(),
+        }
+    }
+
+    /// Whether the pool is serving read-only connections to the local replica
+    /// because the sync database is unreachable.
+    pub fn is_offline(&self) -> bool {
+        self.inner.offline.load(Ordering::SeqCst)
+    }
+
+    /// Open a new connection and run the configured PRAGMAs on it.
+    async fn connect(&self) -> Result<Connection> {
+        let conn = match self.inner.retry.run(|| self.connect_primary()).await {
+            Ok(conn) => {
+                self.inner.offline.store(false, Ordering::SeqCst);
+                conn
+            }
+            Err(e) => match &self.inner.offline_fallback {
+                Some(path) => {
+                    tracing::warn!(
+                        error = %e,
+                        "sync database unreachable, serving reads from the local replica"
+                    );
+                    self.inner.offline.store(true, Ordering::SeqCst);
+                    *self.inner.last_reconnect.lock().unwrap() = Some(Instant::now());
+                    let conn = Builder::new_local(path).build().await?.connect()?;
+                    conn.execute("PRAGMA query_only = 1", ()).await?;
+                    conn
+                }
+                None => return Err(e),
+            },
+        };
+        for pragma in &self.inner.pragmas {
+            conn.execute(pragma, ()).await?;
+        }
+        Ok(conn)
+    }
+
+    /// Connect to the database itself, without retries or fallback.
+    async fn connect_primary(&self) -> Result<Connection> {
+        Ok(match &self.inner.db {
+            DatabaseType::Local(db) => db.connect()?,
+            DatabaseType::Sync(db) => db.connect().await?,
+            #[cfg(test)]
+            DatabaseType::Flaky { db, failures } => {
+                if failures
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                    .is_ok()
+                {
+                    return Err(Error::Internal("remote unreachable".to_string()));
+                }
+                db.connect()?
+            }
+            #[cfg(test)]
+            DatabaseType::MockSync { db, .. } => db.connect()?,
         })
     }
 
+    /// Try once to leave offline mode, at most every `max_backoff`.
+    ///
+    /// Returns a fresh connection to the sync database on success, which
+    /// replaces the fallback connection being handed out.
+    async fn reconnect(&self) -> Option<Connection> {
+        {
+            let mut last = self.inner.last_reconnect.lock().unwrap();
+            if last.is_some_and(|at| at.elapsed() < self.inner.retry.max_backoff) {
+                return None;
+            }
+            *last = Some(Instant::now());
+        }
+        let conn = self.connect_primary().await.ok()?;
+        for pragma in &self.inner.pragmas {
+            conn.execute(pragma, ()).await.ok()?;
+        }
+        tracing::info!("sync database reachable again, leaving offline mode");
+        self.inner.offline.store(false, Ordering::SeqCst);
+        Some(conn)
+    }
+
     /// Get the underlying database reference (for creating additional connections).
     /// Returns None if this is a sync database.
     pub fn database(&self) -> Option<&Database> {
         match &self.inner.db {
             DatabaseType::Local(db) => Some(db),
             DatabaseType::Sync(_) => None,
+            #[cfg(test)]
+            DatabaseType::Flaky { db, .. } => Some(db),
+            #[cfg(test)]
+            DatabaseType::MockSync { .. } => None,
         }
     }
 
@@ -121,10 +640,97 @@ impl ConnectionPool {
         match &self.inner.db {
             DatabaseType::Local(_) => None,
             DatabaseType::Sync(db) => Some(db),
+            #[cfg(test)]
+            DatabaseType::Flaky { .. } => None,
+            #[cfg(test)]
+            DatabaseType::MockSync { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+impl ConnectionPool {
+    /// A pool of `db` that behaves like a sync replica, whose pulls apply
+    /// the changes queued with `queue_remote_change()`
+    pub(crate) fn mock_sync(db: Database) -> Self {
+        Self::build(
+            DatabaseType::MockSync {
+                db,
+                remote: std::sync::Mutex::new(Vec::new()),
+            },
+            DEFAULT_TIMEOUT,
+            Vec::new(),
+        )
+    }
+
+    /// Queue a statement for the next pull of a `mock_sync()` pool
+    pub(crate) fn queue_remote_change(&self, sql: &str) {
+        match &self.inner.db {
+            DatabaseType::MockSync { remote, .. } => remote.lock().unwrap().push(sql.to_string()),
+            _ => panic!("not a mock sync pool"),
         }
     }
 }
 
+/// Connection counts reported by `ConnectionPool::metrics()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolMetrics {
+    /// Connections parked in the pool, ready to be handed out
+    pub idle: usize,
+    /// Connections currently checked out
+    pub in_use: usize,
+    /// Maximum number of concurrent connections
+    pub max: usize,
+    /// Sync database unreachable; connections are read-only to the local replica
+    pub offline: bool,
+}
+
+/// Wait for a permit of `semaphore`, up to `timeout`.
+async fn acquire(semaphore: &Arc<Semaphore>, timeout: Duration) -> Result<OwnedSemaphorePermit> {
+    tokio::time::timeout(timeout, Arc::clone(semaphore).acquire_owned())
+        .await
+        .map_err(|_| Error::ConnectionPoolTimeout)?
+        .map_err(|_| Error::Internal("semaphore closed".to_string()))
+}
+
+/// State of a transaction started with `ConnectionPool::begin()`.
+struct PoolTransaction {
+    /// Pool whose slot the transaction holds
+    pool: Arc<ConnectionPoolInner>,
+    /// Connection the transaction runs on, while no caller is using it
+    conn: std::sync::Mutex<Option<Connection>>,
+    /// Lets one caller at a time use the transaction's connection
+    semaphore: Arc<Semaphore>,
+    /// Slot of the pool held until the transaction ends
+    permit: std::sync::Mutex<Option<OwnedSemaphorePermit>>,
+    /// Committed or rolled back
+    finished: AtomicBool,
+    /// An operation failed part way, so the transaction can only roll back
+    aborted: AtomicBool,
+}
+
+impl Drop for PoolTransaction {
+    fn drop(&mut self) {
+        let Some(conn) = self.conn.get_mut().unwrap().take() else {
+            return;
+        };
+        // Roll back a transaction left open, keeping its slot until done so
+        // no other connection runs into its lock. Without a runtime to roll
+        // back on, the connection is closed, which rolls back as well.
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let pool = self.pool.clone();
+        let permit = self.permit.get_mut().unwrap().take();
+        runtime.spawn(async move {
+            if conn.execute("ROLLBACK", ()).await.is_ok() {
+                pool.pool.lock().await.push(conn);
+            }
+            drop(permit);
+        });
+    }
+}
+
 /// A connection borrowed from the pool.
 ///
 /// When dropped, the connection is returned to the pool for reuse and the
@@ -133,7 +739,9 @@ pub struct PooledConnection {
     conn: Option<Connection>,
     pool: Arc<ConnectionPoolInner>,
     /// Held permit - released when this is dropped
-    _permit: OwnedSemaphorePermit,
+    _permit: Option<OwnedSemaphorePermit>,
+    /// Transaction the connection is handed out for, if any
+    tx: Option<Arc<PoolTransaction>>,
 }
 
 impl PooledConnection {
@@ -141,6 +749,25 @@ impl PooledConnection {
     pub fn connection(&self) -> &Connection {
         self.conn.as_ref().expect("connection already taken")
     }
+
+    /// Whether the connection runs a transaction started with
+    /// `ConnectionPool::begin()`.
+    ///
+    /// Operations then join that transaction instead of starting their own.
+    pub fn in_transaction(&self) -> bool {
+        self.tx.is_some()
+    }
+
+    /// Mark the transaction of the connection as failed, so that it can
+    /// only be rolled back.
+    ///
+    /// For operations that fail after writing part of their changes, which
+    /// they cannot undo on their own inside the transaction.
+    pub fn abort_transaction(&self) {
+        if let Some(tx) = &self.tx {
+            tx.aborted.store(true, Ordering::SeqCst);
+        }
+    }
 }
 
 impl std::ops::Deref for PooledConnection {
@@ -153,7 +780,13 @@ impl std::ops::Deref for PooledConnection {
 
 impl Drop for PooledConnection {
     fn drop(&mut self) {
-        if let Some(conn) = self.conn.take() {
+        let Some(conn) = self.conn.take() else {
+            return;
+        };
+        if let Some(tx) = &self.tx {
+            // The connection of a transaction is only handed out by it
+            *tx.conn.lock().unwrap() = Some(conn);
+        } else {
             // Return connection to pool - use try_lock to avoid blocking in drop
             // If we can't get the lock, just drop the connection (it will be recreated)
             if let Ok(mut pool) = self.pool.pool.try_lock() {
@@ -251,4 +884,261 @@ mod tests {
         // All 5 should have completed (serially, since max=1)
         assert_eq!(counter.load(Ordering::SeqCst), 5);
     }
+
+    #[tokio::test]
+    async fn test_connection_pool_warmup() {
+        let db = Builder::new_local(":memory:").build().await.unwrap();
+        let pool = ConnectionPool::new_with_pragmas(db, vec!["PRAGMA cache_size = -4096".into()]);
+        assert_eq!(pool.metrics().idle, 0);
+
+        // Warmup fills the pool up to its limit without checking anything out
+        pool.warmup(4).await.unwrap();
+        let metrics = pool.metrics();
+        assert_eq!(metrics.idle, 4.min(metrics.max));
+        assert_eq!(metrics.in_use, 0);
+
+        // Warming up again does not grow the pool past its limit
+        pool.warmup(4).await.unwrap();
+        assert_eq!(pool.metrics().idle, 4.min(metrics.max));
+
+        // Warmed connections are configured and handed out as-is
+        let conn = pool.get_connection().await.unwrap();
+        assert_eq!(
+            pool.metrics(),
+            PoolMetrics {
+                idle: 4.min(metrics.max) - 1,
+                in_use: 1,
+                max: metrics.max,
+                offline: false,
+            }
+        );
+        let mut rows = conn.query("PRAGMA cache_size", ()).await.unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get_value(0).unwrap().as_integer().copied(), Some(-4096));
+    }
+
+    /// A pool whose first `failures` connects fail, like an unreachable remote
+    async fn flaky_pool(
+        failures: usize,
+        retry: RetryPolicy,
+        fallback: Option<String>,
+    ) -> ConnectionPool {
+        let db = Builder::new_local(":memory:").build().await.unwrap();
+        ConnectionPool::build_with_retry(
+            DatabaseType::Flaky {
+                db,
+                failures: AtomicUsize::new(failures),
+            },
+            DEFAULT_TIMEOUT,
+            Vec::new(),
+            retry,
+            fallback,
+        )
+    }
+
+    fn fast_retry(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(20),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_connect_retries_with_backoff() {
+        // Two failures are absorbed by three attempts
+        let pool = flaky_pool(2, fast_retry(3), None).await;
+        let conn = pool.get_connection().await.unwrap();
+        assert!(conn.conn.is_some());
+        assert!(!pool.metrics().offline);
+
+        // Without a fallback, running out of attempts is an error
+        let pool = flaky_pool(3, fast_retry(3), None).await;
+        assert!(pool.get_connection().await.is_err());
+        assert!(!pool.metrics().offline);
+    }
+
+    #[tokio::test]
+    async fn test_sync_connect_falls_back_offline_and_recovers() {
+        let dir = tempfile::tempdir().unwrap();
+        let replica = dir.path().join("replica.db");
+        let replica = replica.to_str().unwrap();
+        {
+            let db = Builder::new_local(replica).build().await.unwrap();
+            let conn = db.connect().unwrap();
+            conn.execute("CREATE TABLE t (x INTEGER)", ())
+                .await
+                .unwrap();
+            conn.execute("INSERT INTO t VALUES (42)", ()).await.unwrap();
+        }
+
+        // Both attempts fail, so the pool goes offline and reads the replica
+        let pool = flaky_pool(3, fast_retry(2), Some(replica.to_string())).await;
+        let conn = pool.get_connection().await.unwrap();
+        assert!(pool.metrics().offline);
+        let mut rows = conn.query("SELECT x FROM t", ()).await.unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get_value(0).unwrap().as_integer().copied(), Some(42));
+        drop(rows);
+        assert!(conn.execute("INSERT INTO t VALUES (1)", ()).await.is_err());
+        drop(conn);
+
+        // The next reconnect attempt still fails (the last simulated failure)
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        drop(pool.get_connection().await.unwrap());
+        assert!(pool.metrics().offline);
+
+        // Once the remote is back, the pool leaves offline mode
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        let conn = pool.get_connection().await.unwrap();
+        assert!(!pool.metrics().offline);
+        conn.execute("CREATE TABLE online (x INTEGER)", ())
+            .await
+            .unwrap();
+    }
+
+    /// Count the rows of `t` through the pool
+    async fn count_rows(pool: &ConnectionPool) -> i64 {
+        let conn = pool.get_connection().await.unwrap();
+        let mut rows = conn.query("SELECT COUNT(*) FROM t", ()).await.unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        let count = *row.get_value(0).unwrap().as_integer().unwrap();
+        count
+    }
+
+    #[tokio::test]
+    async fn test_sync_now_pulls_remote_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("replica.db");
+        let db = Builder::new_local(path.to_str().unwrap())
+            .build()
+            .await
+            .unwrap();
+        let pool = ConnectionPool::mock_sync(db);
+        let pulls = Arc::new(AtomicUsize::new(0));
+        let counter = pulls.clone();
+        pool.on_sync(Arc::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }));
+
+        let conn = pool.get_connection().await.unwrap();
+        conn.execute("CREATE TABLE t (x INTEGER)", ())
+            .await
+            .unwrap();
+        drop(conn);
+
+        // Remote changes stay invisible until pulled
+        pool.queue_remote_change("INSERT INTO t VALUES (1)");
+        assert_eq!(count_rows(&pool).await, 0);
+        assert_eq!(pulls.load(Ordering::SeqCst), 0);
+
+        pool.sync_now().await.unwrap();
+        assert_eq!(count_rows(&pool).await, 1);
+        assert_eq!(pulls.load(Ordering::SeqCst), 1);
+
+        // Local databases have nothing to pull
+        let db = Builder::new_local(":memory:").build().await.unwrap();
+        assert!(matches!(
+            ConnectionPool::new(db).sync_now().await,
+            Err(Error::SyncNotEnabled)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_sync_refresh_pulls_on_checkout() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("replica.db");
+        let db = Builder::new_local(path.to_str().unwrap())
+            .build()
+            .await
+            .unwrap();
+        let pool = ConnectionPool::mock_sync(db).with_sync_refresh(SyncRefresh::OnCheckout);
+        pool.queue_remote_change("CREATE TABLE t (x INTEGER)");
+        assert_eq!(count_rows(&pool).await, 0);
+
+        pool.queue_remote_change("INSERT INTO t VALUES (1)");
+        assert_eq!(count_rows(&pool).await, 1);
+
+        // With an interval, a recent pull is not repeated
+        let pool = pool.with_sync_refresh(SyncRefresh::Interval(Duration::from_secs(3600)));
+        pool.queue_remote_change("INSERT INTO t VALUES (2)");
+        assert_eq!(count_rows(&pool).await, 1);
+        pool.sync_now().await.unwrap();
+        assert_eq!(count_rows(&pool).await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_commits_or_rolls_back() {
+        let db = Builder::new_local(":memory:").build().await.unwrap();
+        let pool = ConnectionPool::with_timeout(DatabaseType::Local(db), Duration::from_millis(50));
+        pool.get_connection()
+            .await
+            .unwrap()
+            .execute("CREATE TABLE t (x INTEGER)", ())
+            .await
+            .unwrap();
+
+        let tx = pool.begin().await.unwrap();
+        let conn = tx.get_connection().await.unwrap();
+        assert!(conn.in_transaction());
+        conn.execute("INSERT INTO t VALUES (1)", ()).await.unwrap();
+        drop(conn);
+        // The transaction holds the pool until it ends
+        assert!(matches!(
+            pool.get_connection().await,
+            Err(Error::ConnectionPoolTimeout)
+        ));
+        tx.rollback().await.unwrap();
+        assert_eq!(count_rows(&pool).await, 0);
+        assert!(tx.get_connection().await.is_err());
+
+        let tx = pool.begin().await.unwrap();
+        tx.get_connection()
+            .await
+            .unwrap()
+            .execute("INSERT INTO t VALUES (1)", ())
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+        assert_eq!(count_rows(&pool).await, 1);
+
+        // An aborted transaction refuses to commit
+        let tx = pool.begin().await.unwrap();
+        let conn = tx.get_connection().await.unwrap();
+        conn.execute("INSERT INTO t VALUES (2)", ()).await.unwrap();
+        conn.abort_transaction();
+        drop(conn);
+        assert!(tx.commit().await.is_err());
+        assert_eq!(count_rows(&pool).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_transaction_rolls_back() {
+        let db = Builder::new_local(":memory:").build().await.unwrap();
+        let pool = ConnectionPool::with_timeout(DatabaseType::Local(db), Duration::from_secs(1));
+        pool.get_connection()
+            .await
+            .unwrap()
+            .execute("CREATE TABLE t (x INTEGER)", ())
+            .await
+            .unwrap();
+
+        let tx = pool.begin().await.unwrap();
+        tx.get_connection()
+            .await
+            .unwrap()
+            .execute("INSERT INTO t VALUES (1)", ())
+            .await
+            .unwrap();
+
+        // Dropped while the idle connections are locked, the transaction
+        // still ends instead of leaving its connection to the next caller
+        let idle = pool.inner.pool.lock().await;
+        drop(tx);
+        drop(idle);
+        let conn = pool.get_connection().await.unwrap();
+        assert!(conn.is_autocommit().unwrap());
+        drop(conn);
+        assert_eq!(count_rows(&pool).await, 0);
+    }
 }
@@ -6,8 +6,11 @@ use thiserror::Error;
 #[derive(Debug, Error)]
 pub enum Error {
     /// Database error from turso
+    ///
+    /// Errors reporting a full disk are converted to `FsError::NoSpace`
+    /// instead, so they surface as ENOSPC.
     #[error("database error: {0}")]
-    Database(#[from] turso::Error),
+    Database(turso::Error),
 
     /// IO error
     #[error("io error: {0}")]
@@ -61,6 +64,10 @@ pub enum Error {
     #[error("encryption not supported: {0}")]
     EncryptionNotSupported(String),
 
+    /// Read-only open not supported for this configuration
+    #[error("read-only open not supported: {0}")]
+    ReadOnlyNotSupported(String),
+
     /// Invalid encryption key
     #[error("invalid encryption key: {0}")]
     InvalidEncryptionKey(String),
@@ -74,5 +81,54 @@ pub enum Error {
     SchemaVersionMismatch { found: String, expected: String },
 }
 
+impl From<turso::Error> for Error {
+    fn from(err: turso::Error) -> Self {
+        if is_disk_full(&err) {
+            Error::Fs(crate::filesystem::FsError::NoSpace)
+        } else {
+            Error::Database(err)
+        }
+    }
+}
+
+/// Check whether a database error reports a full disk: SQLITE_FULL, or
+/// ENOSPC from turso's I/O layer.
+fn is_disk_full(err: &turso::Error) -> bool {
+    match err {
+        turso::Error::DatabaseFull(_) => true,
+        turso::Error::IoError(kind) => *kind == std::io::ErrorKind::StorageFull,
+        _ => false,
+    }
+}
+
 /// Result type alias using the SDK Error type.
 pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disk_full_errors_become_no_space() {
+        let full = [
+            turso::Error::DatabaseFull("database or disk is full".to_string()),
+            turso::Error::IoError(std::io::Error::from_raw_os_error(libc::ENOSPC).kind()),
+        ];
+        for err in full {
+            assert!(matches!(
+                Error::from(err),
+                Error::Fs(crate::filesystem::FsError::NoSpace)
+            ));
+        }
+
+        let other = [
+            turso::Error::Busy("database is locked".to_string()),
+            turso::Error::IoError(std::io::ErrorKind::PermissionDenied),
+            // Only the variant counts, not what the message says
+            turso::Error::Error("no space left on device".to_string()),
+        ];
+        for err in other {
+            assert!(matches!(Error::from(err), Error::Database(_)));
+        }
+    }
+}
@@ -1,29 +1,202 @@
 use crate::error::{Error, Result};
 use async_trait::async_trait;
 use lru::LruCache;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
 use std::num::NonZeroUsize;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
 use turso::transaction::{Transaction, TransactionBehavior};
 use turso::{Builder, Connection, Value};
 
+use super::block_lru::BlockLru;
 use super::{
     BoxedFile, DirEntry, File, FileSystem, FilesystemStats, FsError, Stats, TimeChange,
-    DEFAULT_DIR_MODE, DEFAULT_FILE_MODE, MAX_NAME_LEN, S_IFLNK, S_IFMT, S_IFREG,
+    DEFAULT_DIR_MODE, DEFAULT_FILE_MODE, FS_APPEND_FL, FS_IMMUTABLE_FL, MAX_NAME_LEN,
+    RENAME_EXCHANGE, RENAME_NOREPLACE, S_IFDIR, S_IFLNK, S_IFMT, S_IFREG,
 };
-use crate::connection_pool::ConnectionPool;
+use crate::connection_pool::{ConnectionPool, PooledConnection};
 use crate::schema::AGENTFS_SCHEMA_VERSION;
 
 const ROOT_INO: i64 = 1;
 const DEFAULT_CHUNK_SIZE: usize = 4096;
 const DENTRY_CACHE_MAX_SIZE: usize = 10000;
+/// Number of inode rows fetched per query by `InodeIter`
+const INODE_ITER_BATCH_SIZE: i64 = 256;
+/// Columns holding inode numbers of this filesystem, as (table, column),
+/// which `AgentFS::optimize` renumbers
+const INODE_COLUMNS: &[(&str, &str)] = &[
+    ("fs_inode", "ino"),
+    ("fs_dentry", "parent_ino"),
+    ("fs_dentry", "ino"),
+    ("fs_data", "ino"),
+    ("fs_symlink", "ino"),
+    ("fs_origin", "delta_ino"),
+];
+
+/// CRC32C (Castagnoli) lookup table, reflected polynomial 0x82F63B78
+const CRC32C_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x82F6_3B78
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Compute the CRC32C checksum of a chunk
+fn crc32c(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &b| {
+        CRC32C_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Checksum to store alongside a chunk, or NULL when checksums are disabled
+///
+/// Writers without checksums enabled clear the column rather than leave a
+/// stale value behind, so a later verified read never trips over it.
+fn chunk_checksum(enabled: bool, data: &[u8]) -> Value {
+    if enabled {
+        Value::Integer(crc32c(data) as i64)
+    } else {
+        Value::Null
+    }
+}
+
+/// Fail with `FsError::FileTooLarge` if a file would grow past `max_file_size`
+fn check_file_size(max_file_size: Option<u64>, end: Option<u64>) -> Result<()> {
+    match (max_file_size, end) {
+        (Some(max), Some(end)) if end <= max => Ok(()),
+        (Some(_), _) => Err(FsError::FileTooLarge.into()),
+        (None, _) => Ok(()),
+    }
+}
+
+/// Fail with `FsError::NotPermitted` if the inode has any of the `denied`
+/// inode flags set
+async fn check_inode_flags(conn: &Connection, ino: i64, denied: u32) -> Result<()> {
+    let mut stmt = conn
+        .prepare_cached("SELECT flags FROM fs_inode WHERE ino = ?")
+        .await?;
+    let mut rows = stmt.query((ino,)).await?;
+    let flags = match rows.next().await? {
+        Some(row) => row
+            .get_value(0)
+            .ok()
+            .and_then(|v| v.as_integer().copied())
+            .unwrap_or(0) as u32,
+        None => 0,
+    };
+    if flags & denied != 0 {
+        return Err(FsError::NotPermitted.into());
+    }
+    Ok(())
+}
+
+/// Verify a chunk read from the database against its stored checksum
+///
+/// Chunks written without checksums enabled have no checksum and are not
+/// verified.
+fn verify_chunk(
+    ino: i64,
+    chunk_index: i64,
+    chunk_size: usize,
+    data: &[u8],
+    stored: Value,
+) -> Result<()> {
+    let Value::Integer(expected) = stored else {
+        return Ok(());
+    };
+    let actual = crc32c(data) as i64;
+    if actual != expected {
+        tracing::error!(
+            ino,
+            offset = chunk_index as u64 * chunk_size as u64,
+            expected,
+            actual,
+            "checksum mismatch reading chunk"
+        );
+        return Err(FsError::ChecksumMismatch.into());
+    }
+    Ok(())
+}
+
+/// Transaction of a single operation
+///
+/// On a connection handed out for a transaction started with
+/// [`AgentFS::begin_tx`], the operation joins that transaction instead of
+/// starting its own: committing is left to the enclosing transaction, and an
+/// operation that ends without committing aborts it, as its partial changes
+/// cannot be undone on their own.
+struct OpTxn<'a> {
+    /// Transaction of this operation alone
+    own: Option<Transaction<'a>>,
+    /// Connection of the enclosing transaction, until the operation commits
+    enclosing: Option<&'a PooledConnection>,
+}
+
+impl<'a> OpTxn<'a> {
+    async fn begin(conn: &'a PooledConnection) -> Result<Self> {
+        if conn.in_transaction() {
+            return Ok(Self {
+                own: None,
+                enclosing: Some(conn),
+            });
+        }
+        let txn = Transaction::new_unchecked(conn, TransactionBehavior::Immediate).await?;
+        Ok(Self {
+            own: Some(txn),
+            enclosing: None,
+        })
+    }
+
+    async fn commit(mut self) -> Result<()> {
+        self.enclosing = None;
+        if let Some(txn) = self.own.take() {
+            txn.commit().await?;
+        }
+        Ok(())
+    }
+
+    async fn rollback(mut self) -> Result<()> {
+        if let Some(txn) = self.own.take() {
+            txn.rollback().await?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for OpTxn<'_> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.enclosing {
+            conn.abort_transaction();
+        }
+    }
+}
 
 /// LRU cache for directory entry lookups.
 ///
 /// Maps (parent_ino, name) -> child_ino to avoid repeated database queries
 /// during path resolution. For a path like `/a/b/c/d`, this reduces queries
 /// from 4 to potentially 0 on cache hits.
+///
+/// Entries are keyed by the parent's inode rather than its path, so renaming
+/// a directory only invalidates the entry naming the directory itself. Its
+/// descendants keep their inode-keyed entries, and a path through the old
+/// name misses the cache at the renamed component and resolves afresh.
 struct DentryCache {
     // Mutex required because LruCache::get() mutates internal order
     entries: Mutex<LruCache<(i64, String), i64>>,
@@ -62,6 +235,96 @@ impl DentryCache {
             .unwrap()
             .pop(&(parent_ino, name.to_string()));
     }
+
+    /// Drop every entry, e.g. after a pull replaced the directory tree
+    fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// Move the entry of `ino` from its old name to its new one.
+    ///
+    /// Both names are updated under one lock, so no lookup observes the
+    /// inode under both names or a replaced inode under the new one.
+    fn rename(&self, old_parent: i64, old_name: &str, new_parent: i64, new_name: &str, ino: i64) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.pop(&(old_parent, old_name.to_string()));
+        entries.put((new_parent, new_name.to_string()), ino);
+    }
+}
+
+/// Callback notified of the inode of a file whose contents changed
+pub type ChangeHook = Arc<dyn Fn(i64) + Send + Sync>;
+
+/// Read-through cache of file chunks, shareable across `AgentFS` instances
+///
+/// Instances opened on the same database can share one cache through
+/// [`AgentFS::with_chunk_cache`], so a chunk one of them reads is served from
+/// memory to the others. Entries are keyed by inode and chunk index. Each
+/// inode has a generation that writes through any sharing instance bump once
+/// they commit, and entries filled under an older generation are never
+/// returned. Writers that do not share the cache are not seen, so share a
+/// cache only between instances of the same database that are read-only or
+/// are its only writers.
+pub struct ChunkCache {
+    chunks: Mutex<BlockLru<Arc<Vec<u8>>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ChunkCache {
+    /// Create a cache holding up to `capacity` chunks
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            chunks: Mutex::new(BlockLru::new(capacity)),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Get the number of chunk lookups served from the cache
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Get the number of chunk lookups that had to read the database
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Get the current data generation of an inode
+    fn generation(&self, ino: i64) -> u64 {
+        self.chunks.lock().unwrap().generation(ino)
+    }
+
+    /// Look up a chunk, ignoring entries from an older generation
+    fn get(&self, ino: i64, chunk_index: u64) -> Option<Arc<Vec<u8>>> {
+        let found = self.chunks.lock().unwrap().get(ino, chunk_index);
+        let counter = if found.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        found
+    }
+
+    /// Fill a chunk read while its inode was at `generation`
+    ///
+    /// Dropped if a write committed since, as the data may predate it.
+    fn insert(&self, ino: i64, chunk_index: u64, generation: u64, data: Arc<Vec<u8>>) {
+        let mut chunks = self.chunks.lock().unwrap();
+        chunks.insert(ino, chunk_index, generation, data);
+    }
+
+    /// Invalidate every cached chunk of an inode after a committed write
+    fn invalidate(&self, ino: i64) {
+        self.chunks.lock().unwrap().invalidate(ino);
+    }
+
+    /// Drop every cached chunk, e.g. after a pull changed the database
+    fn clear(&self) {
+        self.chunks.lock().unwrap().clear();
+    }
 }
 
 /// A filesystem backed by SQLite
@@ -71,6 +334,83 @@ pub struct AgentFS {
     chunk_size: usize,
     /// Cache for directory entry lookups (shared across clones)
     dentry_cache: Arc<DentryCache>,
+    /// Inode generation reported in stats (see `read_inode_generation`)
+    generation: u64,
+    /// Store per-chunk checksums on write and verify them on read
+    checksums: bool,
+    /// Largest size a file may grow to, unlimited if `None`
+    max_file_size: Option<u64>,
+    /// Chunk cache shared with other instances, if any
+    chunk_cache: Option<Arc<ChunkCache>>,
+    /// Called with the inode of every file whose contents change
+    change_hook: Option<ChangeHook>,
+}
+
+/// An entry created by [`AgentFS::create_batch`]
+#[derive(Debug, Clone)]
+pub enum BatchEntry {
+    /// A directory with the given permission bits
+    Dir { path: String, mode: u32 },
+    /// A regular file with the given permission bits and contents
+    File {
+        path: String,
+        mode: u32,
+        data: Vec<u8>,
+    },
+    /// A symbolic link pointing at `target`
+    Symlink { path: String, target: String },
+}
+
+impl BatchEntry {
+    fn path(&self) -> &str {
+        match self {
+            BatchEntry::Dir { path, .. }
+            | BatchEntry::File { path, .. }
+            | BatchEntry::Symlink { path, .. } => path,
+        }
+    }
+}
+
+/// Iterator over every inode in an AgentFS database, in inode order.
+///
+/// Returned by [`AgentFS::iter_inodes`]. Rows are fetched in fixed-size
+/// batches keyed on the last inode seen, so memory use is bounded and no
+/// connection is held between calls to [`InodeIter::next`]. Inodes created
+/// or removed while iterating may or may not be yielded.
+pub struct InodeIter {
+    pool: ConnectionPool,
+    generation: u64,
+    /// Highest inode number yielded so far
+    last_ino: i64,
+    batch: std::collections::VecDeque<Stats>,
+    done: bool,
+}
+
+impl InodeIter {
+    /// Get the next inode and its attributes, or `None` once all are yielded
+    pub async fn next(&mut self) -> Result<Option<(i64, Stats)>> {
+        if self.batch.is_empty() && !self.done {
+            self.fetch_batch().await?;
+        }
+        Ok(self.batch.pop_front().map(|stats| {
+            self.last_ino = stats.ino;
+            (stats.ino, stats)
+        }))
+    }
+
+    async fn fetch_batch(&mut self) -> Result<()> {
+        let conn = self.pool.get_connection().await?;
+        let mut stmt = conn
+            .prepare_cached("SELECT ino, mode, nlink, uid, gid, size, atime, mtime, ctime, rdev, atime_nsec, mtime_nsec, ctime_nsec, btime, btime_nsec FROM fs_inode WHERE ino > ? ORDER BY ino LIMIT ?")
+            .await?;
+        let mut rows = stmt.query((self.last_ino, INODE_ITER_BATCH_SIZE)).await?;
+        while let Some(row) = rows.next().await? {
+            self.batch
+                .push_back(AgentFS::build_stats_from_row(&row, self.generation)?);
+        }
+        self.done = (self.batch.len() as i64) < INODE_ITER_BATCH_SIZE;
+        Ok(())
+    }
 }
 
 /// An open file handle for AgentFS.
@@ -81,6 +421,11 @@ pub struct AgentFSFile {
     pool: ConnectionPool,
     ino: i64,
     chunk_size: usize,
+    generation: u64,
+    checksums: bool,
+    max_file_size: Option<u64>,
+    chunk_cache: Option<Arc<ChunkCache>>,
+    change_hook: Option<ChangeHook>,
 }
 
 #[async_trait]
@@ -114,24 +459,16 @@ impl File for AgentFSFile {
         let start_chunk = offset / chunk_size;
         let end_chunk = (offset + size).saturating_sub(1) / chunk_size;
 
-        let mut stmt = conn
-            .prepare_cached("SELECT chunk_index, data FROM fs_data WHERE ino = ? AND chunk_index >= ? AND chunk_index <= ? ORDER BY chunk_index")
-            .await?;
-        let mut rows = stmt
-            .query((self.ino, start_chunk as i64, end_chunk as i64))
-            .await?;
+        let chunks = match self.cached_chunks(start_chunk, end_chunk) {
+            Some(chunks) => chunks,
+            None => self.load_chunks(&conn, start_chunk, end_chunk).await?,
+        };
 
         let mut result = Vec::with_capacity(size as usize);
         let start_offset_in_chunk = (offset % chunk_size) as usize;
         let mut next_expected_chunk = start_chunk;
 
-        while let Some(row) = rows.next().await? {
-            let chunk_index = row
-                .get_value(0)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0) as u64;
-
+        for (chunk_index, chunk_data) in chunks {
             // Fill gaps with zeros for sparse files
             while next_expected_chunk < chunk_index && result.len() < size as usize {
                 let skip = if next_expected_chunk == start_chunk {
@@ -145,31 +482,29 @@ impl File for AgentFSFile {
                 next_expected_chunk += 1;
             }
 
-            if let Ok(Value::Blob(chunk_data)) = row.get_value(1) {
-                let skip = if chunk_index == start_chunk {
-                    start_offset_in_chunk
-                } else {
-                    0
-                };
-                if skip >= chunk_data.len() {
-                    // Chunk is smaller than skip offset, fill with zeros
-                    let zeros_needed =
-                        std::cmp::min(chunk_size as usize - skip, size as usize - result.len());
+            let skip = if chunk_index == start_chunk {
+                start_offset_in_chunk
+            } else {
+                0
+            };
+            if skip >= chunk_data.len() {
+                // Chunk is smaller than skip offset, fill with zeros
+                let zeros_needed =
+                    std::cmp::min(chunk_size as usize - skip, size as usize - result.len());
+                result.extend(std::iter::repeat_n(0u8, zeros_needed));
+            } else {
+                let remaining = size as usize - result.len();
+                let take = std::cmp::min(chunk_data.len() - skip, remaining);
+                result.extend_from_slice(&chunk_data[skip..skip + take]);
+
+                // If chunk is smaller than chunk_size, pad with zeros
+                let chunk_end = skip + take;
+                if chunk_end < chunk_size as usize && result.len() < size as usize {
+                    let zeros_needed = std::cmp::min(
+                        chunk_size as usize - chunk_end,
+                        size as usize - result.len(),
+                    );
                     result.extend(std::iter::repeat_n(0u8, zeros_needed));
-                } else {
-                    let remaining = size as usize - result.len();
-                    let take = std::cmp::min(chunk_data.len() - skip, remaining);
-                    result.extend_from_slice(&chunk_data[skip..skip + take]);
-
-                    // If chunk is smaller than chunk_size, pad with zeros
-                    let chunk_end = skip + take;
-                    if chunk_end < chunk_size as usize && result.len() < size as usize {
-                        let zeros_needed = std::cmp::min(
-                            chunk_size as usize - chunk_end,
-                            size as usize - result.len(),
-                        );
-                        result.extend(std::iter::repeat_n(0u8, zeros_needed));
-                    }
                 }
             }
             next_expected_chunk = chunk_index + 1;
@@ -187,22 +522,30 @@ impl File for AgentFSFile {
         if data.is_empty() {
             return Ok(());
         }
+        check_file_size(self.max_file_size, offset.checked_add(data.len() as u64))?;
 
         let conn = self.pool.get_connection().await?;
-        let txn = Transaction::new_unchecked(&conn, TransactionBehavior::Immediate).await?;
+        let txn = OpTxn::begin(&conn).await?;
         // Get current file size
         let mut stmt = conn
-            .prepare_cached("SELECT size FROM fs_inode WHERE ino = ?")
+            .prepare_cached("SELECT size, flags FROM fs_inode WHERE ino = ?")
             .await?;
         let mut rows = stmt.query((self.ino,)).await?;
-        let current_size = if let Some(row) = rows.next().await? {
-            row.get_value(0)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0) as u64
+        let (current_size, flags) = if let Some(row) = rows.next().await? {
+            let column = |i| {
+                row.get_value(i)
+                    .ok()
+                    .and_then(|v| v.as_integer().copied())
+                    .unwrap_or(0)
+            };
+            (column(0) as u64, column(1) as u32)
         } else {
-            0
+            (0, 0)
         };
+        if flags & FS_IMMUTABLE_FL != 0 {
+            let _ = txn.rollback().await;
+            return Err(FsError::NotPermitted.into());
+        }
 
         // Write the actual data (sparse gaps are handled by pread which fills
         // missing chunks with zeros, so no need to zero-fill here)
@@ -220,30 +563,38 @@ impl File for AgentFSFile {
         stmt.execute((new_size as i64, now_secs, now_nsec, self.ino))
             .await?;
         txn.commit().await?;
+        self.data_changed();
 
         Ok(())
     }
 
     async fn truncate(&self, new_size: u64) -> Result<()> {
+        check_file_size(self.max_file_size, Some(new_size))?;
         let conn = self.pool.get_connection().await?;
 
         // Get current size
         let mut stmt = conn
-            .prepare_cached("SELECT size FROM fs_inode WHERE ino = ?")
+            .prepare_cached("SELECT size, flags FROM fs_inode WHERE ino = ?")
             .await?;
         let mut rows = stmt.query((self.ino,)).await?;
-        let current_size = if let Some(row) = rows.next().await? {
-            row.get_value(0)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0) as u64
+        let (current_size, flags) = if let Some(row) = rows.next().await? {
+            let column = |i| {
+                row.get_value(i)
+                    .ok()
+                    .and_then(|v| v.as_integer().copied())
+                    .unwrap_or(0)
+            };
+            (column(0) as u64, column(1) as u32)
         } else {
-            0
+            (0, 0)
         };
+        if flags & FS_IMMUTABLE_FL != 0 || (flags & FS_APPEND_FL != 0 && new_size < current_size) {
+            return Err(FsError::NotPermitted.into());
+        }
 
         let chunk_size = self.chunk_size as u64;
 
-        let txn = Transaction::new_unchecked(&conn, TransactionBehavior::Immediate).await?;
+        let txn = OpTxn::begin(&conn).await?;
 
         let result: Result<()> = async {
             if new_size == 0 {
@@ -275,10 +626,11 @@ impl File for AgentFSFile {
                         if let Ok(Value::Blob(mut chunk_data)) = row.get_value(0) {
                             if chunk_data.len() > offset_in_chunk {
                                 chunk_data.truncate(offset_in_chunk);
+                                let checksum = chunk_checksum(self.checksums, &chunk_data);
                                 let mut stmt = conn
-                                    .prepare_cached("UPDATE fs_data SET data = ? WHERE ino = ? AND chunk_index = ?")
+                                    .prepare_cached("UPDATE fs_data SET data = ?, checksum = ? WHERE ino = ? AND chunk_index = ?")
                                     .await?;
-                                stmt.execute((Value::Blob(chunk_data), self.ino, last_chunk_idx as i64)).await?;
+                                stmt.execute((Value::Blob(chunk_data), checksum, self.ino, last_chunk_idx as i64)).await?;
                             }
                         }
                     }
@@ -305,11 +657,16 @@ impl File for AgentFSFile {
             return result;
         }
         txn.commit().await?;
+        self.data_changed();
         Ok(())
     }
 
     async fn fsync(&self) -> Result<()> {
         let conn = self.pool.get_connection().await?;
+        if conn.in_transaction() {
+            // Committing the enclosing transaction makes the data durable
+            return Ok(());
+        }
         conn.prepare_cached("PRAGMA synchronous = FULL")
             .await?
             .execute(())
@@ -326,19 +683,170 @@ impl File for AgentFSFile {
     async fn fstat(&self) -> Result<Stats> {
         let conn = self.pool.get_connection().await?;
         let mut stmt = conn
-            .prepare_cached("SELECT ino, mode, nlink, uid, gid, size, atime, mtime, ctime, rdev, atime_nsec, mtime_nsec, ctime_nsec FROM fs_inode WHERE ino = ?")
+            .prepare_cached("SELECT ino, mode, nlink, uid, gid, size, atime, mtime, ctime, rdev, atime_nsec, mtime_nsec, ctime_nsec, btime, btime_nsec FROM fs_inode WHERE ino = ?")
             .await?;
         let mut rows = stmt.query((self.ino,)).await?;
 
         if let Some(row) = rows.next().await? {
-            AgentFS::build_stats_from_row(&row)
+            AgentFS::build_stats_from_row(&row, self.generation)
         } else {
             Err(FsError::NotFound.into())
         }
     }
+
+    async fn seek_data(&self, offset: u64) -> Result<u64> {
+        let conn = self.pool.get_connection().await?;
+        let size = self.size_with_conn(&conn).await?;
+        if offset >= size {
+            return Err(FsError::NoSuchOffset.into());
+        }
+
+        // Chunks are only stored where data was written, so the first stored
+        // chunk at or after the offset starts the next data region
+        let chunk_size = self.chunk_size as u64;
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT MIN(chunk_index) FROM fs_data WHERE ino = ? AND chunk_index >= ?",
+            )
+            .await?;
+        let mut rows = stmt.query((self.ino, (offset / chunk_size) as i64)).await?;
+        let next_chunk = match rows.next().await? {
+            Some(row) => row.get_value(0).ok().and_then(|v| v.as_integer().copied()),
+            None => None,
+        };
+
+        match next_chunk {
+            Some(chunk_index) => {
+                let start = std::cmp::max(offset, chunk_index as u64 * chunk_size);
+                if start >= size {
+                    return Err(FsError::NoSuchOffset.into());
+                }
+                Ok(start)
+            }
+            None => Err(FsError::NoSuchOffset.into()),
+        }
+    }
+
+    async fn seek_hole(&self, offset: u64) -> Result<u64> {
+        let conn = self.pool.get_connection().await?;
+        let size = self.size_with_conn(&conn).await?;
+        if offset >= size {
+            return Err(FsError::NoSuchOffset.into());
+        }
+
+        // Walk the stored chunks from the offset until the first missing one
+        let chunk_size = self.chunk_size as u64;
+        let first_chunk = (offset / chunk_size) as i64;
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT chunk_index FROM fs_data WHERE ino = ? AND chunk_index >= ? ORDER BY chunk_index",
+            )
+            .await?;
+        let mut rows = stmt.query((self.ino, first_chunk)).await?;
+        let mut hole_chunk = first_chunk;
+        while let Some(row) = rows.next().await? {
+            let chunk_index = row
+                .get_value(0)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(-1);
+            if chunk_index != hole_chunk {
+                break;
+            }
+            hole_chunk += 1;
+        }
+
+        let start = std::cmp::max(offset, hole_chunk as u64 * chunk_size);
+        Ok(std::cmp::min(start, size))
+    }
 }
 
 impl AgentFSFile {
+    /// Get chunks `start..=end` from the shared cache, if all are cached
+    fn cached_chunks(&self, start: u64, end: u64) -> Option<Vec<(u64, Arc<Vec<u8>>)>> {
+        let cache = self.chunk_cache.as_ref()?;
+        (start..=end)
+            .map(|chunk_index| Some((chunk_index, cache.get(self.ino, chunk_index)?)))
+            .collect()
+    }
+
+    /// Read the stored chunks in `start..=end`, filling the shared cache
+    ///
+    /// Chunks missing from a sparse file are skipped.
+    async fn load_chunks(
+        &self,
+        conn: &Connection,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<(u64, Arc<Vec<u8>>)>> {
+        // Taken before reading, so a write committing meanwhile is noticed
+        let generation = self
+            .chunk_cache
+            .as_ref()
+            .map(|cache| cache.generation(self.ino));
+
+        let sql = if self.checksums {
+            "SELECT chunk_index, data, checksum FROM fs_data WHERE ino = ? AND chunk_index >= ? AND chunk_index <= ? ORDER BY chunk_index"
+        } else {
+            "SELECT chunk_index, data FROM fs_data WHERE ino = ? AND chunk_index >= ? AND chunk_index <= ? ORDER BY chunk_index"
+        };
+        let mut stmt = conn.prepare_cached(sql).await?;
+        let mut rows = stmt.query((self.ino, start as i64, end as i64)).await?;
+
+        let mut chunks = Vec::new();
+        while let Some(row) = rows.next().await? {
+            let chunk_index = row
+                .get_value(0)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0) as u64;
+            let Ok(Value::Blob(chunk_data)) = row.get_value(1) else {
+                continue;
+            };
+            if self.checksums {
+                verify_chunk(
+                    self.ino,
+                    chunk_index as i64,
+                    self.chunk_size,
+                    &chunk_data,
+                    row.get_value(2)?,
+                )?;
+            }
+            let chunk_data = Arc::new(chunk_data);
+            if let (Some(cache), Some(generation)) = (&self.chunk_cache, generation) {
+                cache.insert(self.ino, chunk_index, generation, chunk_data.clone());
+            }
+            chunks.push((chunk_index, chunk_data));
+        }
+        Ok(chunks)
+    }
+
+    /// Invalidate caches of this file after a committed write
+    fn data_changed(&self) {
+        if let Some(cache) = &self.chunk_cache {
+            cache.invalidate(self.ino);
+        }
+        if let Some(hook) = &self.change_hook {
+            hook(self.ino);
+        }
+    }
+
+    /// Get the current file size using a provided connection.
+    async fn size_with_conn(&self, conn: &Connection) -> Result<u64> {
+        let mut stmt = conn
+            .prepare_cached("SELECT size FROM fs_inode WHERE ino = ?")
+            .await?;
+        let mut rows = stmt.query((self.ino,)).await?;
+        match rows.next().await? {
+            Some(row) => Ok(row
+                .get_value(0)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0) as u64),
+            None => Err(FsError::NotFound.into()),
+        }
+    }
+
     /// Write data at a specific offset, handling chunk boundaries.
     /// Uses a provided connection to allow reuse within a transaction.
     async fn write_data_at_offset_with_conn(
@@ -360,7 +868,7 @@ impl AgentFSFile {
             .await?;
         let mut insert_stmt = conn
             .prepare_cached(
-                "INSERT OR REPLACE INTO fs_data (ino, chunk_index, data) VALUES (?, ?, ?)",
+                "INSERT OR REPLACE INTO fs_data (ino, chunk_index, data, checksum) VALUES (?, ?, ?, ?)",
             )
             .await?;
         while written < data.len() {
@@ -407,8 +915,9 @@ impl AgentFSFile {
             }
 
             // Save chunk
+            let checksum = chunk_checksum(self.checksums, &chunk_data);
             insert_stmt
-                .execute((self.ino, chunk_index, Value::Blob(chunk_data)))
+                .execute((self.ino, chunk_index, Value::Blob(chunk_data), checksum))
                 .await?;
             insert_stmt.reset()?;
 
@@ -426,6 +935,19 @@ impl AgentFS {
         Self::from_pool(ConnectionPool::new(db)).await
     }
 
+    /// Create a filesystem backed by a private in-memory database
+    ///
+    /// Useful for tests: nothing touches disk and there is nothing to clean
+    /// up. All pooled connections are created from the same turso `Database`,
+    /// which owns the in-memory pages, so they share one database rather than
+    /// each getting its own as with SQLite's per-connection `:memory:`. The
+    /// data lives as long as this filesystem and its clones, and is not
+    /// visible to other instances or other processes.
+    pub async fn new_in_memory() -> Result<Self> {
+        let db = Builder::new_local(":memory:").build().await?;
+        Self::from_pool(ConnectionPool::new(db)).await
+    }
+
     /// Create a filesystem from a connection pool
     pub async fn from_pool(pool: ConnectionPool) -> Result<Self> {
         let conn = pool.get_connection().await?;
@@ -442,32 +964,205 @@ impl AgentFS {
 
         // Get chunk_size from config (or use default)
         let chunk_size = Self::read_chunk_size(&conn).await?;
+        let generation = Self::read_inode_generation(&conn).await?;
 
         let fs = Self {
             pool,
             chunk_size,
             dentry_cache: Arc::new(DentryCache::new(DENTRY_CACHE_MAX_SIZE)),
+            generation,
+            checksums: false,
+            max_file_size: None,
+            chunk_cache: None,
+            change_hook: None,
         };
+        fs.clear_dentries_on_sync();
         Ok(fs)
     }
 
-    /// Get the configured chunk size
-    pub fn chunk_size(&self) -> usize {
-        self.chunk_size
+    /// Create a filesystem from a read-only connection pool
+    ///
+    /// Unlike `from_pool`, nothing is written to the database: the schema and
+    /// configuration must already exist.
+    pub async fn from_pool_readonly(pool: ConnectionPool) -> Result<Self> {
+        let conn = pool.get_connection().await?;
+
+        let chunk_size = Self::read_chunk_size(&conn).await?;
+        let generation = Self::read_inode_generation(&conn).await?;
+
+        let fs = Self {
+            pool,
+            chunk_size,
+            dentry_cache: Arc::new(DentryCache::new(DENTRY_CACHE_MAX_SIZE)),
+            generation,
+            checksums: false,
+            max_file_size: None,
+            chunk_cache: None,
+            change_hook: None,
+        };
+        fs.clear_dentries_on_sync();
+        Ok(fs)
     }
 
-    /// Get a database connection from the pool
-    pub async fn get_connection(&self) -> Result<crate::connection_pool::PooledConnection> {
-        self.pool.get_connection().await
+    /// Drop cached directory entries whenever the pool pulls remote changes
+    fn clear_dentries_on_sync(&self) {
+        let cache = Arc::downgrade(&self.dentry_cache);
+        self.pool.on_sync(Arc::new(move || {
+            if let Some(cache) = cache.upgrade() {
+                cache.clear();
+            }
+        }));
     }
 
-    /// Get the connection pool
-    pub fn get_pool(&self) -> ConnectionPool {
-        self.pool.clone()
+    /// Enable or disable per-chunk checksums
+    ///
+    /// When enabled, a CRC32C checksum is stored alongside every chunk written
+    /// and verified on every read, which fails with
+    /// `FsError::ChecksumMismatch` if the chunk was corrupted. This costs CPU
+    /// on every read and write, so it is off by default.
+    pub fn with_checksums(mut self, enabled: bool) -> Self {
+        self.checksums = enabled;
+        self
     }
 
-    /// Initialize the database schema
-    pub async fn initialize_schema(conn: &Connection) -> Result<()> {
+    /// Limit the size of every file, or lift the limit with `None`
+    ///
+    /// Writes and truncates that would extend a file past the limit fail with
+    /// `FsError::FileTooLarge`, so a single runaway file cannot exhaust the
+    /// database. Files already larger than the limit can still be read and
+    /// shrunk below it.
+    pub fn with_max_file_size(mut self, max_file_size: Option<u64>) -> Self {
+        self.max_file_size = max_file_size;
+        self
+    }
+
+    /// Serve file reads through a chunk cache shared with other instances
+    ///
+    /// See [`ChunkCache`] for when sharing is safe.
+    pub fn with_chunk_cache(mut self, cache: Arc<ChunkCache>) -> Self {
+        // Chunks pulled from a remote may differ from the cached ones
+        let weak = Arc::downgrade(&cache);
+        self.pool.on_sync(Arc::new(move || {
+            if let Some(cache) = weak.upgrade() {
+                cache.clear();
+            }
+        }));
+        self.chunk_cache = Some(cache);
+        self
+    }
+
+    /// Call `hook` with the inode of every file whose contents or size change
+    ///
+    /// The hook runs after the change commits, for writes and truncates
+    /// through this instance and its clones, including through open file
+    /// handles. Caches outside the SDK, such as the kernel's FUSE page cache,
+    /// use it to drop data that another writer made stale. It is called on
+    /// the writing task, so it should return quickly.
+    pub fn with_change_hook(mut self, hook: ChangeHook) -> Self {
+        self.change_hook = Some(hook);
+        self
+    }
+
+    /// Invalidate caches of an inode after a committed write
+    fn data_changed(&self, ino: i64) {
+        if let Some(cache) = &self.chunk_cache {
+            cache.invalidate(ino);
+        }
+        if let Some(hook) = &self.change_hook {
+            hook(ino);
+        }
+    }
+
+    /// Get the configured chunk size
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Get a database connection from the pool
+    pub async fn get_connection(&self) -> Result<PooledConnection> {
+        self.pool.get_connection().await
+    }
+
+    /// Get the connection pool
+    pub fn get_pool(&self) -> ConnectionPool {
+        self.pool.clone()
+    }
+
+    /// Start a transaction, returning a handle to the filesystem inside it
+    ///
+    /// Changes made through the handle, and through files opened with it,
+    /// are applied together by [`AgentFS::commit_tx`] or not at all: a crash
+    /// or [`AgentFS::rollback_tx`] leaves none of them in the database, and
+    /// dropping every clone of the handle without committing rolls back.
+    ///
+    /// The transaction holds the database connection until it ends. Other
+    /// users of the filesystem wait for it, so using `self` rather than the
+    /// handle before the transaction ends fails with
+    /// `Error::ConnectionPoolTimeout`. Change hooks run as each operation
+    /// completes, before the transaction commits.
+    ///
+    /// This groups calls made through the SDK, e.g. by embedders and the
+    /// CLI `fs` commands. It cannot group operations made through a mount,
+    /// which the kernel sends one at a time.
+    pub async fn begin_tx(&self) -> Result<AgentFS> {
+        Ok(AgentFS {
+            pool: self.pool.begin().await?,
+            ..self.clone()
+        })
+    }
+
+    /// Commit the transaction of a handle returned by [`AgentFS::begin_tx`]
+    ///
+    /// Fails, rolling back instead, if the commit fails or an operation in
+    /// the transaction failed after making part of its changes.
+    pub async fn commit_tx(&self) -> Result<()> {
+        let result = self.pool.commit().await;
+        if result.is_err() {
+            self.drop_caches();
+        }
+        result
+    }
+
+    /// Roll back the transaction of a handle returned by [`AgentFS::begin_tx`]
+    pub async fn rollback_tx(&self) -> Result<()> {
+        let result = self.pool.rollback().await;
+        self.drop_caches();
+        result
+    }
+
+    /// Run `f` in a transaction, committing if it succeeds and rolling back
+    /// if it fails
+    ///
+    /// `f` gets the handle of the transaction and must make its changes
+    /// through it. See [`AgentFS::begin_tx`].
+    pub async fn with_transaction<T, F, Fut>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(AgentFS) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let tx = self.begin_tx().await?;
+        match f(tx.clone()).await {
+            Ok(value) => {
+                tx.commit_tx().await?;
+                Ok(value)
+            }
+            Err(e) => {
+                let _ = tx.rollback_tx().await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Drop cached entries and chunks that a rollback may have made stale
+    fn drop_caches(&self) {
+        self.dentry_cache.clear();
+        if let Some(cache) = &self.chunk_cache {
+            cache.clear();
+        }
+    }
+
+    /// Initialize the database schema
+    pub async fn initialize_schema(conn: &Connection) -> Result<()> {
         // Create config table
         conn.execute(
             "CREATE TABLE IF NOT EXISTS fs_config (
@@ -516,6 +1211,29 @@ impl AgentFS {
         .await
         .ok();
 
+        // Add inode flags column (backward compatible migration)
+        conn.execute(
+            "ALTER TABLE fs_inode ADD COLUMN flags INTEGER NOT NULL DEFAULT 0",
+            (),
+        )
+        .await
+        .ok();
+
+        // Add creation time columns (backward compatible migration). Inodes
+        // created before they existed keep 0, meaning unknown.
+        conn.execute(
+            "ALTER TABLE fs_inode ADD COLUMN btime INTEGER NOT NULL DEFAULT 0",
+            (),
+        )
+        .await
+        .ok();
+        conn.execute(
+            "ALTER TABLE fs_inode ADD COLUMN btime_nsec INTEGER NOT NULL DEFAULT 0",
+            (),
+        )
+        .await
+        .ok();
+
         // Create directory entry table
         conn.execute(
             "CREATE TABLE IF NOT EXISTS fs_dentry (
@@ -549,6 +1267,11 @@ impl AgentFS {
         )
         .await?;
 
+        // Add chunk checksum column (backward compatible migration)
+        conn.execute("ALTER TABLE fs_data ADD COLUMN checksum INTEGER", ())
+            .await
+            .ok();
+
         // Create symlink table
         conn.execute(
             "CREATE TABLE IF NOT EXISTS fs_symlink (
@@ -572,6 +1295,22 @@ impl AgentFS {
             .await?;
         }
 
+        // Ensure inode_generation config exists
+        let mut rows = conn
+            .query(
+                "SELECT value FROM fs_config WHERE key = 'inode_generation'",
+                (),
+            )
+            .await?;
+
+        if rows.next().await?.is_none() {
+            conn.execute(
+                "INSERT INTO fs_config (key, value) VALUES ('inode_generation', ?)",
+                (Self::new_inode_generation()?.to_string(),),
+            )
+            .await?;
+        }
+
         // Set schema version
         conn.execute(
             "INSERT OR REPLACE INTO fs_config (key, value) VALUES ('schema_version', ?)",
@@ -595,9 +1334,9 @@ impl AgentFS {
             let now_secs = dur.as_secs() as i64;
             let now_nsec = dur.subsec_nanos() as i64;
             conn.execute(
-                "INSERT INTO fs_inode (ino, mode, nlink, uid, gid, size, atime, mtime, ctime, atime_nsec, mtime_nsec, ctime_nsec)
-                VALUES (?, ?, 2, ?, ?, 0, ?, ?, ?, ?, ?, ?)",
-                (ROOT_INO, DEFAULT_DIR_MODE as i64, uid, gid, now_secs, now_secs, now_secs, now_nsec, now_nsec, now_nsec),
+                "INSERT INTO fs_inode (ino, mode, nlink, uid, gid, size, atime, mtime, ctime, atime_nsec, mtime_nsec, ctime_nsec, btime, btime_nsec)
+                VALUES (?, ?, 2, ?, ?, 0, ?, ?, ?, ?, ?, ?, ?, ?)",
+                (ROOT_INO, DEFAULT_DIR_MODE as i64, uid, gid, now_secs, now_secs, now_secs, now_nsec, now_nsec, now_nsec, now_secs, now_nsec),
             )
             .await?;
         } else {
@@ -612,6 +1351,41 @@ impl AgentFS {
         Ok(())
     }
 
+    /// Delete the inodes left with no links by an earlier instance
+    ///
+    /// Unlinked inodes that are still open stay behind with nlink = 0 until
+    /// they are released with `remove_orphan`. An instance that crashed or
+    /// was killed never releases them. This deletes every such inode,
+    /// including those still open through another instance sharing the
+    /// database, so it must only be called while no other instance has the
+    /// database open read-write, for instance by a mount holding the
+    /// database's mount lock. Returns the number of inodes deleted.
+    pub async fn sweep_orphans(&self) -> Result<u64> {
+        let conn = self.pool.get_connection().await?;
+        let txn = OpTxn::begin(&conn).await?;
+        let mut rows = conn
+            .query("SELECT ino FROM fs_inode WHERE nlink = 0", ())
+            .await?;
+        let mut orphans = Vec::new();
+        while let Some(row) = rows.next().await? {
+            let ino: i64 = row.get(0)?;
+            orphans.push(ino);
+        }
+        drop(rows);
+
+        let swept = orphans.len() as u64;
+        for ino in orphans {
+            conn.execute("DELETE FROM fs_data WHERE ino = ?", (ino,))
+                .await?;
+            conn.execute("DELETE FROM fs_symlink WHERE ino = ?", (ino,))
+                .await?;
+            conn.execute("DELETE FROM fs_inode WHERE ino = ?", (ino,))
+                .await?;
+        }
+        txn.commit().await?;
+        Ok(swept)
+    }
+
     /// Read chunk size from config
     async fn read_chunk_size(conn: &Connection) -> Result<usize> {
         let mut rows = conn
@@ -634,15 +1408,50 @@ impl AgentFS {
         }
     }
 
+    /// Read the inode generation from config
+    ///
+    /// Inode numbers are never reused within a database (AUTOINCREMENT), but a
+    /// database that is deleted and recreated hands out the same numbers again.
+    /// The generation is chosen when the database is created, so `(ino,
+    /// generation)` identifies a file even across such a recreation. Mounts
+    /// report it as the filesystem id, so a mount can be matched to its
+    /// database.
+    pub async fn read_inode_generation(conn: &Connection) -> Result<u64> {
+        let mut rows = conn
+            .query(
+                "SELECT value FROM fs_config WHERE key = 'inode_generation'",
+                (),
+            )
+            .await?;
+
+        let generation = match rows.next().await? {
+            Some(row) => row.get_value(0).ok().and_then(|v| match v {
+                Value::Text(s) => s.parse::<u64>().ok(),
+                Value::Integer(i) => Some(i as u64),
+                _ => None,
+            }),
+            None => None,
+        };
+        generation.ok_or_else(|| Error::Internal("missing inode generation".to_string()))
+    }
+
+    /// Pick a generation for a new database
+    fn new_inode_generation() -> Result<u64> {
+        let dur = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        // Nanosecond creation time; never zero, which means "unknown"
+        Ok((dur.as_nanos() as u64).max(1))
+    }
+
     /// Normalize a path
+    ///
+    /// Relative paths are taken relative to the root, and `.` and `..`
+    /// components are resolved lexically in both cases.
     fn normalize_path(&self, path: &str) -> String {
         let normalized = path.trim_end_matches('/');
         let normalized = if normalized.is_empty() {
             "/"
-        } else if normalized.starts_with('/') {
-            normalized
         } else {
-            return format!("/{}", normalized);
+            normalized
         };
 
         // Handle . and .. components
@@ -674,6 +1483,20 @@ impl AgentFS {
         }
     }
 
+    /// Resolve the target of the symlink at `link_path` to a normalized path
+    ///
+    /// Relative targets are relative to the directory containing the link,
+    /// never to the process working directory or the root, so `../x/y` from
+    /// `/a/b/link` is `/a/x/y`. `..` is applied lexically, which is exact as
+    /// long as `link_path` itself contains no symlinks.
+    fn resolve_link_target(&self, link_path: &str, target: &str) -> String {
+        if target.starts_with('/') {
+            return self.normalize_path(target);
+        }
+        let parent = Path::new(link_path).parent().unwrap_or(Path::new("/"));
+        self.normalize_path(&parent.join(target).to_string_lossy())
+    }
+
     /// Split path into components
     fn split_path(&self, path: &str) -> Vec<String> {
         let normalized = self.normalize_path(path);
@@ -717,121 +1540,420 @@ impl AgentFS {
         Ok(found_ino)
     }
 
-    /// Get link count for an inode
-    async fn get_link_count(&self, conn: &Connection, ino: i64) -> Result<u32> {
+    /// Remove a directory entry, deleting the inode once its last link is
+    /// gone unless `keep_orphan` is set. Returns the inode if it was left
+    /// behind as an orphan.
+    async fn unlink_entry(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        keep_orphan: bool,
+    ) -> Result<Option<i64>> {
+        if name.len() > MAX_NAME_LEN {
+            return Err(FsError::NameTooLong.into());
+        }
+        let conn = self.pool.get_connection().await?;
+
+        // Look up the child inode
+        let ino = self
+            .lookup_child(&conn, parent_ino, name)
+            .await?
+            .ok_or(FsError::NotFound)?;
+
+        // Check if it's a directory (use rmdir for directories)
         let mut stmt = conn
-            .prepare_cached("SELECT nlink FROM fs_inode WHERE ino = ?")
+            .prepare_cached("SELECT mode FROM fs_inode WHERE ino = ?")
             .await?;
         let mut rows = stmt.query((ino,)).await?;
 
         if let Some(row) = rows.next().await? {
-            let nlink = row
+            let mode = row
                 .get_value(0)
                 .ok()
                 .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0);
-            Ok(nlink as u32)
-        } else {
-            Ok(0)
+                .unwrap_or(0) as u32;
+
+            if (mode & S_IFMT) == super::S_IFDIR {
+                return Err(FsError::IsADirectory.into());
+            }
         }
-    }
+        check_inode_flags(&conn, ino, FS_IMMUTABLE_FL | FS_APPEND_FL).await?;
 
-    /// Get file attributes by inode using an existing connection
-    async fn getattr_with_conn(&self, conn: &Connection, ino: i64) -> Result<Option<Stats>> {
+        // Delete the directory entry
         let mut stmt = conn
-            .prepare_cached("SELECT ino, mode, nlink, uid, gid, size, atime, mtime, ctime, rdev, atime_nsec, mtime_nsec, ctime_nsec FROM fs_inode WHERE ino = ?")
+            .prepare_cached("DELETE FROM fs_dentry WHERE parent_ino = ? AND name = ?")
             .await?;
-        let mut rows = stmt.query((ino,)).await?;
+        stmt.execute((parent_ino, name)).await?;
 
-        if let Some(row) = rows.next().await? {
-            let stats = Self::build_stats_from_row(&row)?;
-            Ok(Some(stats))
-        } else {
-            Ok(None)
-        }
-    }
+        // Invalidate cache
+        self.dentry_cache.remove(parent_ino, name);
 
-    /// Build a Stats object from a database row
-    ///
-    /// The row should contain columns in this order:
-    /// ino, mode, nlink, uid, gid, size, atime, mtime, ctime
-    fn build_stats_from_row(row: &turso::Row) -> Result<Stats> {
-        Ok(Stats {
-            ino: row
-                .get_value(0)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0),
-            mode: row
-                .get_value(1)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0) as u32,
-            nlink: row
-                .get_value(2)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(1) as u32,
-            uid: row
-                .get_value(3)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0) as u32,
-            gid: row
-                .get_value(4)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0) as u32,
-            size: row
-                .get_value(5)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0),
-            atime: row
-                .get_value(6)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0),
-            mtime: row
-                .get_value(7)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0),
-            ctime: row
-                .get_value(8)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0),
-            atime_nsec: row
-                .get_value(10)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0) as u32,
-            mtime_nsec: row
-                .get_value(11)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0) as u32,
-            ctime_nsec: row
-                .get_value(12)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0) as u32,
-            rdev: row
-                .get_value(9)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0) as u64,
-        })
-    }
+        // Update parent directory mtime and ctime
+        let dur = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        let now_secs = dur.as_secs() as i64;
+        let now_nsec = dur.subsec_nanos() as i64;
+        let mut stmt = conn
+            .prepare_cached("UPDATE fs_inode SET mtime = ?, ctime = ?, mtime_nsec = ?, ctime_nsec = ? WHERE ino = ?")
+            .await?;
+        stmt.execute((now_secs, now_secs, now_nsec, now_nsec, parent_ino))
+            .await?;
 
-    /// Resolve a path to an inode number
-    async fn resolve_path(&self, path: &str) -> Result<Option<i64>> {
-        let conn = self.pool.get_connection().await?;
-        self.resolve_path_with_conn(&conn, path).await
-    }
+        // Decrement link count and update ctime
+        let mut stmt = conn
+            .prepare_cached(
+                "UPDATE fs_inode SET nlink = nlink - 1, ctime = ?, ctime_nsec = ? WHERE ino = ?",
+            )
+            .await?;
+        stmt.execute((now_secs, now_nsec, ino)).await?;
 
-    /// Resolve a path to an inode number using a provided connection
+        // Check if this was the last link to the inode
+        let link_count = self.get_link_count(&conn, ino).await?;
+        if link_count == 0 && keep_orphan {
+            return Ok(Some(ino));
+        }
+        if link_count == 0 {
+            // Delete data blocks
+            let mut stmt = conn
+                .prepare_cached("DELETE FROM fs_data WHERE ino = ?")
+                .await?;
+            stmt.execute((ino,)).await?;
+
+            // Delete symlink if exists
+            let mut stmt = conn
+                .prepare_cached("DELETE FROM fs_symlink WHERE ino = ?")
+                .await?;
+            stmt.execute((ino,)).await?;
+
+            // Delete inode
+            let mut stmt = conn
+                .prepare_cached("DELETE FROM fs_inode WHERE ino = ?")
+                .await?;
+            stmt.execute((ino,)).await?;
+        }
+
+        Ok(None)
+    }
+
+    /// Rename a directory entry, deleting a replaced destination inode once
+    /// its last link is gone unless `keep_orphan` is set. Returns the inode
+    /// if it was left behind as an orphan.
+    async fn rename_entry(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+        flags: u32,
+        keep_orphan: bool,
+    ) -> Result<Option<i64>> {
+        if flags & !(RENAME_NOREPLACE | RENAME_EXCHANGE) != 0
+            || flags == RENAME_NOREPLACE | RENAME_EXCHANGE
+        {
+            return Err(FsError::NotSupported.into());
+        }
+        if newname.len() > MAX_NAME_LEN {
+            return Err(FsError::NameTooLong.into());
+        }
+        if flags & RENAME_EXCHANGE != 0 {
+            self.exchange(oldparent_ino, oldname, newparent_ino, newname)
+                .await?;
+            return Ok(None);
+        }
+        let conn = self.pool.get_connection().await?;
+
+        // Get source inode
+        let src_ino = self
+            .lookup_child(&conn, oldparent_ino, oldname)
+            .await?
+            .ok_or(FsError::NotFound)?;
+
+        if src_ino == ROOT_INO {
+            return Err(FsError::RootOperation.into());
+        }
+        check_inode_flags(&conn, src_ino, FS_IMMUTABLE_FL | FS_APPEND_FL).await?;
+
+        // Get source stats to check if it's a directory
+        let src_stats = self
+            .getattr_with_conn(&conn, src_ino)
+            .await?
+            .ok_or(FsError::NotFound)?;
+
+        let txn = OpTxn::begin(&conn).await?;
+
+        let result: Result<Option<i64>> = async {
+            let mut orphan = None;
+            // Check if destination exists
+            if let Some(dst_ino) = self.lookup_child(&conn, newparent_ino, newname).await? {
+                if flags & RENAME_NOREPLACE != 0 {
+                    return Err(FsError::AlreadyExists.into());
+                }
+
+                let dst_stats = self.getattr_with_conn(&conn, dst_ino).await?.ok_or(FsError::NotFound)?;
+                check_inode_flags(&conn, dst_ino, FS_IMMUTABLE_FL | FS_APPEND_FL).await?;
+
+                // Can't replace directory with non-directory
+                if dst_stats.is_directory() && !src_stats.is_directory() {
+                    return Err(FsError::IsADirectory.into());
+                }
+
+                // Can't replace non-directory with directory
+                if !dst_stats.is_directory() && src_stats.is_directory() {
+                    return Err(FsError::NotADirectory.into());
+                }
+
+                // If destination is directory, it must be empty
+                if dst_stats.is_directory() {
+                    let mut stmt = conn
+                        .prepare_cached("SELECT COUNT(*) FROM fs_dentry WHERE parent_ino = ?")
+                        .await?;
+                    let mut rows = stmt.query((dst_ino,)).await?;
+
+                    if let Some(row) = rows.next().await? {
+                        let count = row
+                            .get_value(0)
+                            .ok()
+                            .and_then(|v| v.as_integer().copied())
+                            .unwrap_or(0);
+                        if count > 0 {
+                            return Err(FsError::NotEmpty.into());
+                        }
+                    }
+                }
+
+                // Remove destination entry
+                let mut stmt = conn
+                    .prepare_cached("DELETE FROM fs_dentry WHERE parent_ino = ? AND name = ?")
+                    .await?;
+                stmt.execute((newparent_ino, newname)).await?;
+
+                // Decrement link count and update ctime on destination inode
+                let dur_dec = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default();
+                let now_dec = dur_dec.as_secs() as i64;
+                let now_dec_nsec = dur_dec.subsec_nanos() as i64;
+                let mut stmt = conn
+                    .prepare_cached("UPDATE fs_inode SET nlink = nlink - 1, ctime = ?, ctime_nsec = ? WHERE ino = ?")
+                    .await?;
+                stmt.execute((now_dec, now_dec_nsec, dst_ino)).await?;
+
+                // Clean up destination inode if no more links
+                let link_count = self.get_link_count(&conn, dst_ino).await?;
+                if link_count == 0 && keep_orphan && !dst_stats.is_directory() {
+                    orphan = Some(dst_ino);
+                } else if link_count == 0 {
+                    let mut stmt = conn
+                        .prepare_cached("DELETE FROM fs_data WHERE ino = ?")
+                        .await?;
+                    stmt.execute((dst_ino,)).await?;
+                    let mut stmt = conn
+                        .prepare_cached("DELETE FROM fs_symlink WHERE ino = ?")
+                        .await?;
+                    stmt.execute((dst_ino,)).await?;
+                    let mut stmt = conn
+                        .prepare_cached("DELETE FROM fs_inode WHERE ino = ?")
+                        .await?;
+                    stmt.execute((dst_ino,)).await?;
+                }
+            }
+
+            // Update the dentry: change parent and/or name
+            let mut stmt = conn
+                .prepare_cached(
+                    "UPDATE fs_dentry SET parent_ino = ?, name = ? WHERE parent_ino = ? AND name = ?",
+                )
+                .await?;
+            stmt.execute((newparent_ino, newname, oldparent_ino, oldname))
+                .await?;
+
+            // If renaming a directory across parents, adjust parent nlink counts
+            // (the ".." link moves from old parent to new parent)
+            if src_stats.is_directory() && oldparent_ino != newparent_ino {
+                let mut stmt = conn
+                    .prepare_cached("UPDATE fs_inode SET nlink = nlink - 1 WHERE ino = ?")
+                    .await?;
+                stmt.execute((oldparent_ino,)).await?;
+
+                let mut stmt = conn
+                    .prepare_cached("UPDATE fs_inode SET nlink = nlink + 1 WHERE ino = ?")
+                    .await?;
+                stmt.execute((newparent_ino,)).await?;
+            }
+
+            // Update ctime of the inode
+            let dur = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            let now_secs = dur.as_secs() as i64;
+            let now_nsec = dur.subsec_nanos() as i64;
+
+            let mut stmt = conn
+                .prepare_cached("UPDATE fs_inode SET ctime = ?, ctime_nsec = ? WHERE ino = ?")
+                .await?;
+            stmt.execute((now_secs, now_nsec, src_ino)).await?;
+
+            // Update source parent directory timestamps
+            let mut stmt = conn
+                .prepare_cached("UPDATE fs_inode SET mtime = ?, ctime = ?, mtime_nsec = ?, ctime_nsec = ? WHERE ino = ?")
+                .await?;
+            stmt.execute((now_secs, now_secs, now_nsec, now_nsec, oldparent_ino)).await?;
+
+            // Update destination parent directory timestamps
+            if newparent_ino != oldparent_ino {
+                let mut stmt = conn
+                    .prepare_cached("UPDATE fs_inode SET mtime = ?, ctime = ?, mtime_nsec = ?, ctime_nsec = ? WHERE ino = ?")
+                    .await?;
+                stmt.execute((now_secs, now_secs, now_nsec, now_nsec, newparent_ino)).await?;
+            }
+
+            Ok(orphan)
+        }
+        .await;
+
+        match result {
+            Ok(orphan) => {
+                txn.commit().await?;
+
+                // The source inode is now at the destination
+                self.dentry_cache
+                    .rename(oldparent_ino, oldname, newparent_ino, newname, src_ino);
+
+                Ok(orphan)
+            }
+            Err(e) => {
+                let _ = txn.rollback().await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Get link count for an inode
+    async fn get_link_count(&self, conn: &Connection, ino: i64) -> Result<u32> {
+        let mut stmt = conn
+            .prepare_cached("SELECT nlink FROM fs_inode WHERE ino = ?")
+            .await?;
+        let mut rows = stmt.query((ino,)).await?;
+
+        if let Some(row) = rows.next().await? {
+            let nlink = row
+                .get_value(0)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0);
+            Ok(nlink as u32)
+        } else {
+            Ok(0)
+        }
+    }
+
+    /// Get file attributes by inode using an existing connection
+    async fn getattr_with_conn(&self, conn: &Connection, ino: i64) -> Result<Option<Stats>> {
+        let mut stmt = conn
+            .prepare_cached("SELECT ino, mode, nlink, uid, gid, size, atime, mtime, ctime, rdev, atime_nsec, mtime_nsec, ctime_nsec, btime, btime_nsec FROM fs_inode WHERE ino = ?")
+            .await?;
+        let mut rows = stmt.query((ino,)).await?;
+
+        if let Some(row) = rows.next().await? {
+            let stats = Self::build_stats_from_row(&row, self.generation)?;
+            Ok(Some(stats))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Build a Stats object from a database row
+    ///
+    /// The row should contain columns in this order:
+    /// ino, mode, nlink, uid, gid, size, atime, mtime, ctime, rdev,
+    /// atime_nsec, mtime_nsec, ctime_nsec, btime, btime_nsec
+    fn build_stats_from_row(row: &turso::Row, generation: u64) -> Result<Stats> {
+        Ok(Stats {
+            ino: row
+                .get_value(0)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0),
+            mode: row
+                .get_value(1)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0) as u32,
+            nlink: row
+                .get_value(2)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(1) as u32,
+            uid: row
+                .get_value(3)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0) as u32,
+            gid: row
+                .get_value(4)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0) as u32,
+            size: row
+                .get_value(5)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0),
+            atime: row
+                .get_value(6)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0),
+            mtime: row
+                .get_value(7)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0),
+            ctime: row
+                .get_value(8)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0),
+            atime_nsec: row
+                .get_value(10)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0) as u32,
+            mtime_nsec: row
+                .get_value(11)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0) as u32,
+            ctime_nsec: row
+                .get_value(12)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0) as u32,
+            btime: row
+                .get_value(13)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0),
+            btime_nsec: row
+                .get_value(14)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0) as u32,
+            rdev: row
+                .get_value(9)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0) as u64,
+            generation,
+        })
+    }
+
+    /// Resolve a path to an inode number
+    async fn resolve_path(&self, path: &str) -> Result<Option<i64>> {
+        let conn = self.pool.get_connection().await?;
+        self.resolve_path_with_conn(&conn, path).await
+    }
+
+    /// Resolve a path to an inode number using a provided connection
     async fn resolve_path_with_conn(&self, conn: &Connection, path: &str) -> Result<Option<i64>> {
         let components = self.split_path(path);
         if components.is_empty() {
@@ -901,12 +2023,12 @@ impl AgentFS {
         };
 
         let mut stmt = conn
-            .prepare_cached("SELECT ino, mode, nlink, uid, gid, size, atime, mtime, ctime, rdev, atime_nsec, mtime_nsec, ctime_nsec FROM fs_inode WHERE ino = ?")
+            .prepare_cached("SELECT ino, mode, nlink, uid, gid, size, atime, mtime, ctime, rdev, atime_nsec, mtime_nsec, ctime_nsec, btime, btime_nsec FROM fs_inode WHERE ino = ?")
             .await?;
         let mut rows = stmt.query((ino,)).await?;
 
         if let Some(row) = rows.next().await? {
-            let stats = Self::build_stats_from_row(&row)?;
+            let stats = Self::build_stats_from_row(&row, self.generation)?;
             Ok(Some(stats))
         } else {
             Ok(None)
@@ -923,7 +2045,7 @@ impl AgentFS {
         let max_symlink_depth = 40; // Standard limit for symlink following
 
         let mut stmt = conn.prepare_cached(
-            "SELECT ino, mode, nlink, uid, gid, size, atime, mtime, ctime, rdev, atime_nsec, mtime_nsec, ctime_nsec FROM fs_inode WHERE ino = ?",
+            "SELECT ino, mode, nlink, uid, gid, size, atime, mtime, ctime, rdev, atime_nsec, mtime_nsec, ctime_nsec, btime, btime_nsec FROM fs_inode WHERE ino = ?",
         ).await?;
         for _ in 0..max_symlink_depth {
             let ino = match self.resolve_path_with_conn(&conn, &current_path).await? {
@@ -949,22 +2071,12 @@ impl AgentFS {
                         .await?
                         .ok_or(FsError::NotFound)?;
 
-                    // Resolve target path (handle both absolute and relative paths)
-                    current_path = if target.starts_with('/') {
-                        target
-                    } else {
-                        // Relative path - resolve relative to the symlink's directory
-                        let base_path = Path::new(&current_path);
-                        let parent = base_path.parent().unwrap_or(Path::new("/"));
-                        let joined = parent.join(&target);
-                        joined.to_string_lossy().into_owned()
-                    };
-                    current_path = self.normalize_path(&current_path);
+                    current_path = self.resolve_link_target(&current_path, &target);
                     continue; // Follow the symlink
                 }
 
                 // Not a symlink, return the stats
-                let stats = Self::build_stats_from_row(&row)?;
+                let stats = Self::build_stats_from_row(&row, self.generation)?;
                 return Ok(Some(stats));
             } else {
                 return Ok(None);
@@ -991,7 +2103,7 @@ impl AgentFS {
 
             let mut rows = conn
                 .query(
-                    "SELECT ino, mode, nlink, uid, gid, size, atime, mtime, ctime, rdev, atime_nsec, mtime_nsec, ctime_nsec FROM fs_inode WHERE ino = ?",
+                    "SELECT ino, mode, nlink, uid, gid, size, atime, mtime, ctime, rdev, atime_nsec, mtime_nsec, ctime_nsec, btime, btime_nsec FROM fs_inode WHERE ino = ?",
                     (ino,),
                 )
                 .await?;
@@ -1011,22 +2123,12 @@ impl AgentFS {
                         .await?
                         .ok_or(FsError::InvalidPath)?;
 
-                    // Resolve target path (handle both absolute and relative paths)
-                    current_path = if target.starts_with('/') {
-                        target
-                    } else {
-                        // Relative path - resolve relative to the symlink's directory
-                        let base_path = Path::new(&current_path);
-                        let parent = base_path.parent().unwrap_or(Path::new("/"));
-                        let joined = parent.join(&target);
-                        joined.to_string_lossy().into_owned()
-                    };
-                    current_path = self.normalize_path(&current_path);
+                    current_path = self.resolve_link_target(&current_path, &target);
                     continue; // Follow the symlink
                 }
 
                 // Not a symlink, return the stats
-                let stats = Self::build_stats_from_row(&row)?;
+                let stats = Self::build_stats_from_row(&row, self.generation)?;
                 return Ok(Some(stats));
             } else {
                 return Ok(None);
@@ -1071,8 +2173,8 @@ impl AgentFS {
         let now_nsec = dur.subsec_nanos() as i64;
         let mut stmt = conn
             .prepare_cached(
-                "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime, atime_nsec, mtime_nsec, ctime_nsec)
-                VALUES (?, ?, ?, 0, ?, ?, ?, ?, ?, ?) RETURNING ino",
+                "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime, atime_nsec, mtime_nsec, ctime_nsec, btime, btime_nsec)
+                VALUES (?, ?, ?, 0, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING ino",
             )
             .await?;
         let row = stmt
@@ -1086,6 +2188,8 @@ impl AgentFS {
                 now_nsec,
                 now_nsec,
                 now_nsec,
+                now_secs,
+                now_nsec,
             ))
             .await?;
 
@@ -1156,8 +2260,8 @@ impl AgentFS {
         let now_nsec = dur.subsec_nanos() as i64;
         let mut stmt = conn
             .prepare_cached(
-                "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime, rdev, atime_nsec, mtime_nsec, ctime_nsec)
-                VALUES (?, ?, ?, 0, ?, ?, ?, ?, ?, ?, ?) RETURNING ino",
+                "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime, rdev, atime_nsec, mtime_nsec, ctime_nsec, btime, btime_nsec)
+                VALUES (?, ?, ?, 0, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING ino",
             )
             .await?;
         let row = stmt
@@ -1172,6 +2276,8 @@ impl AgentFS {
                 now_nsec,
                 now_nsec,
                 now_nsec,
+                now_secs,
+                now_nsec,
             ))
             .await?;
 
@@ -1238,15 +2344,15 @@ impl AgentFS {
         // Prepare statements before starting the transaction
         let mut inode_stmt = conn
             .prepare_cached(
-                "INSERT INTO fs_inode (mode, nlink, uid, gid, size, atime, mtime, ctime, atime_nsec, mtime_nsec, ctime_nsec)
-                 VALUES (?, 1, ?, ?, 0, ?, ?, ?, ?, ?, ?) RETURNING ino",
+                "INSERT INTO fs_inode (mode, nlink, uid, gid, size, atime, mtime, ctime, atime_nsec, mtime_nsec, ctime_nsec, btime, btime_nsec)
+                 VALUES (?, 1, ?, ?, 0, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING ino",
             )
             .await?;
         let mut dentry_stmt = conn
             .prepare_cached("INSERT INTO fs_dentry (name, parent_ino, ino) VALUES (?, ?, ?)")
             .await?;
 
-        let txn = Transaction::new_unchecked(&conn, TransactionBehavior::Immediate).await?;
+        let txn = OpTxn::begin(&conn).await?;
 
         let dur = SystemTime::now().duration_since(UNIX_EPOCH)?;
         let now_secs = dur.as_secs() as i64;
@@ -1264,6 +2370,8 @@ impl AgentFS {
                 now_nsec,
                 now_nsec,
                 now_nsec,
+                now_secs,
+                now_nsec,
             ))
             .await?;
 
@@ -1294,13 +2402,21 @@ impl AgentFS {
             atime_nsec: now_nsec as u32,
             mtime_nsec: now_nsec as u32,
             ctime_nsec: now_nsec as u32,
+            btime: now_secs,
+            btime_nsec: now_nsec as u32,
             rdev: 0,
+            generation: self.generation,
         };
 
         let file: BoxedFile = Arc::new(AgentFSFile {
             pool: self.pool.clone(),
             ino,
             chunk_size: self.chunk_size,
+            generation: self.generation,
+            checksums: self.checksums,
+            max_file_size: self.max_file_size,
+            chunk_cache: self.chunk_cache.clone(),
+            change_hook: self.change_hook.clone(),
         });
 
         Ok((stats, file))
@@ -1314,17 +2430,35 @@ impl AgentFS {
             None => return Ok(None),
         };
 
+        let mut stmt = conn
+            .prepare_cached("SELECT size FROM fs_inode WHERE ino = ?")
+            .await?;
+        let size = stmt
+            .query_row((ino,))
+            .await?
+            .get_value(0)
+            .ok()
+            .and_then(|v| v.as_integer().copied())
+            .unwrap_or(0) as usize;
+
+        // Chunks missing from a sparse file are holes and stay zero
+        let mut data = vec![0u8; size];
         let mut rows = conn
             .query(
-                "SELECT data FROM fs_data WHERE ino = ? ORDER BY chunk_index",
+                "SELECT chunk_index, data FROM fs_data WHERE ino = ? ORDER BY chunk_index",
                 (ino,),
             )
             .await?;
-
-        let mut data = Vec::new();
         while let Some(row) = rows.next().await? {
-            if let Ok(Value::Blob(chunk)) = row.get_value(0) {
-                data.extend_from_slice(&chunk);
+            let index = row
+                .get_value(0)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0) as usize;
+            if let Ok(Value::Blob(chunk)) = row.get_value(1) {
+                let start = std::cmp::min(index * self.chunk_size, size);
+                let end = std::cmp::min(start + chunk.len(), size);
+                data[start..end].copy_from_slice(&chunk[..end - start]);
             }
         }
 
@@ -1349,11 +2483,13 @@ impl AgentFS {
         let start_chunk = offset / chunk_size;
         let end_chunk = (offset + size).saturating_sub(1) / chunk_size;
 
+        let sql = if self.checksums {
+            "SELECT chunk_index, data, checksum FROM fs_data WHERE ino = ? AND chunk_index >= ? AND chunk_index <= ? ORDER BY chunk_index"
+        } else {
+            "SELECT chunk_index, data FROM fs_data WHERE ino = ? AND chunk_index >= ? AND chunk_index <= ? ORDER BY chunk_index"
+        };
         let mut rows = conn
-            .query(
-                "SELECT chunk_index, data FROM fs_data WHERE ino = ? AND chunk_index >= ? AND chunk_index <= ? ORDER BY chunk_index",
-                (ino, start_chunk as i64, end_chunk as i64),
-            )
+            .query(sql, (ino, start_chunk as i64, end_chunk as i64))
             .await?;
 
         let mut result = Vec::with_capacity(size as usize);
@@ -1361,6 +2497,20 @@ impl AgentFS {
 
         while let Some(row) = rows.next().await? {
             if let Ok(Value::Blob(chunk_data)) = row.get_value(1) {
+                if self.checksums {
+                    let chunk_index = row
+                        .get_value(0)
+                        .ok()
+                        .and_then(|v| v.as_integer().copied())
+                        .unwrap_or(0);
+                    verify_chunk(
+                        ino,
+                        chunk_index,
+                        self.chunk_size,
+                        &chunk_data,
+                        row.get_value(2)?,
+                    )?;
+                }
                 let skip = if result.is_empty() {
                     start_offset_in_chunk
                 } else {
@@ -1386,6 +2536,7 @@ impl AgentFS {
     /// If the offset is beyond the current file size, the file is extended with zeros.
     /// If the file does not exist, it will be created.
     pub async fn pwrite(&self, path: &str, offset: u64, data: &[u8]) -> Result<()> {
+        check_file_size(self.max_file_size, offset.checked_add(data.len() as u64))?;
         let conn = self.pool.get_connection().await?;
         let path = self.normalize_path(path);
         let components = self.split_path(&path);
@@ -1407,15 +2558,16 @@ impl AgentFS {
 
         let name = components.last().unwrap();
 
-        let txn = Transaction::new_unchecked(&conn, TransactionBehavior::Immediate).await?;
+        let txn = OpTxn::begin(&conn).await?;
 
-        let result: Result<()> = async {
+        let result: Result<i64> = async {
             // Calculate the final size upfront
             let write_end = offset + data.len() as u64;
 
             // Get or create the inode
             let (ino, current_size, is_new) =
                 if let Some(ino) = self.resolve_path_with_conn(&conn, &path).await? {
+                    check_inode_flags(&conn, ino, FS_IMMUTABLE_FL).await?;
                     // Get current file size
                     let mut stmt = conn
                         .prepare_cached("SELECT size FROM fs_inode WHERE ino = ?")
@@ -1438,12 +2590,12 @@ impl AgentFS {
                     let new_size = write_end as i64;
                     let mut stmt = conn
                         .prepare_cached(
-                            "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime, nlink, atime_nsec, mtime_nsec, ctime_nsec)
-                        VALUES (?, 0, 0, ?, ?, ?, ?, 1, ?, ?, ?) RETURNING ino",
+                            "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime, nlink, atime_nsec, mtime_nsec, ctime_nsec, btime, btime_nsec)
+                        VALUES (?, 0, 0, ?, ?, ?, ?, 1, ?, ?, ?, ?, ?) RETURNING ino",
                         )
                         .await?;
                     let row = stmt
-                        .query_row((DEFAULT_FILE_MODE as i64, new_size, now_secs, now_secs, now_secs, now_nsec, now_nsec, now_nsec))
+                        .query_row((DEFAULT_FILE_MODE as i64, new_size, now_secs, now_secs, now_secs, now_nsec, now_nsec, now_nsec, now_secs, now_nsec))
                         .await?;
 
                     let ino = row
@@ -1472,7 +2624,7 @@ impl AgentFS {
                     .await?
                     .execute((now_secs, now_nsec, ino))
                     .await?;
-                return Ok(());
+                return Ok(ino);
             }
 
             let chunk_size = self.chunk_size as u64;
@@ -1548,9 +2700,15 @@ impl AgentFS {
                     (ino, chunk_idx as i64),
                 )
                 .await?;
+                let chunk_data = &chunk_data[..actual_len];
                 conn.execute(
-                    "INSERT INTO fs_data (ino, chunk_index, data) VALUES (?, ?, ?)",
-                    (ino, chunk_idx as i64, &chunk_data[..actual_len]),
+                    "INSERT INTO fs_data (ino, chunk_index, data, checksum) VALUES (?, ?, ?, ?)",
+                    (
+                        ino,
+                        chunk_idx as i64,
+                        chunk_data,
+                        chunk_checksum(self.checksums, chunk_data),
+                    ),
                 )
                 .await?;
             }
@@ -1567,13 +2725,14 @@ impl AgentFS {
                 stmt.execute((new_size as i64, now_secs, now_nsec, ino)).await?;
             }
 
-            Ok(())
+            Ok(ino)
         }
         .await;
 
         match result {
-            Ok(()) => {
+            Ok(ino) => {
                 txn.commit().await?;
+                self.data_changed(ino);
                 Ok(())
             }
             Err(e) => {
@@ -1587,8 +2746,9 @@ impl AgentFS {
     ///
     /// This operates directly on chunks without loading the entire file into memory:
     /// - Shrinking: deletes chunks beyond new size, truncates the last chunk if needed
-    /// - Extending: pads with zeros up to the new size
+    /// - Extending: only updates the size, leaving a hole that reads as zeros
     pub async fn truncate(&self, path: &str, new_size: u64) -> Result<()> {
+        check_file_size(self.max_file_size, Some(new_size))?;
         let conn = self.pool.get_connection().await?;
         let path = self.normalize_path(path);
         let ino = self
@@ -1609,10 +2769,16 @@ impl AgentFS {
         } else {
             0
         };
+        let denied = if new_size < current_size {
+            FS_IMMUTABLE_FL | FS_APPEND_FL
+        } else {
+            FS_IMMUTABLE_FL
+        };
+        check_inode_flags(&conn, ino, denied).await?;
 
         let chunk_size = self.chunk_size as u64;
 
-        let txn = Transaction::new_unchecked(&conn, TransactionBehavior::Immediate).await?;
+        let txn = OpTxn::begin(&conn).await?;
 
         let result: Result<()> = async {
             if new_size == 0 {
@@ -1627,90 +2793,38 @@ impl AgentFS {
 
                 // Delete all chunks beyond the last one we need
                 conn.execute(
-                    "DELETE FROM fs_data WHERE ino = ? AND chunk_index > ?",
-                    (ino, last_chunk_idx as i64),
-                )
-                .await?;
-
-                // Calculate where in the last chunk the file should end
-                let end_in_last_chunk = ((new_size - 1) % chunk_size) + 1;
-
-                // If the last chunk needs to be truncated (not a full chunk),
-                // read it, truncate, and rewrite
-                if end_in_last_chunk < chunk_size {
-                    let mut stmt = conn
-                        .prepare_cached("SELECT data FROM fs_data WHERE ino = ? AND chunk_index = ?")
-                        .await?;
-                    let mut rows = stmt.query((ino, last_chunk_idx as i64)).await?;
-
-                    if let Some(row) = rows.next().await? {
-                        if let Ok(Value::Blob(chunk_data)) = row.get_value(0) {
-                            if chunk_data.len() > end_in_last_chunk as usize {
-                                let truncated = &chunk_data[..end_in_last_chunk as usize];
-                                let mut stmt = conn
-                                    .prepare_cached("UPDATE fs_data SET data = ? WHERE ino = ? AND chunk_index = ?")
-                                    .await?;
-                                stmt.execute((truncated, ino, last_chunk_idx as i64)).await?;
-                            }
-                        }
-                    }
-                }
-            } else if new_size > current_size {
-                // Extending: pad last existing chunk and add zero chunks as needed
-                let last_existing_chunk = if current_size == 0 {
-                    None
-                } else {
-                    Some((current_size - 1) / chunk_size)
-                };
-                let last_new_chunk = (new_size - 1) / chunk_size;
+                    "DELETE FROM fs_data WHERE ino = ? AND chunk_index > ?",
+                    (ino, last_chunk_idx as i64),
+                )
+                .await?;
 
-                // Pad the last existing chunk with zeros if it's not full
-                if let Some(last_idx) = last_existing_chunk {
+                // Calculate where in the last chunk the file should end
+                let end_in_last_chunk = ((new_size - 1) % chunk_size) + 1;
+
+                // If the last chunk needs to be truncated (not a full chunk),
+                // read it, truncate, and rewrite
+                if end_in_last_chunk < chunk_size {
                     let mut stmt = conn
                         .prepare_cached("SELECT data FROM fs_data WHERE ino = ? AND chunk_index = ?")
                         .await?;
-                    let mut rows = stmt.query((ino, last_idx as i64)).await?;
+                    let mut rows = stmt.query((ino, last_chunk_idx as i64)).await?;
 
                     if let Some(row) = rows.next().await? {
                         if let Ok(Value::Blob(chunk_data)) = row.get_value(0) {
-                            let current_chunk_len = chunk_data.len();
-                            let needed_len = if last_idx == last_new_chunk {
-                                // Last existing chunk is also the last new chunk
-                                ((new_size - 1) % chunk_size + 1) as usize
-                            } else {
-                                // Need to fill this chunk completely
-                                chunk_size as usize
-                            };
-
-                            if needed_len > current_chunk_len {
-                                let mut padded = chunk_data.clone();
-                                padded.resize(needed_len, 0);
+                            if chunk_data.len() > end_in_last_chunk as usize {
+                                let truncated = &chunk_data[..end_in_last_chunk as usize];
+                                let checksum = chunk_checksum(self.checksums, truncated);
                                 let mut stmt = conn
-                                    .prepare_cached("UPDATE fs_data SET data = ? WHERE ino = ? AND chunk_index = ?")
+                                    .prepare_cached("UPDATE fs_data SET data = ?, checksum = ? WHERE ino = ? AND chunk_index = ?")
                                     .await?;
-                                stmt.execute((&padded[..], ino, last_idx as i64)).await?;
+                                stmt.execute((truncated, checksum, ino, last_chunk_idx as i64)).await?;
                             }
                         }
                     }
                 }
-
-                // Add new zero-filled chunks if needed
-                let start_new_chunk = last_existing_chunk.map(|i| i + 1).unwrap_or(0);
-                for chunk_idx in start_new_chunk..=last_new_chunk {
-                    let chunk_len = if chunk_idx == last_new_chunk {
-                        ((new_size - 1) % chunk_size + 1) as usize
-                    } else {
-                        chunk_size as usize
-                    };
-                    let zeros = vec![0u8; chunk_len];
-                    conn.execute(
-                        "INSERT INTO fs_data (ino, chunk_index, data) VALUES (?, ?, ?)",
-                        (ino, chunk_idx as i64, &zeros[..]),
-                    )
-                    .await?;
-                }
             }
-            // else: new_size == current_size, nothing to do for data
+            // Extending needs no data: the range past the last chunk is a
+            // hole and reads back as zeros
 
             // Update size and mtime
             let dur = SystemTime::now().duration_since(UNIX_EPOCH)?;
@@ -1728,6 +2842,7 @@ impl AgentFS {
         match result {
             Ok(()) => {
                 txn.commit().await?;
+                self.data_changed(ino);
                 Ok(())
             }
             Err(e) => {
@@ -1773,7 +2888,7 @@ impl AgentFS {
     /// Returns entries with their stats in a single JOIN query, avoiding N+1 queries.
     pub async fn readdir_plus(&self, ino: i64) -> Result<Option<Vec<DirEntry>>> {
         let conn = self.pool.get_connection().await?;
-        let mut stmt = conn.prepare_cached("SELECT d.name, i.ino, i.mode, i.nlink, i.uid, i.gid, i.size, i.atime, i.mtime, i.ctime, i.rdev, i.atime_nsec, i.mtime_nsec, i.ctime_nsec
+        let mut stmt = conn.prepare_cached("SELECT d.name, i.ino, i.mode, i.nlink, i.uid, i.gid, i.size, i.atime, i.mtime, i.ctime, i.rdev, i.atime_nsec, i.mtime_nsec, i.ctime_nsec, i.btime, i.btime_nsec
             FROM fs_dentry d
             JOIN fs_inode i ON d.ino = i.ino
             WHERE d.parent_ino = ?
@@ -1865,11 +2980,22 @@ impl AgentFS {
                     .ok()
                     .and_then(|v| v.as_integer().copied())
                     .unwrap_or(0) as u32,
+                btime: row
+                    .get_value(14)
+                    .ok()
+                    .and_then(|v| v.as_integer().copied())
+                    .unwrap_or(0),
+                btime_nsec: row
+                    .get_value(15)
+                    .ok()
+                    .and_then(|v| v.as_integer().copied())
+                    .unwrap_or(0) as u32,
                 rdev: row
                     .get_value(10)
                     .ok()
                     .and_then(|v| v.as_integer().copied())
                     .unwrap_or(0) as u64,
+                generation: self.generation,
             };
 
             entries.push(DirEntry { name, stats });
@@ -1917,13 +3043,14 @@ impl AgentFS {
 
         let mut stmt = conn
             .prepare_cached(
-                "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime, atime_nsec, mtime_nsec, ctime_nsec)
-                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING ino",
+                "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime, atime_nsec, mtime_nsec, ctime_nsec, btime, btime_nsec)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING ino",
             )
             .await?;
         let row = stmt
             .query_row((
                 mode, uid, gid, size, now_secs, now_secs, now_secs, now_nsec, now_nsec, now_nsec,
+                now_secs, now_nsec,
             ))
             .await?;
 
@@ -2113,6 +3240,7 @@ impl AgentFS {
         if ino == ROOT_INO {
             return Err(FsError::RootOperation.into());
         }
+        check_inode_flags(&conn, ino, FS_IMMUTABLE_FL | FS_APPEND_FL).await?;
 
         // Get stats to check if it's a directory
         let stats = self
@@ -2255,6 +3383,7 @@ impl AgentFS {
             .resolve_path_with_conn(&conn, &from_path)
             .await?
             .ok_or(FsError::NotFound)?;
+        check_inode_flags(&conn, src_ino, FS_IMMUTABLE_FL | FS_APPEND_FL).await?;
 
         // Get source stats to check if it's a directory
         let src_stats = self
@@ -2306,7 +3435,7 @@ impl AgentFS {
         let src_name = src_name.clone();
         let dst_name = dst_name.clone();
 
-        let txn = Transaction::new_unchecked(&conn, TransactionBehavior::Immediate).await?;
+        let txn = OpTxn::begin(&conn).await?;
 
         let result: Result<()> = async {
             // Check if destination exists (inside transaction for atomicity)
@@ -2433,56 +3562,552 @@ impl AgentFS {
             Ok(()) => {
                 txn.commit().await?;
 
-                // Invalidate cache for source and destination
-                self.dentry_cache.remove(src_parent_ino, &src_name);
-                self.dentry_cache.remove(dst_parent_ino, &dst_name);
+                // The source inode is now at the destination
+                self.dentry_cache.rename(
+                    src_parent_ino,
+                    &src_name,
+                    dst_parent_ino,
+                    &dst_name,
+                    src_ino,
+                );
+
+                Ok(())
+            }
+            Err(e) => {
+                let _ = txn.rollback().await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Atomically swap two directory entries
+    ///
+    /// Both entries must exist. Each name keeps its place in its directory
+    /// but refers to the other's inode afterwards (`RENAME_EXCHANGE`).
+    async fn exchange(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+    ) -> Result<()> {
+        let conn = self.pool.get_connection().await?;
+
+        let txn = OpTxn::begin(&conn).await?;
+
+        let result: Result<(i64, i64)> = async {
+            let src_ino = self
+                .lookup_child(&conn, oldparent_ino, oldname)
+                .await?
+                .ok_or(FsError::NotFound)?;
+            let dst_ino = self
+                .lookup_child(&conn, newparent_ino, newname)
+                .await?
+                .ok_or(FsError::NotFound)?;
+
+            if src_ino == ROOT_INO || dst_ino == ROOT_INO {
+                return Err(FsError::RootOperation.into());
+            }
+            if src_ino == dst_ino {
+                return Ok((src_ino, dst_ino));
+            }
+            check_inode_flags(&conn, src_ino, FS_IMMUTABLE_FL | FS_APPEND_FL).await?;
+            check_inode_flags(&conn, dst_ino, FS_IMMUTABLE_FL | FS_APPEND_FL).await?;
+
+            let src_stats = self
+                .getattr_with_conn(&conn, src_ino)
+                .await?
+                .ok_or(FsError::NotFound)?;
+            let dst_stats = self
+                .getattr_with_conn(&conn, dst_ino)
+                .await?
+                .ok_or(FsError::NotFound)?;
+
+            for (parent_ino, name, ino) in [
+                (oldparent_ino, oldname, dst_ino),
+                (newparent_ino, newname, src_ino),
+            ] {
+                let mut stmt = conn
+                    .prepare_cached("UPDATE fs_dentry SET ino = ? WHERE parent_ino = ? AND name = ?")
+                    .await?;
+                stmt.execute((ino, parent_ino, name)).await?;
+            }
+
+            // A directory moving to another parent carries its ".." link with it
+            if oldparent_ino != newparent_ino
+                && src_stats.is_directory() != dst_stats.is_directory()
+            {
+                let (from, to) = if src_stats.is_directory() {
+                    (oldparent_ino, newparent_ino)
+                } else {
+                    (newparent_ino, oldparent_ino)
+                };
+                let mut stmt = conn
+                    .prepare_cached("UPDATE fs_inode SET nlink = nlink - 1 WHERE ino = ?")
+                    .await?;
+                stmt.execute((from,)).await?;
+                let mut stmt = conn
+                    .prepare_cached("UPDATE fs_inode SET nlink = nlink + 1 WHERE ino = ?")
+                    .await?;
+                stmt.execute((to,)).await?;
+            }
+
+            let dur = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            let now_secs = dur.as_secs() as i64;
+            let now_nsec = dur.subsec_nanos() as i64;
+
+            for ino in [src_ino, dst_ino] {
+                let mut stmt = conn
+                    .prepare_cached("UPDATE fs_inode SET ctime = ?, ctime_nsec = ? WHERE ino = ?")
+                    .await?;
+                stmt.execute((now_secs, now_nsec, ino)).await?;
+            }
+
+            // Update parent directory timestamps
+            let mut parents = vec![oldparent_ino];
+            if newparent_ino != oldparent_ino {
+                parents.push(newparent_ino);
+            }
+            for parent_ino in parents {
+                let mut stmt = conn
+                    .prepare_cached("UPDATE fs_inode SET mtime = ?, ctime = ?, mtime_nsec = ?, ctime_nsec = ? WHERE ino = ?")
+                    .await?;
+                stmt.execute((now_secs, now_secs, now_nsec, now_nsec, parent_ino)).await?;
+            }
+
+            Ok((src_ino, dst_ino))
+        }
+        .await;
+
+        match result {
+            Ok((src_ino, dst_ino)) => {
+                txn.commit().await?;
+
+                self.dentry_cache.insert(oldparent_ino, oldname, dst_ino);
+                self.dentry_cache.insert(newparent_ino, newname, src_ino);
+
+                Ok(())
+            }
+            Err(e) => {
+                let _ = txn.rollback().await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Get filesystem statistics
+    ///
+    /// Returns the total number of inodes and bytes used by file contents.
+    pub async fn statfs(&self) -> Result<FilesystemStats> {
+        let conn = self.pool.get_connection().await?;
+        // Count total inodes
+        let mut stmt = conn.prepare_cached("SELECT COUNT(*) FROM fs_inode").await?;
+        let mut rows = stmt.query(()).await?;
+
+        let inodes = if let Some(row) = rows.next().await? {
+            row.get_value(0)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0) as u64
+        } else {
+            0
+        };
+
+        // Sum total bytes used (from file sizes in inodes)
+        let mut stmt = conn
+            .prepare_cached("SELECT COALESCE(SUM(size), 0) FROM fs_inode")
+            .await?;
+        let mut rows = stmt.query(()).await?;
+
+        let bytes_used = if let Some(row) = rows.next().await? {
+            row.get_value(0)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0) as u64
+        } else {
+            0
+        };
+
+        Ok(FilesystemStats { inodes, bytes_used })
+    }
+
+    /// Iterate over every inode in the database, including the root
+    ///
+    /// Intended for maintenance tasks that need to visit all inodes
+    /// regardless of the directory tree, such as export or consistency checks.
+    /// Unlinked inodes that are still open are included.
+    pub fn iter_inodes(&self) -> InodeIter {
+        InodeIter {
+            pool: self.pool.clone(),
+            generation: self.generation,
+            last_ino: 0,
+            batch: std::collections::VecDeque::new(),
+            done: false,
+        }
+    }
+
+    /// Create many entries in a single transaction
+    ///
+    /// Meant for seeding a filesystem with a large tree, where creating each
+    /// entry in its own transaction dominates the cost. Entries are created in
+    /// order, so a parent directory must either exist already or appear
+    /// earlier in `entries`. All entries are owned by `uid` and `gid`. Either
+    /// every entry is created or, on the first error, none are. Returns the
+    /// new inode numbers in the order of `entries`.
+    pub async fn create_batch(
+        &self,
+        entries: &[BatchEntry],
+        uid: u32,
+        gid: u32,
+    ) -> Result<Vec<i64>> {
+        let conn = self.pool.get_connection().await?;
+
+        let mut inode_stmt = conn
+            .prepare_cached(
+                "INSERT INTO fs_inode (mode, nlink, uid, gid, size, atime, mtime, ctime, atime_nsec, mtime_nsec, ctime_nsec, btime, btime_nsec)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING ino",
+            )
+            .await?;
+        let mut dentry_stmt = conn
+            .prepare_cached("INSERT INTO fs_dentry (name, parent_ino, ino) VALUES (?, ?, ?)")
+            .await?;
+        let mut data_stmt = conn
+            .prepare_cached(
+                "INSERT INTO fs_data (ino, chunk_index, data, checksum) VALUES (?, ?, ?, ?)",
+            )
+            .await?;
+
+        let dur = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        let now_secs = dur.as_secs() as i64;
+        let now_nsec = dur.subsec_nanos() as i64;
+
+        let txn = OpTxn::begin(&conn).await?;
+
+        // Directories created by this batch, so their children resolve
+        // without a query. Dentries are cached only once committed.
+        let mut created: HashMap<String, i64> = HashMap::new();
+        let mut dentries = Vec::with_capacity(entries.len());
+        // Parents whose nlink grows by the number of new subdirectories
+        let mut parents: HashMap<i64, i64> = HashMap::new();
+
+        let result: Result<Vec<i64>> = async {
+            let mut inos = Vec::with_capacity(entries.len());
+            for entry in entries {
+                let path = self.normalize_path(entry.path());
+                let components = self.split_path(&path);
+                let Some((name, dirs)) = components.split_last() else {
+                    return Err(FsError::RootOperation.into());
+                };
+                if name.len() > MAX_NAME_LEN {
+                    return Err(FsError::NameTooLong.into());
+                }
+                let parent_path = format!("/{}", dirs.join("/"));
+                let parent_ino = match created.get(&parent_path) {
+                    Some(&ino) => ino,
+                    None => self
+                        .resolve_path_with_conn(&conn, &parent_path)
+                        .await?
+                        .ok_or(FsError::NotFound)?,
+                };
+                if self.lookup_child(&conn, parent_ino, name).await?.is_some() {
+                    return Err(FsError::AlreadyExists.into());
+                }
+
+                let (mode, nlink, size) = match entry {
+                    BatchEntry::Dir { mode, .. } => (S_IFDIR | (mode & 0o7777), 2, 0),
+                    BatchEntry::File { mode, data, .. } => {
+                        check_file_size(self.max_file_size, Some(data.len() as u64))?;
+                        (S_IFREG | (mode & 0o7777), 1, data.len() as i64)
+                    }
+                    BatchEntry::Symlink { target, .. } => (S_IFLNK | 0o777, 1, target.len() as i64),
+                };
+                let row = inode_stmt
+                    .query_row((
+                        mode as i64,
+                        nlink,
+                        uid,
+                        gid,
+                        size,
+                        now_secs,
+                        now_secs,
+                        now_secs,
+                        now_nsec,
+                        now_nsec,
+                        now_nsec,
+                        now_secs,
+                        now_nsec,
+                    ))
+                    .await?;
+                let ino = row
+                    .get_value(0)
+                    .ok()
+                    .and_then(|v| v.as_integer().copied())
+                    .ok_or_else(|| Error::Internal("failed to get inode".to_string()))?;
+                dentry_stmt
+                    .execute((name.as_str(), parent_ino, ino))
+                    .await?;
+
+                match entry {
+                    BatchEntry::Dir { .. } => {
+                        created.insert(path.clone(), ino);
+                        *parents.entry(parent_ino).or_default() += 1;
+                    }
+                    BatchEntry::File { data, .. } => {
+                        parents.entry(parent_ino).or_default();
+                        for (chunk_index, chunk) in data.chunks(self.chunk_size).enumerate() {
+                            data_stmt
+                                .execute((
+                                    ino,
+                                    chunk_index as i64,
+                                    chunk,
+                                    chunk_checksum(self.checksums, chunk),
+                                ))
+                                .await?;
+                        }
+                    }
+                    BatchEntry::Symlink { target, .. } => {
+                        parents.entry(parent_ino).or_default();
+                        conn.execute(
+                            "INSERT INTO fs_symlink (ino, target) VALUES (?, ?)",
+                            (ino, target.as_str()),
+                        )
+                        .await?;
+                    }
+                }
+                dentries.push((parent_ino, name.clone(), ino));
+                inos.push(ino);
+            }
+
+            // Touch each parent once rather than once per child
+            let mut stmt = conn
+                .prepare_cached(
+                    "UPDATE fs_inode SET nlink = nlink + ?, ctime = ?, mtime = ?, ctime_nsec = ?, mtime_nsec = ? WHERE ino = ?",
+                )
+                .await?;
+            for (&parent_ino, &subdirs) in &parents {
+                stmt.execute((subdirs, now_secs, now_secs, now_nsec, now_nsec, parent_ino))
+                    .await?;
+            }
+            Ok(inos)
+        }
+        .await;
+
+        match result {
+            Ok(inos) => {
+                txn.commit().await?;
+                for (parent_ino, name, ino) in &dentries {
+                    self.dentry_cache.insert(*parent_ino, name, *ino);
+                }
+                Ok(inos)
+            }
+            Err(e) => {
+                let _ = txn.rollback().await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Write a consistent copy of the database to a new file at `path`
+    ///
+    /// Every table is copied, including those that other layers keep in the
+    /// same database (overlay whiteouts and origins, key-value store, tool
+    /// calls), so the copy opens anywhere the original does. The source is
+    /// read in a single transaction, so concurrent writes are either fully in
+    /// the copy or not at all. The copy gets a new inode generation, so its
+    /// files, and mounts of it, are told apart from the original's. The copy
+    /// is not encrypted. Fails with `FsError::AlreadyExists` if `path` exists.
+    pub async fn snapshot(&self, path: &str) -> Result<()> {
+        self.copy_database(path, false).await
+    }
+
+    /// Write a compact copy of the database, for distribution, to a new file
+    /// at `path`
+    ///
+    /// Like [`AgentFS::snapshot`], but inodes are renumbered contiguously
+    /// from the root in breadth-first order, and inodes no longer reachable
+    /// from the root (e.g. unlinked while open) are left out along with their
+    /// data. Rewriting the database leaves no free pages behind, and the copy
+    /// is vacuumed where the database engine supports it.
+    pub async fn optimize(&self, path: &str) -> Result<()> {
+        self.copy_database(path, true).await
+    }
+
+    /// Copy the database to a new file at `path`, renumbering inodes if asked
+    async fn copy_database(&self, path: &str, renumber: bool) -> Result<()> {
+        if Path::new(path).exists() {
+            return Err(FsError::AlreadyExists.into());
+        }
+        let conn = self.pool.get_connection().await?;
+        let dest_db = Builder::new_local(path).build().await?;
+        let dest = dest_db.connect()?;
+
+        let txn = Transaction::new_unchecked(&conn, TransactionBehavior::Deferred).await?;
+        let result: Result<()> = async {
+            // Tables first, then their indexes
+            let mut rows = conn
+                .query(
+                    "SELECT type, name, sql FROM sqlite_master
+                     WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite_%'
+                     ORDER BY type = 'index'",
+                    (),
+                )
+                .await?;
+            let mut schema = Vec::new();
+            while let Some(row) = rows.next().await? {
+                let kind: String = row.get(0)?;
+                let name: String = row.get(1)?;
+                let sql: String = row.get(2)?;
+                schema.push((kind, name, sql));
+            }
+            drop(rows);
+
+            let inodes = if renumber {
+                Some(Self::renumber_inodes(&conn).await?)
+            } else {
+                None
+            };
+
+            let dest_txn =
+                Transaction::new_unchecked(&dest, TransactionBehavior::Immediate).await?;
+            for (_, _, sql) in &schema {
+                dest.execute(sql, ()).await?;
+            }
+            for (_, table, _) in schema.iter().filter(|(kind, _, _)| kind == "table") {
+                let mut columns = crate::schema::get_table_columns(&conn, table)
+                    .await?
+                    .into_iter()
+                    .map(|c| c.name)
+                    .collect::<Vec<_>>();
+                // Renumbered entries get fresh IDs too
+                if inodes.is_some() && table == "fs_dentry" {
+                    columns.retain(|c| c != "id");
+                }
+                let inode_columns: Vec<usize> = match &inodes {
+                    Some(_) => columns
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, c)| INODE_COLUMNS.contains(&(table.as_str(), c.as_str())))
+                        .map(|(i, _)| i)
+                        .collect(),
+                    None => Vec::new(),
+                };
+                let column_list = columns
+                    .iter()
+                    .map(|c| format!("\"{}\"", c))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let placeholders = vec!["?"; columns.len()].join(", ");
+                let insert = format!(
+                    "INSERT INTO \"{}\" ({}) VALUES ({})",
+                    table, column_list, placeholders
+                );
+                let mut rows = conn
+                    .query(&format!("SELECT {} FROM \"{}\"", column_list, table), ())
+                    .await?;
+                'rows: while let Some(row) = rows.next().await? {
+                    let mut values = (0..columns.len())
+                        .map(|i| row.get_value(i))
+                        .collect::<std::result::Result<Vec<Value>, _>>()?;
+                    if let Some(inodes) = &inodes {
+                        for &i in &inode_columns {
+                            let Value::Integer(ino) = values[i] else {
+                                continue;
+                            };
+                            // Rows of unreachable inodes are dropped
+                            let Some(&new_ino) = inodes.get(&ino) else {
+                                continue 'rows;
+                            };
+                            values[i] = Value::Integer(new_ino);
+                        }
+                    }
+                    dest.execute(&insert, values).await?;
+                }
+            }
+            // The copy's files are not the original's
+            dest.execute(
+                "UPDATE fs_config SET value = ? WHERE key = 'inode_generation'",
+                (Self::new_inode_generation()?.to_string(),),
+            )
+            .await?;
+            dest_txn.commit().await?;
+
+            if inodes.is_some() {
+                if let Err(e) = dest.execute("VACUUM", ()).await {
+                    tracing::debug!(error = %e, "not vacuuming the optimized copy");
+                }
+            }
+
+            // Leave a self-contained file, without a write-ahead log
+            let mut rows = dest.query("PRAGMA wal_checkpoint(TRUNCATE)", ()).await?;
+            while rows.next().await?.is_some() {}
+            Ok(())
+        }
+        .await;
+        txn.commit().await?;
 
-                // Add new entry to cache (source inode is now at destination)
-                self.dentry_cache.insert(dst_parent_ino, &dst_name, src_ino);
+        if result.is_err() {
+            let _ = std::fs::remove_file(path);
+            let _ = std::fs::remove_file(format!("{path}-wal"));
+        }
+        result
+    }
 
-                Ok(())
-            }
-            Err(e) => {
-                let _ = txn.rollback().await;
-                Err(e)
+    /// Number the inodes reachable from the root in breadth-first order,
+    /// visiting entries by name, mapping old inode numbers to new ones
+    async fn renumber_inodes(conn: &Connection) -> Result<HashMap<i64, i64>> {
+        let mut inodes = HashMap::from([(ROOT_INO, ROOT_INO)]);
+        let mut dirs = VecDeque::from([ROOT_INO]);
+        while let Some(dir) = dirs.pop_front() {
+            let mut stmt = conn
+                .prepare_cached(
+                    "SELECT d.ino, i.mode FROM fs_dentry d JOIN fs_inode i ON i.ino = d.ino
+                     WHERE d.parent_ino = ? ORDER BY d.name",
+                )
+                .await?;
+            let mut rows = stmt.query((dir,)).await?;
+            while let Some(row) = rows.next().await? {
+                let ino: i64 = row.get(0)?;
+                let mode: i64 = row.get(1)?;
+                // Hard links keep sharing the inode numbered first
+                if inodes.contains_key(&ino) {
+                    continue;
+                }
+                inodes.insert(ino, inodes.len() as i64 + 1);
+                if mode as u32 & S_IFMT == S_IFDIR {
+                    dirs.push_back(ino);
+                }
             }
         }
+        Ok(inodes)
     }
 
-    /// Get filesystem statistics
+    /// Check that the database is reachable
     ///
-    /// Returns the total number of inodes and bytes used by file contents.
-    pub async fn statfs(&self) -> Result<FilesystemStats> {
+    /// Runs a read-only lookup of the root inode, so a closed, missing, or
+    /// corrupted database surfaces as an error without touching any data.
+    pub async fn health(&self) -> Result<()> {
         let conn = self.pool.get_connection().await?;
-        // Count total inodes
-        let mut stmt = conn.prepare_cached("SELECT COUNT(*) FROM fs_inode").await?;
-        let mut rows = stmt.query(()).await?;
-
-        let inodes = if let Some(row) = rows.next().await? {
-            row.get_value(0)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0) as u64
-        } else {
-            0
-        };
-
-        // Sum total bytes used (from file sizes in inodes)
         let mut stmt = conn
-            .prepare_cached("SELECT COALESCE(SUM(size), 0) FROM fs_inode")
+            .prepare_cached("SELECT ino FROM fs_inode WHERE ino = ?")
             .await?;
-        let mut rows = stmt.query(()).await?;
-
-        let bytes_used = if let Some(row) = rows.next().await? {
-            row.get_value(0)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0) as u64
-        } else {
-            0
-        };
+        let mut rows = stmt.query((ROOT_INO,)).await?;
+        if rows.next().await?.is_none() {
+            return Err(FsError::NotFound.into());
+        }
+        Ok(())
+    }
 
-        Ok(FilesystemStats { inodes, bytes_used })
+    /// Checkpoint the write-ahead log into the main database file
+    ///
+    /// Runs a PASSIVE checkpoint: it copies as many frames as it can without
+    /// waiting on readers or writers, so it never blocks other operations.
+    pub async fn checkpoint(&self) -> Result<()> {
+        let conn = self.pool.get_connection().await?;
+        let mut rows = conn.query("PRAGMA wal_checkpoint(PASSIVE)", ()).await?;
+        while rows.next().await?.is_some() {}
+        Ok(())
     }
 
     /// Synchronize file data to persistent storage
@@ -2492,8 +4117,12 @@ impl AgentFS {
     /// maintaining high performance for normal operations.
     ///
     /// Note: The path parameter is ignored since all data is in a single database.
+    /// Inside a transaction this does nothing, as committing it syncs.
     pub async fn fsync(&self, _path: &str) -> Result<()> {
         let conn = self.pool.get_connection().await?;
+        if conn.in_transaction() {
+            return Ok(());
+        }
         conn.prepare_cached("PRAGMA synchronous = FULL")
             .await?
             .execute(())
@@ -2519,6 +4148,11 @@ impl AgentFS {
             pool: self.pool.clone(),
             ino,
             chunk_size: self.chunk_size,
+            generation: self.generation,
+            checksums: self.checksums,
+            max_file_size: self.max_file_size,
+            chunk_cache: self.chunk_cache.clone(),
+            change_hook: self.change_hook.clone(),
         }))
     }
 
@@ -2579,12 +4213,12 @@ impl FileSystem for AgentFS {
 
         // Get stats for the child inode
         let mut stmt = conn
-            .prepare_cached("SELECT ino, mode, nlink, uid, gid, size, atime, mtime, ctime, rdev, atime_nsec, mtime_nsec, ctime_nsec FROM fs_inode WHERE ino = ?")
+            .prepare_cached("SELECT ino, mode, nlink, uid, gid, size, atime, mtime, ctime, rdev, atime_nsec, mtime_nsec, ctime_nsec, btime, btime_nsec FROM fs_inode WHERE ino = ?")
             .await?;
         let mut rows = stmt.query((child_ino,)).await?;
 
         if let Some(row) = rows.next().await? {
-            let stats = Self::build_stats_from_row(&row)?;
+            let stats = Self::build_stats_from_row(&row, self.generation)?;
             // Cache the lookup result
             self.dentry_cache.insert(parent_ino, name, child_ino);
             Ok(Some(stats))
@@ -2593,6 +4227,29 @@ impl FileSystem for AgentFS {
         }
     }
 
+    async fn exists(&self, parent_ino: i64, name: &str) -> Result<bool> {
+        if name.len() > MAX_NAME_LEN {
+            return Err(FsError::NameTooLong.into());
+        }
+        // ".." has no dentry; answer exactly as `lookup` resolves it
+        if name == ".." {
+            return Ok(self.lookup(parent_ino, name).await?.is_some());
+        }
+        if self.dentry_cache.get(parent_ino, name).is_some() {
+            return Ok(true);
+        }
+        let conn = self.pool.get_connection().await?;
+
+        // Only the dentry is read, not the inode row behind it
+        match self.lookup_child(&conn, parent_ino, name).await? {
+            Some(ino) => {
+                self.dentry_cache.insert(parent_ino, name, ino);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
     async fn getattr(&self, ino: i64) -> Result<Option<Stats>> {
         let conn = self.pool.get_connection().await?;
         self.getattr_with_conn(&conn, ino).await
@@ -2714,7 +4371,7 @@ impl FileSystem for AgentFS {
             return Ok(None);
         }
 
-        let mut stmt = conn.prepare_cached("SELECT d.name, i.ino, i.mode, i.nlink, i.uid, i.gid, i.size, i.atime, i.mtime, i.ctime, i.rdev, i.atime_nsec, i.mtime_nsec, i.ctime_nsec
+        let mut stmt = conn.prepare_cached("SELECT d.name, i.ino, i.mode, i.nlink, i.uid, i.gid, i.size, i.atime, i.mtime, i.ctime, i.rdev, i.atime_nsec, i.mtime_nsec, i.ctime_nsec, i.btime, i.btime_nsec
             FROM fs_dentry d
             JOIN fs_inode i ON d.ino = i.ino
             WHERE d.parent_ino = ?
@@ -2803,11 +4460,22 @@ impl FileSystem for AgentFS {
                     .ok()
                     .and_then(|v| v.as_integer().copied())
                     .unwrap_or(0) as u32,
+                btime: row
+                    .get_value(14)
+                    .ok()
+                    .and_then(|v| v.as_integer().copied())
+                    .unwrap_or(0),
+                btime_nsec: row
+                    .get_value(15)
+                    .ok()
+                    .and_then(|v| v.as_integer().copied())
+                    .unwrap_or(0) as u32,
                 rdev: row
                     .get_value(10)
                     .ok()
                     .and_then(|v| v.as_integer().copied())
                     .unwrap_or(0) as u64,
+                generation: self.generation,
             };
 
             entries.push(DirEntry { name, stats });
@@ -2971,6 +4639,11 @@ impl FileSystem for AgentFS {
             pool: self.pool.clone(),
             ino,
             chunk_size: self.chunk_size,
+            generation: self.generation,
+            checksums: self.checksums,
+            max_file_size: self.max_file_size,
+            chunk_cache: self.chunk_cache.clone(),
+            change_hook: self.change_hook.clone(),
         }))
     }
 
@@ -2998,8 +4671,8 @@ impl FileSystem for AgentFS {
         let now_nsec = dur.subsec_nanos() as i64;
         let mut stmt = conn
             .prepare_cached(
-                "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime, atime_nsec, mtime_nsec, ctime_nsec)
-                VALUES (?, ?, ?, 0, ?, ?, ?, ?, ?, ?) RETURNING ino",
+                "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime, atime_nsec, mtime_nsec, ctime_nsec, btime, btime_nsec)
+                VALUES (?, ?, ?, 0, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING ino",
             )
             .await?;
         let dir_mode = super::S_IFDIR | (mode & 0o7777);
@@ -3014,6 +4687,8 @@ impl FileSystem for AgentFS {
                 now_nsec,
                 now_nsec,
                 now_nsec,
+                now_secs,
+                now_nsec,
             ))
             .await?;
 
@@ -3060,7 +4735,10 @@ impl FileSystem for AgentFS {
             atime_nsec: now_nsec as u32,
             mtime_nsec: now_nsec as u32,
             ctime_nsec: now_nsec as u32,
+            btime: now_secs,
+            btime_nsec: now_nsec as u32,
             rdev: 0,
+            generation: self.generation,
         })
     }
 
@@ -3085,15 +4763,15 @@ impl FileSystem for AgentFS {
         // Prepare statements before starting the transaction
         let mut inode_stmt = conn
             .prepare_cached(
-                "INSERT INTO fs_inode (mode, nlink, uid, gid, size, atime, mtime, ctime, atime_nsec, mtime_nsec, ctime_nsec)
-                 VALUES (?, 1, ?, ?, 0, ?, ?, ?, ?, ?, ?) RETURNING ino",
+                "INSERT INTO fs_inode (mode, nlink, uid, gid, size, atime, mtime, ctime, atime_nsec, mtime_nsec, ctime_nsec, btime, btime_nsec)
+                 VALUES (?, 1, ?, ?, 0, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING ino",
             )
             .await?;
         let mut dentry_stmt = conn
             .prepare_cached("INSERT INTO fs_dentry (name, parent_ino, ino) VALUES (?, ?, ?)")
             .await?;
 
-        let txn = Transaction::new_unchecked(&conn, TransactionBehavior::Immediate).await?;
+        let txn = OpTxn::begin(&conn).await?;
 
         let dur = SystemTime::now().duration_since(UNIX_EPOCH)?;
         let now_secs = dur.as_secs() as i64;
@@ -3111,6 +4789,8 @@ impl FileSystem for AgentFS {
                 now_nsec,
                 now_nsec,
                 now_nsec,
+                now_secs,
+                now_nsec,
             ))
             .await?;
 
@@ -3146,18 +4826,132 @@ impl FileSystem for AgentFS {
             atime_nsec: now_nsec as u32,
             mtime_nsec: now_nsec as u32,
             ctime_nsec: now_nsec as u32,
+            btime: now_secs,
+            btime_nsec: now_nsec as u32,
+            rdev: 0,
+            generation: self.generation,
+        };
+
+        let file: BoxedFile = Arc::new(AgentFSFile {
+            pool: self.pool.clone(),
+            ino,
+            chunk_size: self.chunk_size,
+            generation: self.generation,
+            checksums: self.checksums,
+            max_file_size: self.max_file_size,
+            chunk_cache: self.chunk_cache.clone(),
+            change_hook: self.change_hook.clone(),
+        });
+
+        Ok((stats, file))
+    }
+
+    async fn create_tmpfile(&self, mode: u32, uid: u32, gid: u32) -> Result<(Stats, BoxedFile)> {
+        let conn = self.pool.get_connection().await?;
+
+        let dur = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        let now_secs = dur.as_secs() as i64;
+        let now_nsec = dur.subsec_nanos() as i64;
+        let file_mode = S_IFREG | (mode & 0o7777);
+
+        // The inode gets no directory entry, so it starts out with nlink = 0
+        let mut stmt = conn
+            .prepare_cached(
+                "INSERT INTO fs_inode (mode, nlink, uid, gid, size, atime, mtime, ctime, atime_nsec, mtime_nsec, ctime_nsec, btime, btime_nsec)
+                 VALUES (?, 0, ?, ?, 0, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING ino",
+            )
+            .await?;
+        let row = stmt
+            .query_row((
+                file_mode as i64,
+                uid,
+                gid,
+                now_secs,
+                now_secs,
+                now_secs,
+                now_nsec,
+                now_nsec,
+                now_nsec,
+                now_secs,
+                now_nsec,
+            ))
+            .await?;
+
+        let ino = row
+            .get_value(0)
+            .ok()
+            .and_then(|v| v.as_integer().copied())
+            .ok_or_else(|| Error::Internal("failed to get inode".to_string()))?;
+
+        let stats = Stats {
+            ino,
+            mode: file_mode,
+            nlink: 0,
+            uid,
+            gid,
+            size: 0,
+            atime: now_secs,
+            mtime: now_secs,
+            ctime: now_secs,
+            atime_nsec: now_nsec as u32,
+            mtime_nsec: now_nsec as u32,
+            ctime_nsec: now_nsec as u32,
+            btime: now_secs,
+            btime_nsec: now_nsec as u32,
             rdev: 0,
+            generation: self.generation,
         };
 
         let file: BoxedFile = Arc::new(AgentFSFile {
             pool: self.pool.clone(),
             ino,
             chunk_size: self.chunk_size,
+            generation: self.generation,
+            checksums: self.checksums,
+            max_file_size: self.max_file_size,
+            chunk_cache: self.chunk_cache.clone(),
+            change_hook: self.change_hook.clone(),
         });
 
         Ok((stats, file))
     }
 
+    async fn remove_orphan(&self, ino: i64) -> Result<()> {
+        let conn = self.pool.get_connection().await?;
+
+        let txn = OpTxn::begin(&conn).await?;
+
+        // The inode may have been linked since it was created
+        let mut stmt = conn
+            .prepare_cached("SELECT nlink FROM fs_inode WHERE ino = ?")
+            .await?;
+        let mut rows = stmt.query((ino,)).await?;
+        let orphaned = match rows.next().await? {
+            Some(row) => {
+                row.get_value(0)
+                    .ok()
+                    .and_then(|v| v.as_integer().copied())
+                    .unwrap_or(0)
+                    == 0
+            }
+            None => false,
+        };
+        drop(rows);
+
+        if orphaned {
+            conn.execute("DELETE FROM fs_data WHERE ino = ?", (ino,))
+                .await?;
+            conn.execute("DELETE FROM fs_symlink WHERE ino = ?", (ino,))
+                .await?;
+            conn.execute("DELETE FROM fs_inode WHERE ino = ?", (ino,))
+                .await?;
+        }
+
+        txn.commit().await?;
+
+        Ok(())
+    }
+
     async fn mknod(
         &self,
         parent_ino: i64,
@@ -3183,8 +4977,8 @@ impl FileSystem for AgentFS {
         let now_nsec = dur.subsec_nanos() as i64;
         let mut stmt = conn
             .prepare_cached(
-                "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime, rdev, atime_nsec, mtime_nsec, ctime_nsec)
-                VALUES (?, ?, ?, 0, ?, ?, ?, ?, ?, ?, ?) RETURNING ino",
+                "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime, rdev, atime_nsec, mtime_nsec, ctime_nsec, btime, btime_nsec)
+                VALUES (?, ?, ?, 0, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING ino",
             )
             .await?;
         let row = stmt
@@ -3199,6 +4993,8 @@ impl FileSystem for AgentFS {
                 now_nsec,
                 now_nsec,
                 now_nsec,
+                now_secs,
+                now_nsec,
             ))
             .await?;
 
@@ -3243,7 +5039,10 @@ impl FileSystem for AgentFS {
             atime_nsec: now_nsec as u32,
             mtime_nsec: now_nsec as u32,
             ctime_nsec: now_nsec as u32,
+            btime: now_secs,
+            btime_nsec: now_nsec as u32,
             rdev,
+            generation: self.generation,
         })
     }
 
@@ -3274,13 +5073,14 @@ impl FileSystem for AgentFS {
 
         let mut stmt = conn
             .prepare_cached(
-                "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime, atime_nsec, mtime_nsec, ctime_nsec)
-                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING ino",
+                "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime, atime_nsec, mtime_nsec, ctime_nsec, btime, btime_nsec)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING ino",
             )
             .await?;
         let row = stmt
             .query_row((
                 mode, uid, gid, size, now_secs, now_secs, now_secs, now_nsec, now_nsec, now_nsec,
+                now_secs, now_nsec,
             ))
             .await?;
 
@@ -3305,121 +5105,51 @@ impl FileSystem for AgentFS {
         .await?;
 
         // Increment link count
-        conn.execute(
-            "UPDATE fs_inode SET nlink = nlink + 1 WHERE ino = ?",
-            (ino,),
-        )
-        .await?;
-
-        // Update parent directory ctime and mtime
-        conn.execute(
-            "UPDATE fs_inode SET ctime = ?, mtime = ?, ctime_nsec = ?, mtime_nsec = ? WHERE ino = ?",
-            (now_secs, now_secs, now_nsec, now_nsec, parent_ino),
-        )
-        .await?;
-
-        // Populate dentry cache
-        self.dentry_cache.insert(parent_ino, name, ino);
-
-        Ok(Stats {
-            ino,
-            mode,
-            nlink: 1,
-            uid,
-            gid,
-            size,
-            atime: now_secs,
-            mtime: now_secs,
-            ctime: now_secs,
-            atime_nsec: now_nsec as u32,
-            mtime_nsec: now_nsec as u32,
-            ctime_nsec: now_nsec as u32,
-            rdev: 0,
-        })
-    }
-
-    async fn unlink(&self, parent_ino: i64, name: &str) -> Result<()> {
-        if name.len() > MAX_NAME_LEN {
-            return Err(FsError::NameTooLong.into());
-        }
-        let conn = self.pool.get_connection().await?;
-
-        // Look up the child inode
-        let ino = self
-            .lookup_child(&conn, parent_ino, name)
-            .await?
-            .ok_or(FsError::NotFound)?;
-
-        // Check if it's a directory (use rmdir for directories)
-        let mut stmt = conn
-            .prepare_cached("SELECT mode FROM fs_inode WHERE ino = ?")
-            .await?;
-        let mut rows = stmt.query((ino,)).await?;
-
-        if let Some(row) = rows.next().await? {
-            let mode = row
-                .get_value(0)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0) as u32;
-
-            if (mode & S_IFMT) == super::S_IFDIR {
-                return Err(FsError::IsADirectory.into());
-            }
-        }
-
-        // Delete the directory entry
-        let mut stmt = conn
-            .prepare_cached("DELETE FROM fs_dentry WHERE parent_ino = ? AND name = ?")
-            .await?;
-        stmt.execute((parent_ino, name)).await?;
-
-        // Invalidate cache
-        self.dentry_cache.remove(parent_ino, name);
-
-        // Update parent directory mtime and ctime
-        let dur = SystemTime::now().duration_since(UNIX_EPOCH)?;
-        let now_secs = dur.as_secs() as i64;
-        let now_nsec = dur.subsec_nanos() as i64;
-        let mut stmt = conn
-            .prepare_cached("UPDATE fs_inode SET mtime = ?, ctime = ?, mtime_nsec = ?, ctime_nsec = ? WHERE ino = ?")
-            .await?;
-        stmt.execute((now_secs, now_secs, now_nsec, now_nsec, parent_ino))
-            .await?;
-
-        // Decrement link count and update ctime
-        let mut stmt = conn
-            .prepare_cached(
-                "UPDATE fs_inode SET nlink = nlink - 1, ctime = ?, ctime_nsec = ? WHERE ino = ?",
-            )
-            .await?;
-        stmt.execute((now_secs, now_nsec, ino)).await?;
-
-        // Check if this was the last link to the inode
-        let link_count = self.get_link_count(&conn, ino).await?;
-        if link_count == 0 {
-            // Delete data blocks
-            let mut stmt = conn
-                .prepare_cached("DELETE FROM fs_data WHERE ino = ?")
-                .await?;
-            stmt.execute((ino,)).await?;
+        conn.execute(
+            "UPDATE fs_inode SET nlink = nlink + 1 WHERE ino = ?",
+            (ino,),
+        )
+        .await?;
 
-            // Delete symlink if exists
-            let mut stmt = conn
-                .prepare_cached("DELETE FROM fs_symlink WHERE ino = ?")
-                .await?;
-            stmt.execute((ino,)).await?;
+        // Update parent directory ctime and mtime
+        conn.execute(
+            "UPDATE fs_inode SET ctime = ?, mtime = ?, ctime_nsec = ?, mtime_nsec = ? WHERE ino = ?",
+            (now_secs, now_secs, now_nsec, now_nsec, parent_ino),
+        )
+        .await?;
 
-            // Delete inode
-            let mut stmt = conn
-                .prepare_cached("DELETE FROM fs_inode WHERE ino = ?")
-                .await?;
-            stmt.execute((ino,)).await?;
-        }
+        // Populate dentry cache
+        self.dentry_cache.insert(parent_ino, name, ino);
+
+        Ok(Stats {
+            ino,
+            mode,
+            nlink: 1,
+            uid,
+            gid,
+            size,
+            atime: now_secs,
+            mtime: now_secs,
+            ctime: now_secs,
+            atime_nsec: now_nsec as u32,
+            mtime_nsec: now_nsec as u32,
+            ctime_nsec: now_nsec as u32,
+            btime: now_secs,
+            btime_nsec: now_nsec as u32,
+            rdev: 0,
+            generation: self.generation,
+        })
+    }
 
+    async fn unlink(&self, parent_ino: i64, name: &str) -> Result<()> {
+        self.unlink_entry(parent_ino, name, false).await?;
         Ok(())
     }
 
+    async fn unlink_keep_orphan(&self, parent_ino: i64, name: &str) -> Result<Option<i64>> {
+        self.unlink_entry(parent_ino, name, true).await
+    }
+
     async fn rmdir(&self, parent_ino: i64, name: &str) -> Result<()> {
         if name.len() > MAX_NAME_LEN {
             return Err(FsError::NameTooLong.into());
@@ -3474,292 +5204,204 @@ impl FileSystem for AgentFS {
         }
 
         // Delete the directory entry
-        let mut stmt = conn
-            .prepare_cached("DELETE FROM fs_dentry WHERE parent_ino = ? AND name = ?")
-            .await?;
-        stmt.execute((parent_ino, name)).await?;
-
-        // Invalidate cache
-        self.dentry_cache.remove(parent_ino, name);
-
-        // Decrement link count on removed directory
-        let mut stmt = conn
-            .prepare_cached("UPDATE fs_inode SET nlink = nlink - 1 WHERE ino = ?")
-            .await?;
-        stmt.execute((ino,)).await?;
-
-        // Decrement parent nlink (removed directory's ".." link) and update timestamps
-        let dur = SystemTime::now().duration_since(UNIX_EPOCH)?;
-        let now_secs = dur.as_secs() as i64;
-        let now_nsec = dur.subsec_nanos() as i64;
-        let mut stmt = conn
-            .prepare_cached(
-                "UPDATE fs_inode SET nlink = nlink - 1, ctime = ?, mtime = ?, ctime_nsec = ?, mtime_nsec = ? WHERE ino = ?",
-            )
-            .await?;
-        stmt.execute((now_secs, now_secs, now_nsec, now_nsec, parent_ino))
-            .await?;
-
-        // Delete inode if no more links
-        let link_count = self.get_link_count(&conn, ino).await?;
-        if link_count == 0 {
-            let mut stmt = conn
-                .prepare_cached("DELETE FROM fs_inode WHERE ino = ?")
-                .await?;
-            stmt.execute((ino,)).await?;
-        }
-
-        Ok(())
-    }
-
-    async fn link(&self, ino: i64, newparent_ino: i64, newname: &str) -> Result<Stats> {
-        if newname.len() > MAX_NAME_LEN {
-            return Err(FsError::NameTooLong.into());
-        }
-        let conn = self.pool.get_connection().await?;
-
-        // Check if source inode exists and is not a directory
-        let mut stmt = conn
-            .prepare_cached("SELECT mode FROM fs_inode WHERE ino = ?")
-            .await?;
-        let mut rows = stmt.query((ino,)).await?;
-
-        if let Some(row) = rows.next().await? {
-            let mode = row
-                .get_value(0)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0) as u32;
-
-            if (mode & S_IFMT) == super::S_IFDIR {
-                return Err(FsError::IsADirectory.into());
-            }
-        } else {
-            return Err(FsError::NotFound.into());
-        }
-
-        // Check if destination already exists
-        if self
-            .lookup_child(&conn, newparent_ino, newname)
-            .await?
-            .is_some()
-        {
-            return Err(FsError::AlreadyExists.into());
-        }
-
-        // Create directory entry pointing to the same inode
-        conn.execute(
-            "INSERT INTO fs_dentry (name, parent_ino, ino) VALUES (?, ?, ?)",
-            (newname, newparent_ino, ino),
-        )
-        .await?;
-
-        // Increment link count and update ctime
-        let dur = SystemTime::now().duration_since(UNIX_EPOCH)?;
-        let now_secs = dur.as_secs() as i64;
-        let now_nsec = dur.subsec_nanos() as i64;
-        conn.execute(
-            "UPDATE fs_inode SET nlink = nlink + 1, ctime = ?, ctime_nsec = ? WHERE ino = ?",
-            (now_secs, now_nsec, ino),
-        )
-        .await?;
-
-        // Update parent directory ctime and mtime
-        conn.execute(
-            "UPDATE fs_inode SET ctime = ?, mtime = ?, ctime_nsec = ?, mtime_nsec = ? WHERE ino = ?",
-            (now_secs, now_secs, now_nsec, now_nsec, newparent_ino),
-        )
-        .await?;
-
-        // Populate dentry cache
-        self.dentry_cache.insert(newparent_ino, newname, ino);
-
-        // Return updated stats
-        self.getattr_with_conn(&conn, ino)
-            .await?
-            .ok_or(FsError::NotFound.into())
-    }
-
-    async fn rename(
-        &self,
-        oldparent_ino: i64,
-        oldname: &str,
-        newparent_ino: i64,
-        newname: &str,
-    ) -> Result<()> {
-        if newname.len() > MAX_NAME_LEN {
-            return Err(FsError::NameTooLong.into());
-        }
-        let conn = self.pool.get_connection().await?;
-
-        // Get source inode
-        let src_ino = self
-            .lookup_child(&conn, oldparent_ino, oldname)
-            .await?
-            .ok_or(FsError::NotFound)?;
-
-        if src_ino == ROOT_INO {
-            return Err(FsError::RootOperation.into());
-        }
-
-        // Get source stats to check if it's a directory
-        let src_stats = self
-            .getattr_with_conn(&conn, src_ino)
-            .await?
-            .ok_or(FsError::NotFound)?;
-
-        let txn = Transaction::new_unchecked(&conn, TransactionBehavior::Immediate).await?;
-
-        let result: Result<()> = async {
-            // Check if destination exists
-            if let Some(dst_ino) = self.lookup_child(&conn, newparent_ino, newname).await? {
-                let dst_stats = self.getattr_with_conn(&conn, dst_ino).await?.ok_or(FsError::NotFound)?;
-
-                // Can't replace directory with non-directory
-                if dst_stats.is_directory() && !src_stats.is_directory() {
-                    return Err(FsError::IsADirectory.into());
-                }
-
-                // Can't replace non-directory with directory
-                if !dst_stats.is_directory() && src_stats.is_directory() {
-                    return Err(FsError::NotADirectory.into());
-                }
-
-                // If destination is directory, it must be empty
-                if dst_stats.is_directory() {
-                    let mut stmt = conn
-                        .prepare_cached("SELECT COUNT(*) FROM fs_dentry WHERE parent_ino = ?")
-                        .await?;
-                    let mut rows = stmt.query((dst_ino,)).await?;
-
-                    if let Some(row) = rows.next().await? {
-                        let count = row
-                            .get_value(0)
-                            .ok()
-                            .and_then(|v| v.as_integer().copied())
-                            .unwrap_or(0);
-                        if count > 0 {
-                            return Err(FsError::NotEmpty.into());
-                        }
-                    }
-                }
-
-                // Remove destination entry
-                let mut stmt = conn
-                    .prepare_cached("DELETE FROM fs_dentry WHERE parent_ino = ? AND name = ?")
-                    .await?;
-                stmt.execute((newparent_ino, newname)).await?;
-
-                // Decrement link count and update ctime on destination inode
-                let dur_dec = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap_or_default();
-                let now_dec = dur_dec.as_secs() as i64;
-                let now_dec_nsec = dur_dec.subsec_nanos() as i64;
-                let mut stmt = conn
-                    .prepare_cached("UPDATE fs_inode SET nlink = nlink - 1, ctime = ?, ctime_nsec = ? WHERE ino = ?")
-                    .await?;
-                stmt.execute((now_dec, now_dec_nsec, dst_ino)).await?;
-
-                // Clean up destination inode if no more links
-                let link_count = self.get_link_count(&conn, dst_ino).await?;
-                if link_count == 0 {
-                    let mut stmt = conn
-                        .prepare_cached("DELETE FROM fs_data WHERE ino = ?")
-                        .await?;
-                    stmt.execute((dst_ino,)).await?;
-                    let mut stmt = conn
-                        .prepare_cached("DELETE FROM fs_symlink WHERE ino = ?")
-                        .await?;
-                    stmt.execute((dst_ino,)).await?;
-                    let mut stmt = conn
-                        .prepare_cached("DELETE FROM fs_inode WHERE ino = ?")
-                        .await?;
-                    stmt.execute((dst_ino,)).await?;
-                }
-            }
-
-            // Update the dentry: change parent and/or name
-            let mut stmt = conn
-                .prepare_cached(
-                    "UPDATE fs_dentry SET parent_ino = ?, name = ? WHERE parent_ino = ? AND name = ?",
-                )
-                .await?;
-            stmt.execute((newparent_ino, newname, oldparent_ino, oldname))
-                .await?;
+        let mut stmt = conn
+            .prepare_cached("DELETE FROM fs_dentry WHERE parent_ino = ? AND name = ?")
+            .await?;
+        stmt.execute((parent_ino, name)).await?;
 
-            // If renaming a directory across parents, adjust parent nlink counts
-            // (the ".." link moves from old parent to new parent)
-            if src_stats.is_directory() && oldparent_ino != newparent_ino {
-                let mut stmt = conn
-                    .prepare_cached("UPDATE fs_inode SET nlink = nlink - 1 WHERE ino = ?")
-                    .await?;
-                stmt.execute((oldparent_ino,)).await?;
+        // Invalidate cache
+        self.dentry_cache.remove(parent_ino, name);
 
-                let mut stmt = conn
-                    .prepare_cached("UPDATE fs_inode SET nlink = nlink + 1 WHERE ino = ?")
-                    .await?;
-                stmt.execute((newparent_ino,)).await?;
-            }
+        // Decrement link count on removed directory
+        let mut stmt = conn
+            .prepare_cached("UPDATE fs_inode SET nlink = nlink - 1 WHERE ino = ?")
+            .await?;
+        stmt.execute((ino,)).await?;
 
-            // Update ctime of the inode
-            let dur = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap_or_default();
-            let now_secs = dur.as_secs() as i64;
-            let now_nsec = dur.subsec_nanos() as i64;
+        // Decrement parent nlink (removed directory's ".." link) and update timestamps
+        let dur = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        let now_secs = dur.as_secs() as i64;
+        let now_nsec = dur.subsec_nanos() as i64;
+        let mut stmt = conn
+            .prepare_cached(
+                "UPDATE fs_inode SET nlink = nlink - 1, ctime = ?, mtime = ?, ctime_nsec = ?, mtime_nsec = ? WHERE ino = ?",
+            )
+            .await?;
+        stmt.execute((now_secs, now_secs, now_nsec, now_nsec, parent_ino))
+            .await?;
 
+        // Delete inode if no more links
+        let link_count = self.get_link_count(&conn, ino).await?;
+        if link_count == 0 {
             let mut stmt = conn
-                .prepare_cached("UPDATE fs_inode SET ctime = ?, ctime_nsec = ? WHERE ino = ?")
+                .prepare_cached("DELETE FROM fs_inode WHERE ino = ?")
                 .await?;
-            stmt.execute((now_secs, now_nsec, src_ino)).await?;
+            stmt.execute((ino,)).await?;
+        }
 
-            // Update source parent directory timestamps
-            let mut stmt = conn
-                .prepare_cached("UPDATE fs_inode SET mtime = ?, ctime = ?, mtime_nsec = ?, ctime_nsec = ? WHERE ino = ?")
-                .await?;
-            stmt.execute((now_secs, now_secs, now_nsec, now_nsec, oldparent_ino)).await?;
+        Ok(())
+    }
 
-            // Update destination parent directory timestamps
-            if newparent_ino != oldparent_ino {
-                let mut stmt = conn
-                    .prepare_cached("UPDATE fs_inode SET mtime = ?, ctime = ?, mtime_nsec = ?, ctime_nsec = ? WHERE ino = ?")
-                    .await?;
-                stmt.execute((now_secs, now_secs, now_nsec, now_nsec, newparent_ino)).await?;
+    async fn link(&self, ino: i64, newparent_ino: i64, newname: &str) -> Result<Stats> {
+        if newname.len() > MAX_NAME_LEN {
+            return Err(FsError::NameTooLong.into());
+        }
+        let conn = self.pool.get_connection().await?;
+
+        // Check if source inode exists and is not a directory
+        let mut stmt = conn
+            .prepare_cached("SELECT mode FROM fs_inode WHERE ino = ?")
+            .await?;
+        let mut rows = stmt.query((ino,)).await?;
+
+        if let Some(row) = rows.next().await? {
+            let mode = row
+                .get_value(0)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0) as u32;
+
+            if (mode & S_IFMT) == super::S_IFDIR {
+                return Err(FsError::IsADirectory.into());
             }
+        } else {
+            return Err(FsError::NotFound.into());
+        }
+        check_inode_flags(&conn, ino, FS_IMMUTABLE_FL | FS_APPEND_FL).await?;
 
-            Ok(())
+        // Check if destination already exists
+        if self
+            .lookup_child(&conn, newparent_ino, newname)
+            .await?
+            .is_some()
+        {
+            return Err(FsError::AlreadyExists.into());
         }
-        .await;
 
-        match result {
-            Ok(()) => {
-                txn.commit().await?;
+        // Create directory entry pointing to the same inode
+        conn.execute(
+            "INSERT INTO fs_dentry (name, parent_ino, ino) VALUES (?, ?, ?)",
+            (newname, newparent_ino, ino),
+        )
+        .await?;
+
+        // Increment link count and update ctime
+        let dur = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        let now_secs = dur.as_secs() as i64;
+        let now_nsec = dur.subsec_nanos() as i64;
+        conn.execute(
+            "UPDATE fs_inode SET nlink = nlink + 1, ctime = ?, ctime_nsec = ? WHERE ino = ?",
+            (now_secs, now_nsec, ino),
+        )
+        .await?;
 
-                // Invalidate cache for source and destination
-                self.dentry_cache.remove(oldparent_ino, oldname);
-                self.dentry_cache.remove(newparent_ino, newname);
+        // Update parent directory ctime and mtime
+        conn.execute(
+            "UPDATE fs_inode SET ctime = ?, mtime = ?, ctime_nsec = ?, mtime_nsec = ? WHERE ino = ?",
+            (now_secs, now_secs, now_nsec, now_nsec, newparent_ino),
+        )
+        .await?;
 
-                // Add new entry to cache (source inode is now at destination)
-                self.dentry_cache.insert(newparent_ino, newname, src_ino);
+        // Populate dentry cache
+        self.dentry_cache.insert(newparent_ino, newname, ino);
 
-                Ok(())
-            }
-            Err(e) => {
-                let _ = txn.rollback().await;
-                Err(e)
-            }
+        // Return updated stats
+        self.getattr_with_conn(&conn, ino)
+            .await?
+            .ok_or(FsError::NotFound.into())
+    }
+
+    async fn rename(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+    ) -> Result<()> {
+        self.rename_with_flags(oldparent_ino, oldname, newparent_ino, newname, 0)
+            .await
+    }
+
+    async fn rename_with_flags(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+        flags: u32,
+    ) -> Result<()> {
+        self.rename_entry(oldparent_ino, oldname, newparent_ino, newname, flags, false)
+            .await?;
+        Ok(())
+    }
+
+    async fn rename_keep_orphan(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+        flags: u32,
+    ) -> Result<Option<i64>> {
+        self.rename_entry(oldparent_ino, oldname, newparent_ino, newname, flags, true)
+            .await
+    }
+
+    async fn get_inode_flags(&self, ino: i64) -> Result<u32> {
+        let conn = self.pool.get_connection().await?;
+        let mut stmt = conn
+            .prepare_cached("SELECT flags FROM fs_inode WHERE ino = ?")
+            .await?;
+        let mut rows = stmt.query((ino,)).await?;
+        match rows.next().await? {
+            Some(row) => Ok(row
+                .get_value(0)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0) as u32),
+            None => Err(FsError::NotFound.into()),
         }
     }
 
+    async fn set_inode_flags(&self, ino: i64, flags: u32) -> Result<()> {
+        if flags & !(FS_IMMUTABLE_FL | FS_APPEND_FL) != 0 {
+            return Err(FsError::NotSupported.into());
+        }
+        let conn = self.pool.get_connection().await?;
+        if self.getattr_with_conn(&conn, ino).await?.is_none() {
+            return Err(FsError::NotFound.into());
+        }
+
+        let dur = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        let now_secs = dur.as_secs() as i64;
+        let now_nsec = dur.subsec_nanos() as i64;
+        let mut stmt = conn
+            .prepare_cached(
+                "UPDATE fs_inode SET flags = ?, ctime = ?, ctime_nsec = ? WHERE ino = ?",
+            )
+            .await?;
+        stmt.execute((flags as i64, now_secs, now_nsec, ino))
+            .await?;
+        Ok(())
+    }
+
     async fn statfs(&self) -> Result<FilesystemStats> {
         AgentFS::statfs(self).await
     }
+
+    async fn health(&self) -> Result<()> {
+        AgentFS::health(self).await
+    }
+
+    async fn checkpoint(&self) -> Result<()> {
+        AgentFS::checkpoint(self).await
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::TinyTmpfs;
     use tempfile::tempdir;
 
     async fn create_test_fs() -> Result<(AgentFS, tempfile::TempDir)> {
@@ -3769,6 +5411,71 @@ mod tests {
         Ok((fs, dir))
     }
 
+    #[tokio::test]
+    async fn test_new_in_memory() -> Result<()> {
+        let fs = AgentFS::new_in_memory().await?;
+
+        fs.mkdir("/dir", 0, 0).await?;
+        let (_, file) = fs
+            .create_file("/dir/hello.txt", DEFAULT_FILE_MODE, 0, 0)
+            .await?;
+        file.pwrite(0, b"hello").await?;
+
+        // A clone shares the pool, and with it the same database
+        let clone = fs.clone();
+        assert_eq!(clone.read_file("/dir/hello.txt").await?.unwrap(), b"hello");
+        let dir_ino = clone.resolve_path("/dir").await?.unwrap();
+        assert_eq!(clone.readdir(dir_ino).await?.unwrap(), vec!["hello.txt"]);
+
+        // Separate instances are independent
+        let other = AgentFS::new_in_memory().await?;
+        assert!(other.stat("/dir").await?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_exists() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        fs.mkdir("/dir", 0, 0).await?;
+        fs.create_file("/dir/a.txt", DEFAULT_FILE_MODE, 0, 0)
+            .await?;
+        fs.symlink("/missing", "/dir/dangling", 0, 0).await?;
+        let dir_ino = fs.resolve_path("/dir").await?.unwrap();
+
+        assert!(FileSystem::exists(&fs, ROOT_INO, "dir").await?);
+        assert!(FileSystem::exists(&fs, dir_ino, "a.txt").await?);
+        assert!(FileSystem::exists(&fs, dir_ino, "dangling").await?);
+        assert!(!FileSystem::exists(&fs, dir_ino, "b.txt").await?);
+        assert!(!FileSystem::exists(&fs, ROOT_INO, "a.txt").await?);
+
+        // Removing the entry is seen even after a cached positive answer
+        fs.remove("/dir/a.txt").await?;
+        assert!(!FileSystem::exists(&fs, dir_ino, "a.txt").await?);
+
+        // "." and ".." answer as lookup does, on directories and other files
+        fs.create_file("/dir/c.txt", DEFAULT_FILE_MODE, 0, 0)
+            .await?;
+        let file_ino = fs.resolve_path("/dir/c.txt").await?.unwrap();
+        let link_ino = fs.resolve_path("/dir/dangling").await?.unwrap();
+        for ino in [ROOT_INO, dir_ino, file_ino, link_ino, 9999] {
+            for name in [".", ".."] {
+                assert_eq!(
+                    FileSystem::exists(&fs, ino, name).await?,
+                    FileSystem::lookup(&fs, ino, name).await?.is_some(),
+                    "exists({}, {:?})",
+                    ino,
+                    name
+                );
+            }
+        }
+        assert!(!FileSystem::exists(&fs, dir_ino, ".").await?);
+        assert!(FileSystem::exists(&fs, dir_ino, "..").await?);
+        assert!(FileSystem::exists(&fs, file_ino, "..").await?);
+
+        Ok(())
+    }
+
     // ==================== Chunk Size Boundary Tests ====================
 
     #[tokio::test]
@@ -4217,13 +5924,109 @@ mod tests {
             .query("SELECT COUNT(*) FROM fs_data WHERE ino = ?", (ino,))
             .await?;
 
-        let count = rows
-            .next()
-            .await?
-            .and_then(|r| r.get_value(0).ok().and_then(|v| v.as_integer().copied()))
-            .unwrap_or(-1);
+        let count = rows
+            .next()
+            .await?
+            .and_then(|r| r.get_value(0).ok().and_then(|v| v.as_integer().copied()))
+            .unwrap_or(-1);
+
+        assert_eq!(count, 0, "All chunks should be deleted");
+
+        Ok(())
+    }
+
+    // ==================== Checksum Tests ====================
+
+    #[tokio::test]
+    async fn test_checksum_detects_corrupted_chunk() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        let fs = fs.with_checksums(true);
+
+        let chunk_size = fs.chunk_size();
+        let data: Vec<u8> = (0..chunk_size * 2).map(|i| (i % 251) as u8).collect();
+        let (_, file) = fs
+            .create_file("/checked.txt", DEFAULT_FILE_MODE, 0, 0)
+            .await?;
+        file.pwrite(0, &data).await?;
+        assert_eq!(file.pread(0, data.len() as u64).await?, data);
+
+        // Flip a byte in the second chunk behind the filesystem's back
+        let ino = fs.resolve_path("/checked.txt").await?.unwrap();
+        let mut corrupted = data[chunk_size..].to_vec();
+        corrupted[10] ^= 0xff;
+        {
+            // The pool has a single connection, so release it before reading
+            let conn = fs.pool.get_connection().await?;
+            conn.execute(
+                "UPDATE fs_data SET data = ? WHERE ino = ? AND chunk_index = 1",
+                (corrupted, ino),
+            )
+            .await?;
+        }
+
+        // The intact first chunk still reads fine
+        assert_eq!(file.pread(0, chunk_size as u64).await?, &data[..chunk_size]);
+
+        let err = file
+            .pread(chunk_size as u64, chunk_size as u64)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Fs(FsError::ChecksumMismatch)));
+        let err = fs
+            .pread("/checked.txt", 0, data.len() as u64)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Fs(FsError::ChecksumMismatch)));
+
+        // Rewriting the chunk stores a fresh checksum
+        file.pwrite(chunk_size as u64, &data[chunk_size..]).await?;
+        assert_eq!(file.pread(0, data.len() as u64).await?, data);
+
+        Ok(())
+    }
+
+    // ==================== Sparse File Tests ====================
+
+    #[tokio::test]
+    async fn test_seek_data_and_hole_on_sparse_file() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        let chunk_size = fs.chunk_size() as u64;
+
+        // Data in chunks 0 and 3, holes in chunks 1-2 and past the last write
+        let (_, file) = fs
+            .create_file("/sparse.bin", DEFAULT_FILE_MODE, 0, 0)
+            .await?;
+        file.pwrite(0, &vec![1u8; chunk_size as usize]).await?;
+        file.pwrite(3 * chunk_size, &vec![2u8; chunk_size as usize])
+            .await?;
+        file.truncate(6 * chunk_size).await?;
+
+        assert_eq!(file.seek_data(0).await?, 0);
+        assert_eq!(file.seek_data(10).await?, 10);
+        assert_eq!(file.seek_data(chunk_size).await?, 3 * chunk_size);
+        assert_eq!(file.seek_hole(0).await?, chunk_size);
+        assert_eq!(
+            file.seek_hole(2 * chunk_size + 5).await?,
+            2 * chunk_size + 5
+        );
+        assert_eq!(file.seek_hole(3 * chunk_size).await?, 4 * chunk_size);
+
+        // Only a hole remains past the last data chunk
+        let err = file.seek_data(4 * chunk_size).await.unwrap_err();
+        assert!(matches!(err, Error::Fs(FsError::NoSuchOffset)));
+        assert_eq!(file.seek_hole(5 * chunk_size).await?, 5 * chunk_size);
 
-        assert_eq!(count, 0, "All chunks should be deleted");
+        // Offsets at or past EOF fail with ENXIO
+        let err = file.seek_hole(6 * chunk_size).await.unwrap_err();
+        assert!(matches!(err, Error::Fs(FsError::NoSuchOffset)));
+
+        // A fully allocated file is all data, with its only hole at EOF
+        let (_, dense) = fs
+            .create_file("/dense.bin", DEFAULT_FILE_MODE, 0, 0)
+            .await?;
+        dense.pwrite(0, &[3u8; 100]).await?;
+        assert_eq!(dense.seek_data(42).await?, 42);
+        assert_eq!(dense.seek_hole(42).await?, 100);
 
         Ok(())
     }
@@ -4555,6 +6358,17 @@ mod tests {
         let result = fs.read_file("/test.txt").await?.unwrap();
         assert_eq!(result.len(), 100);
         assert_eq!(&result[..50], &data[..]);
+        assert!(result[50..].iter().all(|&b| b == 0));
+
+        // Extending past several chunks stores no new data
+        let chunk_size = fs.chunk_size();
+        fs.truncate("/test.txt", (chunk_size * 4) as u64).await?;
+        let ino = fs.resolve_path("/test.txt").await?.unwrap();
+        assert_eq!(fs.get_chunk_count(ino).await?, 1);
+        let result = fs.read_file("/test.txt").await?.unwrap();
+        assert_eq!(result.len(), chunk_size * 4);
+        assert_eq!(&result[..50], &data[..]);
+        assert!(result[50..].iter().all(|&b| b == 0));
 
         Ok(())
     }
@@ -4661,6 +6475,68 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_rename_noreplace_existing_file_fails() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+
+        let (_, file) = fs.create_file("/src.txt", DEFAULT_FILE_MODE, 0, 0).await?;
+        file.pwrite(0, b"source").await?;
+        let (_, file) = fs.create_file("/dst.txt", DEFAULT_FILE_MODE, 0, 0).await?;
+        file.pwrite(0, b"destination").await?;
+
+        let err = fs
+            .rename_with_flags(ROOT_INO, "src.txt", ROOT_INO, "dst.txt", RENAME_NOREPLACE)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Fs(FsError::AlreadyExists)), "{err}");
+
+        // Both files are left untouched
+        assert_eq!(fs.read_file("/src.txt").await?.unwrap(), b"source");
+        assert_eq!(fs.read_file("/dst.txt").await?.unwrap(), b"destination");
+
+        // Without a destination it behaves like a plain rename
+        fs.rename_with_flags(ROOT_INO, "src.txt", ROOT_INO, "new.txt", RENAME_NOREPLACE)
+            .await?;
+        assert!(fs.stat("/src.txt").await?.is_none());
+        assert_eq!(fs.read_file("/new.txt").await?.unwrap(), b"source");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rename_exchange_swaps_files() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+
+        fs.mkdir("/subdir", 0, 0).await?;
+        let (_, file) = fs.create_file("/a.txt", DEFAULT_FILE_MODE, 0, 0).await?;
+        file.pwrite(0, b"first").await?;
+        let (_, file) = fs
+            .create_file("/subdir/b.txt", DEFAULT_FILE_MODE, 0, 0)
+            .await?;
+        file.pwrite(0, b"second").await?;
+        let a_ino = fs.resolve_path("/a.txt").await?.unwrap();
+        let b_ino = fs.resolve_path("/subdir/b.txt").await?.unwrap();
+        let subdir_ino = fs.resolve_path("/subdir").await?.unwrap();
+
+        fs.rename_with_flags(ROOT_INO, "a.txt", subdir_ino, "b.txt", RENAME_EXCHANGE)
+            .await?;
+
+        assert_eq!(fs.read_file("/a.txt").await?.unwrap(), b"second");
+        assert_eq!(fs.read_file("/subdir/b.txt").await?.unwrap(), b"first");
+        assert_eq!(fs.lookup(ROOT_INO, "a.txt").await?.unwrap().ino, b_ino);
+        assert_eq!(fs.lookup(subdir_ino, "b.txt").await?.unwrap().ino, a_ino);
+
+        // Exchanging with a missing entry fails without changing anything
+        let err = fs
+            .rename_with_flags(ROOT_INO, "a.txt", ROOT_INO, "missing", RENAME_EXCHANGE)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Fs(FsError::NotFound)), "{err}");
+        assert_eq!(fs.read_file("/a.txt").await?.unwrap(), b"second");
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_rename_directory() -> Result<()> {
         let (fs, _dir) = create_test_fs().await?;
@@ -4686,6 +6562,34 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_rename_directory_invalidates_cached_descendants() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+
+        fs.mkdir("/a", 0, 0).await?;
+        fs.mkdir("/a/b", 0, 0).await?;
+        fs.create_file("/a/b/c.txt", DEFAULT_FILE_MODE, 0, 0)
+            .await?;
+
+        // Resolve the descendant so every component is cached
+        let ino = fs.stat("/a/b/c.txt").await?.unwrap().ino;
+        assert_eq!(fs.stat("/a/b/c.txt").await?.unwrap().ino, ino);
+
+        fs.rename("/a", "/z").await?;
+
+        assert!(fs.stat("/a/b/c.txt").await?.is_none());
+        assert!(fs.stat("/a/b").await?.is_none());
+        assert!(FileSystem::lookup(&fs, ROOT_INO, "a").await?.is_none());
+        assert_eq!(fs.stat("/z/b/c.txt").await?.unwrap().ino, ino);
+
+        // Moving it back restores the old paths, with no stale entry for /z
+        fs.rename("/z", "/a").await?;
+        assert!(fs.stat("/z/b/c.txt").await?.is_none());
+        assert_eq!(fs.stat("/a/b/c.txt").await?.unwrap().ino, ino);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_rename_directory_into_own_subtree_fails() -> Result<()> {
         let (fs, _dir) = create_test_fs().await?;
@@ -4819,6 +6723,44 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_btime_set_at_creation_and_stable() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+
+        let (_, file) = fs.create_file("/born.txt", DEFAULT_FILE_MODE, 0, 0).await?;
+        let created = fs.stat("/born.txt").await?.unwrap();
+        assert!(created.btime > 0);
+        assert_eq!(
+            (created.btime, created.btime_nsec),
+            (created.ctime, created.ctime_nsec)
+        );
+
+        // Small delay to ensure time changes
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+
+        file.pwrite(0, b"data").await?;
+        fs.chmod(created.ino, 0o600).await?;
+        fs.rename("/born.txt", "/renamed.txt").await?;
+
+        let modified = fs.stat("/renamed.txt").await?.unwrap();
+        assert_eq!(
+            (modified.btime, modified.btime_nsec),
+            (created.btime, created.btime_nsec)
+        );
+        assert!(
+            (modified.ctime, modified.ctime_nsec) > (modified.btime, modified.btime_nsec),
+            "ctime should move past the creation time"
+        );
+
+        // Listings report the same creation time
+        let entries = fs.readdir_plus(ROOT_INO).await?.unwrap();
+        let entry = entries.iter().find(|e| e.name == "renamed.txt").unwrap();
+        assert_eq!(entry.stats.btime, created.btime);
+        assert_eq!(entry.stats.btime_nsec, created.btime_nsec);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_chmod_regular_file() -> Result<()> {
         let (fs, _dir) = create_test_fs().await?;
@@ -4895,6 +6837,37 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_stat_follows_relative_symlink_up_and_over() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        fs.mkdir("/a", 0, 0).await?;
+        fs.mkdir("/a/b", 0, 0).await?;
+        fs.mkdir("/a/x", 0, 0).await?;
+        fs.create_file("/a/x/y", DEFAULT_FILE_MODE, 0, 0).await?;
+        // The same name at the root, to catch targets resolved against the
+        // wrong directory
+        fs.mkdir("/x", 0, 0).await?;
+        fs.create_file("/x/y", DEFAULT_FILE_MODE, 0, 0).await?;
+
+        fs.symlink("../x/y", "/a/b/link", 0, 0).await?;
+        let expected = fs.resolve_path("/a/x/y").await?.unwrap();
+        let stats = fs.stat("/a/b/link").await?.unwrap();
+        assert_eq!(stats.ino, expected);
+        assert!(stats.is_file());
+
+        // A chain of relative links resolves each hop against its own directory
+        fs.symlink("b/link", "/a/hop", 0, 0).await?;
+        assert_eq!(fs.stat("/a/hop").await?.unwrap().ino, expected);
+
+        // `..` never climbs above the root
+        fs.symlink("../../../x/y", "/a/b/deep", 0, 0).await?;
+        assert_eq!(
+            fs.stat("/a/b/deep").await?.unwrap().ino,
+            fs.resolve_path("/x/y").await?.unwrap()
+        );
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_chmod_symlink() -> Result<()> {
         let (fs, _dir) = create_test_fs().await?;
@@ -4915,4 +6888,575 @@ mod tests {
 
         Ok(())
     }
+
+    // ==================== Orphan Inode Tests ====================
+
+    #[tokio::test]
+    async fn test_tmpfile_link_into_directory() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        fs.mkdir("/build", 0, 0).await?;
+        let build = fs.lstat("/build").await?.unwrap();
+
+        let (stats, file) = fs.create_tmpfile(0o644, 0, 0).await?;
+        assert_eq!(stats.nlink, 0);
+        assert!(stats.is_file());
+        file.pwrite(0, b"generated output").await?;
+
+        // The orphan is reachable by inode but not by any name
+        assert_eq!(fs.getattr(stats.ino).await?.unwrap().nlink, 0);
+        assert!(fs.readdir(build.ino).await?.unwrap().is_empty());
+
+        let linked = FileSystem::link(&fs, stats.ino, build.ino, "out.o").await?;
+        assert_eq!(linked.nlink, 1);
+
+        // Linked inodes survive remove_orphan()
+        fs.remove_orphan(stats.ino).await?;
+        let data = fs.read_file("/build/out.o").await?.unwrap();
+        assert_eq!(data, b"generated output");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_remove_orphan_deletes_unlinked_tmpfile() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+
+        let (stats, file) = fs.create_tmpfile(0o600, 0, 0).await?;
+        file.pwrite(0, b"scratch").await?;
+
+        assert_eq!(fs.get_chunk_count(stats.ino).await?, 1);
+
+        fs.remove_orphan(stats.ino).await?;
+        assert!(fs.getattr(stats.ino).await?.is_none());
+        assert_eq!(fs.get_chunk_count(stats.ino).await?, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rename_keeps_replaced_inode_as_orphan() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        let (_, file) = FileSystem::create_file(&fs, ROOT_INO, "new.txt", 0o644, 0, 0).await?;
+        file.pwrite(0, b"new").await?;
+        let (old, file) = FileSystem::create_file(&fs, ROOT_INO, "old.txt", 0o644, 0, 0).await?;
+        file.pwrite(0, b"old").await?;
+
+        let orphan = fs
+            .rename_keep_orphan(ROOT_INO, "new.txt", ROOT_INO, "old.txt", 0)
+            .await?;
+        assert_eq!(orphan, Some(old.ino));
+        assert_eq!(fs.read_file("/old.txt").await?.unwrap(), b"new");
+
+        // The replaced file stays readable through its handle until released
+        assert_eq!(fs.getattr(old.ino).await?.unwrap().nlink, 0);
+        assert_eq!(file.pread(0, 3).await?, b"old");
+        fs.remove_orphan(old.ino).await?;
+        assert!(fs.getattr(old.ino).await?.is_none());
+
+        // Without a replaced file, or with a link left, nothing is kept
+        FileSystem::link(
+            &fs,
+            fs.lstat("/old.txt").await?.unwrap().ino,
+            ROOT_INO,
+            "a.txt",
+        )
+        .await?;
+        assert_eq!(
+            fs.rename_keep_orphan(ROOT_INO, "a.txt", ROOT_INO, "b.txt", 0)
+                .await?,
+            None
+        );
+        FileSystem::create_file(&fs, ROOT_INO, "c.txt", 0o644, 0, 0).await?;
+        assert_eq!(
+            fs.rename_keep_orphan(ROOT_INO, "c.txt", ROOT_INO, "b.txt", 0)
+                .await?,
+            None
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sweep_orphans_deletes_unreleased_inodes() -> Result<()> {
+        let dir = tempdir()?;
+        let db_path = dir.path().join("test.db");
+        let db_path = db_path.to_str().unwrap();
+
+        let (tmp, unlinked) = {
+            let fs = AgentFS::new(db_path).await?;
+            let (tmp, file) = fs.create_tmpfile(0o600, 0, 0).await?;
+            file.pwrite(0, b"scratch").await?;
+            let (stats, file) =
+                FileSystem::create_file(&fs, ROOT_INO, "open.txt", 0o644, 0, 0).await?;
+            file.pwrite(0, b"still open").await?;
+            let unlinked = fs.unlink_keep_orphan(ROOT_INO, "open.txt").await?;
+            assert_eq!(unlinked, Some(stats.ino));
+            // Dropped without remove_orphan(), as if the process died
+            (tmp.ino, stats.ino)
+        };
+
+        // Opening the database leaves them alone, as another instance
+        // sharing it may still have them open
+        let fs = AgentFS::new(db_path).await?;
+        for ino in [tmp, unlinked] {
+            assert!(fs.getattr(ino).await?.is_some());
+        }
+
+        assert_eq!(fs.sweep_orphans().await?, 2);
+        for ino in [tmp, unlinked] {
+            assert!(fs.getattr(ino).await?.is_none());
+            assert_eq!(fs.get_chunk_count(ino).await?, 0);
+        }
+        assert!(fs.getattr(ROOT_INO).await?.is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_inode_generation_changes_on_recreate() -> Result<()> {
+        let dir = tempdir()?;
+        let db_path = dir.path().join("test.db");
+        let db_path = db_path.to_str().unwrap();
+
+        let (first, root_generation) = {
+            let fs = AgentFS::new(db_path).await?;
+            let root = fs.getattr(ROOT_INO).await?.unwrap();
+            assert_ne!(root.generation, 0);
+
+            let (stats, _) = FileSystem::create_file(&fs, ROOT_INO, "a.txt", 0o644, 0, 0).await?;
+            assert_eq!(stats.generation, root.generation);
+            let found = fs.lookup(ROOT_INO, "a.txt").await?.unwrap();
+            assert_eq!(found.generation, stats.generation);
+
+            // Deleting and recreating a file yields a different identity
+            fs.unlink(ROOT_INO, "a.txt").await?;
+            let (again, _) = FileSystem::create_file(&fs, ROOT_INO, "a.txt", 0o644, 0, 0).await?;
+            assert_ne!((again.ino, again.generation), (stats.ino, stats.generation));
+            (stats, root.generation)
+        };
+
+        // Reopening the same database keeps the generation stable
+        {
+            let fs = AgentFS::new(db_path).await?;
+            let root = fs.getattr(ROOT_INO).await?.unwrap();
+            assert_eq!(root.generation, root_generation);
+        }
+
+        // A recreated database reuses inode numbers under a new generation
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", db_path, suffix));
+        }
+        let fs = AgentFS::new(db_path).await?;
+        let (stats, _) = FileSystem::create_file(&fs, ROOT_INO, "a.txt", 0o644, 0, 0).await?;
+        assert_eq!(stats.ino, first.ino);
+        assert_ne!(stats.generation, first.generation);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore = "mounts a tmpfs, which requires root"]
+    async fn test_write_past_disk_capacity_returns_enospc() -> Result<()> {
+        let dir = tempdir()?;
+        let tmpfs = TinyTmpfs::mount(dir.path())?;
+
+        let db_path = dir.path().join("full.db");
+        let result: Result<()> = async {
+            let fs = AgentFS::new(db_path.to_str().unwrap()).await?;
+            let (_, file) = FileSystem::create_file(&fs, ROOT_INO, "big", 0o644, 0, 0).await?;
+            let chunk = vec![0xAB; 64 * 1024];
+            for i in 0..1024u64 {
+                file.pwrite(i * chunk.len() as u64, &chunk).await?;
+                file.fsync().await?;
+            }
+            Ok(())
+        }
+        .await;
+        drop(tmpfs);
+
+        let err = result.expect_err("writing 64 MiB to a 512 KiB tmpfs succeeded");
+        assert!(
+            matches!(err, Error::Fs(FsError::NoSpace)),
+            "unexpected error: {}",
+            err
+        );
+        if let Error::Fs(fs_err) = err {
+            assert_eq!(fs_err.to_errno(), libc::ENOSPC);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_iter_inodes_yields_every_inode() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+
+        // Span several batches, with inodes in nested directories
+        let n = INODE_ITER_BATCH_SIZE as usize * 2 + 10;
+        fs.mkdir("/dir", 0, 0).await?;
+        for i in 1..n {
+            fs.create_file(&format!("/dir/file{}", i), DEFAULT_FILE_MODE, 0, 0)
+                .await?;
+        }
+
+        let mut iter = fs.iter_inodes();
+        let mut inos = Vec::new();
+        while let Some((ino, stats)) = iter.next().await? {
+            assert_eq!(ino, stats.ino);
+            inos.push(ino);
+        }
+        assert_eq!(inos.len(), n + 1);
+        assert_eq!(inos[0], ROOT_INO);
+        assert!(inos.windows(2).all(|w| w[0] < w[1]));
+
+        // Exhausted iterators stay exhausted
+        assert!(iter.next().await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_batch() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        fs.mkdir("/existing", 0, 0).await?;
+
+        let data = vec![0x5a; fs.chunk_size() * 2 + 1];
+        let entries = vec![
+            BatchEntry::Dir {
+                path: "/src".to_string(),
+                mode: 0o755,
+            },
+            BatchEntry::File {
+                path: "/src/big.bin".to_string(),
+                mode: 0o600,
+                data: data.clone(),
+            },
+            BatchEntry::Symlink {
+                path: "/src/link".to_string(),
+                target: "big.bin".to_string(),
+            },
+            BatchEntry::File {
+                path: "/existing/empty".to_string(),
+                mode: 0o644,
+                data: Vec::new(),
+            },
+        ];
+        let inos = fs.create_batch(&entries, 1000, 1000).await?;
+        assert_eq!(inos.len(), entries.len());
+
+        let dir = fs.stat("/src").await?.unwrap();
+        assert!(dir.is_directory());
+        assert_eq!(dir.nlink, 2);
+        assert_eq!(fs.stat("/").await?.unwrap().nlink, 4);
+        let file = fs.stat("/src/big.bin").await?.unwrap();
+        assert_eq!(file.ino, inos[1]);
+        assert_eq!(file.mode, S_IFREG | 0o600);
+        assert_eq!(file.uid, 1000);
+        assert_eq!(fs.read_file("/src/big.bin").await?.unwrap(), data);
+        assert_eq!(fs.readlink("/src/link").await?.unwrap(), "big.bin");
+        assert_eq!(fs.read_file("/existing/empty").await?.unwrap(), b"");
+
+        // A failing entry rolls back the whole batch
+        let entries = vec![
+            BatchEntry::Dir {
+                path: "/new".to_string(),
+                mode: 0o755,
+            },
+            BatchEntry::File {
+                path: "/src/big.bin".to_string(),
+                mode: 0o644,
+                data: Vec::new(),
+            },
+        ];
+        let err = fs.create_batch(&entries, 0, 0).await.unwrap_err();
+        assert!(matches!(err, Error::Fs(FsError::AlreadyExists)));
+        assert!(fs.stat("/new").await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pull_drops_cached_entries() -> Result<()> {
+        let dir = tempdir()?;
+        let db_path = dir.path().join("replica.db");
+        let db = turso::Builder::new_local(db_path.to_str().unwrap())
+            .build()
+            .await?;
+        let pool = ConnectionPool::mock_sync(db);
+        let fs = AgentFS::from_pool(pool.clone()).await?;
+
+        fs.mkdir("/a", 0, 0).await?;
+        assert!(fs.stat("/a").await?.is_some());
+
+        // The remote removes the entry, which is not seen until pulled
+        pool.queue_remote_change("DELETE FROM fs_dentry WHERE name = 'a'");
+        assert!(fs.stat("/a").await?.is_some());
+
+        // The cached entry would still resolve the removed name
+        pool.sync_now().await?;
+        assert!(fs.stat("/a").await?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rolled_back_transaction_leaves_fs_unchanged() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        fs.mkdir("/kept", 0, 0).await?;
+        fs.pwrite("/manifest", 0, b"v1").await?;
+
+        let tx = fs.begin_tx().await?;
+        tx.mkdir("/data", 0, 0).await?;
+        tx.pwrite("/data/blob", 0, b"contents").await?;
+        tx.pwrite("/manifest", 0, b"v2").await?;
+        tx.rename("/kept", "/moved").await?;
+        assert!(tx.stat("/moved").await?.is_some());
+        tx.rollback_tx().await?;
+
+        assert!(fs.stat("/data").await?.is_none());
+        assert!(fs.stat("/moved").await?.is_none());
+        assert!(fs.stat("/kept").await?.is_some());
+        assert_eq!(fs.read_file("/manifest").await?, Some(b"v1".to_vec()));
+
+        // A failing closure rolls back, a succeeding one commits
+        let err = fs
+            .with_transaction(|tx| async move {
+                tx.pwrite("/manifest", 0, b"v2").await?;
+                tx.remove("/missing").await
+            })
+            .await;
+        assert!(err.is_err());
+        assert_eq!(fs.read_file("/manifest").await?, Some(b"v1".to_vec()));
+
+        fs.with_transaction(|tx| async move {
+            tx.pwrite("/data.bin", 0, b"contents").await?;
+            tx.pwrite("/manifest", 0, b"v2").await
+        })
+        .await?;
+        assert_eq!(fs.read_file("/data.bin").await?, Some(b"contents".to_vec()));
+        assert_eq!(fs.read_file("/manifest").await?, Some(b"v2".to_vec()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_optimize_renumbers_and_compacts() -> Result<()> {
+        let (fs, dir) = create_test_fs().await?;
+        fs.mkdir("/a", 0, 0).await?;
+        // Removed files leave gaps in the inode numbers and free pages
+        for i in 0..20 {
+            fs.pwrite(&format!("/tmp{}", i), 0, &[0x5a; 64 * 1024])
+                .await?;
+        }
+        fs.mkdir("/a/b", 0, 0).await?;
+        fs.pwrite("/a/f", 0, b"contents").await?;
+        fs.link("/a/f", "/hard").await?;
+        fs.symlink("a/f", "/s", 0, 0).await?;
+        for i in 0..20 {
+            fs.remove(&format!("/tmp{}", i)).await?;
+        }
+        fs.checkpoint().await?;
+        let source_size = std::fs::metadata(dir.path().join("test.db"))?.len();
+
+        let out = dir.path().join("optimized.db");
+        let out = out.to_str().unwrap();
+        fs.optimize(out).await?;
+        assert!(matches!(
+            fs.optimize(out).await,
+            Err(Error::Fs(FsError::AlreadyExists))
+        ));
+        assert!(std::fs::metadata(out)?.len() < source_size);
+
+        let optimized = AgentFS::new(out).await?;
+        let conn = optimized.get_connection().await?;
+        let mut rows = conn
+            .query("SELECT ino FROM fs_inode ORDER BY ino", ())
+            .await?;
+        let mut inodes = Vec::new();
+        while let Some(row) = rows.next().await? {
+            inodes.push(row.get::<i64>(0)?);
+        }
+        drop(rows);
+        drop(conn);
+        assert_eq!(inodes, (1..=5).collect::<Vec<_>>());
+
+        assert_eq!(
+            optimized.read_file("/a/f").await?,
+            Some(b"contents".to_vec())
+        );
+        assert_eq!(optimized.readlink("/s").await?, Some("a/f".to_string()));
+        assert!(optimized.stat("/a/b").await?.unwrap().is_directory());
+        let file = optimized.stat("/a/f").await?.unwrap();
+        assert_eq!(optimized.stat("/hard").await?.unwrap().ino, file.ino);
+        assert_eq!(file.nlink, 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_gets_new_generation() -> Result<()> {
+        let (fs, dir) = create_test_fs().await?;
+        fs.pwrite("/f", 0, b"contents").await?;
+        let out = dir.path().join("snapshot.db");
+        let out = out.to_str().unwrap();
+        fs.snapshot(out).await?;
+
+        let copy = AgentFS::new(out).await?;
+        let original = fs.stat("/f").await?.unwrap();
+        let copied = copy.stat("/f").await?.unwrap();
+        assert_eq!(copied.ino, original.ino);
+        assert_ne!(copied.generation, original.generation);
+        assert_eq!(copy.read_file("/f").await?, Some(b"contents".to_vec()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shared_chunk_cache() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        let cache = Arc::new(ChunkCache::new(64));
+        let second = AgentFS::from_pool_readonly(fs.get_pool())
+            .await?
+            .with_chunk_cache(cache.clone());
+        let first = fs.with_chunk_cache(cache.clone());
+
+        let data = vec![0x42; first.chunk_size() * 4];
+        let (stats, file) = FileSystem::create_file(&first, ROOT_INO, "f", 0o644, 0, 0).await?;
+        file.pwrite(0, &data).await?;
+
+        // The first reader fills the cache from the database
+        assert_eq!(file.pread(0, data.len() as u64).await?, data);
+        let misses = cache.misses();
+        assert!(misses > 0);
+        let hits = cache.hits();
+
+        // The second reader is served entirely from those fills
+        let other = FileSystem::open(&second, stats.ino, libc::O_RDONLY).await?;
+        assert_eq!(other.pread(0, data.len() as u64).await?, data);
+        assert_eq!(cache.misses(), misses);
+        assert_eq!(cache.hits(), hits + 4);
+
+        // A write through one instance is seen by the other
+        file.pwrite(1, b"xyz").await?;
+        let read = other.pread(0, 8).await?;
+        assert_eq!(&read[..], b"\x42xyz\x42\x42\x42\x42");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_change_hook_sees_committed_writes() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        let changed = Arc::new(Mutex::new(Vec::new()));
+        let seen = changed.clone();
+        let fs = fs.with_change_hook(Arc::new(move |ino| seen.lock().unwrap().push(ino)));
+
+        let (stats, file) = FileSystem::create_file(&fs, ROOT_INO, "f", 0o644, 0, 0).await?;
+        file.pwrite(0, b"hello").await?;
+        file.truncate(2).await?;
+        fs.pwrite("/f", 0, b"x").await?;
+        fs.truncate("/f", 0).await?;
+        assert_eq!(*changed.lock().unwrap(), vec![stats.ino; 4]);
+
+        // Reads and metadata changes are not reported
+        file.pread(0, 16).await?;
+        fs.chmod(stats.ino, 0o600).await?;
+        assert_eq!(changed.lock().unwrap().len(), 4);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_inode_flags() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        let (stats, file) = FileSystem::create_file(&fs, ROOT_INO, "locked", 0o644, 0, 0).await?;
+        file.pwrite(0, b"hello").await?;
+        assert_eq!(fs.get_inode_flags(stats.ino).await?, 0);
+
+        // Immutable files can't be written, renamed, linked or removed
+        fs.set_inode_flags(stats.ino, FS_IMMUTABLE_FL).await?;
+        assert_eq!(fs.get_inode_flags(stats.ino).await?, FS_IMMUTABLE_FL);
+        let not_permitted = |r: Result<()>| matches!(r, Err(Error::Fs(FsError::NotPermitted)));
+        assert!(not_permitted(file.pwrite(5, b" world").await));
+        assert!(not_permitted(file.truncate(0).await));
+        assert!(not_permitted(
+            FileSystem::rename(&fs, ROOT_INO, "locked", ROOT_INO, "moved").await
+        ));
+        assert!(not_permitted(
+            FileSystem::link(&fs, stats.ino, ROOT_INO, "alias")
+                .await
+                .map(|_| ())
+        ));
+        assert!(not_permitted(fs.unlink(ROOT_INO, "locked").await));
+        assert_eq!(fs.read_file("/locked").await?.unwrap(), b"hello");
+
+        // Append-only files can grow but not shrink
+        fs.set_inode_flags(stats.ino, FS_APPEND_FL).await?;
+        file.pwrite(5, b" world").await?;
+        assert!(not_permitted(file.truncate(5).await));
+        assert!(not_permitted(fs.unlink(ROOT_INO, "locked").await));
+
+        // Cleared flags lift the restrictions
+        fs.set_inode_flags(stats.ino, 0).await?;
+        file.truncate(5).await?;
+        FileSystem::rename(&fs, ROOT_INO, "locked", ROOT_INO, "moved").await?;
+        assert_eq!(fs.read_file("/moved").await?.unwrap(), b"hello");
+        fs.unlink(ROOT_INO, "moved").await?;
+
+        assert!(matches!(
+            fs.set_inode_flags(ROOT_INO, 0x80000).await,
+            Err(Error::Fs(FsError::NotSupported))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_past_max_file_size_returns_efbig() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        let fs = fs.with_max_file_size(Some(8192));
+        let (_, file) = FileSystem::create_file(&fs, ROOT_INO, "capped", 0o644, 0, 0).await?;
+
+        // Up to the limit is fine
+        file.pwrite(0, &[0xAB; 8192]).await?;
+
+        let err = file.pwrite(8000, &[0xCD; 193]).await.unwrap_err();
+        assert!(
+            matches!(err, Error::Fs(FsError::FileTooLarge)),
+            "unexpected error: {}",
+            err
+        );
+        if let Error::Fs(fs_err) = err {
+            assert_eq!(fs_err.to_errno(), libc::EFBIG);
+        }
+        let err = file.truncate(8193).await.unwrap_err();
+        assert!(matches!(err, Error::Fs(FsError::FileTooLarge)));
+        let err = fs.pwrite("/capped", 8192, b"x").await.unwrap_err();
+        assert!(matches!(err, Error::Fs(FsError::FileTooLarge)));
+        let err = fs.truncate("/capped", u64::MAX).await.unwrap_err();
+        assert!(matches!(err, Error::Fs(FsError::FileTooLarge)));
+
+        // Failed writes leave the file untouched
+        assert_eq!(file.fstat().await?.size, 8192);
+        assert_eq!(fs.read_file("/capped").await?.unwrap(), vec![0xAB; 8192]);
+
+        // Shrinking is always allowed
+        file.truncate(4096).await?;
+        assert_eq!(file.fstat().await?.size, 4096);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_health_fails_on_corrupted_database() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        FileSystem::health(&fs).await?;
+
+        // The pool has a single connection, so it is released before each check
+        let conn = fs.pool.get_connection().await?;
+        conn.execute("DELETE FROM fs_inode WHERE ino = ?", (ROOT_INO,))
+            .await?;
+        drop(conn);
+        let err = FileSystem::health(&fs).await.unwrap_err();
+        assert!(matches!(err, Error::Fs(FsError::NotFound)), "{err}");
+
+        let conn = fs.pool.get_connection().await?;
+        conn.execute("DROP TABLE fs_inode", ()).await?;
+        drop(conn);
+        assert!(FileSystem::health(&fs).await.is_err());
+
+        Ok(())
+    }
 }
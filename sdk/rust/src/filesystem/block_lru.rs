@@ -0,0 +1,185 @@
+//! LRU of file data blocks that writes invalidate per inode.
+//!
+//! Shared by `ChunkCache` and `CachingFs`. Blocks are keyed by inode and
+//! block index. Rather than finding and dropping every block of an inode
+//! when it is written, the inode's generation is bumped, and blocks filled
+//! under an older generation are never returned. A reader takes the
+//! generation before it reads and passes it back when filling the block, so
+//! data read before a write that raced with it is dropped instead of cached.
+
+use lru::LruCache;
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+
+/// Generation and number of cached blocks of an inode
+struct InodeBlocks {
+    generation: u64,
+    cached: usize,
+}
+
+pub(crate) struct BlockLru<V> {
+    /// (ino, block index) -> (generation when filled, block)
+    blocks: LruCache<(i64, u64), (u64, V)>,
+    /// Inodes with blocks cached, or written since their last block left
+    ///
+    /// An inode is forgotten once it has no blocks cached, so this holds at
+    /// most one entry per cached block.
+    inodes: HashMap<i64, InodeBlocks>,
+    /// Last generation handed out
+    epoch: u64,
+    /// Generation of the inodes not in `inodes`
+    ///
+    /// Raised to the generation of every inode that is forgotten, so a
+    /// fill that started before the inode was written is still dropped.
+    floor: u64,
+}
+
+impl<V: Clone> BlockLru<V> {
+    /// Create an LRU holding up to `capacity` blocks
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            blocks: LruCache::new(NonZeroUsize::new(capacity.max(1)).unwrap()),
+            inodes: HashMap::new(),
+            epoch: 0,
+            floor: 0,
+        }
+    }
+
+    /// Get the current generation of an inode, to pass to `insert`
+    pub(crate) fn generation(&self, ino: i64) -> u64 {
+        self.inodes
+            .get(&ino)
+            .map_or(self.floor, |inode| inode.generation)
+    }
+
+    /// Look up a block, ignoring blocks from an older generation
+    pub(crate) fn get(&mut self, ino: i64, index: u64) -> Option<V> {
+        self.get_if(ino, index, |_| true)
+    }
+
+    /// Look up a block, ignoring blocks from an older generation and ones
+    /// `usable` rejects, e.g. because they expired
+    pub(crate) fn get_if(
+        &mut self,
+        ino: i64,
+        index: u64,
+        usable: impl FnOnce(&V) -> bool,
+    ) -> Option<V> {
+        let current = self.generation(ino);
+        match self.blocks.get(&(ino, index)) {
+            Some((generation, block)) if *generation == current && usable(block) => {
+                Some(block.clone())
+            }
+            Some(_) => {
+                self.blocks.pop(&(ino, index));
+                self.release(ino);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Fill a block read while its inode was at `generation`
+    ///
+    /// Dropped if the inode was written since, as the data may predate it.
+    pub(crate) fn insert(&mut self, ino: i64, index: u64, generation: u64, block: V) {
+        if self.generation(ino) != generation {
+            return;
+        }
+        self.inodes
+            .entry(ino)
+            .or_insert(InodeBlocks {
+                generation,
+                cached: 0,
+            })
+            .cached += 1;
+        // Evicts the least recently used block, or replaces this one
+        if let Some(((ino, _), _)) = self.blocks.push((ino, index), (generation, block)) {
+            self.release(ino);
+        }
+    }
+
+    /// Invalidate every cached block of an inode after it was written
+    pub(crate) fn invalidate(&mut self, ino: i64) {
+        self.epoch += 1;
+        let epoch = self.epoch;
+        match self.inodes.get_mut(&ino) {
+            Some(inode) => inode.generation = epoch,
+            // Nothing is cached to invalidate; only fills that are under
+            // way need to be dropped
+            None => self.floor = epoch,
+        }
+    }
+
+    /// Drop every cached block, and any fill under way
+    pub(crate) fn clear(&mut self) {
+        self.blocks.clear();
+        self.inodes.clear();
+        self.epoch += 1;
+        self.floor = self.epoch;
+    }
+
+    /// Account for a block of `ino` leaving the cache
+    fn release(&mut self, ino: i64) {
+        let Some(inode) = self.inodes.get_mut(&ino) else {
+            return;
+        };
+        inode.cached -= 1;
+        if inode.cached == 0 {
+            let generation = inode.generation;
+            self.inodes.remove(&ino);
+            self.floor = self.floor.max(generation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_invalidates_blocks_and_racing_fills() {
+        let mut lru = BlockLru::new(8);
+        let generation = lru.generation(1);
+        lru.insert(1, 0, generation, "old");
+        lru.insert(2, 0, lru.generation(2), "other");
+        assert_eq!(lru.get(1, 0), Some("old"));
+
+        // A read that started before the write is not cached after it
+        let racing = lru.generation(1);
+        lru.invalidate(1);
+        lru.insert(1, 1, racing, "stale");
+        assert_eq!(lru.get(1, 0), None);
+        assert_eq!(lru.get(1, 1), None);
+
+        // The same holds for an inode with nothing cached
+        let racing = lru.generation(3);
+        lru.invalidate(3);
+        lru.insert(3, 0, racing, "stale");
+        assert_eq!(lru.get(3, 0), None);
+        lru.insert(3, 0, lru.generation(3), "new");
+        assert_eq!(lru.get(3, 0), Some("new"));
+
+        // Writes to other inodes leave their blocks alone
+        assert_eq!(lru.get(2, 0), Some("other"));
+    }
+
+    #[test]
+    fn test_inodes_are_forgotten_with_their_blocks() {
+        let mut lru = BlockLru::new(2);
+        for ino in 0..100 {
+            lru.invalidate(ino);
+            lru.insert(ino, 0, lru.generation(ino), ino);
+        }
+        // Only the inodes of the blocks still cached are tracked
+        assert_eq!(lru.inodes.len(), 2);
+        assert_eq!(lru.get(99, 0), Some(99));
+        assert_eq!(lru.get(0, 0), None);
+
+        lru.invalidate(99);
+        assert_eq!(lru.get(99, 0), None);
+        assert_eq!(lru.inodes.len(), 1);
+        lru.clear();
+        assert!(lru.inodes.is_empty());
+    }
+}
@@ -0,0 +1,308 @@
+//! Blocking facade over the async `FileSystem` trait.
+//!
+//! `BlockingFs` lets synchronous code (e.g. ptrace syscall handlers) call
+//! filesystem operations without managing a Tokio runtime itself. Each call
+//! is driven to completion on a runtime owned by (or handed to) the facade.
+//!
+//! The blocking methods must not be called from within an async context:
+//! like `Runtime::block_on()`, they panic when used on a runtime thread.
+
+use crate::error::Result;
+use std::sync::Arc;
+use tokio::runtime::{Handle, Runtime};
+
+use super::{BoxedFile, DirEntry, FileSystem, FilesystemStats, Stats, TimeChange};
+
+/// Blocking wrapper around a `FileSystem`.
+#[derive(Clone)]
+pub struct BlockingFs {
+    fs: Arc<dyn FileSystem>,
+    handle: Handle,
+    /// Dedicated runtime, kept alive for as long as any clone of the facade
+    _runtime: Option<Arc<Runtime>>,
+}
+
+impl BlockingFs {
+    /// Wrap a filesystem, driving its operations on a dedicated runtime.
+    pub fn new(fs: Arc<dyn FileSystem>) -> Result<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("agentfs-blocking")
+            .enable_all()
+            .build()?;
+        Ok(Self {
+            fs,
+            handle: runtime.handle().clone(),
+            _runtime: Some(Arc::new(runtime)),
+        })
+    }
+
+    /// Wrap a filesystem, driving its operations on an existing runtime.
+    pub fn with_handle(fs: Arc<dyn FileSystem>, handle: Handle) -> Self {
+        Self {
+            fs,
+            handle,
+            _runtime: None,
+        }
+    }
+
+    /// Get the wrapped filesystem.
+    pub fn inner(&self) -> &Arc<dyn FileSystem> {
+        &self.fs
+    }
+
+    /// Look up a directory entry by name within a parent directory.
+    pub fn lookup(&self, parent_ino: i64, name: &str) -> Result<Option<Stats>> {
+        self.handle.block_on(self.fs.lookup(parent_ino, name))
+    }
+
+    /// Get file attributes for an inode.
+    pub fn getattr(&self, ino: i64) -> Result<Option<Stats>> {
+        self.handle.block_on(self.fs.getattr(ino))
+    }
+
+    /// Read the target of a symbolic link inode.
+    pub fn readlink(&self, ino: i64) -> Result<Option<String>> {
+        self.handle.block_on(self.fs.readlink(ino))
+    }
+
+    /// List directory entry names.
+    pub fn readdir(&self, ino: i64) -> Result<Option<Vec<String>>> {
+        self.handle.block_on(self.fs.readdir(ino))
+    }
+
+    /// List directory entries with their statistics.
+    pub fn readdir_plus(&self, ino: i64) -> Result<Option<Vec<DirEntry>>> {
+        self.handle.block_on(self.fs.readdir_plus(ino))
+    }
+
+    /// Change file mode/permissions.
+    pub fn chmod(&self, ino: i64, mode: u32) -> Result<()> {
+        self.handle.block_on(self.fs.chmod(ino, mode))
+    }
+
+    /// Change file ownership.
+    pub fn chown(&self, ino: i64, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        self.handle.block_on(self.fs.chown(ino, uid, gid))
+    }
+
+    /// Set file access and modification times.
+    pub fn utimens(&self, ino: i64, atime: TimeChange, mtime: TimeChange) -> Result<()> {
+        self.handle.block_on(self.fs.utimens(ino, atime, mtime))
+    }
+
+    /// Open a file by inode.
+    pub fn open(&self, ino: i64, flags: i32) -> Result<BlockingFile> {
+        let file = self.handle.block_on(self.fs.open(ino, flags))?;
+        Ok(self.wrap_file(file))
+    }
+
+    /// Create a directory.
+    pub fn mkdir(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        mode: u32,
+        uid: u32,
+        gid: u32,
+    ) -> Result<Stats> {
+        self.handle
+            .block_on(self.fs.mkdir(parent_ino, name, mode, uid, gid))
+    }
+
+    /// Create a new empty file and open it.
+    pub fn create_file(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        mode: u32,
+        uid: u32,
+        gid: u32,
+    ) -> Result<(Stats, BlockingFile)> {
+        let (stats, file) = self
+            .handle
+            .block_on(self.fs.create_file(parent_ino, name, mode, uid, gid))?;
+        Ok((stats, self.wrap_file(file)))
+    }
+
+    /// Create a symbolic link.
+    pub fn symlink(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        target: &str,
+        uid: u32,
+        gid: u32,
+    ) -> Result<Stats> {
+        self.handle
+            .block_on(self.fs.symlink(parent_ino, name, target, uid, gid))
+    }
+
+    /// Remove a file (non-directory) from a directory.
+    pub fn unlink(&self, parent_ino: i64, name: &str) -> Result<()> {
+        self.handle.block_on(self.fs.unlink(parent_ino, name))
+    }
+
+    /// Remove an empty directory.
+    pub fn rmdir(&self, parent_ino: i64, name: &str) -> Result<()> {
+        self.handle.block_on(self.fs.rmdir(parent_ino, name))
+    }
+
+    /// Create a hard link.
+    pub fn link(&self, ino: i64, newparent_ino: i64, newname: &str) -> Result<Stats> {
+        self.handle
+            .block_on(self.fs.link(ino, newparent_ino, newname))
+    }
+
+    /// Rename/move a file or directory.
+    pub fn rename(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+    ) -> Result<()> {
+        self.handle.block_on(
+            self.fs
+                .rename(oldparent_ino, oldname, newparent_ino, newname),
+        )
+    }
+
+    /// Get filesystem statistics.
+    pub fn statfs(&self) -> Result<FilesystemStats> {
+        self.handle.block_on(self.fs.statfs())
+    }
+
+    fn wrap_file(&self, file: BoxedFile) -> BlockingFile {
+        BlockingFile {
+            file,
+            handle: self.handle.clone(),
+            _runtime: self._runtime.clone(),
+        }
+    }
+}
+
+/// Blocking wrapper around an open `File`.
+#[derive(Clone)]
+pub struct BlockingFile {
+    file: BoxedFile,
+    handle: Handle,
+    /// Keeps the facade's dedicated runtime alive while the file is open
+    _runtime: Option<Arc<Runtime>>,
+}
+
+impl BlockingFile {
+    /// Get the wrapped file handle.
+    pub fn inner(&self) -> &BoxedFile {
+        &self.file
+    }
+
+    /// Read from the file at the given offset.
+    pub fn pread(&self, offset: u64, size: u64) -> Result<Vec<u8>> {
+        self.handle.block_on(self.file.pread(offset, size))
+    }
+
+    /// Write to the file at the given offset.
+    pub fn pwrite(&self, offset: u64, data: &[u8]) -> Result<()> {
+        self.handle.block_on(self.file.pwrite(offset, data))
+    }
+
+    /// Truncate the file to the specified size.
+    pub fn truncate(&self, size: u64) -> Result<()> {
+        self.handle.block_on(self.file.truncate(size))
+    }
+
+    /// Synchronize file data to persistent storage.
+    pub fn fsync(&self) -> Result<()> {
+        self.handle.block_on(self.file.fsync())
+    }
+
+    /// Get file attributes.
+    pub fn fstat(&self) -> Result<Stats> {
+        self.handle.block_on(self.file.fstat())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::{AgentFS, DEFAULT_DIR_MODE, DEFAULT_FILE_MODE};
+
+    fn blocking_fs(dir: &tempfile::TempDir) -> BlockingFs {
+        let db_path = dir.path().join("test.db");
+        let setup = Runtime::new().unwrap();
+        let fs = setup
+            .block_on(AgentFS::new(db_path.to_str().unwrap()))
+            .unwrap();
+        BlockingFs::new(Arc::new(fs)).unwrap()
+    }
+
+    #[test]
+    fn test_blocking_file_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let fs = blocking_fs(&dir);
+
+        let dir_stats = fs.mkdir(1, "data", DEFAULT_DIR_MODE, 0, 0).unwrap();
+        let (stats, file) = fs
+            .create_file(dir_stats.ino, "hello.txt", DEFAULT_FILE_MODE, 0, 0)
+            .unwrap();
+        file.pwrite(0, b"hello, world").unwrap();
+        file.fsync().unwrap();
+        assert_eq!(file.fstat().unwrap().size, 12);
+
+        let found = fs.lookup(dir_stats.ino, "hello.txt").unwrap().unwrap();
+        assert_eq!(found.ino, stats.ino);
+
+        let file = fs.open(found.ino, libc::O_RDONLY).unwrap();
+        assert_eq!(file.pread(7, 5).unwrap(), b"world");
+
+        let entries = fs.readdir_plus(dir_stats.ino).unwrap().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "hello.txt");
+        assert_eq!(entries[0].stats.size, 12);
+    }
+
+    #[test]
+    fn test_blocking_namespace_operations() {
+        let dir = tempfile::tempdir().unwrap();
+        let fs = blocking_fs(&dir);
+
+        let (stats, _file) = fs.create_file(1, "a.txt", DEFAULT_FILE_MODE, 0, 0).unwrap();
+        fs.rename(1, "a.txt", 1, "b.txt").unwrap();
+        assert!(fs.lookup(1, "a.txt").unwrap().is_none());
+
+        fs.symlink(1, "link", "b.txt", 0, 0).unwrap();
+        let link = fs.lookup(1, "link").unwrap().unwrap();
+        assert_eq!(fs.readlink(link.ino).unwrap().as_deref(), Some("b.txt"));
+
+        fs.chmod(stats.ino, 0o600).unwrap();
+        assert_eq!(fs.getattr(stats.ino).unwrap().unwrap().mode & 0o777, 0o600);
+
+        fs.unlink(1, "b.txt").unwrap();
+        assert!(fs.getattr(stats.ino).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_blocking_fs_is_usable_from_other_threads() {
+        let dir = tempfile::tempdir().unwrap();
+        let fs = blocking_fs(&dir);
+
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let fs = fs.clone();
+                std::thread::spawn(move || {
+                    let name = format!("file{}", i);
+                    let (_, file) = fs.create_file(1, &name, DEFAULT_FILE_MODE, 0, 0).unwrap();
+                    file.pwrite(0, name.as_bytes()).unwrap();
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let mut names = fs.readdir(1).unwrap().unwrap();
+        names.sort();
+        assert_eq!(names, vec!["file0", "file1", "file2", "file3"]);
+    }
+}
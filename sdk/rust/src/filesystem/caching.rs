@@ -0,0 +1,729 @@
+//! Read cache in front of a slow `FileSystem`.
+//!
+//! `CachingFs` wraps another filesystem, typically one whose reads go over
+//! the network such as a synced database, and serves repeated lookups,
+//! attribute reads and file reads from memory. It keeps three LRU caches:
+//! directory entries, inode attributes, and fixed-size blocks of file data.
+//! Entries expire after a configurable TTL.
+//!
+//! Every write made through the wrapper invalidates what it may have changed
+//! before returning, so callers always see their own writes. Writes made to
+//! the inner filesystem by anyone else are only seen once the affected
+//! entries expire. To cache the base layer of an overlay, wrap it before
+//! handing it to `OverlayFS::new`.
+
+use crate::error::Result;
+use async_trait::async_trait;
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::block_lru::BlockLru;
+use super::{BoxedFile, DirEntry, File, FileSystem, FilesystemStats, Stats, TimeChange};
+
+/// Default number of directory entries and of inode attributes cached
+const DEFAULT_ENTRY_CAPACITY: usize = 10_000;
+
+/// Default number of file blocks cached (64 MiB of 64 KiB blocks)
+const DEFAULT_BLOCK_CAPACITY: usize = 1024;
+
+/// Default size of a cached file block
+const DEFAULT_BLOCK_SIZE: usize = 64 * 1024;
+
+/// Default time after which cached entries are refetched
+const DEFAULT_TTL: Duration = Duration::from_secs(1);
+
+/// A cached value and when it was filled.
+#[derive(Clone)]
+struct Cached<T> {
+    value: T,
+    filled: Instant,
+}
+
+/// Caches shared by a `CachingFs` and the files opened through it.
+struct FsCache {
+    ttl: Duration,
+    block_size: usize,
+    /// (parent_ino, name) -> ino
+    entries: Mutex<LruCache<(i64, String), Cached<i64>>>,
+    /// ino -> attributes
+    attrs: Mutex<LruCache<i64, Cached<Stats>>>,
+    /// (ino, block index) -> block data; blocks shorter than the block size
+    /// end at end of file
+    blocks: Mutex<BlockLru<Cached<Arc<Vec<u8>>>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+fn lru<K: std::hash::Hash + Eq, V>(capacity: usize) -> LruCache<K, V> {
+    LruCache::new(NonZeroUsize::new(capacity.max(1)).unwrap())
+}
+
+impl FsCache {
+    fn fresh<T>(&self, cached: &Cached<T>) -> bool {
+        cached.filled.elapsed() < self.ttl
+    }
+
+    fn count(&self, hit: bool) {
+        let counter = if hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn get_entry(&self, parent_ino: i64, name: &str) -> Option<i64> {
+        let mut entries = self.entries.lock().unwrap();
+        let key = (parent_ino, name.to_string());
+        match entries.get(&key) {
+            Some(cached) if self.fresh(cached) => Some(cached.value),
+            Some(_) => {
+                entries.pop(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn put_entry(&self, parent_ino: i64, name: &str, ino: i64) {
+        self.entries.lock().unwrap().put(
+            (parent_ino, name.to_string()),
+            Cached {
+                value: ino,
+                filled: Instant::now(),
+            },
+        );
+    }
+
+    /// Drop the entry for `name`, returning the inode it named, if cached.
+    fn pop_entry(&self, parent_ino: i64, name: &str) -> Option<i64> {
+        self.entries
+            .lock()
+            .unwrap()
+            .pop(&(parent_ino, name.to_string()))
+            .map(|cached| cached.value)
+    }
+
+    fn get_attr(&self, ino: i64) -> Option<Stats> {
+        let mut attrs = self.attrs.lock().unwrap();
+        match attrs.get(&ino) {
+            Some(cached) if self.fresh(cached) => Some(cached.value.clone()),
+            Some(_) => {
+                attrs.pop(&ino);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn put_attr(&self, stats: &Stats) {
+        self.attrs.lock().unwrap().put(
+            stats.ino,
+            Cached {
+                value: stats.clone(),
+                filled: Instant::now(),
+            },
+        );
+    }
+
+    fn invalidate_attr(&self, ino: i64) {
+        self.attrs.lock().unwrap().pop(&ino);
+    }
+
+    /// Get the current data generation of an inode
+    fn generation(&self, ino: i64) -> u64 {
+        self.blocks.lock().unwrap().generation(ino)
+    }
+
+    /// Look up a block, ignoring expired entries and ones from an older
+    /// generation
+    fn get_block(&self, ino: i64, index: u64) -> Option<Arc<Vec<u8>>> {
+        let mut blocks = self.blocks.lock().unwrap();
+        blocks
+            .get_if(ino, index, |cached| self.fresh(cached))
+            .map(|cached| cached.value)
+    }
+
+    /// Fill a block read while its inode was at `generation`
+    ///
+    /// Dropped if a write went through since, as the data may predate it.
+    fn put_block(&self, ino: i64, index: u64, generation: u64, data: Arc<Vec<u8>>) {
+        let cached = Cached {
+            value: data,
+            filled: Instant::now(),
+        };
+        let mut blocks = self.blocks.lock().unwrap();
+        blocks.insert(ino, index, generation, cached);
+    }
+
+    /// Invalidate the attributes and every cached block of an inode whose
+    /// contents changed, or that was removed or reused
+    fn invalidate_inode(&self, ino: i64) {
+        self.invalidate_attr(ino);
+        self.blocks.lock().unwrap().invalidate(ino);
+    }
+
+    /// Invalidate the entry `name` was cached under and the inode it named.
+    ///
+    /// When the inode isn't known, every cached attribute is dropped, as
+    /// any of them may have a stale link count.
+    fn invalidate_name(&self, parent_ino: i64, name: &str) {
+        match self.pop_entry(parent_ino, name) {
+            Some(ino) => self.invalidate_inode(ino),
+            None => self.attrs.lock().unwrap().clear(),
+        }
+    }
+}
+
+/// Filesystem serving repeated reads of another one from memory.
+///
+/// Lookups, attributes and file data are cached in LRU caches whose sizes
+/// and TTL are set with the `with_*` builders.
+pub struct CachingFs {
+    inner: Arc<dyn FileSystem>,
+    cache: Arc<FsCache>,
+}
+
+impl CachingFs {
+    /// Wrap `inner` with the default cache sizes and a one second TTL.
+    pub fn new(inner: Arc<dyn FileSystem>) -> Self {
+        Self {
+            inner,
+            cache: Arc::new(FsCache {
+                ttl: DEFAULT_TTL,
+                block_size: DEFAULT_BLOCK_SIZE,
+                entries: Mutex::new(lru(DEFAULT_ENTRY_CAPACITY)),
+                attrs: Mutex::new(lru(DEFAULT_ENTRY_CAPACITY)),
+                blocks: Mutex::new(BlockLru::new(DEFAULT_BLOCK_CAPACITY)),
+                hits: AtomicU64::new(0),
+                misses: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    fn cache_mut(&mut self) -> &mut FsCache {
+        Arc::get_mut(&mut self.cache).expect("cache is configured before use")
+    }
+
+    /// Cache up to `capacity` directory entries and as many inode attributes.
+    pub fn with_entry_capacity(mut self, capacity: usize) -> Self {
+        let cache = self.cache_mut();
+        cache.entries = Mutex::new(lru(capacity));
+        cache.attrs = Mutex::new(lru(capacity));
+        self
+    }
+
+    /// Cache up to `capacity` blocks of file data.
+    pub fn with_block_capacity(mut self, capacity: usize) -> Self {
+        self.cache_mut().blocks = Mutex::new(BlockLru::new(capacity));
+        self
+    }
+
+    /// Read and cache file data in blocks of `block_size` bytes.
+    pub fn with_block_size(mut self, block_size: usize) -> Self {
+        self.cache_mut().block_size = block_size.max(1);
+        self
+    }
+
+    /// Refetch cached entries once they are older than `ttl`.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_mut().ttl = ttl;
+        self
+    }
+
+    /// Get the number of reads served from the cache
+    pub fn hits(&self) -> u64 {
+        self.cache.hits.load(Ordering::Relaxed)
+    }
+
+    /// Get the number of reads that went to the inner filesystem
+    pub fn misses(&self) -> u64 {
+        self.cache.misses.load(Ordering::Relaxed)
+    }
+
+    /// Wrap a file opened on the inner filesystem.
+    fn wrap(&self, ino: i64, file: BoxedFile) -> BoxedFile {
+        Arc::new(CachingFile {
+            inner: file,
+            ino,
+            cache: self.cache.clone(),
+        })
+    }
+
+    /// Cache the attributes of a newly created inode, whose number may
+    /// have been used by a removed one, and invalidate its parent.
+    fn created(&self, parent_ino: i64, name: &str, stats: &Stats) {
+        self.cache.invalidate_inode(stats.ino);
+        self.cache.invalidate_attr(parent_ino);
+        self.cache.put_entry(parent_ino, name, stats.ino);
+        self.cache.put_attr(stats);
+    }
+}
+
+#[async_trait]
+impl FileSystem for CachingFs {
+    async fn lookup(&self, parent_ino: i64, name: &str) -> Result<Option<Stats>> {
+        if let Some(stats) = self
+            .cache
+            .get_entry(parent_ino, name)
+            .and_then(|ino| self.cache.get_attr(ino))
+        {
+            self.cache.count(true);
+            return Ok(Some(stats));
+        }
+        self.cache.count(false);
+        let stats = self.inner.lookup(parent_ino, name).await?;
+        if let Some(stats) = &stats {
+            self.cache.put_entry(parent_ino, name, stats.ino);
+            self.cache.put_attr(stats);
+        }
+        Ok(stats)
+    }
+
+    async fn getattr(&self, ino: i64) -> Result<Option<Stats>> {
+        if let Some(stats) = self.cache.get_attr(ino) {
+            self.cache.count(true);
+            return Ok(Some(stats));
+        }
+        self.cache.count(false);
+        let stats = self.inner.getattr(ino).await?;
+        if let Some(stats) = &stats {
+            self.cache.put_attr(stats);
+        }
+        Ok(stats)
+    }
+
+    async fn readlink(&self, ino: i64) -> Result<Option<String>> {
+        self.inner.readlink(ino).await
+    }
+
+    async fn readdir(&self, ino: i64) -> Result<Option<Vec<String>>> {
+        self.inner.readdir(ino).await
+    }
+
+    async fn readdir_plus(&self, ino: i64) -> Result<Option<Vec<DirEntry>>> {
+        let entries = self.inner.readdir_plus(ino).await?;
+        // Warm the cache for the lookups that usually follow a listing
+        for entry in entries.iter().flatten() {
+            self.cache.put_entry(ino, &entry.name, entry.stats.ino);
+            self.cache.put_attr(&entry.stats);
+        }
+        Ok(entries)
+    }
+
+    async fn chmod(&self, ino: i64, mode: u32) -> Result<()> {
+        let result = self.inner.chmod(ino, mode).await;
+        self.cache.invalidate_attr(ino);
+        result
+    }
+
+    async fn chown(&self, ino: i64, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        let result = self.inner.chown(ino, uid, gid).await;
+        self.cache.invalidate_attr(ino);
+        result
+    }
+
+    async fn utimens(&self, ino: i64, atime: TimeChange, mtime: TimeChange) -> Result<()> {
+        let result = self.inner.utimens(ino, atime, mtime).await;
+        self.cache.invalidate_attr(ino);
+        result
+    }
+
+    async fn open(&self, ino: i64, flags: i32) -> Result<BoxedFile> {
+        let file = self.inner.open(ino, flags).await;
+        if flags & libc::O_TRUNC != 0 {
+            self.cache.invalidate_inode(ino);
+        }
+        Ok(self.wrap(ino, file?))
+    }
+
+    async fn mkdir(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        mode: u32,
+        uid: u32,
+        gid: u32,
+    ) -> Result<Stats> {
+        let stats = self.inner.mkdir(parent_ino, name, mode, uid, gid).await?;
+        self.created(parent_ino, name, &stats);
+        Ok(stats)
+    }
+
+    async fn create_file(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        mode: u32,
+        uid: u32,
+        gid: u32,
+    ) -> Result<(Stats, BoxedFile)> {
+        let (stats, file) = self
+            .inner
+            .create_file(parent_ino, name, mode, uid, gid)
+            .await?;
+        self.created(parent_ino, name, &stats);
+        let file = self.wrap(stats.ino, file);
+        Ok((stats, file))
+    }
+
+    async fn create_tmpfile(&self, mode: u32, uid: u32, gid: u32) -> Result<(Stats, BoxedFile)> {
+        let (stats, file) = self.inner.create_tmpfile(mode, uid, gid).await?;
+        self.cache.invalidate_inode(stats.ino);
+        let file = self.wrap(stats.ino, file);
+        Ok((stats, file))
+    }
+
+    async fn remove_orphan(&self, ino: i64) -> Result<()> {
+        let result = self.inner.remove_orphan(ino).await;
+        self.cache.invalidate_inode(ino);
+        result
+    }
+
+    async fn mknod(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        mode: u32,
+        rdev: u64,
+        uid: u32,
+        gid: u32,
+    ) -> Result<Stats> {
+        let stats = self
+            .inner
+            .mknod(parent_ino, name, mode, rdev, uid, gid)
+            .await?;
+        self.created(parent_ino, name, &stats);
+        Ok(stats)
+    }
+
+    async fn symlink(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        target: &str,
+        uid: u32,
+        gid: u32,
+    ) -> Result<Stats> {
+        let stats = self
+            .inner
+            .symlink(parent_ino, name, target, uid, gid)
+            .await?;
+        self.created(parent_ino, name, &stats);
+        Ok(stats)
+    }
+
+    async fn unlink(&self, parent_ino: i64, name: &str) -> Result<()> {
+        let result = self.inner.unlink(parent_ino, name).await;
+        self.cache.invalidate_name(parent_ino, name);
+        self.cache.invalidate_attr(parent_ino);
+        result
+    }
+
+    async fn unlink_keep_orphan(&self, parent_ino: i64, name: &str) -> Result<Option<i64>> {
+        let result = self.inner.unlink_keep_orphan(parent_ino, name).await;
+        self.cache.invalidate_name(parent_ino, name);
+        self.cache.invalidate_attr(parent_ino);
+        result
+    }
+
+    async fn rmdir(&self, parent_ino: i64, name: &str) -> Result<()> {
+        let result = self.inner.rmdir(parent_ino, name).await;
+        self.cache.invalidate_name(parent_ino, name);
+        self.cache.invalidate_attr(parent_ino);
+        result
+    }
+
+    async fn link(&self, ino: i64, newparent_ino: i64, newname: &str) -> Result<Stats> {
+        let result = self.inner.link(ino, newparent_ino, newname).await;
+        self.cache.invalidate_attr(ino);
+        self.cache.invalidate_attr(newparent_ino);
+        let stats = result?;
+        self.cache.put_entry(newparent_ino, newname, stats.ino);
+        Ok(stats)
+    }
+
+    async fn rename(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+    ) -> Result<()> {
+        self.rename_with_flags(oldparent_ino, oldname, newparent_ino, newname, 0)
+            .await
+    }
+
+    async fn rename_with_flags(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+        flags: u32,
+    ) -> Result<()> {
+        let result = if flags == 0 {
+            self.inner
+                .rename(oldparent_ino, oldname, newparent_ino, newname)
+                .await
+        } else {
+            self.inner
+                .rename_with_flags(oldparent_ino, oldname, newparent_ino, newname, flags)
+                .await
+        };
+        self.cache.invalidate_name(oldparent_ino, oldname);
+        self.cache.invalidate_name(newparent_ino, newname);
+        self.cache.invalidate_attr(oldparent_ino);
+        self.cache.invalidate_attr(newparent_ino);
+        result
+    }
+
+    async fn rename_keep_orphan(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+        flags: u32,
+    ) -> Result<Option<i64>> {
+        let result = self
+            .inner
+            .rename_keep_orphan(oldparent_ino, oldname, newparent_ino, newname, flags)
+            .await;
+        self.cache.invalidate_name(oldparent_ino, oldname);
+        self.cache.invalidate_name(newparent_ino, newname);
+        self.cache.invalidate_attr(oldparent_ino);
+        self.cache.invalidate_attr(newparent_ino);
+        result
+    }
+
+    async fn get_inode_flags(&self, ino: i64) -> Result<u32> {
+        self.inner.get_inode_flags(ino).await
+    }
+
+    async fn set_inode_flags(&self, ino: i64, flags: u32) -> Result<()> {
+        let result = self.inner.set_inode_flags(ino, flags).await;
+        self.cache.invalidate_attr(ino);
+        result
+    }
+
+    async fn statfs(&self) -> Result<FilesystemStats> {
+        self.inner.statfs().await
+    }
+
+    async fn health(&self) -> Result<()> {
+        self.inner.health().await
+    }
+
+    async fn checkpoint(&self) -> Result<()> {
+        self.inner.checkpoint().await
+    }
+
+    async fn forget(&self, ino: i64, nlookup: u64) {
+        self.inner.forget(ino, nlookup).await
+    }
+}
+
+/// File opened through a `CachingFs`.
+struct CachingFile {
+    inner: BoxedFile,
+    ino: i64,
+    cache: Arc<FsCache>,
+}
+
+impl CachingFile {
+    /// Get a block of the file, reading it from the inner file on a miss.
+    async fn block(&self, index: u64) -> Result<Arc<Vec<u8>>> {
+        if let Some(data) = self.cache.get_block(self.ino, index) {
+            self.cache.count(true);
+            return Ok(data);
+        }
+        self.cache.count(false);
+        let block_size = self.cache.block_size as u64;
+        let generation = self.cache.generation(self.ino);
+        let data = Arc::new(self.inner.pread(index * block_size, block_size).await?);
+        self.cache
+            .put_block(self.ino, index, generation, data.clone());
+        Ok(data)
+    }
+
+    /// Invalidate the cache after a change to the file, even a failed one,
+    /// which may have been partially applied.
+    fn changed<T>(&self, result: Result<T>) -> Result<T> {
+        self.cache.invalidate_inode(self.ino);
+        result
+    }
+}
+
+#[async_trait]
+impl File for CachingFile {
+    async fn pread(&self, offset: u64, size: u64) -> Result<Vec<u8>> {
+        let block_size = self.cache.block_size as u64;
+        let end = offset.saturating_add(size);
+        let mut data = Vec::with_capacity(size.min(16 * block_size) as usize);
+        let mut pos = offset;
+        while pos < end {
+            let index = pos / block_size;
+            let block = self.block(index).await?;
+            let start = (pos - index * block_size) as usize;
+            if start >= block.len() {
+                break;
+            }
+            let stop = block.len().min((end - index * block_size) as usize);
+            data.extend_from_slice(&block[start..stop]);
+            if block.len() < block_size as usize {
+                // Short block: end of file
+                break;
+            }
+            pos = (index + 1) * block_size;
+        }
+        Ok(data)
+    }
+
+    async fn pwrite(&self, offset: u64, data: &[u8]) -> Result<()> {
+        let result = self.inner.pwrite(offset, data).await;
+        self.changed(result)
+    }
+
+    async fn truncate(&self, size: u64) -> Result<()> {
+        let result = self.inner.truncate(size).await;
+        self.changed(result)
+    }
+
+    async fn fsync(&self) -> Result<()> {
+        self.inner.fsync().await
+    }
+
+    async fn fdatasync(&self) -> Result<()> {
+        self.inner.fdatasync().await
+    }
+
+    async fn flush(&self) -> Result<()> {
+        let result = self.inner.flush().await;
+        // Buffered writes reach the inner filesystem on flush
+        self.changed(result)
+    }
+
+    async fn fstat(&self) -> Result<Stats> {
+        if let Some(stats) = self.cache.get_attr(self.ino) {
+            self.cache.count(true);
+            return Ok(stats);
+        }
+        self.cache.count(false);
+        let stats = self.inner.fstat().await?;
+        self.cache.put_attr(&stats);
+        Ok(stats)
+    }
+
+    async fn seek_data(&self, offset: u64) -> Result<u64> {
+        self.inner.seek_data(offset).await
+    }
+
+    async fn seek_hole(&self, offset: u64) -> Result<u64> {
+        self.inner.seek_hole(offset).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::{AgentFS, MockFileSystem, DEFAULT_FILE_MODE};
+
+    /// A caching filesystem over a mock that counts the calls reaching it.
+    async fn caching_fs(dir: &tempfile::TempDir) -> (CachingFs, Arc<MockFileSystem>) {
+        let db_path = dir.path().join("test.db");
+        let fs = AgentFS::new(db_path.to_str().unwrap()).await.unwrap();
+        let mock = Arc::new(MockFileSystem::new(Arc::new(fs)));
+        let cached = CachingFs::new(mock.clone())
+            .with_block_size(4096)
+            .with_ttl(Duration::from_secs(60));
+        (cached, mock)
+    }
+
+    #[tokio::test]
+    async fn test_caching_fs_serves_warm_reads_from_memory() {
+        let dir = tempfile::tempdir().unwrap();
+        let (fs, mock) = caching_fs(&dir).await;
+
+        let (stats, file) = fs
+            .create_file(1, "data", DEFAULT_FILE_MODE, 0, 0)
+            .await
+            .unwrap();
+        let contents: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+        file.pwrite(0, &contents).await.unwrap();
+
+        // Read everything in small pieces, twice
+        let mut reads = 0;
+        for round in 0..2 {
+            let before = mock.count("pread") + mock.count("lookup") + mock.count("getattr");
+            for offset in (0..contents.len()).step_by(1000) {
+                let found = fs.lookup(1, "data").await.unwrap().unwrap();
+                assert_eq!(found.ino, stats.ino);
+                fs.getattr(stats.ino).await.unwrap().unwrap();
+                let data = file.pread(offset as u64, 1000).await.unwrap();
+                let end = (offset + 1000).min(contents.len());
+                assert_eq!(data, &contents[offset..end]);
+            }
+            let after = mock.count("pread") + mock.count("lookup") + mock.count("getattr");
+            if round == 0 {
+                reads = after - before;
+            } else {
+                // The warm cache answers every read
+                assert_eq!(after - before, 0);
+            }
+        }
+        // 16 blocks and one attribute read, instead of three calls per piece
+        assert!(reads <= 18, "cold pass made {} inner reads", reads);
+        assert!(fs.hits() > 3 * 60);
+    }
+
+    #[tokio::test]
+    async fn test_caching_fs_invalidates_on_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let (fs, _mock) = caching_fs(&dir).await;
+
+        let (stats, file) = fs
+            .create_file(1, "data", DEFAULT_FILE_MODE, 0, 0)
+            .await
+            .unwrap();
+        file.pwrite(0, b"hello world").await.unwrap();
+        assert_eq!(file.pread(0, 64).await.unwrap(), b"hello world");
+        assert_eq!(fs.getattr(stats.ino).await.unwrap().unwrap().size, 11);
+
+        // Writes through the wrapper are visible at once
+        file.pwrite(6, b"there").await.unwrap();
+        assert_eq!(file.pread(0, 64).await.unwrap(), b"hello there");
+        file.truncate(5).await.unwrap();
+        assert_eq!(file.pread(0, 64).await.unwrap(), b"hello");
+        assert_eq!(fs.getattr(stats.ino).await.unwrap().unwrap().size, 5);
+        fs.chmod(stats.ino, 0o600).await.unwrap();
+        assert_eq!(
+            fs.getattr(stats.ino).await.unwrap().unwrap().mode & 0o777,
+            0o600
+        );
+
+        // Renames and unlinks drop the cached entries
+        fs.rename(1, "data", 1, "moved").await.unwrap();
+        assert!(fs.lookup(1, "data").await.unwrap().is_none());
+        assert_eq!(fs.lookup(1, "moved").await.unwrap().unwrap().ino, stats.ino);
+        fs.unlink(1, "moved").await.unwrap();
+        assert!(fs.lookup(1, "moved").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_caching_fs_entries_expire() {
+        let dir = tempfile::tempdir().unwrap();
+        let (fs, mock) = caching_fs(&dir).await;
+        let fs = fs.with_ttl(Duration::from_millis(20));
+
+        let (stats, _file) = fs
+            .create_file(1, "data", DEFAULT_FILE_MODE, 0, 0)
+            .await
+            .unwrap();
+        fs.getattr(stats.ino).await.unwrap();
+        assert_eq!(mock.count("getattr"), 0);
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        fs.getattr(stats.ino).await.unwrap();
+        assert_eq!(mock.count("getattr"), 1);
+    }
+}
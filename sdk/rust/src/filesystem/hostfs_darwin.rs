@@ -164,7 +164,11 @@ fn stat_to_stats(stat: &libc::stat) -> Stats {
         atime_nsec: stat.st_atime_nsec as u32,
         mtime_nsec: stat.st_mtime_nsec as u32,
         ctime_nsec: stat.st_ctime_nsec as u32,
+        btime: stat.st_birthtime,
+        btime_nsec: stat.st_birthtime_nsec as u32,
         rdev: stat.st_rdev as u64,
+        // Host inode generations would need an ioctl on an open fd
+        generation: 0,
     }
 }
 
@@ -131,6 +131,27 @@ impl File for HostFSFile {
         .map_err(|e| Error::Internal(e.to_string()))?
     }
 
+    async fn fdatasync(&self) -> Result<()> {
+        let fd = self.fd.as_raw_fd();
+        tokio::task::spawn_blocking(move || {
+            let result = unsafe { libc::fdatasync(fd) };
+            if result < 0 {
+                return Err(std::io::Error::last_os_error().into());
+            }
+            Ok(())
+        })
+        .await
+        .map_err(|e| Error::Internal(e.to_string()))?
+    }
+
+    async fn seek_data(&self, offset: u64) -> Result<u64> {
+        seek_fd(self.fd.as_raw_fd(), offset, libc::SEEK_DATA).await
+    }
+
+    async fn seek_hole(&self, offset: u64) -> Result<u64> {
+        seek_fd(self.fd.as_raw_fd(), offset, libc::SEEK_HOLE).await
+    }
+
     async fn fstat(&self) -> Result<Stats> {
         let fd = self.fd.as_raw_fd();
         tokio::task::spawn_blocking(move || {
@@ -146,6 +167,23 @@ impl File for HostFSFile {
     }
 }
 
+/// Run `lseek` with `SEEK_DATA` or `SEEK_HOLE` on a host fd.
+async fn seek_fd(fd: RawFd, offset: u64, whence: i32) -> Result<u64> {
+    tokio::task::spawn_blocking(move || {
+        let result = unsafe { libc::lseek(fd, offset as libc::off_t, whence) };
+        if result < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::ENXIO) {
+                return Err(FsError::NoSuchOffset.into());
+            }
+            return Err(err.into());
+        }
+        Ok(result as u64)
+    })
+    .await
+    .map_err(|e| Error::Internal(e.to_string()))?
+}
+
 /// Convert libc::stat to our Stats struct
 fn stat_to_stats(stat: &libc::stat) -> Stats {
     Stats {
@@ -161,7 +199,12 @@ fn stat_to_stats(stat: &libc::stat) -> Stats {
         atime_nsec: stat.st_atime_nsec as u32,
         mtime_nsec: stat.st_mtime_nsec as u32,
         ctime_nsec: stat.st_ctime_nsec as u32,
+        // stat(2) has no birth time; it would take statx(2)
+        btime: 0,
+        btime_nsec: 0,
         rdev: stat.st_rdev,
+        // Host inode generations would need an ioctl on an open fd
+        generation: 0,
     }
 }
 
@@ -0,0 +1,482 @@
+//! Programmable `FileSystem` for testing code built on the trait.
+//!
+//! `MockFileSystem` wraps a real filesystem and forwards every operation to
+//! it, recording each call. Faults injected per operation make calls fail
+//! with a chosen error, take longer, or never finish, so mount layers and
+//! other consumers can be tested against ENOSPC, EIO, slow backends and
+//! hangs without a filesystem that actually misbehaves.
+//!
+//! Operations are named after the trait methods (`"lookup"`, `"mkdir"`,
+//! ...). Files opened through the mock are wrapped too; their operations
+//! are named after the `File` methods (`"pread"`, `"pwrite"`, ...).
+
+use crate::error::{Error, Result};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::{BoxedFile, DirEntry, File, FileSystem, FilesystemStats, FsError, Stats, TimeChange};
+
+/// Misbehaviour injected into an operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Fail with a filesystem error.
+    Error(FsError),
+    /// Fail with an I/O error carrying this errno.
+    Io(i32),
+    /// Wait this long, then run the operation.
+    Delay(Duration),
+    /// Never complete.
+    Hang,
+}
+
+/// A call made through a `MockFileSystem` or one of its files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockCall {
+    /// Operation called
+    pub op: &'static str,
+    /// Inode operated on: the parent directory for operations taking a name
+    pub ino: i64,
+    /// Name operated on, if the operation takes one
+    pub name: Option<String>,
+}
+
+/// Shared state of a mock and the files opened through it.
+#[derive(Default)]
+struct MockState {
+    faults: Mutex<HashMap<&'static str, Fault>>,
+    calls: Mutex<Vec<MockCall>>,
+}
+
+impl MockState {
+    /// Record a call and apply the fault injected into `op`, if any.
+    async fn enter(&self, op: &'static str, ino: i64, name: Option<&str>) -> Result<()> {
+        self.calls.lock().unwrap().push(MockCall {
+            op,
+            ino,
+            name: name.map(str::to_string),
+        });
+        let fault = self.faults.lock().unwrap().get(op).copied();
+        match fault {
+            None => Ok(()),
+            Some(Fault::Error(err)) => Err(err.into()),
+            Some(Fault::Io(errno)) => Err(Error::Io(std::io::Error::from_raw_os_error(errno))),
+            Some(Fault::Delay(delay)) => {
+                tokio::time::sleep(delay).await;
+                Ok(())
+            }
+            Some(Fault::Hang) => std::future::pending().await,
+        }
+    }
+}
+
+/// Filesystem forwarding to another one, with injectable faults and a log
+/// of every call.
+pub struct MockFileSystem {
+    inner: Arc<dyn FileSystem>,
+    state: Arc<MockState>,
+}
+
+impl MockFileSystem {
+    /// Wrap `inner`, forwarding every operation to it until faults are
+    /// injected.
+    pub fn new(inner: Arc<dyn FileSystem>) -> Self {
+        Self {
+            inner,
+            state: Arc::default(),
+        }
+    }
+
+    /// Apply `fault` to every later call of `op`, replacing any fault
+    /// injected into it before.
+    pub fn inject(&self, op: &'static str, fault: Fault) {
+        self.state.faults.lock().unwrap().insert(op, fault);
+    }
+
+    /// Stop injecting faults into `op`.
+    pub fn clear(&self, op: &'static str) {
+        self.state.faults.lock().unwrap().remove(op);
+    }
+
+    /// Calls made so far, in order.
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.state.calls.lock().unwrap().clone()
+    }
+
+    /// Number of calls of `op` made so far.
+    pub fn count(&self, op: &str) -> usize {
+        self.state
+            .calls
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|call| call.op == op)
+            .count()
+    }
+
+    /// Wrap a file opened on the inner filesystem.
+    fn wrap(&self, ino: i64, file: BoxedFile) -> BoxedFile {
+        Arc::new(MockFile {
+            inner: file,
+            ino,
+            state: self.state.clone(),
+        })
+    }
+
+    async fn enter(&self, op: &'static str, ino: i64, name: Option<&str>) -> Result<()> {
+        self.state.enter(op, ino, name).await
+    }
+}
+
+#[async_trait]
+impl FileSystem for MockFileSystem {
+    async fn lookup(&self, parent_ino: i64, name: &str) -> Result<Option<Stats>> {
+        self.enter("lookup", parent_ino, Some(name)).await?;
+        self.inner.lookup(parent_ino, name).await
+    }
+
+    async fn exists(&self, parent_ino: i64, name: &str) -> Result<bool> {
+        self.enter("exists", parent_ino, Some(name)).await?;
+        self.inner.exists(parent_ino, name).await
+    }
+
+    async fn getattr(&self, ino: i64) -> Result<Option<Stats>> {
+        self.enter("getattr", ino, None).await?;
+        self.inner.getattr(ino).await
+    }
+
+    async fn readlink(&self, ino: i64) -> Result<Option<String>> {
+        self.enter("readlink", ino, None).await?;
+        self.inner.readlink(ino).await
+    }
+
+    async fn readdir(&self, ino: i64) -> Result<Option<Vec<String>>> {
+        self.enter("readdir", ino, None).await?;
+        self.inner.readdir(ino).await
+    }
+
+    async fn readdir_plus(&self, ino: i64) -> Result<Option<Vec<DirEntry>>> {
+        self.enter("readdir_plus", ino, None).await?;
+        self.inner.readdir_plus(ino).await
+    }
+
+    async fn chmod(&self, ino: i64, mode: u32) -> Result<()> {
+        self.enter("chmod", ino, None).await?;
+        self.inner.chmod(ino, mode).await
+    }
+
+    async fn chown(&self, ino: i64, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        self.enter("chown", ino, None).await?;
+        self.inner.chown(ino, uid, gid).await
+    }
+
+    async fn utimens(&self, ino: i64, atime: TimeChange, mtime: TimeChange) -> Result<()> {
+        self.enter("utimens", ino, None).await?;
+        self.inner.utimens(ino, atime, mtime).await
+    }
+
+    async fn open(&self, ino: i64, flags: i32) -> Result<BoxedFile> {
+        self.enter("open", ino, None).await?;
+        let file = self.inner.open(ino, flags).await?;
+        Ok(self.wrap(ino, file))
+    }
+
+    async fn mkdir(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        mode: u32,
+        uid: u32,
+        gid: u32,
+    ) -> Result<Stats> {
+        self.enter("mkdir", parent_ino, Some(name)).await?;
+        self.inner.mkdir(parent_ino, name, mode, uid, gid).await
+    }
+
+    async fn create_file(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        mode: u32,
+        uid: u32,
+        gid: u32,
+    ) -> Result<(Stats, BoxedFile)> {
+        self.enter("create_file", parent_ino, Some(name)).await?;
+        let (stats, file) = self
+            .inner
+            .create_file(parent_ino, name, mode, uid, gid)
+            .await?;
+        let file = self.wrap(stats.ino, file);
+        Ok((stats, file))
+    }
+
+    async fn create_tmpfile(&self, mode: u32, uid: u32, gid: u32) -> Result<(Stats, BoxedFile)> {
+        self.enter("create_tmpfile", 0, None).await?;
+        let (stats, file) = self.inner.create_tmpfile(mode, uid, gid).await?;
+        let file = self.wrap(stats.ino, file);
+        Ok((stats, file))
+    }
+
+    async fn remove_orphan(&self, ino: i64) -> Result<()> {
+        self.enter("remove_orphan", ino, None).await?;
+        self.inner.remove_orphan(ino).await
+    }
+
+    async fn mknod(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        mode: u32,
+        rdev: u64,
+        uid: u32,
+        gid: u32,
+    ) -> Result<Stats> {
+        self.enter("mknod", parent_ino, Some(name)).await?;
+        self.inner
+            .mknod(parent_ino, name, mode, rdev, uid, gid)
+            .await
+    }
+
+    async fn symlink(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        target: &str,
+        uid: u32,
+        gid: u32,
+    ) -> Result<Stats> {
+        self.enter("symlink", parent_ino, Some(name)).await?;
+        self.inner.symlink(parent_ino, name, target, uid, gid).await
+    }
+
+    async fn unlink(&self, parent_ino: i64, name: &str) -> Result<()> {
+        self.enter("unlink", parent_ino, Some(name)).await?;
+        self.inner.unlink(parent_ino, name).await
+    }
+
+    async fn unlink_keep_orphan(&self, parent_ino: i64, name: &str) -> Result<Option<i64>> {
+        self.enter("unlink_keep_orphan", parent_ino, Some(name))
+            .await?;
+        self.inner.unlink_keep_orphan(parent_ino, name).await
+    }
+
+    async fn rmdir(&self, parent_ino: i64, name: &str) -> Result<()> {
+        self.enter("rmdir", parent_ino, Some(name)).await?;
+        self.inner.rmdir(parent_ino, name).await
+    }
+
+    async fn link(&self, ino: i64, newparent_ino: i64, newname: &str) -> Result<Stats> {
+        self.enter("link", newparent_ino, Some(newname)).await?;
+        self.inner.link(ino, newparent_ino, newname).await
+    }
+
+    async fn rename(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+    ) -> Result<()> {
+        self.enter("rename", oldparent_ino, Some(oldname)).await?;
+        self.inner
+            .rename(oldparent_ino, oldname, newparent_ino, newname)
+            .await
+    }
+
+    async fn rename_with_flags(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+        flags: u32,
+    ) -> Result<()> {
+        self.enter("rename_with_flags", oldparent_ino, Some(oldname))
+            .await?;
+        self.inner
+            .rename_with_flags(oldparent_ino, oldname, newparent_ino, newname, flags)
+            .await
+    }
+
+    async fn rename_keep_orphan(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+        flags: u32,
+    ) -> Result<Option<i64>> {
+        self.enter("rename_keep_orphan", oldparent_ino, Some(oldname))
+            .await?;
+        self.inner
+            .rename_keep_orphan(oldparent_ino, oldname, newparent_ino, newname, flags)
+            .await
+    }
+
+    async fn get_inode_flags(&self, ino: i64) -> Result<u32> {
+        self.enter("get_inode_flags", ino, None).await?;
+        self.inner.get_inode_flags(ino).await
+    }
+
+    async fn set_inode_flags(&self, ino: i64, flags: u32) -> Result<()> {
+        self.enter("set_inode_flags", ino, None).await?;
+        self.inner.set_inode_flags(ino, flags).await
+    }
+
+    async fn statfs(&self) -> Result<FilesystemStats> {
+        self.enter("statfs", 0, None).await?;
+        self.inner.statfs().await
+    }
+
+    async fn health(&self) -> Result<()> {
+        self.enter("health", 0, None).await?;
+        self.inner.health().await
+    }
+
+    async fn checkpoint(&self) -> Result<()> {
+        self.enter("checkpoint", 0, None).await?;
+        self.inner.checkpoint().await
+    }
+
+    async fn forget(&self, ino: i64, nlookup: u64) {
+        // Nothing to fail; a fault only delays or hangs the call
+        let _ = self.enter("forget", ino, None).await;
+        self.inner.forget(ino, nlookup).await
+    }
+}
+
+/// File opened through a `MockFileSystem`.
+struct MockFile {
+    inner: BoxedFile,
+    ino: i64,
+    state: Arc<MockState>,
+}
+
+impl MockFile {
+    async fn enter(&self, op: &'static str) -> Result<()> {
+        self.state.enter(op, self.ino, None).await
+    }
+}
+
+#[async_trait]
+impl File for MockFile {
+    async fn pread(&self, offset: u64, size: u64) -> Result<Vec<u8>> {
+        self.enter("pread").await?;
+        self.inner.pread(offset, size).await
+    }
+
+    async fn pwrite(&self, offset: u64, data: &[u8]) -> Result<()> {
+        self.enter("pwrite").await?;
+        self.inner.pwrite(offset, data).await
+    }
+
+    async fn truncate(&self, size: u64) -> Result<()> {
+        self.enter("truncate").await?;
+        self.inner.truncate(size).await
+    }
+
+    async fn fsync(&self) -> Result<()> {
+        self.enter("fsync").await?;
+        self.inner.fsync().await
+    }
+
+    async fn fdatasync(&self) -> Result<()> {
+        self.enter("fdatasync").await?;
+        self.inner.fdatasync().await
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.enter("flush").await?;
+        self.inner.flush().await
+    }
+
+    async fn fstat(&self) -> Result<Stats> {
+        self.enter("fstat").await?;
+        self.inner.fstat().await
+    }
+
+    async fn seek_data(&self, offset: u64) -> Result<u64> {
+        self.enter("seek_data").await?;
+        self.inner.seek_data(offset).await
+    }
+
+    async fn seek_hole(&self, offset: u64) -> Result<u64> {
+        self.enter("seek_hole").await?;
+        self.inner.seek_hole(offset).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::{AgentFS, DEFAULT_FILE_MODE};
+
+    async fn mock_fs(dir: &tempfile::TempDir) -> MockFileSystem {
+        let db_path = dir.path().join("test.db");
+        let fs = AgentFS::new(db_path.to_str().unwrap()).await.unwrap();
+        MockFileSystem::new(Arc::new(fs))
+    }
+
+    #[tokio::test]
+    async fn test_mock_records_calls_and_injects_faults() {
+        let dir = tempfile::tempdir().unwrap();
+        let fs = mock_fs(&dir).await;
+
+        let (stats, file) = fs
+            .create_file(1, "data", DEFAULT_FILE_MODE, 0, 0)
+            .await
+            .unwrap();
+        file.pwrite(0, b"hello").await.unwrap();
+        assert!(fs.lookup(1, "data").await.unwrap().is_some());
+        assert_eq!(
+            fs.calls(),
+            vec![
+                MockCall {
+                    op: "create_file",
+                    ino: 1,
+                    name: Some("data".to_string()),
+                },
+                MockCall {
+                    op: "pwrite",
+                    ino: stats.ino,
+                    name: None,
+                },
+                MockCall {
+                    op: "lookup",
+                    ino: 1,
+                    name: Some("data".to_string()),
+                },
+            ]
+        );
+
+        fs.inject("pwrite", Fault::Error(FsError::NoSpace));
+        let err = file.pwrite(5, b" world").await.unwrap_err();
+        assert!(matches!(err, Error::Fs(FsError::NoSpace)));
+
+        fs.inject("getattr", Fault::Io(libc::EIO));
+        let err = fs.getattr(stats.ino).await.unwrap_err();
+        assert!(matches!(err, Error::Io(ref e) if e.raw_os_error() == Some(libc::EIO)));
+
+        // Faults are per operation and can be lifted
+        fs.clear("pwrite");
+        file.pwrite(5, b" world").await.unwrap();
+        assert_eq!(file.pread(0, 64).await.unwrap(), b"hello world");
+        assert_eq!(fs.count("pwrite"), 3);
+    }
+
+    #[tokio::test]
+    async fn test_mock_delays_and_hangs() {
+        let dir = tempfile::tempdir().unwrap();
+        let fs = mock_fs(&dir).await;
+
+        fs.inject("statfs", Fault::Delay(Duration::from_millis(50)));
+        let start = std::time::Instant::now();
+        fs.statfs().await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        fs.inject("statfs", Fault::Hang);
+        let hung = tokio::time::timeout(Duration::from_millis(50), fs.statfs()).await;
+        assert!(hung.is_err());
+    }
+}
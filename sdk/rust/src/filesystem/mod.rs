@@ -1,9 +1,14 @@
 pub mod agentfs;
+mod block_lru;
+pub mod blocking;
+pub mod caching;
 #[cfg(target_os = "macos")]
 pub mod hostfs_darwin;
 #[cfg(target_os = "linux")]
 pub mod hostfs_linux;
+pub mod mock;
 pub mod overlayfs;
+pub mod writer;
 
 use crate::error::Result;
 use async_trait::async_trait;
@@ -11,15 +16,19 @@ use std::sync::Arc;
 use thiserror::Error;
 
 // Re-export implementations
-pub use agentfs::AgentFS;
+pub use agentfs::{AgentFS, BatchEntry, ChangeHook, ChunkCache};
+pub use blocking::{BlockingFile, BlockingFs};
+pub use caching::CachingFs;
 #[cfg(target_os = "macos")]
 pub use hostfs_darwin::HostFS;
 #[cfg(target_os = "linux")]
 pub use hostfs_linux::HostFS;
+pub use mock::{Fault, MockCall, MockFileSystem};
 pub use overlayfs::OverlayFS;
+pub use writer::FileWriter;
 
 /// Filesystem-specific errors with errno semantics
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
 pub enum FsError {
     #[error("Path does not exist")]
     NotFound,
@@ -53,6 +62,30 @@ pub enum FsError {
 
     #[error("Filename too long")]
     NameTooLong,
+
+    #[error("Operation not supported")]
+    NotSupported,
+
+    #[error("No space left on device")]
+    NoSpace,
+
+    #[error("File too large")]
+    FileTooLarge,
+
+    #[error("Data integrity check failed")]
+    ChecksumMismatch,
+
+    #[error("No data or hole at or past offset")]
+    NoSuchOffset,
+
+    #[error("Read-only file system")]
+    ReadOnly,
+
+    #[error("Operation not permitted")]
+    NotPermitted,
+
+    #[error("Invalid cross-device link")]
+    CrossDevice,
 }
 
 impl FsError {
@@ -70,6 +103,14 @@ impl FsError {
             FsError::SymlinkLoop => libc::ELOOP,
             FsError::InvalidRename => libc::EINVAL,
             FsError::NameTooLong => libc::ENAMETOOLONG,
+            FsError::NotSupported => libc::EOPNOTSUPP,
+            FsError::NoSpace => libc::ENOSPC,
+            FsError::FileTooLarge => libc::EFBIG,
+            FsError::ChecksumMismatch => libc::EIO,
+            FsError::NoSuchOffset => libc::ENXIO,
+            FsError::ReadOnly => libc::EROFS,
+            FsError::NotPermitted => libc::EPERM,
+            FsError::CrossDevice => libc::EXDEV,
         }
     }
 }
@@ -91,6 +132,14 @@ pub const S_IFSOCK: u32 = 0o140000; // Socket
 pub const DEFAULT_FILE_MODE: u32 = S_IFREG | 0o644; // Regular file, rw-r--r--
 pub const DEFAULT_DIR_MODE: u32 = S_IFDIR | 0o755; // Directory, rwxr-xr-x
 
+// Flags for rename_with_flags (same values as Linux renameat2)
+pub const RENAME_NOREPLACE: u32 = 1 << 0; // Fail if the destination exists
+pub const RENAME_EXCHANGE: u32 = 1 << 1; // Atomically swap source and destination
+
+// Inode flags for set_inode_flags (same values as Linux FS_IOC_GETFLAGS)
+pub const FS_IMMUTABLE_FL: u32 = 0x10; // Cannot be written, renamed, linked or removed
+pub const FS_APPEND_FL: u32 = 0x20; // Can only be appended to
+
 /// Represents a timestamp change request for utimens.
 #[derive(Debug, Clone, Copy)]
 pub enum TimeChange {
@@ -117,7 +166,14 @@ pub struct Stats {
     pub atime_nsec: u32,
     pub mtime_nsec: u32,
     pub ctime_nsec: u32,
+    /// Creation (birth) time. 0 if unknown, as for inodes created before
+    /// it was recorded.
+    pub btime: i64,
+    pub btime_nsec: u32,
     pub rdev: u64, // Device ID for special files (char/block devices)
+    /// Inode generation. Changes when an inode number is reused for a different
+    /// file, so `(ino, generation)` identifies a file. 0 if unknown.
+    pub generation: u64,
 }
 
 /// Filesystem statistics for statfs
@@ -150,6 +206,22 @@ impl Stats {
     pub fn is_symlink(&self) -> bool {
         (self.mode & S_IFMT) == S_IFLNK
     }
+
+    pub fn is_fifo(&self) -> bool {
+        (self.mode & S_IFMT) == S_IFIFO
+    }
+
+    pub fn is_socket(&self) -> bool {
+        (self.mode & S_IFMT) == S_IFSOCK
+    }
+
+    pub fn is_char_device(&self) -> bool {
+        (self.mode & S_IFMT) == S_IFCHR
+    }
+
+    pub fn is_block_device(&self) -> bool {
+        (self.mode & S_IFMT) == S_IFBLK
+    }
 }
 
 /// An open file handle for performing I/O operations.
@@ -171,8 +243,50 @@ pub trait File: Send + Sync {
     /// Synchronize file data to persistent storage.
     async fn fsync(&self) -> Result<()>;
 
+    /// Synchronize file data, but not necessarily metadata, to persistent
+    /// storage (like POSIX fdatasync).
+    async fn fdatasync(&self) -> Result<()> {
+        self.fsync().await
+    }
+
+    /// Write back buffered data when a file descriptor is closed.
+    ///
+    /// Unlike `fsync`, this makes no durability guarantee. It exists so that
+    /// write errors reach the caller of `close(2)`. Files that write through
+    /// immediately have nothing to flush.
+    async fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
     /// Get file statistics.
     async fn fstat(&self) -> Result<Stats>;
+
+    /// Find the start of the next data region at or after `offset` (like
+    /// `lseek` with `SEEK_DATA`).
+    ///
+    /// Fails with `FsError::NoSuchOffset` when `offset` is at or past the end
+    /// of the file. Files without hole tracking are all data, so `offset` is
+    /// returned unchanged.
+    async fn seek_data(&self, offset: u64) -> Result<u64> {
+        if offset >= self.fstat().await?.size as u64 {
+            return Err(FsError::NoSuchOffset.into());
+        }
+        Ok(offset)
+    }
+
+    /// Find the start of the next hole at or after `offset` (like `lseek`
+    /// with `SEEK_HOLE`).
+    ///
+    /// The end of the file counts as a hole. Fails with
+    /// `FsError::NoSuchOffset` when `offset` is at or past the end of the
+    /// file. Files without hole tracking have no holes before EOF.
+    async fn seek_hole(&self, offset: u64) -> Result<u64> {
+        let size = self.fstat().await?.size as u64;
+        if offset >= size {
+            return Err(FsError::NoSuchOffset.into());
+        }
+        Ok(size)
+    }
 }
 
 /// A boxed File trait object for dynamic dispatch.
@@ -193,6 +307,14 @@ pub trait FileSystem: Send + Sync {
     /// Returns `Ok(None)` if the entry does not exist.
     async fn lookup(&self, parent_ino: i64, name: &str) -> Result<Option<Stats>>;
 
+    /// Check whether a directory entry exists, without following symlinks.
+    ///
+    /// Cheaper than `lookup` where the backend can answer without reading
+    /// the entry's attributes. The default implementation calls `lookup`.
+    async fn exists(&self, parent_ino: i64, name: &str) -> Result<bool> {
+        Ok(self.lookup(parent_ino, name).await?.is_some())
+    }
+
     /// Get file attributes for an inode.
     ///
     /// Returns stats for the inode itself (does not follow symlinks).
@@ -258,6 +380,42 @@ pub trait FileSystem: Send + Sync {
         gid: u32,
     ) -> Result<(Stats, BoxedFile)>;
 
+    /// Create a new empty file and return a writer streaming into it.
+    ///
+    /// Unlike writing a whole buffer with `pwrite`, the writer holds at most
+    /// `writer::DEFAULT_WRITER_BUFFER` bytes at a time, so content of any size
+    /// can be copied in with bounded memory.
+    async fn create_writer(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        mode: u32,
+        uid: u32,
+        gid: u32,
+    ) -> Result<FileWriter> {
+        let (_, file) = self.create_file(parent_ino, name, mode, uid, gid).await?;
+        Ok(FileWriter::new(file))
+    }
+
+    /// Create an anonymous regular file that is not linked into any directory.
+    ///
+    /// This implements `O_TMPFILE` semantics: the new inode has a link count of
+    /// zero and can be given a name later with `link()`. An inode that is never
+    /// linked should be released with `remove_orphan()` once it is closed.
+    ///
+    /// The default implementation returns `FsError::NotSupported`.
+    async fn create_tmpfile(&self, _mode: u32, _uid: u32, _gid: u32) -> Result<(Stats, BoxedFile)> {
+        Err(FsError::NotSupported.into())
+    }
+
+    /// Remove an inode if it is no longer linked into any directory.
+    ///
+    /// Inodes that still have links are left untouched. The default
+    /// implementation is a no-op.
+    async fn remove_orphan(&self, _ino: i64) -> Result<()> {
+        Ok(())
+    }
+
     /// Create a special file node (FIFO, device, socket, or regular file).
     ///
     /// Returns the stats of the newly created node.
@@ -286,6 +444,17 @@ pub trait FileSystem: Send + Sync {
     /// Remove a file (non-directory) from a directory.
     async fn unlink(&self, parent_ino: i64, name: &str) -> Result<()>;
 
+    /// Remove a file like `unlink`, but keep its inode when this was its
+    /// last link, so that it stays usable through open handles (POSIX
+    /// unlink-while-open). Returns the inode if it was kept; release it
+    /// with `remove_orphan()` once nothing refers to it any longer.
+    ///
+    /// The default implementation calls `unlink` and keeps nothing.
+    async fn unlink_keep_orphan(&self, parent_ino: i64, name: &str) -> Result<Option<i64>> {
+        self.unlink(parent_ino, name).await?;
+        Ok(None)
+    }
+
     /// Remove an empty directory.
     async fn rmdir(&self, parent_ino: i64, name: &str) -> Result<()>;
 
@@ -304,9 +473,90 @@ pub trait FileSystem: Send + Sync {
         newname: &str,
     ) -> Result<()>;
 
+    /// Rename/move a file or directory with `renameat2` semantics.
+    ///
+    /// `flags` may be `RENAME_NOREPLACE` (fail with `FsError::AlreadyExists`
+    /// if the destination exists) or `RENAME_EXCHANGE` (atomically swap the
+    /// two entries, both of which must exist). The default implementation
+    /// only handles a plain rename and returns `FsError::NotSupported` for
+    /// any flag.
+    async fn rename_with_flags(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+        flags: u32,
+    ) -> Result<()> {
+        if flags != 0 {
+            return Err(FsError::NotSupported.into());
+        }
+        self.rename(oldparent_ino, oldname, newparent_ino, newname)
+            .await
+    }
+
+    /// Rename like `rename_with_flags`, but keep the inode of a replaced
+    /// destination when this was its last link, as `unlink_keep_orphan`
+    /// does. Returns the inode if it was kept; release it with
+    /// `remove_orphan()` once nothing refers to it any longer.
+    ///
+    /// The default implementation calls `rename_with_flags` and keeps nothing.
+    async fn rename_keep_orphan(
+        &self,
+        oldparent_ino: i64,
+        oldname: &str,
+        newparent_ino: i64,
+        newname: &str,
+        flags: u32,
+    ) -> Result<Option<i64>> {
+        self.rename_with_flags(oldparent_ino, oldname, newparent_ino, newname, flags)
+            .await?;
+        Ok(None)
+    }
+
+    /// Get the inode flags (`FS_IMMUTABLE_FL`, `FS_APPEND_FL`) of an inode.
+    ///
+    /// The default implementation reports no flags.
+    async fn get_inode_flags(&self, _ino: i64) -> Result<u32> {
+        Ok(0)
+    }
+
+    /// Replace the inode flags of an inode.
+    ///
+    /// Immutable inodes cannot be written, truncated, renamed, linked or
+    /// removed, and append-only inodes cannot be shrunk, renamed, linked or
+    /// removed; those operations fail with `FsError::NotPermitted`. Writes
+    /// below the end of an append-only file are refused when it is opened,
+    /// by the adapters that implement `O_APPEND`. The default implementation
+    /// returns `FsError::NotSupported`.
+    async fn set_inode_flags(&self, _ino: i64, _flags: u32) -> Result<()> {
+        Err(FsError::NotSupported.into())
+    }
+
     /// Get filesystem statistics.
     async fn statfs(&self) -> Result<FilesystemStats>;
 
+    /// Check that the backing store is reachable.
+    ///
+    /// This is a cheap, side-effect free probe meant for readiness checks.
+    /// The default implementation stats the root inode and fails with
+    /// `FsError::NotFound` if it is missing.
+    async fn health(&self) -> Result<()> {
+        match self.getattr(1).await? {
+            Some(_) => Ok(()),
+            None => Err(FsError::NotFound.into()),
+        }
+    }
+
+    /// Checkpoint the backing store's write-ahead log, if it has one.
+    ///
+    /// Long-lived mounts call this periodically to keep the log from growing
+    /// without bound. It must not block concurrent operations. The default
+    /// implementation does nothing.
+    async fn checkpoint(&self) -> Result<()> {
+        Ok(())
+    }
+
     /// Forget about an inode (called when kernel drops inode from cache).
     ///
     /// The `nlookup` parameter indicates how many lookups the kernel is forgetting.
@@ -1,4 +1,4 @@
-use crate::error::Result;
+use crate::error::{Error, Result};
 use async_trait::async_trait;
 use std::{
     collections::{HashMap, HashSet},
@@ -11,6 +11,8 @@ use std::{
 use tracing::trace;
 use turso::{Connection, Value};
 
+use crate::connection_pool::ConnectionPool;
+
 use super::{
     agentfs::AgentFS, BoxedFile, DirEntry, FileSystem, FilesystemStats, FsError, Stats, TimeChange,
 };
@@ -36,14 +38,71 @@ struct InodeInfo {
     path: String,
 }
 
+/// Read-only view of the overlay's base layer.
+///
+/// Exposes only the operations that leave the base untouched, so no overlay
+/// code path can modify the host directory underneath it: anything that
+/// changes a base entry has to copy it up to the delta first.
+struct LowerLayer(Arc<dyn FileSystem>);
+
+impl LowerLayer {
+    async fn lookup(&self, parent_ino: i64, name: &str) -> Result<Option<Stats>> {
+        self.0.lookup(parent_ino, name).await
+    }
+
+    async fn getattr(&self, ino: i64) -> Result<Option<Stats>> {
+        self.0.getattr(ino).await
+    }
+
+    async fn readlink(&self, ino: i64) -> Result<Option<String>> {
+        self.0.readlink(ino).await
+    }
+
+    async fn readdir(&self, ino: i64) -> Result<Option<Vec<String>>> {
+        self.0.readdir(ino).await
+    }
+
+    async fn readdir_plus(&self, ino: i64) -> Result<Option<Vec<DirEntry>>> {
+        self.0.readdir_plus(ino).await
+    }
+
+    /// Open a base file for reading.
+    ///
+    /// Fails with `FsError::ReadOnly` if `flags` would allow writing or
+    /// truncate the file.
+    async fn open(&self, ino: i64, flags: i32) -> Result<LowerFile> {
+        if flags & libc::O_ACCMODE != libc::O_RDONLY || flags & libc::O_TRUNC != 0 {
+            return Err(FsError::ReadOnly.into());
+        }
+        Ok(LowerFile(self.0.open(ino, flags).await?))
+    }
+
+    async fn health(&self) -> Result<()> {
+        self.0.health().await
+    }
+
+    async fn forget(&self, ino: i64, nlookup: u64) {
+        self.0.forget(ino, nlookup).await
+    }
+}
+
+/// Read-only handle to a base layer file
+struct LowerFile(BoxedFile);
+
+impl LowerFile {
+    async fn pread(&self, offset: u64, size: u64) -> Result<Vec<u8>> {
+        self.0.pread(offset, size).await
+    }
+}
+
 /// A copy-on-write overlay filesystem using inode-based operations.
 ///
 /// Combines a read-only base layer with a writable delta layer (AgentFS).
 /// All modifications are written to the delta layer, while reads fall back
 /// to the base layer if not found in delta.
 pub struct OverlayFS {
-    /// The underlying read-only base filesystem
-    base: Arc<dyn FileSystem>,
+    /// The underlying base filesystem, which is never modified
+    base: LowerLayer,
     /// The delta layer where modifications go
     delta: AgentFS,
     /// Map from overlay inode to underlying layer info
@@ -58,6 +117,9 @@ pub struct OverlayFS {
     whiteouts: RwLock<HashSet<String>>,
     /// Origin mapping: delta_ino -> base_ino (for copy-up consistency)
     origin_map: RwLock<HashMap<i64, i64>>,
+    /// Hard-linked base files copied up this session: base_ino -> delta_ino.
+    /// Their other links are linked to the same delta inode when looked up.
+    linked_copies: RwLock<HashMap<i64, i64>>,
 }
 
 impl OverlayFS {
@@ -80,7 +142,7 @@ impl OverlayFS {
         path_map.insert("/".to_string(), ROOT_INO);
 
         Self {
-            base,
+            base: LowerLayer(base),
             delta,
             inode_map: RwLock::new(inode_map),
             reverse_map: RwLock::new(reverse_map),
@@ -88,11 +150,23 @@ impl OverlayFS {
             next_ino: AtomicI64::new(2),
             whiteouts: RwLock::new(HashSet::new()),
             origin_map: RwLock::new(HashMap::new()),
+            linked_copies: RwLock::new(HashMap::new()),
         }
     }
 
     /// Initialize the overlay filesystem schema
     pub async fn init_schema(conn: &Connection, base_path: &str) -> Result<()> {
+        Self::create_tables(conn).await?;
+        conn.execute(
+            "INSERT OR REPLACE INTO fs_overlay_config (key, value) VALUES ('base_path', ?1)",
+            [Value::Text(base_path.to_string())],
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Create the tables holding the overlay's whiteouts, origins and config
+    async fn create_tables(conn: &Connection) -> Result<()> {
         conn.execute(
             "CREATE TABLE IF NOT EXISTS fs_whiteout (
                 path TEXT PRIMARY KEY,
@@ -109,11 +183,6 @@ impl OverlayFS {
             (),
         )
         .await?;
-        conn.execute(
-            "INSERT OR REPLACE INTO fs_overlay_config (key, value) VALUES ('base_path', ?1)",
-            [Value::Text(base_path.to_string())],
-        )
-        .await?;
         conn.execute(
             "CREATE TABLE IF NOT EXISTS fs_origin (
                 delta_ino INTEGER PRIMARY KEY,
@@ -125,6 +194,37 @@ impl OverlayFS {
         Ok(())
     }
 
+    /// Create an overlay whose delta layer starts as a copy of a snapshot
+    ///
+    /// The snapshot at `snapshot_path` (see [`OverlayFS::snapshot_delta`]) is
+    /// copied to a new database at `delta_path`, which becomes the delta. The
+    /// snapshot itself is only read, so it can seed any number of overlays.
+    /// As with [`OverlayFS::new`], call [`OverlayFS::init`] before use.
+    pub async fn from_snapshot(
+        base: Arc<dyn FileSystem>,
+        snapshot_path: &str,
+        delta_path: &str,
+    ) -> Result<Self> {
+        if !std::path::Path::new(snapshot_path).is_file() {
+            return Err(FsError::NotFound.into());
+        }
+        let db = turso::Builder::new_local(snapshot_path).build().await?;
+        let snapshot = AgentFS::from_pool_readonly(ConnectionPool::new(db)).await?;
+        snapshot.snapshot(delta_path).await?;
+        drop(snapshot);
+        let delta = AgentFS::new(delta_path).await?;
+        Ok(Self::new(base, delta))
+    }
+
+    /// Write a copy of the delta layer to a new database at `path`
+    ///
+    /// The copy holds everything this overlay changed relative to its base,
+    /// including whiteouts, so passing it to [`OverlayFS::from_snapshot`]
+    /// branches the sandbox state. See [`AgentFS::snapshot`].
+    pub async fn snapshot_delta(&self, path: &str) -> Result<()> {
+        self.delta.snapshot(path).await
+    }
+
     /// Initialize the overlay filesystem
     pub async fn init(&self, base_path: &str) -> Result<()> {
         let conn = self.delta.get_connection().await?;
@@ -134,6 +234,20 @@ impl OverlayFS {
         Ok(())
     }
 
+    /// Initialize the overlay without recording a base path
+    ///
+    /// [`OverlayFS::init`] records the host directory the overlay was created
+    /// on, so that tools can reopen it from the delta alone. A base that is
+    /// not a host directory, such as another AgentFS database, can't be
+    /// found that way and has to be passed to [`OverlayFS::new`] every time.
+    pub async fn init_tables(&self) -> Result<()> {
+        let conn = self.delta.get_connection().await?;
+        Self::create_tables(&conn).await?;
+        self.load_whiteouts(&conn).await?;
+        self.load_origins(&conn).await?;
+        Ok(())
+    }
+
     /// Load whiteouts from database into memory
     async fn load_whiteouts(&self, conn: &Connection) -> Result<()> {
         let mut rows = conn.query("SELECT path FROM fs_whiteout", ()).await?;
@@ -357,7 +471,7 @@ impl OverlayFS {
 
     /// Get a reference to the base layer
     pub fn base(&self) -> &Arc<dyn FileSystem> {
-        &self.base
+        &self.base.0
     }
 
     /// Get a reference to the delta layer
@@ -382,6 +496,19 @@ impl OverlayFS {
         self.origin_map.read().unwrap().get(&delta_ino).copied()
     }
 
+    /// Get the delta inode holding the copy of a hard-linked base file, if
+    /// one of its links has been copied up
+    fn get_linked_copy(&self, base_stats: &Stats) -> Option<i64> {
+        if !base_stats.is_file() || base_stats.nlink < 2 {
+            return None;
+        }
+        self.linked_copies
+            .read()
+            .unwrap()
+            .get(&base_stats.ino)
+            .copied()
+    }
+
     /// Promote an overlay inode from base layer to delta layer.
     ///
     /// When a directory that was originally looked up from base gets a
@@ -546,6 +673,19 @@ impl OverlayFS {
             parent_ino = stats.ino;
         }
 
+        // Another link to this file was copied up already: link to its copy,
+        // so that all names keep sharing one inode
+        if let Some(delta_ino) = self.get_linked_copy(&base_stats) {
+            match FileSystem::link(&self.delta, delta_ino, parent_ino, name).await {
+                Ok(_) => return Ok(delta_ino),
+                // The copy was removed; copy this link up on its own
+                Err(Error::Fs(FsError::NotFound)) => {
+                    self.linked_copies.write().unwrap().remove(&base_ino);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
         // Copy based on file type
         let delta_ino = if base_stats.is_symlink() {
             let target = self
@@ -589,6 +729,12 @@ impl OverlayFS {
             )
             .await?;
             delta_file.pwrite(0, &content).await?;
+            if base_stats.is_file() && base_stats.nlink > 1 {
+                self.linked_copies
+                    .write()
+                    .unwrap()
+                    .insert(base_ino, stats.ino);
+            }
             stats.ino
         };
 
@@ -598,6 +744,24 @@ impl OverlayFS {
         Ok(delta_ino)
     }
 
+    /// Fail with `FsError::CrossDevice` if the entry `info` with `stats`
+    /// cannot be renamed or linked within the delta layer.
+    ///
+    /// A directory that exists in base would leave its base contents behind
+    /// at the old path, and special files in base cannot be copied up.
+    /// Callers such as `mv` and `cp` fall back to copying on EXDEV.
+    fn check_same_layer(&self, info: &InodeInfo, stats: &Stats) -> Result<()> {
+        let in_base = match info.layer {
+            Layer::Base => true,
+            Layer::Delta => self.get_origin_ino(info.underlying_ino).is_some(),
+        };
+        let copyable = stats.is_file() || stats.is_symlink();
+        if (stats.is_directory() && in_base) || (info.layer == Layer::Base && !copyable) {
+            return Err(FsError::CrossDevice.into());
+        }
+        Ok(())
+    }
+
     /// Copy-up a file and update the inode mapping so subsequent operations
     /// go to the delta layer. Returns the delta inode.
     async fn copy_up_and_update_mapping(&self, overlay_ino: i64, info: &InodeInfo) -> Result<i64> {
@@ -701,6 +865,16 @@ impl FileSystem for OverlayFS {
         };
 
         if let Some(base_stats) = self.base.lookup(base_parent_ino, name).await? {
+            // A hard link to a base file that was copied up under another name
+            if self.get_linked_copy(&base_stats).is_some() {
+                let delta_ino = self.copy_up(&path, base_stats.ino).await?;
+                let mut stats = FileSystem::getattr(&self.delta, delta_ino)
+                    .await?
+                    .ok_or(FsError::NotFound)?;
+                stats.ino = self.get_or_create_overlay_ino(Layer::Delta, delta_ino, &path);
+                return Ok(Some(stats));
+            }
+
             let ino = self.get_or_create_overlay_ino(Layer::Base, base_stats.ino, &path);
             let mut stats = base_stats;
             stats.ino = ino;
@@ -853,11 +1027,19 @@ impl FileSystem for OverlayFS {
                     };
 
                     if !self.is_whiteout(&entry_path) && !child_whiteouts.contains(&entry.name) {
+                        let linked_copy = self.get_linked_copy(&entry.stats);
                         let overlay_ino = self.get_or_create_overlay_ino(
                             Layer::Base,
                             entry.stats.ino,
                             &entry_path,
                         );
+                        // Show the shared copy of a hard link copied up under another name
+                        if let Some(delta_ino) = linked_copy {
+                            if let Some(stats) = FileSystem::getattr(&self.delta, delta_ino).await?
+                            {
+                                entry.stats = stats;
+                            }
+                        }
                         entry.stats.ino = overlay_ino;
                         entries_map.insert(entry.name.clone(), entry);
                     }
@@ -1199,6 +1381,12 @@ impl FileSystem for OverlayFS {
             .ok_or(FsError::NotFound)?;
         let new_path = self.build_path(newparent_ino, newname)?;
 
+        let stats = self.getattr(ino).await?.ok_or(FsError::NotFound)?;
+        if stats.is_directory() {
+            return Err(FsError::IsADirectory.into());
+        }
+        self.check_same_layer(&info, &stats)?;
+
         // Ensure file is in delta (copy up if needed)
         let delta_ino = if info.layer == Layer::Delta {
             info.underlying_ino
@@ -1253,6 +1441,7 @@ impl FileSystem for OverlayFS {
         let src_info = self
             .get_inode_info(src_stats.ino)
             .ok_or(FsError::NotFound)?;
+        self.check_same_layer(&src_info, &src_stats)?;
 
         // If source is in base, copy to delta first
         if src_info.layer == Layer::Base {
@@ -1297,6 +1486,15 @@ impl FileSystem for OverlayFS {
         FileSystem::statfs(&self.delta).await
     }
 
+    async fn health(&self) -> Result<()> {
+        FileSystem::health(&self.delta).await?;
+        self.base.health().await
+    }
+
+    async fn checkpoint(&self) -> Result<()> {
+        FileSystem::checkpoint(&self.delta).await
+    }
+
     async fn forget(&self, ino: i64, nlookup: u64) {
         // Look up the inode info to determine which layer it belongs to
         let info = match self.get_inode_info(ino) {
@@ -1348,6 +1546,49 @@ mod tests {
         Ok((overlay, base_dir, delta_dir))
     }
 
+    #[tokio::test]
+    async fn test_overlay_from_snapshot_restores_delta() -> Result<()> {
+        let (overlay, base_dir, delta_dir) = create_test_overlay().await?;
+
+        // First run: add a file and delete one from the base
+        let (_, file) = overlay
+            .create_file(ROOT_INO, "run1.txt", DEFAULT_FILE_MODE, 0, 0)
+            .await?;
+        file.pwrite(0, b"from run 1").await?;
+        overlay.unlink(ROOT_INO, "base.txt").await?;
+
+        let snapshot_path = delta_dir.path().join("snapshot.db");
+        let snapshot_path = snapshot_path.to_str().unwrap();
+        overlay.snapshot_delta(snapshot_path).await?;
+        assert!(overlay.snapshot_delta(snapshot_path).await.is_err());
+
+        // Changes after the snapshot are not part of it
+        overlay
+            .create_file(ROOT_INO, "late.txt", DEFAULT_FILE_MODE, 0, 0)
+            .await?;
+
+        // Second run starts from the snapshot
+        let base = Arc::new(HostFS::new(base_dir.path())?);
+        let delta_path = delta_dir.path().join("delta2.db");
+        let restored =
+            OverlayFS::from_snapshot(base, snapshot_path, delta_path.to_str().unwrap()).await?;
+        restored.init(base_dir.path().to_str().unwrap()).await?;
+
+        let stats = restored.lookup(ROOT_INO, "run1.txt").await?.unwrap();
+        let file = restored.open(stats.ino, libc::O_RDONLY).await?;
+        assert_eq!(file.pread(0, 64).await?, b"from run 1");
+        assert!(restored.lookup(ROOT_INO, "base.txt").await?.is_none());
+        assert!(restored.lookup(ROOT_INO, "late.txt").await?.is_none());
+        assert!(restored.lookup(ROOT_INO, "subdir").await?.is_some());
+
+        // The branches are independent
+        restored
+            .create_file(ROOT_INO, "run2.txt", DEFAULT_FILE_MODE, 0, 0)
+            .await?;
+        assert!(overlay.lookup(ROOT_INO, "run2.txt").await?.is_none());
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_overlay_lookup_base() -> Result<()> {
         let (overlay, _base_dir, _delta_dir) = create_test_overlay().await?;
@@ -1421,6 +1662,124 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_overlay_writes_never_reach_base() -> Result<()> {
+        let (overlay, base_dir, _delta_dir) = create_test_overlay().await?;
+        let base_path = base_dir.path().join("subdir/nested.txt");
+        let base_mode = std::fs::metadata(&base_path)?.permissions().mode();
+
+        let subdir = overlay.lookup(ROOT_INO, "subdir").await?.unwrap();
+        let nested = overlay.lookup(subdir.ino, "nested.txt").await?.unwrap();
+        let file = overlay.open(nested.ino, libc::O_RDWR).await?;
+        file.pwrite(0, b"changed").await?;
+        file.truncate(3).await?;
+        overlay.chmod(nested.ino, 0o600).await?;
+        overlay
+            .rename(ROOT_INO, "base.txt", subdir.ino, "moved.txt")
+            .await?;
+
+        // The base directory is exactly as it was
+        assert_eq!(std::fs::read(&base_path)?, b"nested");
+        assert_eq!(
+            std::fs::metadata(&base_path)?.permissions().mode(),
+            base_mode
+        );
+        assert_eq!(
+            std::fs::read(base_dir.path().join("base.txt"))?,
+            b"base content"
+        );
+        assert!(!base_dir.path().join("subdir/moved.txt").exists());
+
+        // The changes live in the delta
+        let delta = overlay.delta();
+        let delta_subdir = FileSystem::lookup(delta, ROOT_INO, "subdir")
+            .await?
+            .unwrap();
+        let delta_nested = FileSystem::lookup(delta, delta_subdir.ino, "nested.txt")
+            .await?
+            .unwrap();
+        assert_eq!(delta_nested.mode & 0o777, 0o600);
+        let delta_file = FileSystem::open(delta, delta_nested.ino, libc::O_RDONLY).await?;
+        assert_eq!(delta_file.pread(0, 64).await?, b"cha");
+        assert!(FileSystem::lookup(delta, delta_subdir.ino, "moved.txt")
+            .await?
+            .is_some());
+
+        // The base layer refuses to be opened for writing
+        let base_subdir = overlay.base.lookup(ROOT_INO, "subdir").await?.unwrap();
+        let base_nested = overlay
+            .base
+            .lookup(base_subdir.ino, "nested.txt")
+            .await?
+            .unwrap();
+        for flags in [libc::O_WRONLY, libc::O_RDWR, libc::O_RDONLY | libc::O_TRUNC] {
+            let err = overlay
+                .base
+                .open(base_nested.ino, flags)
+                .await
+                .err()
+                .unwrap();
+            assert!(matches!(err, crate::error::Error::Fs(FsError::ReadOnly)));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_overlay_copy_up_preserves_hard_links() -> Result<()> {
+        let (overlay, base_dir, _delta_dir) = create_test_overlay().await?;
+        std::fs::write(base_dir.path().join("first.txt"), b"shared")?;
+        std::fs::hard_link(
+            base_dir.path().join("first.txt"),
+            base_dir.path().join("subdir/second.txt"),
+        )?;
+
+        let subdir = overlay.lookup(ROOT_INO, "subdir").await?.unwrap();
+        let first = overlay.lookup(ROOT_INO, "first.txt").await?.unwrap();
+        let second = overlay.lookup(subdir.ino, "second.txt").await?.unwrap();
+        assert_eq!(first.ino, second.ino);
+
+        // Modify through one name
+        let file = overlay.open(first.ino, libc::O_RDWR).await?;
+        file.pwrite(0, b"SHARED, modified").await?;
+
+        // The other name sees the change
+        let second = overlay.lookup(subdir.ino, "second.txt").await?.unwrap();
+        assert_eq!(second.ino, first.ino);
+        assert_eq!(second.size, 16);
+        assert_eq!(second.nlink, 2);
+        let file = overlay.open(second.ino, libc::O_RDONLY).await?;
+        assert_eq!(file.pread(0, 64).await?, b"SHARED, modified");
+        let entries = overlay.readdir_plus(subdir.ino).await?.unwrap();
+        let entry = entries.iter().find(|e| e.name == "second.txt").unwrap();
+        assert_eq!(entry.stats.size, 16);
+
+        // Both names share one inode in the delta, so later writes through
+        // either are seen through the other
+        let delta = overlay.delta();
+        let delta_first = FileSystem::lookup(delta, ROOT_INO, "first.txt")
+            .await?
+            .unwrap();
+        let delta_subdir = FileSystem::lookup(delta, ROOT_INO, "subdir")
+            .await?
+            .unwrap();
+        let delta_second = FileSystem::lookup(delta, delta_subdir.ino, "second.txt")
+            .await?
+            .unwrap();
+        assert_eq!(delta_first.ino, delta_second.ino);
+        let file = overlay.open(second.ino, libc::O_RDWR).await?;
+        file.pwrite(0, b"again").await?;
+        let file = overlay.open(first.ino, libc::O_RDONLY).await?;
+        assert_eq!(file.pread(0, 5).await?, b"again");
+
+        // The base is untouched
+        assert_eq!(std::fs::read(base_dir.path().join("first.txt"))?, b"shared");
+        assert_eq!(
+            std::fs::read(base_dir.path().join("subdir/second.txt"))?,
+            b"shared"
+        );
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_overlay_copy_on_write_inode_stability() -> Result<()> {
         let (overlay, _base_dir, _delta_dir) = create_test_overlay().await?;
@@ -2564,6 +2923,68 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_overlay_link_base_special_file_is_cross_device() -> Result<()> {
+        let (overlay, base_dir, _delta_dir) = create_test_overlay().await?;
+        let _socket = std::os::unix::net::UnixListener::bind(base_dir.path().join("sock"))?;
+
+        // A socket in base cannot be copied up to link it in delta
+        let sock_stats = overlay.lookup(ROOT_INO, "sock").await?.unwrap();
+        let err = overlay
+            .link(sock_stats.ino, ROOT_INO, "sock-link")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, crate::error::Error::Fs(FsError::CrossDevice)));
+        assert!(overlay.lookup(ROOT_INO, "sock-link").await?.is_none());
+
+        // Regular files are copied up and linked as before
+        let file_stats = overlay.lookup(ROOT_INO, "base.txt").await?.unwrap();
+        overlay
+            .link(file_stats.ino, ROOT_INO, "base-link.txt")
+            .await?;
+        assert!(overlay.lookup(ROOT_INO, "base-link.txt").await?.is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_overlay_rename_base_dir_is_cross_device() -> Result<()> {
+        let (overlay, _base_dir, _delta_dir) = create_test_overlay().await?;
+
+        // Moving the directory in delta would leave nested.txt behind
+        let err = overlay
+            .rename(ROOT_INO, "subdir", ROOT_INO, "moved")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, crate::error::Error::Fs(FsError::CrossDevice)));
+        let subdir = overlay.lookup(ROOT_INO, "subdir").await?.unwrap();
+        assert!(overlay.lookup(subdir.ino, "nested.txt").await?.is_some());
+        assert!(overlay.lookup(ROOT_INO, "moved").await?.is_none());
+
+        // Still the case once the directory was copied up for a new entry
+        overlay
+            .create_file(subdir.ino, "new.txt", DEFAULT_FILE_MODE, 0, 0)
+            .await?;
+        let err = overlay
+            .rename(ROOT_INO, "subdir", ROOT_INO, "moved")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, crate::error::Error::Fs(FsError::CrossDevice)));
+
+        // Directories that only exist in delta move as a whole
+        let dir = overlay.mkdir(ROOT_INO, "fresh", 0o755, 0, 0).await?;
+        overlay
+            .create_file(dir.ino, "inner.txt", DEFAULT_FILE_MODE, 0, 0)
+            .await?;
+        overlay
+            .rename(ROOT_INO, "fresh", ROOT_INO, "renamed")
+            .await?;
+        let renamed = overlay.lookup(ROOT_INO, "renamed").await?.unwrap();
+        assert!(overlay.lookup(renamed.ino, "inner.txt").await?.is_some());
+
+        Ok(())
+    }
+
     /// Test rename of base file across directories where both parents have
     /// been promoted. Source directory must get a whiteout for the original
     /// file, even though the base-walk must resolve through promoted parents.
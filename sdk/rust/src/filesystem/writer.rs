@@ -0,0 +1,221 @@
+//! Streaming writes into a new file.
+//!
+//! `FileWriter` implements `AsyncWrite`, so large inputs can be copied into
+//! a filesystem with `tokio::io::copy` without holding them in memory. Bytes
+//! are collected into a buffer of bounded size and written out at increasing
+//! offsets whenever it fills; at most one write is in flight at a time.
+
+use crate::error::Error;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::AsyncWrite;
+
+use super::BoxedFile;
+
+/// Bytes collected before they are written to the file.
+pub const DEFAULT_WRITER_BUFFER: usize = 1024 * 1024;
+
+type PendingWrite = Pin<Box<dyn Future<Output = Result<(), Error>> + Send>>;
+
+/// Writes a stream of bytes to a file, a buffer at a time.
+///
+/// Call `flush()` or `shutdown()` when done: bytes still in the buffer when
+/// the writer is dropped are lost.
+pub struct FileWriter {
+    file: BoxedFile,
+    buf: Vec<u8>,
+    capacity: usize,
+    /// Offset of the first byte in `buf`
+    offset: u64,
+    /// Write of the previous buffer, while it runs
+    pending: Option<PendingWrite>,
+}
+
+impl FileWriter {
+    /// Write to `file` from its start, buffering up to `DEFAULT_WRITER_BUFFER`
+    /// bytes.
+    pub fn new(file: BoxedFile) -> Self {
+        Self::with_capacity(file, DEFAULT_WRITER_BUFFER)
+    }
+
+    /// Write to `file` from its start, buffering up to `capacity` bytes.
+    pub fn with_capacity(file: BoxedFile, capacity: usize) -> Self {
+        assert!(capacity > 0, "buffer capacity must be > 0");
+        Self {
+            file,
+            buf: Vec::with_capacity(capacity),
+            capacity,
+            offset: 0,
+            pending: None,
+        }
+    }
+
+    /// Write to the file `wrap` makes of the current one, e.g. to observe
+    /// the writes of a writer created by another layer.
+    ///
+    /// Fails if anything was written already, as those bytes went to the
+    /// current file.
+    pub fn map_file(mut self, wrap: impl FnOnce(BoxedFile) -> BoxedFile) -> Result<Self, Error> {
+        if self.position() != 0 || self.pending.is_some() {
+            return Err(Error::Internal(
+                "cannot replace the file of a writer after writing".to_string(),
+            ));
+        }
+        self.file = wrap(self.file);
+        Ok(self)
+    }
+
+    /// Total bytes accepted so far, written or still buffered.
+    pub fn position(&self) -> u64 {
+        self.offset + self.buf.len() as u64
+    }
+
+    /// Start writing out the buffer.
+    fn start_write(&mut self) {
+        let file = self.file.clone();
+        let offset = self.offset;
+        let data = std::mem::replace(&mut self.buf, Vec::with_capacity(self.capacity));
+        self.offset += data.len() as u64;
+        self.pending = Some(Box::pin(async move { file.pwrite(offset, &data).await }));
+    }
+
+    /// Wait for the write in flight, if any.
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let Some(pending) = self.pending.as_mut() else {
+            return Poll::Ready(Ok(()));
+        };
+        let result = ready!(pending.as_mut().poll(cx));
+        self.pending = None;
+        Poll::Ready(result.map_err(to_io_error))
+    }
+}
+
+impl AsyncWrite for FileWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        data: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+        if this.buf.len() == this.capacity {
+            this.start_write();
+            ready!(this.poll_pending(cx))?;
+        }
+        let n = data.len().min(this.capacity - this.buf.len());
+        this.buf.extend_from_slice(&data[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+        if !this.buf.is_empty() {
+            this.start_write();
+            ready!(this.poll_pending(cx))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+/// Surface an SDK error through the `io::Error` that `AsyncWrite` returns,
+/// keeping its errno where it has one.
+fn to_io_error(err: Error) -> io::Error {
+    match err {
+        Error::Io(err) => err,
+        Error::Fs(err) => io::Error::from_raw_os_error(err.to_errno()),
+        err => io::Error::other(err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::{
+        AgentFS, Fault, FileSystem, FsError, MockFileSystem, DEFAULT_FILE_MODE,
+    };
+    use std::sync::Arc;
+    use tokio::io::AsyncWriteExt;
+
+    const ROOT_INO: i64 = 1;
+
+    #[tokio::test]
+    async fn test_stream_large_input() {
+        let fs = AgentFS::new_in_memory().await.unwrap();
+        let mock = MockFileSystem::new(Arc::new(fs.clone()));
+        let capacity = 64 * 1024;
+        let data: Vec<u8> = (0..5 * 1024 * 1024 + 123)
+            .map(|i| (i % 251) as u8)
+            .collect();
+
+        let (_, file) = mock
+            .create_file(ROOT_INO, "big.bin", DEFAULT_FILE_MODE, 0, 0)
+            .await
+            .unwrap();
+        let mut writer = FileWriter::with_capacity(file, capacity);
+        let copied = tokio::io::copy(&mut &data[..], &mut writer).await.unwrap();
+        writer.shutdown().await.unwrap();
+        assert_eq!(copied, data.len() as u64);
+        assert_eq!(writer.position(), data.len() as u64);
+
+        // The input went out a buffer at a time, not in one piece at the end
+        assert_eq!(mock.count("pwrite"), data.len().div_ceil(capacity));
+        assert_eq!(fs.read_file("/big.bin").await.unwrap().unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn test_map_file_wraps_writes() {
+        let fs = AgentFS::new_in_memory().await.unwrap();
+        let writer = fs
+            .create_writer(ROOT_INO, "out.bin", DEFAULT_FILE_MODE, 0, 0)
+            .await
+            .unwrap();
+        let mock = Arc::new(MockFileSystem::new(Arc::new(fs.clone())));
+        let (_, other) = mock
+            .create_file(ROOT_INO, "other.bin", DEFAULT_FILE_MODE, 0, 0)
+            .await
+            .unwrap();
+
+        // Writes go wherever the wrapper sends them
+        let mut writer = writer.map_file(|_| other).unwrap();
+        writer.write_all(b"redirected").await.unwrap();
+        writer.shutdown().await.unwrap();
+        assert_eq!(mock.count("pwrite"), 1);
+        assert_eq!(
+            fs.read_file("/other.bin").await.unwrap().unwrap(),
+            b"redirected"
+        );
+        assert!(fs.read_file("/out.bin").await.unwrap().unwrap().is_empty());
+
+        // Bytes already accepted belong to the current file
+        let mut writer = fs
+            .create_writer(ROOT_INO, "late.bin", DEFAULT_FILE_MODE, 0, 0)
+            .await
+            .unwrap();
+        writer.write_all(b"early").await.unwrap();
+        assert!(writer.map_file(|file| file).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_write_errors_keep_errno() {
+        let fs = AgentFS::new_in_memory().await.unwrap();
+        let mock = MockFileSystem::new(Arc::new(fs));
+        let (_, file) = mock
+            .create_file(ROOT_INO, "full.bin", DEFAULT_FILE_MODE, 0, 0)
+            .await
+            .unwrap();
+        mock.inject("pwrite", Fault::Error(FsError::NoSpace));
+
+        // The failure shows once the buffer is written out
+        let mut writer = FileWriter::with_capacity(file, 16);
+        writer.write_all(b"buffered").await.unwrap();
+        let err = writer.flush().await.unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(FsError::NoSpace.to_errno()));
+    }
+}
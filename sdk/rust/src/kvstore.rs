@@ -26,6 +26,13 @@ impl KvStore {
         Ok(kv)
     }
 
+    /// Create a KV store from a read-only connection pool
+    ///
+    /// The schema is not initialized, so the database must already have it.
+    pub fn from_pool_readonly(pool: ConnectionPool) -> Self {
+        Self { pool }
+    }
+
     /// Initialize the database schema
     async fn initialize(&self) -> Result<()> {
         let conn = self.pool.get_connection().await?;
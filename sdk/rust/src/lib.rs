@@ -3,6 +3,8 @@ pub mod error;
 pub mod filesystem;
 pub mod kvstore;
 pub mod schema;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod toolcalls;
 
 use error::{Error, Result};
@@ -19,9 +21,9 @@ pub use turso::sync::{DatabaseSyncStats, PartialBootstrapStrategy, PartialSyncOp
 #[cfg(any(target_os = "linux", target_os = "macos"))]
 pub use filesystem::HostFS;
 pub use filesystem::{
-    BoxedFile, DirEntry, File, FileSystem, FilesystemStats, FsError, OverlayFS, Stats, TimeChange,
-    DEFAULT_DIR_MODE, DEFAULT_FILE_MODE, S_IFBLK, S_IFCHR, S_IFDIR, S_IFIFO, S_IFLNK, S_IFMT,
-    S_IFREG, S_IFSOCK,
+    BlockingFile, BlockingFs, BoxedFile, DirEntry, File, FileSystem, FilesystemStats, FsError,
+    OverlayFS, Stats, TimeChange, DEFAULT_DIR_MODE, DEFAULT_FILE_MODE, S_IFBLK, S_IFCHR, S_IFDIR,
+    S_IFIFO, S_IFLNK, S_IFMT, S_IFREG, S_IFSOCK,
 };
 pub use kvstore::KvStore;
 pub use schema::{SchemaVersion, AGENTFS_SCHEMA_VERSION};
@@ -47,30 +49,67 @@ pub struct Mount {
 /// it's mounted. If not, it's not. No stale state possible.
 #[cfg(target_os = "linux")]
 pub fn get_mounts() -> Vec<Mount> {
-    let Ok(contents) = std::fs::read_to_string("/proc/mounts") else {
+    let Ok(contents) = std::fs::read_to_string("/proc/self/mounts")
+        .or_else(|_| std::fs::read_to_string("/proc/mounts"))
+    else {
         return vec![];
     };
+    parse_mounts(&contents)
+}
+
+/// Extract agentfs filesystems from a mount table in `/proc/mounts` format
+///
+/// An entry belongs to agentfs if its source is `agentfs:<id>` or its
+/// filesystem type is `fuse.agentfs`. Mountpoints are unescaped, so paths
+/// containing spaces are returned as-is.
+pub fn parse_mounts(contents: &str) -> Vec<Mount> {
     contents
         .lines()
         .filter_map(|line| {
             let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 2 && parts[0].starts_with("agentfs:") {
-                let agent_id = parts[0].strip_prefix("agentfs:")?.to_string();
-                // Skip the internal "fuse" mount used by the daemon
-                if agent_id == "fuse" {
-                    return None;
-                }
-                Some(Mount {
-                    id: agent_id,
-                    mountpoint: PathBuf::from(parts[1]),
-                })
-            } else {
-                None
+            if parts.len() < 3 {
+                return None;
             }
+            let agent_id = match parts[0].strip_prefix("agentfs:") {
+                Some(id) => id.to_string(),
+                None if parts[2] == "fuse.agentfs" => parts[0].to_string(),
+                None => return None,
+            };
+            // Skip the internal "fuse" mount used by the daemon
+            if agent_id == "fuse" {
+                return None;
+            }
+            Some(Mount {
+                id: agent_id,
+                mountpoint: PathBuf::from(unescape_mount_field(parts[1])),
+            })
         })
         .collect()
 }
 
+/// Undo the octal escaping (`\040` for space etc.) applied to /proc/mounts fields
+pub fn unescape_mount_field(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes
+            .get(i + 1..i + 4)
+            .filter(|digits| digits.iter().all(|b| (b'0'..=b'7').contains(b)));
+        if let (b'\\', Some(digits)) = (bytes[i], octal) {
+            let code = digits
+                .iter()
+                .fold(0u32, |acc, b| acc * 8 + u32::from(b - b'0'));
+            out.push(code as u8);
+            i += 4;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
 /// Get all currently mounted agentfs filesystems (non-Linux stub)
 #[cfg(not(target_os = "linux"))]
 pub fn get_mounts() -> Vec<Mount> {
@@ -88,6 +127,14 @@ pub struct SyncOptions {
     pub auth_token: Option<String>,
     /// Partial sync options
     pub partial_sync: Option<PartialSyncOpts>,
+    /// Retry policy for connecting to the synced database
+    pub retry: connection_pool::RetryPolicy,
+    /// Serve read-only connections from the local replica while the remote
+    /// is unreachable, instead of failing
+    pub offline_reads: bool,
+    /// When to pull remote changes into the local replica, besides explicit
+    /// calls to `AgentFS::pull()`
+    pub refresh: connection_pool::SyncRefresh,
 }
 
 /// Configuration options for local encryption
@@ -116,6 +163,25 @@ pub struct AgentFSOptions {
     pub sync: SyncOptions,
     /// Encryption configuration for database at rest
     pub encryption: Option<EncryptionConfig>,
+    /// SQLite page cache size, applied to every pooled connection.
+    ///
+    /// Uses `PRAGMA cache_size` semantics: a positive value is a number of
+    /// pages, a negative value is a size in KiB. The cache is allocated per
+    /// connection and grows up to this limit as pages are read, so memory use
+    /// can reach this size for every open connection. When unset, SQLite's
+    /// default (about 2 MiB) is used.
+    pub cache_size: Option<i64>,
+    /// Store a checksum with every file data chunk and verify it on read.
+    ///
+    /// A corrupted chunk then fails the read with `FsError::ChecksumMismatch`
+    /// instead of returning bad data. Off by default, as it costs CPU on every
+    /// read and write.
+    pub checksums: bool,
+    /// Largest size, in bytes, that any single file may grow to.
+    ///
+    /// Writes and truncates that would extend a file past it fail with
+    /// `FsError::FileTooLarge` (EFBIG). Unlimited when unset.
+    pub max_file_size: Option<u64>,
 }
 
 impl AgentFSOptions {
@@ -157,6 +223,9 @@ impl AgentFSOptions {
             base: None,
             sync: SyncOptions::default(),
             encryption: None,
+            cache_size: None,
+            checksums: false,
+            max_file_size: None,
         }
     }
 
@@ -168,6 +237,9 @@ impl AgentFSOptions {
             base: None,
             sync: SyncOptions::default(),
             encryption: None,
+            cache_size: None,
+            checksums: false,
+            max_file_size: None,
         }
     }
 
@@ -179,6 +251,9 @@ impl AgentFSOptions {
             base: None,
             sync: SyncOptions::default(),
             encryption: None,
+            cache_size: None,
+            checksums: false,
+            max_file_size: None,
         }
     }
 
@@ -213,6 +288,35 @@ impl AgentFSOptions {
         self
     }
 
+    /// Set the SQLite page cache size (`PRAGMA cache_size` semantics)
+    ///
+    /// Positive values are pages, negative values are KiB, e.g. `-65536` for a
+    /// 64 MiB cache per connection.
+    pub fn with_cache_size(mut self, cache_size: i64) -> Self {
+        self.cache_size = Some(cache_size);
+        self
+    }
+
+    /// Enable per-chunk checksums, verified on every read
+    pub fn with_checksums(mut self, enabled: bool) -> Self {
+        self.checksums = enabled;
+        self
+    }
+
+    /// Limit the size of every file to `max_file_size` bytes
+    pub fn with_max_file_size(mut self, max_file_size: u64) -> Self {
+        self.max_file_size = Some(max_file_size);
+        self
+    }
+
+    /// PRAGMA statements to run on every pooled connection
+    fn connection_pragmas(&self) -> Vec<String> {
+        self.cache_size
+            .map(|size| format!("PRAGMA cache_size = {}", size))
+            .into_iter()
+            .collect()
+    }
+
     /// Resolve an id-or-path string to AgentFSOptions
     ///
     /// Resolution order (first match wins):
@@ -308,6 +412,7 @@ impl AgentFS {
 
         let db_path = options.db_path()?;
         let meta_path = format!("{db_path}-info");
+        let pragmas = options.connection_pragmas();
 
         // Determine if this is a synced database:
         // 1. If sync.remote_url is set, create a new synced database
@@ -324,7 +429,13 @@ impl AgentFS {
                 builder = builder.with_partial_sync_opts_experimental(partial_sync);
             }
             let db = builder.build().await?;
-            let pool = connection_pool::ConnectionPool::new_sync(db.clone());
+            let pool = connection_pool::ConnectionPool::new_sync_with_retry(
+                db.clone(),
+                pragmas.clone(),
+                options.sync.retry,
+                options.sync.offline_reads.then(|| db_path.clone()),
+            )
+            .with_sync_refresh(options.sync.refresh);
             (Some(db), pool)
         } else if std::fs::exists(&meta_path).unwrap_or(false) {
             let mut builder = turso::sync::Builder::new_remote(&db_path);
@@ -332,7 +443,13 @@ impl AgentFS {
                 builder = builder.with_auth_token(auth_token);
             }
             let db = builder.build().await?;
-            let pool = connection_pool::ConnectionPool::new_sync(db.clone());
+            let pool = connection_pool::ConnectionPool::new_sync_with_retry(
+                db.clone(),
+                pragmas.clone(),
+                options.sync.retry,
+                options.sync.offline_reads.then(|| db_path.clone()),
+            )
+            .with_sync_refresh(options.sync.refresh);
             (Some(db), pool)
         } else {
             let db = if let Some(ref enc_config) = options.encryption {
@@ -347,7 +464,7 @@ impl AgentFS {
             } else {
                 Builder::new_local(&db_path).build().await?
             };
-            let pool = connection_pool::ConnectionPool::new(db);
+            let pool = connection_pool::ConnectionPool::new_with_pragmas(db, pragmas);
             (None, pool)
         };
 
@@ -364,7 +481,12 @@ impl AgentFS {
             OverlayFS::init_schema(&conn, &base_path_str).await?;
         }
 
-        Self::open_with_pool(pool, sync_db).await
+        let mut agentfs = Self::open_with_pool(pool, sync_db).await?;
+        agentfs.fs = agentfs
+            .fs
+            .with_checksums(options.checksums)
+            .with_max_file_size(options.max_file_size);
+        Ok(agentfs)
     }
 
     /// Open an AgentFS instance from a connection pool
@@ -385,6 +507,75 @@ impl AgentFS {
         })
     }
 
+    /// Open an existing local database read-only
+    ///
+    /// Nothing is written when opening, and every pooled connection runs with
+    /// `PRAGMA query_only`, so readers only ever take shared locks. This lets
+    /// several instances, in one process or many, read the same database at
+    /// once. No writer may be active on the database meanwhile: readers see a
+    /// consistent snapshot per query, but a concurrent writer can block them
+    /// or be blocked by them.
+    ///
+    /// Synced databases and overlay initialization (`options.base`) are not
+    /// supported, since both write on open.
+    pub async fn open_readonly(options: AgentFSOptions) -> Result<Self> {
+        if options.sync.remote_url.is_some() {
+            return Err(Error::ReadOnlyNotSupported(
+                "cannot create a synced database read-only".to_string(),
+            ));
+        }
+        if options.base.is_some() {
+            return Err(Error::ReadOnlyNotSupported(
+                "cannot initialize an overlay read-only".to_string(),
+            ));
+        }
+
+        let db_path = options.db_path()?;
+        if std::fs::exists(format!("{db_path}-info")).unwrap_or(false) {
+            return Err(Error::ReadOnlyNotSupported(
+                "synced databases must be opened read-write".to_string(),
+            ));
+        }
+        if !Path::new(&db_path).is_file() {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("database not found: {db_path}"),
+            )));
+        }
+
+        let mut pragmas = options.connection_pragmas();
+        pragmas.push("PRAGMA query_only = 1".to_string());
+
+        let db = if let Some(ref enc_config) = options.encryption {
+            Builder::new_local(&db_path)
+                .experimental_encryption(true)
+                .with_encryption(EncryptionOpts {
+                    cipher: enc_config.cipher.clone(),
+                    hexkey: enc_config.hex_key.clone(),
+                })
+                .build()
+                .await?
+        } else {
+            Builder::new_local(&db_path).build().await?
+        };
+        let pool = connection_pool::ConnectionPool::new_with_pragmas(db, pragmas);
+
+        let conn = pool.get_connection().await?;
+        schema::check_schema_version(&conn).await?;
+        drop(conn);
+
+        Ok(Self {
+            kv: KvStore::from_pool_readonly(pool.clone()),
+            fs: filesystem::AgentFS::from_pool_readonly(pool.clone())
+                .await?
+                .with_checksums(options.checksums)
+                .with_max_file_size(options.max_file_size),
+            tools: ToolCalls::from_pool_readonly(pool.clone()),
+            pool,
+            sync_db: None,
+        })
+    }
+
     /// Open an AgentFS instance from a sync database
     pub async fn open_with_sync_db(db: turso::sync::Database) -> Result<Self> {
         let pool = connection_pool::ConnectionPool::new_sync(db.clone());
@@ -426,10 +617,14 @@ impl AgentFS {
     }
 
     /// Pull changes from remote database
+    ///
+    /// Cached directory entries and file contents are dropped afterwards, so
+    /// reads see the pulled changes.
     pub async fn pull(&self) -> Result<()> {
-        let db = self.sync_db.as_ref().ok_or(Error::SyncNotEnabled)?;
-        db.pull().await?;
-        Ok(())
+        if self.sync_db.is_none() {
+            return Err(Error::SyncNotEnabled);
+        }
+        self.pool.sync_now().await
     }
 
     /// Push local changes to remote database
@@ -649,6 +844,23 @@ impl AgentFS {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_mounts_finds_agentfs_entries() {
+        let contents = "\
+proc /proc proc rw,nosuid,nodev,noexec,relatime 0 0
+agentfs:my-agent /tmp/my\\040mount fuse.agentfs rw,nosuid,nodev 0 0
+agentfs:fuse /run/agentfs fuse.agentfs rw 0 0
+custom-name /mnt/custom fuse.agentfs rw 0 0
+sshfs#host: /mnt/ssh fuse.sshfs rw 0 0
+";
+        let mounts = parse_mounts(contents);
+        assert_eq!(mounts.len(), 2);
+        assert_eq!(mounts[0].id, "my-agent");
+        assert_eq!(mounts[0].mountpoint, PathBuf::from("/tmp/my mount"));
+        assert_eq!(mounts[1].id, "custom-name");
+        assert_eq!(mounts[1].mountpoint, PathBuf::from("/mnt/custom"));
+    }
+
     #[tokio::test]
     async fn test_agentfs_creation() {
         let agentfs = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
@@ -656,6 +868,82 @@ mod tests {
         let _conn = agentfs.get_connection().await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_cache_size_applied_to_connections() {
+        let agentfs = AgentFS::open(AgentFSOptions::ephemeral().with_cache_size(-8192))
+            .await
+            .unwrap();
+
+        let conn = agentfs.get_connection().await.unwrap();
+        let mut rows = conn.query("PRAGMA cache_size", ()).await.unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        let cache_size = row.get_value(0).unwrap().as_integer().copied();
+        assert_eq!(cache_size, Some(-8192));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_open_readonly_allows_concurrent_readers() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("shared.db");
+        let db_path = db_path.to_str().unwrap();
+
+        {
+            let writer = AgentFS::open(AgentFSOptions::with_path(db_path))
+                .await
+                .unwrap();
+            let (_, file) = writer
+                .fs
+                .create_file("/shared.txt", DEFAULT_FILE_MODE, 0, 0)
+                .await
+                .unwrap();
+            file.pwrite(0, b"shared contents").await.unwrap();
+            file.fsync().await.unwrap();
+            writer.kv.set("key", &"value").await.unwrap();
+        }
+
+        let first = AgentFS::open_readonly(AgentFSOptions::with_path(db_path))
+            .await
+            .unwrap();
+        let second = AgentFS::open_readonly(AgentFSOptions::with_path(db_path))
+            .await
+            .unwrap();
+
+        let read = |agentfs: &AgentFS| {
+            let fs = agentfs.fs.clone();
+            tokio::spawn(async move {
+                for _ in 0..50 {
+                    let data = fs.read_file("/shared.txt").await.unwrap().unwrap();
+                    assert_eq!(data, b"shared contents");
+                }
+            })
+        };
+        let (a, b) = tokio::join!(read(&first), read(&second));
+        a.unwrap();
+        b.unwrap();
+
+        let value: Option<String> = second.kv.get("key").await.unwrap();
+        assert_eq!(value.as_deref(), Some("value"));
+
+        // Read-only instances reject writes
+        assert!(first
+            .fs
+            .create_file("/new.txt", DEFAULT_FILE_MODE, 0, 0)
+            .await
+            .is_err());
+        assert!(first.fs.stat("/new.txt").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_open_readonly_missing_database_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("missing.db");
+
+        let result =
+            AgentFS::open_readonly(AgentFSOptions::with_path(db_path.to_str().unwrap())).await;
+        assert!(matches!(result, Err(Error::Io(_))));
+        assert!(!db_path.exists());
+    }
+
     #[tokio::test]
     async fn test_agentfs_with_id() {
         let agentfs = AgentFS::open(AgentFSOptions::with_id("test-agent"))
@@ -45,8 +45,8 @@ impl SchemaVersion {
 
 /// Column information from PRAGMA table_info.
 #[derive(Debug)]
-struct ColumnInfo {
-    name: String,
+pub(crate) struct ColumnInfo {
+    pub(crate) name: String,
 }
 
 /// Detect the schema version of an existing database by introspecting fs_inode columns.
@@ -104,7 +104,10 @@ pub async fn check_schema_version(conn: &Connection) -> Result<()> {
 }
 
 /// Get column information for a table using PRAGMA table_info.
-async fn get_table_columns(conn: &Connection, table_name: &str) -> Result<Vec<ColumnInfo>> {
+pub(crate) async fn get_table_columns(
+    conn: &Connection,
+    table_name: &str,
+) -> Result<Vec<ColumnInfo>> {
     let mut rows = conn
         .query(&format!("PRAGMA table_info({})", table_name), ())
         .await?;
@@ -0,0 +1,48 @@
+//! Helpers for tests of how AgentFS behaves when the host filesystem fails.
+//!
+//! Enabled by the `testing` feature, so crates built on the SDK can share
+//! them in their own tests.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A tmpfs too small for more than a few hundred KiB, mounted over a
+/// directory and lazily unmounted when dropped.
+///
+/// Mounting needs root, so tests using it are `#[ignore]`d and run with
+/// `cargo test -- --ignored` where root is available.
+pub struct TinyTmpfs {
+    path: PathBuf,
+}
+
+impl TinyTmpfs {
+    /// Size of the tmpfs
+    pub const SIZE: &'static str = "512k";
+
+    /// Mount a tiny tmpfs at `dir`.
+    pub fn mount(dir: &Path) -> io::Result<Self> {
+        let output = Command::new("mount")
+            .args(["-t", "tmpfs", "-o"])
+            .arg(format!("size={}", Self::SIZE))
+            .arg("tmpfs")
+            .arg(dir)
+            .output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "mounting a tmpfs at {} failed (root is required): {}",
+                dir.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(Self {
+            path: dir.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for TinyTmpfs {
+    fn drop(&mut self) {
+        let _ = Command::new("umount").arg("-l").arg(&self.path).output();
+    }
+}
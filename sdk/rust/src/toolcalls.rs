@@ -88,6 +88,13 @@ impl ToolCalls {
         Ok(tc)
     }
 
+    /// Create a tool calls tracker from a read-only connection pool
+    ///
+    /// The schema is not initialized, so the database must already have it.
+    pub fn from_pool_readonly(pool: ConnectionPool) -> Self {
+        Self { pool }
+    }
+
     /// Initialize the database schema
     async fn initialize(&self) -> Result<()> {
         let conn = self.pool.get_connection().await?;